@@ -130,6 +130,20 @@ pub(crate) struct CargoOptions {
     unstable_flags: Vec<String>,
 }
 
+impl CargoOptions {
+    /// Returns true if any options that select build targets were specified.
+    fn has_target_selection(&self) -> bool {
+        self.lib
+            || !self.bin.is_empty()
+            || self.bins
+            || !self.test.is_empty()
+            || self.tests
+            || !self.bench.is_empty()
+            || self.benches
+            || self.all_targets
+    }
+}
+
 #[derive(Clone, Debug)]
 pub(crate) struct CargoCli<'a> {
     cargo_path: Utf8PathBuf,
@@ -166,6 +180,17 @@ impl<'a> CargoCli<'a> {
         self
     }
 
+    /// Adds target selection options that build bench targets along with tests, unless target
+    /// selection options were already specified.
+    pub(crate) fn add_bench_targets(&mut self, options: &CargoOptions) -> &mut Self {
+        if !options.has_target_selection() {
+            // --lib can't be used here since it errors out for packages without a library, but
+            // --tests includes library and binary unit tests.
+            self.args.extend(["--tests", "--benches"]);
+        }
+        self
+    }
+
     pub(crate) fn add_options(&mut self, options: &'a CargoOptions) -> &mut Self {
         if options.lib {
             self.args.push("--lib");
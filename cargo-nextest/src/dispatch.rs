@@ -8,24 +8,50 @@ use crate::{
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use clap::{ArgEnum, Args, Parser, Subcommand};
-use color_eyre::eyre::{Report, Result, WrapErr};
+use color_eyre::eyre::{eyre, Report, Result, WrapErr};
 use guppy::graph::PackageGraph;
+use nextest_metadata::{RunSummary, RustTestBinaryKind, RustTestHarness};
 use nextest_runner::{
-    config::NextestConfig,
-    errors::{TargetRunnerError, WriteEventError},
+    affected::AffectedPackages,
+    archive::{archive_to_file, ExtractedArchive},
+    config::{
+        ConfigDiagnostic, NextestConfig, NextestProfile, NextestVersionEval, NoTestsBehavior,
+        TestThreads, ToolConfigFile,
+    },
+    coverage::{CoverageCollector, LLVM_PROFILE_FILE_ENV},
+    double_spawn::DoubleSpawnInfo,
+    errors::TargetRunnerError,
+    external::ExternalBinaryList,
+    filter_expr::FilterExpr,
+    last_run::LastRun,
+    list_cache::ListCache,
+    metadata_cache::MetadataCache,
     partition::PartitionerBuilder,
-    reporter::{StatusLevel, TestOutputDisplay, TestReporterBuilder},
-    runner::TestRunnerBuilder,
+    record::{RecordedRun, RunRecorder},
+    remote::{SshRunner, SshRunnerBuilder},
+    reporter::{
+        CancelReason, JsonReporter, LibtestReporter, Reporter, StatusLevel, TapReporter,
+        TestOutputDisplay, TestReporterBuilder,
+    },
+    run_diff::RunDiffBuilder,
+    run_merge::RunMerge,
+    runner::{check_network_isolation, RunStats, TestRunnerBuilder},
     signal::SignalHandler,
+    store::{RunStore, StoreLock},
     target_runner::TargetRunner,
     test_filter::{RunIgnored, TestFilterBuilder},
-    test_list::{OutputFormat, RustTestArtifact, SerializableFormat, TestList},
+    test_list::{BinaryList, OutputFormat, RustTestArtifact, SerializableFormat, TestList},
+    timings::TestTimings,
 };
 use owo_colors::{OwoColorize, Style};
+use semver::Version;
 use std::{
     error::Error,
+    ffi::OsString,
     fmt::Write as _,
     io::{BufWriter, Cursor, Write},
+    num::NonZeroUsize,
+    time::{Duration, SystemTime},
 };
 use supports_color::Stream;
 
@@ -46,6 +72,18 @@ impl CargoNextestApp {
         let NextestSubcommand::Nextest(app) = self.subcommand;
         app.exec()
     }
+
+    /// Executes the app with a package graph that has already been constructed, rather than
+    /// running `cargo metadata`.
+    ///
+    /// `cargo_metadata` is the JSON the graph was constructed from, which is stored in archives and
+    /// recordings of runs. The graph is ignored by commands that read it from an archive or a
+    /// recording instead.
+    pub fn exec_with_graph(self, cargo_metadata: String, graph: PackageGraph) -> Result<()> {
+        let NextestSubcommand::Nextest(mut app) = self.subcommand;
+        app.metadata_opts.preloaded = Some((cargo_metadata, graph));
+        app.exec()
+    }
 }
 
 #[derive(Debug, Subcommand)]
@@ -57,9 +95,8 @@ enum NextestSubcommand {
 #[derive(Debug, Args)]
 #[clap(version)]
 struct AppImpl {
-    /// Path to Cargo.toml
-    #[clap(long, global = true, value_name = "PATH")]
-    manifest_path: Option<Utf8PathBuf>,
+    #[clap(flatten)]
+    metadata_opts: MetadataOpts,
 
     #[clap(flatten)]
     output: OutputOpts,
@@ -71,18 +108,127 @@ struct AppImpl {
     command: Command,
 }
 
+/// Options for finding the workspace and constructing its package graph.
+#[derive(Debug, Default, Args)]
+struct MetadataOpts {
+    /// Path to Cargo.toml
+    #[clap(long, global = true, value_name = "PATH")]
+    manifest_path: Option<Utf8PathBuf>,
+
+    /// Run `cargo metadata` even if its output from a previous invocation is still valid
+    #[clap(long, global = true)]
+    no_metadata_cache: bool,
+
+    /// A package graph passed in by the caller, along with the JSON it was constructed from.
+    #[clap(skip)]
+    preloaded: Option<(String, PackageGraph)>,
+}
+
+impl MetadataOpts {
+    /// Returns the `cargo metadata` JSON for the workspace, along with the package graph
+    /// constructed from it.
+    fn load(&self, output: OutputContext) -> Result<(String, PackageGraph)> {
+        if let Some((json, graph)) = &self.preloaded {
+            return Ok((json.clone(), graph.clone()));
+        }
+
+        let cache = if self.no_metadata_cache {
+            None
+        } else {
+            MetadataCache::new(self.manifest_path.as_deref())
+        };
+        if let Some(cache) = &cache {
+            match cache.read() {
+                Ok(Some(json)) => match graph_from_json(&json) {
+                    Ok(graph) => return Ok((json, graph)),
+                    Err(err) => log::warn!("{} (running cargo metadata)", err),
+                },
+                Ok(None) => {}
+                Err(err) => log::warn!("{} (running cargo metadata)", err),
+            }
+        }
+
+        let json = cargo_metadata_json(self.manifest_path.as_deref(), output)?;
+        let graph = graph_from_json(&json)?;
+        if let Some(cache) = &cache {
+            if let Err(err) = cache.write(&json) {
+                log::warn!("{}", err);
+            }
+        }
+        Ok((json, graph))
+    }
+}
+
 #[derive(Debug, Args)]
 struct ConfigOpts {
     /// Config file [default: workspace-root/.config/nextest.toml]
     #[clap(long, global = true, value_name = "PATH")]
     pub config_file: Option<Utf8PathBuf>,
+
+    /// Tool-specific config files, layered under the workspace config
+    ///
+    /// Meant for tools built on top of nextest. Can be passed in multiple times, with later files
+    /// taking precedence over earlier ones.
+    #[clap(long = "tool-config-file", global = true, value_name = "TOOL:PATH")]
+    pub tool_config_files: Vec<ToolConfigFile>,
 }
 
 impl ConfigOpts {
     /// Creates a nextest config with the given options.
+    ///
+    /// Problems found in the config that don't stop it from being read, such as misspelled keys,
+    /// are logged as warnings.
     pub fn make_config(&self, workspace_root: &Utf8Path) -> Result<NextestConfig, ExpectedError> {
-        NextestConfig::from_sources(workspace_root, self.config_file.as_deref())
-            .map_err(ExpectedError::config_parse_error)
+        let config = self.read_config(workspace_root)?;
+        for diagnostic in self.check_config(workspace_root) {
+            log::warn!("{}", diagnostic);
+        }
+        Ok(config)
+    }
+
+    /// Reads the config, and checks that it can be used with this version of nextest.
+    ///
+    /// The versions are checked before the rest of the config is read, so that a config meant for a
+    /// newer version of nextest is reported as such rather than as a parse error.
+    fn read_config(&self, workspace_root: &Utf8Path) -> Result<NextestConfig, ExpectedError> {
+        let nextest_version = NextestConfig::read_nextest_version(
+            workspace_root,
+            self.config_file.as_deref(),
+            &self.tool_config_files,
+        )
+        .map_err(ExpectedError::config_parse_error)?;
+
+        let current =
+            Version::parse(env!("CARGO_PKG_VERSION")).expect("package version is valid semver");
+        match nextest_version.eval(&current) {
+            NextestVersionEval::Satisfied => {}
+            NextestVersionEval::Error { required, current } => {
+                return Err(ExpectedError::required_version_not_met(required, current));
+            }
+            NextestVersionEval::Warn {
+                recommended,
+                current,
+            } => log::warn!(
+                "this repository recommends nextest version {}, but the current version is {}",
+                recommended,
+                current
+            ),
+        }
+
+        NextestConfig::from_sources(
+            workspace_root,
+            self.config_file.as_deref(),
+            &self.tool_config_files,
+        )
+        .map_err(ExpectedError::config_parse_error)
+    }
+
+    fn check_config(&self, workspace_root: &Utf8Path) -> Vec<ConfigDiagnostic> {
+        NextestConfig::check_sources(
+            workspace_root,
+            self.config_file.as_deref(),
+            &self.tool_config_files,
+        )
     }
 }
 
@@ -95,6 +241,10 @@ enum Command {
     ///
     /// For more information, see <https://nexte.st/book/listing>.
     List {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
         #[clap(flatten)]
         build_filter: TestBuildFilter,
 
@@ -108,6 +258,16 @@ enum Command {
             value_name = "FMT"
         )]
         message_format: MessageFormatOpts,
+
+        /// Type of listing
+        #[clap(
+            long,
+            arg_enum,
+            default_value_t,
+            help_heading = "OUTPUT OPTIONS",
+            value_name = "TYPE"
+        )]
+        list_type: ListTypeOpts,
     },
     /// Build and run tests
     ///
@@ -117,29 +277,187 @@ enum Command {
     /// For more information, see <https://nexte.st/book/running>.
     Run {
         /// Nextest profile to use
-        #[clap(long, short = 'P')]
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
         profile: Option<String>,
 
         /// Run tests serially and do not capture output
         #[clap(
             long,
             alias = "nocapture",
+            // Declared here rather than on the reporter options, since those are shared with
+            // `cargo nextest replay`.
+            conflicts_with_all = &["failure-output", "success-output"],
             help_heading = "RUNNER OPTIONS",
             display_order = 100
         )]
         no_capture: bool,
 
+        /// Start each test process stopped, and wait for Enter before running the test
+        ///
+        /// The ID of each stopped process is printed out, so that a debugger can be attached to it
+        /// before the test starts. Implies --no-capture. Only supported on Unix.
+        #[clap(
+            long,
+            conflicts_with_all = &["failure-output", "success-output", "tui"],
+            help_heading = "RUNNER OPTIONS",
+            display_order = 101
+        )]
+        wait_for_debugger: bool,
+
         #[clap(flatten)]
         build_filter: TestBuildFilter,
 
+        /// Only run tests that failed in the last run with this profile
+        ///
+        /// Tests that fail stay on this list until they pass, even if they weren't run in between.
+        #[clap(long, help_heading = "FILTER OPTIONS")]
+        rerun_failed: bool,
+
         #[clap(flatten)]
         runner_opts: TestRunnerOpts,
 
         #[clap(flatten)]
         reporter_opts: TestReporterOpts,
+
+        /// Record test events to this file, for `cargo nextest replay`
+        #[clap(long, value_name = "PATH", help_heading = "REPORTER OPTIONS")]
+        record: Option<Utf8PathBuf>,
+
+        /// What to do if another nextest process is using the profile's store directory
+        #[clap(
+            long,
+            arg_enum,
+            default_value_t,
+            value_name = "ACTION",
+            env = "NEXTEST_STORE_LOCK",
+            help_heading = "RUNNER OPTIONS"
+        )]
+        store_lock: StoreLockOpt,
+    },
+    /// Replay a recorded test run
+    ///
+    /// This command reads a run recorded with `cargo nextest run --record`, and reports it again
+    /// without building or running any tests. Reports configured in the profile, such as JUnit
+    /// XML, are written out as well.
+    ///
+    /// For more information, see <https://nexte.st/book/record-replay>.
+    Replay {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        /// The recorded run
+        #[clap(value_name = "PATH")]
+        recording: Utf8PathBuf,
+
+        #[clap(flatten)]
+        reporter_opts: TestReporterOpts,
+    },
+    /// Compare two recorded test runs
+    ///
+    /// This command compares two runs recorded with `cargo nextest run --record`, and lists the
+    /// tests that started failing, started passing or became flaky in the new run, along with
+    /// tests that got noticeably slower. Exits with a non-zero status if any tests failed in the
+    /// new run that didn't fail in the old one.
+    ///
+    /// For more information, see <https://nexte.st/book/record-replay>.
+    Diff {
+        /// The recorded run to compare against
+        #[clap(value_name = "OLD")]
+        old: Utf8PathBuf,
+
+        /// The recorded run to compare
+        #[clap(value_name = "NEW")]
+        new: Utf8PathBuf,
+
+        /// Report tests that passed in both runs, and became at least this many times slower
+        #[clap(long, value_name = "RATIO", default_value_t = RunDiffBuilder::DEFAULT_MIN_SLOWDOWN_RATIO)]
+        min_slowdown_ratio: f64,
+
+        /// Don't report tests that became slower by less than this amount
+        #[clap(long, value_name = "DURATION", default_value = "100ms", parse(try_from_str = humantime::parse_duration))]
+        min_slowdown: Duration,
+
+        /// Output format
+        #[clap(
+            short = 'T',
+            long,
+            arg_enum,
+            default_value_t,
+            help_heading = "OUTPUT OPTIONS",
+            value_name = "FMT"
+        )]
+        message_format: MessageFormatOpts,
+    },
+    /// Combine the run summaries of partitioned test runs
+    ///
+    /// This command reads the run-summary.json written by each shard of a run split up with
+    /// `--partition`, and combines them into a single summary. Exits with a non-zero status if any
+    /// tests failed, or if any tests weren't run by exactly one shard -- for example, because a
+    /// shard's summary is missing.
+    ///
+    /// For more information, see <https://nexte.st/book/partitioning>.
+    Merge {
+        /// The run summaries to combine, one for each shard
+        #[clap(value_name = "SUMMARY", required = true)]
+        summaries: Vec<Utf8PathBuf>,
+
+        /// Write the combined summary to this file, rather than to standard output
+        #[clap(long, short = 'o', value_name = "PATH")]
+        output: Option<Utf8PathBuf>,
+    },
+    /// Build and archive tests
+    ///
+    /// This command builds test binaries and archives them to a file, along with the metadata
+    /// required to run them. The archive can then be run on another machine with `cargo nextest
+    /// run --archive-file`, without requiring Cargo to be present.
+    ///
+    /// For more information, see <https://nexte.st/book/reusing-builds>.
+    Archive {
+        #[clap(flatten)]
+        cargo_options: CargoOptions,
+
+        /// File to write the archive to, typically ending in .tar.zst
+        #[clap(long, value_name = "PATH")]
+        archive_file: Utf8PathBuf,
+    },
+    /// Manage the store directory
+    ///
+    /// Nextest keeps state carried over between runs, along with artifacts of earlier runs such as
+    /// core dumps and retained temporary directories, in a store directory for each profile.
+    ///
+    /// For more information, see <https://nexte.st/book/configuration#the-store-directory>.
+    Store {
+        /// Nextest profile to use
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
+
+        #[clap(subcommand)]
+        command: StoreCommand,
+    },
+    /// Check the config, and show the settings of a profile
+    ///
+    /// Reports unknown keys, invalid filter expressions and overrides that never take effect, then
+    /// prints the settings of the profile as TOML, resolved from every config source.
+    ///
+    /// For more information, see <https://nexte.st/book/configuration#checking-the-config>.
+    ShowConfig {
+        /// Nextest profile to show
+        #[clap(long, short = 'P', env = "NEXTEST_PROFILE")]
+        profile: Option<String>,
     },
 }
 
+#[derive(Debug, Subcommand)]
+enum StoreCommand {
+    /// List earlier runs that have artifacts in the store, most recent first
+    List,
+    /// Remove artifacts of earlier runs according to the profile's retention policy
+    ///
+    /// This is done automatically by `cargo nextest run`.
+    Gc,
+}
+
 #[derive(Copy, Clone, Debug, ArgEnum)]
 enum MessageFormatOpts {
     Human,
@@ -163,60 +481,447 @@ impl Default for MessageFormatOpts {
     }
 }
 
+#[derive(Copy, Clone, Debug, ArgEnum)]
+enum StoreLockOpt {
+    /// Wait for the other process to finish.
+    Wait,
+    /// Exit with an error.
+    Fail,
+}
+
+impl Default for StoreLockOpt {
+    fn default() -> Self {
+        Self::Wait
+    }
+}
+
+#[derive(Copy, Clone, Debug, ArgEnum)]
+enum ListTypeOpts {
+    /// List test binaries and the tests within them.
+    Full,
+    /// Only list test binaries, without querying them for their tests.
+    BinariesOnly,
+}
+
+/// The kind of build target a test binary was built from.
+#[derive(Copy, Clone, Debug, ArgEnum)]
+enum BinaryKindOpt {
+    /// Unit tests for libraries
+    Lib,
+    /// Integration tests
+    Test,
+    /// Benchmarks
+    Bench,
+    /// Unit tests for binaries
+    Bin,
+    /// Examples
+    Example,
+    /// Unit tests for procedural macros
+    ProcMacro,
+}
+
+impl BinaryKindOpt {
+    fn to_kind(self) -> RustTestBinaryKind {
+        match self {
+            Self::Lib => RustTestBinaryKind::Lib,
+            Self::Test => RustTestBinaryKind::Test,
+            Self::Bench => RustTestBinaryKind::Bench,
+            Self::Bin => RustTestBinaryKind::Bin,
+            Self::Example => RustTestBinaryKind::Example,
+            Self::ProcMacro => RustTestBinaryKind::ProcMacro,
+        }
+    }
+}
+
+impl Default for ListTypeOpts {
+    fn default() -> Self {
+        Self::Full
+    }
+}
+
 #[derive(Debug, Args)]
 #[clap(next_help_heading = "FILTER OPTIONS")]
 struct TestBuildFilter {
     #[clap(flatten)]
     cargo_options: CargoOptions,
 
+    #[clap(flatten)]
+    reuse_build: ReuseBuildOpts,
+
+    #[clap(flatten)]
+    remote: RemoteOpts,
+
     /// Run ignored tests
     #[clap(long, possible_values = RunIgnored::variants(), default_value_t, value_name = "WHICH")]
     run_ignored: RunIgnored,
 
-    /// Test partition, e.g. hash:1/2 or count:2/3
+    /// Test partition, e.g. hash:1/2, count:2/3 or duration:1/4
     #[clap(long)]
     partition: Option<PartitionerBuilder>,
 
+    /// Also run benchmarks, once each in test mode
+    ///
+    /// If no target selection options are specified, bench targets are built along with tests.
+    #[clap(long)]
+    include_benches: bool,
+
+    /// Run every test binary to list its tests, even if it hasn't changed since it was last listed
+    #[clap(long)]
+    no_list_cache: bool,
+
+    /// Only run tests in this package, without changing what's built
+    ///
+    /// Unlike --package, this also applies to tests run from an archive. May be specified multiple
+    /// times.
+    #[clap(long, value_name = "NAME")]
+    filter_package: Vec<String>,
+
+    /// Only run tests in binaries of this kind
+    ///
+    /// May be specified multiple times.
+    #[clap(long, arg_enum, value_name = "KIND")]
+    binary_kind: Vec<BinaryKindOpt>,
+
+    /// Only run tests in the binary with this ID, e.g. my-package::my-test
+    ///
+    /// May be specified multiple times.
+    #[clap(long, value_name = "ID")]
+    binary_id: Vec<String>,
+
+    /// Only run tests in packages affected by the changed files listed in this file
+    ///
+    /// The file lists one path per line, relative to the workspace root, as printed by `git diff
+    /// --name-only`; use '-' to read the list from standard input. Tests are run in the packages
+    /// containing the changed files, and in all packages that depend on them. If a changed file
+    /// isn't in any package, all tests are run.
+    #[clap(long, value_name = "PATH")]
+    changed_files: Option<Utf8PathBuf>,
+
+    /// Test filter expression, e.g. 'package(my-crate) and not test(slow)'
+    ///
+    /// May be specified multiple times, in which case tests matching any of the expressions are
+    /// run. Test name filters apply as well.
+    #[clap(long, short = 'E', value_name = "EXPR")]
+    filter_expr: Vec<String>,
+
+    /// Don't apply the profile's default filter
+    ///
+    /// The default filter is also ignored if any other filters are passed in.
+    #[clap(long)]
+    ignore_default_filter: bool,
+
+    /// Skip tests whose names contain this pattern
+    ///
+    /// May be specified multiple times. Skip patterns take precedence over test name filters.
+    #[clap(long, value_name = "PATTERN")]
+    skip: Vec<String>,
+
+    /// Only run tests whose names are exactly equal to the filters, rather than containing them
+    ///
+    /// This applies to skip patterns as well.
+    #[clap(long)]
+    exact: bool,
+
     // TODO: add regex-based filtering in the future?
     /// Test name filter
+    ///
+    /// For compatibility with `cargo test`, libtest's --skip and --exact options are also
+    /// accepted after `--`.
     #[clap(name = "FILTERS", help_heading = None)]
     filter: Vec<String>,
 }
 
 impl TestBuildFilter {
+    /// Builds the list of tests to run.
+    ///
+    /// If tests are to be run on a remote host, the runner for that host is returned as well.
+    #[allow(clippy::too_many_arguments)]
     fn compute<'g>(
         &self,
         manifest_path: Option<&'g Utf8Path>,
         graph: &'g PackageGraph,
+        prebuilt: Option<&PrebuiltBinaries>,
+        config: &NextestConfig,
+        profile: &NextestProfile<'_>,
         output: OutputContext,
         runner: Option<&TargetRunner>,
-    ) -> Result<TestList<'g>> {
-        // Don't use the manifest path from the graph to ensure that if the user cd's into a
-        // particular crate and runs cargo nextest, then it behaves identically to cargo test.
-        let mut cargo_cli = CargoCli::new("test", manifest_path, output);
+        rerun_failed: Option<LastRun>,
+    ) -> Result<(TestList<'g>, Option<SshRunner>)> {
+        // Check the filters before building anything.
+        let (patterns, skip, exact) = self.name_filters()?;
+        let exprs = self
+            .filter_expr
+            .iter()
+            .map(FilterExpr::parse)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(ExpectedError::filter_expr_parse_error)?;
 
-        // Only build tests in the cargo test invocation, do not run them.
-        cargo_cli.add_args(["--no-run", "--message-format", "json-render-diagnostics"]);
-        cargo_cli.add_options(&self.cargo_options);
+        let test_artifacts =
+            self.compute_artifacts(manifest_path, graph, prebuilt, config, output)?;
+        let mut test_filter =
+            TestFilterBuilder::new(self.run_ignored, self.partition.clone(), &patterns);
+        test_filter
+            .set_skip_patterns(&skip)
+            .set_exact(exact)
+            .set_exprs(exprs)
+            .set_include_benches(self.include_benches)
+            .set_packages(&self.filter_package)
+            .set_kinds(self.binary_kind.iter().map(|kind| kind.to_kind()))
+            .set_binary_ids(&self.binary_id)
+            .set_excludes(
+                profile
+                    .excludes()
+                    .iter()
+                    .map(|exclude| exclude.filter().clone()),
+            );
+        if let Some(last_run) = rerun_failed {
+            test_filter.set_rerun_failed(last_run);
+        }
+        if let Some(changed_files) = &self.changed_files {
+            match AffectedPackages::compute(graph, read_changed_files(changed_files)?) {
+                AffectedPackages::All { file } => {
+                    log::info!("{} isn't in any package, so all tests are affected", file);
+                }
+                AffectedPackages::Packages(package_ids) => {
+                    log::info!("{} packages affected by changed files", package_ids.len());
+                    test_filter.set_affected_packages(package_ids);
+                }
+            }
+        }
+        // The default filter is meant for routine runs: any filter passed in explicitly replaces
+        // it.
+        let explicit_filters = !patterns.is_empty()
+            || !skip.is_empty()
+            || !self.filter_expr.is_empty()
+            || !self.filter_package.is_empty()
+            || !self.binary_kind.is_empty()
+            || !self.binary_id.is_empty();
+        match profile.default_filter() {
+            Some(default_filter) if !explicit_filters && !self.ignore_default_filter => {
+                test_filter.set_default_filter(default_filter.clone());
+            }
+            _ => {}
+        }
 
-        let expression = cargo_cli.to_expression();
-        let output = expression
-            .stdout_capture()
-            .unchecked()
-            .run()
-            .wrap_err("failed to build tests")?;
-        if !output.status.success() {
-            return Err(Report::new(ExpectedError::build_failed(
-                cargo_cli.all_args(),
-                output.status.code(),
-            )));
+        // Binaries extracted from an archive are in a temporary directory, so there's no point
+        // caching their test lists.
+        let store_dir = config.store_dir();
+        let extracted = matches!(prebuilt, Some(PrebuiltBinaries::Archive(_)));
+        let cache = if self.no_list_cache || extracted {
+            None
+        } else {
+            match ListCache::read(&store_dir) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    log::warn!("{} (all test binaries will be listed)", err);
+                    Some(ListCache::default())
+                }
+            }
+        };
+
+        let (mut test_list, remote) = match self.remote.builder() {
+            Some(builder) => {
+                let remote = builder
+                    .build(&test_artifacts, self.reuse_build.workspace_root(graph))
+                    .wrap_err("failed to set up remote host")?;
+                let test_list =
+                    TestList::new_remote(test_artifacts, &test_filter, &remote, cache.as_ref())
+                        .wrap_err("error building test list")?;
+                (test_list, Some(remote))
+            }
+            None => {
+                let test_list = TestList::new(test_artifacts, &test_filter, runner, cache.as_ref())
+                    .wrap_err("error building test list")?;
+                (test_list, None)
+            }
+        };
+        if let Some(cache) = &cache {
+            if let Err(err) = cache.write(&store_dir) {
+                log::warn!("{}", err);
+            }
         }
 
-        let test_artifacts = RustTestArtifact::from_messages(graph, Cursor::new(output.stdout))?;
+        if let Some(PartitionerBuilder::Duration {
+            shard,
+            total_shards,
+        }) = self.partition
+        {
+            // Without timings, every test is assumed to take the same time.
+            let timings = match TestTimings::read(&store_dir) {
+                Ok(timings) if timings.is_empty() => {
+                    log::warn!("no test timings recorded yet (tests will be partitioned by count)");
+                    timings
+                }
+                Ok(timings) => timings,
+                Err(err) => {
+                    log::warn!("{} (tests will be partitioned by count)", err);
+                    TestTimings::default()
+                }
+            };
+            test_list.partition_by_duration(shard, total_shards, &timings);
+        }
+
+        Ok((test_list, remote))
+    }
+
+    /// Returns the test name patterns, skip patterns and whether matching is exact.
+    ///
+    /// Scripts written for `cargo test` pass `--skip` and `--exact` to libtest after `--`, where
+    /// they end up among the filters.
+    fn name_filters(&self) -> Result<(Vec<&str>, Vec<&str>, bool)> {
+        let mut patterns = vec![];
+        let mut skip: Vec<_> = self.skip.iter().map(String::as_str).collect();
+        let mut exact = self.exact;
+
+        let mut filters = self.filter.iter();
+        while let Some(filter) = filters.next() {
+            match filter.as_str() {
+                "--exact" => exact = true,
+                "--skip" => match filters.next() {
+                    Some(pattern) => skip.push(pattern),
+                    None => return Err(eyre!("--skip requires a pattern")),
+                },
+                pattern => patterns.push(pattern),
+            }
+        }
+        Ok((patterns, skip, exact))
+    }
+
+    /// Builds test binaries, or reads them from an extracted archive or a list of external binaries.
+    fn compute_artifacts<'g>(
+        &self,
+        manifest_path: Option<&'g Utf8Path>,
+        graph: &'g PackageGraph,
+        prebuilt: Option<&PrebuiltBinaries>,
+        config: &NextestConfig,
+        output: OutputContext,
+    ) -> Result<Vec<RustTestArtifact<'g>>> {
+        let workspace_root = self.reuse_build.workspace_root(graph);
+        let mut test_artifacts = match prebuilt {
+            Some(PrebuiltBinaries::Archive(extracted)) => extracted
+                .rust_test_artifacts(graph, workspace_root)
+                .wrap_err("error reading test binaries from archive")?,
+            Some(PrebuiltBinaries::External(binaries)) => {
+                binaries.rust_test_artifacts(graph, workspace_root)?
+            }
+            None => build_test_artifacts(
+                &self.cargo_options,
+                self.include_benches,
+                manifest_path,
+                graph,
+                output,
+            )?,
+        };
+        for artifact in &mut test_artifacts {
+            if config.is_opaque_binary(&artifact.binary_id) {
+                artifact.harness = RustTestHarness::Opaque;
+            }
+        }
+        Ok(test_artifacts)
+    }
+}
+
+/// Options for running tests on a remote host.
+#[derive(Debug, Default, Args)]
+#[clap(next_help_heading = "REMOTE OPTIONS")]
+struct RemoteOpts {
+    /// Copy test binaries to this host and run tests on it over SSH, as [user@]hostname
+    #[clap(long, value_name = "HOST")]
+    remote: Option<String>,
+
+    /// Directory on the remote host to copy test binaries to [default: a temporary directory]
+    #[clap(long, value_name = "DIR", requires = "remote")]
+    remote_dir: Option<String>,
 
-        let test_filter =
-            TestFilterBuilder::new(self.run_ignored, self.partition.clone(), &self.filter);
-        TestList::new(test_artifacts, &test_filter, runner).wrap_err("error building test list")
+    /// Path to the workspace on the remote host, which tests are run within [default: the
+    /// directory test binaries are copied to]
+    #[clap(long, value_name = "PATH", requires = "remote")]
+    remote_workspace: Option<String>,
+}
+
+impl RemoteOpts {
+    fn builder(&self) -> Option<SshRunnerBuilder> {
+        let mut builder = SshRunnerBuilder::new(self.remote.as_deref()?);
+        if let Some(remote_dir) = &self.remote_dir {
+            builder.set_remote_dir(remote_dir);
+        }
+        if let Some(remote_workspace) = &self.remote_workspace {
+            builder.set_remote_workspace(remote_workspace);
+        }
+        Some(builder)
+    }
+}
+
+/// Options for reusing test binaries from an archive, or running binaries built without Cargo.
+#[derive(Debug, Default, Args)]
+#[clap(next_help_heading = "REUSE BUILD OPTIONS")]
+struct ReuseBuildOpts {
+    /// Run tests from an archive created by `cargo nextest archive`, instead of building them
+    #[clap(long, value_name = "PATH")]
+    archive_file: Option<Utf8PathBuf>,
+
+    /// Run the test binaries listed in this JSON file, instead of building them with Cargo
+    ///
+    /// This is meant for binaries built by other build systems, such as Bazel or Buck. Cargo isn't
+    /// run at all, and the current directory is treated as the workspace root.
+    #[clap(long, value_name = "PATH", conflicts_with = "archive-file")]
+    external_binaries: Option<Utf8PathBuf>,
+
+    /// Directory to extract the archive to [default: a temporary directory]
+    #[clap(long, value_name = "DIR", requires = "archive-file")]
+    extract_to: Option<Utf8PathBuf>,
+
+    /// Path to the workspace on this machine, if different from where the archive was created
+    #[clap(long, value_name = "PATH", requires = "archive-file")]
+    workspace_remap: Option<Utf8PathBuf>,
+}
+
+/// Test binaries that were built ahead of time, rather than by this invocation.
+#[derive(Debug)]
+enum PrebuiltBinaries {
+    /// Binaries extracted from an archive.
+    Archive(ExtractedArchive),
+
+    /// Binaries built without Cargo.
+    External(ExternalBinaryList),
+}
+
+impl ReuseBuildOpts {
+    /// Extracts the archive or reads the list of external binaries if one was specified, and
+    /// constructs the package graph either from it or from `cargo metadata`.
+    ///
+    /// The `cargo metadata` JSON the graph was constructed from is returned as well.
+    fn load_graph(
+        &self,
+        metadata_opts: &MetadataOpts,
+        output: OutputContext,
+    ) -> Result<(String, PackageGraph, Option<PrebuiltBinaries>)> {
+        if let Some(archive_file) = &self.archive_file {
+            let extracted = ExtractedArchive::extract(archive_file, self.extract_to.as_deref())
+                .wrap_err_with(|| format!("failed to extract archive '{}'", archive_file))?;
+            let json = extracted.cargo_metadata().to_owned();
+            let graph = graph_from_json(&json)?;
+            return Ok((json, graph, Some(PrebuiltBinaries::Archive(extracted))));
+        }
+        if let Some(external_binaries) = &self.external_binaries {
+            let binaries = ExternalBinaryList::read(external_binaries)?;
+            let cwd = std::env::current_dir().wrap_err("failed to get current directory")?;
+            let cwd = Utf8PathBuf::from_path_buf(cwd)
+                .map_err(|cwd| eyre!("current directory {} is invalid UTF-8", cwd.display()))?;
+            let json = binaries.cargo_metadata(&cwd);
+            let graph = graph_from_json(&json)?;
+            return Ok((json, graph, Some(PrebuiltBinaries::External(binaries))));
+        }
+
+        let (json, graph) = metadata_opts.load(output)?;
+        Ok((json, graph, None))
+    }
+
+    /// Returns the workspace root that tests are run within.
+    fn workspace_root<'a>(&'a self, graph: &'a PackageGraph) -> &'a Utf8Path {
+        self.workspace_remap
+            .as_deref()
+            .unwrap_or_else(|| graph.workspace().root())
     }
 }
 
@@ -224,18 +929,23 @@ impl TestBuildFilter {
 #[derive(Debug, Default, Args)]
 #[clap(next_help_heading = "RUNNER OPTIONS")]
 pub struct TestRunnerOpts {
-    /// Number of tests to run simultaneously [default: logical CPU count]
+    /// Number of tests to run simultaneously [default: from profile]
+    ///
+    /// Either a positive number, a negative number which is subtracted from the number of logical
+    /// CPUs, "num-cpus", or a multiple like "num-cpus*2". Can also be set through the
+    /// NEXTEST_TEST_THREADS environment variable, which is ignored with --no-capture.
     #[clap(
         long,
         short = 'j',
         visible_alias = "jobs",
         value_name = "THREADS",
-        conflicts_with = "no-capture"
+        allow_hyphen_values = true,
+        conflicts_with_all = &["no-capture", "wait-for-debugger"]
     )]
-    test_threads: Option<usize>,
+    test_threads: Option<TestThreads>,
 
     /// Number of retries for failing tests [default: from profile]
-    #[clap(long)]
+    #[clap(long, env = "NEXTEST_RETRIES")]
     retries: Option<usize>,
 
     /// Cancel test run on the first failure
@@ -245,12 +955,115 @@ pub struct TestRunnerOpts {
     /// Run all tests regardless of failure
     #[clap(long, overrides_with = "fail-fast")]
     no_fail_fast: bool,
+
+    /// List the tests that would be run, in the order they'd be started in, without running them
+    ///
+    /// Setup scripts aren't run either.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// What to do if no tests match the filters [default: from profile]
+    #[clap(long, possible_values = NoTestsBehavior::variants(), value_name = "ACTION")]
+    no_tests: Option<NoTestsBehavior>,
+
+    /// Cancel test run after this many failures
+    #[clap(long, value_name = "N", conflicts_with_all = &["fail-fast", "no-fail-fast"])]
+    max_fail: Option<NonZeroUsize>,
+
+    /// Run each test this many times in a row, and report how often each test passed
+    ///
+    /// Implies --no-fail-fast unless --fail-fast is passed in.
+    #[clap(long, value_name = "N")]
+    repeat: Option<NonZeroUsize>,
+
+    /// Run tests in a random order, within and across binaries
+    ///
+    /// The seed used is printed out at the start of the run.
+    #[clap(long)]
+    shuffle: bool,
+
+    /// Seed to shuffle tests with, to reproduce the order of an earlier run (implies --shuffle)
+    #[clap(long, value_name = "SEED")]
+    shuffle_seed: Option<u64>,
+
+    /// Run failing tests once more under the recorder, such as rr, after the run [default: from
+    /// profile]
+    ///
+    /// The recorder is set through `recorder` in the profile. Recordings are written to
+    /// `recordings` within the profile's store directory.
+    #[clap(long)]
+    record_failures: bool,
+
+    /// Collect LLVM source-based coverage data for each test
+    ///
+    /// Test binaries must be built with `-C instrument-coverage`. Coverage data is written to
+    /// `coverage` within the profile's store directory. Not supported with --remote.
+    #[clap(long, conflicts_with = "remote")]
+    coverage: bool,
+
+    /// Merge coverage data into a single .profdata file after the run
+    #[clap(long, requires = "coverage")]
+    coverage_merge: bool,
+
+    /// Path to llvm-profdata, used to merge coverage data [default: llvm-profdata]
+    #[clap(long, requires = "coverage-merge", value_name = "PATH")]
+    llvm_profdata: Option<Utf8PathBuf>,
 }
 
 impl TestRunnerOpts {
-    fn to_builder(&self, no_capture: bool) -> TestRunnerBuilder {
+    /// The environment variable that sets the number of test threads, if --test-threads isn't
+    /// passed in.
+    const TEST_THREADS_ENV: &'static str = "NEXTEST_TEST_THREADS";
+
+    /// Returns the number of test threads passed in on the command line or through the
+    /// environment.
+    ///
+    /// --test-threads can't be combined with --no-capture, but the environment variable may be set
+    /// for every run, so it's ignored with a warning instead. It isn't read by clap, since clap
+    /// would reject it as conflicting with --no-capture.
+    fn test_threads(&self, no_capture: bool) -> Result<Option<TestThreads>> {
+        self.test_threads_impl(no_capture, std::env::var_os(Self::TEST_THREADS_ENV))
+    }
+
+    fn test_threads_impl(
+        &self,
+        no_capture: bool,
+        env_value: Option<OsString>,
+    ) -> Result<Option<TestThreads>> {
+        if let Some(test_threads) = self.test_threads {
+            return Ok(Some(test_threads));
+        }
+        let value = match env_value {
+            Some(value) => value,
+            None => return Ok(None),
+        };
+        let test_threads = value
+            .to_str()
+            .and_then(|value| value.parse::<TestThreads>().ok())
+            .ok_or_else(|| {
+                eyre!(
+                    "invalid value for {}: {}\n(expected a non-zero integer, \"num-cpus\" or \
+                     \"num-cpus*<N>\")",
+                    Self::TEST_THREADS_ENV,
+                    value.to_string_lossy()
+                )
+            })?;
+        if no_capture {
+            log::warn!(
+                "ignoring {}={}: --no-capture runs tests serially",
+                Self::TEST_THREADS_ENV,
+                value.to_string_lossy()
+            );
+            return Ok(None);
+        }
+        Ok(Some(test_threads))
+    }
+
+    fn to_builder<'a>(&self, no_capture: bool) -> Result<TestRunnerBuilder<'a>> {
         let mut builder = TestRunnerBuilder::default();
-        builder.set_no_capture(no_capture);
+        builder
+            .set_no_capture(no_capture)
+            .set_double_spawn(DoubleSpawnInfo::try_enable());
         if let Some(retries) = self.retries {
             builder.set_retries(retries);
         }
@@ -258,12 +1071,52 @@ impl TestRunnerOpts {
             builder.set_fail_fast(false);
         } else if self.fail_fast {
             builder.set_fail_fast(true);
+        } else if self.repeat.is_some() {
+            // The point of repeating tests is to find out how often they fail, so keep going.
+            builder.set_fail_fast(false);
         }
-        if let Some(test_threads) = self.test_threads {
+        if let Some(max_fail) = self.max_fail {
+            builder.set_max_fail(max_fail);
+        }
+        if let Some(repeat) = self.repeat {
+            builder.set_repeat(repeat);
+        }
+        if let Some(test_threads) = self.test_threads(no_capture)? {
             builder.set_test_threads(test_threads);
         }
+        if self.shuffle || self.shuffle_seed.is_some() {
+            let seed = self.shuffle_seed.unwrap_or_else(rand::random);
+            builder.set_shuffle_seed(seed);
+        }
+        if self.record_failures {
+            builder.set_record_failures(true);
+        }
 
-        builder
+        Ok(builder)
+    }
+
+    fn coverage_collector(&self, store_dir: &Utf8Path) -> Result<Option<CoverageCollector>> {
+        if !self.coverage {
+            return Ok(None);
+        }
+        let collector = CoverageCollector::new(store_dir.join("coverage"))
+            .wrap_err("failed to set up coverage collection")?;
+        Ok(Some(collector))
+    }
+
+    fn merge_coverage(&self, collector: &CoverageCollector) -> Result<()> {
+        if !self.coverage_merge {
+            return Ok(());
+        }
+        let llvm_profdata = self
+            .llvm_profdata
+            .as_deref()
+            .unwrap_or_else(|| Utf8Path::new("llvm-profdata"));
+        let profdata = collector
+            .merge(llvm_profdata)
+            .wrap_err("failed to merge coverage data")?;
+        log::info!("merged coverage data to {}", profdata);
+        Ok(())
     }
 }
 
@@ -274,7 +1127,6 @@ struct TestReporterOpts {
     #[clap(
         long,
         possible_values = TestOutputDisplay::variants(),
-        conflicts_with = "no-capture",
         value_name = "WHEN"
     )]
     failure_output: Option<TestOutputDisplay>,
@@ -283,7 +1135,6 @@ struct TestReporterOpts {
     #[clap(
         long,
         possible_values = TestOutputDisplay::variants(),
-        conflicts_with = "no-capture",
         value_name = "WHEN"
     )]
     success_output: Option<TestOutputDisplay>,
@@ -292,6 +1143,35 @@ struct TestReporterOpts {
     /// Test statuses to output
     #[clap(long, possible_values = StatusLevel::variants(), value_name = "LEVEL")]
     status_level: Option<StatusLevel>,
+
+    /// Don't show a progress bar while tests are running
+    ///
+    /// The progress bar is only shown if standard error is a terminal.
+    #[clap(long)]
+    hide_progress_bar: bool,
+
+    /// Show a full-screen dashboard while tests are running
+    ///
+    /// The dashboard lists running tests and failures so far. Select a test with the arrow keys and
+    /// press `c` to cancel it, or press `q` to cancel the run. Only supported if standard input and
+    /// standard error are terminals.
+    #[clap(long)]
+    tui: bool,
+
+    /// List the N slowest tests at the end of the run [default: from profile]
+    #[clap(long, value_name = "N")]
+    slowest: Option<usize>,
+
+    /// Additionally stream test events to stdout in this format
+    #[clap(long, arg_enum, default_value_t, value_name = "FMT")]
+    message_format: RunMessageFormatOpts,
+
+    /// Write test events in the --message-format format to this file, rather than to stdout
+    ///
+    /// Required to combine --message-format with --no-capture, since test output is written to
+    /// stdout as well.
+    #[clap(long, value_name = "PATH")]
+    message_format_file: Option<Utf8PathBuf>,
 }
 
 impl TestReporterOpts {
@@ -307,8 +1187,80 @@ impl TestReporterOpts {
         if let Some(status_level) = self.status_level {
             builder.set_status_level(status_level);
         }
+        if let Some(slowest) = self.slowest {
+            builder.set_slowest(slowest);
+        }
         builder
     }
+
+    /// Returns a reporter for machine-readable output, written to the --message-format-file if
+    /// specified, and otherwise to stdout alongside the human-readable output on stderr.
+    ///
+    /// With `test_output_on_stdout`, tests write their own output to stdout, where it would be
+    /// mixed in with the events. Events must then be written to a file.
+    fn machine_reporter<'a>(
+        &self,
+        test_output_on_stdout: bool,
+    ) -> Result<Option<Box<dyn Reporter<'a> + 'a>>> {
+        if let RunMessageFormatOpts::Human = self.message_format {
+            if self.message_format_file.is_some() {
+                return Err(eyre!(
+                    "--message-format-file requires a machine-readable --message-format"
+                ));
+            }
+            return Ok(None);
+        }
+        let writer: Box<dyn Write + Send> = match &self.message_format_file {
+            Some(path) => {
+                let file = std::fs::File::create(path)
+                    .wrap_err_with(|| format!("failed to create message format file '{}'", path))?;
+                Box::new(BufWriter::new(file))
+            }
+            None if test_output_on_stdout => {
+                return Err(eyre!(
+                    "--message-format {} can't be written to stdout with --no-capture, since \
+                     test output is written there too (pass in --message-format-file to write \
+                     events to a file instead)",
+                    self.message_format.name()
+                ));
+            }
+            None => Box::new(BufWriter::new(std::io::stdout())),
+        };
+        Ok(match self.message_format {
+            RunMessageFormatOpts::Human => None,
+            RunMessageFormatOpts::Json => Some(Box::new(JsonReporter::new().with_writer(writer))),
+            RunMessageFormatOpts::Tap => Some(Box::new(TapReporter::new().with_writer(writer))),
+            RunMessageFormatOpts::Libtest => {
+                Some(Box::new(LibtestReporter::new().with_writer(writer)))
+            }
+        })
+    }
+}
+
+#[derive(Copy, Clone, Debug, ArgEnum)]
+enum RunMessageFormatOpts {
+    /// Only produce human-readable output on stderr.
+    Human,
+    /// Write one JSON object per test event to stdout.
+    Json,
+    /// Write test results to stdout in TAP version 13 format.
+    Tap,
+    /// Write test results to stdout in the same format as `cargo test`.
+    Libtest,
+}
+
+impl RunMessageFormatOpts {
+    fn name(self) -> &'static str {
+        self.to_possible_value()
+            .expect("no values are skipped")
+            .get_name()
+    }
+}
+
+impl Default for RunMessageFormatOpts {
+    fn default() -> Self {
+        Self::Human
+    }
 }
 
 impl AppImpl {
@@ -316,87 +1268,566 @@ impl AppImpl {
     fn exec(self) -> Result<()> {
         let output = self.output.init();
 
-        let graph = build_graph(self.manifest_path.as_deref(), output)?;
-
         match self.command {
             Command::List {
+                profile,
                 build_filter,
                 message_format,
+                list_type,
             } => {
-                let target_runner = runner_for_target(build_filter.cargo_options.target.as_deref());
+                let (_, graph, prebuilt) = build_filter
+                    .reuse_build
+                    .load_graph(&self.metadata_opts, output)?;
+                let config = self
+                    .config_opts
+                    .make_config(build_filter.reuse_build.workspace_root(&graph))?;
+                let profile = config
+                    .profile(profile.as_deref().unwrap_or(NextestConfig::DEFAULT_PROFILE))
+                    .map_err(ExpectedError::profile_not_found)?;
+                let target_runner =
+                    runner_for_target(build_filter.cargo_options.target.as_deref(), &config);
 
-                let mut test_list = build_filter.compute(
-                    self.manifest_path.as_deref(),
-                    &graph,
-                    output,
-                    target_runner.as_ref(),
-                )?;
-                if output.color.should_colorize(Stream::Stdout) {
-                    test_list.colorize();
-                }
                 let stdout = std::io::stdout();
                 let lock = stdout.lock();
                 // Buffer the output to minimize syscalls.
                 let mut writer = BufWriter::new(lock);
-                test_list.write(message_format.to_output_format(output.verbose), &mut writer)?;
+                let output_format = message_format.to_output_format(output.verbose);
+                match list_type {
+                    ListTypeOpts::Full => {
+                        let (mut test_list, _remote) = build_filter.compute(
+                            self.metadata_opts.manifest_path.as_deref(),
+                            &graph,
+                            prebuilt.as_ref(),
+                            &config,
+                            &profile,
+                            output,
+                            target_runner.as_ref(),
+                            None,
+                        )?;
+                        if output.color.should_colorize(Stream::Stdout) {
+                            test_list.colorize();
+                        }
+                        test_list.write(output_format, &mut writer)?;
+                    }
+                    ListTypeOpts::BinariesOnly => {
+                        let test_artifacts = build_filter.compute_artifacts(
+                            self.metadata_opts.manifest_path.as_deref(),
+                            &graph,
+                            prebuilt.as_ref(),
+                            &config,
+                            output,
+                        )?;
+                        let mut binary_list = BinaryList::new(test_artifacts);
+                        if output.color.should_colorize(Stream::Stdout) {
+                            binary_list.colorize();
+                        }
+                        binary_list.write(output_format, &mut writer)?;
+                    }
+                }
                 writer.flush()?;
             }
             Command::Run {
                 ref profile,
                 no_capture,
+                wait_for_debugger,
                 ref build_filter,
+                rerun_failed,
                 ref runner_opts,
                 ref reporter_opts,
+                ref record,
+                store_lock,
             } => {
-                let config = self.config_opts.make_config(graph.workspace().root())?;
+                // Tests waiting for a debugger are run one at a time, and Enter is read from the
+                // terminal.
+                let no_capture = no_capture || wait_for_debugger;
+                if wait_for_debugger && DoubleSpawnInfo::try_enable().current_exe().is_none() {
+                    return Err(eyre!(
+                        "--wait-for-debugger is only supported on Unix, with NEXTEST_DOUBLE_SPAWN \
+                         not set to 0"
+                    ));
+                }
+                let (cargo_metadata, graph, prebuilt) = build_filter
+                    .reuse_build
+                    .load_graph(&self.metadata_opts, output)?;
+                let workspace_root = build_filter.reuse_build.workspace_root(&graph);
+                let config = self.config_opts.make_config(workspace_root)?;
                 let profile = config
                     .profile(profile.as_deref().unwrap_or(NextestConfig::DEFAULT_PROFILE))
                     .map_err(ExpectedError::profile_not_found)?;
                 let store_dir = profile.store_dir();
                 std::fs::create_dir_all(&store_dir)
                     .wrap_err_with(|| format!("failed to create store dir '{}'", store_dir))?;
+                let store = RunStore::new(store_dir);
+                // Held until the run finishes, so that the state written out at the end of the run
+                // isn't interleaved with another process's.
+                let _lock = lock_store(&store, store_lock)?;
+                // Artifacts of earlier runs are cleaned up before this run adds its own. Failing to
+                // clean them up shouldn't stop tests from running.
+                if let Err(err) = store.gc(config.store_retention(), SystemTime::now()) {
+                    log::warn!("{}", err);
+                }
 
-                let target_runner = runner_for_target(build_filter.cargo_options.target.as_deref());
+                let coverage = runner_opts.coverage_collector(store_dir)?;
+                if let Some(coverage) = &coverage {
+                    // Instrumented test binaries write out coverage data while tests are being
+                    // listed, too: keep it out of the current directory.
+                    std::env::set_var(LLVM_PROFILE_FILE_ENV, coverage.list_profile_file());
+                }
+
+                let target_runner =
+                    runner_for_target(build_filter.cargo_options.target.as_deref(), &config);
 
-                let test_list = build_filter.compute(
-                    self.manifest_path.as_deref(),
+                let last_run = if rerun_failed {
+                    let last_run = LastRun::read(store_dir)?;
+                    if last_run.is_empty() {
+                        log::warn!("no failed tests were recorded by the last run");
+                    }
+                    Some(last_run)
+                } else {
+                    None
+                };
+
+                let (test_list, remote) = build_filter.compute(
+                    self.metadata_opts.manifest_path.as_deref(),
                     &graph,
+                    prebuilt.as_ref(),
+                    &config,
+                    &profile,
                     output,
                     target_runner.as_ref(),
+                    last_run,
                 )?;
 
-                let mut reporter = reporter_opts
-                    .to_builder(no_capture)
+                if test_list.run_count() == 0 {
+                    match runner_opts.no_tests.unwrap_or_else(|| profile.no_tests()) {
+                        NoTestsBehavior::Pass => {}
+                        NoTestsBehavior::Warn => log::warn!("no tests to run"),
+                        NoTestsBehavior::Fail => {
+                            return Err(Report::new(ExpectedError::no_tests_run()));
+                        }
+                    }
+                }
+
+                let timings = match TestTimings::read(store_dir) {
+                    Ok(timings) => Some(timings),
+                    Err(err) => {
+                        // Timings are only used to decide the order to run tests in, so carry on
+                        // without them.
+                        log::warn!("{} (tests will be run in the order listed)", err);
+                        None
+                    }
+                };
+
+                if runner_opts.dry_run {
+                    let mut runner_builder = runner_opts.to_builder(no_capture)?;
+                    if let Some(timings) = timings {
+                        runner_builder.set_timings(timings);
+                    }
+                    let runner = runner_builder.build(&test_list, &profile, SignalHandler::noop());
+                    let stdout = std::io::stdout();
+                    let mut writer = BufWriter::new(stdout.lock());
+                    runner.write_dry_run(&mut writer)?;
+                    writer.flush()?;
+                    return Ok(());
+                }
+
+                let mut runner_builder = runner_opts.to_builder(no_capture)?;
+                runner_builder.set_wait_for_debugger(wait_for_debugger);
+                let mut reporter_builder = reporter_opts.to_builder(no_capture);
+                reporter_builder
                     .set_verbose(output.verbose)
-                    .build(&test_list, &profile);
+                    .set_quiet(output.quiet)
+                    .set_progress_bar(
+                        !reporter_opts.hide_progress_bar && atty::is(atty::Stream::Stderr),
+                    );
+                if let Some(timings) = &timings {
+                    reporter_builder.set_timings(timings.clone());
+                }
+                if reporter_opts.tui {
+                    if atty::is(atty::Stream::Stdin) && atty::is(atty::Stream::Stderr) {
+                        reporter_builder.set_tui(runner_builder.controller());
+                    } else {
+                        log::warn!(
+                            "--tui requires standard input and standard error to be terminals \
+                             (output will be printed line by line)"
+                        );
+                    }
+                }
+                let mut reporter = reporter_builder.build(&test_list, &profile);
                 if output.color.should_colorize(Stream::Stderr) {
                     reporter.colorize();
                 }
 
-                let handler = SignalHandler::new().wrap_err("failed to set up Ctrl-C handler")?;
-                let mut runner_builder = runner_opts.to_builder(no_capture);
+                let handler = SignalHandler::new().wrap_err("failed to set up signal handler")?;
+                // Machine-readable output is reported first, so that consumers see it as soon as
+                // possible.
+                if let Some(machine_reporter) = reporter_opts.machine_reporter(no_capture)? {
+                    runner_builder.add_reporter(machine_reporter);
+                }
+                runner_builder
+                    .add_reporter(reporter.with_writer(BufWriter::new(std::io::stderr())));
+                if let Some(record) = record {
+                    let recorder = RunRecorder::create(record, cargo_metadata)
+                        .wrap_err_with(|| format!("failed to create recording at '{}'", record))?;
+                    runner_builder.add_reporter(recorder);
+                }
                 if let Some(target_runner) = target_runner {
                     runner_builder.set_target_runner(target_runner);
                 }
+                if let Some(remote) = remote {
+                    runner_builder.set_remote(remote);
+                }
+                if let Some(coverage) = &coverage {
+                    runner_builder.set_coverage(coverage.clone());
+                }
+                if profile.network_isolation() {
+                    match check_network_isolation() {
+                        Ok(()) => {
+                            runner_builder.set_network_isolation(true);
+                        }
+                        Err(err) => {
+                            let source = err
+                                .source()
+                                .map_or_else(String::new, |source| format!(": {}", source));
+                            log::warn!("{}{} (tests will be run with network access)", err, source);
+                        }
+                    }
+                }
+                if let Some(timings) = timings {
+                    runner_builder.set_timings(timings);
+                }
 
                 let runner = runner_builder.build(&test_list, &profile, handler);
-                let stderr = std::io::stderr();
-                let mut writer = BufWriter::new(stderr);
-                let run_stats = runner.try_execute(|event| {
-                    // Write and flush the event.
-                    reporter.report_event(event, &mut writer)?;
-                    writer.flush().map_err(WriteEventError::Io)
-                })?;
+                let run_stats = runner.execute(|_| {})?;
+                // Coverage data is still useful if some tests failed, so merge it first.
+                if let Some(coverage) = &coverage {
+                    runner_opts.merge_coverage(coverage)?;
+                }
                 if !run_stats.is_success() {
+                    return Err(Report::new(run_failed_error(&run_stats)));
+                }
+            }
+            Command::Replay {
+                ref profile,
+                ref recording,
+                ref reporter_opts,
+            } => {
+                let recorded = RecordedRun::read(recording)?;
+                let graph = graph_from_json(recorded.cargo_metadata())?;
+                let config = self.config_opts.make_config(graph.workspace().root())?;
+                let profile = config
+                    .profile(profile.as_deref().unwrap_or(NextestConfig::DEFAULT_PROFILE))
+                    .map_err(ExpectedError::profile_not_found)?;
+                let test_list = recorded.test_list(&graph)?;
+
+                let mut reporter = reporter_opts
+                    .to_builder(false)
+                    .set_verbose(output.verbose)
+                    .set_quiet(output.quiet)
+                    .set_replay(true)
+                    .build(&test_list, &profile);
+                if output.color.should_colorize(Stream::Stderr) {
+                    reporter.colorize();
+                }
+
+                let mut reporters = vec![];
+                // Replayed output is written to stderr, so stdout is free for events.
+                if let Some(machine_reporter) = reporter_opts.machine_reporter(false)? {
+                    reporters.push(machine_reporter);
+                }
+                reporters.push(Box::new(
+                    reporter.with_writer(BufWriter::new(std::io::stderr())),
+                ));
+                recorded.replay(&test_list, &mut reporters)?;
+
+                match recorded.run_stats() {
+                    Some(run_stats) if run_stats.is_success() => {}
+                    Some(run_stats) => return Err(Report::new(run_failed_error(&run_stats))),
+                    None => {
+                        log::warn!("recording at {} ends before the run finished", recording);
+                        return Err(Report::new(ExpectedError::test_run_failed()));
+                    }
+                }
+            }
+            Command::Diff {
+                ref old,
+                ref new,
+                min_slowdown_ratio,
+                min_slowdown,
+                message_format,
+            } => {
+                let old = RecordedRun::read(old)?;
+                let new = RecordedRun::read(new)?;
+                let mut diff = RunDiffBuilder::default()
+                    .set_min_slowdown_ratio(min_slowdown_ratio)
+                    .set_min_slowdown(min_slowdown)
+                    .build(&old.to_summary(), &new.to_summary());
+                if output.color.should_colorize(Stream::Stdout) {
+                    diff.colorize();
+                }
+
+                let stdout = std::io::stdout();
+                let mut writer = BufWriter::new(stdout.lock());
+                diff.write(message_format.to_output_format(output.verbose), &mut writer)?;
+                writer.flush()?;
+
+                let new_failures = diff.summary().newly_failing.len();
+                if new_failures > 0 {
+                    return Err(Report::new(ExpectedError::new_failures(new_failures)));
+                }
+            }
+            Command::Merge {
+                ref summaries,
+                output: ref output_file,
+            } => {
+                let shards = summaries
+                    .iter()
+                    .map(|path| {
+                        let json = std::fs::read_to_string(path)
+                            .wrap_err_with(|| format!("failed to read '{}'", path))?;
+                        RunSummary::parse_json(json)
+                            .wrap_err_with(|| format!("failed to parse run summary '{}'", path))
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let merge = RunMerge::new(&shards);
+
+                match output_file {
+                    Some(path) => {
+                        let file = std::fs::File::create(path)
+                            .wrap_err_with(|| format!("failed to create '{}'", path))?;
+                        let mut writer = BufWriter::new(file);
+                        SerializableFormat::JsonPretty.to_writer(merge.summary(), &mut writer)?;
+                        writer.flush()?;
+                    }
+                    None => {
+                        let stdout = std::io::stdout();
+                        let mut writer = BufWriter::new(stdout.lock());
+                        SerializableFormat::JsonPretty.to_writer(merge.summary(), &mut writer)?;
+                        writeln!(writer)?;
+                        writer.flush()?;
+                    }
+                }
+
+                for test in merge.missing_tests() {
+                    log::error!(
+                        "{} {} wasn't run by any shard",
+                        test.binary_id,
+                        test.test_name
+                    );
+                }
+                for test in merge.duplicate_tests() {
+                    let paths: Vec<_> = test
+                        .shards
+                        .iter()
+                        .map(|&index| summaries[index].as_str())
+                        .collect();
+                    log::error!(
+                        "{} {} was run by more than one shard: {}",
+                        test.binary_id,
+                        test.test_name,
+                        paths.join(", ")
+                    );
+                }
+
+                if !merge.is_complete() {
+                    return Err(Report::new(ExpectedError::incomplete_shards(
+                        merge.missing_tests().len(),
+                        merge.duplicate_tests().len(),
+                    )));
+                }
+                if !merge.summary().run_stats.is_success() {
                     return Err(Report::new(ExpectedError::test_run_failed()));
                 }
             }
+            Command::Archive {
+                ref cargo_options,
+                ref archive_file,
+            } => {
+                let (json, graph) = self.metadata_opts.load(output)?;
+                let test_artifacts = build_test_artifacts(
+                    cargo_options,
+                    false,
+                    self.metadata_opts.manifest_path.as_deref(),
+                    &graph,
+                    output,
+                )?;
+                archive_to_file(&graph, &json, &test_artifacts, archive_file)
+                    .wrap_err_with(|| format!("failed to archive tests to '{}'", archive_file))?;
+                log::info!(
+                    "archived {} test binaries to {}",
+                    test_artifacts.len(),
+                    archive_file
+                );
+            }
+            Command::Store {
+                ref profile,
+                ref command,
+            } => {
+                let (_, graph) = self.metadata_opts.load(output)?;
+                let config = self.config_opts.make_config(graph.workspace().root())?;
+                let profile = config
+                    .profile(profile.as_deref().unwrap_or(NextestConfig::DEFAULT_PROFILE))
+                    .map_err(ExpectedError::profile_not_found)?;
+                let store = RunStore::new(profile.store_dir());
+                match command {
+                    StoreCommand::List => {
+                        let now = SystemTime::now();
+                        let stdout = std::io::stdout();
+                        let mut writer = BufWriter::new(stdout.lock());
+                        for run in store.runs()? {
+                            // Round ages down to the second, so they're readable.
+                            let age = now
+                                .duration_since(run.modified())
+                                .map_or(Duration::ZERO, |age| Duration::from_secs(age.as_secs()));
+                            let kinds: Vec<_> = run
+                                .artifacts
+                                .iter()
+                                .map(|artifact| artifact.kind.to_string())
+                                .collect();
+                            writeln!(
+                                writer,
+                                "{}  {:>12} ago  {:>10}  {}",
+                                run.run_id,
+                                humantime::format_duration(age).to_string(),
+                                display_bytes(run.size()),
+                                kinds.join(", ")
+                            )?;
+                        }
+                        writer.flush()?;
+                    }
+                    StoreCommand::Gc => {
+                        let _lock = lock_store(&store, StoreLockOpt::Wait)?;
+                        let summary = store.gc(config.store_retention(), SystemTime::now())?;
+                        println!(
+                            "removed artifacts of {} runs from {} ({} freed)",
+                            summary.removed_runs,
+                            store.dir(),
+                            display_bytes(summary.bytes_freed)
+                        );
+                    }
+                }
+            }
+            Command::ShowConfig { ref profile } => {
+                let (_, graph) = self.metadata_opts.load(output)?;
+                let workspace_root = graph.workspace().root();
+                report_config_diagnostics(&self.config_opts.check_config(workspace_root))?;
+                let config = self.config_opts.read_config(workspace_root)?;
+                let resolved = config
+                    .profile_to_toml(profile.as_deref().unwrap_or(NextestConfig::DEFAULT_PROFILE))
+                    .map_err(ExpectedError::profile_not_found)?;
+
+                let stdout = std::io::stdout();
+                let mut writer = BufWriter::new(stdout.lock());
+                writeln!(
+                    writer,
+                    "# Config sources, in increasing order of precedence:"
+                )?;
+                for source in config.sources() {
+                    writeln!(writer, "#   {}", source)?;
+                }
+                writeln!(writer)?;
+                write!(writer, "{}", resolved)?;
+                writer.flush()?;
+            }
         }
         Ok(())
     }
 }
 
-fn build_graph(manifest_path: Option<&Utf8Path>, output: OutputContext) -> Result<PackageGraph> {
+/// Logs the problems found in the config, failing if any of them are errors.
+fn report_config_diagnostics(diagnostics: &[ConfigDiagnostic]) -> Result<(), ExpectedError> {
+    let mut errors = 0;
+    for diagnostic in diagnostics {
+        if diagnostic.is_error() {
+            log::error!("{}", diagnostic);
+            errors += 1;
+        } else {
+            log::warn!("{}", diagnostic);
+        }
+    }
+    if errors > 0 {
+        return Err(ExpectedError::config_check_failed(errors));
+    }
+    Ok(())
+}
+
+/// Locks the store, so that other nextest processes don't write to it at the same time.
+fn lock_store(store: &RunStore, action: StoreLockOpt) -> Result<StoreLock> {
+    if let Some(lock) = store.try_lock()? {
+        return Ok(lock);
+    }
+    match action {
+        StoreLockOpt::Wait => {
+            log::warn!(
+                "waiting for another nextest process to finish using {}",
+                store.dir()
+            );
+            Ok(store.lock()?)
+        }
+        StoreLockOpt::Fail => Err(eyre!(
+            "another nextest process is using {} (pass in --store-lock wait to wait for it)",
+            store.dir()
+        )),
+    }
+}
+
+/// Formats a number of bytes for display, e.g. "1.5 MiB".
+fn display_bytes(bytes: u64) -> String {
+    const UNITS: &[&str] = &["KiB", "MiB", "GiB", "TiB"];
+    if bytes < 1024 {
+        return format!("{} B", bytes);
+    }
+    let mut size = bytes as f64;
+    let mut unit = "B";
+    for &next_unit in UNITS {
+        if size < 1024.0 {
+            break;
+        }
+        size /= 1024.0;
+        unit = next_unit;
+    }
+    format!("{:.1} {}", size, unit)
+}
+
+/// Builds test binaries with `cargo test --no-run`, returning the list of artifacts produced.
+fn build_test_artifacts<'g>(
+    cargo_options: &CargoOptions,
+    include_benches: bool,
+    manifest_path: Option<&Utf8Path>,
+    graph: &'g PackageGraph,
+    output: OutputContext,
+) -> Result<Vec<RustTestArtifact<'g>>> {
+    // Don't use the manifest path from the graph to ensure that if the user cd's into a
+    // particular crate and runs cargo nextest, then it behaves identically to cargo test.
+    let mut cargo_cli = CargoCli::new("test", manifest_path, output);
+
+    // Only build tests in the cargo test invocation, do not run them.
+    cargo_cli.add_args(["--no-run", "--message-format", "json-render-diagnostics"]);
+    cargo_cli.add_options(cargo_options);
+    if include_benches {
+        cargo_cli.add_bench_targets(cargo_options);
+    }
+
+    let expression = cargo_cli.to_expression();
+    let output = expression
+        .stdout_capture()
+        .unchecked()
+        .run()
+        .wrap_err("failed to build tests")?;
+    if !output.status.success() {
+        return Err(Report::new(ExpectedError::build_failed(
+            cargo_cli.all_args(),
+            output.status.code(),
+        )));
+    }
+
+    Ok(RustTestArtifact::from_messages(
+        graph,
+        Cursor::new(output.stdout),
+    )?)
+}
+
+fn cargo_metadata_json(manifest_path: Option<&Utf8Path>, output: OutputContext) -> Result<String> {
     let mut cargo_cli = CargoCli::new("metadata", manifest_path, output);
     // Construct a package graph with --no-deps since we don't need full dependency
     // information.
@@ -413,13 +1844,52 @@ fn build_graph(manifest_path: Option<&Utf8Path>, output: OutputContext) -> Resul
         return Err(ExpectedError::cargo_metadata_failed().into());
     }
 
-    let json =
-        String::from_utf8(output.stdout).wrap_err("cargo metadata output is invalid UTF-8")?;
-    Ok(guppy::CargoMetadata::parse_json(&json)?.build_graph()?)
+    String::from_utf8(output.stdout).wrap_err("cargo metadata output is invalid UTF-8")
+}
+
+/// Returns the error for a run that wasn't successful, depending on why it failed.
+fn run_failed_error(run_stats: &RunStats) -> ExpectedError {
+    if run_stats.cancel_reason == Some(CancelReason::Signal) {
+        ExpectedError::test_run_interrupted()
+    } else {
+        ExpectedError::test_run_failed()
+    }
+}
+
+/// Reads a list of changed files, one per line, from the given file or from standard input if the
+/// path is `-`.
+fn read_changed_files(path: &Utf8Path) -> Result<Vec<String>> {
+    let contents = if path == "-" {
+        use std::io::Read;
+
+        let mut contents = String::new();
+        std::io::stdin()
+            .read_to_string(&mut contents)
+            .wrap_err("failed to read changed files from standard input")?;
+        contents
+    } else {
+        std::fs::read_to_string(path)
+            .wrap_err_with(|| format!("failed to read changed files from '{}'", path))?
+    };
+    Ok(contents
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(str::to_owned)
+        .collect())
+}
+
+fn graph_from_json(json: &str) -> Result<PackageGraph> {
+    Ok(guppy::CargoMetadata::parse_json(json)?.build_graph()?)
 }
 
-fn runner_for_target(triple: Option<&str>) -> Option<TargetRunner> {
-    match TargetRunner::for_target(triple) {
+fn runner_for_target(triple: Option<&str>, config: &NextestConfig) -> Option<TargetRunner> {
+    // A configured target runner takes precedence over the built-in runners for WebAssembly.
+    let runner = TargetRunner::for_target(triple).and_then(|runner| match (runner, triple) {
+        (None, Some(triple)) => TargetRunner::for_wasm_target(triple, config.wasm()),
+        (runner, _) => Ok(runner),
+    });
+    match runner {
         Ok(runner) => runner,
         Err(err) => {
             warn_on_target_runner_err(&err).expect("writing to a string is infallible");
@@ -446,3 +1916,260 @@ fn warn_on_target_runner_err(err: &TargetRunnerError) -> Result<(), std::fmt::Er
     log::warn!("{}", s);
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+    use std::ffi::OsStr;
+
+    fn parse(args: &[&str]) -> Result<AppImpl, clap::Error> {
+        let args = ["cargo", "nextest"].iter().chain(args);
+        CargoNextestApp::try_parse_from(args).map(|app| {
+            let NextestSubcommand::Nextest(app) = app.subcommand;
+            app
+        })
+    }
+
+    fn profile(args: &[&str]) -> Option<String> {
+        let app = parse(args).expect("arguments are valid");
+        match app.command {
+            Command::List { profile, .. }
+            | Command::Run { profile, .. }
+            | Command::Replay { profile, .. }
+            | Command::Store { profile, .. }
+            | Command::ShowConfig { profile } => profile,
+            other => panic!("command {:?} doesn't take a profile", other),
+        }
+    }
+
+    #[test]
+    fn profile_from_env() {
+        let commands: &[&[&str]] = &[
+            &["list"],
+            &["run"],
+            &["replay", "recording"],
+            &["store", "list"],
+            &["show-config"],
+        ];
+
+        // NEXTEST_PROFILE is read by clap, which takes it from the process environment. Rather
+        // than setting it for every test in the process, check that each command reads it.
+        let app = CargoNextestApp::command();
+        let nextest = app
+            .find_subcommand("nextest")
+            .expect("nextest subcommand exists");
+        for command in commands {
+            let subcommand = nextest
+                .find_subcommand(command[0])
+                .expect("subcommand exists");
+            let arg = subcommand
+                .get_arguments()
+                .find(|arg| arg.get_id() == "profile")
+                .unwrap_or_else(|| panic!("{:?} takes a profile", command));
+            assert_eq!(
+                arg.get_env(),
+                Some(OsStr::new("NEXTEST_PROFILE")),
+                "NEXTEST_PROFILE is used for {:?}",
+                command
+            );
+
+            // -P comes right after the command, since `store` takes a subcommand of its own.
+            let mut args = command.to_vec();
+            args.splice(1..1, ["-P", "local"]);
+            assert_eq!(
+                profile(&args).as_deref(),
+                Some("local"),
+                "-P is used for {:?}",
+                command
+            );
+        }
+
+        // An unknown profile is reported with its name, wherever it came from.
+        let name = profile(&["run", "-P", "nonexistent"]).expect("profile is set");
+        let config = NextestConfig::default_config("/fake/dir");
+        let err = config.profile(&name).expect_err("profile doesn't exist");
+        assert!(
+            err.to_string().contains("nonexistent"),
+            "error names the profile: {}",
+            err
+        );
+    }
+
+    fn runner_opts(args: &[&str]) -> TestRunnerOpts {
+        match parse(args).expect("arguments are valid").command {
+            Command::Run { runner_opts, .. } => runner_opts,
+            other => panic!("expected run, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn wait_for_debugger_conflicts() {
+        match parse(&["run", "--wait-for-debugger"])
+            .expect("--wait-for-debugger is valid on its own")
+            .command
+        {
+            Command::Run {
+                wait_for_debugger, ..
+            } => assert!(wait_for_debugger),
+            other => panic!("expected run, found {:?}", other),
+        }
+
+        // Test output is passed through, and tests are run one at a time.
+        for args in &[
+            &["--failure-output", "final"][..],
+            &["--success-output", "never"],
+            &["--tui"],
+            &["-j", "2"],
+        ] {
+            let mut all_args = vec!["run", "--wait-for-debugger"];
+            all_args.extend_from_slice(args);
+            let err = parse(&all_args).expect_err("arguments conflict");
+            assert_eq!(
+                err.kind(),
+                clap::ErrorKind::ArgumentConflict,
+                "{:?} conflicts with --wait-for-debugger",
+                args
+            );
+        }
+    }
+
+    #[test]
+    fn test_threads_with_no_capture() {
+        let err = parse(&["run", "-j", "8", "--no-capture"])
+            .expect_err("--test-threads conflicts with --no-capture");
+        assert_eq!(err.kind(), clap::ErrorKind::ArgumentConflict);
+        parse(&["run", "-j", "8", "--wait-for-debugger"])
+            .expect_err("--test-threads conflicts with --wait-for-debugger");
+
+        let count = |n| Some(TestThreads::Count(NonZeroUsize::new(n).unwrap()));
+        let env_value = || Some(OsString::from("4"));
+        assert_eq!(
+            runner_opts(&["run"])
+                .test_threads_impl(false, env_value())
+                .unwrap(),
+            count(4),
+            "the environment variable is used"
+        );
+        assert_eq!(
+            runner_opts(&["run", "-j", "2"])
+                .test_threads_impl(false, env_value())
+                .unwrap(),
+            count(2),
+            "--test-threads overrides the environment variable"
+        );
+        assert_eq!(
+            runner_opts(&["run", "--no-capture"])
+                .test_threads_impl(true, env_value())
+                .unwrap(),
+            None,
+            "the environment variable is ignored with --no-capture"
+        );
+
+        let err = runner_opts(&["run"])
+            .test_threads_impl(false, Some("lots".into()))
+            .expect_err("invalid environment variable is rejected");
+        assert!(
+            err.to_string().contains("NEXTEST_TEST_THREADS"),
+            "error names the variable: {}",
+            err
+        );
+
+        assert_eq!(
+            runner_opts(&["run"])
+                .test_threads_impl(false, None)
+                .unwrap(),
+            None
+        );
+    }
+
+    fn reporter_opts(args: &[&str]) -> TestReporterOpts {
+        match parse(args).expect("arguments are valid").command {
+            Command::Run { reporter_opts, .. } => reporter_opts,
+            other => panic!("expected run, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn message_format_with_no_capture() {
+        let opts = reporter_opts(&["run", "--message-format", "json", "--no-capture"]);
+        let err = opts
+            .machine_reporter(true)
+            .err()
+            .expect("JSON events can't share stdout with test output");
+        assert!(
+            err.to_string().contains("--message-format-file"),
+            "error suggests writing to a file: {}",
+            err
+        );
+        assert!(
+            opts.machine_reporter(false)
+                .expect("JSON events can be written to stdout")
+                .is_some(),
+            "without test output on stdout, events are written there"
+        );
+
+        let path = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .expect("temp dir is valid UTF-8")
+            .join(format!(
+                "nextest-message-format-{}.json",
+                std::process::id()
+            ));
+        let opts = reporter_opts(&[
+            "run",
+            "--message-format",
+            "json",
+            "--message-format-file",
+            path.as_str(),
+            "--no-capture",
+        ]);
+        let reporter = opts
+            .machine_reporter(true)
+            .expect("events are written to a file");
+        assert!(reporter.is_some());
+        assert!(path.exists(), "message format file was created");
+        drop(reporter);
+        std::fs::remove_file(&path).expect("message format file can be removed");
+
+        let opts = reporter_opts(&["run", "--message-format-file", path.as_str()]);
+        assert!(
+            opts.machine_reporter(false).is_err(),
+            "a file without a machine-readable format is rejected"
+        );
+    }
+
+    #[test]
+    fn required_version_checked_before_config_is_parsed() {
+        let dir = Utf8PathBuf::from_path_buf(std::env::temp_dir())
+            .expect("temp dir is valid UTF-8")
+            .join(format!("nextest-required-version-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).expect("created temp dir");
+        let config_file = dir.join("nextest.toml");
+        // A setting that this version of nextest rejects, as a config written for a newer version
+        // might contain.
+        std::fs::write(
+            &config_file,
+            r#"
+            nextest-version = { required = ">=1000.0.0" }
+
+            [profile.default]
+            slow-timeout = "not a duration"
+        "#,
+        )
+        .expect("wrote config");
+
+        let opts = ConfigOpts {
+            config_file: Some(config_file),
+            tool_config_files: vec![],
+        };
+        let err = opts
+            .read_config(&dir)
+            .expect_err("required version isn't met");
+        std::fs::remove_dir_all(&dir).expect("temp dir can be removed");
+        assert!(
+            matches!(err, ExpectedError::RequiredVersionNotMet { .. }),
+            "the required version is reported rather than the invalid setting: {:?}",
+            err
+        );
+    }
+}
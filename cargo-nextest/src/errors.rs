@@ -2,8 +2,9 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use nextest_metadata::NextestExitCode;
-use nextest_runner::errors::{ConfigParseError, ProfileNotFound};
+use nextest_runner::errors::{ConfigParseError, FilterExprParseError, ProfileNotFound};
 use owo_colors::{OwoColorize, Stream};
+use semver::{Version, VersionReq};
 use std::{
     error::{self, Error},
     fmt,
@@ -20,11 +21,30 @@ pub enum ExpectedError {
     ConfigParseError {
         err: ConfigParseError,
     },
+    FilterExprParseError {
+        err: FilterExprParseError,
+    },
+    ConfigCheckFailed {
+        errors: usize,
+    },
+    RequiredVersionNotMet {
+        required: VersionReq,
+        current: Version,
+    },
     BuildFailed {
         escaped_command: Vec<String>,
         exit_code: Option<i32>,
     },
     TestRunFailed,
+    TestRunInterrupted,
+    NoTestsRun,
+    NewFailures {
+        count: usize,
+    },
+    IncompleteShards {
+        missing: usize,
+        duplicates: usize,
+    },
 }
 
 impl ExpectedError {
@@ -40,6 +60,18 @@ impl ExpectedError {
         Self::ConfigParseError { err }
     }
 
+    pub(crate) fn filter_expr_parse_error(err: FilterExprParseError) -> Self {
+        Self::FilterExprParseError { err }
+    }
+
+    pub(crate) fn config_check_failed(errors: usize) -> Self {
+        Self::ConfigCheckFailed { errors }
+    }
+
+    pub(crate) fn required_version_not_met(required: VersionReq, current: Version) -> Self {
+        Self::RequiredVersionNotMet { required, current }
+    }
+
     pub(crate) fn build_failed(
         command: impl IntoIterator<Item = impl AsRef<str>>,
         exit_code: Option<i32>,
@@ -57,15 +89,40 @@ impl ExpectedError {
         Self::TestRunFailed
     }
 
+    pub(crate) fn test_run_interrupted() -> Self {
+        Self::TestRunInterrupted
+    }
+
+    pub(crate) fn no_tests_run() -> Self {
+        Self::NoTestsRun
+    }
+
+    pub(crate) fn new_failures(count: usize) -> Self {
+        Self::NewFailures { count }
+    }
+
+    pub(crate) fn incomplete_shards(missing: usize, duplicates: usize) -> Self {
+        Self::IncompleteShards {
+            missing,
+            duplicates,
+        }
+    }
+
     /// Returns the exit code for the process.
     pub fn process_exit_code(&self) -> i32 {
         match self {
             Self::CargoMetadataFailed => NextestExitCode::CARGO_METADATA_FAILED,
-            Self::ProfileNotFound { .. } | Self::ConfigParseError { .. } => {
-                NextestExitCode::SETUP_ERROR
-            }
+            Self::ProfileNotFound { .. }
+            | Self::ConfigParseError { .. }
+            | Self::FilterExprParseError { .. }
+            | Self::ConfigCheckFailed { .. } => NextestExitCode::SETUP_ERROR,
+            Self::RequiredVersionNotMet { .. } => NextestExitCode::REQUIRED_VERSION_NOT_MET,
             Self::BuildFailed { .. } => NextestExitCode::BUILD_FAILED,
-            Self::TestRunFailed => NextestExitCode::TEST_RUN_FAILED,
+            Self::TestRunFailed | Self::NewFailures { .. } | Self::IncompleteShards { .. } => {
+                NextestExitCode::TEST_RUN_FAILED
+            }
+            Self::TestRunInterrupted => NextestExitCode::TEST_RUN_INTERRUPTED,
+            Self::NoTestsRun => NextestExitCode::NO_TESTS_RUN,
         }
     }
 
@@ -84,6 +141,28 @@ impl ExpectedError {
                 log::error!("{}", err);
                 err.source()
             }
+            Self::FilterExprParseError { err } => {
+                log::error!("{}", err);
+                err.source()
+            }
+            Self::ConfigCheckFailed { errors } => {
+                log::error!(
+                    "found {} {} in the config",
+                    errors,
+                    if *errors == 1 { "error" } else { "errors" }
+                );
+                None
+            }
+            Self::RequiredVersionNotMet { required, current } => {
+                log::error!(
+                    "this repository requires nextest version {}, but the current version is {} \
+                     (update nextest with {})",
+                    required.if_supports_color(Stream::Stderr, |x| x.bold()),
+                    current.if_supports_color(Stream::Stderr, |x| x.bold()),
+                    "cargo install cargo-nextest".if_supports_color(Stream::Stderr, |x| x.bold()),
+                );
+                None
+            }
             Self::BuildFailed {
                 escaped_command,
                 exit_code,
@@ -112,6 +191,36 @@ impl ExpectedError {
                 log::error!("test run failed");
                 None
             }
+            Self::TestRunInterrupted => {
+                log::error!("test run interrupted");
+                None
+            }
+            Self::NoTestsRun => {
+                log::error!(
+                    "no tests to run (use {} to treat this as a success)",
+                    "--no-tests=pass".if_supports_color(Stream::Stderr, |x| x.bold())
+                );
+                None
+            }
+            Self::NewFailures { count } => {
+                log::error!(
+                    "{} {} failed in the new run but not in the old one",
+                    count,
+                    if *count == 1 { "test" } else { "tests" }
+                );
+                None
+            }
+            Self::IncompleteShards {
+                missing,
+                duplicates,
+            } => {
+                log::error!(
+                    "shards don't cover every test exactly once ({} missing, {} duplicated)",
+                    missing,
+                    duplicates
+                );
+                None
+            }
         };
 
         while let Some(err) = next_error {
@@ -128,8 +237,15 @@ impl fmt::Display for ExpectedError {
             Self::CargoMetadataFailed => writeln!(f, "cargo metadata failed"),
             Self::ProfileNotFound { .. } => writeln!(f, "profile not found"),
             Self::ConfigParseError { .. } => writeln!(f, "config read error"),
+            Self::FilterExprParseError { .. } => writeln!(f, "filter expression parse error"),
+            Self::ConfigCheckFailed { .. } => writeln!(f, "config check failed"),
+            Self::RequiredVersionNotMet { .. } => writeln!(f, "required version not met"),
             Self::BuildFailed { .. } => writeln!(f, "build failed"),
             Self::TestRunFailed => writeln!(f, "test run failed"),
+            Self::TestRunInterrupted => writeln!(f, "test run interrupted"),
+            Self::NoTestsRun => writeln!(f, "no tests to run"),
+            Self::NewFailures { .. } => writeln!(f, "new failures"),
+            Self::IncompleteShards { .. } => writeln!(f, "incomplete shards"),
         }
     }
 }
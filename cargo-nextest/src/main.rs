@@ -4,8 +4,17 @@
 use cargo_nextest::{CargoNextestApp, ExpectedError};
 use clap::Parser;
 use color_eyre::Result;
+use nextest_runner::double_spawn;
+use std::ffi::OsStr;
 
 fn main() -> Result<()> {
+    // Test processes may be started through this binary: handle that before doing anything else,
+    // since it happens once for every test.
+    let mut args = std::env::args_os();
+    if args.nth(1).as_deref() == Some(OsStr::new(double_spawn::SUBCOMMAND_NAME)) {
+        double_spawn::exec(args);
+    }
+
     color_eyre::install()?;
     let _ = enable_ansi_support::enable_ansi_support();
 
@@ -15,7 +15,11 @@ pub(crate) struct OutputOpts {
     /// Verbose output
     #[clap(long, short, global = true)]
     pub(crate) verbose: bool,
-    // TODO: quiet?
+
+    /// Quiet output: only print a character for each test, then a summary
+    #[clap(long, short, global = true, conflicts_with = "verbose")]
+    pub(crate) quiet: bool,
+
     /// Produce color output: auto, always, never
     #[clap(
         long,
@@ -30,11 +34,19 @@ pub(crate) struct OutputOpts {
 
 impl OutputOpts {
     pub(crate) fn init(self) -> OutputContext {
-        let OutputOpts { verbose, color } = self;
+        let OutputOpts {
+            verbose,
+            quiet,
+            color,
+        } = self;
 
         color.init();
 
-        OutputContext { verbose, color }
+        OutputContext {
+            verbose,
+            quiet,
+            color,
+        }
     }
 }
 
@@ -42,6 +54,7 @@ impl OutputOpts {
 #[must_use]
 pub(crate) struct OutputContext {
     pub(crate) verbose: bool,
+    pub(crate) quiet: bool,
     pub(crate) color: Color,
 }
 
@@ -0,0 +1,54 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tests for test processes started through the hidden double-spawn subcommand.
+
+#![cfg(unix)]
+
+use nextest_runner::double_spawn::SUBCOMMAND_NAME;
+use std::process::Command;
+
+#[test]
+fn pause_stops_before_test() {
+    let marker = std::env::temp_dir().join(format!("nextest-double-spawn-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cargo-nextest"))
+        .args([SUBCOMMAND_NAME, "--pause", "--", "sh", "-c"])
+        .arg(format!("touch '{}'; exit 7", marker.display()))
+        .spawn()
+        .expect("started double-spawn process");
+    let pid = child.id() as libc::pid_t;
+
+    let mut status = 0;
+    // WUNTRACED returns once the process stops, without collecting its exit status.
+    // SAFETY: status is a valid c_int for waitpid to write to.
+    let ret = unsafe { libc::waitpid(pid, &mut status, libc::WUNTRACED) };
+    assert_eq!(
+        ret,
+        pid,
+        "waitpid failed: {}",
+        std::io::Error::last_os_error()
+    );
+    assert!(
+        libc::WIFSTOPPED(status) && libc::WSTOPSIG(status) == libc::SIGSTOP,
+        "process stopped itself (wait status {:#x})",
+        status
+    );
+    assert!(!marker.exists(), "test hasn't started while stopped");
+
+    // SAFETY: kill has no memory safety requirements.
+    assert_eq!(
+        unsafe { libc::kill(pid, libc::SIGCONT) },
+        0,
+        "resumed process"
+    );
+    let status = child.wait().expect("waited for process");
+    assert_eq!(
+        status.code(),
+        Some(7),
+        "test ran to completion once resumed"
+    );
+    assert!(marker.exists(), "test ran once resumed");
+    let _ = std::fs::remove_file(&marker);
+}
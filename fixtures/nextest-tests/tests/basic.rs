@@ -17,9 +17,18 @@ fn test_failure_error() -> Result<(), String> {
 fn nextest_attempt() -> usize {
     static NEXTEST_ATTEMPT_ENV: &str = "__NEXTEST_ATTEMPT";
     match env::var(NEXTEST_ATTEMPT_ENV) {
-        Ok(var) => var
-            .parse()
-            .expect("__NEXTEST_ATTEMPT should be a positive integer"),
+        Ok(var) => {
+            let attempt = var
+                .parse()
+                .expect("__NEXTEST_ATTEMPT should be a positive integer");
+            // Nextest also sets the documented variable, to the same value.
+            let documented: usize = env::var("NEXTEST_ATTEMPT")
+                .expect("NEXTEST_ATTEMPT should be set along with __NEXTEST_ATTEMPT")
+                .parse()
+                .expect("NEXTEST_ATTEMPT should be a positive integer");
+            assert_eq!(documented, attempt, "NEXTEST_ATTEMPT matches __NEXTEST_ATTEMPT");
+            attempt
+        }
         Err(_) => 1,
     }
 }
@@ -21,6 +21,12 @@ pub enum CommandError {
 
     /// Error parsing JSON output.
     Json(serde_json::Error),
+
+    /// Error parsing a binary list.
+    BinaryList(ParseBinaryListError),
+
+    /// Error parsing a test list.
+    TestList(ParseTestListSummaryError),
 }
 
 impl fmt::Display for CommandError {
@@ -42,6 +48,12 @@ impl fmt::Display for CommandError {
             Self::Json(_) => {
                 write!(f, "parsing `cargo nextest` JSON output failed")
             }
+            Self::BinaryList(_) => {
+                write!(f, "parsing `cargo nextest` binary list failed")
+            }
+            Self::TestList(_) => {
+                write!(f, "parsing `cargo nextest` test list failed")
+            }
         }
     }
 }
@@ -52,6 +64,96 @@ impl error::Error for CommandError {
             Self::Exec(err) => Some(err),
             Self::CommandFailed { .. } => None,
             Self::Json(err) => Some(err),
+            Self::BinaryList(err) => Some(err),
+            Self::TestList(err) => Some(err),
+        }
+    }
+}
+
+/// An error that occurs while parsing a [`BinaryListSummary`](crate::BinaryListSummary).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ParseBinaryListError {
+    /// The binary list is not valid JSON, or doesn't match the expected structure.
+    Json(serde_json::Error),
+
+    /// The binary list was written in a format version newer than this version of nextest-metadata
+    /// supports.
+    UnsupportedVersion {
+        /// The format version of the binary list.
+        format_version: u32,
+
+        /// The newest format version supported.
+        max_supported: u32,
+    },
+}
+
+impl fmt::Display for ParseBinaryListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Json(_) => write!(f, "error parsing binary list JSON"),
+            Self::UnsupportedVersion {
+                format_version,
+                max_supported,
+            } => write!(
+                f,
+                "binary list has format version {}, but only versions up to {} are supported \
+                 (try upgrading nextest)",
+                format_version, max_supported
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseBinaryListError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Json(err) => Some(err),
+            Self::UnsupportedVersion { .. } => None,
+        }
+    }
+}
+
+/// An error that occurs while parsing a [`TestListSummary`](crate::TestListSummary).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ParseTestListSummaryError {
+    /// The test list is not valid JSON, or doesn't match the expected structure.
+    Json(serde_json::Error),
+
+    /// The test list was written in a format version newer than this version of nextest-metadata
+    /// supports.
+    UnsupportedVersion {
+        /// The format version of the test list.
+        format_version: u32,
+
+        /// The newest format version supported.
+        max_supported: u32,
+    },
+}
+
+impl fmt::Display for ParseTestListSummaryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Json(_) => write!(f, "error parsing test list JSON"),
+            Self::UnsupportedVersion {
+                format_version,
+                max_supported,
+            } => write!(
+                f,
+                "test list has format version {}, but only versions up to {} are supported \
+                 (try upgrading nextest)",
+                format_version, max_supported
+            ),
+        }
+    }
+}
+
+impl error::Error for ParseTestListSummaryError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Json(err) => Some(err),
+            Self::UnsupportedVersion { .. } => None,
         }
     }
 }
@@ -10,6 +10,9 @@
 pub enum NextestExitCode {}
 
 impl NextestExitCode {
+    /// The test run was canceled because nextest received a signal, e.g. through Ctrl-C.
+    pub const TEST_RUN_INTERRUPTED: i32 = 103;
+
     /// Running `cargo metadata` produced an error.
     pub const CARGO_METADATA_FAILED: i32 = 102;
 
@@ -21,4 +24,12 @@ impl NextestExitCode {
 
     /// A user issue happened while setting up a nextest invocation.
     pub const SETUP_ERROR: i32 = 96;
+
+    /// The config requires a newer version of nextest than the one running, through
+    /// `nextest-version`.
+    pub const REQUIRED_VERSION_NOT_MET: i32 = 92;
+
+    /// No tests matched the filters passed in, and the profile is configured to treat this as an
+    /// error through `no-tests = "fail"`.
+    pub const NO_TESTS_RUN: i32 = 4;
 }
@@ -0,0 +1,47 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use serde::{Deserialize, Serialize};
+
+/// Root element for a serializable list of flaky tests, generated by `cargo nextest run`.
+///
+/// A test is flaky if it failed at least once, then passed on a retry. At the end of each run,
+/// nextest writes this list to `flaky-tests.json` within the profile's store directory (by default,
+/// `target/nextest/<profile>/flaky-tests.json`).
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct FlakyTestsSummary {
+    /// The flaky tests in this run, sorted by binary ID and then by test name.
+    pub flaky_tests: Vec<FlakyTestSummary>,
+}
+
+impl FlakyTestsSummary {
+    /// The name of the file the flaky test list is written to, within the profile's store
+    /// directory.
+    pub const FILE_NAME: &'static str = "flaky-tests.json";
+
+    /// Parse JSON output for a flaky test list generated by nextest.
+    pub fn parse_json(json: impl AsRef<str>) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json.as_ref())
+    }
+}
+
+/// A single flaky test.
+///
+/// Part of a [`FlakyTestsSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct FlakyTestSummary {
+    /// The unique identifier of the binary the test is in.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub test_name: String,
+
+    /// The attempt on which the test passed. Always greater than 1.
+    pub passed_on_attempt: usize,
+
+    /// The total number of attempts the test was allowed, equal to `1 + retries`.
+    pub total_attempts: usize,
+}
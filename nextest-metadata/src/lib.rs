@@ -6,6 +6,10 @@
 //!
 //! Implemented so far:
 //! * ✅ Listing tests with [`TestListSummary`]
+//! * ✅ Lists of built test binaries with [`BinaryListSummary`]
+//! * ✅ Lists of flaky tests with [`FlakyTestsSummary`]
+//! * ✅ Results of test runs with [`RunSummary`]
+//! * ✅ Differences between test runs with [`RunDiffSummary`]
 //! * ✅ Semantic exit codes with [`NextestExitCode`]
 //!
 //! # Examples
@@ -35,8 +39,12 @@
 
 mod errors;
 mod exit_codes;
+mod flaky;
+mod run_summary;
 mod test_list;
 
 pub use errors::*;
 pub use exit_codes::*;
+pub use flaky::*;
+pub use run_summary::*;
 pub use test_list::*;
@@ -0,0 +1,344 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+use crate::MismatchReason;
+use camino::Utf8PathBuf;
+use serde::{Deserialize, Serialize};
+
+/// Root element for a serializable summary of a test run.
+///
+/// This contains the final result of every test in the run, along with statistics for the run as a
+/// whole. Durations are in seconds.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct RunSummary {
+    /// The time the run took, in seconds.
+    pub elapsed_secs: f64,
+
+    /// Statistics for the run.
+    pub run_stats: RunStatsSummary,
+
+    /// The tests in this run, sorted by binary ID and then by test name.
+    ///
+    /// With `--repeat`, each run of a test is listed separately.
+    pub tests: Vec<TestRunSummary>,
+}
+
+impl RunSummary {
+    /// The name of the file the summary of the last run is written to, within the profile's store
+    /// directory.
+    pub const FILE_NAME: &'static str = "run-summary.json";
+
+    /// Parse JSON output for a run summary generated by nextest.
+    pub fn parse_json(json: impl AsRef<str>) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json.as_ref())
+    }
+}
+
+/// Serializable statistics for a test run.
+///
+/// Part of a [`RunSummary`].
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct RunStatsSummary {
+    /// The total number of tests that were expected to be run at the beginning.
+    ///
+    /// If the test run is canceled, this will be more than `final_run_count`.
+    pub initial_run_count: usize,
+
+    /// The total number of tests that were actually run.
+    pub final_run_count: usize,
+
+    /// The number of tests that passed. Includes `flaky`.
+    pub passed: usize,
+
+    /// The number of tests that passed on retry.
+    pub flaky: usize,
+
+    /// The number of tests that failed.
+    pub failed: usize,
+
+    /// The number of tests that encountered an execution failure.
+    pub exec_failed: usize,
+
+    /// The number of tests that timed out.
+    pub timed_out: usize,
+
+    /// The number of tests that were killed after exceeding a resource limit.
+    pub resource_limited: usize,
+
+    /// The number of quarantined tests that failed. These aren't included in `failed`,
+    /// `exec_failed`, `timed_out` or `resource_limited`.
+    #[serde(default)]
+    pub quarantined: usize,
+
+    /// The number of tests that were skipped.
+    pub skipped: usize,
+
+    /// The number of skipped tests that don't match the profile's default filter. These are
+    /// included in `skipped`.
+    #[serde(default)]
+    pub skipped_by_default_filter: usize,
+
+    /// The number of skipped tests that are excluded through the configuration. These are
+    /// included in `skipped`.
+    #[serde(default)]
+    pub excluded: usize,
+
+    /// The number of tests that weren't run because the run was canceled.
+    pub canceled: usize,
+
+    /// The number of setup or teardown scripts that failed.
+    pub scripts_failed: usize,
+}
+
+impl RunStatsSummary {
+    /// Returns true if this run is considered a success.
+    ///
+    /// This matches the exit status nextest uses for the run: a run fails if it was canceled, if
+    /// any tests failed, or if any setup or teardown scripts failed. Failures of quarantined tests
+    /// don't count.
+    pub fn is_success(&self) -> bool {
+        self.initial_run_count <= self.final_run_count
+            && self.failed + self.exec_failed + self.timed_out + self.resource_limited == 0
+            && self.scripts_failed == 0
+    }
+}
+
+/// The result of a single test in a run.
+///
+/// Part of a [`RunSummary`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestRunSummary {
+    /// The unique identifier of the binary the test is in.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub test_name: String,
+
+    /// The final outcome of the test.
+    pub outcome: TestOutcome,
+
+    /// The reason the test was skipped, if it was.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub skip_reason: Option<MismatchReason>,
+
+    /// Each attempt at running the test, in order. Empty if the test wasn't run.
+    #[serde(default)]
+    pub attempts: Vec<AttemptSummary>,
+}
+
+/// The final outcome of a test in a run.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum TestOutcome {
+    /// The test passed on its first attempt.
+    Passed,
+
+    /// The test failed at least once, then passed on a retry.
+    Flaky,
+
+    /// The test didn't pass on any attempt. The result of the last attempt describes how it failed.
+    Failed,
+
+    /// The test was skipped because it didn't match the filters for the run.
+    Skipped,
+
+    /// The test wasn't run because the run was canceled.
+    Canceled,
+}
+
+/// A single attempt at running a test.
+///
+/// Part of a [`TestRunSummary`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AttemptSummary {
+    /// The attempt number. In the range `[1, total_attempts]`.
+    pub attempt: usize,
+
+    /// The total number of attempts the test was allowed, equal to `1 + retries`.
+    pub total_attempts: usize,
+
+    /// The result of this attempt.
+    pub result: ExecutionResultSummary,
+
+    /// The time this attempt took, in seconds.
+    pub exec_time_secs: f64,
+
+    /// The path to the core dump written out when this attempt crashed, if it was collected.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub core_dump: Option<Utf8PathBuf>,
+
+    /// The path to the output directory of this attempt, if it was retained.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_dir: Option<Utf8PathBuf>,
+}
+
+/// The result of a single attempt at running a test.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum ExecutionResultSummary {
+    /// The test passed.
+    Pass,
+
+    /// The test failed.
+    Fail,
+
+    /// An error occurred while executing the test.
+    ExecFail,
+
+    /// The test was terminated after exceeding its timeout.
+    Timeout,
+
+    /// The test was killed after exceeding one of its resource limits.
+    ResourceLimit,
+}
+
+/// Root element for a serializable summary of the differences between two test runs.
+///
+/// Each list is sorted by binary ID and then by test name.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct RunDiffSummary {
+    /// Tests that failed in the new run, but didn't fail in the old run.
+    ///
+    /// This includes tests that weren't run at all in the old run.
+    pub newly_failing: Vec<OutcomeChangeSummary>,
+
+    /// Tests that failed in the old run, and passed on their first attempt in the new run.
+    pub newly_passing: Vec<OutcomeChangeSummary>,
+
+    /// Tests that were flaky in the new run, but not in the old run.
+    pub newly_flaky: Vec<OutcomeChangeSummary>,
+
+    /// Tests that passed in both runs, but took noticeably longer in the new run.
+    pub duration_regressions: Vec<DurationRegressionSummary>,
+}
+
+impl RunDiffSummary {
+    /// Parse JSON output for a run diff generated by nextest.
+    pub fn parse_json(json: impl AsRef<str>) -> Result<Self, serde_json::Error> {
+        serde_json::from_str(json.as_ref())
+    }
+
+    /// Returns true if there are no differences between the two runs.
+    pub fn is_empty(&self) -> bool {
+        self.newly_failing.is_empty()
+            && self.newly_passing.is_empty()
+            && self.newly_flaky.is_empty()
+            && self.duration_regressions.is_empty()
+    }
+}
+
+/// A test whose outcome changed between two runs.
+///
+/// Part of a [`RunDiffSummary`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct OutcomeChangeSummary {
+    /// The unique identifier of the binary the test is in.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub test_name: String,
+
+    /// The outcome of the test in the old run, or `None` if the test wasn't in the old run.
+    pub old_outcome: Option<TestOutcome>,
+
+    /// The outcome of the test in the new run.
+    pub new_outcome: TestOutcome,
+}
+
+/// A test that took noticeably longer to pass in the new run than in the old one.
+///
+/// Part of a [`RunDiffSummary`].
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct DurationRegressionSummary {
+    /// The unique identifier of the binary the test is in.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub test_name: String,
+
+    /// The time the passing attempt took in the old run, in seconds.
+    ///
+    /// With `--repeat`, this is the mean across all passing runs of the test.
+    pub old_exec_time_secs: f64,
+
+    /// The time the passing attempt took in the new run, in seconds.
+    ///
+    /// With `--repeat`, this is the mean across all passing runs of the test.
+    pub new_exec_time_secs: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_run_summary() {
+        let summary = RunSummary::parse_json(
+            r#"{
+                "elapsed-secs": 1.5,
+                "run-stats": {
+                    "initial-run-count": 3,
+                    "final-run-count": 2,
+                    "passed": 1,
+                    "flaky": 1,
+                    "failed": 1,
+                    "exec-failed": 0,
+                    "timed-out": 0,
+                    "resource-limited": 0,
+                    "skipped": 1,
+                    "canceled": 1,
+                    "scripts-failed": 0
+                },
+                "tests": [
+                    {
+                        "binary-id": "my-crate",
+                        "test-name": "tests::flaky",
+                        "outcome": "flaky",
+                        "attempts": [
+                            { "attempt": 1, "total-attempts": 2, "result": "timeout", "exec-time-secs": 1.0 },
+                            { "attempt": 2, "total-attempts": 2, "result": "pass", "exec-time-secs": 0.25 }
+                        ]
+                    },
+                    {
+                        "binary-id": "my-crate",
+                        "test-name": "tests::ignored",
+                        "outcome": "skipped",
+                        "skip-reason": "ignored"
+                    }
+                ]
+            }"#,
+        )
+        .expect("run summary parsed");
+
+        assert_eq!(summary.run_stats.flaky, 1);
+        let flaky = &summary.tests[0];
+        assert_eq!(flaky.outcome, TestOutcome::Flaky);
+        assert_eq!(flaky.attempts.len(), 2);
+        assert_eq!(flaky.attempts[0].result, ExecutionResultSummary::Timeout);
+        let skipped = &summary.tests[1];
+        assert_eq!(skipped.outcome, TestOutcome::Skipped);
+        assert_eq!(skipped.skip_reason, Some(MismatchReason::Ignored));
+        assert!(
+            skipped.attempts.is_empty(),
+            "skipped tests have no attempts"
+        );
+
+        let json = serde_json::to_string(&summary).expect("serialized");
+        assert_eq!(
+            RunSummary::parse_json(&json).expect("round trip works"),
+            summary
+        );
+    }
+}
@@ -5,7 +5,7 @@ use camino::{Utf8Path, Utf8PathBuf};
 use serde::{Deserialize, Serialize};
 use std::{collections::BTreeMap, fmt, path::PathBuf, process::Command};
 
-use crate::CommandError;
+use crate::{CommandError, ParseBinaryListError, ParseTestListSummaryError};
 
 /// Command builder for `cargo nextest list`.
 #[derive(Clone, Debug, Default)]
@@ -82,7 +82,27 @@ impl ListCommand {
     ///
     ///
     pub fn exec(&self) -> Result<TestListSummary, CommandError> {
+        let stdout = self.exec_stdout(self.cargo_command())?;
+
+        // Try parsing stdout.
+        TestListSummary::parse_json(String::from_utf8_lossy(&stdout))
+            .map_err(CommandError::TestList)
+    }
+
+    /// Executes `cargo nextest list --list-type binaries-only` and parses the output into a
+    /// [`BinaryListSummary`].
+    ///
+    /// Test binaries are built, but not queried for the tests they contain.
+    pub fn exec_binaries_only(&self) -> Result<BinaryListSummary, CommandError> {
         let mut command = self.cargo_command();
+        command.arg("--list-type=binaries-only");
+        let stdout = self.exec_stdout(command)?;
+
+        BinaryListSummary::parse_json(String::from_utf8_lossy(&stdout))
+            .map_err(CommandError::BinaryList)
+    }
+
+    fn exec_stdout(&self, mut command: Command) -> Result<Vec<u8>, CommandError> {
         let output = command.output().map_err(CommandError::Exec)?;
 
         if !output.status.success() {
@@ -92,16 +112,27 @@ impl ListCommand {
             return Err(CommandError::CommandFailed { exit_code, stderr });
         }
 
-        // Try parsing stdout.
-        serde_json::from_slice(&output.stdout).map_err(CommandError::Json)
+        Ok(output.stdout)
     }
 }
 
 /// Root element for a serializable list of tests generated by nextest.
-#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize, Serialize)]
+///
+/// A test list can be loaded back into nextest without the test binaries being present, for
+/// example to analyze the tests in it on another machine.
+///
+/// This format is versioned: see [`Self::FORMAT_VERSION`].
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
 #[serde(rename_all = "kebab-case")]
 #[non_exhaustive]
 pub struct TestListSummary {
+    /// The version of the format this test list is in.
+    ///
+    /// Test lists written before the format was versioned don't have this field, and are treated
+    /// as version 1.
+    #[serde(default = "initial_format_version")]
+    pub format_version: u32,
+
     /// Number of tests (including skipped and ignored) across all binaries.
     pub test_count: usize,
 
@@ -111,9 +142,43 @@ pub struct TestListSummary {
 }
 
 impl TestListSummary {
+    /// The version of the test list format written by this version of nextest-metadata.
+    ///
+    /// The version is bumped under the same rules as [`BinaryListSummary::FORMAT_VERSION`].
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Creates a new, empty test list in the current format version.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
     /// Parse JSON output from `cargo nextest list --format json`.
-    pub fn parse_json(json: impl AsRef<str>) -> Result<Self, serde_json::Error> {
-        serde_json::from_str(json.as_ref())
+    ///
+    /// Returns an error if the test list is in a newer format version than
+    /// [`Self::FORMAT_VERSION`].
+    pub fn parse_json(json: impl AsRef<str>) -> Result<Self, ParseTestListSummaryError> {
+        let json = json.as_ref();
+
+        let VersionProbe { format_version } =
+            serde_json::from_str(json).map_err(ParseTestListSummaryError::Json)?;
+        if format_version > Self::FORMAT_VERSION {
+            return Err(ParseTestListSummaryError::UnsupportedVersion {
+                format_version,
+                max_supported: Self::FORMAT_VERSION,
+            });
+        }
+
+        serde_json::from_str(json).map_err(ParseTestListSummaryError::Json)
+    }
+}
+
+impl Default for TestListSummary {
+    fn default() -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            test_count: 0,
+            rust_suites: BTreeMap::new(),
+        }
     }
 }
 
@@ -140,10 +205,247 @@ pub struct RustTestSuiteSummary {
     /// The working directory that tests within this package are run in.
     pub cwd: Utf8PathBuf,
 
+    /// The platform this binary was built for.
+    #[serde(default)]
+    pub build_platform: BuildPlatform,
+
+    /// The test harness this binary was run with.
+    #[serde(default)]
+    pub harness: RustTestHarness,
+
     /// Test case names and other information about them.
     pub testcases: BTreeMap<String, RustTestCaseSummary>,
 }
 
+/// Root element for a serializable list of Rust test binaries generated by nextest.
+///
+/// Unlike [`TestListSummary`], this does not contain information about the tests within each
+/// binary. It is used to record the binaries built by Cargo, so that they can be run later without
+/// Cargo being present, and is produced by `cargo nextest list --list-type binaries-only`.
+///
+/// This format is versioned: see [`Self::FORMAT_VERSION`].
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub struct BinaryListSummary {
+    /// The version of the format this binary list is in.
+    ///
+    /// Binary lists written before the format was versioned don't have this field, and are treated
+    /// as version 1.
+    #[serde(default = "initial_format_version")]
+    pub format_version: u32,
+
+    /// A map of Rust test binaries, keyed by a unique identifier for each binary.
+    pub rust_binaries: BTreeMap<String, RustTestBinarySummary>,
+}
+
+impl BinaryListSummary {
+    /// The version of the binary list format written by this version of nextest-metadata.
+    ///
+    /// The format version is bumped whenever a change is made that older consumers can't
+    /// understand, for example if a field is removed or its meaning changes. Adding new fields
+    /// does not bump the version: consumers ignore fields they don't know about.
+    pub const FORMAT_VERSION: u32 = 1;
+
+    /// Creates a new, empty binary list in the current format version.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse JSON output for a binary list generated by nextest.
+    ///
+    /// Returns an error if the binary list is in a newer format version than
+    /// [`Self::FORMAT_VERSION`].
+    pub fn parse_json(json: impl AsRef<str>) -> Result<Self, ParseBinaryListError> {
+        let json = json.as_ref();
+
+        // Check the version first, since binary lists in newer formats may not deserialize
+        // correctly.
+        let VersionProbe { format_version } =
+            serde_json::from_str(json).map_err(ParseBinaryListError::Json)?;
+        if format_version > Self::FORMAT_VERSION {
+            return Err(ParseBinaryListError::UnsupportedVersion {
+                format_version,
+                max_supported: Self::FORMAT_VERSION,
+            });
+        }
+
+        serde_json::from_str(json).map_err(ParseBinaryListError::Json)
+    }
+}
+
+impl Default for BinaryListSummary {
+    fn default() -> Self {
+        Self {
+            format_version: Self::FORMAT_VERSION,
+            rust_binaries: BTreeMap::new(),
+        }
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct VersionProbe {
+    #[serde(default = "initial_format_version")]
+    format_version: u32,
+}
+
+fn initial_format_version() -> u32 {
+    1
+}
+
+/// A serializable Rust test binary.
+///
+/// Part of a [`BinaryListSummary`].
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct RustTestBinarySummary {
+    /// The name of the test binary within the package.
+    pub binary_name: String,
+
+    /// The unique package ID assigned by Cargo to this test.
+    ///
+    /// This package ID can be used for lookups in `cargo metadata`.
+    pub package_id: String,
+
+    /// The path to the test binary executable.
+    pub binary_path: Utf8PathBuf,
+
+    /// The working directory that tests within this binary are run in.
+    pub cwd: Utf8PathBuf,
+
+    /// The platform this binary was built for.
+    #[serde(default)]
+    pub build_platform: BuildPlatform,
+
+    /// The test harness this binary was built with.
+    #[serde(default)]
+    pub harness: RustTestHarness,
+
+    /// The kind of build target this binary was built from.
+    ///
+    /// This is `None` for binary lists produced by older versions of nextest. In that case, the
+    /// kind can be looked up in `cargo metadata` through the package ID and binary name.
+    #[serde(default)]
+    pub kind: Option<RustTestBinaryKind>,
+
+    /// The features that must be enabled for this binary to be built, as specified with
+    /// `required-features` in `Cargo.toml`.
+    #[serde(default)]
+    pub required_features: Vec<String>,
+
+    /// The settings of the Cargo profile this binary was built with, or `None` if they aren't
+    /// known.
+    #[serde(default)]
+    pub profile: Option<BuildProfile>,
+}
+
+/// The kind of Cargo build target a test binary was built from.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum RustTestBinaryKind {
+    /// Unit tests for a library.
+    Lib,
+
+    /// An integration test, in the `tests` directory or defined with `[[test]]`.
+    Test,
+
+    /// A benchmark, in the `benches` directory or defined with `[[bench]]`.
+    Bench,
+
+    /// Unit tests for a binary.
+    Bin,
+
+    /// An example, in the `examples` directory or defined with `[[example]]`.
+    Example,
+
+    /// Unit tests for a procedural macro library.
+    ProcMacro,
+}
+
+impl RustTestBinaryKind {
+    /// Returns the string used to represent this kind, e.g. in `cargo metadata` output.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RustTestBinaryKind::Lib => "lib",
+            RustTestBinaryKind::Test => "test",
+            RustTestBinaryKind::Bench => "bench",
+            RustTestBinaryKind::Bin => "bin",
+            RustTestBinaryKind::Example => "example",
+            RustTestBinaryKind::ProcMacro => "proc-macro",
+        }
+    }
+}
+
+impl fmt::Display for RustTestBinaryKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// The settings of the Cargo profile a test binary was built with.
+///
+/// Cargo doesn't report the name of the profile, only the settings that matter for the binary.
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct BuildProfile {
+    /// The optimization level: 0-3, `s` or `z`.
+    pub opt_level: String,
+
+    /// The amount of debug info: 0 for none, 1 for limited and 2 for full.
+    pub debuginfo: Option<u32>,
+
+    /// Whether `cfg(debug_assertions)` is enabled.
+    pub debug_assertions: bool,
+
+    /// Whether overflow checks are enabled.
+    pub overflow_checks: bool,
+}
+
+/// The platform a test binary was built for.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum BuildPlatform {
+    /// The target platform, passed in with `--target` or the same as the host if not specified.
+    Target,
+
+    /// The host platform. Tests for procedural macros are built for the host, since procedural
+    /// macros are run by the compiler.
+    Host,
+}
+
+impl Default for BuildPlatform {
+    fn default() -> Self {
+        BuildPlatform::Target
+    }
+}
+
+/// The test harness used by a Rust test binary.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+#[non_exhaustive]
+pub enum RustTestHarness {
+    /// The standard libtest harness.
+    Libtest,
+
+    /// A custom harness, defined with `harness = false` in `Cargo.toml`.
+    ///
+    /// Custom harnesses are queried for their tests in the same way as libtest. If that fails, the
+    /// binary is run as a single opaque test.
+    Custom,
+
+    /// A binary that is run as a single test, without being queried for the tests it contains.
+    Opaque,
+}
+
+impl Default for RustTestHarness {
+    fn default() -> Self {
+        RustTestHarness::Libtest
+    }
+}
+
 /// Serializable information about an individual test case within a Rust test suite.
 ///
 /// Part of a [`RustTestSuiteSummary`].
@@ -193,8 +495,23 @@ pub enum MismatchReason {
     /// This test does not match the provided string filters.
     String,
 
+    /// This test does not match the provided filter expressions.
+    Expression,
+
     /// This test is in a different partition.
     Partition,
+
+    /// Only tests that failed in the last run are being run, and this test did not fail.
+    NotFailed,
+
+    /// This test is excluded by the default filter of the profile being run.
+    DefaultFilter,
+
+    /// This test is in a binary that was excluded by package, binary kind or binary ID.
+    Binary,
+
+    /// This test is excluded through an `[[exclude]]` section in the configuration.
+    Excluded,
 }
 
 impl fmt::Display for MismatchReason {
@@ -202,7 +519,76 @@ impl fmt::Display for MismatchReason {
         match self {
             MismatchReason::Ignored => write!(f, "does not match the run-ignored option"),
             MismatchReason::String => write!(f, "does not match the provided string filters"),
+            MismatchReason::Expression => {
+                write!(f, "does not match the provided filter expressions")
+            }
             MismatchReason::Partition => write!(f, "is in a different partition"),
+            MismatchReason::NotFailed => write!(f, "did not fail in the last run"),
+            MismatchReason::DefaultFilter => write!(f, "is excluded by the default filter"),
+            MismatchReason::Binary => write!(f, "is in a binary that was filtered out"),
+            MismatchReason::Excluded => write!(f, "is excluded by the configuration"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_list_format_version() {
+        let test_list = TestListSummary::parse_json(r#"{ "test-count": 0, "rust-suites": {} }"#)
+            .expect("unversioned test list parsed");
+        assert_eq!(test_list, TestListSummary::new());
+
+        match TestListSummary::parse_json(r#"{ "format-version": 2, "suites": [] }"#) {
+            Err(ParseTestListSummaryError::UnsupportedVersion { format_version, .. }) => {
+                assert_eq!(format_version, 2);
+            }
+            other => panic!("expected unsupported version error, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn binary_list_format_version() {
+        // Binary lists from before the format was versioned are version 1.
+        let binary_list = BinaryListSummary::parse_json(
+            r#"{
+                "rust-binaries": {
+                    "my-crate": {
+                        "binary-name": "my-crate",
+                        "package-id": "my-crate 0.1.0 (path+file:///my-crate)",
+                        "binary-path": "/fake/my-crate",
+                        "cwd": "/fake"
+                    }
+                }
+            }"#,
+        )
+        .expect("unversioned binary list parsed");
+        assert_eq!(binary_list.format_version, 1);
+        let summary = &binary_list.rust_binaries["my-crate"];
+        assert_eq!(summary.build_platform, BuildPlatform::Target);
+        assert_eq!(summary.harness, RustTestHarness::Libtest);
+        assert_eq!(summary.kind, None);
+        assert!(summary.required_features.is_empty());
+        assert_eq!(summary.profile, None);
+
+        let json = serde_json::to_string(&BinaryListSummary::new()).expect("serialized");
+        assert_eq!(
+            BinaryListSummary::parse_json(&json).expect("round trip works"),
+            BinaryListSummary::new()
+        );
+
+        // Newer versions are rejected, even if they don't match the current structure.
+        match BinaryListSummary::parse_json(r#"{ "format-version": 2, "binaries": [] }"#) {
+            Err(ParseBinaryListError::UnsupportedVersion {
+                format_version,
+                max_supported,
+            }) => {
+                assert_eq!(format_version, 2);
+                assert_eq!(max_supported, BinaryListSummary::FORMAT_VERSION);
+            }
+            other => panic!("expected unsupported version error, found {:?}", other),
         }
     }
 }
@@ -0,0 +1,198 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Selecting the packages affected by a set of changed files.
+//!
+//! Given the files changed by a commit or pull request, [`AffectedPackages::compute`] works out
+//! which local packages contain those files, and adds every package that depends on them, directly
+//! or indirectly. Only the tests in those packages can be affected by the change.
+
+use camino::{Utf8Component, Utf8Path, Utf8PathBuf};
+use guppy::{
+    graph::{DependencyDirection, PackageGraph},
+    PackageId,
+};
+use std::collections::BTreeSet;
+
+/// The packages affected by a set of changed files.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum AffectedPackages {
+    /// A changed file isn't in any local package, or is a workspace-level file, so any package may
+    /// be affected.
+    ///
+    /// Workspace-level files, like the workspace's `Cargo.toml` and `Cargo.lock`, a
+    /// `rust-toolchain` file, or anything in `.cargo` or `.config`, can affect every package. They
+    /// affect everything even if the workspace root is also the directory of a package.
+    All {
+        /// The first changed file that isn't in a local package, or is a workspace-level file.
+        file: Utf8PathBuf,
+    },
+
+    /// Only these packages are affected: the packages containing the changed files, along with
+    /// their transitive reverse dependencies.
+    Packages(BTreeSet<PackageId>),
+}
+
+impl AffectedPackages {
+    /// Works out the packages affected by the given changed files.
+    ///
+    /// Relative paths are relative to the workspace root, which is what `git diff --name-only`
+    /// prints if the workspace is at the root of the repository. A file belongs to the innermost
+    /// local package whose directory it's in; local packages are those in the workspace, along with
+    /// path dependencies outside of it. Workspace-level files affect every package. Reverse
+    /// dependencies include packages that only depend on
+    /// an affected package as a dev-dependency, since those are used by tests.
+    pub fn compute(
+        graph: &PackageGraph,
+        changed_files: impl IntoIterator<Item = impl AsRef<Utf8Path>>,
+    ) -> Self {
+        let workspace_root = graph.workspace().root();
+        let local_packages: Vec<_> = graph
+            .packages()
+            .filter(|package| package.source().is_local())
+            .filter_map(|package| Some((package.manifest_path().parent()?, package.id())))
+            .collect();
+
+        let mut changed_packages = BTreeSet::new();
+        for file in changed_files {
+            let file = normalize(&workspace_root.join(file.as_ref()));
+            if is_workspace_file(workspace_root, &file) {
+                return AffectedPackages::All { file };
+            }
+            let package = local_packages
+                .iter()
+                .filter(|(dir, _)| file.starts_with(dir))
+                .max_by_key(|(dir, _)| dir.components().count());
+            match package {
+                Some((_, package_id)) => {
+                    changed_packages.insert(*package_id);
+                }
+                None => return AffectedPackages::All { file },
+            }
+        }
+
+        let affected = graph
+            .query_reverse(changed_packages)
+            .expect("package IDs are from the graph")
+            .resolve();
+        AffectedPackages::Packages(
+            affected
+                .package_ids(DependencyDirection::Forward)
+                .cloned()
+                .collect(),
+        )
+    }
+}
+
+/// Files in the workspace root that can affect every package.
+static WORKSPACE_FILES: &[&str] = &[
+    "Cargo.toml",
+    "Cargo.lock",
+    "rust-toolchain",
+    "rust-toolchain.toml",
+];
+
+/// Directories in the workspace root whose files can affect every package.
+static WORKSPACE_DIRS: &[&str] = &[".cargo", ".config"];
+
+fn is_workspace_file(workspace_root: &Utf8Path, file: &Utf8Path) -> bool {
+    let relative = match file.strip_prefix(normalize(workspace_root)) {
+        Ok(relative) => relative,
+        Err(_) => return false,
+    };
+    let mut components = relative.components();
+    match (components.next(), components.next()) {
+        (Some(Utf8Component::Normal(name)), None) => WORKSPACE_FILES.contains(&name),
+        (Some(Utf8Component::Normal(name)), Some(_)) => WORKSPACE_DIRS.contains(&name),
+        _ => false,
+    }
+}
+
+/// Resolves `.` and `..` components without touching the file system, since changed files may
+/// have been deleted.
+fn normalize(path: &Utf8Path) -> Utf8PathBuf {
+    let mut normalized = Utf8PathBuf::new();
+    for component in path.components() {
+        match component {
+            Utf8Component::CurDir => {}
+            Utf8Component::ParentDir => {
+                normalized.pop();
+            }
+            component => normalized.push(component),
+        }
+    }
+    normalized
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_list::test_helpers::PACKAGE_GRAPH_FIXTURE;
+
+    fn names(graph: &PackageGraph, affected: &AffectedPackages) -> Vec<String> {
+        match affected {
+            AffectedPackages::All { file } => panic!("expected packages, found all ({})", file),
+            AffectedPackages::Packages(ids) => ids
+                .iter()
+                .map(|id| graph.metadata(id).unwrap().name().to_owned())
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn affected_packages() {
+        let graph = &*PACKAGE_GRAPH_FIXTURE;
+
+        let affected = AffectedPackages::compute(graph, Vec::<Utf8PathBuf>::new());
+        assert!(names(graph, &affected).is_empty(), "no changes => nothing");
+
+        // metadata-helper is a path dependency outside the workspace, and metadata-base depends
+        // on it.
+        let affected = AffectedPackages::compute(graph, ["../metadata-helper/src/lib.rs"]);
+        let mut affected = names(graph, &affected);
+        affected.sort();
+        assert_eq!(affected, vec!["metadata-base", "metadata-helper"]);
+
+        let affected = AffectedPackages::compute(
+            graph,
+            ["/Users/fakeuser/local/testcrates/metadata/metadata-helper/Cargo.toml"],
+        );
+        assert_eq!(names(graph, &affected).len(), 2, "absolute paths work too");
+
+        let affected = AffectedPackages::compute(graph, ["src/lib.rs", "../ci/run.sh"]);
+        assert_eq!(
+            affected,
+            AffectedPackages::All {
+                file: "/Users/fakeuser/local/testcrates/metadata/ci/run.sh".into()
+            },
+            "a file outside any package affects everything"
+        );
+
+        // The workspace root is also the directory of metadata-base, but workspace-level files
+        // affect everything.
+        let root = Utf8Path::new("/Users/fakeuser/local/testcrates/metadata/metadata-base");
+        for file in &[
+            "Cargo.toml",
+            "Cargo.lock",
+            "rust-toolchain",
+            "rust-toolchain.toml",
+            ".cargo/config.toml",
+            ".config/nextest.toml",
+        ] {
+            let affected = AffectedPackages::compute(graph, vec!["src/lib.rs", file]);
+            assert_eq!(
+                affected,
+                AffectedPackages::All {
+                    file: root.join(file)
+                },
+                "{} affects everything",
+                file
+            );
+        }
+        assert_eq!(
+            AffectedPackages::compute(graph, ["src/Cargo.toml", "src/.config/a.rs"]),
+            AffectedPackages::compute(graph, ["src/lib.rs"]),
+            "only files in the workspace root itself are workspace-level"
+        );
+    }
+}
@@ -0,0 +1,291 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Archiving and reusing pre-built test binaries.
+//!
+//! An archive is a `.tar.zst` file containing:
+//! * the Cargo metadata for the workspace, so that the package graph can be reconstructed without
+//!   Cargo;
+//! * a [`BinaryListSummary`] describing each test binary;
+//! * the test binaries themselves, at their paths relative to the Cargo target directory.
+//!
+//! Archives are created with [`archive_to_file`], and extracted with [`ExtractedArchive::extract`].
+
+use crate::{
+    errors::{ArchiveCreateError, ArchiveExtractError, FromBinaryListError},
+    test_list::RustTestArtifact,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::graph::PackageGraph;
+use nextest_metadata::BinaryListSummary;
+use serde::Deserialize;
+use std::{convert::TryFrom, fs::File, io};
+use tempfile::TempDir;
+
+/// The path within an archive that the target directory is stored at.
+const TARGET_DIR_NAME: &str = "target";
+
+/// The path within an archive that Cargo metadata is stored at.
+const CARGO_METADATA_PATH: &str = "target/nextest/cargo-metadata.json";
+
+/// The path within an archive that the binary list is stored at.
+const BINARY_LIST_PATH: &str = "target/nextest/binaries-metadata.json";
+
+/// Archives the given test binaries to a `.tar.zst` file, along with the metadata required to run
+/// them on another machine.
+///
+/// `cargo_metadata` is the JSON output of `cargo metadata` that `graph` was built from.
+pub fn archive_to_file(
+    graph: &PackageGraph,
+    cargo_metadata: &str,
+    test_artifacts: &[RustTestArtifact<'_>],
+    archive_file: &Utf8Path,
+) -> Result<(), ArchiveCreateError> {
+    let target_dir = serde_json::from_str::<MetadataTargetDir>(cargo_metadata)
+        .map_err(ArchiveCreateError::CargoMetadata)?
+        .target_directory;
+    let workspace_root = graph.workspace().root();
+
+    // Binary paths are stored relative to the archive root, and working directories relative to
+    // the workspace root.
+    let mut binary_list = BinaryListSummary::default();
+    let mut binaries = Vec::with_capacity(test_artifacts.len());
+    for artifact in test_artifacts {
+        let rel_binary_path = artifact
+            .binary_path
+            .strip_prefix(&target_dir)
+            .map_err(|_| ArchiveCreateError::BinaryOutsideTargetDir {
+                binary_path: artifact.binary_path.clone(),
+                target_dir: target_dir.clone(),
+            })?;
+        let rel_cwd = artifact.cwd.strip_prefix(workspace_root).map_err(|_| {
+            ArchiveCreateError::CwdOutsideWorkspace {
+                cwd: artifact.cwd.clone(),
+                workspace_root: workspace_root.to_path_buf(),
+            }
+        })?;
+
+        let archive_path = Utf8Path::new(TARGET_DIR_NAME).join(rel_binary_path);
+        let mut summary = artifact.to_summary();
+        summary.binary_path = archive_path.clone();
+        summary.cwd = rel_cwd.to_path_buf();
+        binary_list
+            .rust_binaries
+            .insert(artifact.binary_id.clone(), summary);
+        binaries.push((artifact.binary_path.as_path(), archive_path));
+    }
+
+    let binary_list_json =
+        serde_json::to_vec_pretty(&binary_list).map_err(ArchiveCreateError::Json)?;
+
+    write_archive(archive_file, cargo_metadata, &binary_list_json, &binaries).map_err(|error| {
+        ArchiveCreateError::Write {
+            archive_file: archive_file.to_path_buf(),
+            error,
+        }
+    })
+}
+
+/// An archive that has been extracted to a directory.
+///
+/// If the archive was extracted to a temporary directory, the directory is removed when this is
+/// dropped.
+#[derive(Debug)]
+pub struct ExtractedArchive {
+    dir: Utf8PathBuf,
+    // Held to keep the temporary directory alive.
+    _tempdir: Option<TempDir>,
+    cargo_metadata: String,
+    binary_list: BinaryListSummary,
+}
+
+impl ExtractedArchive {
+    /// Extracts the given archive to `extract_to`, or to a temporary directory if not specified.
+    pub fn extract(
+        archive_file: &Utf8Path,
+        extract_to: Option<&Utf8Path>,
+    ) -> Result<Self, ArchiveExtractError> {
+        let (dir, tempdir) = match extract_to {
+            Some(dir) => (dir.to_path_buf(), None),
+            None => {
+                let tempdir = tempfile::Builder::new()
+                    .prefix("nextest-archive-")
+                    .tempdir()
+                    .map_err(ArchiveExtractError::TempDirCreate)?;
+                let dir = Utf8PathBuf::try_from(tempdir.path().to_path_buf()).map_err(|err| {
+                    ArchiveExtractError::TempDirCreate(io::Error::new(
+                        io::ErrorKind::InvalidData,
+                        err,
+                    ))
+                })?;
+                (dir, Some(tempdir))
+            }
+        };
+
+        unpack_archive(archive_file, &dir).map_err(|error| ArchiveExtractError::Read {
+            archive_file: archive_file.to_path_buf(),
+            error,
+        })?;
+
+        let cargo_metadata = read_metadata_file(&dir, CARGO_METADATA_PATH)?;
+        let binary_list =
+            BinaryListSummary::parse_json(read_metadata_file(&dir, BINARY_LIST_PATH)?)
+                .map_err(ArchiveExtractError::BinaryList)?;
+
+        Ok(Self {
+            dir,
+            _tempdir: tempdir,
+            cargo_metadata,
+            binary_list,
+        })
+    }
+
+    /// Returns the directory the archive was extracted to.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// Returns the Cargo metadata stored in the archive, as JSON.
+    ///
+    /// This can be used to construct a [`PackageGraph`] without running Cargo.
+    pub fn cargo_metadata(&self) -> &str {
+        &self.cargo_metadata
+    }
+
+    /// Returns the list of binaries stored in the archive.
+    ///
+    /// Binary paths in this list are relative to [`Self::dir`], and working directories are
+    /// relative to the workspace root.
+    pub fn binary_list(&self) -> &BinaryListSummary {
+        &self.binary_list
+    }
+
+    /// Returns the list of test binaries in the extracted archive.
+    ///
+    /// `workspace_root` is the location of the workspace on this machine, which may be different
+    /// from where the archive was created. Tests are run within this directory.
+    pub fn rust_test_artifacts<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        workspace_root: &Utf8Path,
+    ) -> Result<Vec<RustTestArtifact<'g>>, FromBinaryListError> {
+        let mut binary_list = self.binary_list.clone();
+        for summary in binary_list.rust_binaries.values_mut() {
+            summary.binary_path = self.dir.join(&summary.binary_path);
+            summary.cwd = workspace_root.join(&summary.cwd);
+        }
+        RustTestArtifact::from_binary_list(graph, &binary_list)
+    }
+}
+
+// ---
+// Helper methods
+// ---
+
+#[derive(Deserialize)]
+struct MetadataTargetDir {
+    target_directory: Utf8PathBuf,
+}
+
+fn write_archive(
+    archive_file: &Utf8Path,
+    cargo_metadata: &str,
+    binary_list_json: &[u8],
+    binaries: &[(&Utf8Path, Utf8PathBuf)],
+) -> io::Result<()> {
+    let file = File::create(archive_file)?;
+    // Level 0 means the zstd default.
+    let encoder = zstd::Encoder::new(file, 0)?;
+    let mut builder = tar::Builder::new(encoder);
+
+    append_data(&mut builder, CARGO_METADATA_PATH, cargo_metadata.as_bytes())?;
+    append_data(&mut builder, BINARY_LIST_PATH, binary_list_json)?;
+    for (binary_path, archive_path) in binaries {
+        builder.append_path_with_name(binary_path, archive_path)?;
+    }
+
+    let encoder = builder.into_inner()?;
+    encoder.finish()?;
+    Ok(())
+}
+
+fn append_data<W: io::Write>(
+    builder: &mut tar::Builder<W>,
+    path: &str,
+    data: &[u8],
+) -> io::Result<()> {
+    let mut header = tar::Header::new_gnu();
+    header.set_size(data.len() as u64);
+    header.set_mode(0o644);
+    header.set_cksum();
+    builder.append_data(&mut header, path, data)
+}
+
+fn unpack_archive(archive_file: &Utf8Path, dir: &Utf8Path) -> io::Result<()> {
+    let file = File::open(archive_file)?;
+    let decoder = zstd::Decoder::new(file)?;
+    std::fs::create_dir_all(dir)?;
+    tar::Archive::new(decoder).unpack(dir)
+}
+
+fn read_metadata_file(dir: &Utf8Path, path: &str) -> Result<String, ArchiveExtractError> {
+    let path = dir.join(path);
+    std::fs::read_to_string(&path)
+        .map_err(|error| ArchiveExtractError::MetadataRead { path, error })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nextest_metadata::{
+        BuildPlatform, RustTestBinaryKind, RustTestBinarySummary, RustTestHarness,
+    };
+
+    #[test]
+    fn archive_round_trip() {
+        let src = tempfile::tempdir().expect("created temp dir");
+        let src = Utf8Path::from_path(src.path()).expect("temp dir is valid UTF-8");
+        let binary_path = src.join("my-test-binary");
+        std::fs::write(&binary_path, b"not really a binary").expect("wrote binary");
+
+        let mut binary_list = BinaryListSummary::default();
+        binary_list.rust_binaries.insert(
+            "my-package::my-test".to_owned(),
+            RustTestBinarySummary {
+                binary_name: "my-test".to_owned(),
+                package_id: "my-package 0.1.0 (path+file:///my-package)".to_owned(),
+                binary_path: "target/debug/deps/my-test-0123".into(),
+                cwd: "my-package".into(),
+                build_platform: BuildPlatform::Target,
+                harness: RustTestHarness::Custom,
+                kind: Some(RustTestBinaryKind::Test),
+                required_features: vec!["my-feature".to_owned()],
+                profile: None,
+            },
+        );
+        let binary_list_json = serde_json::to_vec(&binary_list).expect("serialized binary list");
+
+        let archive_file = src.join("archive.tar.zst");
+        write_archive(
+            &archive_file,
+            r#"{"fake": "metadata"}"#,
+            &binary_list_json,
+            &[(&binary_path, "target/debug/deps/my-test-0123".into())],
+        )
+        .expect("wrote archive");
+
+        let extracted = ExtractedArchive::extract(&archive_file, None).expect("extracted archive");
+        assert_eq!(extracted.cargo_metadata(), r#"{"fake": "metadata"}"#);
+        assert_eq!(extracted.binary_list(), &binary_list);
+        assert_eq!(
+            std::fs::read(extracted.dir().join("target/debug/deps/my-test-0123"))
+                .expect("read extracted binary"),
+            b"not really a binary"
+        );
+
+        // The temporary directory is cleaned up on drop.
+        let dir = extracted.dir().to_path_buf();
+        drop(extracted);
+        assert!(!dir.exists(), "extracted dir {} was cleaned up", dir);
+    }
+}
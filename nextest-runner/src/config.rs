@@ -4,13 +4,38 @@
 //! Configuration support for nextest.
 
 use crate::{
-    errors::{ConfigParseError, ProfileNotFound},
-    reporter::{StatusLevel, TestOutputDisplay},
+    errors::{
+        ConfigParseError, CpuSetParseError, NoTestsBehaviorParseError, ProfileNotFound,
+        TestThreadsParseError, ToolConfigError, ToolConfigFileParseError,
+    },
+    filter_expr::FilterExpr,
+    reporter::{
+        ColorTheme, NotifyFormat, NotifyTemplate, StatusLevel, StyleSpec, TestOutputDisplay,
+    },
+    test_list::TestInstance,
 };
 use camino::{Utf8Path, Utf8PathBuf};
-use config::{Config, File, FileFormat};
-use serde::Deserialize;
-use std::{collections::HashMap, time::Duration};
+use config::{Config, ConfigError, File, FileFormat};
+use rand::Rng;
+use semver::{Version, VersionReq};
+use serde::{
+    de::{DeserializeOwned, IntoDeserializer},
+    Deserialize,
+};
+use std::{
+    collections::{BTreeMap, BTreeSet, HashMap},
+    convert::TryFrom,
+    ffi::OsString,
+    fmt,
+    num::NonZeroUsize,
+    str::FromStr,
+    time::Duration,
+};
+use target_spec::TargetSpec;
+
+mod check;
+
+pub use check::{ConfigDiagnostic, ConfigDiagnosticKind, ConfigLocation};
 
 /// Overall configuration for nextest.
 ///
@@ -23,6 +48,8 @@ use std::{collections::HashMap, time::Duration};
 #[derive(Clone, Debug)]
 pub struct NextestConfig {
     workspace_root: Utf8PathBuf,
+    sources: Vec<ConfigSource>,
+    merged: toml::Value,
     inner: NextestConfigImpl,
 }
 
@@ -39,37 +66,189 @@ impl NextestConfig {
     /// Environment configuration uses this prefix, plus a _.
     pub const ENVIRONMENT_PREFIX: &'static str = "NEXTEST";
 
+    /// Environment variables starting with this prefix override individual settings. For example,
+    /// `NEXTEST_CONFIG_PROFILE__CI__FAIL_FAST=false` sets `profile.ci.fail-fast` to false.
+    pub const ENV_OVERRIDE_PREFIX: &'static str = "NEXTEST_CONFIG_";
+
+    /// The environment variable that overrides the location of the
+    /// [user config file](Self::user_config_path). If it's set to an empty string, the user config
+    /// isn't read.
+    pub const USER_CONFIG_ENV: &'static str = "NEXTEST_USER_CONFIG_FILE";
+
     /// The name of the default profile.
     pub const DEFAULT_PROFILE: &'static str = "default";
 
-    /// Reads the nextest config from the given file, or if not specified from `.config/nextest.toml`
-    /// in the given directory.
+    /// Reads the nextest config from all of its sources, in increasing order of precedence:
     ///
-    /// If the file isn't specified and the directory doesn't have `.config/nextest.toml`, uses the
-    /// default config options.
-    pub fn from_sources(
+    /// 1. the [default config](Self::DEFAULT_CONFIG).
+    /// 2. the given tool config files, in order.
+    /// 3. the [user config file](Self::user_config_path), if it exists.
+    /// 4. the given file, or if not specified `.config/nextest.toml` in the given directory if it
+    ///    exists.
+    /// 5. environment variables starting with [`ENV_OVERRIDE_PREFIX`](Self::ENV_OVERRIDE_PREFIX).
+    ///
+    /// Settings in later sources override the same settings in earlier ones. Lists, such as
+    /// `[[profile.default.overrides]]`, are replaced rather than appended to.
+    pub fn from_sources<'a>(
         workspace_root: impl Into<Utf8PathBuf>,
         config_file: Option<&Utf8Path>,
+        tool_config_files: impl IntoIterator<Item = &'a ToolConfigFile>,
     ) -> Result<Self, ConfigParseError> {
-        let workspace_root = workspace_root.into();
-        let (config_file, config) = Self::read_from_sources(&workspace_root, config_file)?;
-        let inner = config
-            .try_into()
-            .map_err(|err| ConfigParseError::new(config_file, err))?;
-        Ok(Self {
-            workspace_root,
-            inner,
-        })
+        Self::from_sources_impl(
+            workspace_root.into(),
+            config_file,
+            tool_config_files,
+            Self::user_config_path().as_deref(),
+            std::env::vars_os(),
+        )
+    }
+
+    /// Reads only the versions of nextest that the config requires or recommends, from the same
+    /// sources as [`from_sources`](Self::from_sources).
+    ///
+    /// The rest of the config isn't deserialized, so the versions can be checked before settings
+    /// that this version of nextest doesn't understand cause errors.
+    pub fn read_nextest_version<'a>(
+        workspace_root: impl Into<Utf8PathBuf>,
+        config_file: Option<&Utf8Path>,
+        tool_config_files: impl IntoIterator<Item = &'a ToolConfigFile>,
+    ) -> Result<NextestVersionConfig, ConfigParseError> {
+        Self::read_nextest_version_impl(
+            &workspace_root.into(),
+            config_file,
+            tool_config_files,
+            Self::user_config_path().as_deref(),
+            std::env::vars_os(),
+        )
     }
 
     /// Returns the default nextest config.
     pub fn default_config(workspace_root: impl Into<Utf8PathBuf>) -> Self {
         let config = Self::make_default_config();
-        let inner = config.try_into().expect("default config is always valid");
-        Self {
-            workspace_root: workspace_root.into(),
-            inner,
+        Self::from_config(workspace_root.into(), vec![ConfigSource::Default], config)
+            .expect("default config is always valid")
+    }
+
+    /// Returns the path to the user config file, which applies to every workspace.
+    ///
+    /// This is `$XDG_CONFIG_HOME/nextest.toml` if `XDG_CONFIG_HOME` is set, and
+    /// `~/.config/nextest.toml` otherwise. It can be overridden through
+    /// [`USER_CONFIG_ENV`](Self::USER_CONFIG_ENV). Returns `None` if the user config is disabled,
+    /// or if its location couldn't be determined.
+    pub fn user_config_path() -> Option<Utf8PathBuf> {
+        if let Some(path) = std::env::var_os(Self::USER_CONFIG_ENV) {
+            return if path.is_empty() {
+                None
+            } else {
+                Utf8PathBuf::from_path_buf(path.into()).ok()
+            };
+        }
+        let config_dir = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => dir.into(),
+            _ => home::home_dir()?.join(".config"),
+        };
+        let config_dir = Utf8PathBuf::from_path_buf(config_dir).ok()?;
+        Some(config_dir.join("nextest.toml"))
+    }
+
+    /// Returns the sources the config was read from, in increasing order of precedence.
+    ///
+    /// Files that don't exist aren't included.
+    pub fn sources(&self) -> &[ConfigSource] {
+        &self.sources
+    }
+
+    /// Deserializes the `[tool.<name>]` section of the config, if any.
+    ///
+    /// Nextest doesn't interpret these sections itself: tools built on top of nextest can use them
+    /// for settings of their own, which are layered in the same way as the rest of the config.
+    pub fn tool_config<T: DeserializeOwned>(
+        &self,
+        tool: &str,
+    ) -> Result<Option<T>, ToolConfigError> {
+        self.inner
+            .tool
+            .get(tool)
+            .map(|section| {
+                section
+                    .clone()
+                    .try_into()
+                    .map_err(|err| ToolConfigError::new(tool, err))
+            })
+            .transpose()
+    }
+
+    /// Returns the config merged from all of its [sources](Self::sources), as TOML.
+    pub fn to_toml(&self) -> String {
+        toml::to_string_pretty(&self.merged).expect("merged config is valid TOML")
+    }
+
+    /// Returns the settings of the profile with the given name as TOML, as a
+    /// `[profile.<name>]` section.
+    ///
+    /// Settings are resolved the same way they are for [`profile`](Self::profile): the section
+    /// includes settings inherited from the default profile, and settings from
+    /// `[profile.<name>.target.<platform>]` sections that match the current platform. Overrides
+    /// are listed in the order they're matched against tests.
+    pub fn profile_to_toml(&self, name: impl AsRef<str>) -> Result<String, ProfileNotFound> {
+        let name = name.as_ref();
+        // Check that the profile exists.
+        self.make_profile(name)?;
+
+        let profiles = self.merged.get("profile").and_then(toml::Value::as_table);
+        let profile_table = |name: &str| {
+            profiles
+                .and_then(|profiles| profiles.get(name))
+                .and_then(toml::Value::as_table)
+                .cloned()
+                .unwrap_or_default()
+        };
+        let mut default = profile_table(Self::DEFAULT_PROFILE);
+        let mut custom = if name == Self::DEFAULT_PROFILE {
+            None
+        } else {
+            Some(profile_table(name))
+        };
+
+        let mut overrides = vec![];
+        for profile in custom.iter_mut().chain(std::iter::once(&mut default)) {
+            if let Some(toml::Value::Array(profile_overrides)) = profile.remove("overrides") {
+                overrides.extend(profile_overrides);
+            }
         }
+
+        let mut resolved = toml::value::Table::new();
+        for profile in std::iter::once(default).chain(custom) {
+            let platform_settings = matching_platform_settings(&profile);
+            for (key, value) in profile.into_iter().chain(platform_settings) {
+                merge_profile_setting(&mut resolved, key, value);
+            }
+        }
+        // Unknown keys are reported by check_sources, and don't have any effect. Platform
+        // settings were merged in above.
+        let mut unknown = vec!["target".to_owned()];
+        let _: Result<CustomProfileImpl, _> =
+            serde_ignored::deserialize(toml::Value::Table(resolved.clone()), |path| {
+                if let serde_ignored::Path::Map {
+                    parent: serde_ignored::Path::Root,
+                    key,
+                } = path
+                {
+                    unknown.push(key);
+                }
+            });
+        for key in unknown {
+            resolved.remove(&key);
+        }
+        if !overrides.is_empty() {
+            resolved.insert("overrides".to_owned(), toml::Value::Array(overrides));
+        }
+
+        let mut profiles = toml::value::Table::new();
+        profiles.insert(name.to_owned(), toml::Value::Table(resolved));
+        let mut root = toml::value::Table::new();
+        root.insert("profile".to_owned(), toml::Value::Table(profiles));
+        Ok(toml::to_string_pretty(&root).expect("profile is valid TOML"))
     }
 
     /// Returns the profile with the given name, or an error if a profile was specified but not
@@ -78,35 +257,179 @@ impl NextestConfig {
         self.make_profile(name.as_ref())
     }
 
+    /// Returns the absolute store directory.
+    ///
+    /// Each profile has a store directory of its own within this one.
+    pub fn store_dir(&self) -> Utf8PathBuf {
+        self.workspace_root.join(&self.inner.store.dir)
+    }
+
+    /// Returns how long the artifacts of earlier runs are kept in each profile's store directory.
+    pub fn store_retention(&self) -> StoreRetention {
+        StoreRetention::new(self.inner.store.max_age, self.inner.store.max_size)
+    }
+
+    /// Returns true if the test binary with the given ID is marked as opaque.
+    ///
+    /// Opaque binaries are run as a single test, rather than being queried for the tests they
+    /// contain.
+    pub fn is_opaque_binary(&self, binary_id: &str) -> bool {
+        self.inner
+            .test_binaries
+            .opaque
+            .iter()
+            .any(|opaque| opaque == binary_id)
+    }
+
+    /// Returns the runners used for tests built for WebAssembly targets.
+    pub fn wasm(&self) -> &WasmConfig {
+        &self.inner.wasm
+    }
+
+    /// Returns the versions of nextest that the config requires or recommends.
+    pub fn nextest_version(&self) -> &NextestVersionConfig {
+        &self.inner.nextest_version
+    }
+
+    /// Returns the experimental features enabled through the `experimental` list.
+    pub fn experimental(&self) -> &BTreeSet<ConfigExperimental> {
+        &self.inner.experimental
+    }
+
     // ---
     // Helper methods
     // ---
 
-    fn read_from_sources(
+    pub(crate) fn from_sources_impl<'a>(
+        workspace_root: Utf8PathBuf,
+        config_file: Option<&Utf8Path>,
+        tool_config_files: impl IntoIterator<Item = &'a ToolConfigFile>,
+        user_config_file: Option<&Utf8Path>,
+        env_vars: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Result<Self, ConfigParseError> {
+        let (workspace_source, sources, config) = Self::read_from_sources(
+            &workspace_root,
+            config_file,
+            tool_config_files,
+            user_config_file,
+            env_vars,
+        )?;
+        Self::from_config(workspace_root, sources, config)
+            .map_err(|err| ConfigParseError::new(workspace_source, err))
+    }
+
+    pub(crate) fn read_nextest_version_impl<'a>(
+        workspace_root: &Utf8Path,
+        config_file: Option<&Utf8Path>,
+        tool_config_files: impl IntoIterator<Item = &'a ToolConfigFile>,
+        user_config_file: Option<&Utf8Path>,
+        env_vars: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Result<NextestVersionConfig, ConfigParseError> {
+        let (workspace_source, _, config) = Self::read_from_sources(
+            workspace_root,
+            config_file,
+            tool_config_files,
+            user_config_file,
+            env_vars,
+        )?;
+        let version_only: NextestVersionOnlyImpl = config
+            .try_into()
+            .map_err(|err| ConfigParseError::new(workspace_source, err))?;
+        Ok(version_only.nextest_version)
+    }
+
+    fn from_config(
+        workspace_root: Utf8PathBuf,
+        sources: Vec<ConfigSource>,
+        config: Config,
+    ) -> Result<Self, ConfigError> {
+        let merged = config.clone().try_into()?;
+        let inner: NextestConfigImpl = config.try_into()?;
+        inner.validate()?;
+        Ok(Self {
+            workspace_root,
+            sources,
+            merged,
+            inner,
+        })
+    }
+
+    /// Merges all sources into a config. Also returns the source that errors deserializing the
+    /// config are attributed to, and the sources that were read.
+    fn read_from_sources<'a>(
         workspace_root: &Utf8Path,
         file: Option<&Utf8Path>,
-    ) -> Result<(Utf8PathBuf, Config), ConfigParseError> {
+        tool_files: impl IntoIterator<Item = &'a ToolConfigFile>,
+        user_file: Option<&Utf8Path>,
+        env_vars: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Result<(ConfigSource, Vec<ConfigSource>, Config), ConfigParseError> {
         // First, get the default config.
         let mut config = Self::make_default_config();
+        let mut sources = vec![ConfigSource::Default];
+
+        // Tool config files provide defaults for the tool's users, so they come before any config
+        // the user wrote.
+        for tool_file in tool_files {
+            let source = ConfigSource::Tool {
+                tool: tool_file.tool.clone(),
+                path: tool_file.config_file.clone(),
+            };
+            let tool_config = Config::new()
+                .with_merged(File::new(tool_file.config_file.as_str(), FileFormat::Toml))
+                .and_then(|tool_config| {
+                    tool_file.check_tool_sections(&tool_config)?;
+                    Ok(tool_config)
+                })
+                .map_err(|err| ConfigParseError::new(source.clone(), err))?;
+            config
+                .merge(tool_config)
+                .map_err(|err| ConfigParseError::new(source.clone(), err))?;
+            sources.push(source);
+        }
+
+        // Next, merge in the user config, if it exists.
+        if let Some(user_file) = user_file.filter(|user_file| user_file.is_file()) {
+            let source = ConfigSource::User(user_file.to_owned());
+            config
+                .merge(File::new(user_file.as_str(), FileFormat::Toml))
+                .map_err(|err| ConfigParseError::new(source.clone(), err))?;
+            sources.push(source);
+        }
 
-        // Next, merge in the config from the given file.
-        let config_path = match file {
+        // Then, merge in the config from the given file.
+        let workspace_source = match file {
             Some(file) => {
+                let source = ConfigSource::Workspace(file.to_owned());
                 config
                     .merge(File::new(file.as_str(), FileFormat::Toml))
-                    .map_err(|err| ConfigParseError::new(file, err))?;
-                file.to_owned()
+                    .map_err(|err| ConfigParseError::new(source.clone(), err))?;
+                sources.push(source.clone());
+                source
             }
             None => {
                 let config_path = workspace_root.join(Self::CONFIG_PATH);
-                config
-                    .merge(File::new(config_path.as_str(), FileFormat::Toml).required(false))
-                    .map_err(|err| ConfigParseError::new(config_path.clone(), err))?;
-                config_path
+                let source = ConfigSource::Workspace(config_path.clone());
+                if config_path.is_file() {
+                    config
+                        .merge(File::new(config_path.as_str(), FileFormat::Toml))
+                        .map_err(|err| ConfigParseError::new(source.clone(), err))?;
+                    sources.push(source.clone());
+                }
+                source
             }
         };
 
-        Ok((config_path, config))
+        // Finally, apply overrides from the environment.
+        for (var, value) in env_overrides(env_vars)? {
+            let source = ConfigSource::Env(var);
+            let contents = toml::to_string(&value).expect("override is valid TOML");
+            config
+                .merge(File::from_str(&contents, FileFormat::Toml))
+                .map_err(|err| ConfigParseError::new(source.clone(), err))?;
+            sources.push(source);
+        }
+
+        Ok((workspace_source, sources, config))
     }
 
     fn make_default_config() -> Config {
@@ -115,193 +438,2701 @@ impl NextestConfig {
             .expect("default config is valid")
     }
 
-    fn make_profile(&self, name: &str) -> Result<NextestProfile<'_>, ProfileNotFound> {
+    fn make_profile<'cfg>(&'cfg self, name: &str) -> Result<NextestProfile<'cfg>, ProfileNotFound> {
         let custom_profile = self.inner.profiles.get(name)?;
 
         // The profile was found: construct the NextestProfile.
-        let mut store_dir = self.workspace_root.join(&self.inner.store.dir);
+        let mut store_dir = self.store_dir();
         store_dir.push(name);
 
+        // Platform-specific sections are matched against the platform nextest is running on. If
+        // it isn't known, no sections match.
+        let platform = target_spec::Platform::current().ok();
+        let matching_sections = |sections: &'cfg [(TargetSpec, PlatformProfileImpl)]| {
+            sections
+                .iter()
+                .filter(|(spec, _)| {
+                    platform
+                        .as_ref()
+                        .map_or(false, |platform| spec.eval(platform) == Some(true))
+                })
+                .map(|(_, section)| section)
+                .collect()
+        };
+
         Ok(NextestProfile {
+            name: name.to_owned(),
+            workspace_root: &self.workspace_root,
             store_dir,
+            scripts: &self.inner.scripts,
+            test_groups: &self.inner.test_groups,
+            excludes: &self.inner.excludes,
+            quarantine: &self.inner.quarantine,
             default_profile: &self.inner.profiles.default,
+            default_platform: matching_sections(&self.inner.profiles.default.target),
             custom_profile,
+            custom_platform: custom_profile
+                .map_or_else(Vec::new, |profile| matching_sections(&profile.target)),
         })
     }
 }
 
-/// A configuration profile for nextest. Contains most configuration used by the nextest runner.
-///
-/// Returned by [`NextestConfig::profile`].
-#[derive(Clone, Debug)]
-pub struct NextestProfile<'cfg> {
-    store_dir: Utf8PathBuf,
-    default_profile: &'cfg DefaultProfileImpl,
-    custom_profile: Option<&'cfg CustomProfileImpl>,
+/// Returns the settings from the `[target.<platform>]` sections of a profile that match the
+/// current platform. As with [`NextestProfile`], the first matching section that specifies a
+/// setting wins.
+fn matching_platform_settings(profile: &toml::value::Table) -> toml::value::Table {
+    let mut settings = toml::value::Table::new();
+    let platform = match target_spec::Platform::current() {
+        Ok(platform) => platform,
+        Err(_) => return settings,
+    };
+    let sections = profile.get("target").and_then(toml::Value::as_table);
+    for (spec, section) in sections.into_iter().flatten() {
+        let matches =
+            TargetSpec::new(spec.clone()).map_or(false, |spec| spec.eval(&platform) == Some(true));
+        if let (true, Some(section)) = (matches, section.as_table()) {
+            for (key, value) in section {
+                settings.entry(key.clone()).or_insert_with(|| value.clone());
+            }
+        }
+    }
+    settings
 }
 
-impl<'cfg> NextestProfile<'cfg> {
-    /// Returns the absolute profile-specific store directory.
-    pub fn store_dir(&self) -> &Utf8Path {
-        &self.store_dir
+/// Merges a setting from a profile into the settings resolved so far, in the same way that the
+/// [`NextestProfile`] accessors do.
+fn merge_profile_setting(resolved: &mut toml::value::Table, key: String, value: toml::Value) {
+    match (key.as_str(), resolved.get_mut(&key), value) {
+        // These sections are merged setting by setting.
+        (
+            "resource-limits" | "throttle" | "slowest-tests" | "colors" | "junit" | "tap" | "html"
+            | "chrome-trace",
+            Some(toml::Value::Table(resolved)),
+            toml::Value::Table(section),
+        ) => resolved.extend(section),
+        // These sections replace the default profile's section only if they set an endpoint.
+        ("otlp", Some(_), toml::Value::Table(section)) if !section.contains_key("endpoint") => {}
+        ("notify", Some(_), toml::Value::Table(section)) if !section.contains_key("url") => {}
+        (_, _, value) => {
+            resolved.insert(key, value);
+        }
     }
+}
 
-    /// Returns the retry count for this profile.
-    pub fn retries(&self) -> usize {
-        self.custom_profile
-            .map(|profile| profile.retries)
-            .flatten()
-            .unwrap_or(self.default_profile.retries)
-    }
+/// A source that nextest configuration is read from, returned by [`NextestConfig::sources`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigSource {
+    /// The default config shipped with nextest, [`NextestConfig::DEFAULT_CONFIG`].
+    Default,
 
-    /// Returns the time after which tests are treated as slow for this profile.
-    pub fn slow_timeout(&self) -> Duration {
-        self.custom_profile
-            .map(|profile| profile.slow_timeout)
-            .flatten()
-            .unwrap_or(self.default_profile.slow_timeout)
+    /// A [tool config file](ToolConfigFile).
+    Tool {
+        /// The name of the tool.
+        tool: String,
+
+        /// The path to the config file.
+        path: Utf8PathBuf,
+    },
+
+    /// The user config file, which applies to every workspace.
+    User(Utf8PathBuf),
+
+    /// The workspace's config file: `.config/nextest.toml`, or the file passed in with
+    /// `--config-file`.
+    Workspace(Utf8PathBuf),
+
+    /// An environment variable that overrides a single setting.
+    Env(String),
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigSource::Default => write!(f, "default config"),
+            ConfigSource::Tool { tool, path } => write!(f, "config for tool {} at {}", tool, path),
+            ConfigSource::User(path) => write!(f, "user config at {}", path),
+            ConfigSource::Workspace(path) => write!(f, "workspace config at {}", path),
+            ConfigSource::Env(var) => write!(f, "environment variable {}", var),
+        }
     }
+}
 
-    /// Returns the test status level.
-    pub fn status_level(&self) -> StatusLevel {
-        self.custom_profile
-            .map(|profile| profile.status_level)
-            .flatten()
-            .unwrap_or(self.default_profile.status_level)
+/// A config file provided by a tool built on top of nextest, such as a coverage driver.
+///
+/// Tool config files are layered under the user's own config, so they provide defaults without
+/// overriding anything the user has set. Tools can keep settings of their own in a
+/// `[tool.<name>]` section, read through [`NextestConfig::tool_config`]; a tool config file can't
+/// set the sections of other tools.
+///
+/// Parsed from strings of the form `<tool-name>:<path>`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ToolConfigFile {
+    /// The name of the tool.
+    pub tool: String,
+
+    /// The path to the config file.
+    pub config_file: Utf8PathBuf,
+}
+
+impl ToolConfigFile {
+    /// Checks that the tool config doesn't set the sections of other tools.
+    fn check_tool_sections(&self, config: &Config) -> Result<(), ConfigError> {
+        let sections = match config.get_table("tool") {
+            Ok(sections) => sections,
+            Err(ConfigError::NotFound(_)) => return Ok(()),
+            Err(err) => return Err(err),
+        };
+        match sections.keys().find(|&tool| tool != &self.tool) {
+            Some(other) => Err(ConfigError::Message(format!(
+                "config file for tool `{}` sets [tool.{}], but can only set [tool.{}]",
+                self.tool, other, self.tool
+            ))),
+            None => Ok(()),
+        }
     }
+}
 
-    /// Returns the failure output config for this profile.
-    pub fn failure_output(&self) -> TestOutputDisplay {
-        self.custom_profile
-            .map(|profile| profile.failure_output)
-            .flatten()
-            .unwrap_or(self.default_profile.failure_output)
+impl FromStr for ToolConfigFile {
+    type Err = ToolConfigFileParseError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        match input.split_once(':') {
+            Some((tool, config_file)) if !tool.is_empty() && !config_file.is_empty() => Ok(Self {
+                tool: tool.to_owned(),
+                config_file: config_file.into(),
+            }),
+            _ => Err(ToolConfigFileParseError::new(input)),
+        }
     }
+}
 
-    /// Returns the failure output config for this profile.
-    pub fn success_output(&self) -> TestOutputDisplay {
-        self.custom_profile
-            .map(|profile| profile.success_output)
-            .flatten()
-            .unwrap_or(self.default_profile.success_output)
+/// Parses settings overridden through environment variables, sorted by variable name.
+///
+/// The part of the variable name after the prefix is the path to the setting, with `__` between
+/// keys and `_` in place of `-`. Values are parsed as TOML, and are treated as strings if they
+/// aren't valid TOML: `NEXTEST_CONFIG_STORE__MAX_AGE=30d` sets `store.max-age` to `"30d"`.
+fn env_overrides(
+    env_vars: impl IntoIterator<Item = (OsString, OsString)>,
+) -> Result<Vec<(String, toml::Value)>, ConfigParseError> {
+    let mut overrides = vec![];
+    for (var, value) in env_vars {
+        let var = match var.into_string() {
+            Ok(var) if var.starts_with(NextestConfig::ENV_OVERRIDE_PREFIX) => var,
+            _ => continue,
+        };
+        let error = |message: &str| {
+            ConfigParseError::new(
+                ConfigSource::Env(var.clone()),
+                ConfigError::Message(message.to_owned()),
+            )
+        };
+        let value = value
+            .into_string()
+            .map_err(|_| error("value isn't valid UTF-8"))?;
+        let keys: Vec<_> = var[NextestConfig::ENV_OVERRIDE_PREFIX.len()..]
+            .split("__")
+            .map(|key| key.to_lowercase().replace('_', "-"))
+            .collect();
+        if keys.iter().any(|key| key.is_empty()) {
+            return Err(error(
+                "name must be a path to a setting, with `__` between keys",
+            ));
+        }
+
+        let mut override_ = match toml::from_str::<toml::value::Table>(&format!("v = {}", value)) {
+            Ok(mut table) => table.remove("v").expect("key was just parsed"),
+            Err(_) => toml::Value::String(value),
+        };
+        for key in keys.into_iter().rev() {
+            let mut table = toml::value::Table::new();
+            table.insert(key, override_);
+            override_ = toml::Value::Table(table);
+        }
+        overrides.push((var, override_));
     }
+    overrides.sort_by(|(a, _), (b, _)| a.cmp(b));
+    Ok(overrides)
+}
 
-    /// Returns the fail-fast config for this profile.
-    pub fn fail_fast(&self) -> bool {
-        self.custom_profile
-            .map(|profile| profile.fail_fast)
-            .flatten()
-            .unwrap_or(self.default_profile.fail_fast)
+/// How long the artifacts of earlier runs are kept in a profile's store directory, returned by
+/// [`NextestConfig::store_retention`]. See [`RunStore::gc`](crate::store::RunStore::gc).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct StoreRetention {
+    max_age: Option<Duration>,
+    max_size: Option<u64>,
+}
+
+impl StoreRetention {
+    /// Creates a new retention policy. `None` means that there's no limit.
+    pub fn new(max_age: Option<Duration>, max_size: Option<u64>) -> Self {
+        Self { max_age, max_size }
     }
 
-    /// Returns the JUnit configuration for this profile.
-    pub fn junit(&self) -> Option<NextestJunitConfig<'cfg>> {
-        let path = self
-            .custom_profile
-            .map(|profile| &profile.junit.path)
-            .unwrap_or(&self.default_profile.junit.path)
-            .as_deref();
+    /// Returns the age after which the artifacts of a run are removed, if limited.
+    pub fn max_age(&self) -> Option<Duration> {
+        self.max_age
+    }
 
-        path.map(|path| {
-            let path = self.store_dir.join(path);
-            let report_name = self
-                .custom_profile
-                .map(|profile| profile.junit.report_name.as_deref())
-                .flatten()
-                .unwrap_or(&self.default_profile.junit.report_name);
-            NextestJunitConfig { path, report_name }
-        })
+    /// Returns the total size that the artifacts of earlier runs may take up, if limited. Once it's
+    /// exceeded, the artifacts of the oldest runs are removed first.
+    pub fn max_size(&self) -> Option<u64> {
+        self.max_size
     }
 }
 
-/// JUnit configuration for nextest, returned by a [`NextestProfile`].
-#[derive(Clone, Debug)]
-pub struct NextestJunitConfig<'cfg> {
-    path: Utf8PathBuf,
-    report_name: &'cfg str,
+/// The versions of nextest that the config requires or recommends, set through
+/// `nextest-version` and returned by [`NextestConfig::nextest_version`].
+///
+/// `nextest-version` is either a version requirement, which is required, or a table with
+/// `required` and `recommended` keys:
+///
+/// ```toml
+/// nextest-version = { required = ">=0.9.50", recommended = ">=0.9.60" }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize, Eq, PartialEq)]
+#[serde(try_from = "NextestVersionImpl")]
+pub struct NextestVersionConfig {
+    required: Option<VersionReq>,
+    recommended: Option<VersionReq>,
 }
 
-impl<'cfg> NextestJunitConfig<'cfg> {
-    /// Returns the absolute path to the JUnit report.
-    pub fn path(&self) -> &Utf8Path {
-        &self.path
+impl NextestVersionConfig {
+    /// Returns the versions of nextest that the config can't be used with older versions than, if
+    /// set.
+    pub fn required(&self) -> Option<&VersionReq> {
+        self.required.as_ref()
     }
 
-    /// Returns the name of the JUnit report.
-    pub fn report_name(&self) -> &'cfg str {
-        self.report_name
+    /// Returns the versions of nextest that are recommended for use with the config, if set.
+    pub fn recommended(&self) -> Option<&VersionReq> {
+        self.recommended.as_ref()
     }
-}
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct NextestConfigImpl {
-    store: StoreConfigImpl,
-    #[serde(rename = "profile")]
-    profiles: NextestProfilesImpl,
+    /// Checks the given version of nextest against the requirements.
+    ///
+    /// Pre-release versions are matched as if they were the corresponding release.
+    pub fn eval(&self, current: &Version) -> NextestVersionEval {
+        let mut release = current.clone();
+        release.pre = semver::Prerelease::EMPTY;
+        if let Some(required) = self.required.as_ref().filter(|req| !req.matches(&release)) {
+            return NextestVersionEval::Error {
+                required: required.clone(),
+                current: current.clone(),
+            };
+        }
+        if let Some(recommended) = self
+            .recommended
+            .as_ref()
+            .filter(|req| !req.matches(&release))
+        {
+            return NextestVersionEval::Warn {
+                recommended: recommended.clone(),
+                current: current.clone(),
+            };
+        }
+        NextestVersionEval::Satisfied
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct StoreConfigImpl {
-    dir: Utf8PathBuf,
+impl TryFrom<NextestVersionImpl> for NextestVersionConfig {
+    type Error = String;
+
+    fn try_from(value: NextestVersionImpl) -> Result<Self, Self::Error> {
+        let (required, recommended) = match value {
+            NextestVersionImpl::Required(required) => (Some(required), None),
+            NextestVersionImpl::Tiers {
+                required,
+                recommended,
+            } => (required, recommended),
+        };
+        let parse = |input: Option<String>| {
+            input
+                .map(|input| {
+                    VersionReq::parse(&input)
+                        .map_err(|err| format!("invalid nextest-version `{}`: {}", input, err))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            required: parse(required)?,
+            recommended: parse(recommended)?,
+        })
+    }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct NextestProfilesImpl {
-    default: DefaultProfileImpl,
-    #[serde(flatten)]
-    other: HashMap<String, CustomProfileImpl>,
+/// An experimental feature, which must be enabled through the `experimental` list in the config to
+/// be used. Returned by [`NextestConfig::experimental`].
+///
+/// Experimental features may change or be removed in any release.
+#[derive(Copy, Clone, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[serde(try_from = "String")]
+#[non_exhaustive]
+pub enum ConfigExperimental {
+    /// Setup scripts, defined through `[script.<name>]` sections.
+    SetupScripts,
 }
 
-impl NextestProfilesImpl {
-    fn get(&self, profile: &str) -> Result<Option<&CustomProfileImpl>, ProfileNotFound> {
-        let custom_profile = match profile {
-            NextestConfig::DEFAULT_PROFILE => None,
-            other => Some(
-                self.other
-                    .get(other)
-                    .ok_or_else(|| ProfileNotFound::new(profile, self.all_profiles()))?,
-            ),
-        };
-        Ok(custom_profile)
+impl ConfigExperimental {
+    /// All experimental features.
+    pub const ALL: &'static [ConfigExperimental] = &[ConfigExperimental::SetupScripts];
+
+    /// Returns the name of the feature, as written in the `experimental` list.
+    pub fn name(self) -> &'static str {
+        match self {
+            ConfigExperimental::SetupScripts => "setup-scripts",
+        }
     }
+}
 
-    fn all_profiles(&self) -> impl Iterator<Item = &str> {
-        self.other
-            .keys()
-            .map(|key| key.as_str())
-            .chain(std::iter::once(NextestConfig::DEFAULT_PROFILE))
+impl fmt::Display for ConfigExperimental {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.name())
     }
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct DefaultProfileImpl {
-    retries: usize,
-    status_level: StatusLevel,
-    failure_output: TestOutputDisplay,
-    success_output: TestOutputDisplay,
-    fail_fast: bool,
-    #[serde(with = "humantime_serde")]
-    slow_timeout: Duration,
-    junit: DefaultJunitImpl,
+/// Deserializes the `experimental` list, ignoring features that this version of nextest doesn't
+/// know about. They're reported by [`NextestConfig::check_sources`] instead, so that a config that
+/// enables a feature added in a later version can still be read.
+fn deserialize_experimental<'de, D>(
+    deserializer: D,
+) -> Result<BTreeSet<ConfigExperimental>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let names = Vec::<String>::deserialize(deserializer)?;
+    Ok(names
+        .into_iter()
+        .filter_map(|name| ConfigExperimental::try_from(name).ok())
+        .collect())
 }
 
-#[derive(Clone, Debug, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct DefaultJunitImpl {
-    #[serde(default)]
-    path: Option<Utf8PathBuf>,
-    report_name: String,
+impl TryFrom<String> for ConfigExperimental {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|feature| feature.name() == value)
+            .ok_or_else(|| {
+                let known: Vec<_> = Self::ALL.iter().map(|feature| feature.name()).collect();
+                format!(
+                    "unknown experimental feature `{}` (known features: {})",
+                    value,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+/// The result of checking the running version of nextest against a [`NextestVersionConfig`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum NextestVersionEval {
+    /// The version satisfies every requirement.
+    Satisfied,
+
+    /// The version doesn't satisfy the required version, so the config can't be used with it.
+    Error {
+        /// The required versions.
+        required: VersionReq,
+
+        /// The running version.
+        current: Version,
+    },
+
+    /// The version satisfies the required version, but not the recommended one.
+    Warn {
+        /// The recommended versions.
+        recommended: VersionReq,
+
+        /// The running version.
+        current: Version,
+    },
+}
+
+/// A configuration profile for nextest. Contains most configuration used by the nextest runner.
+///
+/// Returned by [`NextestConfig::profile`].
+#[derive(Clone, Debug)]
+pub struct NextestProfile<'cfg> {
+    name: String,
+    workspace_root: &'cfg Utf8Path,
+    store_dir: Utf8PathBuf,
+    scripts: &'cfg BTreeMap<String, ScriptConfig>,
+    test_groups: &'cfg BTreeMap<String, TestGroupConfig>,
+    excludes: &'cfg [ExcludeConfig],
+    quarantine: &'cfg [QuarantineConfig],
+    default_profile: &'cfg DefaultProfileImpl,
+    // The [profile.<name>.target.<platform>] sections that match the current platform.
+    default_platform: Vec<&'cfg PlatformProfileImpl>,
+    custom_profile: Option<&'cfg CustomProfileImpl>,
+    custom_platform: Vec<&'cfg PlatformProfileImpl>,
+}
+
+impl<'cfg> NextestProfile<'cfg> {
+    /// The highest nice value that tests can be run at, which is the lowest priority on Unix.
+    pub const MAX_NICE: u8 = 19;
+
+    /// Returns the name of the profile.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the absolute profile-specific store directory.
+    pub fn store_dir(&self) -> &Utf8Path {
+        &self.store_dir
+    }
+
+    /// Returns the setup scripts configured through `[script.<name>]` sections, sorted by name.
+    ///
+    /// Setup commands are run in this order, and teardown commands in the reverse order. The order
+    /// scripts are declared in isn't kept, since config files are merged as unordered tables.
+    pub fn scripts(&self) -> impl Iterator<Item = (&'cfg str, &'cfg ScriptConfig)> + 'cfg {
+        self.scripts
+            .iter()
+            .map(|(name, script)| (name.as_str(), script))
+    }
+
+    /// Returns the test groups configured through `[test-groups.<name>]` sections.
+    pub fn test_groups(&self) -> impl Iterator<Item = (&'cfg str, &'cfg TestGroupConfig)> + 'cfg {
+        self.test_groups
+            .iter()
+            .map(|(name, group)| (name.as_str(), group))
+    }
+
+    /// Returns the tests excluded through `[[exclude]]` sections, in the order they're defined.
+    pub fn excludes(&self) -> &'cfg [ExcludeConfig] {
+        self.excludes
+    }
+
+    /// Returns the reason given for excluding this test, if it's matched by any `[[exclude]]`
+    /// section.
+    ///
+    /// If multiple sections match, the reason from the first one is returned.
+    pub fn exclude_reason(&self, test: &TestInstance<'_>) -> Option<&'cfg str> {
+        self.excludes
+            .iter()
+            .find(|exclude| exclude.filter.matches(test))
+            .map(|exclude| exclude.reason.as_str())
+    }
+
+    /// Returns the tests quarantined through `[[quarantine]]` sections, in the order they're
+    /// defined.
+    pub fn quarantine(&self) -> &'cfg [QuarantineConfig] {
+        self.quarantine
+    }
+
+    /// Returns the reason given for quarantining this test, if it's matched by any
+    /// `[[quarantine]]` section.
+    ///
+    /// If multiple sections match, the reason from the first one is returned.
+    pub fn quarantine_reason(&self, test: &TestInstance<'_>) -> Option<&'cfg str> {
+        self.quarantine
+            .iter()
+            .find(|quarantine| quarantine.filter.matches(test))
+            .map(|quarantine| quarantine.reason.as_str())
+    }
+
+    /// Returns the number of tests to run simultaneously.
+    pub fn test_threads(&self) -> TestThreads {
+        self.custom_profile
+            .and_then(|profile| profile.test_threads)
+            .unwrap_or(self.default_profile.test_threads)
+    }
+
+    /// Returns the retry policy for this profile.
+    pub fn retries(&self) -> RetryPolicy {
+        self.platform_setting(|section| section.retries)
+            .or_else(|| self.custom_profile.and_then(|profile| profile.retries))
+            .or_else(|| self.default_platform_setting(|section| section.retries))
+            .unwrap_or(self.default_profile.retries)
+    }
+
+    /// Returns the time after which tests are treated as slow for this profile, as well as the
+    /// number of slow periods after which tests are terminated.
+    pub fn slow_timeout(&self) -> SlowTimeout {
+        self.platform_setting(|section| section.slow_timeout)
+            .or_else(|| self.custom_profile.and_then(|profile| profile.slow_timeout))
+            .or_else(|| self.default_platform_setting(|section| section.slow_timeout))
+            .unwrap_or(self.default_profile.slow_timeout)
+    }
+
+    /// Returns the time tests are given to exit after being asked to terminate, before they are
+    /// killed.
+    pub fn grace_period(&self) -> Duration {
+        self.platform_setting(|section| section.grace_period)
+            .or_else(|| self.custom_profile.and_then(|profile| profile.grace_period))
+            .or_else(|| self.default_platform_setting(|section| section.grace_period))
+            .unwrap_or(self.default_profile.grace_period)
+    }
+
+    /// Returns the maximum number of bytes of standard output and of standard error to keep for
+    /// each test, if limited.
+    ///
+    /// If a test writes more than this to a stream, only the beginning and end of its output are
+    /// kept. Not set by default.
+    pub fn max_output_size(&self) -> Option<u64> {
+        self.custom_profile
+            .and_then(|profile| profile.max_output_size)
+            .or(self.default_profile.max_output_size)
+    }
+
+    /// Returns the configuration for per-test temporary directories.
+    pub fn test_tmpdir(&self) -> TestTmpdirConfig {
+        self.custom_profile
+            .and_then(|profile| profile.test_tmpdir)
+            .unwrap_or(self.default_profile.test_tmpdir)
+    }
+
+    /// Returns the configuration for per-test output directories.
+    pub fn test_output_dir(&self) -> TestOutputDirConfig {
+        self.custom_profile
+            .and_then(|profile| profile.test_output_dir)
+            .unwrap_or(self.default_profile.test_output_dir)
+    }
+
+    /// Returns the filter expression that tests must match to be run, unless other filters are
+    /// passed in explicitly.
+    pub fn default_filter(&self) -> Option<&'cfg FilterExpr> {
+        self.custom_profile
+            .and_then(|profile| profile.default_filter.as_ref())
+            .or(self.default_profile.default_filter.as_ref())
+    }
+
+    /// Returns the maximum number of tests from the same binary that are run in a single process.
+    ///
+    /// This can be overridden for individual tests: see [`TestSettings::batch_size`].
+    pub fn batch_size(&self) -> NonZeroUsize {
+        self.custom_profile
+            .and_then(|profile| profile.batch_size)
+            .unwrap_or(self.default_profile.batch_size)
+    }
+
+    /// Returns true if libtest test binaries should be asked to report results as JSON.
+    ///
+    /// This gives more accurate per-test durations, measured by libtest itself.
+    pub fn libtest_json(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.libtest_json)
+            .unwrap_or(self.default_profile.libtest_json)
+    }
+
+    /// Returns true if each test process should be run in a network namespace of its own.
+    pub fn network_isolation(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.network_isolation)
+            .unwrap_or(self.default_profile.network_isolation)
+    }
+
+    /// Returns the environment variable configuration for test processes.
+    ///
+    /// An `[env]` section in a custom profile replaces the one in the default profile.
+    pub fn env(&self) -> &'cfg EnvConfig {
+        self.platform_setting(|section| section.env.as_ref())
+            .or_else(|| self.custom_profile.and_then(|profile| profile.env.as_ref()))
+            .or_else(|| self.default_platform_setting(|section| section.env.as_ref()))
+            .unwrap_or(&self.default_profile.env)
+    }
+
+    /// Returns the period after which tests that haven't produced any output are reported as
+    /// silent, if configured.
+    pub fn heartbeat_period(&self) -> Option<Duration> {
+        self.custom_profile
+            .and_then(|profile| profile.heartbeat_period)
+            .or(self.default_profile.heartbeat_period)
+    }
+
+    /// Returns the limits on the resources used by tests in this profile.
+    ///
+    /// Limits set in a custom profile take precedence over limits set in the default profile.
+    pub fn resource_limits(&self) -> ResourceLimits {
+        let default = self.default_profile.resource_limits;
+        self.custom_profile
+            .map_or(default, |profile| profile.resource_limits.or(default))
+    }
+
+    /// Returns the limits on how busy the machine may get before fewer tests are run at once.
+    ///
+    /// Limits set in a custom profile take precedence over limits set in the default profile.
+    pub fn throttle(&self) -> ThrottleConfig {
+        let default = self.default_profile.throttle;
+        self.custom_profile
+            .map_or(default, |profile| profile.throttle.or(default))
+    }
+
+    /// Returns the sets of CPUs that test processes are pinned to, by the global slot they run in.
+    ///
+    /// A test running in slot `n` is pinned to the set at index `n % len`. If empty, tests aren't
+    /// pinned to any CPUs.
+    pub fn cpu_affinity(&self) -> &'cfg [CpuSet] {
+        self.custom_profile
+            .and_then(|profile| profile.cpu_affinity.as_deref())
+            .unwrap_or(&self.default_profile.cpu_affinity)
+    }
+
+    /// Returns the nice value that test processes are run at, from 0 to 19.
+    ///
+    /// Tests are only ever run at a lower priority than nextest: if nextest is already running at
+    /// this nice value or higher, test processes aren't changed. On Windows, this is mapped to a
+    /// priority class.
+    pub fn nice(&self) -> u8 {
+        self.custom_profile
+            .and_then(|profile| profile.nice)
+            .or(self.default_profile.nice)
+            .unwrap_or(0)
+    }
+
+    /// Returns true if core dumps written by crashing tests should be collected.
+    ///
+    /// Only supported on Unix.
+    pub fn core_dumps(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.core_dumps)
+            .unwrap_or(self.default_profile.core_dumps)
+    }
+
+    /// Returns true if tests that failed should be run again under the
+    /// [recorder](Self::recorder) once all tests have finished.
+    pub fn record_failures(&self) -> bool {
+        self.custom_profile
+            .and_then(|profile| profile.record_failures)
+            .unwrap_or(self.default_profile.record_failures)
+    }
+
+    /// Returns the command that failing tests are recorded under, for example `rr record`.
+    pub fn recorder(&self) -> &WrapperCommand {
+        self.custom_profile
+            .and_then(|profile| profile.recorder.as_ref())
+            .unwrap_or(&self.default_profile.recorder)
+    }
+
+    /// Returns the configuration for the list of slowest tests printed at the end of a run.
+    ///
+    /// Settings in a custom profile take precedence over settings in the default profile.
+    pub fn slowest_tests(&self) -> SlowestTests {
+        let default = self.default_profile.slowest_tests;
+        self.custom_profile
+            .map_or(default, |profile| profile.slowest_tests.or(default))
+    }
+
+    /// Returns the colors and styles used by the reporter.
+    ///
+    /// Settings in a custom profile take precedence over settings in the default profile.
+    pub fn colors(&self) -> ColorConfig {
+        let default = self.default_profile.colors;
+        self.custom_profile
+            .map_or(default, |profile| profile.colors.or(default))
+    }
+
+    /// Returns the settings for an individual test, taking `[[profile.<name>.overrides]]` sections
+    /// into account.
+    ///
+    /// Overrides in a custom profile take precedence over overrides in the default profile. Within a
+    /// profile, the first override that matches the test and specifies a setting is used for that
+    /// setting.
+    pub fn test_settings(&self, test: &TestInstance<'_>) -> TestSettings {
+        self.test_settings_impl(|filter| filter.matches(test))
+    }
+
+    /// Returns the test status level.
+    pub fn status_level(&self) -> StatusLevel {
+        self.custom_profile
+            .map(|profile| profile.status_level)
+            .flatten()
+            .unwrap_or(self.default_profile.status_level)
+    }
+
+    /// Returns the failure output config for this profile.
+    pub fn failure_output(&self) -> TestOutputDisplay {
+        self.custom_profile
+            .map(|profile| profile.failure_output)
+            .flatten()
+            .unwrap_or(self.default_profile.failure_output)
+    }
+
+    /// Returns the failure output config for this profile.
+    pub fn success_output(&self) -> TestOutputDisplay {
+        self.custom_profile
+            .map(|profile| profile.success_output)
+            .flatten()
+            .unwrap_or(self.default_profile.success_output)
+    }
+
+    /// Returns the fail-fast config for this profile.
+    pub fn fail_fast(&self) -> bool {
+        self.custom_profile
+            .map(|profile| profile.fail_fast)
+            .flatten()
+            .unwrap_or(self.default_profile.fail_fast)
+    }
+
+    /// Returns what to do if no tests are run because none of them match the filters.
+    pub fn no_tests(&self) -> NoTestsBehavior {
+        self.custom_profile
+            .and_then(|profile| profile.no_tests)
+            .unwrap_or(self.default_profile.no_tests)
+    }
+
+    /// Returns whether tests are told to produce colored output.
+    pub fn test_color(&self) -> TestColor {
+        self.custom_profile
+            .and_then(|profile| profile.test_color)
+            .unwrap_or(self.default_profile.test_color)
+    }
+
+    /// Returns the value of `RUST_BACKTRACE` set for tests.
+    ///
+    /// If `RUST_BACKTRACE` is set in nextest's environment, tests inherit it instead.
+    pub fn rust_backtrace(&self) -> RustBacktrace {
+        self.custom_profile
+            .and_then(|profile| profile.rust_backtrace)
+            .unwrap_or(self.default_profile.rust_backtrace)
+    }
+
+    /// Returns the JUnit configuration for this profile.
+    pub fn junit(&self) -> Option<NextestJunitConfig<'cfg>> {
+        let path = self
+            .custom_profile
+            .map(|profile| &profile.junit.path)
+            .unwrap_or(&self.default_profile.junit.path)
+            .as_deref();
+
+        path.map(|path| {
+            let path = self.store_dir.join(path);
+            let report_name = self
+                .custom_profile
+                .map(|profile| profile.junit.report_name.as_deref())
+                .flatten()
+                .unwrap_or(&self.default_profile.junit.report_name);
+            NextestJunitConfig { path, report_name }
+        })
+    }
+
+    /// Returns the absolute path to write a TAP report to for this profile, if configured.
+    pub fn tap_path(&self) -> Option<Utf8PathBuf> {
+        self.custom_profile
+            .and_then(|profile| profile.tap.path.as_deref())
+            .or(self.default_profile.tap.path.as_deref())
+            .map(|path| self.store_dir.join(path))
+    }
+
+    /// Returns the absolute path to write an HTML report to for this profile, if configured.
+    pub fn html_path(&self) -> Option<Utf8PathBuf> {
+        self.custom_profile
+            .and_then(|profile| profile.html.path.as_deref())
+            .or(self.default_profile.html.path.as_deref())
+            .map(|path| self.store_dir.join(path))
+    }
+
+    /// Returns the absolute path to write a Chrome trace of the test schedule to for this profile,
+    /// if configured.
+    pub fn chrome_trace_path(&self) -> Option<Utf8PathBuf> {
+        self.custom_profile
+            .and_then(|profile| profile.chrome_trace.path.as_deref())
+            .or(self.default_profile.chrome_trace.path.as_deref())
+            .map(|path| self.store_dir.join(path))
+    }
+
+    /// Returns the OpenTelemetry export configuration for this profile, if an endpoint is
+    /// configured.
+    pub fn otlp(&self) -> Option<NextestOtlpConfig<'cfg>> {
+        // A custom profile that sets an endpoint replaces the default profile's section entirely,
+        // so that headers meant for one collector aren't sent to another.
+        let otlp = self
+            .custom_profile
+            .map(|profile| &profile.otlp)
+            .filter(|otlp| otlp.endpoint.is_some())
+            .unwrap_or(&self.default_profile.otlp);
+        otlp.endpoint.as_deref().map(|endpoint| NextestOtlpConfig {
+            endpoint,
+            headers: &otlp.headers,
+        })
+    }
+
+    /// Returns the configuration for notifications sent at the end of a run, if a URL is
+    /// configured.
+    pub fn notify(&self) -> Option<NextestNotifyConfig<'cfg>> {
+        // As with OpenTelemetry export, a custom profile that sets a URL replaces the default
+        // profile's section entirely.
+        let notify = self
+            .custom_profile
+            .map(|profile| &profile.notify)
+            .filter(|notify| notify.url.is_some())
+            .unwrap_or(&self.default_profile.notify);
+        notify.url.as_deref().map(|url| NextestNotifyConfig {
+            url,
+            format: notify.format,
+            template: &notify.template,
+            headers: &notify.headers,
+        })
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    pub(crate) fn workspace_root(&self) -> &'cfg Utf8Path {
+        self.workspace_root
+    }
+
+    /// Returns a setting from the first platform-specific section of the custom profile that
+    /// specifies it.
+    fn platform_setting<T>(
+        &self,
+        get: impl Fn(&'cfg PlatformProfileImpl) -> Option<T>,
+    ) -> Option<T> {
+        self.custom_platform.iter().find_map(|section| get(section))
+    }
+
+    /// Returns a setting from the first platform-specific section of the default profile that
+    /// specifies it.
+    fn default_platform_setting<T>(
+        &self,
+        get: impl Fn(&'cfg PlatformProfileImpl) -> Option<T>,
+    ) -> Option<T> {
+        self.default_platform
+            .iter()
+            .find_map(|section| get(section))
+    }
+
+    fn test_settings_impl(&self, matches: impl Fn(&FilterExpr) -> bool) -> TestSettings {
+        let mut retries = None;
+        let mut slow_timeout = None;
+        let mut heartbeat_period = None;
+        let mut grace_period = None;
+        let mut threads_required = None;
+        let mut priority = None;
+        let mut batch_size = None;
+        let mut test_group = None;
+        let mut wrapper = None;
+        let mut resource_limits = ResourceLimits::default();
+        let mut disable_aslr = None;
+        let mut failure_output = None;
+        let mut success_output = None;
+
+        let overrides = self
+            .custom_profile
+            .map(|profile| profile.overrides.as_slice())
+            .unwrap_or(&[])
+            .iter()
+            .chain(&self.default_profile.overrides);
+        for override_ in overrides {
+            if !matches(&override_.filter) {
+                continue;
+            }
+            retries = retries.or(override_.retries);
+            slow_timeout = slow_timeout.or(override_.slow_timeout);
+            heartbeat_period = heartbeat_period.or(override_.heartbeat_period);
+            grace_period = grace_period.or(override_.grace_period);
+            threads_required = threads_required.or(override_.threads_required);
+            priority = priority.or(override_.priority);
+            batch_size = batch_size.or(override_.batch_size);
+            test_group = test_group.or(override_.test_group.as_ref());
+            wrapper = wrapper.or(override_.wrapper.as_ref());
+            resource_limits = resource_limits.or(override_.resource_limits);
+            disable_aslr = disable_aslr.or(override_.disable_aslr);
+            failure_output = failure_output.or(override_.failure_output);
+            success_output = success_output.or(override_.success_output);
+        }
+
+        TestSettings {
+            retries: retries.unwrap_or_else(|| self.retries()),
+            slow_timeout: slow_timeout.unwrap_or_else(|| self.slow_timeout()),
+            heartbeat_period: heartbeat_period.or_else(|| self.heartbeat_period()),
+            grace_period: grace_period.unwrap_or_else(|| self.grace_period()),
+            threads_required: threads_required.unwrap_or_default(),
+            priority: priority.unwrap_or(0),
+            batch_size: batch_size.unwrap_or_else(|| self.batch_size()),
+            test_group: test_group.cloned(),
+            wrapper: wrapper.cloned(),
+            resource_limits: resource_limits.or(self.resource_limits()),
+            disable_aslr: disable_aslr.unwrap_or(false),
+            failure_output: failure_output.unwrap_or_else(|| self.failure_output()),
+            success_output: success_output.unwrap_or_else(|| self.success_output()),
+        }
+    }
+}
+
+/// Settings for an individual test, returned by [`NextestProfile::test_settings`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TestSettings {
+    retries: RetryPolicy,
+    slow_timeout: SlowTimeout,
+    heartbeat_period: Option<Duration>,
+    grace_period: Duration,
+    threads_required: ThreadsRequired,
+    priority: i32,
+    batch_size: NonZeroUsize,
+    test_group: Option<String>,
+    wrapper: Option<WrapperCommand>,
+    resource_limits: ResourceLimits,
+    disable_aslr: bool,
+    failure_output: TestOutputDisplay,
+    success_output: TestOutputDisplay,
+}
+
+impl TestSettings {
+    /// Returns the retry policy for this test.
+    pub fn retries(&self) -> RetryPolicy {
+        self.retries
+    }
+
+    /// Returns the slow timeout for this test.
+    pub fn slow_timeout(&self) -> SlowTimeout {
+        self.slow_timeout
+    }
+
+    /// Returns the period after which this test is reported as silent if it hasn't produced any
+    /// output, if configured.
+    pub fn heartbeat_period(&self) -> Option<Duration> {
+        self.heartbeat_period
+    }
+
+    /// Returns the time this test is given to exit after being asked to terminate, before it is
+    /// killed.
+    pub fn grace_period(&self) -> Duration {
+        self.grace_period
+    }
+
+    /// Returns the number of test threads this test requires while running.
+    ///
+    /// The runner reserves this many threads (capped to the total number of test threads) for the
+    /// test, so that fewer tests run alongside it.
+    pub fn threads_required(&self) -> ThreadsRequired {
+        self.threads_required
+    }
+
+    /// Returns the scheduling priority of this test.
+    ///
+    /// Tests with a higher priority are started before tests with a lower one. The default is 0.
+    pub fn priority(&self) -> i32 {
+        self.priority
+    }
+
+    /// Returns the maximum number of tests, including this one, that are run in a single process
+    /// along with this test.
+    ///
+    /// Only tests from the same binary with the same settings are run together. If this is 1, the
+    /// test is run in a process of its own.
+    pub fn batch_size(&self) -> NonZeroUsize {
+        self.batch_size
+    }
+
+    /// Returns the name of the test group this test is in, if any.
+    pub fn test_group(&self) -> Option<&str> {
+        self.test_group.as_deref()
+    }
+
+    /// Returns the command this test is run under, if any.
+    pub fn wrapper(&self) -> Option<&WrapperCommand> {
+        self.wrapper.as_ref()
+    }
+
+    /// Returns the limits on the resources this test may use.
+    pub fn resource_limits(&self) -> ResourceLimits {
+        self.resource_limits
+    }
+
+    /// Returns true if this test is run with address space layout randomization (ASLR) disabled,
+    /// so that addresses are the same from one run to the next. Only supported on Linux.
+    pub fn disable_aslr(&self) -> bool {
+        self.disable_aslr
+    }
+
+    /// Returns when to display the output of this test if it fails.
+    pub fn failure_output(&self) -> TestOutputDisplay {
+        self.failure_output
+    }
+
+    /// Returns when to display the output of this test if it passes.
+    pub fn success_output(&self) -> TestOutputDisplay {
+        self.success_output
+    }
+}
+
+/// Limits on the resources a test may use, returned by [`TestSettings::resource_limits`].
+///
+/// In configuration, this is specified as a table, for example
+/// `resource-limits = { address-space = "4GiB", cpu-time = "5m" }`. Limits are only enforced on
+/// Unix.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ResourceLimits {
+    #[serde(default, deserialize_with = "deserialize_byte_size")]
+    address_space: Option<u64>,
+    #[serde(default, with = "humantime_serde")]
+    cpu_time: Option<Duration>,
+}
+
+impl ResourceLimits {
+    pub(crate) fn new(address_space: Option<u64>, cpu_time: Option<Duration>) -> Self {
+        Self {
+            address_space,
+            cpu_time,
+        }
+    }
+
+    /// Returns the maximum size of a test process's virtual memory in bytes, if limited
+    /// (`RLIMIT_AS`).
+    pub fn address_space(&self) -> Option<u64> {
+        self.address_space
+    }
+
+    /// Returns the maximum amount of CPU time a test process may use, if limited (`RLIMIT_CPU`).
+    pub fn cpu_time(&self) -> Option<Duration> {
+        self.cpu_time
+    }
+
+    /// Returns true if no limits are set.
+    pub fn is_empty(&self) -> bool {
+        self.address_space.is_none() && self.cpu_time.is_none()
+    }
+
+    /// Fills in limits that aren't set in `self` from `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            address_space: self.address_space.or(other.address_space),
+            cpu_time: self.cpu_time.or(other.cpu_time),
+        }
+    }
+}
+
+/// Limits on how busy the machine may get before fewer tests are run at once, returned by
+/// [`NextestProfile::throttle`].
+///
+/// In configuration, this is specified as a table, for example
+/// `throttle = { max-load = 1.5, min-available-memory = "2GiB" }`. While the machine is over
+/// either limit, the runner reduces the number of test threads in use, and raises it again once
+/// the machine has recovered.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ThrottleConfig {
+    #[serde(default)]
+    max_load: Option<f64>,
+    #[serde(default, deserialize_with = "deserialize_byte_size")]
+    min_available_memory: Option<u64>,
+}
+
+impl ThrottleConfig {
+    /// Returns the maximum one-minute load average per CPU, if limited. Only supported on Unix.
+    pub fn max_load(&self) -> Option<f64> {
+        self.max_load
+    }
+
+    /// Returns the minimum amount of available memory in bytes, if limited. Only supported on
+    /// Linux.
+    pub fn min_available_memory(&self) -> Option<u64> {
+        self.min_available_memory
+    }
+
+    /// Returns true if no limits are set.
+    pub fn is_empty(&self) -> bool {
+        self.max_load.is_none() && self.min_available_memory.is_none()
+    }
+
+    /// Fills in limits that aren't set in `self` from `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            max_load: self.max_load.or(other.max_load),
+            min_available_memory: self.min_available_memory.or(other.min_available_memory),
+        }
+    }
+}
+
+/// A set of CPUs that a test process may run on. Part of [`NextestProfile::cpu_affinity`].
+///
+/// In configuration, this is specified as a list of CPU numbers and inclusive ranges, in the same
+/// format as Linux's `cpuset` lists: for example, `"0-3,8"` is the set of CPUs 0, 1, 2, 3 and 8. A
+/// single CPU can also be specified as a number. CPUs are numbered from 0, up to
+/// [`Self::MAX_CPUS`].
+#[derive(Copy, Clone, Eq, PartialEq)]
+pub struct CpuSet {
+    bits: [u64; CpuSet::WORDS],
+}
+
+impl CpuSet {
+    /// The number of CPUs that can be part of a set, matching `CPU_SETSIZE` on Linux.
+    pub const MAX_CPUS: usize = 1024;
+
+    const WORDS: usize = Self::MAX_CPUS / 64;
+
+    /// Returns true if `cpu` is part of the set.
+    pub fn contains(&self, cpu: usize) -> bool {
+        cpu < Self::MAX_CPUS && self.bits[cpu / 64] & (1 << (cpu % 64)) != 0
+    }
+
+    /// Returns the CPUs in the set, in ascending order.
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..Self::MAX_CPUS).filter(move |&cpu| self.contains(cpu))
+    }
+
+    fn empty() -> Self {
+        Self {
+            bits: [0; Self::WORDS],
+        }
+    }
+
+    fn insert(&mut self, cpu: usize) {
+        self.bits[cpu / 64] |= 1 << (cpu % 64);
+    }
+}
+
+impl FromStr for CpuSet {
+    type Err = CpuSetParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse_cpu = |cpu: &str| {
+            cpu.trim()
+                .parse::<usize>()
+                .ok()
+                .filter(|&cpu| cpu < Self::MAX_CPUS)
+                .ok_or_else(|| CpuSetParseError::new(s))
+        };
+
+        let mut set = Self::empty();
+        for part in s.split(',') {
+            let (start, end) = match part.split_once('-') {
+                Some((start, end)) => (parse_cpu(start)?, parse_cpu(end)?),
+                None => {
+                    let cpu = parse_cpu(part)?;
+                    (cpu, cpu)
+                }
+            };
+            if start > end {
+                return Err(CpuSetParseError::new(s));
+            }
+            for cpu in start..=end {
+                set.insert(cpu);
+            }
+        }
+        Ok(set)
+    }
+}
+
+impl fmt::Display for CpuSet {
+    /// Writes out the set in the same format it's parsed from, with ranges merged together.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut cpus = self.iter().peekable();
+        let mut first = true;
+        while let Some(start) = cpus.next() {
+            let mut end = start;
+            while cpus.peek() == Some(&(end + 1)) {
+                end = cpus.next().expect("peeked CPU exists");
+            }
+            if !first {
+                write!(f, ",")?;
+            }
+            first = false;
+            if start == end {
+                write!(f, "{}", start)?;
+            } else {
+                write!(f, "{}-{}", start, end)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for CpuSet {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "CpuSet({})", self)
+    }
+}
+
+impl<'de> Deserialize<'de> for CpuSet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct CpuSetVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for CpuSetVisitor {
+            type Value = CpuSet;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a CPU number, or a list of CPUs like \"0-3,8\"")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.to_string().parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.to_string().parse().map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(CpuSetVisitor)
+    }
+}
+
+fn deserialize_byte_size<'de, D>(deserializer: D) -> Result<Option<u64>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    struct ByteSizeVisitor;
+
+    impl<'de> serde::de::Visitor<'de> for ByteSizeVisitor {
+        type Value = Option<u64>;
+
+        fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+            write!(
+                formatter,
+                "a number of bytes, or a size like \"512MiB\" or \"4GB\""
+            )
+        }
+
+        fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            Ok(Some(v))
+        }
+
+        fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            match u64::try_from(v) {
+                Ok(v) => Ok(Some(v)),
+                Err(_) => Err(serde::de::Error::invalid_value(
+                    serde::de::Unexpected::Signed(v),
+                    &self,
+                )),
+            }
+        }
+
+        fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+        where
+            E: serde::de::Error,
+        {
+            parse_byte_size(v).map(Some).ok_or_else(|| {
+                serde::de::Error::invalid_value(serde::de::Unexpected::Str(v), &self)
+            })
+        }
+    }
+
+    deserializer.deserialize_any(ByteSizeVisitor)
+}
+
+/// Parses a size like "512MiB" into a number of bytes.
+///
+/// Suffixes with an "i" are powers of 1024, and suffixes without are powers of 1000.
+fn parse_byte_size(input: &str) -> Option<u64> {
+    let input = input.trim();
+    let split = input
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(input.len());
+    let (number, suffix) = input.split_at(split);
+    let number: u64 = number.parse().ok()?;
+    let multiplier: u64 = match suffix.trim() {
+        "" | "B" => 1,
+        "KB" => 1000,
+        "MB" => 1000 * 1000,
+        "GB" => 1000 * 1000 * 1000,
+        "TB" => 1000 * 1000 * 1000 * 1000,
+        "KiB" => 1 << 10,
+        "MiB" => 1 << 20,
+        "GiB" => 1 << 30,
+        "TiB" => 1 << 40,
+        _ => return None,
+    };
+    number.checked_mul(multiplier)
+}
+
+/// What to do if no tests are run because none of them match the filters, returned by
+/// [`NextestProfile::no_tests`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NoTestsBehavior {
+    /// Treat the run as a success.
+    Pass,
+
+    /// Print a warning, but treat the run as a success.
+    Warn,
+
+    /// Treat the run as a failure, with exit code
+    /// [`NO_TESTS_RUN`](nextest_metadata::NextestExitCode::NO_TESTS_RUN).
+    Fail,
+}
+
+impl NoTestsBehavior {
+    /// String representations of all known variants.
+    pub fn variants() -> &'static [&'static str] {
+        &["pass", "warn", "fail"]
+    }
+}
+
+impl FromStr for NoTestsBehavior {
+    type Err = NoTestsBehaviorParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let val = match s {
+            "pass" => NoTestsBehavior::Pass,
+            "warn" => NoTestsBehavior::Warn,
+            "fail" => NoTestsBehavior::Fail,
+            other => return Err(NoTestsBehaviorParseError::new(other)),
+        };
+        Ok(val)
+    }
+}
+
+impl fmt::Display for NoTestsBehavior {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NoTestsBehavior::Pass => write!(f, "pass"),
+            NoTestsBehavior::Warn => write!(f, "warn"),
+            NoTestsBehavior::Fail => write!(f, "fail"),
+        }
+    }
+}
+
+/// Whether tests are told to produce colored output, returned by [`NextestProfile::test_color`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestColor {
+    /// Leave it up to tests. Since their output isn't a terminal, most tests don't produce colored
+    /// output, but some do anyway.
+    Auto,
+
+    /// Tell tests to produce colored output, through the `CARGO_TERM_COLOR` and `CLICOLOR_FORCE`
+    /// environment variables. Colors in captured output are kept.
+    Always,
+
+    /// Tell tests not to produce colored output, through the `CARGO_TERM_COLOR` and `NO_COLOR`
+    /// environment variables. Any colors that tests produce anyway are stripped from captured
+    /// output, so they don't show up in reports either.
+    Never,
+}
+
+impl TestColor {
+    /// Returns the environment variables to set for tests.
+    pub(crate) fn env(self) -> &'static [(&'static str, &'static str)] {
+        match self {
+            TestColor::Auto => &[],
+            TestColor::Always => &[("CARGO_TERM_COLOR", "always"), ("CLICOLOR_FORCE", "1")],
+            TestColor::Never => &[("CARGO_TERM_COLOR", "never"), ("NO_COLOR", "1")],
+        }
+    }
+}
+
+/// The value of `RUST_BACKTRACE` set for tests, returned by [`NextestProfile::rust_backtrace`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+pub enum RustBacktrace {
+    /// `RUST_BACKTRACE=0`: panics don't print backtraces.
+    #[serde(rename = "0")]
+    Off,
+
+    /// `RUST_BACKTRACE=1`: panics print short backtraces, with frames from the standard library's
+    /// panic machinery left out.
+    #[serde(rename = "1")]
+    Short,
+
+    /// `RUST_BACKTRACE=full`: panics print full backtraces.
+    #[serde(rename = "full")]
+    Full,
+}
+
+impl RustBacktrace {
+    /// Returns the value of `RUST_BACKTRACE` for this setting.
+    pub fn as_env_value(self) -> &'static str {
+        match self {
+            RustBacktrace::Off => "0",
+            RustBacktrace::Short => "1",
+            RustBacktrace::Full => "full",
+        }
+    }
+}
+
+/// The number of tests to run simultaneously, returned by [`NextestProfile::test_threads`].
+///
+/// This is specified either as a positive number, as a negative number which is added to the number
+/// of logical CPUs, or as `"num-cpus"` or `"num-cpus*<N>"`. It's resolved by the runner once it
+/// starts, through [`Self::compute`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TestThreads {
+    /// Run this many tests simultaneously.
+    Count(NonZeroUsize),
+
+    /// Run as many tests simultaneously as there are logical CPUs, plus this offset, which is
+    /// usually zero or negative.
+    NumCpusPlus(isize),
+
+    /// Run this many tests simultaneously per logical CPU.
+    NumCpusTimes(NonZeroUsize),
+}
+
+impl TestThreads {
+    /// Returns the number of tests to run simultaneously, given the number of logical CPUs.
+    ///
+    /// The result is always at least 1.
+    pub fn compute(self, num_cpus: usize) -> usize {
+        let threads = match self {
+            TestThreads::Count(count) => count.get(),
+            TestThreads::NumCpusPlus(offset) if offset < 0 => {
+                num_cpus.saturating_sub(offset.unsigned_abs())
+            }
+            TestThreads::NumCpusPlus(offset) => num_cpus.saturating_add(offset as usize),
+            TestThreads::NumCpusTimes(multiplier) => num_cpus.saturating_mul(multiplier.get()),
+        };
+        threads.max(1)
+    }
+}
+
+impl Default for TestThreads {
+    fn default() -> Self {
+        TestThreads::NumCpusPlus(0)
+    }
+}
+
+impl FromStr for TestThreads {
+    type Err = TestThreadsParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "num-cpus" {
+            return Ok(TestThreads::NumCpusPlus(0));
+        }
+        if let Some(multiplier) = s.strip_prefix("num-cpus*") {
+            return multiplier
+                .trim()
+                .parse()
+                .map(TestThreads::NumCpusTimes)
+                .map_err(|_| TestThreadsParseError::new(s));
+        }
+        match s.parse::<isize>() {
+            Ok(threads) => {
+                TestThreads::from_int(threads).ok_or_else(|| TestThreadsParseError::new(s))
+            }
+            Err(_) => Err(TestThreadsParseError::new(s)),
+        }
+    }
+}
+
+impl TestThreads {
+    fn from_int(threads: isize) -> Option<Self> {
+        if threads < 0 {
+            Some(TestThreads::NumCpusPlus(threads))
+        } else {
+            NonZeroUsize::new(threads as usize).map(TestThreads::Count)
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for TestThreads {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct TestThreadsVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for TestThreadsVisitor {
+            type Value = TestThreads;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    formatter,
+                    "a non-zero integer, \"num-cpus\" or \"num-cpus*<N>\""
+                )
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                isize::try_from(v)
+                    .ok()
+                    .and_then(TestThreads::from_int)
+                    .ok_or_else(|| {
+                        E::invalid_value(serde::de::Unexpected::Unsigned(v), &"a non-zero integer")
+                    })
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                isize::try_from(v)
+                    .ok()
+                    .and_then(TestThreads::from_int)
+                    .ok_or_else(|| {
+                        E::invalid_value(serde::de::Unexpected::Signed(v), &"a non-zero integer")
+                    })
+            }
+        }
+
+        deserializer.deserialize_any(TestThreadsVisitor)
+    }
+}
+
+/// The number of test threads a test requires, returned by [`TestSettings::threads_required`].
+///
+/// In configuration, this is specified either as a number (`threads-required = 4`) or as
+/// `threads-required = "num-test-threads"`.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ThreadsRequired {
+    /// The test requires this many threads.
+    Count(NonZeroUsize),
+
+    /// The test requires all test threads, so it runs completely serially: it doesn't start until
+    /// all running tests have finished, and no other tests are started while it runs.
+    NumTestThreads,
+}
+
+impl ThreadsRequired {
+    /// Returns the number of threads to reserve for the test, given the total number of test
+    /// threads.
+    ///
+    /// The number is capped so that tests requiring more threads than are available can still run.
+    pub fn compute(self, test_threads: usize) -> usize {
+        match self {
+            ThreadsRequired::Count(count) => count.get().min(test_threads),
+            ThreadsRequired::NumTestThreads => test_threads,
+        }
+    }
+}
+
+impl Default for ThreadsRequired {
+    fn default() -> Self {
+        ThreadsRequired::Count(NonZeroUsize::new(1).expect("1 is non-zero"))
+    }
+}
+
+impl<'de> Deserialize<'de> for ThreadsRequired {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct ThreadsRequiredVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for ThreadsRequiredVisitor {
+            type Value = ThreadsRequired;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a positive integer or \"num-test-threads\"")
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                match v {
+                    "num-test-threads" => Ok(ThreadsRequired::NumTestThreads),
+                    other => Err(E::invalid_value(
+                        serde::de::Unexpected::Str(other),
+                        &"\"num-test-threads\"",
+                    )),
+                }
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                usize::try_from(v)
+                    .ok()
+                    .and_then(NonZeroUsize::new)
+                    .map(ThreadsRequired::Count)
+                    .ok_or_else(|| {
+                        E::invalid_value(serde::de::Unexpected::Unsigned(v), &"a positive integer")
+                    })
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                usize::try_from(v)
+                    .ok()
+                    .and_then(NonZeroUsize::new)
+                    .map(ThreadsRequired::Count)
+                    .ok_or_else(|| {
+                        E::invalid_value(serde::de::Unexpected::Signed(v), &"a positive integer")
+                    })
+            }
+        }
+
+        deserializer.deserialize_any(ThreadsRequiredVisitor)
+    }
+}
+
+/// Configuration for the temporary directories created for each test, returned by
+/// [`NextestProfile::test_tmpdir`].
+///
+/// In configuration, this is specified as a table, for example
+/// `test-tmpdir = { enabled = true, retain = "on-failure" }`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestTmpdirConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    retain: RetainTmpdir,
+}
+
+impl TestTmpdirConfig {
+    /// Returns true if a temporary directory is created for each attempt of each test.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns when temporary directories are kept around after a test finishes.
+    pub fn retain(&self) -> RetainTmpdir {
+        self.retain
+    }
+}
+
+/// Configuration for the output directories created for each test, returned by
+/// [`NextestProfile::test_output_dir`].
+///
+/// In configuration, this is specified as a table, for example
+/// `test-output-dir = { enabled = true, retain = "always" }`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestOutputDirConfig {
+    #[serde(default)]
+    enabled: bool,
+    #[serde(default)]
+    retain: RetainTmpdir,
+}
+
+impl TestOutputDirConfig {
+    /// Returns true if an output directory is created for each attempt of each test.
+    pub fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Returns when output directories are kept around after a test finishes. Empty directories
+    /// are always removed.
+    pub fn retain(&self) -> RetainTmpdir {
+        self.retain
+    }
+}
+
+/// When to keep a directory created for a test after it finishes, part of a [`TestTmpdirConfig`]
+/// or a [`TestOutputDirConfig`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RetainTmpdir {
+    /// Always delete the directory.
+    Never,
+
+    /// Keep the directory if the test failed, so that its contents can be inspected.
+    ///
+    /// This is the default.
+    OnFailure,
+
+    /// Always keep the directory.
+    Always,
+}
+
+impl Default for RetainTmpdir {
+    fn default() -> Self {
+        RetainTmpdir::OnFailure
+    }
+}
+
+/// The environment that test processes are run in, defined in a `[profile.<name>.env]` section.
+///
+/// Returned by [`NextestProfile::env`].
+#[derive(Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct EnvConfig {
+    #[serde(default)]
+    set: BTreeMap<String, String>,
+    #[serde(default)]
+    remove: Vec<String>,
+    #[serde(default)]
+    sanitize: bool,
+    #[serde(default)]
+    passthrough: Vec<String>,
+}
+
+impl EnvConfig {
+    /// Returns the environment variables set for every test process, on top of the inherited
+    /// environment.
+    pub fn set(&self) -> &BTreeMap<String, String> {
+        &self.set
+    }
+
+    /// Returns the environment variables removed from the inherited environment.
+    pub fn remove(&self) -> &[String] {
+        &self.remove
+    }
+
+    /// Returns true if test processes only inherit the variables in [`Self::passthrough`], rather
+    /// than nextest's entire environment.
+    pub fn sanitize(&self) -> bool {
+        self.sanitize
+    }
+
+    /// Returns the environment variables inherited from nextest's environment if
+    /// [`Self::sanitize`] is true.
+    pub fn passthrough(&self) -> &[String] {
+        &self.passthrough
+    }
+
+    /// Returns true if test processes inherit nextest's entire environment unchanged.
+    pub fn inherits_all(&self) -> bool {
+        !self.sanitize && self.remove.is_empty()
+    }
+
+    /// Returns the variables inherited from the given environment, after `sanitize`,
+    /// `passthrough` and `remove` are applied.
+    pub fn inherited_vars<I>(&self, vars: I) -> Vec<(OsString, OsString)>
+    where
+        I: IntoIterator<Item = (OsString, OsString)>,
+    {
+        vars.into_iter()
+            .filter(|(name, _)| {
+                // Variables with non-UTF-8 names can't be listed in the configuration.
+                let name = name.to_str();
+                let listed = |list: &[String]| {
+                    name.map_or(false, |name| {
+                        list.iter().any(|other| env_name_eq(name, other))
+                    })
+                };
+                (!self.sanitize || listed(&self.passthrough)) && !listed(&self.remove)
+            })
+            .collect()
+    }
+}
+
+/// Environment variable names are case-insensitive on Windows.
+fn env_name_eq(a: &str, b: &str) -> bool {
+    if cfg!(windows) {
+        a.eq_ignore_ascii_case(b)
+    } else {
+        a == b
+    }
+}
+
+/// A test group, defined in a `[test-groups.<name>]` section.
+///
+/// Tests are assigned to a group through the `test-group` setting in overrides. The runner limits
+/// the number of tests in a group that run at the same time, independently of tests outside the
+/// group.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestGroupConfig {
+    max_threads: NonZeroUsize,
+    #[serde(default)]
+    scope: TestGroupScope,
+}
+
+impl TestGroupConfig {
+    /// Returns the maximum number of tests in this group that may run at the same time.
+    ///
+    /// If the group is scoped to packages or binaries, this limit applies to each package or
+    /// binary separately.
+    pub fn max_threads(&self) -> NonZeroUsize {
+        self.max_threads
+    }
+
+    /// Returns what the group's limit applies to.
+    pub fn scope(&self) -> TestGroupScope {
+        self.scope
+    }
+}
+
+/// What a test group's limit applies to, returned by [`TestGroupConfig::scope`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum TestGroupScope {
+    /// The limit applies to all tests in the group together.
+    Global,
+
+    /// The limit applies to the tests in each package separately.
+    Package,
+
+    /// The limit applies to the tests in each binary separately.
+    Binary,
+}
+
+impl Default for TestGroupScope {
+    fn default() -> Self {
+        TestGroupScope::Global
+    }
+}
+
+/// A set of tests that are never run, defined in an `[[exclude]]` section.
+///
+/// Excluded tests are skipped regardless of the filters passed in, and the reason they're excluded
+/// is shown in reports. This is meant for tests that are known to be broken, so that skipping them
+/// leaves an audit trail.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExcludeConfig {
+    #[serde(deserialize_with = "deserialize_filter_expr")]
+    filter: FilterExpr,
+    reason: String,
+}
+
+impl ExcludeConfig {
+    /// Returns the filter expression matching the excluded tests.
+    pub fn filter(&self) -> &FilterExpr {
+        &self.filter
+    }
+
+    /// Returns the reason the tests are excluded.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// A set of tests whose failures don't fail the run, defined in a `[[quarantine]]` section.
+///
+/// Quarantined tests are run as usual, but their failures are reported separately rather than
+/// counted as failures. This is meant for flaky tests that should stay visible without blocking
+/// CI.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct QuarantineConfig {
+    #[serde(deserialize_with = "deserialize_filter_expr")]
+    filter: FilterExpr,
+    reason: String,
+}
+
+impl QuarantineConfig {
+    /// Returns the filter expression matching the quarantined tests.
+    pub fn filter(&self) -> &FilterExpr {
+        &self.filter
+    }
+
+    /// Returns the reason the tests are quarantined.
+    pub fn reason(&self) -> &str {
+        &self.reason
+    }
+}
+
+/// A setup script, defined in a `[script.<name>]` section.
+///
+/// The setup command is run before any tests, and the teardown command (if specified) is run after
+/// all tests have finished.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ScriptConfig {
+    pub(crate) setup: ScriptCommand,
+    #[serde(default)]
+    pub(crate) teardown: Option<ScriptCommand>,
+}
+
+impl ScriptConfig {
+    /// Returns the command run before tests are executed.
+    pub fn setup(&self) -> &ScriptCommand {
+        &self.setup
+    }
+
+    /// Returns the command run after tests are executed, if any.
+    pub fn teardown(&self) -> Option<&ScriptCommand> {
+        self.teardown.as_ref()
+    }
+}
+
+/// A command to run as part of a [`ScriptConfig`].
+///
+/// Commands are specified as strings and split on whitespace, similar to target runners.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(try_from = "String")]
+pub struct ScriptCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl ScriptCommand {
+    /// Returns the program to run.
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Returns the arguments passed to the program.
+    pub fn args(&self) -> impl Iterator<Item = &str> {
+        self.args.iter().map(AsRef::as_ref)
+    }
+}
+
+impl TryFrom<String> for ScriptCommand {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut iter = value.split_whitespace();
+        let program = iter.next().ok_or("script command must not be empty")?;
+        Ok(Self {
+            program: program.to_owned(),
+            args: iter.map(String::from).collect(),
+        })
+    }
+}
+
+/// The runners used for tests built for WebAssembly targets, returned by [`NextestConfig::wasm`].
+///
+/// These are only used if no [target runner](crate::target_runner::TargetRunner) is configured for
+/// the target. Like target runners, they're specified as strings and split on whitespace, and the
+/// test binary and its arguments are appended to them.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub struct WasmConfig {
+    runtime: String,
+    bindgen_runner: String,
+}
+
+impl WasmConfig {
+    /// Returns the WebAssembly runtime that tests built for WASI targets, such as `wasm32-wasi`,
+    /// are run with.
+    pub fn runtime(&self) -> &str {
+        &self.runtime
+    }
+
+    /// Returns the runner that tests built for `wasm32-unknown-unknown` are run with, normally
+    /// `wasm-bindgen-test-runner`.
+    pub fn bindgen_runner(&self) -> &str {
+        &self.bindgen_runner
+    }
+}
+
+/// A command that tests are run under, for example a memory checker or a debugger, returned by
+/// [`TestSettings::wrapper`] and [`NextestProfile::recorder`].
+///
+/// Tests are run as `<program> <args...> <test binary> <test args...>`. Like script commands,
+/// wrapper commands are specified as strings and split on whitespace.
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq)]
+#[serde(try_from = "String")]
+pub struct WrapperCommand {
+    program: String,
+    args: Vec<String>,
+}
+
+impl WrapperCommand {
+    /// Returns the program to run.
+    pub fn program(&self) -> &str {
+        &self.program
+    }
+
+    /// Returns the arguments passed to the program before the test binary.
+    pub fn args(&self) -> impl Iterator<Item = &str> {
+        self.args.iter().map(AsRef::as_ref)
+    }
+}
+
+impl TryFrom<String> for WrapperCommand {
+    type Error = &'static str;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        let mut iter = value.split_whitespace();
+        let program = iter.next().ok_or("wrapper command must not be empty")?;
+        Ok(Self {
+            program: program.to_owned(),
+            args: iter.map(String::from).collect(),
+        })
+    }
+}
+
+/// Configuration for the list of slowest tests printed at the end of a run, returned by
+/// [`NextestProfile::slowest_tests`].
+///
+/// In configuration, this is specified as a table, for example
+/// `slowest-tests = { count = 5, threshold = "30s" }`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct SlowestTests {
+    #[serde(default)]
+    count: Option<usize>,
+    #[serde(default, with = "humantime_serde")]
+    threshold: Option<Duration>,
+}
+
+impl SlowestTests {
+    /// Returns the number of slowest tests to list. Zero means that they aren't listed.
+    pub fn count(&self) -> usize {
+        self.count.unwrap_or(0)
+    }
+
+    /// Returns the time above which tests are flagged as too slow, if any.
+    ///
+    /// Tests that take longer than this are always listed, even if they aren't among the slowest
+    /// [`count`](Self::count) tests.
+    pub fn threshold(&self) -> Option<Duration> {
+        self.threshold
+    }
+
+    /// Fills in settings that aren't set in `self` from `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            count: self.count.or(other.count),
+            threshold: self.threshold.or(other.threshold),
+        }
+    }
+}
+
+/// Colors and styles used by the reporter, returned by [`NextestProfile::colors`].
+///
+/// In configuration, this is specified as a table, for example
+/// `colors = { theme = "basic", fail = "bright-red underline" }`. Styles that aren't set are taken
+/// from the theme.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ColorConfig {
+    #[serde(default)]
+    theme: Option<ColorTheme>,
+    #[serde(default)]
+    pass: Option<StyleSpec>,
+    #[serde(default)]
+    retry: Option<StyleSpec>,
+    #[serde(default)]
+    fail: Option<StyleSpec>,
+    #[serde(default)]
+    skip: Option<StyleSpec>,
+    #[serde(default)]
+    count: Option<StyleSpec>,
+    #[serde(default)]
+    binary_id: Option<StyleSpec>,
+    #[serde(default)]
+    test_name: Option<StyleSpec>,
+}
+
+impl ColorConfig {
+    /// Returns the theme that provides default styles.
+    pub fn theme(&self) -> ColorTheme {
+        self.theme.unwrap_or_default()
+    }
+
+    /// Returns the style for passing tests, if set.
+    pub fn pass(&self) -> Option<StyleSpec> {
+        self.pass
+    }
+
+    /// Returns the style for retried tests, if set.
+    pub fn retry(&self) -> Option<StyleSpec> {
+        self.retry
+    }
+
+    /// Returns the style for failing tests, if set.
+    pub fn fail(&self) -> Option<StyleSpec> {
+        self.fail
+    }
+
+    /// Returns the style for skipped, slow and flaky tests, if set.
+    pub fn skip(&self) -> Option<StyleSpec> {
+        self.skip
+    }
+
+    /// Returns the style for counts in summaries, if set.
+    pub fn count(&self) -> Option<StyleSpec> {
+        self.count
+    }
+
+    /// Returns the style for binary IDs, if set.
+    pub fn binary_id(&self) -> Option<StyleSpec> {
+        self.binary_id
+    }
+
+    /// Returns the style for test names, if set.
+    pub fn test_name(&self) -> Option<StyleSpec> {
+        self.test_name
+    }
+
+    /// Fills in settings that aren't set in `self` from `other`.
+    fn or(self, other: Self) -> Self {
+        Self {
+            theme: self.theme.or(other.theme),
+            pass: self.pass.or(other.pass),
+            retry: self.retry.or(other.retry),
+            fail: self.fail.or(other.fail),
+            skip: self.skip.or(other.skip),
+            count: self.count.or(other.count),
+            binary_id: self.binary_id.or(other.binary_id),
+            test_name: self.test_name.or(other.test_name),
+        }
+    }
+}
+
+/// Slow timeout configuration for a profile, returned by [`NextestProfile::slow_timeout`].
+///
+/// In configuration, this is specified either as a duration (`slow-timeout = "60s"`) or as a table
+/// (`slow-timeout = { period = "60s", terminate-after = 2 }`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct SlowTimeout {
+    pub(crate) period: Duration,
+    pub(crate) terminate_after: Option<NonZeroUsize>,
+}
+
+impl SlowTimeout {
+    /// Returns the time after which a test is marked as slow.
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Returns the number of periods after which a test is terminated, if any.
+    ///
+    /// If this is `None`, tests are never terminated.
+    pub fn terminate_after(&self) -> Option<NonZeroUsize> {
+        self.terminate_after
+    }
+}
+
+impl<'de> Deserialize<'de> for SlowTimeout {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct SlowTimeoutVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for SlowTimeoutVisitor {
+            type Value = SlowTimeout;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    formatter,
+                    "a duration or a table with `period` and optionally `terminate-after`"
+                )
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let period = humantime_serde::deserialize(v.into_deserializer())?;
+                Ok(SlowTimeout {
+                    period,
+                    terminate_after: None,
+                })
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "kebab-case")]
+                struct SlowTimeoutImpl {
+                    #[serde(with = "humantime_serde")]
+                    period: Duration,
+                    #[serde(default)]
+                    terminate_after: Option<NonZeroUsize>,
+                }
+
+                let SlowTimeoutImpl {
+                    period,
+                    terminate_after,
+                } = SlowTimeoutImpl::deserialize(serde::de::value::MapAccessDeserializer::new(
+                    map,
+                ))?;
+                Ok(SlowTimeout {
+                    period,
+                    terminate_after,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(SlowTimeoutVisitor)
+    }
+}
+
+/// Retry configuration for a profile, returned by [`NextestProfile::retries`].
+///
+/// In configuration, this is specified either as a number of retries (`retries = 2`), which retries
+/// immediately, or as a table with a backoff policy
+/// (`retries = { backoff = "exponential", count = 3, delay = "1s" }`).
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum RetryPolicy {
+    /// Wait a fixed amount of time between attempts.
+    Fixed {
+        /// The number of times to retry a failing test.
+        count: usize,
+
+        /// The delay between attempts.
+        delay: Duration,
+
+        /// If true, a random amount of up to half the delay is subtracted from each delay.
+        jitter: bool,
+    },
+
+    /// Double the delay after each attempt.
+    Exponential {
+        /// The number of times to retry a failing test.
+        count: usize,
+
+        /// The delay before the first retry.
+        delay: Duration,
+
+        /// If true, a random amount of up to half the delay is subtracted from each delay.
+        jitter: bool,
+
+        /// The maximum delay between attempts, if any.
+        max_delay: Option<Duration>,
+    },
+}
+
+impl RetryPolicy {
+    /// Creates a new retry policy with the given number of retries and no delay between attempts.
+    pub fn new_without_delay(count: usize) -> Self {
+        RetryPolicy::Fixed {
+            count,
+            delay: Duration::ZERO,
+            jitter: false,
+        }
+    }
+
+    /// Returns the number of times a failing test is retried.
+    pub fn count(&self) -> usize {
+        match self {
+            RetryPolicy::Fixed { count, .. } | RetryPolicy::Exponential { count, .. } => *count,
+        }
+    }
+
+    /// Returns the delay to wait for after the given attempt fails, before the next attempt.
+    ///
+    /// `attempt` is 1-based. If jitter is enabled, the returned delay is randomized.
+    pub fn delay_after(&self, attempt: usize) -> Duration {
+        let (delay, jitter) = match *self {
+            RetryPolicy::Fixed { delay, jitter, .. } => (delay, jitter),
+            RetryPolicy::Exponential {
+                delay,
+                jitter,
+                max_delay,
+                ..
+            } => {
+                let exponent = u32::try_from(attempt.saturating_sub(1)).unwrap_or(u32::MAX);
+                let delay = 2u32
+                    .checked_pow(exponent)
+                    .and_then(|factor| delay.checked_mul(factor))
+                    .unwrap_or(Duration::MAX);
+                (
+                    max_delay.map_or(delay, |max_delay| delay.min(max_delay)),
+                    jitter,
+                )
+            }
+        };
+
+        if jitter {
+            delay.mul_f64(rand::thread_rng().gen_range(0.5..=1.0))
+        } else {
+            delay
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for RetryPolicy {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct RetryPolicyVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for RetryPolicyVisitor {
+            type Value = RetryPolicy;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    formatter,
+                    "a number of retries or a table with `backoff`, `count` and `delay`"
+                )
+            }
+
+            fn visit_u64<E>(self, v: u64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let count = usize::try_from(v).map_err(E::custom)?;
+                Ok(RetryPolicy::new_without_delay(count))
+            }
+
+            fn visit_i64<E>(self, v: i64) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                let count = usize::try_from(v).map_err(|_| {
+                    E::invalid_value(serde::de::Unexpected::Signed(v), &"a non-negative integer")
+                })?;
+                Ok(RetryPolicy::new_without_delay(count))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "kebab-case")]
+                enum Backoff {
+                    Fixed,
+                    Exponential,
+                }
+
+                // A plain struct rather than an enum tagged by `backoff`: tagged enums are
+                // buffered, so `check_sources` wouldn't see unknown keys in them.
+                #[derive(Deserialize)]
+                #[serde(rename_all = "kebab-case")]
+                struct RetryPolicyImpl {
+                    backoff: Backoff,
+                    count: usize,
+                    #[serde(with = "humantime_serde")]
+                    delay: Duration,
+                    #[serde(default)]
+                    jitter: bool,
+                    // Only used for exponential backoff.
+                    #[serde(default, with = "humantime_serde")]
+                    max_delay: Option<Duration>,
+                }
+
+                let RetryPolicyImpl {
+                    backoff,
+                    count,
+                    delay,
+                    jitter,
+                    max_delay,
+                } = RetryPolicyImpl::deserialize(serde::de::value::MapAccessDeserializer::new(
+                    map,
+                ))?;
+                let policy = match backoff {
+                    Backoff::Fixed => RetryPolicy::Fixed {
+                        count,
+                        delay,
+                        jitter,
+                    },
+                    Backoff::Exponential => RetryPolicy::Exponential {
+                        count,
+                        delay,
+                        jitter,
+                        max_delay,
+                    },
+                };
+                Ok(policy)
+            }
+        }
+
+        deserializer.deserialize_any(RetryPolicyVisitor)
+    }
+}
+
+/// JUnit configuration for nextest, returned by a [`NextestProfile`].
+#[derive(Clone, Debug)]
+pub struct NextestJunitConfig<'cfg> {
+    path: Utf8PathBuf,
+    report_name: &'cfg str,
+}
+
+impl<'cfg> NextestJunitConfig<'cfg> {
+    /// Returns the absolute path to the JUnit report.
+    pub fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Returns the name of the JUnit report.
+    pub fn report_name(&self) -> &'cfg str {
+        self.report_name
+    }
+}
+
+/// OpenTelemetry export configuration for nextest, returned by a [`NextestProfile`].
+#[derive(Clone, Debug)]
+pub struct NextestOtlpConfig<'cfg> {
+    endpoint: &'cfg str,
+    headers: &'cfg BTreeMap<String, String>,
+}
+
+impl<'cfg> NextestOtlpConfig<'cfg> {
+    /// Returns the OTLP/HTTP endpoint that traces are sent to.
+    pub fn endpoint(&self) -> &'cfg str {
+        self.endpoint
+    }
+
+    /// Returns the additional HTTP headers sent along with traces, e.g. for authentication.
+    pub fn headers(&self) -> impl Iterator<Item = (&'cfg str, &'cfg str)> + 'cfg {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+/// Configuration for notifications sent at the end of a run, returned by a [`NextestProfile`].
+#[derive(Clone, Debug)]
+pub struct NextestNotifyConfig<'cfg> {
+    url: &'cfg str,
+    format: NotifyFormat,
+    template: &'cfg NotifyTemplate,
+    headers: &'cfg BTreeMap<String, String>,
+}
+
+impl<'cfg> NextestNotifyConfig<'cfg> {
+    /// Returns the URL that notifications are sent to.
+    pub fn url(&self) -> &'cfg str {
+        self.url
+    }
+
+    /// Returns the form that notifications are sent in.
+    pub fn format(&self) -> NotifyFormat {
+        self.format
+    }
+
+    /// Returns the template for the text of notifications sent as [`NotifyFormat::Slack`].
+    pub fn template(&self) -> &'cfg NotifyTemplate {
+        self.template
+    }
+
+    /// Returns the additional HTTP headers sent along with notifications.
+    pub fn headers(&self) -> impl Iterator<Item = (&'cfg str, &'cfg str)> + 'cfg {
+        self.headers
+            .iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct NextestConfigImpl {
+    store: StoreConfigImpl,
+    test_binaries: TestBinariesConfigImpl,
+    wasm: WasmConfig,
+    #[serde(default, rename = "script")]
+    scripts: BTreeMap<String, ScriptConfig>,
+    #[serde(default)]
+    test_groups: BTreeMap<String, TestGroupConfig>,
+    #[serde(default, rename = "exclude")]
+    excludes: Vec<ExcludeConfig>,
+    #[serde(default)]
+    quarantine: Vec<QuarantineConfig>,
+    #[serde(rename = "profile")]
+    profiles: NextestProfilesImpl,
+    #[serde(default)]
+    tool: BTreeMap<String, toml::Value>,
+    #[serde(default)]
+    nextest_version: NextestVersionConfig,
+    #[serde(default, deserialize_with = "deserialize_experimental")]
+    experimental: BTreeSet<ConfigExperimental>,
+}
+
+impl NextestConfigImpl {
+    /// Checks invariants that can't be expressed through deserialization.
+    fn validate(&self) -> Result<(), ConfigError> {
+        if !self.scripts.is_empty() {
+            self.require_experimental(
+                ConfigExperimental::SetupScripts,
+                "[script.<name>] sections",
+            )?;
+        }
+
+        let overrides = self.profiles.default.overrides.iter().chain(
+            self.profiles
+                .other
+                .values()
+                .flat_map(|profile| &profile.overrides),
+        );
+        for override_ in overrides {
+            if let Some(test_group) = &override_.test_group {
+                if !self.test_groups.contains_key(test_group) {
+                    return Err(ConfigError::Message(format!(
+                        "override for filter `{}` specifies unknown test group `{}` \
+                         (test groups must be defined in a [test-groups.{}] section)",
+                        override_.filter, test_group, test_group
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn require_experimental(
+        &self,
+        feature: ConfigExperimental,
+        used_by: &str,
+    ) -> Result<(), ConfigError> {
+        if self.experimental.contains(&feature) {
+            return Ok(());
+        }
+        Err(ConfigError::Message(format!(
+            "{} require the experimental feature `{}`, which isn't enabled \
+             (add `experimental = [\"{}\"]` to the config to enable it)",
+            used_by, feature, feature
+        )))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct StoreConfigImpl {
+    dir: Utf8PathBuf,
+    #[serde(default, with = "humantime_serde")]
+    max_age: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_byte_size")]
+    max_size: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TestBinariesConfigImpl {
+    opaque: Vec<String>,
+}
+
+#[derive(Clone, Debug)]
+struct NextestProfilesImpl {
+    default: DefaultProfileImpl,
+    other: HashMap<String, CustomProfileImpl>,
+}
+
+// This isn't derived with `#[serde(flatten)]`, since flattened fields are buffered before they're
+// deserialized, which hides unknown keys in custom profiles from `check_sources`.
+impl<'de> Deserialize<'de> for NextestProfilesImpl {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct NextestProfilesVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for NextestProfilesVisitor {
+            type Value = NextestProfilesImpl;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(formatter, "a table of profiles")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                let mut default = None;
+                let mut other = HashMap::new();
+                while let Some(name) = map.next_key::<String>()? {
+                    if name == NextestConfig::DEFAULT_PROFILE {
+                        default = Some(map.next_value()?);
+                    } else {
+                        let profile = map.next_value()?;
+                        other.insert(name, profile);
+                    }
+                }
+                let default = default.ok_or_else(|| {
+                    serde::de::Error::missing_field(NextestConfig::DEFAULT_PROFILE)
+                })?;
+                Ok(NextestProfilesImpl { default, other })
+            }
+        }
+
+        deserializer.deserialize_map(NextestProfilesVisitor)
+    }
+}
+
+impl NextestProfilesImpl {
+    fn get(&self, profile: &str) -> Result<Option<&CustomProfileImpl>, ProfileNotFound> {
+        let custom_profile = match profile {
+            NextestConfig::DEFAULT_PROFILE => None,
+            other => Some(
+                self.other
+                    .get(other)
+                    .ok_or_else(|| ProfileNotFound::new(profile, self.all_profiles()))?,
+            ),
+        };
+        Ok(custom_profile)
+    }
+
+    fn all_profiles(&self) -> impl Iterator<Item = &str> {
+        self.other
+            .keys()
+            .map(|key| key.as_str())
+            .chain(std::iter::once(NextestConfig::DEFAULT_PROFILE))
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultProfileImpl {
+    #[serde(default)]
+    test_threads: TestThreads,
+    retries: RetryPolicy,
+    status_level: StatusLevel,
+    failure_output: TestOutputDisplay,
+    success_output: TestOutputDisplay,
+    fail_fast: bool,
+    no_tests: NoTestsBehavior,
+    test_color: TestColor,
+    rust_backtrace: RustBacktrace,
+    slow_timeout: SlowTimeout,
+    #[serde(with = "humantime_serde")]
+    grace_period: Duration,
+    #[serde(default, with = "humantime_serde")]
+    heartbeat_period: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_byte_size")]
+    max_output_size: Option<u64>,
+    #[serde(default)]
+    test_tmpdir: TestTmpdirConfig,
+    #[serde(default)]
+    test_output_dir: TestOutputDirConfig,
+    #[serde(default)]
+    env: EnvConfig,
+    batch_size: NonZeroUsize,
+    #[serde(default)]
+    libtest_json: bool,
+    #[serde(default)]
+    network_isolation: bool,
+    #[serde(default, deserialize_with = "deserialize_opt_filter_expr")]
+    default_filter: Option<FilterExpr>,
+    #[serde(default)]
+    resource_limits: ResourceLimits,
+    #[serde(default)]
+    throttle: ThrottleConfig,
+    #[serde(default)]
+    cpu_affinity: Vec<CpuSet>,
+    #[serde(default, deserialize_with = "deserialize_nice")]
+    nice: Option<u8>,
+    #[serde(default)]
+    core_dumps: bool,
+    #[serde(default)]
+    record_failures: bool,
+    recorder: WrapperCommand,
+    #[serde(default)]
+    slowest_tests: SlowestTests,
+    #[serde(default)]
+    colors: ColorConfig,
+    junit: DefaultJunitImpl,
+    #[serde(default)]
+    tap: TapImpl,
+    #[serde(default)]
+    html: HtmlImpl,
+    #[serde(default)]
+    chrome_trace: ChromeTraceImpl,
+    #[serde(default)]
+    otlp: OtlpImpl,
+    #[serde(default)]
+    notify: NotifyImpl,
+    #[serde(default)]
+    overrides: Vec<ProfileOverrideImpl>,
+    #[serde(default, deserialize_with = "deserialize_platform_sections")]
+    target: Vec<(TargetSpec, PlatformProfileImpl)>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct DefaultJunitImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+    report_name: String,
 }
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 struct CustomProfileImpl {
     #[serde(default)]
-    retries: Option<usize>,
+    test_threads: Option<TestThreads>,
+    #[serde(default)]
+    retries: Option<RetryPolicy>,
     #[serde(default)]
     status_level: Option<StatusLevel>,
     #[serde(default)]
@@ -310,30 +3141,2064 @@ struct CustomProfileImpl {
     success_output: Option<TestOutputDisplay>,
     #[serde(default)]
     fail_fast: Option<bool>,
-    #[serde(with = "humantime_serde")]
     #[serde(default)]
-    slow_timeout: Option<Duration>,
+    no_tests: Option<NoTestsBehavior>,
+    #[serde(default)]
+    test_color: Option<TestColor>,
+    #[serde(default)]
+    rust_backtrace: Option<RustBacktrace>,
+    #[serde(default)]
+    slow_timeout: Option<SlowTimeout>,
+    #[serde(default, with = "humantime_serde")]
+    grace_period: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    heartbeat_period: Option<Duration>,
+    #[serde(default, deserialize_with = "deserialize_byte_size")]
+    max_output_size: Option<u64>,
+    #[serde(default)]
+    test_tmpdir: Option<TestTmpdirConfig>,
+    #[serde(default)]
+    test_output_dir: Option<TestOutputDirConfig>,
+    #[serde(default)]
+    env: Option<EnvConfig>,
+    #[serde(default)]
+    batch_size: Option<NonZeroUsize>,
+    #[serde(default)]
+    libtest_json: Option<bool>,
+    #[serde(default)]
+    network_isolation: Option<bool>,
+    #[serde(default, deserialize_with = "deserialize_opt_filter_expr")]
+    default_filter: Option<FilterExpr>,
+    #[serde(default)]
+    resource_limits: ResourceLimits,
+    #[serde(default)]
+    throttle: ThrottleConfig,
+    #[serde(default)]
+    cpu_affinity: Option<Vec<CpuSet>>,
+    #[serde(default, deserialize_with = "deserialize_nice")]
+    nice: Option<u8>,
+    #[serde(default)]
+    core_dumps: Option<bool>,
+    #[serde(default)]
+    record_failures: Option<bool>,
+    #[serde(default)]
+    recorder: Option<WrapperCommand>,
+    #[serde(default)]
+    slowest_tests: SlowestTests,
+    #[serde(default)]
+    colors: ColorConfig,
     #[serde(default)]
     junit: JunitImpl,
+    #[serde(default)]
+    tap: TapImpl,
+    #[serde(default)]
+    html: HtmlImpl,
+    #[serde(default)]
+    chrome_trace: ChromeTraceImpl,
+    #[serde(default)]
+    otlp: OtlpImpl,
+    #[serde(default)]
+    notify: NotifyImpl,
+    #[serde(default)]
+    overrides: Vec<ProfileOverrideImpl>,
+    #[serde(default, deserialize_with = "deserialize_platform_sections")]
+    target: Vec<(TargetSpec, PlatformProfileImpl)>,
+}
+
+/// Settings in a `[profile.<name>.target.<platform>]` section, which only apply if the platform
+/// matches.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct PlatformProfileImpl {
+    #[serde(default)]
+    retries: Option<RetryPolicy>,
+    #[serde(default)]
+    slow_timeout: Option<SlowTimeout>,
+    #[serde(default, with = "humantime_serde")]
+    grace_period: Option<Duration>,
+    #[serde(default)]
+    env: Option<EnvConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ProfileOverrideImpl {
+    #[serde(deserialize_with = "deserialize_filter_expr")]
+    filter: FilterExpr,
+    #[serde(default)]
+    retries: Option<RetryPolicy>,
+    #[serde(default)]
+    slow_timeout: Option<SlowTimeout>,
+    #[serde(default, with = "humantime_serde")]
+    grace_period: Option<Duration>,
+    #[serde(default, with = "humantime_serde")]
+    heartbeat_period: Option<Duration>,
+    #[serde(default)]
+    threads_required: Option<ThreadsRequired>,
+    #[serde(default)]
+    priority: Option<i32>,
+    #[serde(default)]
+    batch_size: Option<NonZeroUsize>,
+    #[serde(default)]
+    test_group: Option<String>,
+    #[serde(default)]
+    wrapper: Option<WrapperCommand>,
+    #[serde(default)]
+    resource_limits: ResourceLimits,
+    #[serde(default)]
+    disable_aslr: Option<bool>,
+    #[serde(default)]
+    failure_output: Option<TestOutputDisplay>,
+    #[serde(default)]
+    success_output: Option<TestOutputDisplay>,
+}
+
+fn deserialize_filter_expr<'de, D>(deserializer: D) -> Result<FilterExpr, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let input = String::deserialize(deserializer)?;
+    FilterExpr::parse(input).map_err(serde::de::Error::custom)
+}
+
+fn deserialize_nice<'de, D>(deserializer: D) -> Result<Option<u8>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let nice = u64::deserialize(deserializer)?;
+    match u8::try_from(nice) {
+        Ok(nice) if nice <= NextestProfile::MAX_NICE => Ok(Some(nice)),
+        _ => Err(serde::de::Error::invalid_value(
+            serde::de::Unexpected::Unsigned(nice),
+            &"a nice value from 0 to 19",
+        )),
+    }
+}
+
+fn deserialize_opt_filter_expr<'de, D>(deserializer: D) -> Result<Option<FilterExpr>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    deserialize_filter_expr(deserializer).map(Some)
+}
+
+/// Platforms are specified as target triples or `cfg()` expressions, like in Cargo's
+/// `[target.<platform>]` sections.
+fn deserialize_platform_sections<'de, D>(
+    deserializer: D,
+) -> Result<Vec<(TargetSpec, PlatformProfileImpl)>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let sections = BTreeMap::<String, PlatformProfileImpl>::deserialize(deserializer)?;
+    sections
+        .into_iter()
+        .map(|(platform, section)| {
+            let spec = TargetSpec::new(platform.clone()).map_err(|err| {
+                serde::de::Error::custom(format!("invalid platform `{}`: {}", platform, err))
+            })?;
+            Ok((spec, section))
+        })
+        .collect()
+}
+
+/// Deserializes only `nextest-version`, ignoring every other key.
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct NextestVersionOnlyImpl {
+    #[serde(default)]
+    nextest_version: NextestVersionConfig,
+}
+
+enum NextestVersionImpl {
+    Required(String),
+    Tiers {
+        required: Option<String>,
+        recommended: Option<String>,
+    },
+}
+
+// Not derived with `#[serde(untagged)]`, which would hide unknown keys in the table form from
+// `check_sources`.
+impl<'de> Deserialize<'de> for NextestVersionImpl {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        struct NextestVersionVisitor;
+
+        impl<'de> serde::de::Visitor<'de> for NextestVersionVisitor {
+            type Value = NextestVersionImpl;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                write!(
+                    formatter,
+                    "a version requirement or a table with `required` and `recommended`"
+                )
+            }
+
+            fn visit_str<E>(self, v: &str) -> Result<Self::Value, E>
+            where
+                E: serde::de::Error,
+            {
+                Ok(NextestVersionImpl::Required(v.to_owned()))
+            }
+
+            fn visit_map<A>(self, map: A) -> Result<Self::Value, A::Error>
+            where
+                A: serde::de::MapAccess<'de>,
+            {
+                #[derive(Deserialize)]
+                #[serde(rename_all = "kebab-case")]
+                struct TiersImpl {
+                    #[serde(default)]
+                    required: Option<String>,
+                    #[serde(default)]
+                    recommended: Option<String>,
+                }
+
+                let TiersImpl {
+                    required,
+                    recommended,
+                } = TiersImpl::deserialize(serde::de::value::MapAccessDeserializer::new(map))?;
+                Ok(NextestVersionImpl::Tiers {
+                    required,
+                    recommended,
+                })
+            }
+        }
+
+        deserializer.deserialize_any(NextestVersionVisitor)
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct JunitImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+    report_name: Option<String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct TapImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct HtmlImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct ChromeTraceImpl {
+    #[serde(default)]
+    path: Option<Utf8PathBuf>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct OtlpImpl {
+    #[serde(default)]
+    endpoint: Option<String>,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct NotifyImpl {
+    #[serde(default)]
+    url: Option<String>,
+    #[serde(default)]
+    format: NotifyFormat,
+    #[serde(default, deserialize_with = "deserialize_notify_template")]
+    template: NotifyTemplate,
+    #[serde(default)]
+    headers: BTreeMap<String, String>,
 }
 
-#[derive(Clone, Debug, Default, Deserialize)]
-#[serde(rename_all = "kebab-case")]
-struct JunitImpl {
-    #[serde(default)]
-    path: Option<Utf8PathBuf>,
-    report_name: Option<String>,
-}
+fn deserialize_notify_template<'de, D>(deserializer: D) -> Result<NotifyTemplate, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let input = String::deserialize(deserializer)?;
+    input.parse().map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_config_is_valid() {
+        let default_config = NextestConfig::default_config("foo");
+        let default_profile = default_config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile should exist");
+        assert_eq!(
+            default_profile.max_output_size(),
+            None,
+            "output isn't limited by default"
+        );
+    }
+
+    #[test]
+    fn parse_slow_timeout() {
+        let config_contents = r#"
+            [profile.default]
+            slow-timeout = "30s"
+
+            [profile.ci]
+            slow-timeout = { period = "60s", terminate-after = 3 }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(
+            default_profile.slow_timeout(),
+            SlowTimeout {
+                period: Duration::from_secs(30),
+                terminate_after: None,
+            }
+        );
+
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        assert_eq!(
+            ci_profile.slow_timeout(),
+            SlowTimeout {
+                period: Duration::from_secs(60),
+                terminate_after: NonZeroUsize::new(3),
+            }
+        );
+    }
+
+    #[test]
+    fn parse_scripts() {
+        let config_contents = r#"
+            experimental = ["setup-scripts"]
+
+            [script.db]
+            setup = "scripts/start-db.sh --port 5432"
+            teardown = "scripts/stop-db.sh"
+
+            [script.cache]
+            setup = "scripts/warm-cache.sh"
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        let scripts: Vec<_> = profile.scripts().collect();
+        assert_eq!(scripts.len(), 2, "two scripts defined");
+
+        // Scripts are returned in name order.
+        let (name, script) = scripts[0];
+        assert_eq!(name, "cache");
+        assert_eq!(script.setup().program(), "scripts/warm-cache.sh");
+        assert_eq!(script.teardown(), None);
+
+        let (name, script) = scripts[1];
+        assert_eq!(name, "db");
+        assert_eq!(script.setup().program(), "scripts/start-db.sh");
+        assert_eq!(
+            script.setup().args().collect::<Vec<_>>(),
+            vec!["--port", "5432"]
+        );
+        assert_eq!(
+            script.teardown().map(|command| command.program()),
+            Some("scripts/stop-db.sh")
+        );
+    }
+
+    #[test]
+    fn experimental_features() {
+        let read_config = |contents: &str| {
+            let inner: NextestConfigImpl = NextestConfig::make_default_config()
+                .with_merged(File::from_str(contents, FileFormat::Toml))
+                .expect("config is valid TOML")
+                .try_into()?;
+            inner.validate()?;
+            Ok::<_, ConfigError>(inner)
+        };
+
+        let scripts = r#"
+            [script.db]
+            setup = "scripts/start-db.sh"
+        "#;
+        let err = read_config(scripts).expect_err("setup scripts aren't enabled");
+        assert!(
+            err.to_string()
+                .contains("require the experimental feature `setup-scripts`"),
+            "error message {} mentions the feature",
+            err
+        );
+
+        let inner = read_config(&format!("experimental = [\"setup-scripts\"]\n{}", scripts))
+            .expect("setup scripts are enabled");
+        assert_eq!(
+            inner.experimental.into_iter().collect::<Vec<_>>(),
+            vec![ConfigExperimental::SetupScripts]
+        );
+
+        let inner = read_config(&format!(
+            "experimental = [\"setup-scripts\", \"time-travel\"]\n{}",
+            scripts
+        ))
+        .expect("unknown features are ignored");
+        assert_eq!(
+            inner.experimental.into_iter().collect::<Vec<_>>(),
+            vec![ConfigExperimental::SetupScripts]
+        );
+
+        let err = read_config(&format!("experimental = [\"time-travel\"]\n{}", scripts))
+            .expect_err("unknown features don't enable known ones");
+        assert!(
+            err.to_string()
+                .contains("require the experimental feature `setup-scripts`"),
+            "error message {} mentions the feature",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_overrides() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "binary(/::/)"
+            retries = 5
+
+            [[profile.default.overrides]]
+            filter = "test(db::)"
+            retries = 1
+            slow-timeout = { period = "120s", terminate-after = 2 }
+            threads-required = 4
+            test-group = "db"
+
+            [test-groups.db]
+            max-threads = 2
+
+            [profile.ci]
+            retries = 2
+
+            [[profile.ci.overrides]]
+            filter = "test(db::)"
+            retries = 3
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+
+        // (package name, binary ID, test name, profile, expected settings)
+        let default_slow_timeout = default_profile.slow_timeout();
+        let db_slow_timeout = SlowTimeout {
+            period: Duration::from_secs(120),
+            terminate_after: NonZeroUsize::new(2),
+        };
+        let cases = [
+            (
+                "foo",
+                "foo",
+                "unit_test",
+                &default_profile,
+                0,
+                default_slow_timeout,
+                1,
+                None,
+            ),
+            (
+                "foo",
+                "foo::integration",
+                "it",
+                &default_profile,
+                5,
+                default_slow_timeout,
+                1,
+                None,
+            ),
+            (
+                "foo",
+                "foo",
+                "db::unit_test",
+                &default_profile,
+                1,
+                db_slow_timeout,
+                4,
+                Some("db"),
+            ),
+            // The first matching override wins.
+            (
+                "foo",
+                "foo::integration",
+                "db::it",
+                &default_profile,
+                5,
+                db_slow_timeout,
+                4,
+                Some("db"),
+            ),
+            // The custom profile's own retries are used if no override matches.
+            (
+                "foo",
+                "foo",
+                "unit_test",
+                &ci_profile,
+                2,
+                default_slow_timeout,
+                1,
+                None,
+            ),
+            // Custom profile overrides take precedence over default profile overrides.
+            (
+                "foo",
+                "foo::integration",
+                "db::it",
+                &ci_profile,
+                3,
+                db_slow_timeout,
+                4,
+                Some("db"),
+            ),
+            (
+                "foo",
+                "foo::integration",
+                "it",
+                &ci_profile,
+                5,
+                default_slow_timeout,
+                1,
+                None,
+            ),
+        ];
+
+        for (package_name, binary_id, test_name, profile, retries, slow_timeout, threads, group) in
+            cases
+        {
+            let settings = profile.test_settings_impl(|filter| {
+                filter.matches_parts(package_name, binary_id, test_name)
+            });
+            assert_eq!(
+                settings,
+                TestSettings {
+                    retries: RetryPolicy::new_without_delay(retries),
+                    slow_timeout,
+                    heartbeat_period: None,
+                    grace_period: Duration::from_secs(10),
+                    threads_required: ThreadsRequired::Count(NonZeroUsize::new(threads).unwrap()),
+                    priority: 0,
+                    batch_size: NonZeroUsize::new(1).unwrap(),
+                    test_group: group.map(str::to_owned),
+                    wrapper: None,
+                    resource_limits: ResourceLimits::default(),
+                    disable_aslr: false,
+                    failure_output: TestOutputDisplay::Immediate,
+                    success_output: TestOutputDisplay::Never,
+                },
+                "settings match for {} {}",
+                binary_id,
+                test_name
+            );
+        }
+    }
+
+    #[test]
+    fn parse_heartbeat_period() {
+        let config_contents = r#"
+            [profile.default]
+            heartbeat-period = "30s"
+
+            [[profile.default.overrides]]
+            filter = "test(db::)"
+            heartbeat-period = "2m"
+
+            [profile.ci]
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(
+            default_profile.heartbeat_period(),
+            Some(Duration::from_secs(30))
+        );
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        assert_eq!(
+            ci_profile.heartbeat_period(),
+            Some(Duration::from_secs(30)),
+            "custom profile inherits from the default profile"
+        );
+
+        let settings = default_profile
+            .test_settings_impl(|filter| filter.matches_parts("foo", "foo", "db::it"));
+        assert_eq!(settings.heartbeat_period(), Some(Duration::from_secs(120)));
+        let settings = default_profile
+            .test_settings_impl(|filter| filter.matches_parts("foo", "foo", "unit_test"));
+        assert_eq!(settings.heartbeat_period(), Some(Duration::from_secs(30)));
+
+        let config = make_config("");
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(
+            default_profile.heartbeat_period(),
+            None,
+            "disabled by default"
+        );
+    }
+
+    #[test]
+    fn parse_output_display() {
+        let config_contents = r#"
+            [profile.default]
+            failure-output = "final"
+
+            [[profile.default.overrides]]
+            filter = "test(db::)"
+            failure-output = "immediate-final"
+            success-output = "final"
+
+            [profile.ci]
+            failure-output = "never"
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(default_profile.failure_output(), TestOutputDisplay::Final);
+        assert_eq!(default_profile.success_output(), TestOutputDisplay::Never);
+
+        let settings = default_profile
+            .test_settings_impl(|filter| filter.matches_parts("foo", "foo", "db::it"));
+        assert_eq!(settings.failure_output(), TestOutputDisplay::ImmediateFinal);
+        assert_eq!(settings.success_output(), TestOutputDisplay::Final);
+        let settings = default_profile
+            .test_settings_impl(|filter| filter.matches_parts("foo", "foo", "unit_test"));
+        assert_eq!(settings.failure_output(), TestOutputDisplay::Final);
+        assert_eq!(settings.success_output(), TestOutputDisplay::Never);
+
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        let settings =
+            ci_profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "unit_test"));
+        assert_eq!(settings.failure_output(), TestOutputDisplay::Never);
+        let settings =
+            ci_profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "db::it"));
+        assert_eq!(
+            settings.failure_output(),
+            TestOutputDisplay::ImmediateFinal,
+            "overrides in the default profile take precedence over custom profile settings"
+        );
+    }
+
+    #[test]
+    fn parse_colors() {
+        let config_contents = r#"
+            [profile.default]
+            colors = { fail = "bright-red underline" }
+
+            [profile.ci]
+            colors = { theme = "monochrome", pass = "none" }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let colors = default_profile.colors();
+        assert_eq!(colors.theme(), ColorTheme::Default);
+        assert_eq!(
+            colors.fail().map(|spec| spec.to_string()).as_deref(),
+            Some("bright-red underline")
+        );
+        assert_eq!(colors.pass(), None);
+
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        let colors = ci_profile.colors();
+        assert_eq!(colors.theme(), ColorTheme::Monochrome);
+        assert_eq!(colors.pass(), Some(StyleSpec::default()));
+        assert_eq!(
+            colors.fail().map(|spec| spec.to_string()).as_deref(),
+            Some("bright-red underline"),
+            "custom profile inherits styles from the default profile"
+        );
+    }
+
+    #[test]
+    fn parse_slowest_tests() {
+        let config_contents = r#"
+            [profile.default]
+            slowest-tests = { count = 5 }
+
+            [profile.ci]
+            slowest-tests = { threshold = "1m" }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let slowest_tests = default_profile.slowest_tests();
+        assert_eq!(slowest_tests.count(), 5);
+        assert_eq!(slowest_tests.threshold(), None);
+
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        let slowest_tests = ci_profile.slowest_tests();
+        assert_eq!(
+            slowest_tests.count(),
+            5,
+            "custom profile inherits the count from the default profile"
+        );
+        assert_eq!(slowest_tests.threshold(), Some(Duration::from_secs(60)));
+
+        let config = make_config("");
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(
+            default_profile.slowest_tests(),
+            SlowestTests::default(),
+            "disabled by default"
+        );
+    }
+
+    #[test]
+    fn parse_grace_period() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(containers::)"
+            grace-period = "1m"
+
+            [profile.ci]
+            grace-period = "0s"
+        "#;
+        let config = make_config(config_contents);
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(default_profile.grace_period(), Duration::from_secs(10));
+        let settings = default_profile
+            .test_settings_impl(|filter| filter.matches_parts("foo", "foo", "containers::it"));
+        assert_eq!(settings.grace_period(), Duration::from_secs(60));
+
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        assert_eq!(ci_profile.grace_period(), Duration::ZERO);
+        let settings =
+            ci_profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "unit_test"));
+        assert_eq!(settings.grace_period(), Duration::ZERO);
+    }
 
     #[test]
-    fn default_config_is_valid() {
-        let default_config = NextestConfig::default_config("foo");
-        default_config
+    fn parse_test_tmpdir() {
+        let config_contents = r#"
+            [profile.ci]
+            test-tmpdir = { enabled = true }
+
+            [profile.debug]
+            test-tmpdir = { enabled = true, retain = "always" }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_tmpdir = config
             .profile(NextestConfig::DEFAULT_PROFILE)
-            .expect("default profile should exist");
+            .expect("default profile exists")
+            .test_tmpdir();
+        assert!(!default_tmpdir.enabled(), "disabled by default");
+
+        let ci_tmpdir = config
+            .profile("ci")
+            .expect("ci profile exists")
+            .test_tmpdir();
+        assert!(ci_tmpdir.enabled());
+        assert_eq!(ci_tmpdir.retain(), RetainTmpdir::OnFailure);
+
+        let debug_tmpdir = config
+            .profile("debug")
+            .expect("debug profile exists")
+            .test_tmpdir();
+        assert_eq!(debug_tmpdir.retain(), RetainTmpdir::Always);
+    }
+
+    #[test]
+    fn parse_store_retention() {
+        let config = make_config("");
+        assert_eq!(config.store_retention(), StoreRetention::default());
+
+        let config = make_config(
+            r#"
+            [store]
+            max-age = "1w"
+            max-size = "2GiB"
+        "#,
+        );
+        assert_eq!(
+            config.store_retention(),
+            StoreRetention::new(Some(Duration::from_secs(7 * 24 * 60 * 60)), Some(2 << 30))
+        );
+    }
+
+    #[test]
+    fn hierarchical_sources() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is valid UTF-8");
+        let user_file = dir.join("user.toml");
+        std::fs::write(
+            &user_file,
+            r#"
+            [profile.default]
+            retries = 1
+            fail-fast = false
+            slow-timeout = "30s"
+        "#,
+        )
+        .expect("wrote user config");
+        let workspace_root = dir.join("workspace");
+        std::fs::create_dir_all(workspace_root.join(".config")).expect("created config dir");
+        let workspace_file = workspace_root.join(NextestConfig::CONFIG_PATH);
+        std::fs::write(
+            &workspace_file,
+            r#"
+            [profile.default]
+            retries = 2
+            slow-timeout = "45s"
+        "#,
+        )
+        .expect("wrote workspace config");
+        let env_vars = vec![
+            ("NEXTEST_CONFIG_PROFILE__DEFAULT__RETRIES", "3"),
+            ("NEXTEST_CONFIG_STORE__MAX_AGE", "30d"),
+            ("NEXTEST_RETRIES", "5"),
+        ];
+        let env_vars = env_vars
+            .into_iter()
+            .map(|(var, value)| (var.into(), value.into()));
+
+        let config = NextestConfig::from_sources_impl(
+            workspace_root.clone(),
+            None,
+            &[],
+            Some(&user_file),
+            env_vars,
+        )
+        .expect("config is valid");
+        assert_eq!(
+            config.sources(),
+            &[
+                ConfigSource::Default,
+                ConfigSource::User(user_file.clone()),
+                ConfigSource::Workspace(workspace_file),
+                ConfigSource::Env("NEXTEST_CONFIG_PROFILE__DEFAULT__RETRIES".to_owned()),
+                ConfigSource::Env("NEXTEST_CONFIG_STORE__MAX_AGE".to_owned()),
+            ]
+        );
+        let profile = config.profile("default").expect("default profile exists");
+        assert_eq!(
+            profile.retries(),
+            RetryPolicy::new_without_delay(3),
+            "env wins"
+        );
+        assert_eq!(
+            profile.slow_timeout().period,
+            Duration::from_secs(45),
+            "workspace config overrides user config"
+        );
+        assert!(!profile.fail_fast(), "user config overrides defaults");
+        assert_eq!(
+            config.store_retention().max_age(),
+            Some(Duration::from_secs(30 * 24 * 60 * 60))
+        );
+        assert!(config.to_toml().contains("max-age = '30d'"));
+
+        // A missing user config file is skipped.
+        let config = NextestConfig::from_sources_impl(
+            workspace_root.clone(),
+            None,
+            &[],
+            Some(&dir.join("missing.toml")),
+            std::iter::empty(),
+        )
+        .expect("config is valid");
+        assert_eq!(config.sources().len(), 2);
+
+        let err = NextestConfig::from_sources_impl(
+            workspace_root,
+            None,
+            &[],
+            None,
+            std::iter::once(("NEXTEST_CONFIG_PROFILE____RETRIES".into(), "3".into())),
+        )
+        .expect_err("empty key is invalid");
+        assert_eq!(
+            err.config_source(),
+            &ConfigSource::Env("NEXTEST_CONFIG_PROFILE____RETRIES".to_owned())
+        );
+    }
+
+    #[test]
+    fn tool_config_files() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is valid UTF-8");
+        let tool_file = dir.join("tool.toml");
+        std::fs::write(
+            &tool_file,
+            r#"
+            [profile.default]
+            retries = 2
+            slow-timeout = "90s"
+
+            [tool.my-tool]
+            output = "lcov"
+        "#,
+        )
+        .expect("wrote tool config");
+        let workspace_root = dir.join("workspace");
+        std::fs::create_dir_all(workspace_root.join(".config")).expect("created config dir");
+        std::fs::write(
+            workspace_root.join(NextestConfig::CONFIG_PATH),
+            r#"
+            [profile.default]
+            retries = 4
+        "#,
+        )
+        .expect("wrote workspace config");
+
+        let tool_config_file: ToolConfigFile = format!("my-tool:{}", tool_file)
+            .parse()
+            .expect("valid tool config file");
+        assert_eq!(tool_config_file.tool, "my-tool");
+        assert!("my-tool".parse::<ToolConfigFile>().is_err());
+        assert!(":path".parse::<ToolConfigFile>().is_err());
+
+        let config = NextestConfig::from_sources_impl(
+            workspace_root.clone(),
+            None,
+            std::iter::once(&tool_config_file),
+            None,
+            std::iter::empty(),
+        )
+        .expect("config is valid");
+        let profile = config.profile("default").expect("default profile exists");
+        assert_eq!(
+            profile.retries(),
+            RetryPolicy::new_without_delay(4),
+            "workspace config overrides tool config"
+        );
+        assert_eq!(profile.slow_timeout().period, Duration::from_secs(90));
+
+        #[derive(Debug, Deserialize, Eq, PartialEq)]
+        struct MyToolConfig {
+            output: String,
+        }
+        assert_eq!(
+            config.tool_config::<MyToolConfig>("my-tool").unwrap(),
+            Some(MyToolConfig {
+                output: "lcov".to_owned()
+            })
+        );
+        assert_eq!(
+            config.tool_config::<MyToolConfig>("other-tool").unwrap(),
+            None
+        );
+
+        // Tools can't set each other's sections.
+        let other_tool_file = ToolConfigFile {
+            tool: "other-tool".to_owned(),
+            config_file: tool_file,
+        };
+        let err = NextestConfig::from_sources_impl(
+            workspace_root,
+            None,
+            std::iter::once(&other_tool_file),
+            None,
+            std::iter::empty(),
+        )
+        .expect_err("tool config sets another tool's section");
+        assert!(matches!(
+            err.config_source(),
+            ConfigSource::Tool { tool, .. } if tool == "other-tool"
+        ));
+    }
+
+    #[test]
+    fn nextest_version() {
+        let version = |input: &str| Version::parse(input).unwrap();
+
+        let config = make_config("");
+        assert_eq!(config.nextest_version(), &NextestVersionConfig::default());
+        assert_eq!(
+            config.nextest_version().eval(&version("0.1.0")),
+            NextestVersionEval::Satisfied
+        );
+
+        let config = make_config(r#"nextest-version = ">=0.9.50""#);
+        let nextest_version = config.nextest_version();
+        assert_eq!(
+            nextest_version.eval(&version("0.9.50")),
+            NextestVersionEval::Satisfied
+        );
+        assert_eq!(
+            nextest_version.eval(&version("0.9.50-rc.1")),
+            NextestVersionEval::Satisfied,
+            "pre-releases are matched as releases"
+        );
+        assert_eq!(
+            nextest_version.eval(&version("0.9.49")),
+            NextestVersionEval::Error {
+                required: VersionReq::parse(">=0.9.50").unwrap(),
+                current: version("0.9.49"),
+            }
+        );
+
+        let config =
+            make_config(r#"nextest-version = { required = ">=0.9.50", recommended = ">=0.9.60" }"#);
+        let nextest_version = config.nextest_version();
+        assert!(matches!(
+            nextest_version.eval(&version("0.9.40")),
+            NextestVersionEval::Error { .. }
+        ));
+        assert_eq!(
+            nextest_version.eval(&version("0.9.55")),
+            NextestVersionEval::Warn {
+                recommended: VersionReq::parse(">=0.9.60").unwrap(),
+                current: version("0.9.55"),
+            }
+        );
+        assert_eq!(
+            nextest_version.eval(&version("0.9.60")),
+            NextestVersionEval::Satisfied
+        );
+
+        let config = NextestConfig::make_default_config()
+            .with_merged(File::from_str(
+                r#"nextest-version = "not a version""#,
+                FileFormat::Toml,
+            ))
+            .unwrap();
+        let err = config
+            .try_into::<NextestConfigImpl>()
+            .expect_err("invalid version requirement");
+        assert!(
+            err.to_string()
+                .contains("invalid nextest-version `not a version`"),
+            "error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn read_nextest_version() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is valid UTF-8");
+        let config_file = dir.join("nextest.toml");
+        std::fs::write(
+            &config_file,
+            r#"
+            nextest-version = { required = ">=1000.0.0" }
+
+            [profile.default]
+            slow-timeout = "not a duration"
+        "#,
+        )
+        .expect("wrote config");
+
+        let nextest_version = NextestConfig::read_nextest_version_impl(
+            dir,
+            Some(&config_file),
+            &[],
+            None,
+            std::iter::empty(),
+        )
+        .expect("nextest-version is read without the rest of the config");
+        assert_eq!(
+            nextest_version.required(),
+            Some(&VersionReq::parse(">=1000.0.0").unwrap())
+        );
+        NextestConfig::from_sources_impl(
+            dir.to_owned(),
+            Some(&config_file),
+            &[],
+            None,
+            std::iter::empty(),
+        )
+        .expect_err("the rest of the config is invalid");
+    }
+
+    #[test]
+    fn profile_to_toml() {
+        let config_contents = r#"
+            [profile.default]
+            retries = 2
+
+            [[profile.default.overrides]]
+            filter = "test(default)"
+            retries = 3
+
+            [profile.ci]
+            fail-fast = false
+            retires = 3
+            slowest-tests = { count = 5 }
+            junit = { path = "junit.xml" }
+            notify = { template = "ignored without a URL" }
+
+            [[profile.ci.overrides]]
+            filter = "test(ci)"
+            retries = 4
+
+            [profile.ci.target.'cfg(all())']
+            grace-period = "1s"
+
+            [profile.ci.target.'cfg(any())']
+            grace-period = "2s"
+        "#;
+        let config = make_config(config_contents);
+        let resolved: toml::Value =
+            toml::from_str(&config.profile_to_toml("ci").expect("ci profile exists"))
+                .expect("resolved profile is valid TOML");
+        let profile = &resolved["profile"]["ci"];
+
+        assert_eq!(profile["retries"].as_integer(), Some(2), "inherited");
+        assert_eq!(profile["fail-fast"].as_bool(), Some(false), "overridden");
+        assert_eq!(
+            profile["grace-period"].as_str(),
+            Some("1s"),
+            "matching platform"
+        );
+        assert_eq!(profile["slowest-tests"]["count"].as_integer(), Some(5));
+        assert_eq!(profile["junit"]["path"].as_str(), Some("junit.xml"));
+        assert_eq!(
+            profile["junit"]["report-name"].as_str(),
+            Some("nextest-run"),
+            "junit sections are merged"
+        );
+        assert_eq!(
+            profile["notify"]["template"].as_str(),
+            Some("nextest run {status}: {passed} passed, {failed} failed, {skipped} skipped in {elapsed}s"),
+            "notify sections without a URL are ignored"
+        );
+        assert!(profile.get("target").is_none());
+        assert!(
+            profile.get("retires").is_none(),
+            "unknown keys are left out"
+        );
+
+        let filters: Vec<_> = profile["overrides"]
+            .as_array()
+            .expect("overrides are an array")
+            .iter()
+            .map(|override_| override_["filter"].as_str().unwrap())
+            .collect();
+        assert_eq!(filters, vec!["test(ci)", "test(default)"]);
+
+        assert!(config.profile_to_toml("nonexistent").is_err());
+    }
+
+    #[test]
+    fn parse_test_output_dir() {
+        let config_contents = r#"
+            [profile.ci]
+            test-output-dir = { enabled = true, retain = "always" }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_output_dir = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .test_output_dir();
+        assert!(!default_output_dir.enabled(), "disabled by default");
+        assert_eq!(default_output_dir.retain(), RetainTmpdir::OnFailure);
+
+        let ci_output_dir = config
+            .profile("ci")
+            .expect("ci profile exists")
+            .test_output_dir();
+        assert!(ci_output_dir.enabled());
+        assert_eq!(ci_output_dir.retain(), RetainTmpdir::Always);
+    }
+
+    #[test]
+    fn parse_default_filter() {
+        let config_contents = r#"
+            [profile.default]
+            default-filter = "not package(slow-e2e)"
+
+            [profile.ci]
+            default-filter = "all()"
+
+            [profile.local]
+            retries = 1
+        "#;
+        let config = make_config(config_contents);
+
+        let default_filter = |name| {
+            config
+                .profile(name)
+                .expect("profile exists")
+                .default_filter()
+                .map(|expr| expr.as_str().to_owned())
+        };
+        assert_eq!(
+            default_filter(NextestConfig::DEFAULT_PROFILE).as_deref(),
+            Some("not package(slow-e2e)")
+        );
+        assert_eq!(default_filter("ci").as_deref(), Some("all()"));
+        assert_eq!(
+            default_filter("local").as_deref(),
+            Some("not package(slow-e2e)"),
+            "inherited from the default profile"
+        );
+    }
+
+    #[test]
+    fn parse_excludes() {
+        let config_contents = r#"
+            [[exclude]]
+            filter = "test(flaky_network)"
+            reason = "fails intermittently on CI, see #123"
+
+            [[exclude]]
+            filter = "package(legacy)"
+            reason = "being rewritten"
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        let excludes: Vec<_> = profile
+            .excludes()
+            .iter()
+            .map(|exclude| (exclude.filter().as_str(), exclude.reason()))
+            .collect();
+        assert_eq!(
+            excludes,
+            vec![
+                (
+                    "test(flaky_network)",
+                    "fails intermittently on CI, see #123"
+                ),
+                ("package(legacy)", "being rewritten"),
+            ]
+        );
+    }
+
+    #[test]
+    fn parse_no_tests() {
+        let config_contents = r#"
+            [profile.ci]
+            no-tests = "fail"
+        "#;
+        let config = make_config(config_contents);
+
+        let no_tests = |name| config.profile(name).expect("profile exists").no_tests();
+        assert_eq!(
+            no_tests(NextestConfig::DEFAULT_PROFILE),
+            NoTestsBehavior::Warn
+        );
+        assert_eq!(no_tests("ci"), NoTestsBehavior::Fail);
+        assert_eq!("pass".parse(), Ok(NoTestsBehavior::Pass));
+        assert!("sometimes".parse::<NoTestsBehavior>().is_err());
+    }
+
+    #[test]
+    fn parse_test_color() {
+        let config_contents = r#"
+            [profile.ci]
+            test-color = "never"
+        "#;
+        let config = make_config(config_contents);
+
+        let test_color = |name| config.profile(name).expect("profile exists").test_color();
+        assert_eq!(test_color(NextestConfig::DEFAULT_PROFILE), TestColor::Auto);
+        assert_eq!(test_color("ci"), TestColor::Never);
+        assert_eq!(TestColor::Never.env()[1], ("NO_COLOR", "1"));
+    }
+
+    #[test]
+    fn parse_rust_backtrace() {
+        let config_contents = r#"
+            [profile.ci]
+            rust-backtrace = "full"
+        "#;
+        let config = make_config(config_contents);
+
+        let rust_backtrace = |name| {
+            config
+                .profile(name)
+                .expect("profile exists")
+                .rust_backtrace()
+        };
+        assert_eq!(
+            rust_backtrace(NextestConfig::DEFAULT_PROFILE),
+            RustBacktrace::Short
+        );
+        assert_eq!(rust_backtrace("ci"), RustBacktrace::Full);
+
+        let config_contents = r#"
+            [profile.default]
+            rust-backtrace = "yes"
+        "#;
+        NextestConfig::make_default_config()
+            .with_merged(File::from_str(config_contents, FileFormat::Toml))
+            .expect("config is valid TOML")
+            .try_into::<NextestConfigImpl>()
+            .expect_err("invalid rust-backtrace value");
+    }
+
+    #[test]
+    fn parse_libtest_json() {
+        let config_contents = r#"
+            [profile.ci]
+            libtest-json = true
+        "#;
+        let config = make_config(config_contents);
+
+        let libtest_json = |name| config.profile(name).expect("profile exists").libtest_json();
+        assert!(!libtest_json(NextestConfig::DEFAULT_PROFILE));
+        assert!(libtest_json("ci"));
+    }
+
+    #[test]
+    fn parse_core_dumps() {
+        let config_contents = r#"
+            [profile.ci]
+            core-dumps = true
+        "#;
+        let config = make_config(config_contents);
+
+        let core_dumps = |name| config.profile(name).expect("profile exists").core_dumps();
+        assert!(!core_dumps(NextestConfig::DEFAULT_PROFILE));
+        assert!(core_dumps("ci"));
+    }
+
+    #[test]
+    fn parse_record_failures() {
+        let config_contents = r#"
+            [profile.ci]
+            record-failures = true
+
+            [profile.chaos]
+            recorder = "rr record --chaos"
+        "#;
+        let config = make_config(config_contents);
+
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert!(!profile.record_failures());
+        assert_eq!(profile.recorder().program(), "rr");
+        assert_eq!(profile.recorder().args().collect::<Vec<_>>(), ["record"]);
+
+        let profile = config.profile("ci").expect("ci profile exists");
+        assert!(profile.record_failures());
+        assert_eq!(profile.recorder().args().collect::<Vec<_>>(), ["record"]);
+
+        let profile = config.profile("chaos").expect("chaos profile exists");
+        assert!(!profile.record_failures());
+        assert_eq!(
+            profile.recorder().args().collect::<Vec<_>>(),
+            ["record", "--chaos"]
+        );
+    }
+
+    #[test]
+    fn parse_quarantine() {
+        let config_contents = r#"
+            [[quarantine]]
+            filter = "test(flaky_network)"
+            reason = "flaky on CI, see #456"
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        let quarantine: Vec<_> = profile
+            .quarantine()
+            .iter()
+            .map(|quarantine| (quarantine.filter().as_str(), quarantine.reason()))
+            .collect();
+        assert_eq!(
+            quarantine,
+            vec![("test(flaky_network)", "flaky on CI, see #456")]
+        );
+    }
+
+    #[test]
+    fn parse_env() {
+        let config_contents = r#"
+            [profile.default.env]
+            set = { RUST_LOG = "debug" }
+            remove = ["HTTP_PROXY"]
+
+            [profile.ci.env]
+            sanitize = true
+            passthrough = ["PATH", "HOME", "HTTP_PROXY"]
+            remove = ["HTTP_PROXY"]
+        "#;
+        let config = make_config(config_contents);
+        let vars = || {
+            ["PATH", "HOME", "HTTP_PROXY", "SECRET"]
+                .iter()
+                .map(|name| (OsString::from(name), OsString::from("value")))
+        };
+        let names = |vars: Vec<(OsString, OsString)>| -> Vec<OsString> {
+            vars.into_iter().map(|(name, _)| name).collect()
+        };
+
+        let default_env = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .env();
+        assert_eq!(
+            default_env.set().get("RUST_LOG").map(String::as_str),
+            Some("debug"),
+            "variable names keep their case"
+        );
+        assert!(!default_env.inherits_all());
+        assert_eq!(
+            names(default_env.inherited_vars(vars())),
+            ["PATH", "HOME", "SECRET"]
+        );
+
+        // The ci profile's section replaces the default profile's.
+        let ci_env = config.profile("ci").expect("ci profile exists").env();
+        assert!(ci_env.set().is_empty());
+        assert_eq!(names(ci_env.inherited_vars(vars())), ["PATH", "HOME"]);
+
+        let config = make_config("");
+        let default_env = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .env();
+        assert!(default_env.inherits_all());
+    }
+
+    #[test]
+    fn parse_test_threads() {
+        let nonzero = |n| NonZeroUsize::new(n).unwrap();
+        for (input, expected) in [
+            ("4", TestThreads::Count(nonzero(4))),
+            ("-1", TestThreads::NumCpusPlus(-1)),
+            ("num-cpus", TestThreads::NumCpusPlus(0)),
+            ("num-cpus*2", TestThreads::NumCpusTimes(nonzero(2))),
+        ] {
+            assert_eq!(input.parse::<TestThreads>().unwrap(), expected, "{}", input);
+        }
+        for invalid in ["0", "num-cpus*0", "num-cpus+1", "many"] {
+            invalid
+                .parse::<TestThreads>()
+                .expect_err("value is invalid");
+        }
+
+        assert_eq!(TestThreads::Count(nonzero(4)).compute(16), 4);
+        assert_eq!(TestThreads::NumCpusPlus(-1).compute(16), 15);
+        assert_eq!(
+            TestThreads::NumCpusPlus(-32).compute(16),
+            1,
+            "at least one test is run"
+        );
+        assert_eq!(TestThreads::NumCpusTimes(nonzero(2)).compute(16), 32);
+
+        let config = make_config(
+            r#"
+            [profile.default]
+            test-threads = -2
+
+            [profile.ci]
+            test-threads = "num-cpus*2"
+        "#,
+        );
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(default_profile.test_threads(), TestThreads::NumCpusPlus(-2));
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        assert_eq!(
+            ci_profile.test_threads(),
+            TestThreads::NumCpusTimes(nonzero(2))
+        );
+
+        let config = make_config("");
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(default_profile.test_threads(), TestThreads::NumCpusPlus(0));
+    }
+
+    #[test]
+    fn parse_platform_sections() {
+        let config_contents = r#"
+            [profile.default]
+            slow-timeout = "60s"
+
+            [profile.default.target.'cfg(unix)']
+            retries = 2
+
+            [profile.default.target.'cfg(not(unix))']
+            retries = 3
+            slow-timeout = "180s"
+
+            [profile.ci]
+            retries = 1
+
+            [profile.ci.target.'cfg(unix)'.env]
+            set = { CI_UNIX = "1" }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let (retries, slow_timeout) = if cfg!(unix) { (2, 60) } else { (3, 180) };
+        assert_eq!(default_profile.retries().count(), retries);
+        assert_eq!(
+            default_profile.slow_timeout().period(),
+            Duration::from_secs(slow_timeout)
+        );
+
+        // Settings in a custom profile take precedence over the default profile's platform
+        // sections.
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        assert_eq!(ci_profile.retries().count(), 1);
+        assert_eq!(
+            ci_profile.env().set().contains_key("CI_UNIX"),
+            cfg!(unix),
+            "ci profile's platform section only applies on Unix"
+        );
+
+        let config_contents = r#"
+            [profile.default.target.'not-a-triple']
+            retries = 1
+        "#;
+        let err = NextestConfig::make_default_config()
+            .with_merged(File::from_str(config_contents, FileFormat::Toml))
+            .expect("config is valid TOML")
+            .try_into::<NextestConfigImpl>()
+            .expect_err("unknown platforms are rejected");
+        assert!(
+            err.to_string().contains("invalid platform `not-a-triple`"),
+            "error mentions the platform: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_wrapper() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(leaks::)"
+            wrapper = "valgrind --leak-check=full --error-exitcode=1"
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        let settings =
+            profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "leaks::it"));
+        let wrapper = settings.wrapper().expect("wrapper is set");
+        assert_eq!(wrapper.program(), "valgrind");
+        assert_eq!(
+            wrapper.args().collect::<Vec<_>>(),
+            ["--leak-check=full", "--error-exitcode=1"]
+        );
+
+        let settings =
+            profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "unit_test"));
+        assert_eq!(settings.wrapper(), None);
+
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "all()"
+            wrapper = " "
+        "#;
+        let config = NextestConfig::make_default_config()
+            .with_merged(File::from_str(config_contents, FileFormat::Toml))
+            .expect("config is valid TOML");
+        let err = config
+            .try_into::<NextestConfigImpl>()
+            .expect_err("empty wrapper is rejected");
+        assert!(
+            err.to_string()
+                .contains("wrapper command must not be empty"),
+            "error message {} mentions empty wrapper",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_disable_aslr() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(crash::)"
+            disable-aslr = true
+
+            [[profile.default.overrides]]
+            filter = "all()"
+            disable-aslr = false
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        let settings =
+            profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "crash::it"));
+        assert!(settings.disable_aslr(), "ASLR is disabled for crash::it");
+        let settings =
+            profile.test_settings_impl(|filter| filter.matches_parts("foo", "foo", "unit_test"));
+        assert!(!settings.disable_aslr(), "ASLR is enabled for unit_test");
+    }
+
+    #[test]
+    fn parse_retries() {
+        let config_contents = r#"
+            [profile.fixed]
+            retries = { backoff = "fixed", count = 2, delay = "1s" }
+
+            [profile.exponential]
+            retries = { backoff = "exponential", count = 4, delay = "2s", max-delay = "5s" }
+
+            [profile.jitter]
+            retries = { backoff = "exponential", count = 3, delay = "10s", jitter = true }
+        "#;
+        let config = make_config(config_contents);
+
+        let default_retries = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .retries();
+        assert_eq!(default_retries, RetryPolicy::new_without_delay(0));
+        assert_eq!(default_retries.delay_after(1), Duration::ZERO);
+
+        let fixed = config
+            .profile("fixed")
+            .expect("fixed profile exists")
+            .retries();
+        assert_eq!(
+            fixed,
+            RetryPolicy::Fixed {
+                count: 2,
+                delay: Duration::from_secs(1),
+                jitter: false,
+            }
+        );
+        assert_eq!(fixed.delay_after(1), Duration::from_secs(1));
+        assert_eq!(fixed.delay_after(2), Duration::from_secs(1));
+
+        let exponential = config
+            .profile("exponential")
+            .expect("exponential profile exists")
+            .retries();
+        assert_eq!(exponential.count(), 4);
+        let delays: Vec<_> = (1..=4)
+            .map(|attempt| exponential.delay_after(attempt))
+            .collect();
+        assert_eq!(
+            delays,
+            vec![
+                Duration::from_secs(2),
+                Duration::from_secs(4),
+                Duration::from_secs(5),
+                Duration::from_secs(5),
+            ]
+        );
+
+        let jitter = config
+            .profile("jitter")
+            .expect("jitter profile exists")
+            .retries();
+        for attempt in 1..=3 {
+            let max = Duration::from_secs(10) * 2u32.pow(attempt as u32 - 1);
+            let delay = jitter.delay_after(attempt);
+            assert!(
+                delay >= max / 2 && delay <= max,
+                "delay {:?} for attempt {} is within [{:?}, {:?}]",
+                delay,
+                attempt,
+                max / 2,
+                max
+            );
+        }
+    }
+
+    #[test]
+    fn parse_overrides_invalid_filter() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(foo"
+            retries = 5
+        "#;
+        let config = NextestConfig::make_default_config()
+            .with_merged(File::from_str(config_contents, FileFormat::Toml))
+            .expect("config is valid TOML");
+        let err = config
+            .try_into::<NextestConfigImpl>()
+            .expect_err("invalid filter expression is rejected");
+        assert!(
+            err.to_string().contains("error parsing filter expression"),
+            "error message {} mentions filter expression",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_batch_size() {
+        let config_contents = r#"
+            [profile.default]
+            batch-size = 64
+
+            [[profile.default.overrides]]
+            filter = "test(isolated::)"
+            batch-size = 1
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        assert_eq!(profile.batch_size().get(), 64);
+
+        let batch_size = |test_name| {
+            profile
+                .test_settings_impl(|filter| filter.matches_parts("foo", "foo", test_name))
+                .batch_size()
+                .get()
+        };
+        assert_eq!(batch_size("isolated::state"), 1);
+        assert_eq!(batch_size("unit_test"), 64);
+
+        let config_contents = r#"
+            [profile.default]
+            batch-size = 0
+        "#;
+        let config = NextestConfig::make_default_config()
+            .with_merged(File::from_str(config_contents, FileFormat::Toml))
+            .expect("config is valid TOML");
+        config
+            .try_into::<NextestConfigImpl>()
+            .expect_err("a batch size of 0 is rejected");
+    }
+
+    #[test]
+    fn parse_priority() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(smoke::)"
+            priority = 10
+
+            [[profile.default.overrides]]
+            filter = "test(slow::)"
+            priority = -5
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        for (test_name, expected) in [("smoke::login", 10), ("slow::build", -5), ("unit_test", 0)] {
+            let priority = profile
+                .test_settings_impl(|filter| filter.matches_parts("foo", "foo", test_name))
+                .priority();
+            assert_eq!(priority, expected, "for test {}", test_name);
+        }
+    }
+
+    #[test]
+    fn parse_threads_required() {
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(serial::)"
+            threads-required = "num-test-threads"
+
+            [[profile.default.overrides]]
+            filter = "test(heavy::)"
+            threads-required = 4
+        "#;
+        let config = make_config(config_contents);
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+
+        let cases = [
+            ("serial::port", ThreadsRequired::NumTestThreads, 8),
+            (
+                "heavy::build",
+                ThreadsRequired::Count(NonZeroUsize::new(4).unwrap()),
+                4,
+            ),
+            ("unit_test", ThreadsRequired::default(), 1),
+        ];
+        for (test_name, expected, computed) in cases {
+            let threads_required = profile
+                .test_settings_impl(|filter| filter.matches_parts("foo", "foo", test_name))
+                .threads_required();
+            assert_eq!(threads_required, expected, "for test {}", test_name);
+            assert_eq!(
+                threads_required.compute(8),
+                computed,
+                "computed threads for test {}",
+                test_name
+            );
+        }
+        // Capped to the number of test threads.
+        assert_eq!(
+            ThreadsRequired::Count(NonZeroUsize::new(4).unwrap()).compute(2),
+            2
+        );
+
+        for invalid in &["0", "\"all\"", "-1"] {
+            let config_contents = format!(
+                r#"
+                [[profile.default.overrides]]
+                filter = "all()"
+                threads-required = {}
+            "#,
+                invalid
+            );
+            let config = NextestConfig::make_default_config()
+                .with_merged(File::from_str(&config_contents, FileFormat::Toml))
+                .expect("config is valid TOML");
+            config
+                .try_into::<NextestConfigImpl>()
+                .expect_err("invalid threads-required is rejected");
+        }
+    }
+
+    #[test]
+    fn parse_test_groups() {
+        let config = make_config(
+            r#"
+            [test-groups.db]
+            max-threads = 2
+        "#,
+        );
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let groups: Vec<_> = profile
+            .test_groups()
+            .map(|(name, group)| (name, group.max_threads().get()))
+            .collect();
+        assert_eq!(groups, vec![("db", 2)]);
+
+        let config = make_config(
+            r#"
+            [test-groups.fixtures]
+            max-threads = 1
+            scope = "package"
+        "#,
+        );
+        let profile = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists");
+        let groups: Vec<_> = profile
+            .test_groups()
+            .map(|(name, group)| (name, group.scope()))
+            .collect();
+        assert_eq!(groups, vec![("fixtures", TestGroupScope::Package)]);
+
+        let config_contents = r#"
+            [[profile.default.overrides]]
+            filter = "test(db::)"
+            test-group = "unknown"
+        "#;
+        let inner: NextestConfigImpl = NextestConfig::make_default_config()
+            .with_merged(File::from_str(config_contents, FileFormat::Toml))
+            .expect("config is valid TOML")
+            .try_into()
+            .expect("config deserializes correctly");
+        let err = inner
+            .validate()
+            .expect_err("unknown test group is rejected");
+        assert!(
+            err.to_string().contains("unknown test group `unknown`"),
+            "error message {} mentions test group",
+            err
+        );
+    }
+
+    #[test]
+    fn parse_resource_limits() {
+        let config = make_config(
+            r#"
+            [profile.default]
+            resource-limits = { address-space = "4GiB", cpu-time = "5m" }
+
+            [profile.ci]
+            resource-limits = { cpu-time = "10m" }
+
+            [[profile.ci.overrides]]
+            filter = "test(large)"
+            resource-limits = { address-space = 1000 }
+        "#,
+        );
+
+        let default_limits = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .resource_limits();
+        assert_eq!(default_limits.address_space(), Some(4 << 30));
+        assert_eq!(default_limits.cpu_time(), Some(Duration::from_secs(300)));
+
+        let ci_profile = config.profile("ci").expect("ci profile exists");
+        // Each limit is set separately.
+        let ci_limits = ci_profile.resource_limits();
+        assert_eq!(ci_limits.address_space(), Some(4 << 30));
+        assert_eq!(ci_limits.cpu_time(), Some(Duration::from_secs(600)));
+        let large_limits = ci_profile
+            .test_settings_impl(|filter| filter.matches_parts("foo", "foo", "large"))
+            .resource_limits();
+        assert_eq!(large_limits.address_space(), Some(1000));
+        assert_eq!(large_limits.cpu_time(), Some(Duration::from_secs(600)));
+    }
+
+    #[test]
+    fn parse_throttle() {
+        let config = make_config(
+            r#"
+            [profile.default]
+            throttle = { max-load = 1.5 }
+
+            [profile.ci]
+            throttle = { min-available-memory = "2GiB" }
+        "#,
+        );
+
+        let default_throttle = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .throttle();
+        assert_eq!(default_throttle.max_load(), Some(1.5));
+        assert_eq!(default_throttle.min_available_memory(), None);
+
+        // Each limit is set separately.
+        let ci_throttle = config.profile("ci").expect("ci profile exists").throttle();
+        assert_eq!(ci_throttle.max_load(), Some(1.5));
+        assert_eq!(ci_throttle.min_available_memory(), Some(2 << 30));
+
+        let config = make_config("");
+        assert!(config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .throttle()
+            .is_empty());
+    }
+
+    #[test]
+    fn parse_cpu_affinity() {
+        let config = make_config(
+            r#"
+            [profile.default]
+            cpu-affinity = ["0-1", "2,3", 4]
+
+            [profile.ci]
+            cpu-affinity = []
+        "#,
+        );
+
+        let sets: Vec<_> = config
+            .profile(NextestConfig::DEFAULT_PROFILE)
+            .expect("default profile exists")
+            .cpu_affinity()
+            .iter()
+            .map(|set| set.to_string())
+            .collect();
+        assert_eq!(sets, vec!["0-1", "2-3", "4"]);
+
+        // An empty list turns off pinning in a custom profile.
+        assert!(config
+            .profile("ci")
+            .expect("ci profile exists")
+            .cpu_affinity()
+            .is_empty());
+    }
+
+    #[test]
+    fn parse_nice() {
+        let config = make_config(
+            r#"
+            [profile.default]
+            nice = 10
+
+            [profile.ci]
+            nice = 0
+        "#,
+        );
+        assert_eq!(
+            config
+                .profile(NextestConfig::DEFAULT_PROFILE)
+                .expect("default profile exists")
+                .nice(),
+            10
+        );
+        assert_eq!(config.profile("ci").expect("ci profile exists").nice(), 0);
+
+        for invalid in ["20", "-5"] {
+            let result: Result<NextestConfigImpl, _> = NextestConfig::make_default_config()
+                .with_merged(File::from_str(
+                    &format!("[profile.default]\nnice = {}", invalid),
+                    FileFormat::Toml,
+                ))
+                .expect("config is valid TOML")
+                .try_into();
+            result.expect_err("nice value is out of range");
+        }
+    }
+
+    #[test]
+    fn parse_cpu_sets() {
+        let cpus = |s: &str| {
+            s.parse::<CpuSet>()
+                .ok()
+                .map(|set| set.iter().collect::<Vec<_>>())
+        };
+        assert_eq!(cpus("3"), Some(vec![3]));
+        assert_eq!(cpus("0-2, 8"), Some(vec![0, 1, 2, 8]));
+        assert_eq!(cpus("1023"), Some(vec![1023]));
+        assert_eq!(cpus(""), None);
+        assert_eq!(cpus("1024"), None);
+        assert_eq!(cpus("3-1"), None);
+        assert_eq!(cpus("0-"), None);
+        assert_eq!(cpus("a"), None);
+
+        let set: CpuSet = "8,0-1,2,5-6,7".parse().expect("CPU set is valid");
+        assert_eq!(set.to_string(), "0-2,5-8");
+    }
+
+    #[test]
+    fn parse_byte_sizes() {
+        assert_eq!(parse_byte_size("1024"), Some(1024));
+        assert_eq!(parse_byte_size("1024B"), Some(1024));
+        assert_eq!(parse_byte_size("512MiB"), Some(512 << 20));
+        assert_eq!(parse_byte_size(" 4 GB "), Some(4_000_000_000));
+        assert_eq!(parse_byte_size("1TiB"), Some(1 << 40));
+        assert_eq!(parse_byte_size("4gb"), None);
+        assert_eq!(parse_byte_size("GiB"), None);
+        assert_eq!(parse_byte_size("-1"), None);
+        assert_eq!(parse_byte_size("99999999999TiB"), None);
+    }
+
+    fn make_config(contents: &str) -> NextestConfig {
+        let config = NextestConfig::make_default_config()
+            .with_merged(File::from_str(contents, FileFormat::Toml))
+            .expect("config is valid");
+        NextestConfig {
+            workspace_root: "/fake/dir".into(),
+            sources: vec![ConfigSource::Default],
+            merged: config.clone().try_into().expect("config is valid TOML"),
+            inner: config.try_into().expect("config deserializes correctly"),
+        }
     }
 }
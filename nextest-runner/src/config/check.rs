@@ -0,0 +1,850 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Checks config sources for mistakes that don't stop the config from being read, such as
+//! misspelled keys, which serde otherwise ignores.
+
+use super::{
+    env_overrides, ConfigExperimental, ConfigSource, NextestConfig, NextestConfigImpl,
+    ToolConfigFile,
+};
+use crate::filter_expr::FilterExpr;
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{de, Deserialize, Deserializer};
+use std::{convert::TryFrom, ffi::OsString, fmt};
+
+impl NextestConfig {
+    /// Checks the sources that [`from_sources`](Self::from_sources) reads the config from, and
+    /// returns the problems found in them, in the order the sources are read.
+    ///
+    /// Problems that stop the config from being read at all are reported by `from_sources`
+    /// instead, although syntax errors are reported here too. Files that can't be read are
+    /// skipped.
+    pub fn check_sources<'a>(
+        workspace_root: impl Into<Utf8PathBuf>,
+        config_file: Option<&Utf8Path>,
+        tool_config_files: impl IntoIterator<Item = &'a ToolConfigFile>,
+    ) -> Vec<ConfigDiagnostic> {
+        Self::check_sources_impl(
+            &workspace_root.into(),
+            config_file,
+            tool_config_files,
+            Self::user_config_path().as_deref(),
+            std::env::vars_os(),
+        )
+    }
+
+    fn check_sources_impl<'a>(
+        workspace_root: &Utf8Path,
+        file: Option<&Utf8Path>,
+        tool_files: impl IntoIterator<Item = &'a ToolConfigFile>,
+        user_file: Option<&Utf8Path>,
+        env_vars: impl IntoIterator<Item = (OsString, OsString)>,
+    ) -> Vec<ConfigDiagnostic> {
+        let mut diagnostics = vec![];
+
+        let mut files = vec![];
+        for tool_file in tool_files {
+            let source = ConfigSource::Tool {
+                tool: tool_file.tool.clone(),
+                path: tool_file.config_file.clone(),
+            };
+            files.push((source, tool_file.config_file.clone()));
+        }
+        if let Some(user_file) = user_file {
+            files.push((
+                ConfigSource::User(user_file.to_owned()),
+                user_file.to_owned(),
+            ));
+        }
+        let file = file.map_or_else(|| workspace_root.join(Self::CONFIG_PATH), ToOwned::to_owned);
+        files.push((ConfigSource::Workspace(file.clone()), file));
+
+        for (source, path) in files {
+            let contents = match std::fs::read_to_string(&path) {
+                Ok(contents) => contents,
+                Err(_) => continue,
+            };
+            match toml::from_str::<SpannedValue>(&contents) {
+                Ok(spanned) => {
+                    let value = toml::from_str(&contents).expect("contents were parsed above");
+                    Checker::new(&source, &contents, &mut diagnostics).check(&spanned, value);
+                }
+                Err(err) => diagnostics.push(ConfigDiagnostic {
+                    source,
+                    location: None,
+                    kind: ConfigDiagnosticKind::Syntax {
+                        message: err.to_string(),
+                    },
+                }),
+            }
+        }
+
+        // Errors in the names of overrides are reported when the config is read.
+        if let Ok(overrides) = env_overrides(env_vars) {
+            for (var, value) in overrides {
+                let source = ConfigSource::Env(var);
+                Checker::new(&source, "", &mut diagnostics)
+                    .check(&SpannedValue::from(&value), value);
+            }
+        }
+
+        diagnostics
+    }
+}
+
+/// A problem found in a config source by [`NextestConfig::check_sources`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ConfigDiagnostic {
+    source: ConfigSource,
+    location: Option<ConfigLocation>,
+    kind: ConfigDiagnosticKind,
+}
+
+impl ConfigDiagnostic {
+    /// Returns the source the problem was found in.
+    pub fn source(&self) -> &ConfigSource {
+        &self.source
+    }
+
+    /// Returns where in the source the problem was found, if known.
+    pub fn location(&self) -> Option<ConfigLocation> {
+        self.location
+    }
+
+    /// Returns the kind of problem found.
+    pub fn kind(&self) -> &ConfigDiagnosticKind {
+        &self.kind
+    }
+
+    /// Returns true if the problem stops the config from being used, and false if it's a warning.
+    pub fn is_error(&self) -> bool {
+        match self.kind {
+            ConfigDiagnosticKind::Syntax { .. }
+            | ConfigDiagnosticKind::InvalidFilterExpr { .. } => true,
+            ConfigDiagnosticKind::UnknownKey { .. }
+            | ConfigDiagnosticKind::UnknownExperimentalFeature { .. }
+            | ConfigDiagnosticKind::ConflictingOverride { .. } => false,
+        }
+    }
+}
+
+impl fmt::Display for ConfigDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let path = match &self.source {
+            ConfigSource::Tool { path, .. }
+            | ConfigSource::User(path)
+            | ConfigSource::Workspace(path) => Some(path),
+            ConfigSource::Default | ConfigSource::Env(_) => None,
+        };
+        match (path, self.location) {
+            (Some(path), Some(location)) => write!(f, "{}:{}: ", path, location)?,
+            _ => write!(f, "{}: ", self.source)?,
+        }
+
+        match &self.kind {
+            ConfigDiagnosticKind::Syntax { message } => write!(f, "{}", message),
+            ConfigDiagnosticKind::UnknownKey { key } => write!(f, "unknown key `{}`", key),
+            ConfigDiagnosticKind::UnknownExperimentalFeature { feature } => write!(
+                f,
+                "unknown experimental feature `{}`, ignoring it (known features: {})",
+                feature,
+                ConfigExperimental::ALL
+                    .iter()
+                    .map(|feature| feature.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            ConfigDiagnosticKind::InvalidFilterExpr { key, message } => {
+                write!(f, "invalid filter expression for `{}`: {}", key, message)
+            }
+            ConfigDiagnosticKind::ConflictingOverride { key, earlier } => write!(
+                f,
+                "`{}` has no effect: the earlier override `{}` sets it for every test that this \
+                 override matches",
+                key, earlier
+            ),
+        }
+    }
+}
+
+/// The kind of problem found in a [`ConfigDiagnostic`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum ConfigDiagnosticKind {
+    /// The source isn't valid TOML.
+    Syntax {
+        /// The error message produced by the TOML parser.
+        message: String,
+    },
+
+    /// A key that nextest doesn't recognize, such as a misspelled setting.
+    UnknownKey {
+        /// The full path to the key, for example `profile.ci.retires`.
+        key: String,
+    },
+
+    /// A feature in the `experimental` list that nextest doesn't recognize, such as one added in a
+    /// later version. It's ignored.
+    UnknownExperimentalFeature {
+        /// The name of the feature.
+        feature: String,
+    },
+
+    /// A filter expression that couldn't be parsed.
+    InvalidFilterExpr {
+        /// The full path to the key the filter expression is set for.
+        key: String,
+
+        /// The error produced while parsing the filter expression.
+        message: String,
+    },
+
+    /// A setting in an override that never applies, because an earlier override in the same
+    /// profile sets it for every test that the override matches.
+    ConflictingOverride {
+        /// The full path to the setting, for example `profile.default.overrides[1].retries`.
+        key: String,
+
+        /// The full path to the earlier override, for example `profile.default.overrides[0]`.
+        earlier: String,
+    },
+}
+
+/// A location within a config file. Lines and columns start at 1.
+#[derive(Copy, Clone, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub struct ConfigLocation {
+    /// The line number.
+    pub line: usize,
+
+    /// The column number, in characters.
+    pub column: usize,
+}
+
+impl ConfigLocation {
+    fn from_offset(contents: &str, offset: usize) -> Self {
+        let before = &contents[..offset];
+        let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+        Self {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl fmt::Display for ConfigLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
+
+// ---
+// Checking
+// ---
+
+struct Checker<'a> {
+    source: &'a ConfigSource,
+    contents: &'a str,
+    diagnostics: &'a mut Vec<ConfigDiagnostic>,
+}
+
+impl<'a> Checker<'a> {
+    fn new(
+        source: &'a ConfigSource,
+        contents: &'a str,
+        diagnostics: &'a mut Vec<ConfigDiagnostic>,
+    ) -> Self {
+        Self {
+            source,
+            contents,
+            diagnostics,
+        }
+    }
+
+    /// Checks a source, given both with spans (to locate problems) and as a plain value (to read
+    /// it the way nextest does).
+    fn check(&mut self, spanned: &SpannedValue, value: toml::Value) {
+        let start = self.diagnostics.len();
+        let invalid_filters = self.check_values(spanned);
+        self.check_unknown_keys(spanned, value, &invalid_filters);
+        // Report problems in the order they appear in the source.
+        self.diagnostics[start..].sort_by_key(|diagnostic| diagnostic.location);
+    }
+
+    /// Checks the `experimental` list, filter expressions and overrides. Returns the paths to the
+    /// filter expressions that couldn't be parsed.
+    fn check_values(&mut self, value: &SpannedValue) -> Vec<Vec<PathSegment>> {
+        // Values of the wrong type are reported when the config is read.
+        let mut invalid_filters = vec![];
+
+        if let Some((key, SpannedValue::Array(items))) = value.entry("experimental") {
+            // Items don't have spans of their own, so they're reported at the key.
+            for item in items {
+                if let SpannedValue::String(name) = item {
+                    if ConfigExperimental::try_from(name.clone()).is_err() {
+                        self.push(
+                            key.span,
+                            ConfigDiagnosticKind::UnknownExperimentalFeature {
+                                feature: name.clone(),
+                            },
+                        );
+                    }
+                }
+            }
+        }
+
+        for name in &["exclude", "quarantine"] {
+            if let Some((_, SpannedValue::Array(items))) = value.entry(name) {
+                for (idx, item) in items.iter().enumerate() {
+                    let path = vec![PathSegment::key(name), PathSegment::Index(idx)];
+                    self.check_filter(item, path, "filter", &mut invalid_filters);
+                }
+            }
+        }
+
+        if let Some((_, SpannedValue::Table(profiles))) = value.entry("profile") {
+            for (name, profile) in profiles {
+                let path = vec![PathSegment::key("profile"), PathSegment::key(&name.name)];
+                self.check_filter(
+                    profile,
+                    path.clone(),
+                    "default-filter",
+                    &mut invalid_filters,
+                );
+
+                if let Some((_, SpannedValue::Array(items))) = profile.entry("overrides") {
+                    let mut path = path;
+                    path.push(PathSegment::key("overrides"));
+                    for (idx, item) in items.iter().enumerate() {
+                        let mut item_path = path.clone();
+                        item_path.push(PathSegment::Index(idx));
+                        self.check_filter(item, item_path, "filter", &mut invalid_filters);
+                    }
+                    self.check_conflicting_overrides(items, &display_path(&path));
+                }
+            }
+        }
+
+        invalid_filters
+    }
+
+    /// Checks the filter expression set for `name` in the table at `path`, if any.
+    fn check_filter(
+        &mut self,
+        table: &SpannedValue,
+        mut path: Vec<PathSegment>,
+        name: &str,
+        invalid_filters: &mut Vec<Vec<PathSegment>>,
+    ) {
+        if let Some((key, SpannedValue::String(input))) = table.entry(name) {
+            if let Err(err) = FilterExpr::parse(input.as_str()) {
+                path.push(PathSegment::key(name));
+                self.push(
+                    key.span,
+                    ConfigDiagnosticKind::InvalidFilterExpr {
+                        key: display_path(&path),
+                        message: err.to_string(),
+                    },
+                );
+                invalid_filters.push(path);
+            }
+        }
+    }
+
+    /// Reports settings that are shadowed by an earlier override with the same filter, or one that
+    /// matches every test: the first override that matches a test and sets a setting wins.
+    fn check_conflicting_overrides(&mut self, items: &[SpannedValue], path: &str) {
+        let filter = |item: &SpannedValue| match item.get("filter") {
+            Some(SpannedValue::String(filter)) => Some(filter.trim().to_owned()),
+            _ => None,
+        };
+
+        for (idx, item) in items.iter().enumerate() {
+            let entries = match item {
+                SpannedValue::Table(entries) => entries,
+                _ => continue,
+            };
+            let item_filter = filter(item);
+            for (key, _) in entries.iter().filter(|(key, _)| key.name != "filter") {
+                let earlier = items[..idx].iter().position(|earlier| {
+                    let earlier_filter = filter(earlier);
+                    earlier.get(&key.name).is_some()
+                        && earlier_filter.is_some()
+                        && (earlier_filter == item_filter
+                            || earlier_filter.as_deref() == Some("all()"))
+                });
+                if let Some(earlier) = earlier {
+                    self.push(
+                        key.span,
+                        ConfigDiagnosticKind::ConflictingOverride {
+                            key: format!("{}[{}].{}", path, idx, key.name),
+                            earlier: format!("{}[{}]", path, earlier),
+                        },
+                    );
+                }
+            }
+        }
+    }
+
+    /// Reports the keys that are ignored when the source is read into the config. The source is
+    /// merged into the default config first, so that settings it doesn't set are still present.
+    fn check_unknown_keys(
+        &mut self,
+        spanned: &SpannedValue,
+        value: toml::Value,
+        invalid_filters: &[Vec<PathSegment>],
+    ) {
+        let mut config: toml::Value =
+            toml::from_str(NextestConfig::DEFAULT_CONFIG).expect("default config is valid");
+        merge_value(&mut config, value);
+        // Invalid filter expressions were reported above. Replace them, so that reading the config
+        // doesn't stop at them.
+        for path in invalid_filters {
+            if let Some(filter) = value_at_mut(&mut config, path) {
+                *filter = toml::Value::String("all()".to_owned());
+            }
+        }
+
+        // Other errors stop the config from being read here too, but they're reported by
+        // `from_sources`.
+        let mut unknown = vec![];
+        let _: Result<NextestConfigImpl, _> = serde_ignored::deserialize(config, |path| {
+            unknown.push(PathSegment::from_ignored(&path))
+        });
+        for path in unknown {
+            self.push(
+                spanned.span_at(&path),
+                ConfigDiagnosticKind::UnknownKey {
+                    key: display_path(&path),
+                },
+            );
+        }
+    }
+
+    fn push(&mut self, span: Option<usize>, kind: ConfigDiagnosticKind) {
+        self.diagnostics.push(ConfigDiagnostic {
+            source: self.source.clone(),
+            location: span.map(|offset| ConfigLocation::from_offset(self.contents, offset)),
+            kind,
+        });
+    }
+}
+
+/// A segment of the path to a value in a config source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum PathSegment {
+    Key(String),
+    Index(usize),
+}
+
+impl PathSegment {
+    fn key(name: &str) -> Self {
+        PathSegment::Key(name.to_owned())
+    }
+
+    fn from_ignored(path: &serde_ignored::Path<'_>) -> Vec<Self> {
+        let (parent, segment) = match path {
+            serde_ignored::Path::Root => return vec![],
+            serde_ignored::Path::Seq { parent, index } => {
+                (parent, Some(PathSegment::Index(*index)))
+            }
+            serde_ignored::Path::Map { parent, key } => (parent, Some(PathSegment::key(key))),
+            serde_ignored::Path::Some { parent }
+            | serde_ignored::Path::NewtypeStruct { parent }
+            | serde_ignored::Path::NewtypeVariant { parent } => (parent, None),
+        };
+        let mut segments = Self::from_ignored(parent);
+        segments.extend(segment);
+        segments
+    }
+}
+
+/// Formats a path the way it's written in diagnostics, for example `profile.ci.overrides[1]`.
+fn display_path(path: &[PathSegment]) -> String {
+    let mut output = String::new();
+    for segment in path {
+        match segment {
+            PathSegment::Key(key) => {
+                if !output.is_empty() {
+                    output.push('.');
+                }
+                output.push_str(key);
+            }
+            PathSegment::Index(idx) => output.push_str(&format!("[{}]", idx)),
+        }
+    }
+    output
+}
+
+/// Merges `value` into `base` the way config sources are merged: tables are merged key by key,
+/// and other values replace the value in `base`.
+fn merge_value(base: &mut toml::Value, value: toml::Value) {
+    match (base, value) {
+        (toml::Value::Table(base), toml::Value::Table(table)) => {
+            for (key, value) in table {
+                match base.get_mut(&key) {
+                    Some(base_value) => merge_value(base_value, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, value) => *base = value,
+    }
+}
+
+fn value_at_mut<'v>(
+    mut value: &'v mut toml::Value,
+    path: &[PathSegment],
+) -> Option<&'v mut toml::Value> {
+    for segment in path {
+        value = match (segment, value) {
+            (PathSegment::Key(key), toml::Value::Table(table)) => table.get_mut(key)?,
+            (PathSegment::Index(idx), toml::Value::Array(items)) => items.get_mut(*idx)?,
+            _ => return None,
+        };
+    }
+    Some(value)
+}
+
+// ---
+// Parsing with spans
+// ---
+
+/// A TOML value that records where each key starts, so that problems can be reported with line
+/// numbers.
+#[derive(Debug)]
+enum SpannedValue {
+    Table(Vec<(SpannedKey, SpannedValue)>),
+    Array(Vec<SpannedValue>),
+    String(String),
+    Other,
+}
+
+impl SpannedValue {
+    fn entry(&self, name: &str) -> Option<(&SpannedKey, &SpannedValue)> {
+        match self {
+            SpannedValue::Table(entries) => entries
+                .iter()
+                .find(|(key, _)| key.name == name)
+                .map(|(key, value)| (key, value)),
+            _ => None,
+        }
+    }
+
+    fn get(&self, name: &str) -> Option<&SpannedValue> {
+        self.entry(name).map(|(_, value)| value)
+    }
+
+    /// Returns where the last key in the path starts.
+    fn span_at(&self, path: &[PathSegment]) -> Option<usize> {
+        let mut value = self;
+        let mut span = None;
+        for segment in path {
+            match (segment, value) {
+                (PathSegment::Key(name), _) => {
+                    let (key, entry) = value.entry(name)?;
+                    span = key.span;
+                    value = entry;
+                }
+                (PathSegment::Index(idx), SpannedValue::Array(items)) => value = items.get(*idx)?,
+                _ => return None,
+            }
+        }
+        span
+    }
+}
+
+impl From<&toml::Value> for SpannedValue {
+    fn from(value: &toml::Value) -> Self {
+        match value {
+            toml::Value::Table(table) => SpannedValue::Table(
+                table
+                    .iter()
+                    .map(|(name, value)| {
+                        let key = SpannedKey {
+                            name: name.clone(),
+                            span: None,
+                        };
+                        (key, value.into())
+                    })
+                    .collect(),
+            ),
+            toml::Value::Array(items) => {
+                SpannedValue::Array(items.iter().map(SpannedValue::from).collect())
+            }
+            toml::Value::String(value) => SpannedValue::String(value.clone()),
+            _ => SpannedValue::Other,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for SpannedValue {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct SpannedValueVisitor;
+
+        impl<'de> de::Visitor<'de> for SpannedValueVisitor {
+            type Value = SpannedValue;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a TOML value")
+            }
+
+            fn visit_bool<E: de::Error>(self, _: bool) -> Result<Self::Value, E> {
+                Ok(SpannedValue::Other)
+            }
+
+            fn visit_i64<E: de::Error>(self, _: i64) -> Result<Self::Value, E> {
+                Ok(SpannedValue::Other)
+            }
+
+            fn visit_u64<E: de::Error>(self, _: u64) -> Result<Self::Value, E> {
+                Ok(SpannedValue::Other)
+            }
+
+            fn visit_f64<E: de::Error>(self, _: f64) -> Result<Self::Value, E> {
+                Ok(SpannedValue::Other)
+            }
+
+            fn visit_str<E: de::Error>(self, value: &str) -> Result<Self::Value, E> {
+                Ok(SpannedValue::String(value.to_owned()))
+            }
+
+            fn visit_seq<A>(self, mut seq: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::SeqAccess<'de>,
+            {
+                let mut items = vec![];
+                while let Some(item) = seq.next_element()? {
+                    items.push(item);
+                }
+                Ok(SpannedValue::Array(items))
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: de::MapAccess<'de>,
+            {
+                let mut entries = vec![];
+                while let Some(key) = map.next_key::<SpannedKey>()? {
+                    let value = map.next_value()?;
+                    entries.push((key, value));
+                }
+                Ok(SpannedValue::Table(entries))
+            }
+        }
+
+        deserializer.deserialize_any(SpannedValueVisitor)
+    }
+}
+
+#[derive(Debug)]
+struct SpannedKey {
+    name: String,
+    // The byte offset the key starts at.
+    span: Option<usize>,
+}
+
+impl<'de> Deserialize<'de> for SpannedKey {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let key = toml::Spanned::<String>::deserialize(deserializer)?;
+        Ok(Self {
+            span: Some(key.start()),
+            name: key.into_inner(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+
+    fn check(source: &ConfigSource, contents: &str) -> Vec<ConfigDiagnostic> {
+        let spanned: SpannedValue = toml::from_str(contents).expect("config is valid");
+        let value = toml::from_str(contents).expect("config is valid");
+        let mut diagnostics = vec![];
+        Checker::new(source, contents, &mut diagnostics).check(&spanned, value);
+        diagnostics
+    }
+
+    #[test]
+    fn default_config_is_clean() {
+        let diagnostics = check(&ConfigSource::Default, NextestConfig::DEFAULT_CONFIG);
+        assert_eq!(diagnostics, vec![]);
+    }
+
+    #[test]
+    fn unknown_keys() {
+        let contents = indoc! {r#"
+            nextest-version = { required = "0.9.50", recomended = "0.9.60" }
+            colour = "always"
+
+            [profile.default]
+            retries = { backoff = "exponential", count = 2, delay = "1s", max-dealy = "10s" }
+
+            [profile.ci.target.'cfg(unix)']
+            grace-period = "1s"
+            grace = "1s"
+
+            [[profile.ci.overrides]]
+            filter = "test(foo)"
+            slow-timout = "60s"
+
+            [tool.my-tool]
+            anything = true
+        "#};
+        let source = ConfigSource::Workspace("nextest.toml".into());
+        let diagnostics: Vec<_> = check(&source, contents)
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.location().expect("key is located").to_string(),
+                    diagnostic.kind().clone(),
+                )
+            })
+            .collect();
+        let unknown = |location: &str, key: &str| {
+            (
+                location.to_owned(),
+                ConfigDiagnosticKind::UnknownKey {
+                    key: key.to_owned(),
+                },
+            )
+        };
+        assert_eq!(
+            diagnostics,
+            vec![
+                unknown("1:42", "nextest-version.recomended"),
+                unknown("2:1", "colour"),
+                unknown("5:63", "profile.default.retries.max-dealy"),
+                unknown("9:1", "profile.ci.target.cfg(unix).grace"),
+                unknown("13:1", "profile.ci.overrides[0].slow-timout"),
+            ]
+        );
+    }
+
+    #[test]
+    fn unknown_experimental_features() {
+        let contents = indoc! {r#"
+            experimental = ["setup-scripts", "time-travel"]
+        "#};
+        let source = ConfigSource::Workspace("nextest.toml".into());
+        let diagnostics = check(&source, contents);
+        assert_eq!(diagnostics.len(), 1, "diagnostics: {:?}", diagnostics);
+        assert_eq!(
+            diagnostics[0].kind(),
+            &ConfigDiagnosticKind::UnknownExperimentalFeature {
+                feature: "time-travel".to_owned()
+            }
+        );
+        assert!(!diagnostics[0].is_error(), "unknown features are ignored");
+        assert_eq!(
+            diagnostics[0].to_string(),
+            "nextest.toml:1:1: unknown experimental feature `time-travel`, ignoring it \
+             (known features: setup-scripts)"
+        );
+    }
+
+    #[test]
+    fn check_sources() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let workspace_root = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        let config_path = workspace_root.join("nextest.toml");
+        std::fs::write(
+            &config_path,
+            indoc! {r#"
+                [profile.ci]
+                retires = 2
+                slow-timeout = { period = "60s", terminate-afer = 2 }
+
+                [[profile.ci.overrides]]
+                filter = "all()"
+                retries = 3
+
+                [[profile.ci.overrides]]
+                filter = "test(foo)"
+                retries = 4
+                threads-required = 2
+
+                [[profile.ci.overrides]]
+                filter = "test(foo"
+                threads-required = 3
+
+                [tool.my-tool]
+                anything = true
+            "#},
+        )
+        .expect("wrote config");
+
+        let env_vars = vec![(
+            "NEXTEST_CONFIG_PROFILE__CI__FAIL_FST".into(),
+            "false".into(),
+        )];
+        let diagnostics = NextestConfig::check_sources_impl(
+            workspace_root,
+            Some(&config_path),
+            &[],
+            None,
+            env_vars,
+        );
+        let messages: Vec<_> = diagnostics
+            .iter()
+            .map(|diagnostic| {
+                (
+                    diagnostic.location().map(|location| location.to_string()),
+                    diagnostic.kind().clone(),
+                    diagnostic.is_error(),
+                )
+            })
+            .collect();
+
+        let unknown = |key: &str| ConfigDiagnosticKind::UnknownKey {
+            key: key.to_owned(),
+        };
+        assert_eq!(messages.len(), 5, "diagnostics: {:?}", diagnostics);
+        assert_eq!(
+            messages[0],
+            (Some("2:1".to_owned()), unknown("profile.ci.retires"), false)
+        );
+        assert_eq!(
+            messages[1],
+            (
+                Some("3:34".to_owned()),
+                unknown("profile.ci.slow-timeout.terminate-afer"),
+                false
+            )
+        );
+        assert_eq!(
+            messages[2],
+            (
+                Some("11:1".to_owned()),
+                ConfigDiagnosticKind::ConflictingOverride {
+                    key: "profile.ci.overrides[1].retries".to_owned(),
+                    earlier: "profile.ci.overrides[0]".to_owned(),
+                },
+                false
+            )
+        );
+        match &messages[3] {
+            (Some(location), ConfigDiagnosticKind::InvalidFilterExpr { key, .. }, true) => {
+                assert_eq!(location, "15:1");
+                assert_eq!(key, "profile.ci.overrides[2].filter");
+            }
+            other => panic!("expected invalid filter expression, found {:?}", other),
+        }
+        assert_eq!(messages[4], (None, unknown("profile.ci.fail-fst"), false));
+        assert_eq!(
+            diagnostics[4].to_string(),
+            "environment variable NEXTEST_CONFIG_PROFILE__CI__FAIL_FST: unknown key \
+             `profile.ci.fail-fst`"
+        );
+    }
+}
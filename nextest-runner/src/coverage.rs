@@ -0,0 +1,195 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! LLVM source-based code coverage.
+//!
+//! Test binaries must be built with `-C instrument-coverage` for coverage data to be produced. When
+//! coverage is enabled, each test is run with `LLVM_PROFILE_FILE` pointing to a unique `.profraw`
+//! file within a run-scoped directory. After the run, the `.profraw` files can be merged into a
+//! single `.profdata` file with `llvm-profdata`.
+
+use crate::{errors::CoverageError, test_list::TestInstance};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{convert::TryFrom, ffi::OsStr, fs};
+
+/// The environment variable LLVM reads the path to write coverage data to from.
+pub const LLVM_PROFILE_FILE_ENV: &str = "LLVM_PROFILE_FILE";
+
+/// The name of the merged coverage data file, within the coverage directory.
+pub const MERGED_PROFDATA_NAME: &str = "coverage.profdata";
+
+/// Collects coverage data for a test run into a directory.
+#[derive(Clone, Debug)]
+pub struct CoverageCollector {
+    dir: Utf8PathBuf,
+}
+
+impl CoverageCollector {
+    /// Creates a new collector that writes coverage data to `dir`.
+    ///
+    /// Coverage data is scoped to a single run: any data left over in `dir` from a previous run is
+    /// removed.
+    pub fn new(dir: impl Into<Utf8PathBuf>) -> Result<Self, CoverageError> {
+        let dir = dir.into();
+        if dir.exists() {
+            fs::remove_dir_all(&dir).map_err(|error| CoverageError::CreateDir {
+                dir: dir.clone(),
+                error,
+            })?;
+        }
+        fs::create_dir_all(&dir).map_err(|error| CoverageError::CreateDir {
+            dir: dir.clone(),
+            error,
+        })?;
+        Ok(Self { dir })
+    }
+
+    /// Returns the directory coverage data is written to.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// Returns the list of `.profraw` files written out so far, in sorted order.
+    pub fn profraw_files(&self) -> Result<Vec<Utf8PathBuf>, CoverageError> {
+        let read_dir_error = |error| CoverageError::ReadDir {
+            dir: self.dir.clone(),
+            error,
+        };
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&self.dir).map_err(read_dir_error)? {
+            let path = entry.map_err(read_dir_error)?.path();
+            // Files are named by nextest, so non-UTF-8 paths can't be coverage data.
+            if let Ok(path) = Utf8PathBuf::try_from(path) {
+                if path.extension() == Some("profraw") {
+                    files.push(path);
+                }
+            }
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    /// Merges the `.profraw` files written out during the run into a single `.profdata` file, using
+    /// the given `llvm-profdata` executable.
+    ///
+    /// Returns the path to the merged file.
+    pub fn merge(&self, llvm_profdata: impl AsRef<OsStr>) -> Result<Utf8PathBuf, CoverageError> {
+        let files = self.profraw_files()?;
+        if files.is_empty() {
+            return Err(CoverageError::NoProfileData {
+                dir: self.dir.clone(),
+            });
+        }
+
+        // Pass the list of files through a file, since there may be too many of them to fit on
+        // the command line.
+        let input_files = self.dir.join("profraw-files.txt");
+        let contents: String = files.iter().map(|file| format!("{}\n", file)).collect();
+        fs::write(&input_files, contents).map_err(|error| CoverageError::CreateDir {
+            dir: self.dir.clone(),
+            error,
+        })?;
+
+        let output = self.dir.join(MERGED_PROFDATA_NAME);
+        let llvm_profdata = llvm_profdata.as_ref();
+        let result = duct::cmd!(
+            llvm_profdata,
+            "merge",
+            "-sparse",
+            "--input-files",
+            input_files.as_str(),
+            "-o",
+            output.as_str()
+        )
+        .stdout_capture()
+        .stderr_capture()
+        .unchecked()
+        .run()
+        .map_err(|error| CoverageError::MergeExec {
+            program: llvm_profdata.to_string_lossy().into_owned(),
+            error,
+        })?;
+        if !result.status.success() {
+            return Err(CoverageError::MergeFailed {
+                status: result.status,
+                stderr: String::from_utf8_lossy(&result.stderr).into_owned(),
+            });
+        }
+
+        Ok(output)
+    }
+
+    /// Returns a value for `LLVM_PROFILE_FILE` to use while listing tests.
+    ///
+    /// Test binaries are executed to list the tests within them. Coverage data from those
+    /// executions isn't interesting, so it is written to a subdirectory that isn't merged.
+    pub fn list_profile_file(&self) -> Utf8PathBuf {
+        self.dir.join("list").join("%p.profraw")
+    }
+
+    /// Returns the value of `LLVM_PROFILE_FILE` for a single attempt of a test.
+    ///
+    /// `%p` is replaced by LLVM with the process ID, so that subprocesses spawned by the test write
+    /// out their own data.
+    pub(crate) fn profile_file(
+        &self,
+        test_instance: &TestInstance<'_>,
+        attempt: usize,
+    ) -> Utf8PathBuf {
+        self.dir.join(format!(
+            "{}-{}-{}-%p.profraw",
+            sanitize(&test_instance.bin_info.binary_id),
+            sanitize(test_instance.name),
+            attempt
+        ))
+    }
+}
+
+/// Replaces characters that aren't safe to use in file names.
+pub(crate) fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sanitize_names() {
+        assert_eq!(sanitize("my-crate::integration"), "my-crate__integration");
+        assert_eq!(sanitize("tests::foo<T>/bar %p"), "tests__foo_T__bar__p");
+    }
+
+    #[test]
+    fn profraw_files_run_scoped() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(tempdir.path())
+            .expect("temp dir is valid UTF-8")
+            .join("coverage");
+
+        let collector = CoverageCollector::new(&dir).expect("created coverage dir");
+        fs::write(dir.join("b-1.profraw"), b"").expect("wrote file");
+        fs::write(dir.join("a-1.profraw"), b"").expect("wrote file");
+        fs::write(dir.join("other.txt"), b"").expect("wrote file");
+        assert_eq!(
+            collector.profraw_files().expect("listed files"),
+            vec![dir.join("a-1.profraw"), dir.join("b-1.profraw")]
+        );
+
+        // Creating a new collector clears out data from the previous run.
+        let collector = CoverageCollector::new(&dir).expect("recreated coverage dir");
+        assert!(collector.profraw_files().expect("listed files").is_empty());
+        assert!(matches!(
+            collector.merge("llvm-profdata"),
+            Err(CoverageError::NoProfileData { .. })
+        ));
+    }
+}
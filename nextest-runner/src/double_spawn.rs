@@ -0,0 +1,290 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Starting test processes through a copy of nextest ("double-spawning").
+//!
+//! Setting up a test process on Unix, e.g. putting it in a process group of its own or applying
+//! [resource limits](crate::config::ResourceLimits), normally happens between `fork` and `exec`.
+//! Only async-signal-safe functions can be called there, errors can't be reported in any detail,
+//! and it rules out faster ways of spawning processes.
+//!
+//! With double-spawning, nextest instead starts a copy of itself with the hidden `__double-spawn`
+//! subcommand. That process is an ordinary program: it sets itself up, then replaces itself with
+//! the test through `exec`. Along the way:
+//!
+//! * The signal mask and the dispositions of ignored signals are reset, so tests don't inherit
+//!   them from nextest.
+//! * If the test can't be executed, the error is written to standard error and the process exits
+//!   with [`EXEC_FAILED_EXIT_CODE`]. Nextest reports this as the test failing to start, just like
+//!   an error starting the process directly.
+//! * Nextest also moves the process into its process group from the outside, so that signals sent
+//!   to the group right after the test is started aren't lost.
+//! * With `--wait-for-debugger`, the process stops itself right before executing the test, so that
+//!   a debugger can be attached to it.
+//!
+//! Double-spawning is only supported on Unix, and can be disabled by setting
+//! `NEXTEST_DOUBLE_SPAWN=0`. Tests run on remote hosts are never double-spawned.
+
+use crate::{config::ResourceLimits, runner::process::ProcessSetup};
+use std::{
+    convert::TryFrom,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    process::Output,
+    time::Duration,
+};
+
+/// The name of the hidden subcommand that test processes are started through.
+pub const SUBCOMMAND_NAME: &str = "__double-spawn";
+
+/// The exit code of the double-spawn process if the test couldn't be executed.
+pub const EXEC_FAILED_EXIT_CODE: i32 = 126;
+
+/// The start of the message written to standard error if the test couldn't be executed.
+pub(crate) const EXEC_FAILED_MESSAGE: &str = "nextest: error running test: ";
+
+/// Separates the options for setting up the process from the test command.
+const COMMAND_SEPARATOR: &str = "--";
+
+/// Whether test processes are double-spawned, and the nextest binary that does so.
+#[derive(Clone, Debug, Default)]
+pub struct DoubleSpawnInfo {
+    current_exe: Option<PathBuf>,
+}
+
+impl DoubleSpawnInfo {
+    /// Enables double-spawning if it's supported on this platform, and hasn't been disabled by
+    /// setting `NEXTEST_DOUBLE_SPAWN=0`.
+    ///
+    /// This must only be called by a binary that handles [`SUBCOMMAND_NAME`] by calling [`exec`].
+    pub fn try_enable() -> Self {
+        if !cfg!(unix) || std::env::var_os("NEXTEST_DOUBLE_SPAWN").map_or(false, |v| v == "0") {
+            return Self::disabled();
+        }
+        // If the path to nextest can't be determined, tests are started directly instead.
+        Self {
+            current_exe: std::env::current_exe().ok(),
+        }
+    }
+
+    /// Returns an instance that starts test processes directly.
+    pub fn disabled() -> Self {
+        Self { current_exe: None }
+    }
+
+    /// Returns the binary that test processes are started through, if double-spawning is enabled.
+    pub fn current_exe(&self) -> Option<&Path> {
+        self.current_exe.as_deref()
+    }
+
+    /// Returns the program and arguments to prefix the command for a test with, if double-spawning
+    /// is enabled.
+    pub(crate) fn command(&self, setup: ProcessSetup) -> Option<Vec<OsString>> {
+        let current_exe = self.current_exe.as_ref()?;
+        let mut command = vec![current_exe.as_os_str().to_owned(), SUBCOMMAND_NAME.into()];
+        command.extend(setup_args(setup).into_iter().map(OsString::from));
+        command.push(COMMAND_SEPARATOR.into());
+        Some(command)
+    }
+}
+
+/// Returns true if the output is that of a double-spawn process that failed to execute the test.
+pub(crate) fn exec_failed(output: &Output) -> bool {
+    output.status.code() == Some(EXEC_FAILED_EXIT_CODE)
+        && output.stderr.starts_with(EXEC_FAILED_MESSAGE.as_bytes())
+}
+
+/// Runs the `__double-spawn` subcommand with the arguments that follow it: sets up the current
+/// process, then replaces it with the test.
+///
+/// Never returns: if the test can't be executed, an error is written to standard error, and the
+/// process exits with [`EXEC_FAILED_EXIT_CODE`].
+pub fn exec(args: impl IntoIterator<Item = OsString>) -> ! {
+    let err = match exec_impl(args.into_iter().collect()) {
+        Ok(never) => match never {},
+        Err(err) => err,
+    };
+    eprintln!("{}{}", EXEC_FAILED_MESSAGE, err);
+    std::process::exit(EXEC_FAILED_EXIT_CODE)
+}
+
+enum Never {}
+
+fn exec_impl(args: Vec<OsString>) -> io::Result<Never> {
+    let (setup, command) = parse_setup_args(&args)?;
+    let (program, args) = command.split_first().ok_or_else(|| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "no command to run was specified",
+        )
+    })?;
+    imp::exec(setup, program, args)
+}
+
+/// Returns the arguments that tell the double-spawn process how to set itself up.
+fn setup_args(setup: ProcessSetup) -> Vec<String> {
+    let mut args = vec![];
+    if setup.process_group {
+        args.push("--process-group".to_owned());
+    }
+    if let Some(address_space) = setup.resource_limits.address_space() {
+        args.extend(["--address-space".to_owned(), address_space.to_string()]);
+    }
+    if let Some(cpu_time) = setup.resource_limits.cpu_time() {
+        args.extend(["--cpu-time-ms".to_owned(), cpu_time.as_millis().to_string()]);
+    }
+    if setup.core_dumps {
+        args.push("--core-dumps".to_owned());
+    }
+    if setup.disable_aslr {
+        args.push("--disable-aslr".to_owned());
+    }
+    if let Some(cpu_set) = setup.cpu_set {
+        args.extend(["--cpu-set".to_owned(), cpu_set.to_string()]);
+    }
+    if setup.nice > 0 {
+        args.extend(["--nice".to_owned(), setup.nice.to_string()]);
+    }
+    if setup.pause {
+        args.push("--pause".to_owned());
+    }
+    args
+}
+
+/// Parses the arguments written out by [`setup_args`] at the start of `args`, returning them
+/// along with the test command.
+fn parse_setup_args(args: &[OsString]) -> io::Result<(ProcessSetup, &[OsString])> {
+    let invalid = |message: String| io::Error::new(io::ErrorKind::InvalidInput, message);
+    let mut setup = ProcessSetup::default();
+    let mut address_space = None;
+    let mut cpu_time = None;
+    let mut iter = args.iter().enumerate();
+    while let Some((index, arg)) = iter.next() {
+        let mut value = || {
+            let value = iter.next().and_then(|(_, value)| value.to_str());
+            value
+                .and_then(|value| value.parse::<u64>().ok())
+                .ok_or_else(|| {
+                    invalid(format!("expected a number after {}", arg.to_string_lossy()))
+                })
+        };
+        match arg.to_str() {
+            Some(COMMAND_SEPARATOR) => {
+                setup.resource_limits = ResourceLimits::new(address_space, cpu_time);
+                return Ok((setup, &args[index + 1..]));
+            }
+            Some("--process-group") => setup.process_group = true,
+            Some("--address-space") => address_space = Some(value()?),
+            Some("--cpu-time-ms") => cpu_time = Some(Duration::from_millis(value()?)),
+            Some("--core-dumps") => setup.core_dumps = true,
+            Some("--disable-aslr") => setup.disable_aslr = true,
+            Some("--cpu-set") => {
+                let cpu_set = iter
+                    .next()
+                    .and_then(|(_, value)| value.to_str())
+                    .and_then(|value| value.parse().ok())
+                    .ok_or_else(|| invalid("expected a CPU set after --cpu-set".to_owned()))?;
+                setup.cpu_set = Some(cpu_set);
+            }
+            Some("--nice") => {
+                setup.nice = u8::try_from(value()?)
+                    .map_err(|_| invalid("expected a nice value after --nice".to_owned()))?;
+            }
+            Some("--pause") => setup.pause = true,
+            _ => {
+                return Err(invalid(format!(
+                    "unexpected argument: {}",
+                    arg.to_string_lossy()
+                )))
+            }
+        }
+    }
+    Err(invalid(format!("expected {}", COMMAND_SEPARATOR)))
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::Never;
+    use crate::runner::process::{self, ProcessSetup};
+    use std::{ffi::OsString, io, os::unix::process::CommandExt, process::Command};
+
+    pub(super) fn exec(
+        setup: ProcessSetup,
+        program: &OsString,
+        args: &[OsString],
+    ) -> io::Result<Never> {
+        process::set_up_current_process(setup)?;
+        if setup.pause {
+            // Nextest waits for this process to stop, then resumes it once a debugger has been
+            // attached. The process ID is kept across exec, so the debugger can attach to it now.
+            // SAFETY: raise has no memory safety requirements.
+            if unsafe { libc::raise(libc::SIGSTOP) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        // The standard library resets the signal mask and the disposition of SIGPIPE before
+        // executing the program. Other signals nextest handles are reset by exec itself.
+        Err(Command::new(program).args(args).exec())
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::Never;
+    use crate::runner::process::ProcessSetup;
+    use std::{ffi::OsString, io};
+
+    pub(super) fn exec(
+        _setup: ProcessSetup,
+        _program: &OsString,
+        _args: &[OsString],
+    ) -> io::Result<Never> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "double-spawning is only supported on Unix",
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn setup_args_round_trip() {
+        let setup = ProcessSetup {
+            process_group: true,
+            resource_limits: ResourceLimits::new(Some(4 << 30), Some(Duration::from_millis(1500))),
+            core_dumps: true,
+            disable_aslr: true,
+            cpu_set: Some("0-3,8".parse().expect("CPU set is valid")),
+            nice: 10,
+            pause: true,
+        };
+        let mut args: Vec<OsString> = setup_args(setup).into_iter().map(Into::into).collect();
+        args.extend(
+            ["--", "/bin/test", "--exact", "--"]
+                .iter()
+                .map(OsString::from),
+        );
+        let (parsed, command) = parse_setup_args(&args).expect("arguments are valid");
+        assert_eq!(parsed, setup);
+        assert_eq!(command, ["/bin/test", "--exact", "--"]);
+
+        let args: Vec<OsString> = vec!["--".into(), "/bin/test".into()];
+        let (parsed, _) = parse_setup_args(&args).expect("arguments are valid");
+        assert_eq!(parsed, ProcessSetup::default());
+
+        for invalid in [
+            &["/bin/test"][..],
+            &["--cpu-time-ms", "--", "/bin/test"],
+            &["--cpu-set", "1-0", "--", "/bin/test"],
+            &["--nice", "300", "--", "/bin/test"],
+            &["--process-group"],
+        ] {
+            let args: Vec<OsString> = invalid.iter().map(OsString::from).collect();
+            parse_setup_args(&args).expect_err("arguments are invalid");
+        }
+    }
+}
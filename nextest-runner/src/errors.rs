@@ -4,38 +4,59 @@
 //! Errors produced by nextest.
 
 use crate::{
+    config::{ConfigSource, NoTestsBehavior},
     reporter::{StatusLevel, TestOutputDisplay},
     test_filter::RunIgnored,
 };
 use camino::Utf8PathBuf;
 use config::ConfigError;
-use std::{borrow::Cow, error, fmt};
+use nextest_metadata::ParseBinaryListError;
+use std::{borrow::Cow, error, fmt, process::ExitStatus};
 
 /// An error that occurred while parsing the config.
 #[derive(Debug)]
 #[non_exhaustive]
 pub struct ConfigParseError {
-    config_file: Utf8PathBuf,
+    // Boxed to keep results small.
+    source: Box<ConfigSource>,
     err: ConfigError,
 }
 
 impl ConfigParseError {
-    pub(crate) fn new(config_file: impl Into<Utf8PathBuf>, err: ConfigError) -> Self {
+    pub(crate) fn new(source: ConfigSource, err: ConfigError) -> Self {
         Self {
-            config_file: config_file.into(),
+            source: Box::new(source),
             err,
         }
     }
+
+    /// Returns the source of the config that failed to parse.
+    pub fn config_source(&self) -> &ConfigSource {
+        &self.source
+    }
 }
 
 impl fmt::Display for ConfigParseError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "failed to parse nextest config at `{}`",
-            self.config_file
-        )?;
-        Ok(())
+        match &*self.source {
+            ConfigSource::Default => write!(f, "failed to parse default nextest config"),
+            ConfigSource::Tool { tool, path } => write!(
+                f,
+                "failed to parse nextest config for tool `{}` at `{}`",
+                tool, path
+            ),
+            ConfigSource::User(path) => {
+                write!(f, "failed to parse nextest user config at `{}`", path)
+            }
+            ConfigSource::Workspace(path) => {
+                write!(f, "failed to parse nextest config at `{}`", path)
+            }
+            ConfigSource::Env(var) => write!(
+                f,
+                "failed to parse nextest config from environment variable `{}`",
+                var
+            ),
+        }
     }
 }
 
@@ -106,6 +127,139 @@ impl fmt::Display for TestOutputDisplayParseError {
 
 impl error::Error for TestOutputDisplayParseError {}
 
+/// Error returned while parsing a [`NoTestsBehavior`] value from a string.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NoTestsBehaviorParseError {
+    input: String,
+}
+
+impl NoTestsBehaviorParseError {
+    pub(crate) fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+        }
+    }
+}
+
+impl fmt::Display for NoTestsBehaviorParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unrecognized value for no-tests: {}\n(known values: {})",
+            self.input,
+            NoTestsBehavior::variants().join(", ")
+        )
+    }
+}
+
+impl error::Error for NoTestsBehaviorParseError {}
+
+/// Error returned while parsing a [`NotifyTemplate`](crate::reporter::NotifyTemplate) from a string.
+#[derive(Clone, Debug)]
+pub struct NotifyTemplateParseError {
+    input: String,
+    message: String,
+}
+
+impl NotifyTemplateParseError {
+    pub(crate) fn new(input: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for NotifyTemplateParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid notification template: {}
+(template: {})",
+            self.message, self.input
+        )
+    }
+}
+
+impl error::Error for NotifyTemplateParseError {}
+
+/// Error returned while parsing a [`StyleSpec`](crate::reporter::StyleSpec) from a string.
+#[derive(Clone, Debug)]
+pub struct StyleSpecParseError {
+    input: String,
+    message: String,
+}
+
+impl StyleSpecParseError {
+    pub(crate) fn new(input: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for StyleSpecParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid style `{}`: {}", self.input, self.message)
+    }
+}
+
+impl error::Error for StyleSpecParseError {}
+
+/// Error returned while parsing a [`CpuSet`](crate::config::CpuSet) value from a string.
+#[derive(Clone, Debug)]
+pub struct CpuSetParseError {
+    input: String,
+}
+
+impl CpuSetParseError {
+    pub(crate) fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+        }
+    }
+}
+
+impl fmt::Display for CpuSetParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "invalid CPU set: {}\n(expected CPU numbers and ranges below {}, like \"0-3,8\")",
+            self.input,
+            crate::config::CpuSet::MAX_CPUS
+        )
+    }
+}
+
+impl error::Error for CpuSetParseError {}
+
+/// Error returned while parsing a [`TestThreads`](crate::config::TestThreads) value from a string.
+#[derive(Clone, Debug)]
+pub struct TestThreadsParseError {
+    input: String,
+}
+
+impl TestThreadsParseError {
+    pub(crate) fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+        }
+    }
+}
+
+impl fmt::Display for TestThreadsParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unrecognized value for test-threads: {}\n(expected a non-zero integer, \"num-cpus\" or \"num-cpus*<N>\")",
+            self.input
+        )
+    }
+}
+
+impl error::Error for TestThreadsParseError {}
+
 /// Error returned while parsing a [`StatusLevel`] value from a string.
 #[derive(Clone, Debug)]
 pub struct StatusLevelParseError {
@@ -131,101 +285,817 @@ impl fmt::Display for StatusLevelParseError {
     }
 }
 
-impl error::Error for StatusLevelParseError {}
+impl error::Error for StatusLevelParseError {}
+
+/// An error that occurs while parsing a [`RunIgnored`] value from a string.
+#[derive(Clone, Debug)]
+pub struct RunIgnoredParseError {
+    input: String,
+}
+
+impl RunIgnoredParseError {
+    pub(crate) fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+        }
+    }
+}
+
+impl fmt::Display for RunIgnoredParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unrecognized value for run-ignored: {}\n(known values: {})",
+            self.input,
+            RunIgnored::variants().join(", ")
+        )
+    }
+}
+
+impl error::Error for RunIgnoredParseError {}
+
+/// An error that occurs while parsing a
+/// [`PartitionerBuilder`](crate::partition::PartitionerBuilder) input.
+#[derive(Clone, Debug)]
+pub struct PartitionerBuilderParseError {
+    expected_format: Option<&'static str>,
+    message: Cow<'static, str>,
+}
+
+impl PartitionerBuilderParseError {
+    pub(crate) fn new(
+        expected_format: Option<&'static str>,
+        message: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            expected_format,
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for PartitionerBuilderParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.expected_format {
+            Some(format) => {
+                write!(
+                    f,
+                    "partition must be in the format \"{}\":\n{}",
+                    format, self.message
+                )
+            }
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl error::Error for PartitionerBuilderParseError {}
+
+/// An error that occurs while parsing a [`ToolConfigFile`](crate::config::ToolConfigFile) from a
+/// string.
+#[derive(Clone, Debug)]
+pub struct ToolConfigFileParseError {
+    input: String,
+}
+
+impl ToolConfigFileParseError {
+    pub(crate) fn new(input: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+        }
+    }
+}
+
+impl fmt::Display for ToolConfigFileParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "tool config file must be in the format \"<tool-name>:<path>\": {}",
+            self.input
+        )
+    }
+}
+
+impl error::Error for ToolConfigFileParseError {}
+
+/// An error that occurs while deserializing a tool's section of the config, through
+/// [`NextestConfig::tool_config`](crate::config::NextestConfig::tool_config).
+#[derive(Debug)]
+pub struct ToolConfigError {
+    tool: String,
+    err: toml::de::Error,
+}
+
+impl ToolConfigError {
+    pub(crate) fn new(tool: impl Into<String>, err: toml::de::Error) -> Self {
+        Self {
+            tool: tool.into(),
+            err,
+        }
+    }
+}
+
+impl fmt::Display for ToolConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "failed to parse [tool.{}] section of nextest config",
+            self.tool
+        )
+    }
+}
+
+impl error::Error for ToolConfigError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+/// An error that occurs while parsing a [`FilterExpr`](crate::filter_expr::FilterExpr).
+#[derive(Clone, Debug)]
+pub struct FilterExprParseError {
+    input: String,
+    message: String,
+}
+
+impl FilterExprParseError {
+    pub(crate) fn new(input: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            input: input.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for FilterExprParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "error parsing filter expression '{}': {}",
+            self.input, self.message
+        )
+    }
+}
+
+impl error::Error for FilterExprParseError {}
+
+/// An error that occurs in [`RustTestArtifact::from_messages`](crate::test_list::RustTestArtifact::from_messages).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FromMessagesError {
+    /// An error occurred while reading Cargo's JSON messages.
+    ReadMessages(std::io::Error),
+
+    /// An error occurred while querying the package graph.
+    PackageGraph(guppy::Error),
+
+    /// An error occurred while reading a package's `Cargo.toml`.
+    ReadManifest {
+        /// The path to `Cargo.toml`.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while parsing a package's `Cargo.toml`.
+    ParseManifest {
+        /// The path to `Cargo.toml`.
+        path: Utf8PathBuf,
+
+        /// The underlying TOML error.
+        error: toml::de::Error,
+    },
+}
+
+impl fmt::Display for FromMessagesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromMessagesError::ReadMessages(_) => {
+                write!(f, "error reading Cargo JSON messages")
+            }
+            FromMessagesError::PackageGraph(_) => {
+                write!(f, "error querying package graph")
+            }
+            FromMessagesError::ReadManifest { path, .. } => {
+                write!(f, "error reading manifest at {}", path)
+            }
+            FromMessagesError::ParseManifest { path, .. } => {
+                write!(f, "error parsing manifest at {}", path)
+            }
+        }
+    }
+}
+
+impl error::Error for FromMessagesError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FromMessagesError::ReadMessages(error) => Some(error),
+            FromMessagesError::PackageGraph(error) => Some(error),
+            FromMessagesError::ReadManifest { error, .. } => Some(error),
+            FromMessagesError::ParseManifest { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurs in [`RustTestArtifact::from_binary_list`](crate::test_list::RustTestArtifact::from_binary_list).
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum FromBinaryListError {
+    /// An error occurred while querying the package graph.
+    PackageGraph(guppy::Error),
+}
+
+impl fmt::Display for FromBinaryListError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FromBinaryListError::PackageGraph(_) => {
+                write!(f, "error querying package graph")
+            }
+        }
+    }
+}
+
+impl error::Error for FromBinaryListError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            FromBinaryListError::PackageGraph(error) => Some(error),
+        }
+    }
+}
+
+/// An error that occurs while creating an archive of test binaries.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ArchiveCreateError {
+    /// An error occurred while parsing Cargo metadata.
+    CargoMetadata(serde_json::Error),
+
+    /// A test binary is not within the Cargo target directory.
+    BinaryOutsideTargetDir {
+        /// The path to the test binary.
+        binary_path: Utf8PathBuf,
+
+        /// The target directory.
+        target_dir: Utf8PathBuf,
+    },
+
+    /// The working directory for a test binary is not within the workspace root.
+    CwdOutsideWorkspace {
+        /// The working directory.
+        cwd: Utf8PathBuf,
+
+        /// The workspace root.
+        workspace_root: Utf8PathBuf,
+    },
+
+    /// An error occurred while serializing the binary list.
+    Json(serde_json::Error),
+
+    /// An error occurred while writing to the archive.
+    Write {
+        /// The archive file.
+        archive_file: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for ArchiveCreateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveCreateError::CargoMetadata(_) => {
+                write!(f, "error parsing Cargo metadata")
+            }
+            ArchiveCreateError::BinaryOutsideTargetDir {
+                binary_path,
+                target_dir,
+            } => {
+                write!(
+                    f,
+                    "test binary {} is not within target directory {}",
+                    binary_path, target_dir
+                )
+            }
+            ArchiveCreateError::CwdOutsideWorkspace {
+                cwd,
+                workspace_root,
+            } => {
+                write!(
+                    f,
+                    "test working directory {} is not within workspace root {}",
+                    cwd, workspace_root
+                )
+            }
+            ArchiveCreateError::Json(_) => {
+                write!(f, "error serializing binary list to JSON")
+            }
+            ArchiveCreateError::Write { archive_file, .. } => {
+                write!(f, "error writing archive to {}", archive_file)
+            }
+        }
+    }
+}
+
+impl error::Error for ArchiveCreateError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ArchiveCreateError::CargoMetadata(error) => Some(error),
+            ArchiveCreateError::BinaryOutsideTargetDir { .. } => None,
+            ArchiveCreateError::CwdOutsideWorkspace { .. } => None,
+            ArchiveCreateError::Json(error) => Some(error),
+            ArchiveCreateError::Write { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurs while extracting an archive of test binaries.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ArchiveExtractError {
+    /// An error occurred while creating a temporary directory to extract the archive to.
+    TempDirCreate(std::io::Error),
+
+    /// An error occurred while reading the archive.
+    Read {
+        /// The archive file.
+        archive_file: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while reading a metadata file from the extracted archive.
+    MetadataRead {
+        /// The metadata file.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while parsing the binary list.
+    BinaryList(ParseBinaryListError),
+}
+
+impl fmt::Display for ArchiveExtractError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ArchiveExtractError::TempDirCreate(_) => {
+                write!(
+                    f,
+                    "error creating temporary directory to extract archive to"
+                )
+            }
+            ArchiveExtractError::Read { archive_file, .. } => {
+                write!(f, "error extracting archive {}", archive_file)
+            }
+            ArchiveExtractError::MetadataRead { path, .. } => {
+                write!(f, "error reading {} from extracted archive", path)
+            }
+            ArchiveExtractError::BinaryList(_) => {
+                write!(f, "error parsing binary list from archive")
+            }
+        }
+    }
+}
+
+impl error::Error for ArchiveExtractError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ArchiveExtractError::TempDirCreate(error) => Some(error),
+            ArchiveExtractError::Read { error, .. } => Some(error),
+            ArchiveExtractError::MetadataRead { error, .. } => Some(error),
+            ArchiveExtractError::BinaryList(error) => Some(error),
+        }
+    }
+}
+
+/// An error that occurs while collecting or merging coverage data.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CoverageError {
+    /// An error occurred while creating or writing to the coverage directory.
+    CreateDir {
+        /// The coverage directory.
+        dir: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while reading the coverage directory.
+    ReadDir {
+        /// The coverage directory.
+        dir: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// No coverage data was written out during the run.
+    NoProfileData {
+        /// The coverage directory.
+        dir: Utf8PathBuf,
+    },
+
+    /// An error occurred while executing `llvm-profdata`.
+    MergeExec {
+        /// The `llvm-profdata` executable.
+        program: String,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// `llvm-profdata` exited with a non-zero status.
+    MergeFailed {
+        /// The exit status.
+        status: ExitStatus,
+
+        /// The standard error of the process.
+        stderr: String,
+    },
+}
+
+impl fmt::Display for CoverageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CoverageError::CreateDir { dir, .. } => {
+                write!(f, "error writing to coverage directory {}", dir)
+            }
+            CoverageError::ReadDir { dir, .. } => {
+                write!(f, "error reading coverage directory {}", dir)
+            }
+            CoverageError::NoProfileData { dir } => {
+                write!(
+                    f,
+                    "no coverage data found in {} (were tests built with `-C instrument-coverage`?)",
+                    dir
+                )
+            }
+            CoverageError::MergeExec { program, .. } => {
+                write!(f, "error executing {}", program)
+            }
+            CoverageError::MergeFailed { status, stderr } => {
+                write!(
+                    f,
+                    "llvm-profdata failed to merge coverage data ({})",
+                    status
+                )?;
+                if !stderr.is_empty() {
+                    write!(f, ":\n{}", stderr.trim_end())?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl error::Error for CoverageError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CoverageError::CreateDir { error, .. } => Some(error),
+            CoverageError::ReadDir { error, .. } => Some(error),
+            CoverageError::NoProfileData { .. } => None,
+            CoverageError::MergeExec { error, .. } => Some(error),
+            CoverageError::MergeFailed { .. } => None,
+        }
+    }
+}
+
+/// An error that occurs while reading test timings recorded by previous runs.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum TestTimingsError {
+    /// An error occurred while reading the timings file.
+    Read {
+        /// The path to the timings file.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while parsing the timings file.
+    Parse {
+        /// The path to the timings file.
+        path: Utf8PathBuf,
+
+        /// The underlying JSON error.
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for TestTimingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TestTimingsError::Read { path, .. } => {
+                write!(f, "error reading test timings from {}", path)
+            }
+            TestTimingsError::Parse { path, .. } => {
+                write!(f, "error parsing test timings from {}", path)
+            }
+        }
+    }
+}
+
+impl error::Error for TestTimingsError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            TestTimingsError::Read { error, .. } => Some(error),
+            TestTimingsError::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurs while reading or writing the cache of test lists.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ListCacheError {
+    /// An error occurred while reading the cache file.
+    Read {
+        /// The path to the cache file.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while parsing the cache file.
+    Parse {
+        /// The path to the cache file.
+        path: Utf8PathBuf,
+
+        /// The underlying JSON error.
+        error: serde_json::Error,
+    },
+
+    /// An error occurred while writing the cache file.
+    Write {
+        /// The path to the cache file.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for ListCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ListCacheError::Read { path, .. } => {
+                write!(f, "error reading cached test lists from {}", path)
+            }
+            ListCacheError::Parse { path, .. } => {
+                write!(f, "error parsing cached test lists from {}", path)
+            }
+            ListCacheError::Write { path, .. } => {
+                write!(f, "error writing cached test lists to {}", path)
+            }
+        }
+    }
+}
+
+impl error::Error for ListCacheError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ListCacheError::Read { error, .. } => Some(error),
+            ListCacheError::Parse { error, .. } => Some(error),
+            ListCacheError::Write { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurs while listing or removing the artifacts of earlier runs in a store
+/// directory.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum StoreError {
+    /// An error occurred while reading a directory in the store.
+    Read {
+        /// The path to the directory.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while removing the artifacts of a run.
+    Remove {
+        /// The path to the artifacts.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while locking the store.
+    Lock {
+        /// The path to the lock file.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for StoreError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StoreError::Read { path, .. } => {
+                write!(f, "error reading store directory {}", path)
+            }
+            StoreError::Remove { path, .. } => {
+                write!(f, "error removing {} from the store", path)
+            }
+            StoreError::Lock { path, .. } => {
+                write!(f, "error locking store at {}", path)
+            }
+        }
+    }
+}
+
+impl error::Error for StoreError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            StoreError::Read { error, .. } => Some(error),
+            StoreError::Remove { error, .. } => Some(error),
+            StoreError::Lock { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurs while reading or writing cached `cargo metadata` output.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum MetadataCacheError {
+    /// An error occurred while reading the cache file.
+    Read {
+        /// The path to the cache file.
+        path: Utf8PathBuf,
 
-/// An error that occurs while parsing a [`RunIgnored`] value from a string.
-#[derive(Clone, Debug)]
-pub struct RunIgnoredParseError {
-    input: String,
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while parsing the cache file.
+    Parse {
+        /// The path to the cache file.
+        path: Utf8PathBuf,
+
+        /// The underlying JSON error.
+        error: serde_json::Error,
+    },
+
+    /// The `cargo metadata` output to be cached couldn't be parsed.
+    Metadata(serde_json::Error),
+
+    /// An error occurred while writing the cache file.
+    Write {
+        /// The path to the cache file.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
 }
 
-impl RunIgnoredParseError {
-    pub(crate) fn new(input: impl Into<String>) -> Self {
-        Self {
-            input: input.into(),
+impl fmt::Display for MetadataCacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MetadataCacheError::Read { path, .. } => {
+                write!(f, "error reading cached cargo metadata from {}", path)
+            }
+            MetadataCacheError::Parse { path, .. } => {
+                write!(f, "error parsing cached cargo metadata from {}", path)
+            }
+            MetadataCacheError::Metadata(_) => {
+                write!(f, "error parsing cargo metadata output")
+            }
+            MetadataCacheError::Write { path, .. } => {
+                write!(f, "error writing cached cargo metadata to {}", path)
+            }
         }
     }
 }
 
-impl fmt::Display for RunIgnoredParseError {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(
-            f,
-            "unrecognized value for run-ignored: {}\n(known values: {})",
-            self.input,
-            RunIgnored::variants().join(", ")
-        )
+impl error::Error for MetadataCacheError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            MetadataCacheError::Read { error, .. } => Some(error),
+            MetadataCacheError::Parse { error, .. } => Some(error),
+            MetadataCacheError::Metadata(error) => Some(error),
+            MetadataCacheError::Write { error, .. } => Some(error),
+        }
     }
 }
 
-impl error::Error for RunIgnoredParseError {}
+/// An error that occurs while reading a list of test binaries built outside of Cargo.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ExternalBinaryListError {
+    /// An error occurred while reading the file.
+    Read {
+        /// The path to the file.
+        path: Utf8PathBuf,
 
-/// An error that occurs while parsing a
-/// [`PartitionerBuilder`](crate::partition::PartitionerBuilder) input.
-#[derive(Clone, Debug)]
-pub struct PartitionerBuilderParseError {
-    expected_format: Option<&'static str>,
-    message: Cow<'static, str>,
-}
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
 
-impl PartitionerBuilderParseError {
-    pub(crate) fn new(
-        expected_format: Option<&'static str>,
-        message: impl Into<Cow<'static, str>>,
-    ) -> Self {
-        Self {
-            expected_format,
-            message: message.into(),
-        }
-    }
+    /// An error occurred while parsing the file.
+    Parse {
+        /// The path to the file.
+        path: Utf8PathBuf,
+
+        /// The underlying JSON error.
+        error: serde_json::Error,
+    },
+
+    /// Two binaries have the same ID.
+    DuplicateBinaryId(String),
+
+    /// A binary's package wasn't found in the package graph.
+    PackageGraph(guppy::Error),
 }
 
-impl fmt::Display for PartitionerBuilderParseError {
+impl fmt::Display for ExternalBinaryListError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        match self.expected_format {
-            Some(format) => {
+        match self {
+            ExternalBinaryListError::Read { path, .. } => {
+                write!(f, "error reading test binaries from {}", path)
+            }
+            ExternalBinaryListError::Parse { path, .. } => {
+                write!(f, "error parsing test binaries from {}", path)
+            }
+            ExternalBinaryListError::DuplicateBinaryId(binary_id) => {
                 write!(
                     f,
-                    "partition must be in the format \"{}\":\n{}",
-                    format, self.message
+                    "more than one test binary has the ID `{}` (set binary-id to tell them apart)",
+                    binary_id
                 )
             }
-            None => write!(f, "{}", self.message),
+            ExternalBinaryListError::PackageGraph(_) => {
+                write!(f, "error looking up test binary in package graph")
+            }
         }
     }
 }
 
-impl error::Error for PartitionerBuilderParseError {}
+impl error::Error for ExternalBinaryListError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            ExternalBinaryListError::Read { error, .. } => Some(error),
+            ExternalBinaryListError::Parse { error, .. } => Some(error),
+            ExternalBinaryListError::DuplicateBinaryId(_) => None,
+            ExternalBinaryListError::PackageGraph(error) => Some(error),
+        }
+    }
+}
 
-/// An error that occurs in [`RustTestArtifact::from_messages`](crate::test_list::RustTestArtifact::from_messages).
+/// An error that occurs while reading the failed tests recorded by the last run.
 #[derive(Debug)]
 #[non_exhaustive]
-pub enum FromMessagesError {
-    /// An error occurred while reading Cargo's JSON messages.
-    ReadMessages(std::io::Error),
+pub enum LastRunError {
+    /// An error occurred while reading the last run file.
+    Read {
+        /// The path to the last run file.
+        path: Utf8PathBuf,
 
-    /// An error occurred while querying the package graph.
-    PackageGraph(guppy::Error),
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while parsing the last run file.
+    Parse {
+        /// The path to the last run file.
+        path: Utf8PathBuf,
+
+        /// The underlying JSON error.
+        error: serde_json::Error,
+    },
 }
 
-impl fmt::Display for FromMessagesError {
+impl fmt::Display for LastRunError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
-            FromMessagesError::ReadMessages(_) => {
-                write!(f, "error reading Cargo JSON messages")
+            LastRunError::Read { path, .. } => {
+                write!(f, "error reading failed tests from {}", path)
             }
-            FromMessagesError::PackageGraph(_) => {
-                write!(f, "error querying package graph")
+            LastRunError::Parse { path, .. } => {
+                write!(f, "error parsing failed tests from {}", path)
             }
         }
     }
 }
 
-impl error::Error for FromMessagesError {
+impl error::Error for LastRunError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
-            FromMessagesError::ReadMessages(error) => Some(error),
-            FromMessagesError::PackageGraph(error) => Some(error),
+            LastRunError::Read { error, .. } => Some(error),
+            LastRunError::Parse { error, .. } => Some(error),
         }
     }
 }
@@ -337,6 +1207,9 @@ pub enum WriteEventError {
     /// An error occurred while writing the event to the provided output.
     Io(std::io::Error),
 
+    /// An error occurred while serializing the event to JSON.
+    Json(serde_json::Error),
+
     /// An error occurred while operating on the file system.
     Fs {
         /// The file being operated on.
@@ -354,6 +1227,15 @@ pub enum WriteEventError {
         /// The underlying error.
         error: JunitError,
     },
+
+    /// An error occurred while sending events over HTTP.
+    Http {
+        /// The URL events were being sent to.
+        url: String,
+
+        /// The underlying error.
+        error: HttpPostError,
+    },
 }
 
 impl fmt::Display for WriteEventError {
@@ -362,12 +1244,18 @@ impl fmt::Display for WriteEventError {
             WriteEventError::Io(_) => {
                 write!(f, "error writing to output")
             }
+            WriteEventError::Json(_) => {
+                write!(f, "error serializing event to JSON")
+            }
             WriteEventError::Fs { file, .. } => {
                 write!(f, "error operating on path {}", file)
             }
             WriteEventError::Junit { file, .. } => {
                 write!(f, "error writing JUnit output to {}", file)
             }
+            WriteEventError::Http { url, .. } => {
+                write!(f, "error sending events to {}", url)
+            }
         }
     }
 }
@@ -376,8 +1264,10 @@ impl error::Error for WriteEventError {
     fn source(&self) -> Option<&(dyn error::Error + 'static)> {
         match self {
             WriteEventError::Io(error) => Some(error),
+            WriteEventError::Json(error) => Some(error),
             WriteEventError::Fs { error, .. } => Some(error),
             WriteEventError::Junit { error, .. } => Some(error),
+            WriteEventError::Http { error, .. } => Some(error),
         }
     }
 }
@@ -406,6 +1296,83 @@ impl error::Error for JunitError {
     }
 }
 
+/// An error that occurred while making an HTTP request.
+#[derive(Debug)]
+pub struct HttpPostError {
+    err: Box<ureq::Error>,
+}
+
+impl HttpPostError {
+    pub(crate) fn new(err: ureq::Error) -> Self {
+        Self { err: Box::new(err) }
+    }
+}
+
+impl fmt::Display for HttpPostError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.err)
+    }
+}
+
+impl error::Error for HttpPostError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        self.err.source()
+    }
+}
+
+/// An error that occurred while setting up the signal handler.
+#[derive(Debug)]
+pub struct SignalHandlerSetupError {
+    err: std::io::Error,
+}
+
+impl SignalHandlerSetupError {
+    pub(crate) fn new(err: std::io::Error) -> Self {
+        Self { err }
+    }
+}
+
+impl fmt::Display for SignalHandlerSetupError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "error setting up signal handler")
+    }
+}
+
+impl error::Error for SignalHandlerSetupError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&self.err)
+    }
+}
+
+/// An error that occurred while checking whether tests can be isolated from the network.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum NetworkIsolationError {
+    /// Network isolation isn't supported on this platform.
+    Unsupported,
+
+    /// A network namespace couldn't be created.
+    Setup(std::io::Error),
+}
+
+impl fmt::Display for NetworkIsolationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Unsupported => write!(f, "network isolation is only supported on Linux"),
+            Self::Setup(_) => write!(f, "error creating network namespace"),
+        }
+    }
+}
+
+impl error::Error for NetworkIsolationError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Unsupported => None,
+            Self::Setup(error) => Some(error),
+        }
+    }
+}
+
 /// An error occurred determining the target runner
 #[derive(Debug)]
 pub enum TargetRunnerError {
@@ -507,3 +1474,189 @@ impl error::Error for TargetRunnerError {
         }
     }
 }
+
+/// An error that occurred while setting up a remote host to run tests on.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SshRunnerError {
+    /// An error occurred while creating the directory to copy test binaries to.
+    CreateDir {
+        /// The remote host.
+        host: String,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// An error occurred while copying test binaries to the remote host.
+    CopyBinaries {
+        /// The remote host.
+        host: String,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+}
+
+impl fmt::Display for SshRunnerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::CreateDir { host, .. } => {
+                write!(f, "error creating directory on remote host {}", host)
+            }
+            Self::CopyBinaries { host, .. } => {
+                write!(f, "error copying test binaries to remote host {}", host)
+            }
+        }
+    }
+}
+
+impl error::Error for SshRunnerError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::CreateDir { error, .. } => Some(error),
+            Self::CopyBinaries { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurred while reading a recorded test run.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReadRecordError {
+    /// An error occurred while reading the recording.
+    Io {
+        /// The path to the recording.
+        path: Utf8PathBuf,
+
+        /// The underlying IO error.
+        error: std::io::Error,
+    },
+
+    /// The recording is empty.
+    MissingHeader {
+        /// The path to the recording.
+        path: Utf8PathBuf,
+    },
+
+    /// The recording was made by a newer version of nextest, in a format this version doesn't
+    /// support.
+    UnsupportedVersion {
+        /// The path to the recording.
+        path: Utf8PathBuf,
+
+        /// The format version of the recording.
+        format_version: u32,
+
+        /// The newest format version supported.
+        max_supported: u32,
+    },
+
+    /// A line in the recording couldn't be parsed.
+    Parse {
+        /// The path to the recording.
+        path: Utf8PathBuf,
+
+        /// The line number, starting from 1.
+        line: usize,
+
+        /// The underlying JSON error.
+        error: serde_json::Error,
+    },
+}
+
+impl fmt::Display for ReadRecordError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::Io { path, .. } => write!(f, "error reading recorded run from {}", path),
+            Self::MissingHeader { path } => write!(f, "recorded run at {} is empty", path),
+            Self::UnsupportedVersion {
+                path,
+                format_version,
+                max_supported,
+            } => write!(
+                f,
+                "recorded run at {} has format version {}, but only versions up to {} are \
+                 supported (try upgrading nextest)",
+                path, format_version, max_supported
+            ),
+            Self::Parse { path, line, .. } => {
+                write!(f, "error parsing line {} of recorded run at {}", line, path)
+            }
+        }
+    }
+}
+
+impl error::Error for ReadRecordError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::Io { error, .. } => Some(error),
+            Self::MissingHeader { .. } | Self::UnsupportedVersion { .. } => None,
+            Self::Parse { error, .. } => Some(error),
+        }
+    }
+}
+
+/// An error that occurred while replaying a recorded test run.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum ReplayError {
+    /// A package in the recorded test list couldn't be found in the recorded package graph.
+    PackageGraph(guppy::Error),
+
+    /// An event refers to a test that isn't in the recorded test list.
+    UnknownTest {
+        /// The binary ID of the test.
+        binary_id: String,
+
+        /// The name of the test.
+        test_name: String,
+    },
+
+    /// A test-finished event doesn't have any results.
+    NoRunStatuses {
+        /// The binary ID of the test.
+        binary_id: String,
+
+        /// The name of the test.
+        test_name: String,
+    },
+
+    /// A reporter failed to write out an event.
+    WriteEvent(WriteEventError),
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::PackageGraph(_) => write!(f, "error querying recorded package graph"),
+            Self::UnknownTest {
+                binary_id,
+                test_name,
+            } => write!(
+                f,
+                "recorded event refers to unknown test {} {}",
+                binary_id, test_name
+            ),
+            Self::NoRunStatuses {
+                binary_id,
+                test_name,
+            } => write!(
+                f,
+                "recorded test {} {} finished without any results",
+                binary_id, test_name
+            ),
+            Self::WriteEvent(_) => write!(f, "error reporting replayed event"),
+        }
+    }
+}
+
+impl error::Error for ReplayError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            Self::PackageGraph(error) => Some(error),
+            Self::UnknownTest { .. } | Self::NoRunStatuses { .. } => None,
+            Self::WriteEvent(error) => Some(error),
+        }
+    }
+}
@@ -0,0 +1,316 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Test binaries built outside of Cargo.
+//!
+//! Build systems like Bazel and Buck build Rust test binaries themselves, without Cargo. nextest
+//! can run such binaries given a list of their paths, along with the directory each one should be
+//! run in and any environment variables it needs.
+//!
+//! The rest of nextest works in terms of the packages in a Cargo workspace, so a package is made
+//! up for each binary and named after it. These packages are described in the same format as
+//! `cargo metadata` output, from which a package graph can be constructed without running Cargo.
+
+use crate::{errors::ExternalBinaryListError, test_list::RustTestArtifact};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::{graph::PackageGraph, PackageId};
+use nextest_metadata::{BuildPlatform, RustTestBinaryKind, RustTestHarness};
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The version given to the packages made up for external binaries.
+const EXTERNAL_PACKAGE_VERSION: &str = "0.0.0";
+
+/// A list of test binaries built outside of Cargo.
+///
+/// This is usually read from a JSON file, in the form:
+///
+/// ```json
+/// {
+///   "binaries": [
+///     {
+///       "path": "bazel-bin/my-crate/my_test",
+///       "binary-id": "my-crate::my_test",
+///       "cwd": "my-crate",
+///       "env": { "MY_VAR": "value" }
+///     }
+///   ]
+/// }
+/// ```
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExternalBinaryList {
+    binaries: Vec<ExternalBinary>,
+}
+
+/// A test binary built outside of Cargo.
+///
+/// Part of an [`ExternalBinaryList`].
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ExternalBinary {
+    /// The path to the binary.
+    pub path: Utf8PathBuf,
+
+    /// A unique identifier for the binary. Defaults to the file name of the binary.
+    #[serde(default)]
+    pub binary_id: Option<String>,
+
+    /// The directory to run the binary in, relative to the workspace root. Defaults to the
+    /// workspace root.
+    #[serde(default)]
+    pub cwd: Option<Utf8PathBuf>,
+
+    /// Environment variables to set when running the binary.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+
+    /// The test harness the binary was built with.
+    #[serde(default)]
+    pub harness: RustTestHarness,
+}
+
+impl ExternalBinary {
+    /// Creates a new external binary at the given path, with default settings.
+    pub fn new(path: impl Into<Utf8PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            binary_id: None,
+            cwd: None,
+            env: BTreeMap::new(),
+            harness: RustTestHarness::default(),
+        }
+    }
+
+    fn binary_id(&self) -> &str {
+        self.binary_id
+            .as_deref()
+            .or_else(|| self.path.file_name())
+            .unwrap_or_else(|| self.path.as_str())
+    }
+}
+
+impl ExternalBinaryList {
+    /// Creates a new list from the given binaries.
+    ///
+    /// Returns an error if two binaries have the same ID.
+    pub fn new(binaries: Vec<ExternalBinary>) -> Result<Self, ExternalBinaryListError> {
+        let list = Self { binaries };
+        list.check_unique_ids()?;
+        Ok(list)
+    }
+
+    /// Reads a list of binaries from a JSON file.
+    ///
+    /// Relative paths to binaries are relative to the directory the file is in.
+    pub fn read(path: &Utf8Path) -> Result<Self, ExternalBinaryListError> {
+        let contents =
+            std::fs::read_to_string(path).map_err(|error| ExternalBinaryListError::Read {
+                path: path.to_owned(),
+                error,
+            })?;
+        let mut list: Self =
+            serde_json::from_str(&contents).map_err(|error| ExternalBinaryListError::Parse {
+                path: path.to_owned(),
+                error,
+            })?;
+        if let Some(dir) = path.parent() {
+            for binary in &mut list.binaries {
+                binary.path = dir.join(&binary.path);
+            }
+        }
+        list.check_unique_ids()?;
+        Ok(list)
+    }
+
+    /// Returns the binaries in this list.
+    pub fn binaries(&self) -> &[ExternalBinary] {
+        &self.binaries
+    }
+
+    /// Returns JSON in the format produced by `cargo metadata`, describing a workspace at
+    /// `workspace_root` with a made-up package for each binary.
+    ///
+    /// A package graph constructed from this JSON can be passed into
+    /// [`Self::rust_test_artifacts`].
+    pub fn cargo_metadata(&self, workspace_root: &Utf8Path) -> String {
+        let packages: Vec<_> = self
+            .binaries
+            .iter()
+            .map(|binary| {
+                let binary_id = binary.binary_id();
+                json!({
+                    "name": binary_id,
+                    "version": EXTERNAL_PACKAGE_VERSION,
+                    "id": package_id(binary_id),
+                    "license": null,
+                    "license_file": null,
+                    "description": null,
+                    "source": null,
+                    "dependencies": [],
+                    "targets": [{
+                        "kind": ["test"],
+                        "crate_types": ["bin"],
+                        "name": binary_id,
+                        "src_path": binary.path,
+                        "edition": "2018",
+                        "doctest": false,
+                    }],
+                    "features": {},
+                    "manifest_path": manifest_path(workspace_root, binary_id),
+                    "metadata": null,
+                    "publish": null,
+                    "authors": [],
+                    "categories": [],
+                    "keywords": [],
+                    "readme": null,
+                    "repository": null,
+                    "edition": "2018",
+                    "links": null,
+                })
+            })
+            .collect();
+        let workspace_members: Vec<_> = self
+            .binaries
+            .iter()
+            .map(|binary| package_id(binary.binary_id()))
+            .collect();
+
+        json!({
+            "packages": packages,
+            "workspace_members": workspace_members,
+            "resolve": null,
+            "target_directory": workspace_root.join("target"),
+            "version": 1,
+            "workspace_root": workspace_root,
+        })
+        .to_string()
+    }
+
+    /// Returns test artifacts for the binaries in this list.
+    ///
+    /// `graph` must have been constructed from the output of [`Self::cargo_metadata`], called
+    /// with the same workspace root.
+    pub fn rust_test_artifacts<'g>(
+        &self,
+        graph: &'g PackageGraph,
+        workspace_root: &Utf8Path,
+    ) -> Result<Vec<RustTestArtifact<'g>>, ExternalBinaryListError> {
+        self.binaries
+            .iter()
+            .map(|binary| {
+                let binary_id = binary.binary_id();
+                let package = graph
+                    .metadata(&PackageId::new(package_id(binary_id)))
+                    .map_err(ExternalBinaryListError::PackageGraph)?;
+                let cwd = match &binary.cwd {
+                    Some(cwd) => workspace_root.join(cwd),
+                    None => workspace_root.to_owned(),
+                };
+                Ok(RustTestArtifact {
+                    binary_id: binary_id.to_owned(),
+                    package,
+                    binary_path: binary.path.clone(),
+                    binary_name: binary_id.to_owned(),
+                    cwd,
+                    build_platform: BuildPlatform::Target,
+                    harness: binary.harness,
+                    env: binary.env.clone(),
+                    kind: RustTestBinaryKind::Test,
+                    required_features: vec![],
+                    profile: None,
+                })
+            })
+            .collect()
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn check_unique_ids(&self) -> Result<(), ExternalBinaryListError> {
+        let mut seen = BTreeSet::new();
+        for binary in &self.binaries {
+            if !seen.insert(binary.binary_id()) {
+                return Err(ExternalBinaryListError::DuplicateBinaryId(
+                    binary.binary_id().to_owned(),
+                ));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn package_id(binary_id: &str) -> String {
+    format!("{} {} (external)", binary_id, EXTERNAL_PACKAGE_VERSION)
+}
+
+fn manifest_path(workspace_root: &Utf8Path, binary_id: &str) -> Utf8PathBuf {
+    // The manifest doesn't exist, but each package needs a path of its own within the workspace.
+    let dir_name: String = binary_id
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c } else { '-' })
+        .collect();
+    workspace_root
+        .join(".nextest-external")
+        .join(dir_name)
+        .join("Cargo.toml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn external_binaries() {
+        let list: ExternalBinaryList = serde_json::from_str(
+            r#"{
+                "binaries": [
+                    { "path": "/bazel-bin/a/a_test" },
+                    {
+                        "path": "/bazel-bin/b/b_test",
+                        "binary-id": "b::b_test",
+                        "cwd": "b",
+                        "env": { "MY_VAR": "value" },
+                        "harness": "custom"
+                    }
+                ]
+            }"#,
+        )
+        .expect("list is valid");
+
+        let workspace_root = Utf8Path::new("/workspace");
+        let graph = guppy::CargoMetadata::parse_json(list.cargo_metadata(workspace_root))
+            .expect("metadata is valid")
+            .build_graph()
+            .expect("graph is valid");
+        let artifacts = list
+            .rust_test_artifacts(&graph, workspace_root)
+            .expect("artifacts found");
+
+        assert_eq!(artifacts.len(), 2);
+        assert_eq!(artifacts[0].binary_id, "a_test");
+        assert_eq!(artifacts[0].package.name(), "a_test");
+        assert_eq!(artifacts[0].cwd, "/workspace");
+        assert_eq!(artifacts[0].harness, RustTestHarness::Libtest);
+        assert!(artifacts[0].env.is_empty());
+
+        assert_eq!(artifacts[1].binary_id, "b::b_test");
+        assert_eq!(artifacts[1].binary_path, "/bazel-bin/b/b_test");
+        assert_eq!(artifacts[1].cwd, "/workspace/b");
+        assert_eq!(artifacts[1].harness, RustTestHarness::Custom);
+        assert_eq!(artifacts[1].env["MY_VAR"], "value");
+
+        let duplicate = vec![
+            ExternalBinary::new("/a/my_test"),
+            ExternalBinary::new("/b/my_test"),
+        ];
+        match ExternalBinaryList::new(duplicate) {
+            Err(ExternalBinaryListError::DuplicateBinaryId(binary_id)) => {
+                assert_eq!(binary_id, "my_test");
+            }
+            other => panic!("expected duplicate binary ID error, found {:?}", other),
+        }
+    }
+}
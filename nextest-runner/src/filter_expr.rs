@@ -0,0 +1,405 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Filter expressions, used to select sets of tests from configuration.
+//!
+//! A filter expression is made up of *predicates*, combined with `and`, `or` and `not` (or `&`,
+//! `|` and `!`). Parentheses can be used for grouping. The supported predicates are:
+//!
+//! * `all()`: matches all tests.
+//! * `none()`: matches no tests.
+//! * `test(matcher)`: matches the name of the test. The default matcher is *contains*.
+//! * `package(matcher)`: matches the name of the package the test is in. The default matcher is
+//!   *equals*.
+//! * `binary(matcher)`: matches the binary ID of the test. The default matcher is *equals*.
+//!
+//! A matcher can be prefixed with `=` to match exactly, `~` to match a substring, or wrapped in
+//! `/` to match a regular expression. For example, `test(/^db::/) and not package(=my-crate)`.
+//!
+//! The main structure in this module is [`FilterExpr`].
+
+use crate::{errors::FilterExprParseError, test_list::TestInstance};
+use regex::Regex;
+use std::fmt;
+
+/// A parsed filter expression.
+#[derive(Clone, Debug)]
+pub struct FilterExpr {
+    input: String,
+    expr: ExprImpl,
+}
+
+impl FilterExpr {
+    /// Parses a filter expression from the given input.
+    pub fn parse(input: impl Into<String>) -> Result<Self, FilterExprParseError> {
+        let input = input.into();
+        let expr = Parser::new(&input)
+            .parse()
+            .map_err(|message| FilterExprParseError::new(input.clone(), message))?;
+        Ok(Self { input, expr })
+    }
+
+    /// Returns the string this filter expression was parsed from.
+    pub fn as_str(&self) -> &str {
+        &self.input
+    }
+
+    /// Returns true if the given test matches this filter expression.
+    pub fn matches(&self, test: &TestInstance<'_>) -> bool {
+        self.matches_parts(
+            test.bin_info.package.name(),
+            &test.bin_info.binary_id,
+            test.name,
+        )
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    pub(crate) fn matches_parts(
+        &self,
+        package_name: &str,
+        binary_id: &str,
+        test_name: &str,
+    ) -> bool {
+        self.expr.matches(&MatchContext {
+            package_name,
+            binary_id,
+            test_name,
+        })
+    }
+}
+
+impl fmt::Display for FilterExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.input)
+    }
+}
+
+struct MatchContext<'a> {
+    package_name: &'a str,
+    binary_id: &'a str,
+    test_name: &'a str,
+}
+
+#[derive(Clone, Debug)]
+enum ExprImpl {
+    All,
+    None,
+    Test(NameMatcher),
+    Package(NameMatcher),
+    Binary(NameMatcher),
+    Not(Box<ExprImpl>),
+    And(Box<ExprImpl>, Box<ExprImpl>),
+    Or(Box<ExprImpl>, Box<ExprImpl>),
+}
+
+impl ExprImpl {
+    fn matches(&self, cx: &MatchContext<'_>) -> bool {
+        match self {
+            ExprImpl::All => true,
+            ExprImpl::None => false,
+            ExprImpl::Test(matcher) => matcher.is_match(cx.test_name),
+            ExprImpl::Package(matcher) => matcher.is_match(cx.package_name),
+            ExprImpl::Binary(matcher) => matcher.is_match(cx.binary_id),
+            ExprImpl::Not(expr) => !expr.matches(cx),
+            ExprImpl::And(a, b) => a.matches(cx) && b.matches(cx),
+            ExprImpl::Or(a, b) => a.matches(cx) || b.matches(cx),
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum NameMatcher {
+    Equal(String),
+    Contains(String),
+    Regex(Regex),
+}
+
+impl NameMatcher {
+    fn is_match(&self, input: &str) -> bool {
+        match self {
+            NameMatcher::Equal(s) => input == s,
+            NameMatcher::Contains(s) => input.contains(s.as_str()),
+            NameMatcher::Regex(regex) => regex.is_match(input),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum DefaultMatcher {
+    Equal,
+    Contains,
+}
+
+// ---
+// Parser
+// ---
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn new(input: &'a str) -> Self {
+        Self { input, pos: 0 }
+    }
+
+    fn parse(mut self) -> Result<ExprImpl, String> {
+        let expr = self.parse_or()?;
+        self.skip_whitespace();
+        if self.pos < self.input.len() {
+            return Err(self.error("unexpected input"));
+        }
+        Ok(expr)
+    }
+
+    fn parse_or(&mut self) -> Result<ExprImpl, String> {
+        let mut expr = self.parse_and()?;
+        loop {
+            self.skip_whitespace();
+            if self.eat("|") || self.eat_keyword("or") {
+                let rhs = self.parse_and()?;
+                expr = ExprImpl::Or(Box::new(expr), Box::new(rhs));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn parse_and(&mut self) -> Result<ExprImpl, String> {
+        let mut expr = self.parse_unary()?;
+        loop {
+            self.skip_whitespace();
+            if self.eat("&") || self.eat_keyword("and") {
+                let rhs = self.parse_unary()?;
+                expr = ExprImpl::And(Box::new(expr), Box::new(rhs));
+            } else {
+                return Ok(expr);
+            }
+        }
+    }
+
+    fn parse_unary(&mut self) -> Result<ExprImpl, String> {
+        self.skip_whitespace();
+        if self.eat("!") || self.eat_keyword("not") {
+            let expr = self.parse_unary()?;
+            Ok(ExprImpl::Not(Box::new(expr)))
+        } else {
+            self.parse_atom()
+        }
+    }
+
+    fn parse_atom(&mut self) -> Result<ExprImpl, String> {
+        self.skip_whitespace();
+        if self.eat("(") {
+            let expr = self.parse_or()?;
+            self.skip_whitespace();
+            if !self.eat(")") {
+                return Err(self.error("expected ')'"));
+            }
+            return Ok(expr);
+        }
+
+        let name_start = self.pos;
+        let name_len = self
+            .rest()
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+            .unwrap_or_else(|| self.rest().len());
+        let name = &self.input[name_start..(name_start + name_len)];
+        if name.is_empty() {
+            return Err(self.error("expected a predicate"));
+        }
+        self.pos += name_len;
+
+        self.skip_whitespace();
+        if !self.eat("(") {
+            return Err(self.error("expected '('"));
+        }
+
+        let expr = match name {
+            "all" => {
+                self.expect_close()?;
+                ExprImpl::All
+            }
+            "none" => {
+                self.expect_close()?;
+                ExprImpl::None
+            }
+            "test" => ExprImpl::Test(self.parse_matcher(DefaultMatcher::Contains)?),
+            "package" => ExprImpl::Package(self.parse_matcher(DefaultMatcher::Equal)?),
+            "binary" => ExprImpl::Binary(self.parse_matcher(DefaultMatcher::Equal)?),
+            other => {
+                self.pos = name_start;
+                return Err(self.error(&format!("unknown predicate '{}'", other)));
+            }
+        };
+        Ok(expr)
+    }
+
+    /// Parses a matcher and the closing parenthesis following it.
+    fn parse_matcher(&mut self, default: DefaultMatcher) -> Result<NameMatcher, String> {
+        self.skip_whitespace();
+        if self.eat("/") {
+            // Regex: read up to the next unescaped '/'.
+            let rest = self.rest();
+            let mut end = None;
+            let mut escaped = false;
+            for (idx, c) in rest.char_indices() {
+                match c {
+                    '\\' => escaped = !escaped,
+                    '/' if !escaped => {
+                        end = Some(idx);
+                        break;
+                    }
+                    _ => escaped = false,
+                }
+            }
+            let end = end.ok_or_else(|| self.error("expected closing '/' for regex"))?;
+            let pattern = rest[..end].replace("\\/", "/");
+            let regex = Regex::new(&pattern)
+                .map_err(|err| self.error(&format!("invalid regex: {}", err)))?;
+            self.pos += end + 1;
+            self.expect_close()?;
+            return Ok(NameMatcher::Regex(regex));
+        }
+
+        let end = self
+            .rest()
+            .find(')')
+            .ok_or_else(|| self.error("expected ')'"))?;
+        let arg = self.rest()[..end].trim();
+        let matcher = if let Some(s) = arg.strip_prefix('=') {
+            NameMatcher::Equal(s.to_owned())
+        } else if let Some(s) = arg.strip_prefix('~') {
+            NameMatcher::Contains(s.to_owned())
+        } else if arg.is_empty() {
+            return Err(self.error("expected a matcher"));
+        } else {
+            match default {
+                DefaultMatcher::Equal => NameMatcher::Equal(arg.to_owned()),
+                DefaultMatcher::Contains => NameMatcher::Contains(arg.to_owned()),
+            }
+        };
+        self.pos += end + 1;
+        Ok(matcher)
+    }
+
+    fn expect_close(&mut self) -> Result<(), String> {
+        self.skip_whitespace();
+        if self.eat(")") {
+            Ok(())
+        } else {
+            Err(self.error("expected ')'"))
+        }
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_whitespace(&mut self) {
+        let rest = self.rest();
+        self.pos += rest.len() - rest.trim_start().len();
+    }
+
+    fn eat(&mut self, token: &str) -> bool {
+        if self.rest().starts_with(token) {
+            self.pos += token.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    /// Eats a keyword like `and`, as long as it isn't the prefix of a longer identifier.
+    fn eat_keyword(&mut self, keyword: &str) -> bool {
+        let rest = self.rest();
+        let is_keyword = rest.starts_with(keyword)
+            && !rest[keyword.len()..].starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_');
+        if is_keyword {
+            self.pos += keyword.len();
+        }
+        is_keyword
+    }
+
+    fn error(&self, message: &str) -> String {
+        format!("{} at position {}", message, self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(input: &str, package_name: &str, binary_id: &str, test_name: &str) -> bool {
+        let expr = FilterExpr::parse(input).expect("filter expression is valid");
+        expr.matches_parts(package_name, binary_id, test_name)
+    }
+
+    #[test]
+    fn filter_expr_matches() {
+        assert!(matches("all()", "foo", "foo", "test_a"));
+        assert!(!matches("none()", "foo", "foo", "test_a"));
+
+        // test() defaults to contains, package() and binary() default to equals.
+        assert!(matches("test(test_)", "foo", "foo", "test_a"));
+        assert!(!matches("test(=test_)", "foo", "foo", "test_a"));
+        assert!(matches("package(foo)", "foo", "foo", "test_a"));
+        assert!(!matches("package(fo)", "foo", "foo", "test_a"));
+        assert!(matches("package(~fo)", "foo", "foo", "test_a"));
+        assert!(matches(
+            "binary(foo::integration)",
+            "foo",
+            "foo::integration",
+            "t"
+        ));
+        assert!(matches("binary(/::/)", "foo", "foo::integration", "t"));
+        assert!(!matches("binary(/::/)", "foo", "foo", "t"));
+        assert!(matches(r"test(/^a\/b$/)", "foo", "foo", "a/b"));
+
+        // Operators and precedence: and binds tighter than or.
+        assert!(matches(
+            "package(bar) or package(foo) and test(a)",
+            "foo",
+            "foo",
+            "test_a"
+        ));
+        assert!(!matches(
+            "(package(bar) | package(foo)) & test(b)",
+            "foo",
+            "foo",
+            "test_a"
+        ));
+        assert!(matches("not package(bar)", "foo", "foo", "test_a"));
+        assert!(!matches("!!package(bar)", "foo", "foo", "test_a"));
+    }
+
+    #[test]
+    fn filter_expr_parse_errors() {
+        for input in &[
+            "",
+            "test",
+            "test(",
+            "test()",
+            "unknown(foo)",
+            "all(",
+            "test(/abc)",
+            "test(/[/)",
+            "all() and",
+            "all() none()",
+        ] {
+            assert!(
+                FilterExpr::parse(*input).is_err(),
+                "input {:?} should fail to parse",
+                input
+            );
+        }
+    }
+}
@@ -1,9 +1,19 @@
 // Copyright (c) The nextest Contributors
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
-use crate::test_list::Styles;
+use crate::{errors::HttpPostError, test_list::Styles};
+use camino::Utf8Path;
 use owo_colors::OwoColorize;
-use std::io::{self, Write};
+use std::{
+    fs,
+    hash::Hasher,
+    io::{self, Read, Write},
+    time::Duration,
+};
+use twox_hash::XxHash64;
+
+/// How long to wait for a response when sending events over HTTP.
+const HTTP_TIMEOUT: Duration = Duration::from_secs(30);
 
 /// Write out a test name.
 pub(crate) fn write_test_name(
@@ -26,3 +36,36 @@ pub(crate) fn write_test_name(
 
     Ok(())
 }
+
+/// POST a JSON body to the given URL, with additional headers.
+pub(crate) fn post_json<'a>(
+    url: &str,
+    headers: impl IntoIterator<Item = (&'a str, &'a str)>,
+    body: &serde_json::Value,
+) -> Result<(), HttpPostError> {
+    let agent = ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build();
+    let mut request = agent.post(url);
+    for (name, value) in headers {
+        request = request.set(name, value);
+    }
+    request
+        .set("Content-Type", "application/json")
+        .send_string(&body.to_string())
+        .map_err(HttpPostError::new)?;
+    Ok(())
+}
+
+/// Returns a hash of the contents of a file.
+pub(crate) fn hash_file(path: &Utf8Path) -> io::Result<u64> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = XxHash64::default();
+    let mut buf = vec![0; 64 * 1024];
+    loop {
+        match file.read(&mut buf) {
+            Ok(0) => return Ok(hasher.finish()),
+            Ok(n) => hasher.write(&buf[..n]),
+            Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+            Err(error) => return Err(error),
+        }
+    }
+}
@@ -0,0 +1,155 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Tests that failed in previous runs.
+//!
+//! At the end of each run, nextest records the tests that are currently failing within the
+//! profile's store directory. With `--rerun-failed`, only these tests are run, which shortens the
+//! edit-test loop after many tests fail at once.
+
+use crate::{
+    errors::{LastRunError, WriteEventError},
+    test_list::{TestInstance, TestList},
+};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs, io,
+};
+
+/// Tests that were failing as of the last run.
+///
+/// A test that fails stays in this list until it passes: tests that aren't run (for example,
+/// because they were filtered out or the run was canceled) keep their previous state.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct LastRun {
+    /// Map of binary ID to the names of failed tests in that binary.
+    failed: BTreeMap<String, BTreeSet<String>>,
+}
+
+impl LastRun {
+    /// The name of the file failed tests are stored in, within the profile's store directory.
+    pub const FILE_NAME: &'static str = "last-run.json";
+
+    /// Reads failed tests from the given store directory.
+    ///
+    /// Returns an empty list if no runs have been recorded yet.
+    pub fn read(store_dir: &Utf8Path) -> Result<Self, LastRunError> {
+        let path = store_dir.join(Self::FILE_NAME);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(LastRunError::Read { path, error }),
+        };
+        serde_json::from_str(&contents).map_err(|error| LastRunError::Parse { path, error })
+    }
+
+    /// Returns true if no failed tests have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns the number of failed tests.
+    pub fn failed_count(&self) -> usize {
+        self.failed.values().map(BTreeSet::len).sum()
+    }
+
+    /// Returns true if the given test failed.
+    pub fn is_failed(&self, binary_id: &str, test_name: &str) -> bool {
+        self.failed
+            .get(binary_id)
+            .map_or(false, |tests| tests.contains(test_name))
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    /// Discards tests that are no longer in the test list.
+    pub(crate) fn retain_listed(&mut self, test_list: &TestList<'_>) {
+        let mut failed: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for test_instance in test_list.iter_tests() {
+            let binary_id = &test_instance.bin_info.binary_id;
+            if self.is_failed(binary_id, test_instance.name) {
+                failed
+                    .entry(binary_id.clone())
+                    .or_default()
+                    .insert(test_instance.name.to_owned());
+            }
+        }
+        self.failed = failed;
+    }
+
+    /// Records whether the given test passed or failed.
+    pub(crate) fn record(&mut self, test_instance: &TestInstance<'_>, passed: bool) {
+        let binary_id = &test_instance.bin_info.binary_id;
+        if passed {
+            if let Some(tests) = self.failed.get_mut(binary_id) {
+                tests.remove(test_instance.name);
+                if tests.is_empty() {
+                    self.failed.remove(binary_id);
+                }
+            }
+        } else {
+            self.failed
+                .entry(binary_id.clone())
+                .or_default()
+                .insert(test_instance.name.to_owned());
+        }
+    }
+
+    pub(crate) fn write(&self, store_dir: &Utf8Path) -> Result<(), WriteEventError> {
+        fs::create_dir_all(store_dir).map_err(|error| WriteEventError::Fs {
+            file: store_dir.to_path_buf(),
+            error,
+        })?;
+        let path = store_dir.join(Self::FILE_NAME);
+        let f = fs::File::create(&path).map_err(|error| WriteEventError::Fs {
+            file: path.clone(),
+            error,
+        })?;
+        serde_json::to_writer_pretty(f, self).map_err(WriteEventError::Json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_write_last_run() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let store_dir = Utf8Path::from_path(tempdir.path()).expect("temp dir is valid UTF-8");
+
+        // No history => no failed tests.
+        let last_run = LastRun::read(store_dir).expect("missing file is not an error");
+        assert!(last_run.is_empty(), "no runs recorded yet");
+
+        std::fs::write(
+            store_dir.join(LastRun::FILE_NAME),
+            r#"{ "failed": { "my-crate": ["tests::a", "tests::b"], "other-crate": ["c"] } }"#,
+        )
+        .expect("wrote file");
+        let last_run = LastRun::read(store_dir).expect("read last run");
+        assert_eq!(last_run.failed_count(), 3);
+        assert!(last_run.is_failed("my-crate", "tests::a"));
+        assert!(last_run.is_failed("other-crate", "c"));
+        assert!(!last_run.is_failed("my-crate", "c"));
+        assert!(!last_run.is_failed("new-crate", "tests::a"));
+
+        last_run.write(store_dir).expect("wrote last run");
+        assert_eq!(
+            LastRun::read(store_dir).expect("read last run"),
+            last_run,
+            "round trip works"
+        );
+
+        std::fs::write(store_dir.join(LastRun::FILE_NAME), "not json").expect("wrote file");
+        assert!(matches!(
+            LastRun::read(store_dir),
+            Err(LastRunError::Parse { .. })
+        ));
+    }
+}
@@ -17,8 +17,15 @@
 //! 3. Separately, a [`test_filter::TestFilter`] is created based on text filters, along with the
 //!    run-ignored and partitioning filters if provided.
 //! 4. The list of test binaries and test filter are combined. Each binary is run with `--list` to
-//!    grab the list of tests, the given filters are applied to it, and everything is put together
-//!    to create a [`test_list::TestList`].
+//!    grab the list of tests (unless the list was [cached](list_cache) by a previous invocation),
+//!    the given filters are applied to it, and everything is put together to create a
+//!    [`test_list::TestList`].
+//!
+//! Alternatively, test binaries and the list of them can be packaged into an [archive] ahead of
+//! time. Extracting the archive produces the same list of [`test_list::RustTestArtifact`]
+//! instances, without requiring Cargo to be present.
+//!
+//! Test binaries can also be copied to another machine and run there over SSH: see [`remote`].
 //!
 //! If `cargo nextest list-tests` is called, this [`test_list::TestList`] is printed out. If `cargo
 //! nextest run` is called, nextest proceeds to run the tests.
@@ -26,28 +33,49 @@
 //! ## Running the tests
 //!
 //! 1. A new [`runner::TestRunner`] is created with the test list and appropriate configuration.
-//! 2. The runner sets up two thread pools:
-//!     * The *run pool*: each thread in this pool executes a test (+ 1 thread for overall
-//!       management).
-//!     * The *wait pool*: each thread in this pool monitors the status of a test being run by the
-//!       run pool.
+//! 2. The runner sets up a single-threaded async runtime. Each running test is a task on it, and
+//!    the runtime waits on all of their processes, output, timeouts and signals at once, so that
+//!    no thread is needed per running test.
 //! 3. The test runner is executed with a callback to send [`reporter::TestEvent`] instances to the
-//!    test reporter.
+//!    test reporter. Before any tests are run, setup [scripts](script) are executed; their teardown
+//!    commands are run once all tests have finished.
 //! 4. The test runner iterates over the test list to get individual [`test_list::TestInstance`]
-//!    information. Test instances are sent to the thread pool to be executed.
-//! 5. If a test fails and fail-fast is true, or if a signal is encountered, the run is cancelled;
-//!    currently executing tests are allowed to complete, but no new tests are scheduled.
+//!    information. Test instances are started as tasks as long as enough test threads are free.
+//! 5. If a test fails and fail-fast is true (or the maximum number of failures is reached), or if a
+//!    signal is encountered, the run is cancelled; currently executing tests are allowed to
+//!    complete, but no new tests are scheduled. Tests that weren't started are reported as
+//!    canceled.
 //! 6. The test reporter sees events and prints them to stderr (and aggregates them if necessary
 //!    based on configs).
+//!
+//! Events can also be [recorded](record) to a file, and replayed through reporters later without
+//! running any tests. Two recorded runs can be compared with [`run_diff`], and the results of a
+//! [partitioned](partition) run can be combined with [`run_merge`].
 
+pub mod affected;
+pub mod archive;
 pub mod config;
+pub mod coverage;
+pub mod double_spawn;
 pub mod errors;
+pub mod external;
+pub mod filter_expr;
 mod helpers;
+pub mod last_run;
+pub mod list_cache;
+pub mod metadata_cache;
 pub mod partition;
+pub mod record;
+pub mod remote;
 pub mod reporter;
+pub mod run_diff;
+pub mod run_merge;
 pub mod runner;
+pub mod script;
 pub mod signal;
 mod stopwatch;
+pub mod store;
 pub mod target_runner;
 pub mod test_filter;
 pub mod test_list;
+pub mod timings;
@@ -0,0 +1,188 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Test lists cached between invocations.
+//!
+//! Listing the tests in a binary means running it twice, which is pure overhead if the binary
+//! hasn't changed since the last time nextest was run. nextest records the list output of each
+//! binary within the store directory, along with the binary's modification time, size and a hash
+//! of its contents, and reuses the output for as long as the binary stays the same.
+//!
+//! Only binaries that use the standard libtest harness are cached: custom harnesses may generate
+//! their tests at runtime, for example from files on disk.
+
+use crate::{errors::ListCacheError, helpers::hash_file};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io, sync::Mutex, time::SystemTime};
+
+/// The list output of test binaries, as recorded by previous invocations.
+#[derive(Debug, Default)]
+pub struct ListCache {
+    // The cache is looked up and added to while binaries are being listed in parallel.
+    entries: Mutex<BTreeMap<Utf8PathBuf, CacheEntry>>,
+}
+
+impl ListCache {
+    /// The name of the file the cache is stored in, within the store directory.
+    pub const FILE_NAME: &'static str = "list-cache.json";
+
+    /// Reads the cache from the given store directory.
+    ///
+    /// Returns an empty cache if nothing has been cached yet.
+    pub fn read(store_dir: &Utf8Path) -> Result<Self, ListCacheError> {
+        let path = store_dir.join(Self::FILE_NAME);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(ListCacheError::Read { path, error }),
+        };
+        let entries = serde_json::from_str(&contents)
+            .map_err(|error| ListCacheError::Parse { path, error })?;
+        Ok(Self {
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Writes the cache out to the given store directory.
+    ///
+    /// Entries for binaries that no longer exist are dropped.
+    pub fn write(&self, store_dir: &Utf8Path) -> Result<(), ListCacheError> {
+        let mut entries = self.entries.lock().expect("list cache isn't poisoned");
+        entries.retain(|binary_path, _| binary_path.exists());
+
+        let path = store_dir.join(Self::FILE_NAME);
+        let contents = serde_json::to_string(&*entries).expect("list cache is serializable");
+        fs::create_dir_all(store_dir)
+            .and_then(|()| fs::write(&path, contents))
+            .map_err(|error| ListCacheError::Write { path, error })
+    }
+
+    /// Returns the number of binaries in the cache.
+    pub fn len(&self) -> usize {
+        self.entries
+            .lock()
+            .expect("list cache isn't poisoned")
+            .len()
+    }
+
+    /// Returns true if the cache is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    /// Returns the non-ignored and ignored list output of the binary, if it hasn't changed since
+    /// it was cached.
+    pub(crate) fn get(&self, binary_path: &Utf8Path) -> Option<(String, String)> {
+        let (size, modified) = file_info(binary_path).ok()?;
+        let mut entries = self.entries.lock().expect("list cache isn't poisoned");
+        let entry = entries.get_mut(binary_path)?;
+        if entry.size != size {
+            return None;
+        }
+        if entry.modified != modified {
+            // Rebuilding a binary doesn't always change it, so look at its contents before giving
+            // up on the entry. The lock is held while hashing, but binaries that haven't changed
+            // size are rare enough that this doesn't matter.
+            if hash_file(binary_path).ok()? != entry.hash {
+                return None;
+            }
+            entry.modified = modified;
+        }
+        Some((entry.non_ignored.clone(), entry.ignored.clone()))
+    }
+
+    /// Records the list output of the binary.
+    ///
+    /// Errors reading the binary are ignored: the output just isn't cached.
+    pub(crate) fn insert(&self, binary_path: &Utf8Path, non_ignored: &str, ignored: &str) {
+        let entry = match file_info(binary_path)
+            .and_then(|(size, modified)| Ok((size, modified, hash_file(binary_path)?)))
+        {
+            Ok((size, modified, hash)) => CacheEntry {
+                size,
+                modified,
+                hash,
+                non_ignored: non_ignored.to_owned(),
+                ignored: ignored.to_owned(),
+            },
+            Err(_) => return,
+        };
+        self.entries
+            .lock()
+            .expect("list cache isn't poisoned")
+            .insert(binary_path.to_owned(), entry);
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CacheEntry {
+    size: u64,
+    modified: Timestamp,
+    hash: u64,
+    non_ignored: String,
+    ignored: String,
+}
+
+/// A modification time, as seconds and nanoseconds since the Unix epoch.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+struct Timestamp {
+    secs: u64,
+    nanos: u32,
+}
+
+fn file_info(path: &Utf8Path) -> io::Result<(u64, Timestamp)> {
+    let metadata = fs::metadata(path)?;
+    let since_epoch = metadata
+        .modified()?
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default();
+    let modified = Timestamp {
+        secs: since_epoch.as_secs(),
+        nanos: since_epoch.subsec_nanos(),
+    };
+    Ok((metadata.len(), modified))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn cache_invalidation() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(tempdir.path()).expect("temp dir is valid UTF-8");
+        let binary_path = dir.join("my-test");
+        fs::write(&binary_path, "contents").expect("wrote binary");
+
+        let cache = ListCache::default();
+        assert_eq!(cache.get(&binary_path), None, "nothing is cached yet");
+        cache.insert(&binary_path, "a: test\n", "b: test\n");
+        cache.write(dir).expect("wrote cache");
+
+        let cache = ListCache::read(dir).expect("read cache");
+        let outputs = Some(("a: test\n".to_owned(), "b: test\n".to_owned()));
+        assert_eq!(cache.get(&binary_path), outputs);
+
+        // Rewriting the binary with the same contents keeps the entry.
+        let later = SystemTime::now() + Duration::from_secs(60);
+        fs::write(&binary_path, "contents").expect("wrote binary");
+        filetime::set_file_mtime(&binary_path, later.into()).expect("set modification time");
+        assert_eq!(cache.get(&binary_path), outputs);
+
+        // Changing the binary invalidates the entry, even if its size is the same.
+        fs::write(&binary_path, "CONTENTS").expect("wrote binary");
+        assert_eq!(cache.get(&binary_path), None);
+
+        // Entries for binaries that were removed are dropped when the cache is written.
+        fs::remove_file(&binary_path).expect("removed binary");
+        cache.write(dir).expect("wrote cache");
+        assert!(ListCache::read(dir).expect("read cache").is_empty());
+    }
+}
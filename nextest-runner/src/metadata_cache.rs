@@ -0,0 +1,288 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! `cargo metadata` output cached between invocations.
+//!
+//! The package graph nextest works with is constructed from the output of `cargo metadata`, which
+//! can take a while to produce in large workspaces. nextest records the output in the target
+//! directory, along with hashes of the files it was derived from:
+//! * the manifest nextest was invoked with
+//! * the manifest of every package in the workspace
+//! * the list of entries in the directories within the workspace that packages are in, so that
+//!   packages added to a directory matched by a `members` glob are noticed
+//! * Cargo configuration files, which may change the target directory
+//!
+//! along with some environment variables that affect Cargo. The output is reused as long as none
+//! of these change.
+
+use crate::{errors::MetadataCacheError, helpers::hash_file};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, hash::Hasher, io};
+use twox_hash::XxHash64;
+
+/// Environment variables that affect the output of `cargo metadata`.
+const CARGO_ENV_VARS: &[&str] = &[
+    "CARGO",
+    "CARGO_HOME",
+    "CARGO_TARGET_DIR",
+    "CARGO_BUILD_TARGET_DIR",
+    "RUSTUP_TOOLCHAIN",
+];
+
+/// The cached output of `cargo metadata` for a workspace.
+#[derive(Clone, Debug)]
+pub struct MetadataCache {
+    cache_path: Utf8PathBuf,
+    manifest_path: Utf8PathBuf,
+}
+
+impl MetadataCache {
+    /// The name of the file `cargo metadata` output is stored in, within `target/nextest`.
+    pub const FILE_NAME: &'static str = "cargo-metadata.json";
+
+    /// Finds the cache for the workspace containing the given manifest, or the current directory
+    /// if no manifest is given.
+    ///
+    /// Returns `None` if the manifest or the root of the workspace can't be found. The root is
+    /// taken to be the closest directory containing `Cargo.lock`, so workspaces that have never
+    /// been built aren't cached.
+    pub fn new(manifest_path: Option<&Utf8Path>) -> Option<Self> {
+        let cwd = Utf8PathBuf::from_path_buf(std::env::current_dir().ok()?).ok()?;
+        let manifest_path = match manifest_path {
+            Some(manifest_path) => cwd.join(manifest_path),
+            None => cwd
+                .ancestors()
+                .map(|dir| dir.join("Cargo.toml"))
+                .find(|path| path.is_file())?,
+        };
+        let workspace_dir = manifest_path
+            .ancestors()
+            .skip(1)
+            .find(|dir| dir.join("Cargo.lock").is_file())?;
+
+        let target_dir = match std::env::var("CARGO_TARGET_DIR")
+            .or_else(|_| std::env::var("CARGO_BUILD_TARGET_DIR"))
+        {
+            Ok(target_dir) => cwd.join(target_dir),
+            Err(_) => workspace_dir.join("target"),
+        };
+        let cache_path = target_dir.join("nextest").join(Self::FILE_NAME);
+        Some(Self::with_paths(cache_path, manifest_path))
+    }
+
+    /// Returns the cached output of `cargo metadata`, or `None` if nothing has been cached or any
+    /// of the files it was derived from have changed.
+    pub fn read(&self) -> Result<Option<String>, MetadataCacheError> {
+        let contents = match fs::read_to_string(&self.cache_path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => {
+                return Err(MetadataCacheError::Read {
+                    path: self.cache_path.clone(),
+                    error,
+                })
+            }
+        };
+        let cached: CachedMetadata =
+            serde_json::from_str(&contents).map_err(|error| MetadataCacheError::Parse {
+                path: self.cache_path.clone(),
+                error,
+            })?;
+
+        let key = self.key(
+            cached.key.files.keys().cloned(),
+            cached.key.dirs.keys().cloned(),
+        );
+        if cached.key == key {
+            Ok(Some(cached.cargo_metadata))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Caches the given output of `cargo metadata`.
+    pub fn write(&self, cargo_metadata: &str) -> Result<(), MetadataCacheError> {
+        let parsed: MinimalMetadata =
+            serde_json::from_str(cargo_metadata).map_err(MetadataCacheError::Metadata)?;
+
+        let mut files = vec![parsed.workspace_root.join("Cargo.toml")];
+        let mut dirs = vec![];
+        for package in &parsed.packages {
+            files.push(package.manifest_path.clone());
+            // Member globs are relative to the workspace root, so directories outside of it don't
+            // matter.
+            if let Some(dir) = package.manifest_path.parent().and_then(Utf8Path::parent) {
+                if dir.starts_with(&parsed.workspace_root) {
+                    dirs.push(dir.to_owned());
+                }
+            }
+        }
+        // Cargo reads configuration from every directory above the current one, and from Cargo's
+        // home directory.
+        let mut config_dirs: Vec<_> = std::env::current_dir()
+            .ok()
+            .and_then(|cwd| Utf8PathBuf::from_path_buf(cwd).ok())
+            .map(|cwd| cwd.ancestors().map(|dir| dir.join(".cargo")).collect())
+            .unwrap_or_default();
+        if let Ok(cargo_home) = home::cargo_home() {
+            config_dirs.extend(Utf8PathBuf::from_path_buf(cargo_home).ok());
+        }
+        for config_dir in config_dirs {
+            files.push(config_dir.join("config"));
+            files.push(config_dir.join("config.toml"));
+        }
+
+        let cached = CachedMetadata {
+            key: self.key(files, dirs),
+            cargo_metadata: cargo_metadata.to_owned(),
+        };
+
+        let contents = serde_json::to_string(&cached).expect("cached metadata is serializable");
+        let dir = self
+            .cache_path
+            .parent()
+            .expect("cache path must have a parent");
+        fs::create_dir_all(dir)
+            .and_then(|()| fs::write(&self.cache_path, contents))
+            .map_err(|error| MetadataCacheError::Write {
+                path: self.cache_path.clone(),
+                error,
+            })
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn with_paths(cache_path: Utf8PathBuf, manifest_path: Utf8PathBuf) -> Self {
+        Self {
+            cache_path,
+            manifest_path,
+        }
+    }
+
+    /// Computes the cache key for the current state of the given files and directories.
+    ///
+    /// Files and directories that can't be read have a hash of `None`.
+    fn key(
+        &self,
+        files: impl IntoIterator<Item = Utf8PathBuf>,
+        dirs: impl IntoIterator<Item = Utf8PathBuf>,
+    ) -> CacheKey {
+        CacheKey {
+            manifest_path: self.manifest_path.clone(),
+            env: CARGO_ENV_VARS
+                .iter()
+                .map(|&name| (name.to_owned(), std::env::var(name).ok()))
+                .collect(),
+            files: files
+                .into_iter()
+                .map(|path| {
+                    let hash = hash_file(&path).ok();
+                    (path, hash)
+                })
+                .collect(),
+            dirs: dirs
+                .into_iter()
+                .map(|path| {
+                    let hash = hash_dir_entries(&path).ok();
+                    (path, hash)
+                })
+                .collect(),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CachedMetadata {
+    key: CacheKey,
+    cargo_metadata: String,
+}
+
+#[derive(Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct CacheKey {
+    manifest_path: Utf8PathBuf,
+    env: BTreeMap<String, Option<String>>,
+    files: BTreeMap<Utf8PathBuf, Option<u64>>,
+    dirs: BTreeMap<Utf8PathBuf, Option<u64>>,
+}
+
+/// The parts of `cargo metadata` output used to compute the cache key.
+#[derive(Deserialize)]
+struct MinimalMetadata {
+    workspace_root: Utf8PathBuf,
+    packages: Vec<MinimalPackage>,
+}
+
+#[derive(Deserialize)]
+struct MinimalPackage {
+    manifest_path: Utf8PathBuf,
+}
+
+/// Returns a hash of the names of the entries in a directory.
+fn hash_dir_entries(path: &Utf8Path) -> io::Result<u64> {
+    let mut names = fs::read_dir(path)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<Vec<_>>>()?;
+    names.sort_unstable();
+
+    let mut hasher = XxHash64::default();
+    for name in names {
+        hasher.write(name.to_string_lossy().as_bytes());
+        hasher.write_u8(0);
+    }
+    Ok(hasher.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn cache_invalidation() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let root = Utf8Path::from_path(tempdir.path()).expect("temp dir is valid UTF-8");
+        let manifest_path = root.join("Cargo.toml");
+        let member_manifest_path = root.join("crates/a/Cargo.toml");
+        fs::create_dir_all(root.join("crates/a")).expect("created member dir");
+        fs::write(&manifest_path, "[workspace]\nmembers = [\"crates/*\"]\n").expect("wrote");
+        fs::write(&member_manifest_path, "[package]\nname = \"a\"\n").expect("wrote");
+
+        let cargo_metadata = json!({
+            "workspace_root": root,
+            "packages": [{ "manifest_path": member_manifest_path }],
+        })
+        .to_string();
+        let cache = MetadataCache::with_paths(
+            root.join("target/nextest").join(MetadataCache::FILE_NAME),
+            manifest_path.clone(),
+        );
+        assert_eq!(cache.read().expect("read cache"), None, "nothing cached");
+        cache.write(&cargo_metadata).expect("wrote cache");
+        assert_eq!(
+            cache.read().expect("read cache").as_deref(),
+            Some(cargo_metadata.as_str())
+        );
+
+        let other =
+            MetadataCache::with_paths(cache.cache_path.clone(), member_manifest_path.clone());
+        assert_eq!(
+            other.read().expect("read cache"),
+            None,
+            "invoked with a different manifest"
+        );
+
+        // Adding a package next to an existing one invalidates the cache.
+        fs::create_dir_all(root.join("crates/b")).expect("created member dir");
+        assert_eq!(cache.read().expect("read cache"), None, "package added");
+        cache.write(&cargo_metadata).expect("wrote cache");
+
+        // So does changing a package's manifest.
+        fs::write(&member_manifest_path, "[package]\nname = \"aa\"\n").expect("wrote");
+        assert_eq!(cache.read().expect("read cache"), None, "manifest changed");
+    }
+}
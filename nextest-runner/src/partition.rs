@@ -3,16 +3,12 @@
 
 //! Support for partitioning test runs across several machines.
 //!
-//! At the moment this only supports simple hash-based and count-based sharding. In the future it
-//! could potentially be made smarter: e.g. using data to pick different sets of binaries and tests
-//! to run, with an aim to minimize total build and test times.
+//! This supports simple hash-based and count-based sharding, along with duration-based sharding
+//! that uses the timings recorded by previous runs to give each shard about the same amount of
+//! work.
 
 use crate::errors::PartitionerBuilderParseError;
-use std::{
-    fmt,
-    hash::{Hash, Hasher},
-    str::FromStr,
-};
+use std::{fmt, hash::Hasher, str::FromStr, time::Duration};
 use twox_hash::XxHash64;
 
 /// A builder for creating `Partitioner` instances.
@@ -31,7 +27,11 @@ pub enum PartitionerBuilder {
         total_shards: u64,
     },
 
-    /// Partition based on hashing. Individual partitions are stateless.
+    /// Partition based on hashing the binary ID and test name. Individual partitions are
+    /// stateless.
+    ///
+    /// The shard a test is in only depends on its binary ID and name, so adding or removing tests
+    /// doesn't move any other tests to a different shard.
     Hash {
         /// The shard this is in, counting up from 1.
         shard: u64,
@@ -39,18 +39,31 @@ pub enum PartitionerBuilder {
         /// The total number of shards.
         total_shards: u64,
     },
+
+    /// Partition based on the durations of tests recorded by previous runs, so that each shard
+    /// takes about the same amount of time.
+    ///
+    /// Unlike the other schemes, this needs to know about every test in the run, so it isn't
+    /// applied by the [`Partitioner`] built for each binary. Instead, it's applied to the test list
+    /// as a whole with [`TestList::partition_by_duration`](crate::test_list::TestList::partition_by_duration).
+    Duration {
+        /// The shard this is in, counting up from 1.
+        shard: u64,
+
+        /// The total number of shards.
+        total_shards: u64,
+    },
 }
 
-/// Represents an individual partitioner, typically scoped to a test binary.
+/// Represents an individual partitioner, scoped to a test binary.
 pub trait Partitioner: fmt::Debug {
     /// Returns true if the given test name matches the partition.
     fn test_matches(&mut self, test_name: &str) -> bool;
 }
 
 impl PartitionerBuilder {
-    /// Creates a new `Partitioner` from this `PartitionerBuilder`.
-    pub fn build(&self) -> Box<dyn Partitioner> {
-        // Note we don't use test_binary at the moment but might in the future.
+    /// Creates a new `Partitioner` for the test binary with the given ID.
+    pub fn build(&self, binary_id: &str) -> Box<dyn Partitioner> {
         match self {
             PartitionerBuilder::Count {
                 shard,
@@ -59,7 +72,9 @@ impl PartitionerBuilder {
             PartitionerBuilder::Hash {
                 shard,
                 total_shards,
-            } => Box::new(HashPartitioner::new(*shard, *total_shards)),
+            } => Box::new(HashPartitioner::new(binary_id, *shard, *total_shards)),
+            // Applied to the test list once it has been built.
+            PartitionerBuilder::Duration { .. } => Box::new(DeferredPartitioner),
         }
     }
 }
@@ -83,11 +98,18 @@ impl FromStr for PartitionerBuilder {
                 shard,
                 total_shards,
             })
+        } else if let Some(input) = s.strip_prefix("duration:") {
+            let (shard, total_shards) = parse_shards(input, "duration:M/N")?;
+
+            Ok(PartitionerBuilder::Duration {
+                shard,
+                total_shards,
+            })
         } else {
             Err(PartitionerBuilderParseError::new(
                 None,
                 format!(
-                    "partition input '{}' must begin with \"hash:\" or \"count:\"",
+                    "partition input '{}' must begin with \"hash:\", \"count:\" or \"duration:\"",
                     s
                 ),
             ))
@@ -169,14 +191,16 @@ impl Partitioner for CountPartitioner {
 
 #[derive(Clone, Debug)]
 struct HashPartitioner {
+    binary_id: String,
     shard_minus_one: u64,
     total_shards: u64,
 }
 
 impl HashPartitioner {
-    fn new(shard: u64, total_shards: u64) -> Self {
+    fn new(binary_id: &str, shard: u64, total_shards: u64) -> Self {
         let shard_minus_one = shard - 1;
         Self {
+            binary_id: binary_id.to_owned(),
             shard_minus_one,
             total_shards,
         }
@@ -185,12 +209,72 @@ impl HashPartitioner {
 
 impl Partitioner for HashPartitioner {
     fn test_matches(&mut self, test_name: &str) -> bool {
-        let mut hasher = XxHash64::default();
-        test_name.hash(&mut hasher);
-        hasher.finish() % self.total_shards == self.shard_minus_one
+        stable_hash(&self.binary_id, test_name) % self.total_shards == self.shard_minus_one
+    }
+}
+
+/// Hashes a binary ID and test name.
+///
+/// Shards of a run may be on different machines and built with different versions of Rust, so
+/// this must not change between platforms or releases. The bytes are fed to the hasher directly,
+/// rather than through `Hash`, whose output for strings isn't guaranteed to stay the same.
+fn stable_hash(binary_id: &str, test_name: &str) -> u64 {
+    let mut hasher = XxHash64::with_seed(0);
+    hasher.write(binary_id.as_bytes());
+    // Binary IDs and test names can't contain NUL bytes, so this keeps e.g. ("a", "bc") and
+    // ("ab", "c") apart.
+    hasher.write(&[0]);
+    hasher.write(test_name.as_bytes());
+    hasher.finish()
+}
+
+/// Matches every test: used for schemes that are applied to the test list as a whole.
+#[derive(Clone, Debug)]
+struct DeferredPartitioner;
+
+impl Partitioner for DeferredPartitioner {
+    fn test_matches(&mut self, _test_name: &str) -> bool {
+        true
     }
 }
 
+/// Assigns tests to shards so that the total expected duration of each shard is about the same.
+///
+/// `durations` holds the expected duration of each test, if one was recorded. Tests without a
+/// recorded duration are assumed to take as long as the average test that has one. Returns the
+/// shard each test is assigned to, counting up from 1, in the same order as `durations`.
+///
+/// The assignment only depends on the durations and the order they're passed in, so every shard
+/// arrives at the same assignment as long as they all have the same timings.
+pub(crate) fn balance_by_duration(durations: &[Option<Duration>], total_shards: u64) -> Vec<u64> {
+    let known: Vec<_> = durations.iter().flatten().collect();
+    let default_duration = if known.is_empty() {
+        Duration::ZERO
+    } else {
+        known.iter().copied().sum::<Duration>() / known.len() as u32
+    };
+
+    // Hand out the longest tests first, each to the shard with the least work so far. Ties between
+    // shards go to the one with the fewest tests, so that tests that take no time at all (or runs
+    // without any timings) are spread out evenly.
+    let mut order: Vec<_> = (0..durations.len()).collect();
+    order.sort_by_key(|&index| std::cmp::Reverse(durations[index].unwrap_or(default_duration)));
+
+    let mut loads = vec![(Duration::ZERO, 0_usize); total_shards as usize];
+    let mut shards = vec![0; durations.len()];
+    for index in order {
+        let (shard, load) = loads
+            .iter_mut()
+            .enumerate()
+            .min_by_key(|(shard, load)| (**load, *shard))
+            .expect("total_shards is at least 1");
+        load.0 += durations[index].unwrap_or(default_duration);
+        load.1 += 1;
+        shards[index] = shard as u64 + 1;
+    }
+    shards
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +303,13 @@ mod tests {
                     total_shards: 200,
                 },
             ),
+            (
+                "duration:2/3",
+                PartitionerBuilder::Duration {
+                    shard: 2,
+                    total_shards: 3,
+                },
+            ),
         ];
 
         let failures = vec![
@@ -232,6 +323,7 @@ mod tests {
             "hash:m/2",
             "hash:1/n",
             "hash:1/2/3",
+            "duration:0/2",
         ];
 
         for (input, output) in successes {
@@ -251,4 +343,54 @@ mod tests {
                 .expect_err(&format!("expected input '{}' to fail", input));
         }
     }
+
+    #[test]
+    fn balance_durations() {
+        let secs = |secs| Some(Duration::from_secs(secs));
+
+        let shards = balance_by_duration(&[secs(1), secs(8), secs(3), secs(4), secs(2)], 2);
+        // 8 + 1 on shard 1, 4 + 3 + 2 on shard 2.
+        assert_eq!(shards, vec![1, 1, 2, 2, 2]);
+
+        // Tests without a duration are assumed to take the average time (4 seconds here).
+        let shards = balance_by_duration(&[None, secs(6), secs(2)], 2);
+        assert_eq!(shards, vec![2, 1, 2]);
+
+        // With no timings at all, tests are spread out by count.
+        let shards = balance_by_duration(&[None; 5], 2);
+        assert_eq!(shards, vec![1, 2, 1, 2, 1]);
+
+        assert_eq!(balance_by_duration(&[secs(1), secs(2)], 3), vec![2, 1]);
+    }
+
+    #[test]
+    fn stable_hash_partitions() {
+        // These values must never change: shards may be run by different versions of nextest.
+        assert_eq!(stable_hash("my-crate", "tests::foo"), 8688829064084185251);
+        assert_eq!(
+            stable_hash("my-crate::bin/my-bin", "tests::foo"),
+            3347478223977427022
+        );
+        assert_ne!(stable_hash("a", "bc"), stable_hash("ab", "c"));
+
+        // A test's shard only depends on its binary ID and name, not on the other tests.
+        let builder = PartitionerBuilder::Hash {
+            shard: 1,
+            total_shards: 3,
+        };
+        let names: Vec<_> = (0..100).map(|i| format!("tests::test_{}", i)).collect();
+        let mut partitioner = builder.build("my-crate");
+        let matches: Vec<_> = names
+            .iter()
+            .map(|name| partitioner.test_matches(name))
+            .collect();
+        let mut partitioner = builder.build("my-crate");
+        let reversed: Vec<_> = names
+            .iter()
+            .rev()
+            .map(|name| partitioner.test_matches(name))
+            .collect();
+        assert_eq!(matches, reversed.into_iter().rev().collect::<Vec<_>>());
+        assert!(matches.iter().any(|&m| m) && !matches.iter().all(|&m| m));
+    }
 }
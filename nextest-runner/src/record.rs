@@ -0,0 +1,1028 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Recording test runs, and replaying them later.
+//!
+//! A [`RunRecorder`] is a [`Reporter`] that writes every event in a run out to a file, one JSON
+//! object per line. The first line is a header with everything required to reconstruct the test
+//! list: the Cargo metadata for the workspace, and a summary of the tests that were listed.
+//!
+//! A [`RecordedRun`] reads such a file back in. Its events can then be replayed through any
+//! reporter, for example to regenerate a JUnit report or to re-render the human-readable output,
+//! without building or running any tests.
+//!
+//! Test output is recorded as UTF-8, with any invalid sequences replaced by `U+FFFD`.
+
+use crate::{
+    errors::{ReadRecordError, ReplayError, WriteEventError},
+    reporter::{CancelReason, Reporter, TestEvent, TestEventKind},
+    runner::{ExecuteStatus, ExecutionResult, ExecutionStatuses, RunStats, RunningTest},
+    script::{ScriptKind, ScriptStatus},
+    test_list::{RustTestSuite, TestInstance, TestList},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use guppy::graph::PackageGraph;
+use nextest_metadata::{MismatchReason, RunSummary, TestListSummary, TestOutcome, TestRunSummary};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+use uuid::Uuid;
+
+/// A [`Reporter`] that records every event in a test run to a writer.
+///
+/// The recording can be read back in with [`RecordedRun::read`].
+#[derive(Debug)]
+pub struct RunRecorder<W> {
+    cargo_metadata: String,
+    writer: W,
+}
+
+impl RunRecorder<BufWriter<File>> {
+    /// Creates a new recorder that writes to the given path, replacing any file that's already
+    /// there.
+    ///
+    /// `cargo_metadata` is the output of `cargo metadata` for the workspace the tests are in.
+    pub fn create(
+        path: impl AsRef<Utf8Path>,
+        cargo_metadata: impl Into<String>,
+    ) -> Result<Self, WriteEventError> {
+        let path = path.as_ref();
+        let file = File::create(path).map_err(|error| WriteEventError::Fs {
+            file: path.to_path_buf(),
+            error,
+        })?;
+        Ok(Self::new(cargo_metadata, BufWriter::new(file)))
+    }
+}
+
+impl<W: Write + Send> RunRecorder<W> {
+    /// Creates a new recorder that writes to the given writer.
+    ///
+    /// `cargo_metadata` is the output of `cargo metadata` for the workspace the tests are in.
+    pub fn new(cargo_metadata: impl Into<String>, writer: W) -> Self {
+        Self {
+            cargo_metadata: cargo_metadata.into(),
+            writer,
+        }
+    }
+
+    fn write_line(&mut self, value: &impl Serialize) -> Result<(), WriteEventError> {
+        serde_json::to_writer(&mut self.writer, value).map_err(WriteEventError::Json)?;
+        writeln!(self.writer).map_err(WriteEventError::Io)
+    }
+}
+
+impl<'a, W: Write + Send> Reporter<'a> for RunRecorder<W> {
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        if let TestEventKind::RunStarted { test_list, .. } = &event.kind {
+            let header = RecordHeader {
+                format_version: RecordHeader::FORMAT_VERSION,
+                run_id: event.run_id,
+                cargo_metadata: self.cargo_metadata.clone(),
+                test_list: test_list.to_summary(),
+            };
+            self.write_line(&header)?;
+        }
+        self.write_line(&RecordedEvent::new(event))?;
+        // Flush after every event so that as much as possible is recorded even if nextest is
+        // killed partway through the run.
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+/// A test run read back in from a file written by [`RunRecorder`].
+#[derive(Clone, Debug)]
+pub struct RecordedRun {
+    header: RecordHeader,
+    events: Vec<RecordedEvent>,
+}
+
+impl RecordedRun {
+    /// Reads a recorded run from the given path.
+    pub fn read(path: impl AsRef<Utf8Path>) -> Result<Self, ReadRecordError> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(|error| ReadRecordError::Io {
+            path: path.to_path_buf(),
+            error,
+        })?;
+        Self::from_reader(path, BufReader::new(file))
+    }
+
+    fn from_reader(path: &Utf8Path, reader: impl BufRead) -> Result<Self, ReadRecordError> {
+        let io_error = |error: io::Error| ReadRecordError::Io {
+            path: path.to_path_buf(),
+            error,
+        };
+        let parse_error = |line: usize, error: serde_json::Error| ReadRecordError::Parse {
+            path: path.to_path_buf(),
+            line,
+            error,
+        };
+
+        let mut lines = reader.lines();
+        let header_line = match lines.next() {
+            Some(line) => line.map_err(io_error)?,
+            None => {
+                return Err(ReadRecordError::MissingHeader {
+                    path: path.to_path_buf(),
+                })
+            }
+        };
+        // Check the version before anything else, so that recordings made by newer versions of
+        // nextest produce a useful error rather than a parse failure.
+        let probe: VersionProbe =
+            serde_json::from_str(&header_line).map_err(|error| parse_error(1, error))?;
+        if probe.format_version > RecordHeader::FORMAT_VERSION {
+            return Err(ReadRecordError::UnsupportedVersion {
+                path: path.to_path_buf(),
+                format_version: probe.format_version,
+                max_supported: RecordHeader::FORMAT_VERSION,
+            });
+        }
+        let header: RecordHeader =
+            serde_json::from_str(&header_line).map_err(|error| parse_error(1, error))?;
+
+        let events = lines
+            .enumerate()
+            .filter_map(|(idx, line)| {
+                let line = match line {
+                    Ok(line) => line,
+                    Err(error) => return Some(Err(io_error(error))),
+                };
+                if line.trim().is_empty() {
+                    return None;
+                }
+                // Line numbers are 1-indexed, and the header is on line 1.
+                Some(serde_json::from_str(&line).map_err(|error| parse_error(idx + 2, error)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Self { header, events })
+    }
+
+    /// Returns the unique ID of the run that was recorded.
+    pub fn run_id(&self) -> Uuid {
+        self.header.run_id
+    }
+
+    /// Returns the output of `cargo metadata` for the workspace the run was recorded in.
+    ///
+    /// This can be used to construct the [`PackageGraph`] passed into [`Self::test_list`].
+    pub fn cargo_metadata(&self) -> &str {
+        &self.header.cargo_metadata
+    }
+
+    /// Returns the number of events in this recording.
+    pub fn event_count(&self) -> usize {
+        self.events.len()
+    }
+
+    /// Returns statistics for the recorded run, or `None` if the recording ends before the run
+    /// finished.
+    pub fn run_stats(&self) -> Option<RunStats> {
+        self.events
+            .iter()
+            .rev()
+            .find_map(|event| match &event.kind {
+                RecordedEventKind::RunFinished { run_stats, .. } => Some(*run_stats),
+                _ => None,
+            })
+    }
+
+    /// Constructs a serializable summary of the recorded run.
+    ///
+    /// If the recording ends before the run finished, only the tests that had finished by then are
+    /// included, and the elapsed time and statistics are left empty.
+    pub fn to_summary(&self) -> RunSummary {
+        let mut summary = RunSummary::default();
+        for event in &self.events {
+            match &event.kind {
+                RecordedEventKind::TestFinished { test, run_statuses } => {
+                    // Recordings with no statuses for a test are rejected when replaying them.
+                    if run_statuses.is_empty() {
+                        continue;
+                    }
+                    let run_statuses = ExecutionStatuses::new(
+                        run_statuses
+                            .iter()
+                            .map(RecordedExecuteStatus::to_status)
+                            .collect(),
+                    );
+                    summary
+                        .tests
+                        .push(run_statuses.summary_for(&test.binary_id, &test.test_name));
+                }
+                RecordedEventKind::TestSkipped { test, reason, .. } => {
+                    summary.tests.push(TestRunSummary {
+                        binary_id: test.binary_id.clone(),
+                        test_name: test.test_name.clone(),
+                        outcome: TestOutcome::Skipped,
+                        skip_reason: Some(*reason),
+                        attempts: vec![],
+                    });
+                }
+                RecordedEventKind::TestCanceled { test } => {
+                    summary.tests.push(TestRunSummary {
+                        binary_id: test.binary_id.clone(),
+                        test_name: test.test_name.clone(),
+                        outcome: TestOutcome::Canceled,
+                        skip_reason: None,
+                        attempts: vec![],
+                    });
+                }
+                RecordedEventKind::RunFinished {
+                    elapsed, run_stats, ..
+                } => {
+                    summary.elapsed_secs = elapsed.as_secs_f64();
+                    summary.run_stats = run_stats.to_summary();
+                }
+                _ => {}
+            }
+        }
+        // This is a stable sort, so with --repeat, runs of a test stay in the order they finished
+        // in.
+        summary
+            .tests
+            .sort_by(|a, b| (&a.binary_id, &a.test_name).cmp(&(&b.binary_id, &b.test_name)));
+        summary
+    }
+
+    /// Reconstructs the list of tests that the recorded run was started with.
+    ///
+    /// `graph` must be constructed from [`Self::cargo_metadata`].
+    pub fn test_list<'g>(&self, graph: &'g PackageGraph) -> Result<TestList<'g>, ReplayError> {
+        TestList::from_summary(graph, &self.header.test_list).map_err(ReplayError::PackageGraph)
+    }
+
+    /// Replays the recorded events through the given reporters, in the order they were recorded.
+    ///
+    /// `test_list` must be obtained from [`Self::test_list`]. Events are sent to reporters in the
+    /// order they're passed in.
+    pub fn replay<'a>(
+        &self,
+        test_list: &'a TestList<'a>,
+        reporters: &mut [Box<dyn Reporter<'a> + 'a>],
+    ) -> Result<(), ReplayError> {
+        let suites: HashMap<_, _> = test_list
+            .iter()
+            .map(|(binary, suite)| (suite.binary_id.as_str(), (binary, suite)))
+            .collect();
+        let resolver = TestResolver { suites };
+
+        for event in &self.events {
+            let event = TestEvent {
+                run_id: event.run_id,
+                timestamp: event.timestamp,
+                since_start: event.since_start,
+                kind: event.kind.to_kind(test_list, &resolver)?,
+            };
+            for reporter in reporters.iter_mut() {
+                reporter
+                    .report_event(&event)
+                    .map_err(ReplayError::WriteEvent)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Looks up tests in a test list by binary ID and name.
+struct TestResolver<'a> {
+    suites: HashMap<&'a str, (&'a Utf8Path, &'a RustTestSuite<'a>)>,
+}
+
+impl<'a> TestResolver<'a> {
+    fn resolve(&self, test: &RecordedTest) -> Result<TestInstance<'a>, ReplayError> {
+        let unknown_test = || ReplayError::UnknownTest {
+            binary_id: test.binary_id.clone(),
+            test_name: test.test_name.clone(),
+        };
+        let (binary, suite) = self
+            .suites
+            .get(test.binary_id.as_str())
+            .ok_or_else(unknown_test)?;
+        let (name, test_info) = suite
+            .testcases
+            .get_key_value(&test.test_name)
+            .ok_or_else(unknown_test)?;
+        Ok(TestInstance::new(name, *binary, suite, test_info))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "kebab-case")]
+struct VersionProbe {
+    format_version: u32,
+}
+
+/// The first line of a recording.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordHeader {
+    format_version: u32,
+    run_id: Uuid,
+    cargo_metadata: String,
+    test_list: TestListSummary,
+}
+
+impl RecordHeader {
+    /// The current version of the recording format.
+    const FORMAT_VERSION: u32 = 1;
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedEvent {
+    run_id: Uuid,
+    #[serde(with = "humantime_serde")]
+    timestamp: SystemTime,
+    #[serde(with = "humantime_serde")]
+    since_start: Duration,
+    kind: RecordedEventKind,
+}
+
+impl RecordedEvent {
+    fn new(event: &TestEvent<'_>) -> Self {
+        Self {
+            run_id: event.run_id,
+            timestamp: event.timestamp,
+            since_start: event.since_start,
+            kind: RecordedEventKind::new(&event.kind),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+enum RecordedEventKind {
+    #[serde(rename_all = "kebab-case")]
+    RunStarted {
+        shuffle_seed: Option<u64>,
+        repeat: usize,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestStarted { test: RecordedTest },
+    #[serde(rename_all = "kebab-case")]
+    TestSlow {
+        test: RecordedTest,
+        #[serde(with = "humantime_serde")]
+        elapsed: Duration,
+        will_terminate: bool,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestSilent {
+        test: RecordedTest,
+        #[serde(with = "humantime_serde")]
+        silent_for: Duration,
+        #[serde(with = "humantime_serde")]
+        elapsed: Duration,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestPaused { test: RecordedTest, pid: u32 },
+    #[serde(rename_all = "kebab-case")]
+    TestRetry {
+        test: RecordedTest,
+        run_status: RecordedExecuteStatus,
+        #[serde(with = "humantime_serde")]
+        delay_before_next_attempt: Duration,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestFinished {
+        test: RecordedTest,
+        run_statuses: Vec<RecordedExecuteStatus>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestRecorded {
+        test: RecordedTest,
+        run_status: RecordedExecuteStatus,
+        recording_dir: Utf8PathBuf,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestSkipped {
+        test: RecordedTest,
+        reason: MismatchReason,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        exclude_reason: Option<String>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestCanceled { test: RecordedTest },
+    #[serde(rename_all = "kebab-case")]
+    ScriptFinished {
+        script_name: String,
+        kind: ScriptKind,
+        status: RecordedScriptStatus,
+    },
+    #[serde(rename_all = "kebab-case")]
+    RunInfo {
+        running_tests: Vec<RecordedRunningTest>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    RunBeginCancel {
+        running: usize,
+        // Not present in recordings made by older versions of nextest.
+        #[serde(default)]
+        running_tests: Vec<RecordedRunningTest>,
+        reason: CancelReason,
+    },
+    #[serde(rename_all = "kebab-case")]
+    RunFinished {
+        #[serde(with = "humantime_serde")]
+        start_time: SystemTime,
+        #[serde(with = "humantime_serde")]
+        elapsed: Duration,
+        run_stats: RunStats,
+    },
+}
+
+impl RecordedEventKind {
+    fn new(kind: &TestEventKind<'_>) -> Self {
+        match kind {
+            TestEventKind::RunStarted {
+                shuffle_seed,
+                repeat,
+                ..
+            } => RecordedEventKind::RunStarted {
+                shuffle_seed: *shuffle_seed,
+                repeat: *repeat,
+            },
+            TestEventKind::TestStarted { test_instance } => RecordedEventKind::TestStarted {
+                test: RecordedTest::new(test_instance),
+            },
+            TestEventKind::TestSlow {
+                test_instance,
+                elapsed,
+                will_terminate,
+            } => RecordedEventKind::TestSlow {
+                test: RecordedTest::new(test_instance),
+                elapsed: *elapsed,
+                will_terminate: *will_terminate,
+            },
+            TestEventKind::TestSilent {
+                test_instance,
+                silent_for,
+                elapsed,
+            } => RecordedEventKind::TestSilent {
+                test: RecordedTest::new(test_instance),
+                silent_for: *silent_for,
+                elapsed: *elapsed,
+            },
+            TestEventKind::TestPaused { test_instance, pid } => RecordedEventKind::TestPaused {
+                test: RecordedTest::new(test_instance),
+                pid: *pid,
+            },
+            TestEventKind::TestRetry {
+                test_instance,
+                run_status,
+                delay_before_next_attempt,
+            } => RecordedEventKind::TestRetry {
+                test: RecordedTest::new(test_instance),
+                run_status: RecordedExecuteStatus::new(run_status),
+                delay_before_next_attempt: *delay_before_next_attempt,
+            },
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => RecordedEventKind::TestFinished {
+                test: RecordedTest::new(test_instance),
+                run_statuses: run_statuses
+                    .iter()
+                    .map(RecordedExecuteStatus::new)
+                    .collect(),
+            },
+            TestEventKind::TestRecorded {
+                test_instance,
+                run_status,
+                recording_dir,
+            } => RecordedEventKind::TestRecorded {
+                test: RecordedTest::new(test_instance),
+                run_status: RecordedExecuteStatus::new(run_status),
+                recording_dir: recording_dir.clone(),
+            },
+            TestEventKind::TestSkipped {
+                test_instance,
+                reason,
+                exclude_reason,
+            } => RecordedEventKind::TestSkipped {
+                test: RecordedTest::new(test_instance),
+                reason: *reason,
+                exclude_reason: exclude_reason.clone(),
+            },
+            TestEventKind::TestCanceled { test_instance } => RecordedEventKind::TestCanceled {
+                test: RecordedTest::new(test_instance),
+            },
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => RecordedEventKind::ScriptFinished {
+                script_name: script_name.clone(),
+                kind: *kind,
+                status: RecordedScriptStatus::new(status),
+            },
+            TestEventKind::RunInfo { running_tests } => RecordedEventKind::RunInfo {
+                running_tests: running_tests.iter().map(RecordedRunningTest::new).collect(),
+            },
+            TestEventKind::RunBeginCancel {
+                running,
+                running_tests,
+                reason,
+            } => RecordedEventKind::RunBeginCancel {
+                running: *running,
+                running_tests: running_tests.iter().map(RecordedRunningTest::new).collect(),
+                reason: *reason,
+            },
+            TestEventKind::RunFinished {
+                start_time,
+                elapsed,
+                run_stats,
+            } => RecordedEventKind::RunFinished {
+                start_time: *start_time,
+                elapsed: *elapsed,
+                run_stats: *run_stats,
+            },
+        }
+    }
+
+    fn to_kind<'a>(
+        &self,
+        test_list: &'a TestList<'a>,
+        resolver: &TestResolver<'a>,
+    ) -> Result<TestEventKind<'a>, ReplayError> {
+        let kind = match self {
+            RecordedEventKind::RunStarted {
+                shuffle_seed,
+                repeat,
+            } => TestEventKind::RunStarted {
+                test_list,
+                shuffle_seed: *shuffle_seed,
+                repeat: *repeat,
+            },
+            RecordedEventKind::TestStarted { test } => TestEventKind::TestStarted {
+                test_instance: resolver.resolve(test)?,
+            },
+            RecordedEventKind::TestSlow {
+                test,
+                elapsed,
+                will_terminate,
+            } => TestEventKind::TestSlow {
+                test_instance: resolver.resolve(test)?,
+                elapsed: *elapsed,
+                will_terminate: *will_terminate,
+            },
+            RecordedEventKind::TestSilent {
+                test,
+                silent_for,
+                elapsed,
+            } => TestEventKind::TestSilent {
+                test_instance: resolver.resolve(test)?,
+                silent_for: *silent_for,
+                elapsed: *elapsed,
+            },
+            RecordedEventKind::TestPaused { test, pid } => TestEventKind::TestPaused {
+                test_instance: resolver.resolve(test)?,
+                pid: *pid,
+            },
+            RecordedEventKind::TestRetry {
+                test,
+                run_status,
+                delay_before_next_attempt,
+            } => TestEventKind::TestRetry {
+                test_instance: resolver.resolve(test)?,
+                run_status: run_status.to_status(),
+                delay_before_next_attempt: *delay_before_next_attempt,
+            },
+            RecordedEventKind::TestFinished { test, run_statuses } => {
+                if run_statuses.is_empty() {
+                    return Err(ReplayError::NoRunStatuses {
+                        binary_id: test.binary_id.clone(),
+                        test_name: test.test_name.clone(),
+                    });
+                }
+                TestEventKind::TestFinished {
+                    test_instance: resolver.resolve(test)?,
+                    run_statuses: ExecutionStatuses::new(
+                        run_statuses
+                            .iter()
+                            .map(RecordedExecuteStatus::to_status)
+                            .collect(),
+                    ),
+                }
+            }
+            RecordedEventKind::TestRecorded {
+                test,
+                run_status,
+                recording_dir,
+            } => TestEventKind::TestRecorded {
+                test_instance: resolver.resolve(test)?,
+                run_status: run_status.to_status(),
+                recording_dir: recording_dir.clone(),
+            },
+            RecordedEventKind::TestSkipped {
+                test,
+                reason,
+                exclude_reason,
+            } => TestEventKind::TestSkipped {
+                test_instance: resolver.resolve(test)?,
+                reason: *reason,
+                exclude_reason: exclude_reason.clone(),
+            },
+            RecordedEventKind::TestCanceled { test } => TestEventKind::TestCanceled {
+                test_instance: resolver.resolve(test)?,
+            },
+            RecordedEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => TestEventKind::ScriptFinished {
+                script_name: script_name.clone(),
+                kind: *kind,
+                status: status.to_status(),
+            },
+            RecordedEventKind::RunInfo { running_tests } => TestEventKind::RunInfo {
+                running_tests: running_tests
+                    .iter()
+                    .map(|running_test| running_test.to_running_test(resolver))
+                    .collect::<Result<_, _>>()?,
+            },
+            RecordedEventKind::RunBeginCancel {
+                running,
+                running_tests,
+                reason,
+            } => TestEventKind::RunBeginCancel {
+                running: *running,
+                running_tests: running_tests
+                    .iter()
+                    .map(|running_test| running_test.to_running_test(resolver))
+                    .collect::<Result<_, _>>()?,
+                reason: *reason,
+            },
+            RecordedEventKind::RunFinished {
+                start_time,
+                elapsed,
+                run_stats,
+            } => TestEventKind::RunFinished {
+                start_time: *start_time,
+                elapsed: *elapsed,
+                run_stats: *run_stats,
+            },
+        };
+        Ok(kind)
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedTest {
+    binary_id: String,
+    test_name: String,
+}
+
+impl RecordedTest {
+    fn new(test_instance: &TestInstance<'_>) -> Self {
+        Self {
+            binary_id: test_instance.bin_info.binary_id.clone(),
+            test_name: test_instance.name.to_owned(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedRunningTest {
+    test: RecordedTest,
+    attempt: usize,
+    #[serde(with = "humantime_serde")]
+    elapsed: Duration,
+}
+
+impl RecordedRunningTest {
+    fn new(running_test: &RunningTest<'_>) -> Self {
+        Self {
+            test: RecordedTest::new(&running_test.test_instance),
+            attempt: running_test.attempt,
+            elapsed: running_test.elapsed,
+        }
+    }
+
+    fn to_running_test<'a>(
+        &self,
+        resolver: &TestResolver<'a>,
+    ) -> Result<RunningTest<'a>, ReplayError> {
+        Ok(RunningTest {
+            test_instance: resolver.resolve(&self.test)?,
+            attempt: self.attempt,
+            elapsed: self.elapsed,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedExecuteStatus {
+    attempt: usize,
+    total_attempts: usize,
+    result: ExecutionResult,
+    #[serde(with = "humantime_serde")]
+    start_time: SystemTime,
+    #[serde(with = "humantime_serde")]
+    time_taken: Duration,
+    stdout: String,
+    stderr: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    core_dump: Option<Utf8PathBuf>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    output_dir: Option<Utf8PathBuf>,
+}
+
+impl RecordedExecuteStatus {
+    fn new(status: &ExecuteStatus) -> Self {
+        Self {
+            attempt: status.attempt,
+            total_attempts: status.total_attempts,
+            result: status.result,
+            start_time: status.start_time,
+            time_taken: status.time_taken,
+            stdout: String::from_utf8_lossy(status.stdout()).into_owned(),
+            stderr: String::from_utf8_lossy(status.stderr()).into_owned(),
+            core_dump: status.core_dump.clone(),
+            output_dir: status.output_dir.clone(),
+        }
+    }
+
+    fn to_status(&self) -> ExecuteStatus {
+        ExecuteStatus {
+            attempt: self.attempt,
+            total_attempts: self.total_attempts,
+            stdout_stderr: Arc::new((
+                self.stdout.clone().into_bytes(),
+                self.stderr.clone().into_bytes(),
+            )),
+            result: self.result,
+            start_time: self.start_time,
+            time_taken: self.time_taken,
+            core_dump: self.core_dump.clone(),
+            output_dir: self.output_dir.clone(),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct RecordedScriptStatus {
+    result: ExecutionResult,
+    #[serde(with = "humantime_serde")]
+    start_time: SystemTime,
+    #[serde(with = "humantime_serde")]
+    time_taken: Duration,
+    stdout: String,
+    stderr: String,
+}
+
+impl RecordedScriptStatus {
+    fn new(status: &ScriptStatus) -> Self {
+        Self {
+            result: status.result,
+            start_time: status.start_time,
+            time_taken: status.time_taken,
+            stdout: String::from_utf8_lossy(status.stdout()).into_owned(),
+            stderr: String::from_utf8_lossy(status.stderr()).into_owned(),
+        }
+    }
+
+    fn to_status(&self) -> ScriptStatus {
+        ScriptStatus {
+            stdout_stderr: Arc::new((
+                self.stdout.clone().into_bytes(),
+                self.stderr.clone().into_bytes(),
+            )),
+            result: self.result,
+            start_time: self.start_time,
+            time_taken: self.time_taken,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        runner::ExecutionDescription,
+        test_list::test_helpers::{make_single_binary_test_list, FIXTURE_JSON},
+    };
+    use guppy::CargoMetadata;
+    use indoc::indoc;
+    use std::{
+        io::Cursor,
+        sync::Mutex,
+        time::{Duration, UNIX_EPOCH},
+    };
+
+    fn make_test_list() -> TestList<'static> {
+        make_single_binary_test_list("tests::flaky: test\n", "tests::ignored: test\n")
+    }
+
+    fn make_status(attempt: usize, result: ExecutionResult, stdout: &[u8]) -> ExecuteStatus {
+        ExecuteStatus {
+            attempt,
+            total_attempts: 2,
+            stdout_stderr: Arc::new((stdout.to_vec(), b"stderr".to_vec())),
+            result,
+            start_time: UNIX_EPOCH + Duration::from_secs(attempt as u64),
+            time_taken: Duration::from_millis(250),
+            core_dump: None,
+            output_dir: None,
+        }
+    }
+
+    /// Describes the events it sees.
+    struct DescribingReporter(Arc<Mutex<Vec<String>>>);
+
+    impl<'a> Reporter<'a> for DescribingReporter {
+        fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+            let description = match &event.kind {
+                TestEventKind::RunStarted { test_list, .. } => {
+                    format!("run-started {} tests", test_list.test_count())
+                }
+                TestEventKind::TestFinished {
+                    test_instance,
+                    run_statuses,
+                } => {
+                    let description = match run_statuses.describe() {
+                        ExecutionDescription::Success { .. } => "passed",
+                        ExecutionDescription::Flaky { .. } => "flaky",
+                        ExecutionDescription::Failure { .. } => "failed",
+                    };
+                    let first_stdout =
+                        String::from_utf8_lossy(run_statuses.iter().next().unwrap().stdout())
+                            .into_owned();
+                    format!(
+                        "test-finished {} {} {} {:?}",
+                        test_instance.name,
+                        description,
+                        run_statuses.len(),
+                        first_stdout
+                    )
+                }
+                TestEventKind::TestSkipped {
+                    test_instance,
+                    reason,
+                    ..
+                } => format!("test-skipped {} {:?}", test_instance.name, reason),
+                TestEventKind::RunFinished { run_stats, .. } => {
+                    format!("run-finished {} passed", run_stats.passed)
+                }
+                other => panic!("unexpected event: {:?}", other),
+            };
+            self.0
+                .lock()
+                .unwrap()
+                .push(format!("{:?} {}", event.since_start, description));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        let flaky = tests.next().expect("flaky test exists");
+        let ignored = tests.next().expect("ignored test exists");
+        assert_eq!(flaky.name, "tests::flaky");
+        assert_eq!(ignored.name, "tests::ignored");
+
+        let run_id = Uuid::new_v4();
+        let event = |since_start_ms: u64, kind| TestEvent {
+            run_id,
+            timestamp: UNIX_EPOCH + Duration::from_millis(since_start_ms),
+            since_start: Duration::from_millis(since_start_ms),
+            kind,
+        };
+        let events = vec![
+            event(
+                0,
+                TestEventKind::RunStarted {
+                    test_list: &test_list,
+                    shuffle_seed: None,
+                    repeat: 1,
+                },
+            ),
+            event(
+                10,
+                TestEventKind::TestFinished {
+                    test_instance: flaky,
+                    run_statuses: ExecutionStatuses::new(vec![
+                        // Invalid UTF-8 is replaced when recorded.
+                        make_status(1, ExecutionResult::Fail, b"bad \xff output"),
+                        make_status(2, ExecutionResult::Pass, b"good output"),
+                    ]),
+                },
+            ),
+            event(
+                20,
+                TestEventKind::TestSkipped {
+                    test_instance: ignored,
+                    reason: MismatchReason::Ignored,
+                    exclude_reason: None,
+                },
+            ),
+            event(
+                30,
+                TestEventKind::RunFinished {
+                    start_time: UNIX_EPOCH,
+                    elapsed: Duration::from_millis(30),
+                    run_stats: RunStats {
+                        initial_run_count: 1,
+                        final_run_count: 1,
+                        passed: 1,
+                        flaky: 1,
+                        skipped: 1,
+                        ..RunStats::default()
+                    },
+                },
+            ),
+        ];
+
+        let mut recorder = RunRecorder::new(FIXTURE_JSON, Vec::new());
+        for event in &events {
+            recorder.report_event(event).expect("recording succeeds");
+        }
+        let recording = recorder.writer;
+        assert_eq!(
+            recording.iter().filter(|&&b| b == b'\n').count(),
+            5,
+            "one line for the header and one for each event"
+        );
+
+        let recorded = RecordedRun::from_reader(Utf8Path::new("recording"), Cursor::new(recording))
+            .expect("recording is valid");
+        assert_eq!(recorded.run_id(), run_id);
+        assert_eq!(recorded.event_count(), 4);
+        assert_eq!(recorded.run_stats().expect("run finished").flaky, 1);
+
+        let summary = recorded.to_summary();
+        assert_eq!(summary.run_stats.flaky, 1);
+        let outcomes: Vec<_> = summary
+            .tests
+            .iter()
+            .map(|test| (test.test_name.as_str(), test.outcome, test.attempts.len()))
+            .collect();
+        assert_eq!(
+            outcomes,
+            vec![
+                ("tests::flaky", TestOutcome::Flaky, 2),
+                ("tests::ignored", TestOutcome::Skipped, 0),
+            ]
+        );
+
+        let replayed_graph = CargoMetadata::parse_json(recorded.cargo_metadata())
+            .expect("recorded metadata is valid JSON")
+            .build_graph()
+            .expect("recorded metadata is valid PackageGraph");
+        let replayed_list = recorded
+            .test_list(&replayed_graph)
+            .expect("test list is reconstructed");
+        assert_eq!(replayed_list.to_summary(), test_list.to_summary());
+
+        let descriptions = Arc::new(Mutex::new(vec![]));
+        let mut reporters: Vec<Box<dyn Reporter<'_> + '_>> =
+            vec![Box::new(DescribingReporter(descriptions.clone()))];
+        recorded
+            .replay(&replayed_list, &mut reporters)
+            .expect("replay succeeds");
+        assert_eq!(
+            *descriptions.lock().unwrap(),
+            vec![
+                "0ns run-started 2 tests",
+                "10ms test-finished tests::flaky flaky 2 \"bad \u{fffd} output\"",
+                "20ms test-skipped tests::ignored Ignored",
+                "30ms run-finished 1 passed",
+            ]
+        );
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let recording = indoc! {r#"
+            {"format-version":2,"something-new":true}
+        "#};
+        let error = RecordedRun::from_reader(Utf8Path::new("recording"), Cursor::new(recording))
+            .expect_err("newer format versions are rejected");
+        assert!(
+            matches!(
+                error,
+                ReadRecordError::UnsupportedVersion {
+                    format_version: 2,
+                    max_supported: 1,
+                    ..
+                }
+            ),
+            "unexpected error: {:?}",
+            error
+        );
+    }
+}
@@ -0,0 +1,354 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Running tests on another machine over SSH.
+//!
+//! Before tests are listed, test binaries are copied to a directory on the remote host. Tests are
+//! then listed and run by invoking `ssh`, which passes through the output and exit status of the
+//! remote process. This means that remote tests are reported in the same way as local ones.
+//!
+//! The remote host must have a POSIX shell, `mktemp` and `tar` available. `ssh` is run in batch
+//! mode, so authentication must not require a password prompt.
+
+use crate::{config::WrapperCommand, errors::SshRunnerError, test_list::RustTestArtifact};
+use camino::{Utf8Path, Utf8PathBuf};
+use duct::{cmd, Expression};
+use nextest_metadata::BuildPlatform;
+use std::{
+    collections::HashMap,
+    io,
+    process::{Command, Stdio},
+};
+
+/// Builds an [`SshRunner`].
+#[derive(Clone, Debug)]
+pub struct SshRunnerBuilder {
+    host: String,
+    remote_dir: Option<String>,
+    remote_workspace: Option<String>,
+}
+
+impl SshRunnerBuilder {
+    /// Creates a new builder for running tests on the given host.
+    ///
+    /// The host is passed through to `ssh`, so it can be of the form `[user@]hostname`, or the name
+    /// of a host in the SSH config.
+    pub fn new(host: impl Into<String>) -> Self {
+        Self {
+            host: host.into(),
+            remote_dir: None,
+            remote_workspace: None,
+        }
+    }
+
+    /// Sets the directory on the remote host to copy test binaries to.
+    ///
+    /// The directory is created if it doesn't exist, and is left in place after the run. If this
+    /// isn't set, binaries are copied to a temporary directory that is removed once the
+    /// `SshRunner` is dropped.
+    pub fn set_remote_dir(&mut self, remote_dir: impl Into<String>) -> &mut Self {
+        self.remote_dir = Some(remote_dir.into());
+        self
+    }
+
+    /// Sets the path to the workspace on the remote host.
+    ///
+    /// Tests are run within the directories of their packages in this workspace, as they would be
+    /// locally. If this isn't set, tests are run within the directory binaries are copied to.
+    pub fn set_remote_workspace(&mut self, remote_workspace: impl Into<String>) -> &mut Self {
+        self.remote_workspace = Some(remote_workspace.into());
+        self
+    }
+
+    /// Creates the remote directory and copies the given test binaries to it.
+    ///
+    /// `workspace_root` is the location of the workspace on this machine, which is used to map
+    /// working directories to the remote workspace.
+    pub fn build(
+        self,
+        test_artifacts: &[RustTestArtifact<'_>],
+        workspace_root: &Utf8Path,
+    ) -> Result<SshRunner, SshRunnerError> {
+        let host = self.host;
+        let (remote_dir, is_temp) = match self.remote_dir {
+            Some(remote_dir) => {
+                ssh_expression(&host, &format!("mkdir -p {}", shell_quote(&remote_dir)))
+                    .run()
+                    .map_err(|error| SshRunnerError::CreateDir {
+                        host: host.clone(),
+                        error,
+                    })?;
+                (remote_dir, false)
+            }
+            None => {
+                let remote_dir =
+                    ssh_expression(&host, "mktemp -d \"${TMPDIR:-/tmp}/nextest-remote.XXXXXX\"")
+                        .read()
+                        .map_err(|error| SshRunnerError::CreateDir {
+                            host: host.clone(),
+                            error,
+                        })?;
+                (remote_dir.trim().to_owned(), true)
+            }
+        };
+
+        // Construct the runner before copying binaries, so that a temporary directory is cleaned
+        // up even if copying fails.
+        let mut runner = SshRunner {
+            host,
+            remote_dir,
+            is_temp,
+            remote_workspace: self
+                .remote_workspace
+                .map(|remote_workspace| (workspace_root.to_path_buf(), remote_workspace)),
+            binaries: HashMap::new(),
+        };
+
+        // Binary IDs are unique, so name each binary after its ID. Binaries built for the host
+        // platform are run locally, so they aren't copied.
+        let binaries: Vec<_> = test_artifacts
+            .iter()
+            .filter(|artifact| artifact.build_platform != BuildPlatform::Host)
+            .map(|artifact| {
+                let file_name = artifact
+                    .binary_id
+                    .replace(|c: char| c == ':' || c == '/', "_");
+                (artifact.binary_path.as_path(), file_name)
+            })
+            .collect();
+        runner
+            .copy_binaries(&binaries)
+            .map_err(|error| SshRunnerError::CopyBinaries {
+                host: runner.host.clone(),
+                error,
+            })?;
+        for (binary_path, file_name) in binaries {
+            let remote_path = format!("{}/{}", runner.remote_dir, file_name);
+            runner
+                .binaries
+                .insert(binary_path.to_path_buf(), remote_path);
+        }
+
+        Ok(runner)
+    }
+}
+
+/// Runs test binaries on a remote host over SSH.
+///
+/// An `SshRunner` can be passed into
+/// [`TestList::new_remote`](crate::test_list::TestList::new_remote) and
+/// [`TestRunnerBuilder::set_remote`](crate::runner::TestRunnerBuilder::set_remote). Created with
+/// [`SshRunnerBuilder`].
+#[derive(Debug)]
+pub struct SshRunner {
+    host: String,
+    remote_dir: String,
+    // Whether remote_dir should be removed on drop.
+    is_temp: bool,
+    // The local and remote workspace roots.
+    remote_workspace: Option<(Utf8PathBuf, String)>,
+    // Map of local binary paths to paths on the remote host.
+    binaries: HashMap<Utf8PathBuf, String>,
+}
+
+impl SshRunner {
+    /// Returns the host tests are run on.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// Returns the directory on the remote host that test binaries were copied to.
+    pub fn remote_dir(&self) -> &str {
+        &self.remote_dir
+    }
+
+    /// Returns the directory on the remote host corresponding to the given local working
+    /// directory.
+    pub(crate) fn remote_cwd(&self, cwd: &Utf8Path) -> String {
+        match &self.remote_workspace {
+            Some((local_root, remote_root)) => match cwd.strip_prefix(local_root) {
+                Ok(rel_cwd) if rel_cwd.as_str().is_empty() => remote_root.clone(),
+                Ok(rel_cwd) => format!("{}/{}", remote_root, rel_cwd),
+                Err(_) => remote_root.clone(),
+            },
+            None => self.remote_dir.clone(),
+        }
+    }
+
+    /// Creates an expression that runs the given local binary on the remote host, within
+    /// `remote_cwd` and with the given environment variables set.
+    ///
+    /// If a wrapper is specified, it must be installed on the remote host.
+    pub(crate) fn command<'a>(
+        &self,
+        binary_path: &Utf8Path,
+        wrapper: Option<&WrapperCommand>,
+        args: impl IntoIterator<Item = &'a str>,
+        remote_cwd: &str,
+        env: &[(&str, String)],
+    ) -> Expression {
+        let remote_binary = self
+            .binaries
+            .get(binary_path)
+            .map_or(binary_path.as_str(), String::as_str);
+        let command = match wrapper {
+            Some(wrapper) => {
+                let mut wrapper_args: Vec<_> = wrapper.args().collect();
+                wrapper_args.push(remote_binary);
+                for arg in args {
+                    wrapper_args.push(arg);
+                }
+                remote_command(wrapper.program(), wrapper_args, remote_cwd, env)
+            }
+            None => remote_command(remote_binary, args, remote_cwd, env),
+        };
+        ssh_expression(&self.host, &command)
+    }
+
+    fn copy_binaries(&self, binaries: &[(&Utf8Path, String)]) -> io::Result<()> {
+        // Stream a tarball of the binaries to tar on the remote host, which preserves their
+        // permissions.
+        let mut child = Command::new("ssh")
+            .args(SSH_ARGS)
+            .arg(&self.host)
+            .arg(format!("tar -xf - -C {}", shell_quote(&self.remote_dir)))
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        let stdin = child.stdin.take().expect("stdin was piped");
+        let mut builder = tar::Builder::new(stdin);
+        let res = binaries
+            .iter()
+            .try_for_each(|(binary_path, file_name)| {
+                builder.append_path_with_name(binary_path, file_name)
+            })
+            .and_then(|()| builder.into_inner().map(drop));
+
+        // Wait for ssh to exit even if writing failed, so that the process isn't left behind.
+        let status = child.wait()?;
+        res?;
+        if !status.success() {
+            return Err(io::Error::new(
+                io::ErrorKind::Other,
+                format!("ssh exited with {}", status),
+            ));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for SshRunner {
+    fn drop(&mut self) {
+        if self.is_temp {
+            // Failing to clean up isn't fatal: the directory is on the remote host's temporary
+            // filesystem.
+            let _ = ssh_expression(
+                &self.host,
+                &format!("rm -rf {}", shell_quote(&self.remote_dir)),
+            )
+            .stdout_null()
+            .stderr_null()
+            .unchecked()
+            .run();
+        }
+    }
+}
+
+// ---
+// Helper methods
+// ---
+
+/// Arguments passed to every invocation of `ssh`.
+///
+/// Batch mode means that ssh fails rather than prompting for a password, which would otherwise
+/// hang the run.
+const SSH_ARGS: &[&str] = &["-o", "BatchMode=yes", "--"];
+
+fn ssh_expression(host: &str, remote_command: &str) -> Expression {
+    let args = SSH_ARGS.iter().copied().chain([host, remote_command]);
+    cmd("ssh", args)
+}
+
+/// Returns a shell command that runs `program` with `args` in `cwd`, with `env` set.
+///
+/// `ssh` runs commands through the remote user's shell, so everything is quoted.
+fn remote_command<'a>(
+    program: &str,
+    args: impl IntoIterator<Item = &'a str>,
+    cwd: &str,
+    env: &[(&str, String)],
+) -> String {
+    let mut command = format!("cd {} && exec env", shell_quote(cwd));
+    for (key, value) in env {
+        command.push(' ');
+        command.push_str(&shell_quote(&format!("{}={}", key, value)));
+    }
+    command.push(' ');
+    command.push_str(&shell_quote(program));
+    for arg in args {
+        command.push(' ');
+        command.push_str(&shell_quote(arg));
+    }
+    command
+}
+
+/// Quotes a string for a POSIX shell.
+fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', r"'\''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_remote_command() {
+        assert_eq!(shell_quote(""), "''");
+        assert_eq!(shell_quote("foo bar"), "'foo bar'");
+        assert_eq!(shell_quote("it's"), r"'it'\''s'");
+
+        let env = [
+            ("NEXTEST", "1".to_owned()),
+            ("CARGO_PKG_AUTHORS", "A <a@example.com>:B".to_owned()),
+        ];
+        assert_eq!(
+            remote_command(
+                "/tmp/nextest-remote.abc/my-package__my-test",
+                ["--exact", "tests::it's_a_test", "--nocapture"],
+                "/home/me/my workspace",
+                &env,
+            ),
+            "cd '/home/me/my workspace' && exec env 'NEXTEST=1' \
+             'CARGO_PKG_AUTHORS=A <a@example.com>:B' \
+             '/tmp/nextest-remote.abc/my-package__my-test' '--exact' 'tests::it'\\''s_a_test' \
+             '--nocapture'"
+        );
+    }
+
+    #[test]
+    fn map_remote_cwd() {
+        let mut runner = SshRunner {
+            host: "my-host".to_owned(),
+            remote_dir: "/tmp/nextest-remote.abc".to_owned(),
+            is_temp: false,
+            remote_workspace: None,
+            binaries: HashMap::new(),
+        };
+        assert_eq!(
+            runner.remote_cwd("/home/me/workspace/my-package".into()),
+            "/tmp/nextest-remote.abc"
+        );
+
+        runner.remote_workspace = Some(("/home/me/workspace".into(), "/srv/workspace".to_owned()));
+        assert_eq!(
+            runner.remote_cwd("/home/me/workspace/my-package".into()),
+            "/srv/workspace/my-package"
+        );
+        assert_eq!(
+            runner.remote_cwd("/home/me/workspace".into()),
+            "/srv/workspace"
+        );
+        // Directories outside the workspace are mapped to the workspace root.
+        assert_eq!(runner.remote_cwd("/elsewhere".into()), "/srv/workspace");
+    }
+}
@@ -3,28 +3,58 @@
 
 //! Prints out and aggregates test execution statuses.
 //!
-//! The main structure in this module is [`TestReporter`].
+//! The main structure in this module is [`TestReporter`]. Test events can be consumed by any type
+//! that implements [`Reporter`]: reporters are registered with
+//! [`TestRunnerBuilder::add_reporter`](crate::runner::TestRunnerBuilder::add_reporter), and every
+//! event is sent to each of them in turn.
 
 mod aggregator;
+mod chrome_trace;
+mod html;
+mod json;
+mod libtest;
+mod notify;
+mod otlp;
+mod progress;
+mod tap;
+mod theme;
+mod tui;
+
+pub use json::JsonReporter;
+pub use libtest::LibtestReporter;
+pub use notify::{NotifyFormat, NotifyTemplate};
+pub use tap::TapReporter;
+pub use theme::{ColorTheme, StyleSpec};
 
 use crate::{
-    config::NextestProfile,
+    config::{ColorConfig, NextestProfile, QuarantineConfig, TestColor},
     errors::{StatusLevelParseError, TestOutputDisplayParseError, WriteEventError},
     helpers::write_test_name,
-    reporter::aggregator::EventAggregator,
-    runner::{ExecuteStatus, ExecutionDescription, ExecutionResult, ExecutionStatuses, RunStats},
+    reporter::{aggregator::EventAggregator, progress::ProgressBar, tui::Tui},
+    runner::{
+        ExecuteStatus, ExecutionDescription, ExecutionResult, ExecutionStatuses, RunController,
+        RunStats, RunningTest,
+    },
+    script::{ScriptKind, ScriptStatus},
     test_list::{TestInstance, TestList},
+    timings::TestTimings,
 };
+use camino::Utf8PathBuf;
 use debug_ignore::DebugIgnore;
 use nextest_metadata::MismatchReason;
 use owo_colors::{OwoColorize, Style};
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 use std::{
+    collections::BTreeMap,
     fmt, io,
     io::Write,
     str::FromStr,
     time::{Duration, SystemTime},
 };
+use uuid::Uuid;
+
+/// The number of tests printed on each line in quiet mode.
+const QUIET_LINE_WIDTH: usize = 80;
 
 /// When to display test output in the reporter.
 #[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
@@ -170,7 +200,13 @@ pub struct TestReporterBuilder {
     failure_output: Option<TestOutputDisplay>,
     success_output: Option<TestOutputDisplay>,
     status_level: Option<StatusLevel>,
+    slowest: Option<usize>,
+    quiet: bool,
     verbose: bool,
+    replay: bool,
+    progress_bar: bool,
+    tui: Option<RunController>,
+    timings: Option<TestTimings>,
 }
 
 impl TestReporterBuilder {
@@ -201,11 +237,70 @@ impl TestReporterBuilder {
         self
     }
 
+    /// Sets the number of slowest tests to list at the end of the run.
+    ///
+    /// This overrides the `count` in the profile's `slowest-tests` configuration.
+    pub fn set_slowest(&mut self, slowest: usize) -> &mut Self {
+        self.slowest = Some(slowest);
+        self
+    }
+
+    /// Sets quiet output.
+    ///
+    /// In this mode, a single character is printed for each test that finishes, and details are
+    /// only printed at the end of the run. Output from failing tests is shown at the end of the run
+    /// unless the failure output is [`TestOutputDisplay::Never`].
+    pub fn set_quiet(&mut self, quiet: bool) -> &mut Self {
+        self.quiet = quiet;
+        self
+    }
+
     /// Sets verbose output.
+    ///
+    /// In this mode, the binaries that tests are run from are listed at the start of the run, and
+    /// unless a status level is set, it's treated as at least [`StatusLevel::Skip`].
     pub fn set_verbose(&mut self, verbose: bool) -> &mut Self {
         self.verbose = verbose;
         self
     }
+
+    /// Sets whether to show a progress bar below the output while tests are running.
+    ///
+    /// The progress bar is redrawn in place, so this should only be set if output is going to a
+    /// terminal. It isn't shown in quiet or no-capture mode.
+    pub fn set_progress_bar(&mut self, progress_bar: bool) -> &mut Self {
+        self.progress_bar = progress_bar;
+        self
+    }
+
+    /// Shows a full-screen dashboard while tests are running, instead of printing output line by
+    /// line.
+    ///
+    /// The dashboard lists running tests and failures, and the controller is used to cancel tests
+    /// or the whole run from it. Output is printed as usual once the run finishes. This should only
+    /// be set if standard input and standard error are terminals. It isn't shown in quiet or
+    /// no-capture mode.
+    pub fn set_tui(&mut self, controller: RunController) -> &mut Self {
+        self.tui = Some(controller);
+        self
+    }
+
+    /// Sets the test timings recorded by previous runs, used to estimate how long the run will
+    /// take.
+    pub fn set_timings(&mut self, timings: TestTimings) -> &mut Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Sets whether a [recorded run](crate::record) is being replayed.
+    ///
+    /// While replaying, test timings and the list of failed tests in the store directory aren't
+    /// updated, since the recorded run may be older than the state that's already there. Reports
+    /// such as JUnit are still written out.
+    pub fn set_replay(&mut self, replay: bool) -> &mut Self {
+        self.replay = replay;
+        self
+    }
 }
 
 impl TestReporterBuilder {
@@ -221,9 +316,12 @@ impl TestReporterBuilder {
             .map(|(_, info)| info.binary_id.len())
             .max()
             .unwrap_or_default();
-        let aggregator = EventAggregator::new(profile);
+        let aggregator = EventAggregator::new(profile, self.replay);
 
-        let status_level = self.status_level.unwrap_or_else(|| profile.status_level());
+        let status_level = self.status_level.unwrap_or_else(|| match self.verbose {
+            true => profile.status_level().max(StatusLevel::Skip),
+            false => profile.status_level(),
+        });
         let status_level = match self.no_capture {
             // In no-capture mode, the status level is treated as at least pass.
             true => status_level.max(StatusLevel::Pass),
@@ -232,27 +330,49 @@ impl TestReporterBuilder {
         // failure_output and success_output are meaningless if the runner isn't capturing any
         // output.
         let failure_output = match self.no_capture {
-            true => TestOutputDisplay::Never,
-            false => self
-                .failure_output
-                .unwrap_or_else(|| profile.failure_output()),
+            true => Some(TestOutputDisplay::Never),
+            false => self.failure_output,
         };
         let success_output = match self.no_capture {
-            true => TestOutputDisplay::Never,
-            false => self
-                .success_output
-                .unwrap_or_else(|| profile.success_output()),
+            true => Some(TestOutputDisplay::Never),
+            false => self.success_output,
         };
 
+        let slowest_tests = profile.slowest_tests();
+        let slowest_count = self.slowest.unwrap_or_else(|| slowest_tests.count());
+
         TestReporter {
+            profile,
             status_level,
             failure_output,
             success_output,
             no_capture: self.no_capture,
+            quiet: self.quiet,
+            verbose: self.verbose,
+            quiet_column: 0,
             binary_id_width,
             styles,
+            colors: profile.colors(),
+            progress_bar: (self.progress_bar && !self.quiet && !self.no_capture)
+                .then(|| ProgressBar::new(self.timings.clone())),
+            tui: self
+                .tui
+                .clone()
+                .filter(|_| !self.quiet && !self.no_capture)
+                .map(Tui::new),
+            test_color: profile.test_color(),
+            keep_test_colors: false,
             cancel_status: None,
+            running_at_cancel: DebugIgnore(vec![]),
             final_outputs: DebugIgnore(vec![]),
+            flaky_tests: DebugIgnore(vec![]),
+            quarantine: profile.quarantine(),
+            quarantined_tests: DebugIgnore(vec![]),
+            slowest_count,
+            slow_threshold: slowest_tests.threshold(),
+            test_times: DebugIgnore(vec![]),
+            repeat: 1,
+            pass_rates: DebugIgnore(BTreeMap::new()),
             metadata_reporter: aggregator,
         }
     }
@@ -260,25 +380,80 @@ impl TestReporterBuilder {
 
 /// Functionality to report test results to stderr and JUnit
 pub struct TestReporter<'a> {
+    profile: &'a NextestProfile<'a>,
     status_level: StatusLevel,
-    failure_output: TestOutputDisplay,
-    success_output: TestOutputDisplay,
+    // If these aren't set, they're looked up in the profile for each test, since they can be
+    // overridden for individual tests.
+    failure_output: Option<TestOutputDisplay>,
+    success_output: Option<TestOutputDisplay>,
     no_capture: bool,
+    quiet: bool,
+    verbose: bool,
+    // The number of characters printed on the current line in quiet mode.
+    quiet_column: usize,
     binary_id_width: usize,
     styles: Box<Styles>,
+    colors: ColorConfig,
+    progress_bar: Option<ProgressBar>,
+    tui: Option<Tui>,
+    test_color: TestColor,
+    // Whether colors in the output of tests are written out as is, rather than stripped.
+    keep_test_colors: bool,
 
     // TODO: too many concerns mixed up here. Should have a better model, probably in conjunction
     // with factoring out the different reporters below.
     cancel_status: Option<CancelReason>,
+    // The tests that were running when the run was first canceled.
+    running_at_cancel: DebugIgnore<Vec<RunningTest<'a>>>,
     final_outputs: DebugIgnore<Vec<(TestInstance<'a>, ExecuteStatus)>>,
+    flaky_tests: DebugIgnore<Vec<(TestInstance<'a>, ExecuteStatus)>>,
+    quarantine: &'a [QuarantineConfig],
+    // Quarantined tests that failed, along with the reason they're quarantined.
+    quarantined_tests: DebugIgnore<Vec<(TestInstance<'a>, ExecuteStatus, &'a str)>>,
+    slowest_count: usize,
+    slow_threshold: Option<Duration>,
+    // The time taken by each test, across all attempts. Only recorded if slowest tests are listed.
+    test_times: DebugIgnore<Vec<(TestInstance<'a>, Duration)>>,
+    repeat: usize,
+    pass_rates: DebugIgnore<BTreeMap<(&'a str, &'a str), PassRate<'a>>>,
 
     metadata_reporter: EventAggregator<'a>,
 }
 
+/// The number of times a test passed when it was run repeatedly.
+struct PassRate<'a> {
+    test_instance: TestInstance<'a>,
+    passed: usize,
+    runs: usize,
+}
+
+impl<'a> PassRate<'a> {
+    fn new(test_instance: TestInstance<'a>) -> Self {
+        Self {
+            test_instance,
+            passed: 0,
+            runs: 0,
+        }
+    }
+
+    fn key(&self) -> (&'a str, &'a str) {
+        (
+            &self.test_instance.bin_info.binary_id,
+            self.test_instance.name,
+        )
+    }
+}
+
 impl<'a> TestReporter<'a> {
-    /// Colorizes output.
+    /// Colorizes output, using the colors configured in the profile.
+    ///
+    /// 24-bit colors are only used if standard error supports them.
     pub fn colorize(&mut self) {
-        self.styles.colorize();
+        let truecolor = supports_color::on_cached(supports_color::Stream::Stderr)
+            .map_or(false, |level| level.has_16m);
+        self.styles.colorize(&self.colors, truecolor);
+        // Tests were asked to produce colors, so show them.
+        self.keep_test_colors = self.test_color == TestColor::Always;
     }
 
     /// Report a test event.
@@ -287,7 +462,12 @@ impl<'a> TestReporter<'a> {
         event: TestEvent<'a>,
         writer: impl Write,
     ) -> Result<(), WriteEventError> {
-        self.write_event(event, writer)
+        self.write_event(&event, writer)
+    }
+
+    /// Returns a [`Reporter`] that writes human-readable output to the given writer.
+    pub fn with_writer<W: Write + Send>(self, writer: W) -> WithWriter<Self, W> {
+        WithWriter::new(self, writer)
     }
 
     // ---
@@ -297,11 +477,56 @@ impl<'a> TestReporter<'a> {
     /// Report this test event to the given writer.
     fn write_event(
         &mut self,
-        event: TestEvent<'a>,
-        writer: impl Write,
+        event: &TestEvent<'a>,
+        mut writer: impl Write,
     ) -> Result<(), WriteEventError> {
-        self.write_event_impl(&event, writer)
+        if self.tui.is_some() {
+            return self.write_event_tui(event, writer);
+        }
+        if let Some(progress_bar) = &mut self.progress_bar {
+            progress_bar
+                .clear(&mut writer)
+                .map_err(WriteEventError::Io)?;
+        }
+        self.write_event_impl(event, &mut writer)
             .map_err(WriteEventError::Io)?;
+        if let Some(progress_bar) = &mut self.progress_bar {
+            progress_bar.update(event);
+            progress_bar
+                .draw(self.styles.pass, &mut writer)
+                .map_err(WriteEventError::Io)?;
+        }
+        self.metadata_reporter.write_event(event)?;
+        Ok(())
+    }
+
+    /// Reports this test event while a dashboard is shown in place of the usual output.
+    fn write_event_tui(
+        &mut self,
+        event: &TestEvent<'a>,
+        mut writer: impl Write,
+    ) -> Result<(), WriteEventError> {
+        let tui = self.tui.as_mut().expect("the dashboard is enabled");
+        if let TestEventKind::RunFinished { .. } = &event.kind {
+            // Close the dashboard, and print out everything that was held back before the summary.
+            let buffered = tui.finish();
+            writer.write_all(&buffered).map_err(WriteEventError::Io)?;
+            self.write_event_impl(event, &mut writer)
+                .map_err(WriteEventError::Io)?;
+        } else {
+            tui.update(event, &self.styles)
+                .map_err(WriteEventError::Io)?;
+            let shown = tui.is_shown();
+            let mut output = vec![];
+            self.write_event_impl(event, &mut output)
+                .map_err(WriteEventError::Io)?;
+            let tui = self.tui.as_mut().expect("the dashboard is enabled");
+            if shown {
+                tui.buffer(&output);
+            } else {
+                writer.write_all(&output).map_err(WriteEventError::Io)?;
+            }
+        }
         self.metadata_reporter.write_event(event)?;
         Ok(())
     }
@@ -311,8 +536,39 @@ impl<'a> TestReporter<'a> {
         event: &TestEvent<'a>,
         mut writer: impl Write,
     ) -> io::Result<()> {
-        match event {
-            TestEvent::RunStarted { test_list } => {
+        if self.quiet {
+            match &event.kind {
+                TestEventKind::RunStarted { repeat, .. } => {
+                    self.repeat = *repeat;
+                    return Ok(());
+                }
+                TestEventKind::TestStarted { .. }
+                | TestEventKind::TestSlow { .. }
+                | TestEventKind::TestSilent { .. }
+                | TestEventKind::TestRetry { .. }
+                | TestEventKind::TestSkipped { .. }
+                | TestEventKind::TestCanceled { .. } => return Ok(()),
+                TestEventKind::ScriptFinished { status, .. } if status.result.is_success() => {
+                    return Ok(())
+                }
+                TestEventKind::TestFinished { .. } => {}
+                _ => {
+                    // Anything else is printed on a line of its own.
+                    if self.quiet_column > 0 {
+                        writeln!(writer)?;
+                        self.quiet_column = 0;
+                    }
+                }
+            }
+        }
+
+        match &event.kind {
+            TestEventKind::RunStarted {
+                test_list,
+                shuffle_seed,
+                repeat,
+            } => {
+                self.repeat = *repeat;
                 write!(writer, "{:>12} ", "Starting".style(self.styles.pass))?;
 
                 let count_style = self.styles.count;
@@ -326,12 +582,53 @@ impl<'a> TestReporter<'a> {
 
                 let skip_count = test_list.skip_count();
                 if skip_count > 0 {
-                    write!(writer, " ({} skipped)", skip_count.style(count_style))?;
+                    write!(writer, " ({} skipped", skip_count.style(count_style))?;
+                    let default_filter_skip_count = test_list.default_filter_skip_count();
+                    if default_filter_skip_count > 0 {
+                        write!(
+                            writer,
+                            ", {} by default filter",
+                            default_filter_skip_count.style(count_style)
+                        )?;
+                    }
+                    let excluded_count = test_list.excluded_count();
+                    if excluded_count > 0 {
+                        write!(writer, ", {} excluded", excluded_count.style(count_style))?;
+                    }
+                    write!(writer, ")")?;
+                }
+                if let Some(seed) = shuffle_seed {
+                    write!(writer, " (shuffled with seed {})", seed.style(count_style))?;
+                }
+                if *repeat > 1 {
+                    write!(writer, ", each run {} times", repeat.style(count_style))?;
                 }
 
                 writeln!(writer)?;
+
+                if self.verbose {
+                    for (binary_path, info) in test_list.iter() {
+                        let run_count = info
+                            .testcases
+                            .values()
+                            .filter(|case| case.filter_match.is_match())
+                            .count();
+                        write!(writer, "{:>12} ", "Binary".style(self.styles.pass))?;
+                        write!(
+                            writer,
+                            "{} ({} tests",
+                            info.binary_id.style(self.styles.test_list.binary_id),
+                            run_count.style(count_style),
+                        )?;
+                        let skip_count = info.testcases.len() - run_count;
+                        if skip_count > 0 {
+                            write!(writer, ", {} skipped", skip_count.style(count_style))?;
+                        }
+                        writeln!(writer, "): {}", binary_path)?;
+                    }
+                }
             }
-            TestEvent::TestStarted { test_instance } => {
+            TestEventKind::TestStarted { test_instance } => {
                 // In no-capture mode, print out a test start event.
                 if self.no_capture {
                     // The spacing is to align test instances.
@@ -344,20 +641,52 @@ impl<'a> TestReporter<'a> {
                     writeln!(writer)?;
                 }
             }
-            TestEvent::TestSlow {
+            TestEventKind::TestSlow {
                 test_instance,
                 elapsed,
+                will_terminate,
             } => {
                 if self.status_level >= StatusLevel::Slow {
-                    write!(writer, "{:>12} ", "SLOW".style(self.styles.skip))?;
+                    if *will_terminate {
+                        write!(writer, "{:>12} ", "TERMINATING".style(self.styles.fail))?;
+                    } else {
+                        write!(writer, "{:>12} ", "SLOW".style(self.styles.skip))?;
+                    }
                     self.write_slow_duration(*elapsed, &mut writer)?;
                     self.write_instance(*test_instance, &mut writer)?;
                     writeln!(writer)?;
                 }
             }
-            TestEvent::TestRetry {
+            TestEventKind::TestSilent {
+                test_instance,
+                silent_for,
+                ..
+            } => {
+                if self.status_level >= StatusLevel::Slow {
+                    write!(writer, "{:>12} ", "SILENT".style(self.styles.skip))?;
+                    self.write_slow_duration(*silent_for, &mut writer)?;
+                    self.write_instance(*test_instance, &mut writer)?;
+                    writeln!(writer)?;
+                }
+            }
+            TestEventKind::TestPaused { test_instance, pid } => {
+                // The spacing is to align test instances.
+                write!(
+                    writer,
+                    "{:>12}             ",
+                    "PAUSED".style(self.styles.skip),
+                )?;
+                self.write_instance(*test_instance, &mut writer)?;
+                writeln!(
+                    writer,
+                    ": process {} is waiting for a debugger, press Enter to continue",
+                    pid.style(self.styles.count),
+                )?;
+            }
+            TestEventKind::TestRetry {
                 test_instance,
                 run_status,
+                delay_before_next_attempt,
             } => {
                 if self.status_level >= StatusLevel::Retry {
                     let retry_string =
@@ -369,6 +698,13 @@ impl<'a> TestReporter<'a> {
 
                     // Print the name of the test.
                     self.write_instance(*test_instance, &mut writer)?;
+                    if !delay_before_next_attempt.is_zero() {
+                        write!(
+                            writer,
+                            " (retrying in {:.3}s)",
+                            delay_before_next_attempt.as_secs_f64()
+                        )?;
+                    }
                     writeln!(writer)?;
 
                     // This test is guaranteed to have failed.
@@ -376,20 +712,79 @@ impl<'a> TestReporter<'a> {
                         !run_status.result.is_success(),
                         "only failing tests are retried"
                     );
-                    if self.failure_output.is_immediate() {
+                    if self.failure_output(test_instance).is_immediate() {
                         self.write_run_status(test_instance, run_status, true, &mut writer)?;
                     }
 
                     // The final output doesn't show retries.
                 }
             }
-            TestEvent::TestFinished {
+            TestEventKind::TestFinished {
                 test_instance,
                 run_statuses,
             } => {
                 let describe = run_statuses.describe();
 
-                if self.status_level >= describe.status_level() {
+                if let ExecutionDescription::Flaky { last_status, .. } = describe {
+                    self.flaky_tests.push((*test_instance, last_status.clone()));
+                }
+                let quarantine_reason = if run_statuses.last_status().result.is_success() {
+                    None
+                } else {
+                    self.quarantine
+                        .iter()
+                        .find(|quarantine| quarantine.filter().matches(test_instance))
+                        .map(|quarantine| quarantine.reason())
+                };
+                if let Some(reason) = quarantine_reason {
+                    self.quarantined_tests.push((
+                        *test_instance,
+                        run_statuses.last_status().clone(),
+                        reason,
+                    ));
+                }
+                if self.slowest_count > 0 || self.slow_threshold.is_some() {
+                    let time_taken = run_statuses.iter().map(|status| status.time_taken).sum();
+                    self.test_times.push((*test_instance, time_taken));
+                }
+                if self.repeat > 1 {
+                    let pass_rate = self
+                        .pass_rates
+                        .entry((&test_instance.bin_info.binary_id, test_instance.name))
+                        .or_insert_with(|| PassRate::new(*test_instance));
+                    pass_rate.runs += 1;
+                    if run_statuses.last_status().result.is_success() {
+                        pass_rate.passed += 1;
+                    }
+                }
+
+                if self.quiet {
+                    let last_status = run_statuses.last_status();
+                    if last_status.result.is_success() {
+                        write!(writer, "{}", ".".style(self.styles.pass))?;
+                    } else if quarantine_reason.is_some() {
+                        write!(writer, "{}", "q".style(self.styles.skip))?;
+                    } else {
+                        write!(writer, "{}", "F".style(self.styles.fail))?;
+                    }
+                    self.quiet_column += 1;
+                    if self.quiet_column == QUIET_LINE_WIDTH {
+                        writeln!(writer)?;
+                        self.quiet_column = 0;
+                    }
+
+                    // Output is always deferred to the end of the run.
+                    if self.cancel_status < Some(CancelReason::Signal) {
+                        let test_output_display = match last_status.result.is_success() {
+                            true => self.success_output(test_instance),
+                            false => self.failure_output(test_instance),
+                        };
+                        if test_output_display != TestOutputDisplay::Never {
+                            self.final_outputs
+                                .push((*test_instance, last_status.clone()));
+                        }
+                    }
+                } else if self.status_level >= describe.status_level() {
                     // First, print the status.
                     let last_status = match describe {
                         ExecutionDescription::Success {
@@ -411,6 +806,8 @@ impl<'a> TestReporter<'a> {
                             let status_str = match last_status.result {
                                 ExecutionResult::Fail => "FAIL",
                                 ExecutionResult::ExecFail => "XFAIL",
+                                ExecutionResult::Timeout => "TIMEOUT",
+                                ExecutionResult::ResourceLimit => "RESLIMIT",
                                 ExecutionResult::Pass => unreachable!("this is a failing test"),
                             };
 
@@ -433,14 +830,17 @@ impl<'a> TestReporter<'a> {
 
                     // Print the name of the test.
                     self.write_instance(*test_instance, &mut writer)?;
+                    if quarantine_reason.is_some() {
+                        write!(writer, " ({})", "quarantined".style(self.styles.skip))?;
+                    }
                     writeln!(writer)?;
 
                     // If the test failed to execute, print its output and error status.
                     // (don't print out test failures after Ctrl-C)
                     if self.cancel_status < Some(CancelReason::Signal) {
                         let test_output_display = match last_status.result.is_success() {
-                            true => self.success_output,
-                            false => self.failure_output,
+                            true => self.success_output(test_instance),
+                            false => self.failure_output(test_instance),
                         };
                         if test_output_display.is_immediate() {
                             self.write_run_status(test_instance, last_status, false, &mut writer)?;
@@ -452,9 +852,10 @@ impl<'a> TestReporter<'a> {
                     }
                 }
             }
-            TestEvent::TestSkipped {
+            TestEventKind::TestSkipped {
                 test_instance,
                 reason: _reason,
+                exclude_reason,
             } => {
                 if self.status_level >= StatusLevel::Skip {
                     write!(writer, "{:>12} ", "SKIP".style(self.styles.skip))?;
@@ -462,14 +863,114 @@ impl<'a> TestReporter<'a> {
                     write!(writer, "[         ] ")?;
 
                     self.write_instance(*test_instance, &mut writer)?;
+                    if let Some(exclude_reason) = exclude_reason {
+                        write!(writer, " (excluded: {})", exclude_reason)?;
+                    }
+                    writeln!(writer)?;
+                }
+            }
+            TestEventKind::TestRecorded {
+                test_instance,
+                run_status,
+                recording_dir,
+            } => {
+                if self.status_level >= StatusLevel::Fail {
+                    if run_status.result == ExecutionResult::ExecFail {
+                        // The recorder couldn't be run, so there's no recording. Show why.
+                        write!(writer, "{:>12} ", "RECORD FAIL".style(self.styles.fail))?;
+                        self.write_duration(run_status.time_taken, &mut writer)?;
+                        self.write_instance(*test_instance, &mut writer)?;
+                        writeln!(writer)?;
+                        self.write_run_status(test_instance, run_status, false, &mut writer)?;
+                    } else {
+                        write!(writer, "{:>12} ", "RECORDED".style(self.styles.retry))?;
+                        self.write_duration(run_status.time_taken, &mut writer)?;
+                        self.write_instance(*test_instance, &mut writer)?;
+                        if run_status.result.is_success() {
+                            write!(writer, " (passed this time)")?;
+                        }
+                        writeln!(writer, ": {}", recording_dir)?;
+                    }
+                }
+            }
+            TestEventKind::TestCanceled { test_instance } => {
+                if self.status_level >= StatusLevel::Skip {
+                    write!(writer, "{:>12} ", "CANCELED".style(self.styles.fail))?;
+                    // same spacing [   0.034s]
+                    write!(writer, "[         ] ")?;
+
+                    self.write_instance(*test_instance, &mut writer)?;
+                    writeln!(writer)?;
+                }
+            }
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => {
+                let success = status.result.is_success();
+                let status_level = if success {
+                    StatusLevel::Pass
+                } else {
+                    StatusLevel::Fail
+                };
+                if self.status_level >= status_level {
+                    let kind_str = match kind {
+                        ScriptKind::Setup => "setup",
+                        ScriptKind::Teardown => "teardown",
+                    };
+                    if success {
+                        write!(writer, "{:>12} ", "SCRIPT".style(self.styles.pass))?;
+                    } else {
+                        write!(writer, "{:>12} ", "SCRIPT FAIL".style(self.styles.fail))?;
+                    }
+                    self.write_duration(status.time_taken, &mut writer)?;
+                    writeln!(
+                        writer,
+                        "{} ({})",
+                        script_name.style(self.styles.test_list.binary_id),
+                        kind_str
+                    )?;
+
+                    // Always show the output of failing scripts, since they aren't retried and
+                    // there's no other way to tell what went wrong.
+                    if !success {
+                        self.write_script_status(script_name, status, &mut writer)?;
+                    }
+                }
+            }
+            TestEventKind::RunInfo { running_tests } => {
+                // This was explicitly asked for, so it's printed out regardless of the status
+                // level.
+                writeln!(
+                    writer,
+                    "{:>12} {} tests running",
+                    "Info".style(self.styles.pass),
+                    running_tests.len().style(self.styles.count),
+                )?;
+                for running_test in running_tests {
+                    write!(writer, "{:>12} ", "RUNNING".style(self.styles.pass))?;
+                    self.write_duration(running_test.elapsed, &mut writer)?;
+                    self.write_instance(running_test.test_instance, &mut writer)?;
+                    if running_test.attempt > 1 {
+                        write!(writer, " (attempt {})", running_test.attempt)?;
+                    }
                     writeln!(writer)?;
                 }
             }
-            TestEvent::RunBeginCancel { running, reason } => {
+            TestEventKind::RunBeginCancel {
+                running,
+                running_tests,
+                reason,
+            } => {
                 self.cancel_status = self.cancel_status.max(Some(*reason));
+                if self.running_at_cancel.is_empty() {
+                    self.running_at_cancel = DebugIgnore(running_tests.clone());
+                }
 
                 write!(writer, "{:>12} ", "Canceling".style(self.styles.fail))?;
                 let reason_str = match reason {
+                    CancelReason::SetupScriptFailure => "setup script failure",
                     CancelReason::TestFailure => "test failure",
                     CancelReason::ReportError => "error",
                     CancelReason::Signal => "signal",
@@ -483,7 +984,7 @@ impl<'a> TestReporter<'a> {
                 )?;
             }
 
-            TestEvent::RunFinished {
+            TestEventKind::RunFinished {
                 start_time: _start_time,
                 elapsed,
                 run_stats:
@@ -494,10 +995,23 @@ impl<'a> TestReporter<'a> {
                         flaky,
                         failed,
                         exec_failed,
+                        timed_out,
+                        resource_limited,
+                        quarantined,
                         skipped,
+                        skipped_by_default_filter,
+                        excluded,
+                        scripts_failed,
+                        canceled,
+                        cancel_reason: _,
                     },
             } => {
-                let summary_style = if *failed > 0 || *exec_failed > 0 {
+                let summary_style = if *failed > 0
+                    || *exec_failed > 0
+                    || *timed_out > 0
+                    || *resource_limited > 0
+                    || *scripts_failed > 0
+                {
                     self.styles.fail
                 } else {
                     self.styles.pass
@@ -549,15 +1063,194 @@ impl<'a> TestReporter<'a> {
                     )?;
                 }
 
+                if *timed_out > 0 {
+                    write!(
+                        writer,
+                        "{} {}, ",
+                        timed_out.style(self.styles.count),
+                        "timed out".style(self.styles.fail),
+                    )?;
+                }
+
+                if *resource_limited > 0 {
+                    write!(
+                        writer,
+                        "{} {}, ",
+                        resource_limited.style(self.styles.count),
+                        "over resource limits".style(self.styles.fail),
+                    )?;
+                }
+
+                if *quarantined > 0 {
+                    write!(
+                        writer,
+                        "{} {}, ",
+                        quarantined.style(self.styles.count),
+                        "quarantined".style(self.styles.skip),
+                    )?;
+                }
+
+                if *scripts_failed > 0 {
+                    write!(
+                        writer,
+                        "{} {}, ",
+                        scripts_failed.style(self.styles.count),
+                        "scripts failed".style(self.styles.fail),
+                    )?;
+                }
+
                 write!(
                     writer,
                     "{} {}",
                     skipped.style(self.styles.count),
                     "skipped".style(self.styles.skip),
                 )?;
+                match (*skipped_by_default_filter, *excluded) {
+                    (0, 0) => {}
+                    (by_default_filter, 0) => write!(
+                        writer,
+                        " ({} by default filter)",
+                        by_default_filter.style(self.styles.count),
+                    )?,
+                    (0, excluded) => {
+                        write!(writer, " ({} excluded)", excluded.style(self.styles.count))?
+                    }
+                    (by_default_filter, excluded) => write!(
+                        writer,
+                        " ({} by default filter, {} excluded)",
+                        by_default_filter.style(self.styles.count),
+                        excluded.style(self.styles.count),
+                    )?,
+                }
+
+                if *canceled > 0 {
+                    write!(
+                        writer,
+                        ", {} {}",
+                        canceled.style(self.styles.count),
+                        "canceled".style(self.styles.fail),
+                    )?;
+                }
 
                 writeln!(writer)?;
 
+                // List flaky tests, so that they don't get lost among the test results.
+                if self.status_level >= StatusLevel::Retry {
+                    for (test_instance, last_status) in &*self.flaky_tests {
+                        write!(
+                            writer,
+                            "{:>12} ",
+                            format!(
+                                "FLAKY {}/{}",
+                                last_status.attempt, last_status.total_attempts
+                            )
+                            .style(self.styles.skip)
+                        )?;
+                        self.write_duration(last_status.time_taken, &mut writer)?;
+                        self.write_instance(*test_instance, &mut writer)?;
+                        writeln!(writer)?;
+                    }
+                }
+
+                // List quarantined tests that failed separately from other failures, along with the
+                // reason they're quarantined.
+                if self.status_level >= StatusLevel::Fail {
+                    for (test_instance, last_status, reason) in &*self.quarantined_tests {
+                        write!(writer, "{:>12} ", "QUARANTINED".style(self.styles.skip))?;
+                        self.write_duration(last_status.time_taken, &mut writer)?;
+                        self.write_instance(*test_instance, &mut writer)?;
+                        writeln!(writer, ": {}", reason)?;
+                    }
+                }
+
+                // List the tests that were running when the run was canceled, along with how long
+                // they had been running for, so that it's clear what the run was doing at the time.
+                if self.status_level >= StatusLevel::Fail {
+                    for running_test in &*self.running_at_cancel {
+                        write!(writer, "{:>12} ", "IN FLIGHT".style(self.styles.fail))?;
+                        self.write_duration(running_test.elapsed, &mut writer)?;
+                        self.write_instance(running_test.test_instance, &mut writer)?;
+                        if running_test.attempt > 1 {
+                            write!(writer, " (attempt {})", running_test.attempt)?;
+                        }
+                        writeln!(writer)?;
+                    }
+                }
+
+                // List the slowest tests, so that it's clear what the run spent its time on. Tests
+                // over the threshold are always listed.
+                if self.status_level >= StatusLevel::Fail {
+                    let mut test_times: Vec<_> = self.test_times.iter().collect();
+                    test_times.sort_by(|(a_instance, a_time), (b_instance, b_time)| {
+                        b_time.cmp(a_time).then_with(|| {
+                            (&a_instance.bin_info.binary_id, a_instance.name)
+                                .cmp(&(&b_instance.bin_info.binary_id, b_instance.name))
+                        })
+                    });
+                    for (index, (test_instance, time_taken)) in test_times.into_iter().enumerate() {
+                        let too_slow = self
+                            .slow_threshold
+                            .map_or(false, |threshold| *time_taken > threshold);
+                        if index >= self.slowest_count && !too_slow {
+                            // Tests are sorted by time, so none of the rest are over the threshold
+                            // either.
+                            break;
+                        }
+                        if too_slow {
+                            write!(writer, "{:>12} ", "TOO SLOW".style(self.styles.fail))?;
+                        } else {
+                            write!(writer, "{:>12} ", "SLOWEST".style(self.styles.skip))?;
+                        }
+                        self.write_duration(*time_taken, &mut writer)?;
+                        self.write_instance(*test_instance, &mut writer)?;
+                        if let Some(threshold) = self.slow_threshold.filter(|_| too_slow) {
+                            write!(writer, " (over the {}s threshold)", threshold.as_secs_f64())?;
+                        }
+                        writeln!(writer)?;
+                    }
+                }
+
+                // With --repeat, show how often each test passed. Tests that always passed are
+                // only listed at the pass status level.
+                if self.repeat > 1 {
+                    let mut pass_rates: Vec<_> = self.pass_rates.values().collect();
+                    // Show the least reliable tests first.
+                    pass_rates.sort_by(|a, b| {
+                        (a.passed * b.runs)
+                            .cmp(&(b.passed * a.runs))
+                            .then_with(|| a.key().cmp(&b.key()))
+                    });
+                    for pass_rate in pass_rates {
+                        let all_passed = pass_rate.passed == pass_rate.runs;
+                        let status_level = if all_passed {
+                            StatusLevel::Pass
+                        } else {
+                            StatusLevel::Fail
+                        };
+                        if self.status_level < status_level {
+                            continue;
+                        }
+                        let style = if all_passed {
+                            self.styles.pass
+                        } else {
+                            self.styles.fail
+                        };
+                        write!(writer, "{:>12} ", "PASS RATE".style(style))?;
+                        write!(
+                            writer,
+                            "[{:>8.1}%] ",
+                            100.0 * pass_rate.passed as f64 / pass_rate.runs as f64
+                        )?;
+                        self.write_instance(pass_rate.test_instance, &mut writer)?;
+                        writeln!(
+                            writer,
+                            " ({}/{} passed)",
+                            pass_rate.passed.style(self.styles.count),
+                            pass_rate.runs.style(self.styles.count)
+                        )?;
+                    }
+                }
+
                 // Don't print out test failures if canceled due to Ctrl-C.
                 if self.status_level >= StatusLevel::Fail
                     && self.cancel_status < Some(CancelReason::Signal)
@@ -572,6 +1265,16 @@ impl<'a> TestReporter<'a> {
         Ok(())
     }
 
+    fn failure_output(&self, test_instance: &TestInstance<'_>) -> TestOutputDisplay {
+        self.failure_output
+            .unwrap_or_else(|| self.profile.test_settings(test_instance).failure_output())
+    }
+
+    fn success_output(&self, test_instance: &TestInstance<'_>) -> TestOutputDisplay {
+        self.success_output
+            .unwrap_or_else(|| self.profile.test_settings(test_instance).success_output())
+    }
+
     fn write_instance(&self, instance: TestInstance<'a>, mut writer: impl Write) -> io::Result<()> {
         write!(
             writer,
@@ -631,14 +1334,7 @@ impl<'a> TestReporter<'a> {
             self.write_instance(*test_instance, &mut writer)?;
             writeln!(writer, "{}", " ---".style(header_style))?;
 
-            {
-                // Strip ANSI escapes from the output in case some test framework doesn't check for
-                // ttys before producing color output.
-                // TODO: apply output style once https://github.com/jam1garner/owo-colors/issues/41 is
-                // fixed
-                let mut no_color = strip_ansi_escapes::Writer::new(&mut writer);
-                no_color.write_all(run_status.stdout())?;
-            }
+            self.write_test_output(run_status.stdout(), &mut writer)?;
         }
 
         if !run_status.stderr().is_empty() {
@@ -654,13 +1350,82 @@ impl<'a> TestReporter<'a> {
             self.write_instance(*test_instance, &mut writer)?;
             writeln!(writer, "{}", " ---".style(header_style))?;
 
+            self.write_test_output(run_status.stderr(), &mut writer)?;
+        }
+
+        if let Some(core_dump) = &run_status.core_dump {
+            write!(writer, "\n{}", "--- ".style(header_style))?;
+            let out_len = self.write_attempt(run_status, header_style, &mut writer)?;
+            // The width is to align test instances.
+            write!(
+                writer,
+                "{:width$}",
+                "CORE DUMP:".style(header_style),
+                width = (21 - out_len)
+            )?;
+            self.write_instance(*test_instance, &mut writer)?;
+            writeln!(writer, "{}", " ---".style(header_style))?;
+            writeln!(writer, "{}", core_dump)?;
+        }
+
+        if let Some(output_dir) = &run_status.output_dir {
+            write!(writer, "\n{}", "--- ".style(header_style))?;
+            let out_len = self.write_attempt(run_status, header_style, &mut writer)?;
+            // The width is to align test instances.
+            write!(
+                writer,
+                "{:width$}",
+                "OUTPUT DIR:".style(header_style),
+                width = (21 - out_len)
+            )?;
+            self.write_instance(*test_instance, &mut writer)?;
+            writeln!(writer, "{}", " ---".style(header_style))?;
+            writeln!(writer, "{}", output_dir)?;
+        }
+
+        writeln!(writer)
+    }
+
+    fn write_test_output(&self, output: &[u8], mut writer: impl Write) -> io::Result<()> {
+        if self.keep_test_colors {
+            writer.write_all(output)
+        } else {
+            // Strip ANSI escapes from the output in case some test framework doesn't check for
+            // ttys before producing color output.
+            // TODO: apply output style once https://github.com/jam1garner/owo-colors/issues/41 is
+            // fixed
+            let mut no_color = strip_ansi_escapes::Writer::new(&mut writer);
+            no_color.write_all(output)
+        }
+    }
+
+    fn write_script_status(
+        &self,
+        script_name: &str,
+        status: &ScriptStatus,
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        let header_style = self.styles.fail;
+
+        for (header, output) in [("STDOUT:", status.stdout()), ("STDERR:", status.stderr())] {
+            if output.is_empty() {
+                continue;
+            }
+
+            write!(writer, "\n{}", "--- ".style(header_style))?;
+            // The width is to align script names with test instances.
+            write!(writer, "{:21}", header.style(header_style))?;
+            write!(
+                writer,
+                "{}",
+                script_name.style(self.styles.test_list.binary_id)
+            )?;
+            writeln!(writer, "{}", " ---".style(header_style))?;
+
             {
-                // Strip ANSI escapes from the output in case some test framework doesn't check for
-                // ttys before producing color output.
-                // TODO: apply output style once https://github.com/jam1garner/owo-colors/issues/41 is
-                // fixed
+                // Strip ANSI escapes from the output, similar to test output.
                 let mut no_color = strip_ansi_escapes::Writer::new(&mut writer);
-                no_color.write_all(run_status.stderr())?;
+                no_color.write_all(output)?;
             }
         }
 
@@ -700,17 +1465,129 @@ impl<'a> fmt::Debug for TestReporter<'a> {
     }
 }
 
+/// A consumer of test events.
+///
+/// Reporters are registered with
+/// [`TestRunnerBuilder::add_reporter`](crate::runner::TestRunnerBuilder::add_reporter). If a
+/// reporter returns an error, the test run is canceled and the error is returned from
+/// [`TestRunner::try_execute`](crate::runner::TestRunner::try_execute).
+pub trait Reporter<'a>: Send {
+    /// Reports a test event.
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError>;
+}
+
+impl<'a, R: Reporter<'a> + ?Sized> Reporter<'a> for Box<R> {
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        (**self).report_event(event)
+    }
+}
+
+/// A [`Reporter`] that writes the output of [`TestReporter`], [`JsonReporter`] or [`TapReporter`]
+/// to a writer.
+///
+/// The writer is flushed after every event, so buffered writers can be used without delaying
+/// output.
+#[derive(Debug)]
+pub struct WithWriter<R, W> {
+    reporter: R,
+    writer: W,
+}
+
+impl<R, W> WithWriter<R, W> {
+    /// Creates a new `WithWriter`.
+    pub fn new(reporter: R, writer: W) -> Self {
+        Self { reporter, writer }
+    }
+
+    /// Returns the reporter and the writer.
+    pub fn into_inner(self) -> (R, W) {
+        (self.reporter, self.writer)
+    }
+}
+
+impl<'a, W: Write + Send> Reporter<'a> for WithWriter<TestReporter<'a>, W> {
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        self.reporter.write_event(event, &mut self.writer)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+impl<'a, W: Write + Send> Reporter<'a> for WithWriter<JsonReporter, W> {
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        self.reporter.write_event(event, &mut self.writer)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+impl<'a, W: Write + Send> Reporter<'a> for WithWriter<LibtestReporter, W> {
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        self.reporter.write_event(event, &mut self.writer)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
+impl<'a, W: Write + Send> Reporter<'a> for WithWriter<TapReporter, W> {
+    fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+        self.reporter.write_event(event, &mut self.writer)?;
+        self.writer.flush().map_err(WriteEventError::Io)
+    }
+}
+
 /// A test event.
 ///
-/// Events are produced by a [`TestRunner`](crate::runner::TestRunner) and consumed by a [`TestReporter`].
+/// Events are produced by a [`TestRunner`](crate::runner::TestRunner) and consumed by
+/// [`Reporter`]s such as [`TestReporter`]. Each event is tagged with the ID of the run it's part of
+/// and the time it happened at, so that events can be correlated across systems.
+#[derive(Clone, Debug)]
+pub struct TestEvent<'a> {
+    /// The unique ID of the run this event is part of.
+    ///
+    /// A new ID is generated for each [`TestRunner`](crate::runner::TestRunner).
+    pub run_id: Uuid,
+
+    /// The wall-clock time at which this event happened.
+    ///
+    /// This is computed from the time the run started and [`since_start`](Self::since_start), so
+    /// timestamps of events within a run never go backwards, even if the system clock changes.
+    pub timestamp: SystemTime,
+
+    /// The amount of time since the run started, as measured by a monotonic clock.
+    pub since_start: Duration,
+
+    /// The kind of event this is.
+    pub kind: TestEventKind<'a>,
+}
+
+#[cfg(test)]
+impl<'a> TestEvent<'a> {
+    /// Creates an event of the given kind at the start of a run with a nil ID.
+    pub(crate) fn from_kind(kind: TestEventKind<'a>) -> Self {
+        Self {
+            run_id: Uuid::nil(),
+            timestamp: SystemTime::UNIX_EPOCH,
+            since_start: Duration::ZERO,
+            kind,
+        }
+    }
+}
+
+/// The kind of a [`TestEvent`].
 #[derive(Clone, Debug)]
-pub enum TestEvent<'a> {
+pub enum TestEventKind<'a> {
     /// The test run started.
     RunStarted {
         /// The list of tests that will be run.
         ///
         /// The methods on the test list indicate the number of
         test_list: &'a TestList<'a>,
+
+        /// The seed tests are shuffled with, if tests are being run in a random order.
+        shuffle_seed: Option<u64>,
+
+        /// The number of times each test is run.
+        ///
+        /// This is greater than 1 if `--repeat` is used to stress-test tests.
+        repeat: usize,
     },
 
     // TODO: add events for BinaryStarted and BinaryFinished? May want a slightly different way to
@@ -729,6 +1606,36 @@ pub enum TestEvent<'a> {
 
         /// The amount of time that has elapsed since the beginning of the test.
         elapsed: Duration,
+
+        /// True if the test has hit its timeout and is about to be terminated.
+        will_terminate: bool,
+    },
+
+    /// A test hasn't produced any output for longer than its configured heartbeat period.
+    ///
+    /// This event is repeated every period for as long as the test stays silent.
+    TestSilent {
+        /// The test instance that is silent.
+        test_instance: TestInstance<'a>,
+
+        /// The amount of time since the test last produced output, or since it started if it
+        /// hasn't produced any.
+        silent_for: Duration,
+
+        /// The amount of time that has elapsed since the beginning of the test.
+        elapsed: Duration,
+    },
+
+    /// A test process was stopped right before the test started, so that a debugger can be
+    /// attached to it. See [`TestRunnerBuilder::set_wait_for_debugger`](crate::runner::TestRunnerBuilder::set_wait_for_debugger).
+    ///
+    /// The process is resumed once the user presses Enter.
+    TestPaused {
+        /// The test instance that is paused.
+        test_instance: TestInstance<'a>,
+
+        /// The ID of the stopped process.
+        pid: u32,
     },
 
     /// A test failed and is being retried.
@@ -740,6 +1647,10 @@ pub enum TestEvent<'a> {
 
         /// The status of this attempt to run the test. Will never be success.
         run_status: ExecuteStatus,
+
+        /// The amount of time the runner waits for before the next attempt, as configured by the
+        /// retry policy.
+        delay_before_next_attempt: Duration,
     },
 
     /// A test finished running.
@@ -751,6 +1662,21 @@ pub enum TestEvent<'a> {
         run_statuses: ExecutionStatuses,
     },
 
+    /// A test that failed was run once more under a recorder, after all other tests finished.
+    ///
+    /// This event only occurs if [recording failures](crate::config::NextestProfile::record_failures)
+    /// is enabled.
+    TestRecorded {
+        /// The test instance that was recorded.
+        test_instance: TestInstance<'a>,
+
+        /// The status of the recorded run of the test.
+        run_status: ExecuteStatus,
+
+        /// The directory the recording was written to.
+        recording_dir: Utf8PathBuf,
+    },
+
     /// A test was skipped.
     TestSkipped {
         /// The test instance that was skipped.
@@ -758,6 +1684,35 @@ pub enum TestEvent<'a> {
 
         /// The reason this test was skipped.
         reason: MismatchReason,
+
+        /// The reason given in the configuration for excluding this test, if it was skipped with
+        /// [`MismatchReason::Excluded`].
+        exclude_reason: Option<String>,
+    },
+
+    /// A test wasn't run because the test run was canceled before it could start.
+    TestCanceled {
+        /// The test instance that was canceled.
+        test_instance: TestInstance<'a>,
+    },
+
+    /// A setup or teardown script finished running.
+    ScriptFinished {
+        /// The name of the script, from its `[script.<name>]` section.
+        script_name: String,
+
+        /// Whether this was the setup or teardown command.
+        kind: ScriptKind,
+
+        /// The status of the script.
+        status: ScriptStatus,
+    },
+
+    /// Information about the tests that are currently running was requested, for example with
+    /// `SIGUSR1`.
+    RunInfo {
+        /// The tests that are currently running, longest-running first.
+        running_tests: Vec<RunningTest<'a>>,
     },
 
     /// A cancellation notice was received.
@@ -765,6 +1720,9 @@ pub enum TestEvent<'a> {
         /// The number of tests still running.
         running: usize,
 
+        /// The tests that were running when the run was canceled, longest-running first.
+        running_tests: Vec<RunningTest<'a>>,
+
         /// The reason this run was canceled.
         reason: CancelReason,
     },
@@ -784,8 +1742,12 @@ pub enum TestEvent<'a> {
 
 // Note: the order here matters -- it indicates severity of cancellation
 /// The reason why a test run is being cancelled.
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum CancelReason {
+    /// A setup script failed.
+    SetupScriptFailure,
+
     /// A test failed and --no-fail-fast wasn't specified.
     TestFailure,
 
@@ -810,23 +1772,67 @@ struct Styles {
 }
 
 impl Styles {
-    fn colorize(&mut self) {
-        self.count = Style::new().bold();
-        self.pass = Style::new().green().bold();
-        self.retry = Style::new().magenta().bold();
-        self.fail = Style::new().red().bold();
+    fn colorize(&mut self, colors: &ColorConfig, truecolor: bool) {
         self.pass_output = Style::new().green();
         self.retry_output = Style::new().magenta();
         self.fail_output = Style::new().magenta();
-        self.skip = Style::new().yellow().bold();
         self.test_list.colorize();
+        match colors.theme() {
+            ColorTheme::Default => {
+                self.count = Style::new().bold();
+                self.pass = Style::new().green().bold();
+                self.retry = Style::new().magenta().bold();
+                self.fail = Style::new().red().bold();
+                self.skip = Style::new().yellow().bold();
+            }
+            ColorTheme::Basic => {
+                self.count = Style::new();
+                self.pass = Style::new().green();
+                self.retry = Style::new().magenta();
+                self.fail = Style::new().red();
+                self.skip = Style::new().yellow();
+                self.test_list.binary_id = Style::new().magenta();
+                self.test_list.test_name = Style::new().blue();
+                self.test_list.field = Style::new().yellow();
+            }
+            ColorTheme::Monochrome => {
+                self.count = Style::new().bold();
+                self.pass = Style::new().bold();
+                self.retry = Style::new().bold();
+                self.fail = Style::new().bold().underline();
+                self.skip = Style::new().bold();
+                self.pass_output = Style::new();
+                self.retry_output = Style::new();
+                self.fail_output = Style::new().underline();
+                self.test_list.binary_id = Style::new().bold();
+                self.test_list.test_name = Style::new().bold();
+                self.test_list.module_path = Style::new();
+                self.test_list.field = Style::new().bold();
+            }
+        }
+
+        let overrides = [
+            (colors.count(), &mut self.count),
+            (colors.pass(), &mut self.pass),
+            (colors.retry(), &mut self.retry),
+            (colors.fail(), &mut self.fail),
+            (colors.skip(), &mut self.skip),
+            (colors.binary_id(), &mut self.test_list.binary_id),
+            (colors.test_name(), &mut self.test_list.test_name),
+        ];
+        for (spec, style) in overrides {
+            if let Some(spec) = spec {
+                *style = spec.to_style(truecolor);
+            }
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::NextestConfig;
+    use crate::{config::NextestConfig, test_list::test_helpers::make_single_binary_test_list};
+    use std::sync::Arc;
 
     #[test]
     fn no_capture_settings() {
@@ -844,12 +1850,12 @@ mod tests {
         assert!(reporter.no_capture, "no_capture is true");
         assert_eq!(
             reporter.failure_output,
-            TestOutputDisplay::Never,
+            Some(TestOutputDisplay::Never),
             "failure output is never, overriding other settings"
         );
         assert_eq!(
             reporter.success_output,
-            TestOutputDisplay::Never,
+            Some(TestOutputDisplay::Never),
             "success output is never, overriding other settings"
         );
         assert_eq!(
@@ -858,4 +1864,326 @@ mod tests {
             "status level is pass, overriding other settings"
         );
     }
+
+    #[test]
+    fn verbose_settings() {
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+
+        let mut builder = TestReporterBuilder::default();
+        builder.set_verbose(true);
+        let reporter = builder.build(&test_list, &profile);
+        assert_eq!(
+            reporter.status_level,
+            StatusLevel::Skip,
+            "verbose output shows skipped tests"
+        );
+
+        builder.set_status_level(StatusLevel::Fail);
+        let reporter = builder.build(&test_list, &profile);
+        assert_eq!(
+            reporter.status_level,
+            StatusLevel::Fail,
+            "an explicit status level takes precedence over verbose output"
+        );
+    }
+
+    #[test]
+    fn in_flight_tests_on_cancel() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        let fast = tests.next().expect("fast test exists");
+        let slow = tests.next().expect("slow test exists");
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut reporter = TestReporterBuilder::default().build(&test_list, &profile);
+
+        let events = vec![
+            TestEventKind::RunBeginCancel {
+                running: 2,
+                running_tests: vec![
+                    RunningTest {
+                        test_instance: slow,
+                        attempt: 2,
+                        elapsed: Duration::from_secs(3),
+                    },
+                    RunningTest {
+                        test_instance: fast,
+                        attempt: 1,
+                        elapsed: Duration::from_secs(1),
+                    },
+                ],
+                reason: CancelReason::TestFailure,
+            },
+            // Only the tests running when the run was first canceled are listed.
+            TestEventKind::RunBeginCancel {
+                running: 1,
+                running_tests: vec![RunningTest {
+                    test_instance: slow,
+                    attempt: 2,
+                    elapsed: Duration::from_secs(4),
+                }],
+                reason: CancelReason::Signal,
+            },
+            TestEventKind::RunFinished {
+                start_time: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::from_secs(5),
+                run_stats: RunStats {
+                    initial_run_count: 2,
+                    ..RunStats::default()
+                },
+            },
+        ];
+        let mut buf = Vec::new();
+        for kind in events {
+            reporter
+                .write_event_impl(&TestEvent::from_kind(kind), &mut buf)
+                .expect("writing to a Vec succeeds");
+        }
+        let output = String::from_utf8(buf).expect("output is valid UTF-8");
+        let in_flight: Vec<_> = output
+            .lines()
+            .filter(|line| line.contains("IN FLIGHT"))
+            .collect();
+        assert_eq!(
+            in_flight,
+            [
+                "   IN FLIGHT [   3.000s] fake-package::fake-binary tests::slow (attempt 2)",
+                "   IN FLIGHT [   1.000s] fake-package::fake-binary tests::fast",
+            ],
+            "in-flight tests are listed longest-running first, in output:\n{}",
+            output
+        );
+    }
+
+    #[test]
+    fn test_paused() {
+        let test_list = make_test_list();
+        let fast = test_list.iter_tests().next().expect("fast test exists");
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        // Paused tests are always shown, since the user has to act on them.
+        let mut builder = TestReporterBuilder::default();
+        builder.set_status_level(StatusLevel::None);
+        let mut reporter = builder.build(&test_list, &profile);
+
+        let mut buf = Vec::new();
+        reporter
+            .write_event_impl(
+                &TestEvent::from_kind(TestEventKind::TestPaused {
+                    test_instance: fast,
+                    pid: 1234,
+                }),
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(buf).expect("output is valid UTF-8"),
+            "      PAUSED             fake-package::fake-binary tests::fast: process 1234 is \
+             waiting for a debugger, press Enter to continue\n"
+        );
+    }
+
+    #[test]
+    fn running_tests_on_info() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        let fast = tests.next().expect("fast test exists");
+        let slow = tests.next().expect("slow test exists");
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut builder = TestReporterBuilder::default();
+        // Running tests are listed even at the lowest status level, since they were asked for.
+        builder.set_status_level(StatusLevel::None);
+        let mut reporter = builder.build(&test_list, &profile);
+
+        let mut buf = Vec::new();
+        reporter
+            .write_event_impl(
+                &TestEvent::from_kind(TestEventKind::RunInfo {
+                    running_tests: vec![
+                        RunningTest {
+                            test_instance: slow,
+                            attempt: 3,
+                            elapsed: Duration::from_millis(2500),
+                        },
+                        RunningTest {
+                            test_instance: fast,
+                            attempt: 1,
+                            elapsed: Duration::from_millis(100),
+                        },
+                    ],
+                }),
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(buf).expect("output is valid UTF-8"),
+            "        Info 2 tests running\n     \
+             RUNNING [   2.500s] fake-package::fake-binary tests::slow (attempt 3)\n     \
+             RUNNING [   0.100s] fake-package::fake-binary tests::fast\n"
+        );
+    }
+
+    #[test]
+    fn pass_rates_with_repeat() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        let fast = tests.next().expect("fast test exists");
+        let slow = tests.next().expect("slow test exists");
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+
+        let finished = |test_instance, result| TestEventKind::TestFinished {
+            test_instance,
+            run_statuses: ExecutionStatuses::new(vec![ExecuteStatus {
+                attempt: 1,
+                total_attempts: 1,
+                stdout_stderr: Arc::new((vec![], vec![])),
+                result,
+                start_time: SystemTime::UNIX_EPOCH,
+                time_taken: Duration::from_millis(10),
+                core_dump: None,
+                output_dir: None,
+            }]),
+        };
+        let pass_rates = |status_level| {
+            let mut builder = TestReporterBuilder::default();
+            builder.set_status_level(status_level);
+            let mut reporter = builder.build(&test_list, &profile);
+            let events = vec![
+                TestEventKind::RunStarted {
+                    test_list: &test_list,
+                    shuffle_seed: None,
+                    repeat: 3,
+                },
+                finished(fast, ExecutionResult::Pass),
+                finished(slow, ExecutionResult::Pass),
+                finished(fast, ExecutionResult::Pass),
+                finished(slow, ExecutionResult::Fail),
+                finished(fast, ExecutionResult::Pass),
+                finished(slow, ExecutionResult::Pass),
+                TestEventKind::RunFinished {
+                    start_time: SystemTime::UNIX_EPOCH,
+                    elapsed: Duration::from_secs(1),
+                    run_stats: RunStats {
+                        initial_run_count: 6,
+                        final_run_count: 6,
+                        passed: 5,
+                        failed: 1,
+                        ..RunStats::default()
+                    },
+                },
+            ];
+            let mut buf = Vec::new();
+            for kind in events {
+                reporter
+                    .write_event_impl(&TestEvent::from_kind(kind), &mut buf)
+                    .expect("writing to a Vec succeeds");
+            }
+            String::from_utf8(buf)
+                .expect("output is valid UTF-8")
+                .lines()
+                .filter(|line| line.contains("PASS RATE"))
+                .map(|line| line.to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            pass_rates(StatusLevel::Pass),
+            [
+                "   PASS RATE [    66.7%] fake-package::fake-binary tests::slow (2/3 passed)",
+                "   PASS RATE [   100.0%] fake-package::fake-binary tests::fast (3/3 passed)",
+            ],
+            "least reliable tests are listed first"
+        );
+        assert_eq!(
+            pass_rates(StatusLevel::Fail),
+            ["   PASS RATE [    66.7%] fake-package::fake-binary tests::slow (2/3 passed)"],
+            "tests that always passed are only listed at the pass status level"
+        );
+    }
+
+    #[test]
+    fn flaky_tests_in_summary() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        let fast = tests.next().expect("fast test exists");
+        let slow = tests.next().expect("slow test exists");
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+
+        let status = |attempt, result| ExecuteStatus {
+            attempt,
+            total_attempts: 3,
+            stdout_stderr: Arc::new((vec![], vec![])),
+            result,
+            start_time: SystemTime::UNIX_EPOCH,
+            time_taken: Duration::from_millis(1500),
+            core_dump: None,
+            output_dir: None,
+        };
+        let summary = |status_level| {
+            let mut builder = TestReporterBuilder::default();
+            builder.set_status_level(status_level);
+            let mut reporter = builder.build(&test_list, &profile);
+            let events = vec![
+                TestEventKind::TestFinished {
+                    test_instance: slow,
+                    run_statuses: ExecutionStatuses::new(vec![
+                        status(1, ExecutionResult::Fail),
+                        status(2, ExecutionResult::Pass),
+                    ]),
+                },
+                TestEventKind::TestFinished {
+                    test_instance: fast,
+                    run_statuses: ExecutionStatuses::new(vec![status(1, ExecutionResult::Pass)]),
+                },
+            ];
+            for kind in events {
+                reporter
+                    .write_event_impl(&TestEvent::from_kind(kind), io::sink())
+                    .expect("writing to a sink succeeds");
+            }
+            let mut buf = Vec::new();
+            reporter
+                .write_event_impl(
+                    &TestEvent::from_kind(TestEventKind::RunFinished {
+                        start_time: SystemTime::UNIX_EPOCH,
+                        elapsed: Duration::from_secs(3),
+                        run_stats: RunStats {
+                            initial_run_count: 2,
+                            final_run_count: 2,
+                            passed: 2,
+                            flaky: 1,
+                            ..RunStats::default()
+                        },
+                    }),
+                    &mut buf,
+                )
+                .expect("writing to a Vec succeeds");
+            String::from_utf8(buf)
+                .expect("output is valid UTF-8")
+                .lines()
+                .filter(|line| line.contains("FLAKY"))
+                .map(|line| line.to_owned())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            summary(StatusLevel::Retry),
+            ["   FLAKY 2/3 [   1.500s] fake-package::fake-binary tests::slow"],
+            "flaky tests are listed at the end of the run"
+        );
+        assert_eq!(
+            summary(StatusLevel::Fail),
+            Vec::<String>::new(),
+            "flaky tests aren't listed below the retry status level"
+        );
+    }
+
+    fn make_test_list() -> TestList<'static> {
+        make_single_binary_test_list("tests::fast: test\ntests::slow: test\n", "")
+    }
 }
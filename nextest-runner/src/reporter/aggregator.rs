@@ -6,34 +6,87 @@
 use crate::{
     config::{NextestJunitConfig, NextestProfile},
     errors::{JunitError, WriteEventError},
-    reporter::TestEvent,
-    runner::{ExecuteStatus, ExecutionDescription, ExecutionResult},
+    last_run::LastRun,
+    reporter::{
+        chrome_trace::MetadataChromeTrace, html::MetadataHtml, notify::MetadataNotify,
+        otlp::MetadataOtlp, TapReporter, TestEvent, TestEventKind,
+    },
+    runner::{ExecuteStatus, ExecutionDescription, ExecutionResult, TestPanic},
     test_list::TestInstance,
+    timings::TestTimings,
 };
-use camino::Utf8Path;
+use camino::{Utf8Path, Utf8PathBuf};
 use chrono::{DateTime, FixedOffset, Utc};
 use debug_ignore::DebugIgnore;
+use nextest_metadata::{
+    FlakyTestSummary, FlakyTestsSummary, RunSummary, TestOutcome, TestRunSummary,
+};
 use quick_junit::{NonSuccessKind, Report, TestCase, TestCaseStatus, TestRerun, TestSuite};
-use std::{collections::HashMap, fs::File, time::SystemTime};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    time::SystemTime,
+};
 
 #[derive(Clone, Debug)]
-#[allow(dead_code)]
 pub(crate) struct EventAggregator<'cfg> {
     store_dir: &'cfg Utf8Path,
+    // Whether timings and failed tests are recorded for use by later runs.
+    update_state: bool,
+    flaky: MetadataFlaky,
+    timings: MetadataTimings,
+    last_run: MetadataLastRun,
+    run_summary: MetadataRunSummary,
+    tap: Option<MetadataTap>,
+    html: Option<MetadataHtml>,
+    chrome_trace: Option<MetadataChromeTrace>,
+    otlp: Option<MetadataOtlp<'cfg>>,
+    notify: Option<MetadataNotify<'cfg>>,
     // TODO: log information in a JSONable report (converting that to XML later) instead of directly
     // writing it to XML
     junit: Option<MetadataJunit<'cfg>>,
 }
 
 impl<'cfg> EventAggregator<'cfg> {
-    pub(crate) fn new(profile: &'cfg NextestProfile<'cfg>) -> Self {
+    pub(crate) fn new(profile: &'cfg NextestProfile<'cfg>, replay: bool) -> Self {
         Self {
             store_dir: profile.store_dir(),
+            update_state: !replay,
+            flaky: MetadataFlaky::default(),
+            timings: MetadataTimings::default(),
+            last_run: MetadataLastRun::default(),
+            run_summary: MetadataRunSummary::default(),
+            tap: profile.tap_path().map(MetadataTap::new),
+            html: profile.html_path().map(MetadataHtml::new),
+            chrome_trace: profile.chrome_trace_path().map(MetadataChromeTrace::new),
+            otlp: profile.otlp().map(MetadataOtlp::new),
+            notify: profile.notify().map(MetadataNotify::new),
             junit: profile.junit().map(MetadataJunit::new),
         }
     }
 
-    pub(crate) fn write_event(&mut self, event: TestEvent<'cfg>) -> Result<(), WriteEventError> {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'cfg>) -> Result<(), WriteEventError> {
+        self.flaky.write_event(self.store_dir, event)?;
+        if self.update_state {
+            self.timings.write_event(self.store_dir, event)?;
+            self.last_run.write_event(self.store_dir, event)?;
+            self.run_summary.write_event(self.store_dir, event)?;
+        }
+        if let Some(tap) = &mut self.tap {
+            tap.write_event(event)?;
+        }
+        if let Some(html) = &mut self.html {
+            html.write_event(event)?;
+        }
+        if let Some(chrome_trace) = &mut self.chrome_trace {
+            chrome_trace.write_event(event)?;
+        }
+        if let Some(otlp) = &mut self.otlp {
+            otlp.write_event(event)?;
+        }
+        if let Some(notify) = &mut self.notify {
+            notify.write_event(event)?;
+        }
         if let Some(junit) = &mut self.junit {
             junit.write_event(event)?;
         }
@@ -41,6 +94,259 @@ impl<'cfg> EventAggregator<'cfg> {
     }
 }
 
+/// Records tests that passed only after being retried, and writes them out to the store directory
+/// at the end of the run.
+#[derive(Clone, Debug, Default)]
+struct MetadataFlaky {
+    flaky_tests: Vec<FlakyTestSummary>,
+}
+
+impl MetadataFlaky {
+    fn write_event(
+        &mut self,
+        store_dir: &Utf8Path,
+        event: &TestEvent<'_>,
+    ) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                if let ExecutionDescription::Flaky { last_status, .. } = run_statuses.describe() {
+                    self.flaky_tests.push(FlakyTestSummary {
+                        binary_id: test_instance.bin_info.binary_id.clone(),
+                        test_name: test_instance.name.to_owned(),
+                        passed_on_attempt: last_status.attempt,
+                        total_attempts: last_status.total_attempts,
+                    });
+                }
+            }
+            TestEventKind::RunFinished { .. } => {
+                let mut summary = FlakyTestsSummary::default();
+                summary.flaky_tests = std::mem::take(&mut self.flaky_tests);
+                summary.flaky_tests.sort_by(|a, b| {
+                    (&a.binary_id, &a.test_name).cmp(&(&b.binary_id, &b.test_name))
+                });
+
+                std::fs::create_dir_all(store_dir).map_err(|error| WriteEventError::Fs {
+                    file: store_dir.to_path_buf(),
+                    error,
+                })?;
+                let path = store_dir.join(FlakyTestsSummary::FILE_NAME);
+                let f = File::create(&path).map_err(|error| WriteEventError::Fs {
+                    file: path.clone(),
+                    error,
+                })?;
+                serde_json::to_writer_pretty(f, &summary).map_err(WriteEventError::Json)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Records how long each test took, and writes the timings out to the store directory at the end of
+/// the run.
+///
+/// Timings are merged with those recorded by previous runs, so that tests which weren't run this
+/// time (e.g. because they were filtered out) keep their timings.
+#[derive(Clone, Debug, Default)]
+struct MetadataTimings {
+    timings: TestTimings,
+}
+
+impl MetadataTimings {
+    fn write_event(
+        &mut self,
+        store_dir: &Utf8Path,
+        event: &TestEvent<'_>,
+    ) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::RunStarted { test_list, .. } => {
+                // Timings are only used as a scheduling hint, so if earlier timings can't be read,
+                // start over.
+                self.timings = TestTimings::read(store_dir).unwrap_or_default();
+                self.timings.retain_listed(test_list);
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                self.timings.record(
+                    &test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    run_statuses.last_status().time_taken,
+                );
+            }
+            TestEventKind::RunFinished { .. } => {
+                self.timings.write(store_dir)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Records which tests are failing, and writes them out to the store directory at the end of the
+/// run.
+///
+/// Like timings, failed tests are merged with those recorded by previous runs. A test stays failing
+/// until it passes in a later run.
+#[derive(Clone, Debug, Default)]
+struct MetadataLastRun {
+    last_run: LastRun,
+    // Tests that failed in this run. With --repeat, a test that fails any of its runs is failing.
+    failed_this_run: HashSet<(String, String)>,
+}
+
+impl MetadataLastRun {
+    fn write_event(
+        &mut self,
+        store_dir: &Utf8Path,
+        event: &TestEvent<'_>,
+    ) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::RunStarted { test_list, .. } => {
+                // If failed tests from earlier runs can't be read, start over.
+                self.last_run = LastRun::read(store_dir).unwrap_or_default();
+                self.last_run.retain_listed(test_list);
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                let key = (
+                    test_instance.bin_info.binary_id.clone(),
+                    test_instance.name.to_owned(),
+                );
+                let passed = run_statuses.last_status().result == ExecutionResult::Pass
+                    && !self.failed_this_run.contains(&key);
+                if !passed {
+                    self.failed_this_run.insert(key);
+                }
+                self.last_run.record(test_instance, passed);
+            }
+            TestEventKind::RunFinished { .. } => {
+                self.last_run.write(store_dir)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Records the outcome of every test, and writes a summary of the run out to the store directory at
+/// the end of the run.
+#[derive(Clone, Debug, Default)]
+struct MetadataRunSummary {
+    tests: Vec<TestRunSummary>,
+}
+
+impl MetadataRunSummary {
+    fn write_event(
+        &mut self,
+        store_dir: &Utf8Path,
+        event: &TestEvent<'_>,
+    ) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                self.tests.push(run_statuses.to_summary(test_instance));
+            }
+            TestEventKind::TestSkipped {
+                test_instance,
+                reason,
+                ..
+            } => {
+                self.tests.push(TestRunSummary {
+                    binary_id: test_instance.bin_info.binary_id.clone(),
+                    test_name: test_instance.name.to_owned(),
+                    outcome: TestOutcome::Skipped,
+                    skip_reason: Some(*reason),
+                    attempts: vec![],
+                });
+            }
+            TestEventKind::TestCanceled { test_instance } => {
+                self.tests.push(TestRunSummary {
+                    binary_id: test_instance.bin_info.binary_id.clone(),
+                    test_name: test_instance.name.to_owned(),
+                    outcome: TestOutcome::Canceled,
+                    skip_reason: None,
+                    attempts: vec![],
+                });
+            }
+            TestEventKind::RunFinished {
+                elapsed, run_stats, ..
+            } => {
+                let mut summary = RunSummary::default();
+                summary.elapsed_secs = elapsed.as_secs_f64();
+                summary.run_stats = run_stats.to_summary();
+                summary.tests = std::mem::take(&mut self.tests);
+                // This is a stable sort, so with --repeat, runs of a test stay in the order they
+                // finished in.
+                summary.tests.sort_by(|a, b| {
+                    (&a.binary_id, &a.test_name).cmp(&(&b.binary_id, &b.test_name))
+                });
+
+                std::fs::create_dir_all(store_dir).map_err(|error| WriteEventError::Fs {
+                    file: store_dir.to_path_buf(),
+                    error,
+                })?;
+                let path = store_dir.join(RunSummary::FILE_NAME);
+                let f = File::create(&path).map_err(|error| WriteEventError::Fs {
+                    file: path.clone(),
+                    error,
+                })?;
+                serde_json::to_writer_pretty(f, &summary).map_err(WriteEventError::Json)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// Buffers TAP output for the run, and writes it out to the configured path at the end.
+#[derive(Clone, Debug)]
+struct MetadataTap {
+    path: Utf8PathBuf,
+    reporter: TapReporter,
+    buf: DebugIgnore<Vec<u8>>,
+}
+
+impl MetadataTap {
+    fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            reporter: TapReporter::new(),
+            buf: DebugIgnore(vec![]),
+        }
+    }
+
+    fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        self.reporter.write_event(event, &mut *self.buf)?;
+
+        if let TestEventKind::RunFinished { .. } = &event.kind {
+            let tap_dir = self.path.parent().expect("tap path must have a parent");
+            std::fs::create_dir_all(tap_dir).map_err(|error| WriteEventError::Fs {
+                file: tap_dir.to_path_buf(),
+                error,
+            })?;
+            std::fs::write(&self.path, &*self.buf).map_err(|error| WriteEventError::Fs {
+                file: self.path.clone(),
+                error,
+            })?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Clone, Debug)]
 struct MetadataJunit<'cfg> {
     config: NextestJunitConfig<'cfg>,
@@ -55,15 +361,18 @@ impl<'cfg> MetadataJunit<'cfg> {
         }
     }
 
-    pub(crate) fn write_event(&mut self, event: TestEvent<'cfg>) -> Result<(), WriteEventError> {
-        match event {
-            TestEvent::RunStarted { .. } => {}
-            TestEvent::TestStarted { .. } => {}
-            TestEvent::TestSlow { .. } => {}
-            TestEvent::TestRetry { .. } => {
+    pub(crate) fn write_event(&mut self, event: &TestEvent<'cfg>) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::RunStarted { .. } => {}
+            TestEventKind::TestStarted { .. } => {}
+            TestEventKind::TestSlow { .. }
+            | TestEventKind::TestSilent { .. }
+            | TestEventKind::TestPaused { .. } => {}
+            TestEventKind::ScriptFinished { .. } => {}
+            TestEventKind::TestRetry { .. } => {
                 // Retries are recorded in TestFinished.
             }
-            TestEvent::TestFinished {
+            TestEventKind::TestFinished {
                 test_instance,
                 run_statuses,
             } => {
@@ -71,11 +380,15 @@ impl<'cfg> MetadataJunit<'cfg> {
                     match run_status.result {
                         ExecutionResult::Fail => (NonSuccessKind::Failure, "test failure"),
                         ExecutionResult::ExecFail => (NonSuccessKind::Error, "execution failure"),
+                        ExecutionResult::Timeout => (NonSuccessKind::Failure, "test timeout"),
+                        ExecutionResult::ResourceLimit => {
+                            (NonSuccessKind::Failure, "resource limit exceeded")
+                        }
                         ExecutionResult::Pass => unreachable!("this is a failure status"),
                     }
                 }
 
-                let testsuite = self.testsuite_for(test_instance);
+                let testsuite = self.testsuite_for(*test_instance);
 
                 let (mut testcase_status, main_status, reruns) = match run_statuses.describe() {
                     ExecutionDescription::Success { single_status } => {
@@ -106,17 +419,36 @@ impl<'cfg> MetadataJunit<'cfg> {
                         .set_type(ty)
                         .set_system_out_lossy(rerun.stdout())
                         .set_system_err_lossy(rerun.stderr());
+                    if let Some(panic) = rerun.panic() {
+                        test_rerun
+                            .set_message(&panic.message)
+                            .set_description(panic_description(&panic));
+                        if let Some(backtrace) = panic.backtrace {
+                            test_rerun.set_stack_trace(backtrace);
+                        }
+                    }
                     // TODO: also publish time? it won't be standard JUnit (but maybe that's ok?)
                     testcase_status.add_rerun(test_rerun);
                 }
 
-                // TODO: set message/description on testcase_status?
+                if !main_status.result.is_success() {
+                    if let Some(panic) = main_status.panic() {
+                        testcase_status
+                            .set_message(&panic.message)
+                            .set_description(panic_description(&panic));
+                    }
+                }
 
                 let mut testcase = TestCase::new(test_instance.name, testcase_status);
                 testcase
                     .set_classname(&test_instance.bin_info.binary_id)
                     .set_timestamp(to_datetime(main_status.start_time))
                     .set_time(main_status.time_taken);
+                if let Some(output_dir) = &main_status.output_dir {
+                    testcase
+                        .extra
+                        .insert("output-dir".to_owned(), output_dir.to_string());
+                }
 
                 // TODO: also provide stdout and stderr for passing tests?
                 // TODO: allure seems to want the output to be in a format where text files are
@@ -132,7 +464,39 @@ impl<'cfg> MetadataJunit<'cfg> {
 
                 testsuite.add_test_case(testcase);
             }
-            TestEvent::TestSkipped { .. } => {
+            TestEventKind::TestSkipped {
+                test_instance,
+                exclude_reason: Some(exclude_reason),
+                ..
+            } => {
+                // Excluded tests are always skipped, so reporting them doesn't cause issues when
+                // aggregating runs. The reason is kept as an audit trail.
+                let mut testcase_status = TestCaseStatus::skipped();
+                testcase_status
+                    .set_message(exclude_reason.as_str())
+                    .set_type("excluded");
+                let testcase = TestCase::new(test_instance.name, testcase_status);
+                self.testsuite_for(*test_instance).add_test_case(testcase);
+            }
+            TestEventKind::TestRecorded {
+                test_instance,
+                recording_dir,
+                ..
+            } => {
+                // The test case was added when the test finished.
+                let testsuite = self.testsuite_for(*test_instance);
+                if let Some(testcase) = testsuite
+                    .test_cases
+                    .iter_mut()
+                    .rev()
+                    .find(|testcase| testcase.name == test_instance.name)
+                {
+                    testcase
+                        .extra
+                        .insert("recording".to_owned(), recording_dir.to_string());
+                }
+            }
+            TestEventKind::TestSkipped { .. } => {
                 // TODO: report skipped tests? causes issues if we want to aggregate runs across
                 // skipped and non-skipped tests. Probably needs to be made configurable.
 
@@ -144,8 +508,18 @@ impl<'cfg> MetadataJunit<'cfg> {
                 //
                 // testsuite.add_testcase(testcase);
             }
-            TestEvent::RunBeginCancel { .. } => {}
-            TestEvent::RunFinished {
+            TestEventKind::TestCanceled { test_instance } => {
+                // Unlike filtered-out tests, canceled tests were meant to run, so they're always
+                // reported.
+                let mut testcase_status = TestCaseStatus::skipped();
+                testcase_status
+                    .set_message("test run canceled")
+                    .set_type("canceled");
+                let testcase = TestCase::new(test_instance.name, testcase_status);
+                self.testsuite_for(*test_instance).add_test_case(testcase);
+            }
+            TestEventKind::RunInfo { .. } | TestEventKind::RunBeginCancel { .. } => {}
+            TestEventKind::RunFinished {
                 start_time,
                 elapsed,
                 ..
@@ -153,8 +527,8 @@ impl<'cfg> MetadataJunit<'cfg> {
                 // Write out the report to the given file.
                 let mut report = Report::new(self.config.report_name());
                 report
-                    .set_timestamp(to_datetime(start_time))
-                    .set_time(elapsed)
+                    .set_timestamp(to_datetime(*start_time))
+                    .set_time(*elapsed)
                     .add_test_suites(self.test_suites.drain().map(|(_, testsuite)| testsuite));
 
                 let junit_path = self.config.path();
@@ -185,8 +559,205 @@ impl<'cfg> MetadataJunit<'cfg> {
     }
 }
 
+/// Describes where a test panicked, followed by the backtrace if there is one.
+fn panic_description(panic: &TestPanic) -> String {
+    let mut description = format!("thread '{}' panicked", panic.thread);
+    if let Some(location) = &panic.location {
+        description.push_str(&format!(" at {}", location));
+    }
+    if let Some(backtrace) = &panic.backtrace {
+        description.push_str(&format!("\nstack backtrace:\n{}", backtrace));
+    }
+    description
+}
+
 fn to_datetime(system_time: SystemTime) -> DateTime<FixedOffset> {
     // Serialize using UTC.
     let datetime = DateTime::<Utc>::from(system_time);
     datetime.into()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        reporter::TestEvent,
+        runner::{ExecutionStatuses, RunStats},
+        test_list::{test_helpers::make_single_binary_test_list, TestList},
+    };
+    use nextest_metadata::{ExecutionResultSummary, MismatchReason};
+    use std::{sync::Arc, time::Duration};
+
+    fn make_test_list() -> TestList<'static> {
+        make_single_binary_test_list(
+            "tests::canceled: test\ntests::flaky: test\n",
+            "tests::ignored: test\n",
+        )
+    }
+
+    fn make_status(attempt: usize, result: ExecutionResult) -> ExecuteStatus {
+        ExecuteStatus {
+            attempt,
+            total_attempts: 3,
+            stdout_stderr: Arc::new((vec![], vec![])),
+            result,
+            start_time: SystemTime::UNIX_EPOCH,
+            time_taken: Duration::from_millis(250),
+            core_dump: None,
+            output_dir: None,
+        }
+    }
+
+    #[test]
+    fn write_flaky_tests() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        // This test is canceled in other tests, but passes here.
+        let passing = tests.next().expect("canceled test exists");
+        let flaky = tests.next().expect("flaky test exists");
+
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let store_dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        let mut metadata_flaky = MetadataFlaky::default();
+        let events = vec![
+            TestEventKind::TestFinished {
+                test_instance: flaky,
+                run_statuses: ExecutionStatuses::new(vec![
+                    make_status(1, ExecutionResult::Fail),
+                    make_status(2, ExecutionResult::Timeout),
+                    make_status(3, ExecutionResult::Pass),
+                ]),
+            },
+            TestEventKind::TestFinished {
+                test_instance: passing,
+                run_statuses: ExecutionStatuses::new(vec![make_status(1, ExecutionResult::Pass)]),
+            },
+            TestEventKind::RunFinished {
+                start_time: SystemTime::UNIX_EPOCH,
+                elapsed: Duration::from_secs(1),
+                run_stats: RunStats::default(),
+            },
+        ];
+        for kind in events {
+            metadata_flaky
+                .write_event(store_dir, &TestEvent::from_kind(kind))
+                .expect("writing event succeeds");
+        }
+
+        let json = std::fs::read_to_string(store_dir.join(FlakyTestsSummary::FILE_NAME))
+            .expect("flaky tests were written");
+        let summary = FlakyTestsSummary::parse_json(json).expect("summary is valid");
+        assert_eq!(
+            summary.flaky_tests,
+            [FlakyTestSummary {
+                binary_id: "fake-package::fake-binary".to_owned(),
+                test_name: "tests::flaky".to_owned(),
+                passed_on_attempt: 3,
+                total_attempts: 3,
+            }],
+            "only tests that passed on a retry are flaky"
+        );
+    }
+
+    #[test]
+    fn write_run_summary() {
+        let test_list = make_test_list();
+        let mut tests = test_list.iter_tests();
+        let canceled = tests.next().expect("canceled test exists");
+        let flaky = tests.next().expect("flaky test exists");
+        let ignored = tests.next().expect("ignored test exists");
+
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let store_dir = Utf8Path::from_path(dir.path())
+            .expect("temp dir is UTF-8")
+            .join("store");
+        let mut run_summary = MetadataRunSummary::default();
+        // Tests finish out of order.
+        let events = vec![
+            TestEventKind::TestFinished {
+                test_instance: flaky,
+                run_statuses: ExecutionStatuses::new(vec![
+                    make_status(1, ExecutionResult::Fail),
+                    make_status(2, ExecutionResult::Pass),
+                ]),
+            },
+            TestEventKind::TestSkipped {
+                test_instance: ignored,
+                reason: MismatchReason::Ignored,
+                exclude_reason: None,
+            },
+            TestEventKind::TestCanceled {
+                test_instance: canceled,
+            },
+        ];
+        for kind in events {
+            run_summary
+                .write_event(&store_dir, &TestEvent::from_kind(kind))
+                .expect("recording event succeeds");
+        }
+        assert!(
+            !store_dir.exists(),
+            "summary isn't written until the run finishes"
+        );
+
+        run_summary
+            .write_event(
+                &store_dir,
+                &TestEvent::from_kind(TestEventKind::RunFinished {
+                    start_time: SystemTime::UNIX_EPOCH,
+                    elapsed: Duration::from_millis(1500),
+                    run_stats: RunStats {
+                        initial_run_count: 2,
+                        final_run_count: 1,
+                        passed: 1,
+                        flaky: 1,
+                        skipped: 1,
+                        canceled: 1,
+                        ..RunStats::default()
+                    },
+                }),
+            )
+            .expect("writing summary succeeds");
+        let json = std::fs::read_to_string(store_dir.join(RunSummary::FILE_NAME))
+            .expect("summary was written");
+        let summary = RunSummary::parse_json(json).expect("summary is valid");
+
+        assert_eq!(summary.elapsed_secs, 1.5);
+        assert_eq!(summary.run_stats.flaky, 1);
+        assert_eq!(summary.run_stats.canceled, 1);
+        let tests: Vec<_> = summary
+            .tests
+            .iter()
+            .map(|test| (test.test_name.as_str(), test.outcome, test.skip_reason))
+            .collect();
+        assert_eq!(
+            tests,
+            [
+                ("tests::canceled", TestOutcome::Canceled, None),
+                ("tests::flaky", TestOutcome::Flaky, None),
+                (
+                    "tests::ignored",
+                    TestOutcome::Skipped,
+                    Some(MismatchReason::Ignored)
+                ),
+            ],
+            "tests are sorted by name"
+        );
+        let attempts: Vec<_> = summary.tests[1]
+            .attempts
+            .iter()
+            .map(|attempt| (attempt.attempt, attempt.result))
+            .collect();
+        assert_eq!(
+            attempts,
+            [
+                (1, ExecutionResultSummary::Fail),
+                (2, ExecutionResultSummary::Pass)
+            ]
+        );
+        assert!(
+            summary.tests[2].attempts.is_empty(),
+            "skipped tests aren't run"
+        );
+    }
+}
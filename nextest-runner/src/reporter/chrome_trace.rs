@@ -0,0 +1,325 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Timelines of test runs, in the Chrome trace event format.
+//!
+//! The trace can be loaded into `chrome://tracing` or [Perfetto](https://ui.perfetto.dev) to see
+//! when each test ran, and on which slot. Slots are assigned as tests start: each test takes the
+//! lowest-numbered slot that's free at the time, and keeps it until it finishes, including any
+//! retries.
+
+use crate::{
+    errors::WriteEventError,
+    reporter::{TestEvent, TestEventKind},
+    runner::{ExecutionResult, ExecutionStatuses},
+    script::{ScriptKind, ScriptStatus},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use serde::Serialize;
+use serde_json::json;
+use std::time::SystemTime;
+use uuid::Uuid;
+
+/// The thread ID that setup and teardown scripts are shown on. Slots start at 1.
+const SCRIPT_TID: usize = 0;
+
+/// Collects the start and end times of tests over the course of a run, and writes out a Chrome
+/// trace at the end.
+#[derive(Clone, Debug)]
+pub(super) struct MetadataChromeTrace {
+    path: Utf8PathBuf,
+    run_id: Uuid,
+    start_time: SystemTime,
+    // The test currently running in each slot, by binary ID and test name.
+    slots: Vec<Option<(String, String)>>,
+    has_scripts: bool,
+    trace_events: Vec<TraceEvent>,
+}
+
+impl MetadataChromeTrace {
+    pub(super) fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            run_id: Uuid::nil(),
+            start_time: SystemTime::UNIX_EPOCH,
+            slots: vec![],
+            has_scripts: false,
+            trace_events: vec![],
+        }
+    }
+
+    pub(super) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::RunStarted { .. } => {
+                self.run_id = event.run_id;
+                self.start_time = event.timestamp;
+            }
+            TestEventKind::TestStarted { test_instance } => {
+                self.test_started(&test_instance.bin_info.binary_id, test_instance.name);
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                self.test_finished(
+                    &test_instance.bin_info.binary_id,
+                    test_instance.name,
+                    run_statuses,
+                );
+            }
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => {
+                self.script_finished(script_name, *kind, status);
+            }
+            TestEventKind::RunFinished { .. } => {
+                write_file(&self.path, &self.to_trace())?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn test_started(&mut self, binary_id: &str, test_name: &str) {
+        let test = Some((binary_id.to_owned(), test_name.to_owned()));
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => *slot = test,
+            None => self.slots.push(test),
+        }
+    }
+
+    fn test_finished(
+        &mut self,
+        binary_id: &str,
+        test_name: &str,
+        run_statuses: &ExecutionStatuses,
+    ) {
+        // With --repeat, several runs of a test may be in progress at the same time. They can't be
+        // told apart, so the lowest-numbered slot is freed.
+        let slot_index = self.slots.iter().position(|slot| match slot {
+            Some((slot_binary_id, slot_test_name)) => {
+                slot_binary_id == binary_id && slot_test_name == test_name
+            }
+            None => false,
+        });
+        let slot_index = match slot_index {
+            Some(slot_index) => {
+                self.slots[slot_index] = None;
+                slot_index
+            }
+            // This shouldn't happen, but a test that wasn't seen starting can still be shown.
+            None => {
+                self.slots.push(None);
+                self.slots.len() - 1
+            }
+        };
+
+        for status in run_statuses.iter() {
+            self.trace_events.push(TraceEvent {
+                name: test_name.to_owned(),
+                cat: "test",
+                ph: "X",
+                ts: self.micros_since_start(status.start_time),
+                dur: Some(status.time_taken.as_secs_f64() * 1_000_000.0),
+                pid: 1,
+                tid: slot_index + 1,
+                args: json!({
+                    "binary-id": binary_id,
+                    "attempt": status.attempt,
+                    "total-attempts": status.total_attempts,
+                    "result": result_str(status.result),
+                }),
+            });
+        }
+    }
+
+    fn script_finished(&mut self, script_name: &str, kind: ScriptKind, status: &ScriptStatus) {
+        let kind_str = match kind {
+            ScriptKind::Setup => "setup",
+            ScriptKind::Teardown => "teardown",
+        };
+        self.has_scripts = true;
+        self.trace_events.push(TraceEvent {
+            name: format!("{} ({})", script_name, kind_str),
+            cat: "script",
+            ph: "X",
+            ts: self.micros_since_start(status.start_time),
+            dur: Some(status.time_taken.as_secs_f64() * 1_000_000.0),
+            pid: 1,
+            tid: SCRIPT_TID,
+            args: json!({ "result": result_str(status.result) }),
+        });
+    }
+
+    fn micros_since_start(&self, time: SystemTime) -> f64 {
+        time.duration_since(self.start_time)
+            .unwrap_or_default()
+            .as_secs_f64()
+            * 1_000_000.0
+    }
+
+    fn to_trace(&self) -> serde_json::Value {
+        // Metadata events give the process and each thread a name.
+        let mut trace_events = vec![TraceEvent::metadata(
+            "process_name",
+            0,
+            format!("nextest run {}", self.run_id),
+        )];
+        if self.has_scripts {
+            trace_events.push(TraceEvent::metadata(
+                "thread_name",
+                SCRIPT_TID,
+                "scripts".to_owned(),
+            ));
+        }
+        trace_events.extend(
+            (1..=self.slots.len())
+                .map(|tid| TraceEvent::metadata("thread_name", tid, format!("slot {}", tid))),
+        );
+        trace_events.extend(self.trace_events.iter().cloned());
+
+        json!({
+            "traceEvents": trace_events,
+            "displayTimeUnit": "ms",
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct TraceEvent {
+    name: String,
+    cat: &'static str,
+    ph: &'static str,
+    ts: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    dur: Option<f64>,
+    pid: u32,
+    tid: usize,
+    args: serde_json::Value,
+}
+
+impl TraceEvent {
+    fn metadata(name: &'static str, tid: usize, value: String) -> Self {
+        Self {
+            name: name.to_owned(),
+            cat: "__metadata",
+            ph: "M",
+            ts: 0.0,
+            dur: None,
+            pid: 1,
+            tid,
+            args: json!({ "name": value }),
+        }
+    }
+}
+
+fn result_str(result: ExecutionResult) -> &'static str {
+    match result {
+        ExecutionResult::Pass => "pass",
+        ExecutionResult::Fail => "fail",
+        ExecutionResult::ExecFail => "exec-fail",
+        ExecutionResult::Timeout => "timeout",
+        ExecutionResult::ResourceLimit => "resource-limit",
+    }
+}
+
+fn write_file(path: &Utf8Path, trace: &serde_json::Value) -> Result<(), WriteEventError> {
+    let contents = serde_json::to_vec(trace).map_err(WriteEventError::Json)?;
+    let dir = path.parent().expect("trace path must have a parent");
+    std::fs::create_dir_all(dir).map_err(|error| WriteEventError::Fs {
+        file: dir.to_path_buf(),
+        error,
+    })?;
+    std::fs::write(path, contents).map_err(|error| WriteEventError::Fs {
+        file: path.to_path_buf(),
+        error,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::runner::ExecuteStatus;
+    use std::{sync::Arc, time::Duration};
+
+    fn statuses(start_ms: u64, durations_ms: &[u64]) -> ExecutionStatuses {
+        let mut start_time = SystemTime::UNIX_EPOCH + Duration::from_millis(start_ms);
+        let statuses = durations_ms
+            .iter()
+            .enumerate()
+            .map(|(idx, &duration_ms)| {
+                let status = ExecuteStatus {
+                    attempt: idx + 1,
+                    total_attempts: durations_ms.len(),
+                    stdout_stderr: Arc::new((vec![], vec![])),
+                    result: if idx + 1 == durations_ms.len() {
+                        ExecutionResult::Pass
+                    } else {
+                        ExecutionResult::Fail
+                    },
+                    start_time,
+                    time_taken: Duration::from_millis(duration_ms),
+                    core_dump: None,
+                    output_dir: None,
+                };
+                start_time += Duration::from_millis(duration_ms);
+                status
+            })
+            .collect();
+        ExecutionStatuses::new(statuses)
+    }
+
+    #[test]
+    fn assigns_free_slots() {
+        let mut trace = MetadataChromeTrace::new("/fake/trace.json".into());
+        trace.test_started("my-crate", "a");
+        trace.test_started("my-crate", "b");
+        trace.test_finished("my-crate", "a", &statuses(0, &[10]));
+        // a's slot is free again, so c takes it.
+        trace.test_started("my-crate", "c");
+        trace.test_finished("my-crate", "b", &statuses(0, &[5, 15]));
+        trace.test_finished("my-crate", "c", &statuses(10, &[10]));
+
+        let trace = trace.to_trace();
+        let events = trace["traceEvents"]
+            .as_array()
+            .expect("events are an array");
+        let thread_names: Vec<_> = events
+            .iter()
+            .filter(|event| event["name"] == "thread_name")
+            .map(|event| event["args"]["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            thread_names,
+            vec!["slot 1", "slot 2"],
+            "two slots were used"
+        );
+
+        let spans: Vec<_> = events
+            .iter()
+            .filter(|event| event["ph"] == "X")
+            .map(|event| {
+                (
+                    event["name"].as_str().unwrap(),
+                    event["tid"].as_u64().unwrap(),
+                    event["ts"].as_f64().unwrap(),
+                    event["dur"].as_f64().unwrap(),
+                    event["args"]["result"].as_str().unwrap(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            spans,
+            vec![
+                ("a", 1, 0.0, 10_000.0, "pass"),
+                // Each attempt is a separate span, on the same slot.
+                ("b", 2, 0.0, 5_000.0, "fail"),
+                ("b", 2, 5_000.0, 15_000.0, "pass"),
+                ("c", 1, 10_000.0, 10_000.0, "pass"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,414 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Self-contained HTML reports.
+//!
+//! The report is a single HTML file with inline styles and no external resources, so that it can
+//! be archived as a CI artifact and opened directly in a browser.
+
+use crate::{
+    errors::WriteEventError,
+    reporter::{TestEvent, TestEventKind},
+    runner::{ExecuteStatus, ExecutionDescription, ExecutionResult, ExecutionStatuses, RunStats},
+    script::{ScriptKind, ScriptStatus},
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, Utc};
+use debug_ignore::DebugIgnore;
+use std::{
+    fmt::{self, Write},
+    time::{Duration, SystemTime},
+};
+
+/// Collects test results over the course of a run, and writes out an HTML report at the end.
+#[derive(Clone, Debug)]
+pub(super) struct MetadataHtml {
+    path: Utf8PathBuf,
+    tests: DebugIgnore<Vec<HtmlTest>>,
+    scripts: DebugIgnore<Vec<HtmlScript>>,
+}
+
+impl MetadataHtml {
+    pub(super) fn new(path: Utf8PathBuf) -> Self {
+        Self {
+            path,
+            tests: DebugIgnore(vec![]),
+            scripts: DebugIgnore(vec![]),
+        }
+    }
+
+    pub(super) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                self.tests.push(HtmlTest {
+                    binary_id: test_instance.bin_info.binary_id.clone(),
+                    test_name: test_instance.name.to_owned(),
+                    run_statuses: run_statuses.clone(),
+                });
+            }
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => {
+                self.scripts.push(HtmlScript {
+                    script_name: script_name.clone(),
+                    kind: *kind,
+                    status: status.clone(),
+                });
+            }
+            TestEventKind::RunFinished {
+                start_time,
+                elapsed,
+                run_stats,
+            } => {
+                self.tests.sort_by(|a, b| {
+                    (&a.binary_id, &a.test_name).cmp(&(&b.binary_id, &b.test_name))
+                });
+                let mut html = String::new();
+                render_report(
+                    &mut html,
+                    *start_time,
+                    *elapsed,
+                    run_stats,
+                    &self.tests,
+                    &self.scripts,
+                )
+                .expect("writing to a String is infallible");
+                write_file(&self.path, &html)?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+struct HtmlTest {
+    binary_id: String,
+    test_name: String,
+    run_statuses: ExecutionStatuses,
+}
+
+#[derive(Clone, Debug)]
+struct HtmlScript {
+    script_name: String,
+    kind: ScriptKind,
+    status: ScriptStatus,
+}
+
+fn write_file(path: &Utf8Path, contents: &str) -> Result<(), WriteEventError> {
+    let dir = path.parent().expect("html path must have a parent");
+    std::fs::create_dir_all(dir).map_err(|error| WriteEventError::Fs {
+        file: dir.to_path_buf(),
+        error,
+    })?;
+    std::fs::write(path, contents).map_err(|error| WriteEventError::Fs {
+        file: path.to_path_buf(),
+        error,
+    })
+}
+
+const STYLE: &str = "\
+body { font-family: sans-serif; margin: 2em; }
+table { border-collapse: collapse; margin-bottom: 2em; }
+th, td { border: 1px solid #ccc; padding: 0.3em 0.6em; text-align: left; }
+td.duration { text-align: right; font-family: monospace; }
+pre { background: #f6f6f6; padding: 0.5em; overflow-x: auto; }
+.pass { color: #080; font-weight: bold; }
+.flaky { color: #b80; font-weight: bold; }
+.fail { color: #c00; font-weight: bold; }
+";
+
+fn render_report(
+    out: &mut String,
+    start_time: SystemTime,
+    elapsed: Duration,
+    run_stats: &RunStats,
+    tests: &[HtmlTest],
+    scripts: &[HtmlScript],
+) -> fmt::Result {
+    let start_time = DateTime::<Utc>::from(start_time);
+
+    writeln!(out, "<!DOCTYPE html>")?;
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">")?;
+    writeln!(out, "<title>nextest run report</title>")?;
+    writeln!(out, "<style>\n{}</style>\n</head>\n<body>", STYLE)?;
+    writeln!(out, "<h1>nextest run report</h1>")?;
+    writeln!(
+        out,
+        "<p>Started at {}, took {:.3}s.</p>",
+        start_time.to_rfc3339(),
+        elapsed.as_secs_f64()
+    )?;
+
+    // Summary statistics.
+    writeln!(out, "<h2>Summary</h2>\n<table>")?;
+    let rows = [
+        ("Tests run", run_stats.final_run_count),
+        ("Passed", run_stats.passed),
+        ("Flaky", run_stats.flaky),
+        ("Failed", run_stats.failed),
+        ("Execution failures", run_stats.exec_failed),
+        ("Timed out", run_stats.timed_out),
+        ("Over resource limits", run_stats.resource_limited),
+        ("Skipped", run_stats.skipped),
+        ("Canceled", run_stats.canceled),
+        ("Scripts failed", run_stats.scripts_failed),
+    ];
+    for (name, count) in rows {
+        writeln!(out, "<tr><th>{}</th><td>{}</td></tr>", name, count)?;
+    }
+    writeln!(out, "</table>")?;
+
+    // Per-test results.
+    writeln!(out, "<h2>Tests</h2>\n<table>")?;
+    writeln!(
+        out,
+        "<tr><th>Status</th><th>Binary ID</th><th>Test</th><th>Duration</th><th>Attempts</th></tr>"
+    )?;
+    for test in tests {
+        let last_status = test.run_statuses.last_status();
+        writeln!(
+            out,
+            "<tr><td class=\"{}\">{}</td><td>{}</td><td>{}</td>\
+             <td class=\"duration\">{:.3}s</td><td>{}</td></tr>",
+            status_class(&test.run_statuses),
+            status_str(&test.run_statuses),
+            Escape(&test.binary_id),
+            Escape(&test.test_name),
+            last_status.time_taken.as_secs_f64(),
+            test.run_statuses.len(),
+        )?;
+    }
+    writeln!(out, "</table>")?;
+
+    // Output and retry history for failing and flaky tests.
+    let mut wrote_header = false;
+    for test in tests {
+        if matches!(
+            test.run_statuses.describe(),
+            ExecutionDescription::Success { .. }
+        ) {
+            continue;
+        }
+        if !wrote_header {
+            writeln!(out, "<h2>Failures and retries</h2>")?;
+            wrote_header = true;
+        }
+
+        writeln!(
+            out,
+            "<h3><span class=\"{}\">{}</span> {} {}</h3>",
+            status_class(&test.run_statuses),
+            status_str(&test.run_statuses),
+            Escape(&test.binary_id),
+            Escape(&test.test_name),
+        )?;
+        for status in test.run_statuses.iter() {
+            render_attempt(out, status)?;
+        }
+    }
+
+    if !scripts.is_empty() {
+        writeln!(out, "<h2>Scripts</h2>\n<table>")?;
+        writeln!(
+            out,
+            "<tr><th>Status</th><th>Script</th><th>Kind</th><th>Duration</th></tr>"
+        )?;
+        for script in scripts {
+            let (class, status) = match script.status.result {
+                ExecutionResult::Pass => ("pass", "PASS"),
+                _ => ("fail", "FAIL"),
+            };
+            let kind = match script.kind {
+                ScriptKind::Setup => "setup",
+                ScriptKind::Teardown => "teardown",
+            };
+            writeln!(
+                out,
+                "<tr><td class=\"{}\">{}</td><td>{}</td><td>{}</td>\
+                 <td class=\"duration\">{:.3}s</td></tr>",
+                class,
+                status,
+                Escape(&script.script_name),
+                kind,
+                script.status.time_taken.as_secs_f64(),
+            )?;
+        }
+        writeln!(out, "</table>")?;
+
+        for script in scripts {
+            if script.status.result.is_success() {
+                continue;
+            }
+            writeln!(
+                out,
+                "<h3><span class=\"fail\">FAIL</span> script {}</h3>",
+                Escape(&script.script_name)
+            )?;
+            render_output(out, "stdout", script.status.stdout())?;
+            render_output(out, "stderr", script.status.stderr())?;
+        }
+    }
+
+    writeln!(out, "</body>\n</html>")
+}
+
+fn render_attempt(out: &mut String, status: &ExecuteStatus) -> fmt::Result {
+    let (class, result) = match status.result {
+        ExecutionResult::Pass => ("pass", "PASS"),
+        ExecutionResult::Fail => ("fail", "FAIL"),
+        ExecutionResult::ExecFail => ("fail", "XFAIL"),
+        ExecutionResult::Timeout => ("fail", "TIMEOUT"),
+        ExecutionResult::ResourceLimit => ("fail", "RESLIMIT"),
+    };
+    writeln!(
+        out,
+        "<details><summary>Attempt {}/{}: <span class=\"{}\">{}</span> in {:.3}s</summary>",
+        status.attempt,
+        status.total_attempts,
+        class,
+        result,
+        status.time_taken.as_secs_f64(),
+    )?;
+    render_output(out, "stdout", status.stdout())?;
+    render_output(out, "stderr", status.stderr())?;
+    writeln!(out, "</details>")
+}
+
+fn render_output(out: &mut String, name: &str, output: &[u8]) -> fmt::Result {
+    if output.is_empty() {
+        return Ok(());
+    }
+    // Strip ANSI escapes from the output, similar to the human-readable reporter.
+    let output = strip_ansi_escapes::strip(output).unwrap_or_else(|_| output.to_vec());
+    writeln!(
+        out,
+        "<p>{}:</p>\n<pre>{}</pre>",
+        name,
+        Escape(&String::from_utf8_lossy(&output))
+    )
+}
+
+fn status_class(run_statuses: &ExecutionStatuses) -> &'static str {
+    match run_statuses.describe() {
+        ExecutionDescription::Success { .. } => "pass",
+        ExecutionDescription::Flaky { .. } => "flaky",
+        ExecutionDescription::Failure { .. } => "fail",
+    }
+}
+
+fn status_str(run_statuses: &ExecutionStatuses) -> &'static str {
+    match run_statuses.describe() {
+        ExecutionDescription::Success { .. } => "PASS",
+        ExecutionDescription::Flaky { .. } => "FLAKY",
+        ExecutionDescription::Failure { last_status, .. } => match last_status.result {
+            ExecutionResult::ExecFail => "XFAIL",
+            ExecutionResult::Timeout => "TIMEOUT",
+            ExecutionResult::ResourceLimit => "RESLIMIT",
+            _ => "FAIL",
+        },
+    }
+}
+
+/// Escapes text for inclusion in HTML.
+struct Escape<'a>(&'a str);
+
+impl fmt::Display for Escape<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for c in self.0.chars() {
+            match c {
+                '&' => f.write_str("&amp;")?,
+                '<' => f.write_str("&lt;")?,
+                '>' => f.write_str("&gt;")?,
+                '"' => f.write_str("&quot;")?,
+                '\'' => f.write_str("&#39;")?,
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn escape_html() {
+        assert_eq!(
+            Escape("<a href=\"x\">Tom & Jerry's</a>").to_string(),
+            "&lt;a href=&quot;x&quot;&gt;Tom &amp; Jerry&#39;s&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn render_report_contents() {
+        let status = |attempt, result, stderr: &[u8]| ExecuteStatus {
+            attempt,
+            total_attempts: 2,
+            stdout_stderr: Arc::new((vec![], stderr.to_vec())),
+            result,
+            start_time: SystemTime::UNIX_EPOCH,
+            time_taken: Duration::from_millis(250),
+            core_dump: None,
+            output_dir: None,
+        };
+        let tests = vec![
+            HtmlTest {
+                binary_id: "my-crate".to_owned(),
+                test_name: "tests::passing".to_owned(),
+                run_statuses: ExecutionStatuses::new(vec![status(1, ExecutionResult::Pass, b"")]),
+            },
+            HtmlTest {
+                binary_id: "my-crate".to_owned(),
+                test_name: "tests::flaky<T>".to_owned(),
+                run_statuses: ExecutionStatuses::new(vec![
+                    status(
+                        1,
+                        ExecutionResult::Fail,
+                        b"\x1b[31mpanicked\x1b[0m at 'a < b'",
+                    ),
+                    status(2, ExecutionResult::Pass, b""),
+                ]),
+            },
+        ];
+        let run_stats = RunStats {
+            initial_run_count: 2,
+            final_run_count: 2,
+            passed: 2,
+            flaky: 1,
+            ..RunStats::default()
+        };
+
+        let mut html = String::new();
+        render_report(
+            &mut html,
+            SystemTime::UNIX_EPOCH,
+            Duration::from_secs(1),
+            &run_stats,
+            &tests,
+            &[],
+        )
+        .expect("writing to a String succeeds");
+
+        assert!(html.contains("<tr><th>Flaky</th><td>1</td></tr>"));
+        assert!(
+            html.contains("<td class=\"pass\">PASS</td><td>my-crate</td><td>tests::passing</td>")
+        );
+        assert!(html.contains(
+            "<h3><span class=\"flaky\">FLAKY</span> my-crate tests::flaky&lt;T&gt;</h3>"
+        ));
+        assert!(html.contains("Attempt 1/2: <span class=\"fail\">FAIL</span>"));
+        assert!(html.contains("<pre>panicked at &#39;a &lt; b&#39;</pre>"));
+        // Passing tests don't have a retry history.
+        assert!(!html.contains("</span> my-crate tests::passing</h3>"));
+        assert!(!html.contains("<h2>Scripts</h2>"));
+    }
+}
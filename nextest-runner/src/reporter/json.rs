@@ -0,0 +1,488 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Streaming machine-readable output for test events.
+//!
+//! Each [`TestEvent`] is serialized as a single line of JSON, so that consumers like IDEs and CI
+//! wrappers can track the progress of a run as it happens.
+
+use crate::{
+    errors::WriteEventError,
+    reporter::{CancelReason, TestEvent, TestEventKind, WithWriter},
+    runner::{ExecuteStatus, ExecutionResult, RunStats, RunningTest, TestPanic},
+    script::ScriptKind,
+    test_list::TestInstance,
+};
+use camino::{Utf8Path, Utf8PathBuf};
+use chrono::{DateTime, SecondsFormat, Utc};
+use nextest_metadata::MismatchReason;
+use serde::Serialize;
+use std::io::Write;
+use uuid::Uuid;
+
+/// A reporter that writes out test events as newline-delimited JSON.
+///
+/// Every event is written out as a single JSON object followed by a newline. The `"type"` field
+/// indicates the kind of event.
+#[derive(Clone, Debug, Default)]
+pub struct JsonReporter;
+
+impl JsonReporter {
+    /// Creates a new `JsonReporter`.
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Writes out a test event as a single line of JSON.
+    pub fn write_event(
+        &mut self,
+        event: &TestEvent<'_>,
+        mut writer: impl Write,
+    ) -> Result<(), WriteEventError> {
+        let event = JsonEventWithContext::new(event);
+        serde_json::to_writer(&mut writer, &event).map_err(WriteEventError::Json)?;
+        writeln!(writer).map_err(WriteEventError::Io)
+    }
+
+    /// Returns a [`Reporter`](super::Reporter) that writes JSON lines to the given writer.
+    pub fn with_writer<W: Write + Send>(self, writer: W) -> WithWriter<Self, W> {
+        WithWriter::new(self, writer)
+    }
+}
+
+/// Fields common to all events, followed by the event itself.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonEventWithContext<'a> {
+    run_id: Uuid,
+    timestamp: String,
+    since_start_secs: f64,
+    #[serde(flatten)]
+    event: JsonEvent<'a>,
+}
+
+impl<'a> JsonEventWithContext<'a> {
+    fn new(event: &'a TestEvent<'a>) -> Self {
+        Self {
+            run_id: event.run_id,
+            timestamp: DateTime::<Utc>::from(event.timestamp)
+                .to_rfc3339_opts(SecondsFormat::Millis, true),
+            since_start_secs: event.since_start.as_secs_f64(),
+            event: JsonEvent::new(&event.kind),
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case", tag = "type")]
+enum JsonEvent<'a> {
+    #[serde(rename_all = "kebab-case")]
+    RunStarted {
+        test_count: usize,
+        run_count: usize,
+        skip_count: usize,
+        binary_count: usize,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        shuffle_seed: Option<u64>,
+        repeat: usize,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestStarted {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestSlow {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        elapsed_secs: f64,
+        will_terminate: bool,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestSilent {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        silent_for_secs: f64,
+        elapsed_secs: f64,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestPaused {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        pid: u32,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestRetry {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        #[serde(flatten)]
+        status: JsonStatus,
+        delay_before_next_attempt_secs: f64,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestFinished {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        #[serde(flatten)]
+        status: JsonStatus,
+        flaky: bool,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stdout: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stderr: Option<String>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestRecorded {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        #[serde(flatten)]
+        status: JsonStatus,
+        recording_dir: &'a Utf8Path,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestSkipped {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+        reason: MismatchReason,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        exclude_reason: Option<&'a str>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    TestCanceled {
+        #[serde(flatten)]
+        test: JsonTest<'a>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    ScriptFinished {
+        script_name: &'a str,
+        kind: ScriptKind,
+        result: ExecutionResult,
+        exec_time_secs: f64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stdout: Option<String>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        stderr: Option<String>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    RunInfo {
+        running_tests: Vec<JsonRunningTest<'a>>,
+    },
+    #[serde(rename_all = "kebab-case")]
+    RunBeginCancel {
+        running: usize,
+        running_tests: Vec<JsonRunningTest<'a>>,
+        reason: CancelReason,
+    },
+    #[serde(rename_all = "kebab-case")]
+    RunFinished {
+        elapsed_secs: f64,
+        run_stats: RunStats,
+    },
+}
+
+impl<'a> JsonEvent<'a> {
+    fn new(event: &'a TestEventKind<'a>) -> Self {
+        match event {
+            TestEventKind::RunStarted {
+                test_list,
+                shuffle_seed,
+                repeat,
+            } => JsonEvent::RunStarted {
+                test_count: test_list.test_count(),
+                run_count: test_list.run_count(),
+                skip_count: test_list.skip_count(),
+                binary_count: test_list.binary_count(),
+                shuffle_seed: *shuffle_seed,
+                repeat: *repeat,
+            },
+            TestEventKind::TestStarted { test_instance } => JsonEvent::TestStarted {
+                test: JsonTest::new(test_instance),
+            },
+            TestEventKind::TestSlow {
+                test_instance,
+                elapsed,
+                will_terminate,
+            } => JsonEvent::TestSlow {
+                test: JsonTest::new(test_instance),
+                elapsed_secs: elapsed.as_secs_f64(),
+                will_terminate: *will_terminate,
+            },
+            TestEventKind::TestSilent {
+                test_instance,
+                silent_for,
+                elapsed,
+            } => JsonEvent::TestSilent {
+                test: JsonTest::new(test_instance),
+                silent_for_secs: silent_for.as_secs_f64(),
+                elapsed_secs: elapsed.as_secs_f64(),
+            },
+            TestEventKind::TestPaused { test_instance, pid } => JsonEvent::TestPaused {
+                test: JsonTest::new(test_instance),
+                pid: *pid,
+            },
+            TestEventKind::TestRetry {
+                test_instance,
+                run_status,
+                delay_before_next_attempt,
+            } => JsonEvent::TestRetry {
+                test: JsonTest::new(test_instance),
+                status: JsonStatus::new(run_status),
+                delay_before_next_attempt_secs: delay_before_next_attempt.as_secs_f64(),
+            },
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                let last_status = run_statuses.last_status();
+                // Only include output for failing tests, similar to the default human output.
+                let (stdout, stderr) = if last_status.result.is_success() {
+                    (None, None)
+                } else {
+                    (
+                        Some(String::from_utf8_lossy(last_status.stdout()).into_owned()),
+                        Some(String::from_utf8_lossy(last_status.stderr()).into_owned()),
+                    )
+                };
+                JsonEvent::TestFinished {
+                    test: JsonTest::new(test_instance),
+                    status: JsonStatus::new(last_status),
+                    flaky: last_status.result.is_success() && run_statuses.len() > 1,
+                    stdout,
+                    stderr,
+                }
+            }
+            TestEventKind::TestRecorded {
+                test_instance,
+                run_status,
+                recording_dir,
+            } => JsonEvent::TestRecorded {
+                test: JsonTest::new(test_instance),
+                status: JsonStatus::new(run_status),
+                recording_dir,
+            },
+            TestEventKind::TestSkipped {
+                test_instance,
+                reason,
+                exclude_reason,
+            } => JsonEvent::TestSkipped {
+                test: JsonTest::new(test_instance),
+                reason: *reason,
+                exclude_reason: exclude_reason.as_deref(),
+            },
+            TestEventKind::TestCanceled { test_instance } => JsonEvent::TestCanceled {
+                test: JsonTest::new(test_instance),
+            },
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => {
+                // As with tests, only include output for failing scripts.
+                let (stdout, stderr) = if status.result.is_success() {
+                    (None, None)
+                } else {
+                    (
+                        Some(String::from_utf8_lossy(status.stdout()).into_owned()),
+                        Some(String::from_utf8_lossy(status.stderr()).into_owned()),
+                    )
+                };
+                JsonEvent::ScriptFinished {
+                    script_name,
+                    kind: *kind,
+                    result: status.result,
+                    exec_time_secs: status.time_taken.as_secs_f64(),
+                    stdout,
+                    stderr,
+                }
+            }
+            TestEventKind::RunInfo { running_tests } => JsonEvent::RunInfo {
+                running_tests: running_tests.iter().map(JsonRunningTest::new).collect(),
+            },
+            TestEventKind::RunBeginCancel {
+                running,
+                running_tests,
+                reason,
+            } => JsonEvent::RunBeginCancel {
+                running: *running,
+                running_tests: running_tests.iter().map(JsonRunningTest::new).collect(),
+                reason: *reason,
+            },
+            TestEventKind::RunFinished {
+                elapsed, run_stats, ..
+            } => JsonEvent::RunFinished {
+                elapsed_secs: elapsed.as_secs_f64(),
+                run_stats: *run_stats,
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonTest<'a> {
+    binary_id: &'a str,
+    test_name: &'a str,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonRunningTest<'a> {
+    #[serde(flatten)]
+    test: JsonTest<'a>,
+    attempt: usize,
+    elapsed_secs: f64,
+}
+
+impl<'a> JsonRunningTest<'a> {
+    fn new(running_test: &RunningTest<'a>) -> Self {
+        Self {
+            test: JsonTest::new(&running_test.test_instance),
+            attempt: running_test.attempt,
+            elapsed_secs: running_test.elapsed.as_secs_f64(),
+        }
+    }
+}
+
+impl<'a> JsonTest<'a> {
+    fn new(test_instance: &TestInstance<'a>) -> Self {
+        Self {
+            binary_id: &test_instance.bin_info.binary_id,
+            test_name: test_instance.name,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonStatus {
+    result: ExecutionResult,
+    attempt: usize,
+    total_attempts: usize,
+    exec_time_secs: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    core_dump: Option<Utf8PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    output_dir: Option<Utf8PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    panic: Option<JsonPanic>,
+}
+
+impl JsonStatus {
+    fn new(status: &ExecuteStatus) -> Self {
+        Self {
+            result: status.result,
+            attempt: status.attempt,
+            total_attempts: status.total_attempts,
+            exec_time_secs: status.time_taken.as_secs_f64(),
+            core_dump: status.core_dump.clone(),
+            output_dir: status.output_dir.clone(),
+            panic: if status.result.is_success() {
+                None
+            } else {
+                status.panic().map(JsonPanic::new)
+            },
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct JsonPanic {
+    thread: String,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    location: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    backtrace: Option<String>,
+}
+
+impl JsonPanic {
+    fn new(panic: TestPanic) -> Self {
+        Self {
+            thread: panic.thread,
+            message: panic.message,
+            location: panic.location,
+            backtrace: panic.backtrace,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_list::test_helpers::make_single_binary_test_list;
+    use std::time::Duration;
+
+    #[test]
+    fn write_event_is_single_line() {
+        let mut reporter = JsonReporter::new();
+        let mut buf = Vec::new();
+        reporter
+            .write_event(
+                &TestEvent {
+                    run_id: Uuid::nil(),
+                    timestamp: std::time::UNIX_EPOCH + Duration::from_millis(2500),
+                    since_start: Duration::from_millis(2500),
+                    kind: TestEventKind::RunBeginCancel {
+                        running: 3,
+                        running_tests: vec![],
+                        reason: CancelReason::TestFailure,
+                    },
+                },
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+        reporter
+            .write_event(
+                &TestEvent::from_kind(TestEventKind::RunFinished {
+                    start_time: std::time::SystemTime::now(),
+                    elapsed: Duration::from_millis(1500),
+                    run_stats: RunStats::default(),
+                }),
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+
+        let output = String::from_utf8(buf).expect("output is valid UTF-8");
+        let lines: Vec<_> = output.lines().collect();
+        assert_eq!(lines.len(), 2, "one line per event");
+        assert_eq!(
+            lines[0],
+            r#"{"run-id":"00000000-0000-0000-0000-000000000000","timestamp":"1970-01-01T00:00:02.500Z","since-start-secs":2.5,"type":"run-begin-cancel","running":3,"running-tests":[],"reason":"test-failure"}"#
+        );
+
+        let finished: serde_json::Value =
+            serde_json::from_str(lines[1]).expect("line is valid JSON");
+        assert_eq!(finished["type"], "run-finished");
+        assert_eq!(finished["elapsed-secs"], 1.5);
+        assert_eq!(finished["run-stats"]["initial-run-count"], 0);
+    }
+
+    #[test]
+    fn write_test_paused() {
+        let test_list = make_single_binary_test_list("tests::paused: test\n", "");
+        let test_instance = test_list.iter_tests().next().expect("test exists");
+        let mut buf = Vec::new();
+        JsonReporter::new()
+            .write_event(
+                &TestEvent {
+                    run_id: Uuid::nil(),
+                    timestamp: std::time::UNIX_EPOCH,
+                    since_start: Duration::ZERO,
+                    kind: TestEventKind::TestPaused {
+                        test_instance,
+                        pid: 1234,
+                    },
+                },
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+        let output = String::from_utf8(buf).expect("output is valid UTF-8");
+        assert_eq!(
+            output.lines().collect::<Vec<_>>(),
+            [
+                r#"{"run-id":"00000000-0000-0000-0000-000000000000","timestamp":"1970-01-01T00:00:00.000Z","since-start-secs":0.0,"type":"test-paused","binary-id":"fake-package::fake-binary","test-name":"tests::paused","pid":1234}"#
+            ]
+        );
+    }
+}
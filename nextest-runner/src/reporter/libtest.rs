@@ -0,0 +1,371 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Output that mimics `cargo test`, for tooling that parses its logs.
+//!
+//! libtest prints the results of each test binary as a block, from `running N tests` to the
+//! `test result:` line. Since nextest runs tests from several binaries at once, each binary's
+//! results are collected and its block is written out once all of its tests have finished.
+
+use crate::{
+    errors::WriteEventError,
+    reporter::{TestEvent, TestEventKind, WithWriter},
+    runner::ExecutionResult,
+};
+use camino::Utf8PathBuf;
+use nextest_metadata::{FilterMatch, MismatchReason, RustTestHarness};
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// A reporter that writes out test results in the same format as `cargo test`.
+///
+/// Tests are listed by name within blocks for each binary, in the order they finished. Failing
+/// tests have their output included in the block's `failures:` section. Tests skipped because
+/// they're ignored are listed as `ignored`, and other skipped tests are counted as filtered out.
+/// Tests that didn't run because the run was canceled are listed as `ignored, canceled`.
+#[derive(Clone, Debug, Default)]
+pub struct LibtestReporter {
+    // Binaries, by binary ID, whose results haven't been written out yet.
+    binaries: BTreeMap<String, BinaryResults>,
+}
+
+impl LibtestReporter {
+    /// Creates a new `LibtestReporter`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes out the lines corresponding to a test event, if any.
+    pub fn write_event(
+        &mut self,
+        event: &TestEvent<'_>,
+        writer: impl Write,
+    ) -> Result<(), WriteEventError> {
+        self.write_event_impl(event, writer)
+            .map_err(WriteEventError::Io)
+    }
+
+    /// Returns a [`Reporter`](super::Reporter) that writes libtest-style output to the given
+    /// writer.
+    pub fn with_writer<W: Write + Send>(self, writer: W) -> WithWriter<Self, W> {
+        WithWriter::new(self, writer)
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn write_event_impl(
+        &mut self,
+        event: &TestEvent<'_>,
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        let (binary_id, outcome) = match &event.kind {
+            TestEventKind::RunStarted {
+                test_list, repeat, ..
+            } => {
+                for test_instance in test_list.iter_tests() {
+                    let results = self
+                        .binaries
+                        .entry(test_instance.bin_info.binary_id.clone())
+                        .or_insert_with(|| BinaryResults::new(test_instance.binary.to_owned()));
+                    // Tests that are run produce an event each time they're run, and skipped tests
+                    // produce a single event.
+                    results.remaining += match test_instance.test_info.filter_match {
+                        FilterMatch::Matches => *repeat,
+                        FilterMatch::Mismatch { .. } => 1,
+                    };
+                }
+                return Ok(());
+            }
+            TestEventKind::TestStarted { test_instance } => {
+                if let Some(results) = self.binaries.get_mut(&test_instance.bin_info.binary_id) {
+                    results.start.get_or_insert(event.since_start);
+                }
+                return Ok(());
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                let last_status = run_statuses.last_status();
+                let outcome = match last_status.result {
+                    ExecutionResult::Pass => Outcome::Ok,
+                    ExecutionResult::Fail
+                    | ExecutionResult::ExecFail
+                    | ExecutionResult::Timeout
+                    | ExecutionResult::ResourceLimit => {
+                        let stdout = strip_ansi_escapes::strip(last_status.stdout())?;
+                        let stdout = String::from_utf8_lossy(&stdout);
+                        let mut output = match test_instance.bin_info.harness {
+                            RustTestHarness::Libtest => {
+                                strip_harness_lines(&stdout, test_instance.name)
+                            }
+                            _ => stdout.into_owned(),
+                        };
+                        let stderr = strip_ansi_escapes::strip(last_status.stderr())?;
+                        output.push_str(&String::from_utf8_lossy(&stderr));
+                        Outcome::Failed(output)
+                    }
+                };
+                (
+                    &test_instance.bin_info.binary_id,
+                    (test_instance.name.to_owned(), outcome),
+                )
+            }
+            TestEventKind::TestSkipped {
+                test_instance,
+                reason: MismatchReason::Ignored,
+                ..
+            } => (
+                &test_instance.bin_info.binary_id,
+                (test_instance.name.to_owned(), Outcome::Ignored(None)),
+            ),
+            TestEventKind::TestSkipped { test_instance, .. } => (
+                &test_instance.bin_info.binary_id,
+                (test_instance.name.to_owned(), Outcome::FilteredOut),
+            ),
+            TestEventKind::TestCanceled { test_instance } => (
+                &test_instance.bin_info.binary_id,
+                (
+                    test_instance.name.to_owned(),
+                    Outcome::Ignored(Some("canceled")),
+                ),
+            ),
+            TestEventKind::RunFinished { .. } => {
+                // If the run was canceled, some binaries may not have finished.
+                for (binary_id, results) in std::mem::take(&mut self.binaries) {
+                    if !results.tests.is_empty() {
+                        results.write(&binary_id, event.since_start, &mut writer)?;
+                    }
+                }
+                return Ok(());
+            }
+            TestEventKind::TestSlow { .. }
+            | TestEventKind::TestSilent { .. }
+            | TestEventKind::TestPaused { .. }
+            | TestEventKind::TestRetry { .. }
+            | TestEventKind::TestRecorded { .. }
+            | TestEventKind::ScriptFinished { .. }
+            | TestEventKind::RunInfo { .. }
+            | TestEventKind::RunBeginCancel { .. } => return Ok(()),
+        };
+
+        let results = match self.binaries.get_mut(binary_id) {
+            Some(results) => results,
+            None => return Ok(()),
+        };
+        results.tests.push(outcome);
+        results.remaining = results.remaining.saturating_sub(1);
+        if results.remaining == 0 {
+            let results = self
+                .binaries
+                .remove(binary_id)
+                .expect("binary was just found");
+            results.write(binary_id, event.since_start, &mut writer)?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug)]
+struct BinaryResults {
+    binary_path: Utf8PathBuf,
+    // The number of test events still expected for this binary.
+    remaining: usize,
+    // The time since the start of the run at which the first test in this binary started.
+    start: Option<Duration>,
+    tests: Vec<(String, Outcome)>,
+}
+
+impl BinaryResults {
+    fn new(binary_path: Utf8PathBuf) -> Self {
+        Self {
+            binary_path,
+            remaining: 0,
+            start: None,
+            tests: vec![],
+        }
+    }
+
+    /// Writes out the block for this binary. `end` is the time since the start of the run.
+    fn write(&self, binary_id: &str, end: Duration, mut writer: impl Write) -> io::Result<()> {
+        let listed = self
+            .tests
+            .iter()
+            .filter(|(_, outcome)| *outcome != Outcome::FilteredOut);
+        let count = listed.clone().count();
+
+        writeln!(writer, "     Running {} ({})", binary_id, self.binary_path)?;
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "running {} {}",
+            count,
+            if count == 1 { "test" } else { "tests" }
+        )?;
+        let (mut passed, mut failed, mut ignored, mut filtered_out) = (0, 0, 0, 0);
+        for (name, outcome) in &self.tests {
+            match outcome {
+                Outcome::Ok => {
+                    passed += 1;
+                    writeln!(writer, "test {} ... ok", name)?;
+                }
+                Outcome::Failed(_) => {
+                    failed += 1;
+                    writeln!(writer, "test {} ... FAILED", name)?;
+                }
+                Outcome::Ignored(None) => {
+                    ignored += 1;
+                    writeln!(writer, "test {} ... ignored", name)?;
+                }
+                Outcome::Ignored(Some(reason)) => {
+                    ignored += 1;
+                    writeln!(writer, "test {} ... ignored, {}", name, reason)?;
+                }
+                Outcome::FilteredOut => filtered_out += 1,
+            }
+        }
+
+        if failed > 0 {
+            writeln!(writer)?;
+            writeln!(writer, "failures:")?;
+            writeln!(writer)?;
+            for (name, outcome) in listed.clone() {
+                if let Outcome::Failed(output) = outcome {
+                    writeln!(writer, "---- {} stdout ----", name)?;
+                    write!(writer, "{}", output)?;
+                    if !output.is_empty() && !output.ends_with('\n') {
+                        writeln!(writer)?;
+                    }
+                    writeln!(writer)?;
+                }
+            }
+            writeln!(writer)?;
+            writeln!(writer, "failures:")?;
+            for (name, outcome) in listed {
+                if let Outcome::Failed(_) = outcome {
+                    writeln!(writer, "    {}", name)?;
+                }
+            }
+        }
+
+        let elapsed = end.saturating_sub(self.start.unwrap_or(end));
+        writeln!(writer)?;
+        writeln!(
+            writer,
+            "test result: {}. {} passed; {} failed; {} ignored; 0 measured; {} filtered out; \
+             finished in {:.2}s",
+            if failed > 0 { "FAILED" } else { "ok" },
+            passed,
+            failed,
+            ignored,
+            filtered_out,
+            elapsed.as_secs_f64()
+        )?;
+        writeln!(writer)
+    }
+}
+
+/// Removes the lines printed by libtest itself from the standard output of a single test, so that
+/// they aren't mistaken for the results of a binary.
+fn strip_harness_lines(stdout: &str, test_name: &str) -> String {
+    let started = format!("test {} ... ", test_name);
+    let listed = format!("    {}", test_name);
+    let mut output = String::new();
+    for line in stdout.lines() {
+        let line = line.strip_prefix(&started).unwrap_or(line);
+        let is_harness_line = matches!(line, "running 1 test" | "failures:" | "ok" | "FAILED")
+            || line == listed
+            || line.starts_with("test result: ");
+        if !is_harness_line {
+            output.push_str(line);
+            output.push('\n');
+        }
+    }
+    let output = output.trim_matches('\n');
+    if output.is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", output)
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum Outcome {
+    Ok,
+    // Along with the test's output.
+    Failed(String),
+    // Along with the reason, if any.
+    Ignored(Option<&'static str>),
+    FilteredOut,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn harness_lines() {
+        let stdout = "\nrunning 1 test\ntest tests::fails ... some output\nmore output\nFAILED\n\n\
+                      failures:\n\nfailures:\n    tests::fails\n\n\
+                      test result: FAILED. 0 passed; 1 failed; 0 ignored; 0 measured; 2 filtered \
+                      out; finished in 0.00s\n\n";
+        assert_eq!(
+            strip_harness_lines(stdout, "tests::fails"),
+            "some output\nmore output\n"
+        );
+    }
+
+    #[test]
+    fn binary_block() {
+        let results = BinaryResults {
+            binary_path: "/target/debug/deps/my_crate-0123".into(),
+            remaining: 0,
+            start: Some(Duration::from_millis(500)),
+            tests: vec![
+                ("tests::passes".to_owned(), Outcome::Ok),
+                (
+                    "tests::fails".to_owned(),
+                    Outcome::Failed("it's broken".to_owned()),
+                ),
+                ("tests::other".to_owned(), Outcome::FilteredOut),
+                ("tests::ignored".to_owned(), Outcome::Ignored(None)),
+                (
+                    "tests::canceled".to_owned(),
+                    Outcome::Ignored(Some("canceled")),
+                ),
+            ],
+        };
+        let mut buf = vec![];
+        results
+            .write("my-crate", Duration::from_millis(1750), &mut buf)
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(buf).expect("output is valid UTF-8"),
+            "     Running my-crate (/target/debug/deps/my_crate-0123)
+
+running 4 tests
+test tests::passes ... ok
+test tests::fails ... FAILED
+test tests::ignored ... ignored
+test tests::canceled ... ignored, canceled
+
+failures:
+
+---- tests::fails stdout ----
+it's broken
+
+
+failures:
+    tests::fails
+
+test result: FAILED. 1 passed; 1 failed; 2 ignored; 0 measured; 1 filtered out; finished in 1.25s
+
+"
+        );
+    }
+}
@@ -0,0 +1,362 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Notifications sent over HTTP once a run finishes.
+//!
+//! The notification is either a JSON summary of the run, or a message rendered from a template and
+//! wrapped in the form that Slack's incoming webhooks expect.
+
+use crate::{
+    config::NextestNotifyConfig,
+    errors::{NotifyTemplateParseError, WriteEventError},
+    helpers::post_json,
+    reporter::{TestEvent, TestEventKind},
+    runner::{ExecutionDescription, RunStats},
+};
+use nextest_metadata::RunStatsSummary;
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use std::{fmt::Write, str::FromStr, time::Duration};
+use uuid::Uuid;
+
+/// The form a notification is sent in.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NotifyFormat {
+    /// A JSON summary of the run, including the tests that failed or were flaky.
+    ///
+    /// This is the default.
+    Json,
+
+    /// A JSON object with a single `"text"` field, rendered from the template. This is the format
+    /// accepted by Slack's incoming webhooks.
+    Slack,
+}
+
+impl Default for NotifyFormat {
+    fn default() -> Self {
+        NotifyFormat::Json
+    }
+}
+
+/// A template for the text of a notification.
+///
+/// Placeholders are written as `{name}`, and literal braces as `{{` and `}}`. The supported
+/// placeholders are:
+/// * `{run-id}`: the unique ID of the run
+/// * `{status}`: `passed` or `failed`
+/// * `{elapsed}`: the time the run took, in seconds
+/// * `{test-count}`: the number of tests that were expected to be run
+/// * `{passed}`, `{flaky}`, `{failed}`, `{skipped}`, `{canceled}`: the number of tests with that
+///   result; `{failed}` includes tests that timed out or couldn't be executed
+/// * `{failed-tests}`: the tests that failed, one per line
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct NotifyTemplate {
+    parts: Vec<TemplatePart>,
+}
+
+impl NotifyTemplate {
+    /// The template used if none is configured.
+    pub const DEFAULT: &'static str =
+        "nextest run {status}: {passed} passed, {failed} failed, {skipped} skipped in {elapsed}s";
+
+    fn render(&self, values: &TemplateValues<'_>) -> String {
+        let mut out = String::new();
+        for part in &self.parts {
+            match part {
+                TemplatePart::Literal(literal) => out.push_str(literal),
+                TemplatePart::Placeholder(placeholder) => values.write(*placeholder, &mut out),
+            }
+        }
+        out
+    }
+}
+
+impl Default for NotifyTemplate {
+    fn default() -> Self {
+        Self::DEFAULT
+            .parse()
+            .expect("default template is always valid")
+    }
+}
+
+impl FromStr for NotifyTemplate {
+    type Err = NotifyTemplateParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = vec![];
+        let mut literal = String::new();
+        let mut chars = s.chars().peekable();
+        while let Some(c) = chars.next() {
+            match c {
+                '{' if chars.peek() == Some(&'{') => {
+                    chars.next();
+                    literal.push('{');
+                }
+                '}' if chars.peek() == Some(&'}') => {
+                    chars.next();
+                    literal.push('}');
+                }
+                '{' => {
+                    let mut name = String::new();
+                    loop {
+                        match chars.next() {
+                            Some('}') => break,
+                            Some(c) => name.push(c),
+                            None => {
+                                return Err(NotifyTemplateParseError::new(
+                                    s,
+                                    "unclosed `{` (use `{{` for a literal brace)",
+                                ))
+                            }
+                        }
+                    }
+                    let placeholder = Placeholder::from_name(&name).ok_or_else(|| {
+                        NotifyTemplateParseError::new(
+                            s,
+                            format!("unknown placeholder `{{{}}}`", name),
+                        )
+                    })?;
+                    if !literal.is_empty() {
+                        parts.push(TemplatePart::Literal(std::mem::take(&mut literal)));
+                    }
+                    parts.push(TemplatePart::Placeholder(placeholder));
+                }
+                '}' => {
+                    return Err(NotifyTemplateParseError::new(
+                        s,
+                        "unmatched `}` (use `}}` for a literal brace)",
+                    ))
+                }
+                c => literal.push(c),
+            }
+        }
+        if !literal.is_empty() {
+            parts.push(TemplatePart::Literal(literal));
+        }
+
+        Ok(Self { parts })
+    }
+}
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+enum TemplatePart {
+    Literal(String),
+    Placeholder(Placeholder),
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Placeholder {
+    RunId,
+    Status,
+    Elapsed,
+    TestCount,
+    Passed,
+    Flaky,
+    Failed,
+    Skipped,
+    Canceled,
+    FailedTests,
+}
+
+impl Placeholder {
+    fn from_name(name: &str) -> Option<Self> {
+        let placeholder = match name {
+            "run-id" => Placeholder::RunId,
+            "status" => Placeholder::Status,
+            "elapsed" => Placeholder::Elapsed,
+            "test-count" => Placeholder::TestCount,
+            "passed" => Placeholder::Passed,
+            "flaky" => Placeholder::Flaky,
+            "failed" => Placeholder::Failed,
+            "skipped" => Placeholder::Skipped,
+            "canceled" => Placeholder::Canceled,
+            "failed-tests" => Placeholder::FailedTests,
+            _ => return None,
+        };
+        Some(placeholder)
+    }
+}
+
+struct TemplateValues<'a> {
+    run_id: Uuid,
+    elapsed: Duration,
+    run_stats: &'a RunStats,
+    failed_tests: &'a [NotifyTest],
+}
+
+impl<'a> TemplateValues<'a> {
+    fn write(&self, placeholder: Placeholder, out: &mut String) {
+        let stats = self.run_stats;
+        // Writing to a String never fails.
+        let _ = match placeholder {
+            Placeholder::RunId => write!(out, "{}", self.run_id),
+            Placeholder::Status => {
+                out.push_str(if stats.is_success() {
+                    "passed"
+                } else {
+                    "failed"
+                });
+                Ok(())
+            }
+            Placeholder::Elapsed => write!(out, "{:.3}", self.elapsed.as_secs_f64()),
+            Placeholder::TestCount => write!(out, "{}", stats.initial_run_count),
+            Placeholder::Passed => write!(out, "{}", stats.passed),
+            Placeholder::Flaky => write!(out, "{}", stats.flaky),
+            Placeholder::Failed => write!(out, "{}", stats.failed_count()),
+            Placeholder::Skipped => write!(out, "{}", stats.skipped),
+            Placeholder::Canceled => write!(out, "{}", stats.canceled),
+            Placeholder::FailedTests => {
+                for (idx, test) in self.failed_tests.iter().enumerate() {
+                    if idx > 0 {
+                        out.push('\n');
+                    }
+                    let _ = write!(out, "{} {}", test.binary_id, test.test_name);
+                }
+                Ok(())
+            }
+        };
+    }
+}
+
+/// Collects the tests that failed or were flaky, and sends a notification at the end of the run.
+#[derive(Clone, Debug)]
+pub(super) struct MetadataNotify<'cfg> {
+    config: NextestNotifyConfig<'cfg>,
+    failed_tests: Vec<NotifyTest>,
+    flaky_tests: Vec<NotifyTest>,
+}
+
+impl<'cfg> MetadataNotify<'cfg> {
+    pub(super) fn new(config: NextestNotifyConfig<'cfg>) -> Self {
+        Self {
+            config,
+            failed_tests: vec![],
+            flaky_tests: vec![],
+        }
+    }
+
+    pub(super) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                let test = NotifyTest {
+                    binary_id: test_instance.bin_info.binary_id.clone(),
+                    test_name: test_instance.name.to_owned(),
+                };
+                match run_statuses.describe() {
+                    ExecutionDescription::Success { .. } => {}
+                    ExecutionDescription::Flaky { .. } => self.flaky_tests.push(test),
+                    ExecutionDescription::Failure { .. } => self.failed_tests.push(test),
+                }
+            }
+            TestEventKind::RunFinished {
+                elapsed, run_stats, ..
+            } => {
+                let body = self.to_body(event.run_id, *elapsed, run_stats);
+                let url = self.config.url();
+                post_json(url, self.config.headers(), &body).map_err(|error| {
+                    WriteEventError::Http {
+                        url: url.to_owned(),
+                        error,
+                    }
+                })?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    fn to_body(&self, run_id: Uuid, elapsed: Duration, run_stats: &RunStats) -> serde_json::Value {
+        match self.config.format() {
+            NotifyFormat::Json => json!(NotifySummary {
+                run_id,
+                success: run_stats.is_success(),
+                elapsed_secs: elapsed.as_secs_f64(),
+                run_stats: run_stats.to_summary(),
+                failed_tests: &self.failed_tests,
+                flaky_tests: &self.flaky_tests,
+            }),
+            NotifyFormat::Slack => {
+                let values = TemplateValues {
+                    run_id,
+                    elapsed,
+                    run_stats,
+                    failed_tests: &self.failed_tests,
+                };
+                json!({ "text": self.config.template().render(&values) })
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct NotifySummary<'a> {
+    run_id: Uuid,
+    success: bool,
+    elapsed_secs: f64,
+    run_stats: RunStatsSummary,
+    failed_tests: &'a [NotifyTest],
+    flaky_tests: &'a [NotifyTest],
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "kebab-case")]
+struct NotifyTest {
+    binary_id: String,
+    test_name: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_and_render_template() {
+        let template: NotifyTemplate = "{{{status}}} {failed}/{test-count} failed:\n{failed-tests}"
+            .parse()
+            .expect("template is valid");
+        let run_stats = RunStats {
+            initial_run_count: 3,
+            final_run_count: 3,
+            passed: 1,
+            failed: 1,
+            timed_out: 1,
+            ..RunStats::default()
+        };
+        let failed_tests = vec![
+            NotifyTest {
+                binary_id: "my-crate".to_owned(),
+                test_name: "tests::fails".to_owned(),
+            },
+            NotifyTest {
+                binary_id: "my-crate::it".to_owned(),
+                test_name: "hangs".to_owned(),
+            },
+        ];
+        let values = TemplateValues {
+            run_id: Uuid::nil(),
+            elapsed: Duration::from_millis(1500),
+            run_stats: &run_stats,
+            failed_tests: &failed_tests,
+        };
+        assert_eq!(
+            template.render(&values),
+            "{failed} 2/3 failed:\nmy-crate tests::fails\nmy-crate::it hangs"
+        );
+        assert_eq!(
+            NotifyTemplate::default().render(&values),
+            "nextest run failed: 1 passed, 2 failed, 0 skipped in 1.500s"
+        );
+
+        for invalid in ["{passed", "passed}", "{unknown}"] {
+            invalid
+                .parse::<NotifyTemplate>()
+                .expect_err("template is invalid");
+        }
+    }
+}
@@ -0,0 +1,383 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Export of test runs as OpenTelemetry traces.
+//!
+//! Once a run finishes, a trace is sent to an
+//! [OTLP/HTTP](https://opentelemetry.io/docs/specs/otlp/#otlphttp) endpoint using the JSON
+//! encoding. The run is the root span, and every attempt of every test, as well as every setup
+//! script, is a child span of it. The trace ID is the run ID, so traces can be matched up with
+//! other output from the same run.
+
+use crate::{
+    config::NextestOtlpConfig,
+    errors::WriteEventError,
+    helpers::post_json,
+    reporter::{TestEvent, TestEventKind},
+    runner::{ExecuteStatus, ExecutionResult, RunStats},
+    script::{ScriptKind, ScriptStatus},
+};
+use debug_ignore::DebugIgnore;
+use serde::Serialize;
+use serde_json::json;
+use std::time::{Duration, SystemTime};
+use uuid::Uuid;
+
+// Span kinds and status codes, as defined by the OpenTelemetry protocol.
+const SPAN_KIND_INTERNAL: u32 = 1;
+const STATUS_CODE_OK: u32 = 1;
+const STATUS_CODE_ERROR: u32 = 2;
+
+/// Collects spans over the course of a run, and sends them to an OTLP endpoint at the end.
+#[derive(Clone, Debug)]
+pub(super) struct MetadataOtlp<'cfg> {
+    config: NextestOtlpConfig<'cfg>,
+    trace: DebugIgnore<OtlpTrace>,
+}
+
+impl<'cfg> MetadataOtlp<'cfg> {
+    pub(super) fn new(config: NextestOtlpConfig<'cfg>) -> Self {
+        Self {
+            config,
+            trace: DebugIgnore(OtlpTrace::default()),
+        }
+    }
+
+    pub(super) fn write_event(&mut self, event: &TestEvent<'_>) -> Result<(), WriteEventError> {
+        match &event.kind {
+            TestEventKind::RunStarted { test_list, .. } => {
+                self.trace
+                    .run_started(event.run_id, event.timestamp, test_list.test_count());
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                for status in run_statuses.iter() {
+                    self.trace.test_attempt_finished(
+                        &test_instance.bin_info.binary_id,
+                        test_instance.name,
+                        status,
+                    );
+                }
+            }
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => {
+                self.trace.script_finished(script_name, *kind, status);
+            }
+            TestEventKind::RunFinished { run_stats, .. } => {
+                let request = self.trace.to_request(event.timestamp, run_stats);
+                post_json(self.config.endpoint(), self.config.headers(), &request).map_err(
+                    |error| WriteEventError::Http {
+                        url: self.config.endpoint().to_owned(),
+                        error,
+                    },
+                )?;
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct OtlpTrace {
+    run_id: Uuid,
+    trace_id: String,
+    start_time: Option<SystemTime>,
+    test_count: usize,
+    // Span IDs only need to be unique within a trace, so they're handed out in order. The root
+    // span is always 1.
+    next_span_id: u64,
+    spans: Vec<Span>,
+}
+
+impl OtlpTrace {
+    const ROOT_SPAN_ID: u64 = 1;
+
+    fn run_started(&mut self, run_id: Uuid, start_time: SystemTime, test_count: usize) {
+        self.run_id = run_id;
+        self.trace_id = run_id.simple().to_string();
+        self.start_time = Some(start_time);
+        self.test_count = test_count;
+        self.next_span_id = Self::ROOT_SPAN_ID + 1;
+    }
+
+    fn test_attempt_finished(&mut self, binary_id: &str, test_name: &str, status: &ExecuteStatus) {
+        let span = self.child_span(
+            test_name.to_owned(),
+            status.start_time,
+            status.time_taken,
+            vec![
+                KeyValue::string("nextest.binary_id", binary_id),
+                KeyValue::string("nextest.test_name", test_name),
+                KeyValue::int("nextest.attempt", status.attempt),
+                KeyValue::int("nextest.total_attempts", status.total_attempts),
+                KeyValue::string("nextest.result", result_str(status.result)),
+            ],
+            status.result,
+        );
+        self.spans.push(span);
+    }
+
+    fn script_finished(&mut self, script_name: &str, kind: ScriptKind, status: &ScriptStatus) {
+        let kind_str = match kind {
+            ScriptKind::Setup => "setup",
+            ScriptKind::Teardown => "teardown",
+        };
+        let span = self.child_span(
+            format!("{} script {}", kind_str, script_name),
+            status.start_time,
+            status.time_taken,
+            vec![
+                KeyValue::string("nextest.script_name", script_name),
+                KeyValue::string("nextest.script_kind", kind_str),
+                KeyValue::string("nextest.result", result_str(status.result)),
+            ],
+            status.result,
+        );
+        self.spans.push(span);
+    }
+
+    fn child_span(
+        &mut self,
+        name: String,
+        start_time: SystemTime,
+        time_taken: Duration,
+        attributes: Vec<KeyValue>,
+        result: ExecutionResult,
+    ) -> Span {
+        let span_id = self.next_span_id;
+        self.next_span_id += 1;
+        let status = if result.is_success() {
+            SpanStatus::ok()
+        } else {
+            SpanStatus::error(result_str(result))
+        };
+
+        Span {
+            trace_id: self.trace_id.clone(),
+            span_id: span_id_str(span_id),
+            parent_span_id: Some(span_id_str(Self::ROOT_SPAN_ID)),
+            name,
+            kind: SPAN_KIND_INTERNAL,
+            start_time_unix_nano: unix_nanos(start_time),
+            end_time_unix_nano: unix_nanos(start_time + time_taken),
+            attributes,
+            status,
+        }
+    }
+
+    /// Returns the body of an OTLP export request, including the root span for the run.
+    fn to_request(&self, end_time: SystemTime, run_stats: &RunStats) -> serde_json::Value {
+        let status = if run_stats.is_success() {
+            SpanStatus::ok()
+        } else {
+            SpanStatus::error("run failed")
+        };
+        let root = Span {
+            trace_id: self.trace_id.clone(),
+            span_id: span_id_str(Self::ROOT_SPAN_ID),
+            parent_span_id: None,
+            name: "nextest run".to_owned(),
+            kind: SPAN_KIND_INTERNAL,
+            start_time_unix_nano: unix_nanos(self.start_time.unwrap_or(end_time)),
+            end_time_unix_nano: unix_nanos(end_time),
+            attributes: vec![
+                KeyValue::string("nextest.run_id", &self.run_id.to_string()),
+                KeyValue::int("nextest.test_count", self.test_count),
+                KeyValue::int("nextest.passed", run_stats.passed),
+                KeyValue::int("nextest.flaky", run_stats.flaky),
+                KeyValue::int("nextest.failed", run_stats.failed),
+                KeyValue::int("nextest.exec_failed", run_stats.exec_failed),
+                KeyValue::int("nextest.timed_out", run_stats.timed_out),
+                KeyValue::int("nextest.skipped", run_stats.skipped),
+                KeyValue::int("nextest.canceled", run_stats.canceled),
+            ],
+            status,
+        };
+
+        let spans: Vec<_> = std::iter::once(&root).chain(&self.spans).collect();
+        json!({
+            "resourceSpans": [{
+                "resource": {
+                    "attributes": [KeyValue::string("service.name", "nextest")],
+                },
+                "scopeSpans": [{
+                    "scope": {
+                        "name": "nextest-runner",
+                        "version": env!("CARGO_PKG_VERSION"),
+                    },
+                    "spans": spans,
+                }],
+            }],
+        })
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+struct Span {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    parent_span_id: Option<String>,
+    name: String,
+    kind: u32,
+    // 64-bit integers are encoded as strings in OTLP/JSON.
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue>,
+    status: SpanStatus,
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct KeyValue {
+    key: &'static str,
+    value: AnyValue,
+}
+
+impl KeyValue {
+    fn string(key: &'static str, value: &str) -> Self {
+        Self {
+            key,
+            value: AnyValue::String(value.to_owned()),
+        }
+    }
+
+    fn int(key: &'static str, value: usize) -> Self {
+        Self {
+            key,
+            value: AnyValue::Int(value.to_string()),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize)]
+enum AnyValue {
+    #[serde(rename = "stringValue")]
+    String(String),
+    #[serde(rename = "intValue")]
+    Int(String),
+}
+
+#[derive(Clone, Debug, Serialize)]
+struct SpanStatus {
+    code: u32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    message: Option<&'static str>,
+}
+
+impl SpanStatus {
+    fn ok() -> Self {
+        Self {
+            code: STATUS_CODE_OK,
+            message: None,
+        }
+    }
+
+    fn error(message: &'static str) -> Self {
+        Self {
+            code: STATUS_CODE_ERROR,
+            message: Some(message),
+        }
+    }
+}
+
+fn span_id_str(span_id: u64) -> String {
+    format!("{:016x}", span_id)
+}
+
+fn unix_nanos(time: SystemTime) -> String {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos()
+        .to_string()
+}
+
+fn result_str(result: ExecutionResult) -> &'static str {
+    match result {
+        ExecutionResult::Pass => "pass",
+        ExecutionResult::Fail => "fail",
+        ExecutionResult::ExecFail => "exec-fail",
+        ExecutionResult::Timeout => "timeout",
+        ExecutionResult::ResourceLimit => "resource-limit",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn export_request() {
+        let run_id = Uuid::from_u128(0x0123_4567_89ab_cdef_0123_4567_89ab_cdef);
+        let start_time = SystemTime::UNIX_EPOCH + Duration::from_secs(1);
+        let mut trace = OtlpTrace::default();
+        trace.run_started(run_id, start_time, 2);
+
+        for (attempt, result) in [(1, ExecutionResult::Fail), (2, ExecutionResult::Pass)] {
+            let status = ExecuteStatus {
+                attempt,
+                total_attempts: 2,
+                stdout_stderr: Arc::new((vec![], vec![])),
+                result,
+                start_time: start_time + Duration::from_millis(attempt as u64 * 100),
+                time_taken: Duration::from_millis(50),
+                core_dump: None,
+                output_dir: None,
+            };
+            trace.test_attempt_finished("my-crate", "tests::flaky", &status);
+        }
+
+        let run_stats = RunStats {
+            initial_run_count: 2,
+            final_run_count: 2,
+            passed: 1,
+            flaky: 1,
+            skipped: 1,
+            ..RunStats::default()
+        };
+        let request = trace.to_request(start_time + Duration::from_secs(1), &run_stats);
+        let spans = request["resourceSpans"][0]["scopeSpans"][0]["spans"]
+            .as_array()
+            .expect("spans are an array");
+        assert_eq!(spans.len(), 3, "root span and one span per attempt");
+
+        let root = &spans[0];
+        assert_eq!(root["traceId"], "0123456789abcdef0123456789abcdef");
+        assert_eq!(root["spanId"], "0000000000000001");
+        assert!(
+            root.get("parentSpanId").is_none(),
+            "root span has no parent"
+        );
+        assert_eq!(root["startTimeUnixNano"], "1000000000");
+        assert_eq!(root["endTimeUnixNano"], "2000000000");
+        assert_eq!(root["status"]["code"], STATUS_CODE_OK);
+
+        let failed = &spans[1];
+        assert_eq!(failed["name"], "tests::flaky");
+        assert_eq!(failed["spanId"], "0000000000000002");
+        assert_eq!(failed["parentSpanId"], "0000000000000001");
+        assert_eq!(failed["startTimeUnixNano"], "1100000000");
+        assert_eq!(failed["endTimeUnixNano"], "1150000000");
+        assert_eq!(failed["status"]["code"], STATUS_CODE_ERROR);
+        assert_eq!(
+            failed["attributes"][0],
+            json!({ "key": "nextest.binary_id", "value": { "stringValue": "my-crate" } })
+        );
+        assert_eq!(
+            failed["attributes"][2],
+            json!({ "key": "nextest.attempt", "value": { "intValue": "1" } })
+        );
+
+        let passed = &spans[2];
+        assert_eq!(passed["spanId"], "0000000000000003");
+        assert_eq!(passed["status"]["code"], STATUS_CODE_OK);
+    }
+}
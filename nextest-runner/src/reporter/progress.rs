@@ -0,0 +1,236 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A progress bar shown at the bottom of the human-readable output while tests run.
+//!
+//! The bar is a single line, redrawn after every event. Before anything else is written out, it's
+//! cleared, so that it always stays below the rest of the output. The time remaining is estimated
+//! from the recorded [timings](crate::timings) of the tests that have finished so far compared to
+//! the ones still to run, or from the number of tests if there are no timings.
+
+use crate::{
+    reporter::{TestEvent, TestEventKind},
+    test_list::TestInstance,
+    timings::TestTimings,
+};
+use owo_colors::{OwoColorize, Style};
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+/// The number of characters taken up by the bar itself.
+const BAR_WIDTH: usize = 20;
+
+/// The terminal size assumed if the actual size can't be found out.
+const DEFAULT_TERMINAL_SIZE: (usize, usize) = (80, 24);
+
+#[derive(Debug)]
+pub(super) struct ProgressBar {
+    timings: Option<TestTimings>,
+    start_time: Option<Instant>,
+    total: usize,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    running: usize,
+    // The expected duration of a test that has no recorded timing.
+    default_duration: Duration,
+    expected_total: Duration,
+    expected_finished: Duration,
+    // Whether the bar is currently shown, and so needs to be cleared before writing anything else.
+    visible: bool,
+    finished: bool,
+}
+
+impl ProgressBar {
+    pub(super) fn new(timings: Option<TestTimings>) -> Self {
+        Self {
+            timings,
+            start_time: None,
+            total: 0,
+            passed: 0,
+            failed: 0,
+            skipped: 0,
+            running: 0,
+            default_duration: Duration::from_secs(1),
+            expected_total: Duration::ZERO,
+            expected_finished: Duration::ZERO,
+            visible: false,
+            finished: false,
+        }
+    }
+
+    /// Clears the bar, if it's shown.
+    pub(super) fn clear(&mut self, mut writer: impl Write) -> io::Result<()> {
+        if self.visible {
+            write!(writer, "\r\x1b[2K")?;
+            self.visible = false;
+        }
+        Ok(())
+    }
+
+    /// Updates counts for the event.
+    pub(super) fn update(&mut self, event: &TestEvent<'_>) {
+        match &event.kind {
+            TestEventKind::RunStarted {
+                test_list, repeat, ..
+            } => {
+                self.start_time = Some(Instant::now());
+                self.total = test_list.run_count() * repeat;
+                self.skipped = test_list.skip_count();
+
+                let run_tests = || {
+                    test_list
+                        .iter_tests()
+                        .filter(|test_instance| test_instance.test_info.filter_match.is_match())
+                };
+                if let Some(timings) = &self.timings {
+                    let known: Vec<_> = run_tests()
+                        .filter_map(|test_instance| timings.get(&test_instance))
+                        .collect();
+                    if !known.is_empty() {
+                        self.default_duration = known.iter().sum::<Duration>() / known.len() as u32;
+                    }
+                }
+                let expected_total: Duration = run_tests()
+                    .map(|test_instance| self.expected_duration(&test_instance))
+                    .sum();
+                self.expected_total = expected_total * *repeat as u32;
+            }
+            TestEventKind::TestStarted { .. } => {
+                self.running += 1;
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                self.running = self.running.saturating_sub(1);
+                if run_statuses.last_status().result.is_success() {
+                    self.passed += 1;
+                } else {
+                    self.failed += 1;
+                }
+                self.expected_finished += self.expected_duration(test_instance);
+            }
+            TestEventKind::RunFinished { .. } => {
+                self.finished = true;
+            }
+            _ => {}
+        }
+    }
+
+    /// Draws the bar, unless the run hasn't started or has finished.
+    pub(super) fn draw(&mut self, label_style: Style, mut writer: impl Write) -> io::Result<()> {
+        let start_time = match self.start_time {
+            Some(start_time) if !self.finished => start_time,
+            _ => return Ok(()),
+        };
+        let elapsed = start_time.elapsed();
+        let finished = self.passed + self.failed;
+
+        let filled = match self.total {
+            0 => BAR_WIDTH,
+            total => (BAR_WIDTH * finished / total).min(BAR_WIDTH),
+        };
+        let bar = if filled == BAR_WIDTH {
+            "=".repeat(BAR_WIDTH)
+        } else {
+            format!(
+                "{}>{}",
+                "=".repeat(filled),
+                " ".repeat(BAR_WIDTH - filled - 1)
+            )
+        };
+
+        let mut line = format!(
+            "[{}] [{}] {}/{}: {} running, {} passed, {} failed, {} skipped",
+            format_duration(elapsed),
+            bar,
+            finished,
+            self.total,
+            self.running,
+            self.passed,
+            self.failed,
+            self.skipped,
+        );
+        if let Some(remaining) = self.estimate_remaining(elapsed) {
+            line.push_str(&format!(", ETA {}", format_duration(remaining)));
+        }
+
+        // The line must fit on a single line of the terminal, or it can't be cleared.
+        let (width, _) = terminal_size();
+        let max_len = width.saturating_sub(14);
+        if line.len() > max_len {
+            line.truncate(max_len);
+        }
+        write!(writer, "{:>12} {}", "Running".style(label_style), line)?;
+        self.visible = true;
+        Ok(())
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn expected_duration(&self, test_instance: &TestInstance<'_>) -> Duration {
+        self.timings
+            .as_ref()
+            .and_then(|timings| timings.get(test_instance))
+            .unwrap_or(self.default_duration)
+    }
+
+    fn estimate_remaining(&self, elapsed: Duration) -> Option<Duration> {
+        if self.expected_finished.is_zero() {
+            return None;
+        }
+        let remaining = self.expected_total.saturating_sub(self.expected_finished);
+        Some(elapsed.mul_f64(remaining.as_secs_f64() / self.expected_finished.as_secs_f64()))
+    }
+}
+
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+}
+
+/// Returns the width and height of the terminal that standard error is written to.
+#[cfg(unix)]
+pub(super) fn terminal_size() -> (usize, usize) {
+    // SAFETY: TIOCGWINSZ only writes to the winsize struct passed in.
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size) };
+    if ret == 0 && size.ws_col > 0 && size.ws_row > 0 {
+        (usize::from(size.ws_col), usize::from(size.ws_row))
+    } else {
+        DEFAULT_TERMINAL_SIZE
+    }
+}
+
+#[cfg(not(unix))]
+pub(super) fn terminal_size() -> (usize, usize) {
+    DEFAULT_TERMINAL_SIZE
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn estimate_remaining() {
+        let mut progress_bar = ProgressBar::new(None);
+        assert_eq!(
+            progress_bar.estimate_remaining(Duration::from_secs(10)),
+            None
+        );
+
+        progress_bar.expected_total = Duration::from_secs(40);
+        progress_bar.expected_finished = Duration::from_secs(10);
+        assert_eq!(
+            progress_bar.estimate_remaining(Duration::from_secs(5)),
+            Some(Duration::from_secs(15)),
+            "a quarter of the expected time took 5 seconds, so three quarters take 15"
+        );
+        assert_eq!(format_duration(Duration::from_secs(3725)), "01:02:05");
+    }
+}
@@ -0,0 +1,374 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Test Anything Protocol (TAP) output for test events.
+//!
+//! Output follows [TAP version 13](https://testanything.org/tap-version-13-specification.html), so
+//! that results can be consumed by `prove` and other TAP harnesses.
+
+use crate::{
+    errors::WriteEventError,
+    reporter::{CancelReason, TestEvent, TestEventKind, WithWriter},
+    runner::{ExecuteStatus, ExecutionResult},
+    script::ScriptKind,
+    test_list::TestInstance,
+};
+use std::io::{self, Write};
+
+/// A reporter that writes out test results in TAP version 13 format.
+///
+/// Every finished or skipped test is written out as a test point. Since the number of tests that
+/// will be run isn't known in advance if a run is canceled, the plan (`1..N`) is written out at the
+/// end of the run.
+#[derive(Clone, Debug, Default)]
+pub struct TapReporter {
+    test_points: usize,
+}
+
+impl TapReporter {
+    /// Creates a new `TapReporter`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Writes out the TAP lines corresponding to a test event, if any.
+    pub fn write_event(
+        &mut self,
+        event: &TestEvent<'_>,
+        writer: impl Write,
+    ) -> Result<(), WriteEventError> {
+        self.write_event_impl(event, writer)
+            .map_err(WriteEventError::Io)
+    }
+
+    /// Returns a [`Reporter`](super::Reporter) that writes TAP output to the given writer.
+    pub fn with_writer<W: Write + Send>(self, writer: W) -> WithWriter<Self, W> {
+        WithWriter::new(self, writer)
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn write_event_impl(
+        &mut self,
+        event: &TestEvent<'_>,
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        match &event.kind {
+            TestEventKind::RunStarted {
+                shuffle_seed,
+                repeat,
+                ..
+            } => {
+                writeln!(writer, "TAP version 13")?;
+                if let Some(seed) = shuffle_seed {
+                    writeln!(writer, "# Shuffled with seed {}", seed)?;
+                }
+                if *repeat > 1 {
+                    writeln!(writer, "# Each test run {} times", repeat)?;
+                }
+            }
+            TestEventKind::TestStarted { .. } => {}
+            TestEventKind::TestSlow {
+                test_instance,
+                elapsed,
+                will_terminate,
+            } => {
+                let status = if *will_terminate {
+                    "TERMINATING"
+                } else {
+                    "SLOW"
+                };
+                writeln!(
+                    writer,
+                    "# {} after {:.3}s: {}",
+                    status,
+                    elapsed.as_secs_f64(),
+                    description(test_instance)
+                )?;
+            }
+            TestEventKind::TestSilent {
+                test_instance,
+                silent_for,
+                ..
+            } => {
+                writeln!(
+                    writer,
+                    "# SILENT for {:.3}s: {}",
+                    silent_for.as_secs_f64(),
+                    description(test_instance)
+                )?;
+            }
+            TestEventKind::TestPaused { test_instance, pid } => {
+                writeln!(
+                    writer,
+                    "# PAUSED as process {}: {}",
+                    pid,
+                    description(test_instance)
+                )?;
+            }
+            TestEventKind::TestRetry {
+                test_instance,
+                run_status,
+                ..
+            } => {
+                writeln!(
+                    writer,
+                    "# RETRY {}/{}: {}",
+                    run_status.attempt,
+                    run_status.total_attempts,
+                    description(test_instance)
+                )?;
+            }
+            TestEventKind::TestRecorded {
+                test_instance,
+                recording_dir,
+                ..
+            } => {
+                writeln!(
+                    writer,
+                    "# RECORDED: {}: {}",
+                    description(test_instance),
+                    recording_dir
+                )?;
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                let last_status = run_statuses.last_status();
+                self.test_points += 1;
+                let ok = if last_status.result.is_success() {
+                    "ok"
+                } else {
+                    "not ok"
+                };
+                writeln!(
+                    writer,
+                    "{} {} - {}",
+                    ok,
+                    self.test_points,
+                    description(test_instance)
+                )?;
+                // Diagnostics are only written out for failing and flaky tests.
+                if !last_status.result.is_success() || run_statuses.len() > 1 {
+                    write_yaml_block(last_status, &mut writer)?;
+                }
+            }
+            TestEventKind::TestSkipped {
+                test_instance,
+                reason,
+                exclude_reason,
+            } => {
+                self.test_points += 1;
+                write!(
+                    writer,
+                    "ok {} - {} # SKIP {}",
+                    self.test_points,
+                    description(test_instance),
+                    reason
+                )?;
+                if let Some(exclude_reason) = exclude_reason {
+                    write!(writer, ": {}", exclude_reason)?;
+                }
+                writeln!(writer)?;
+            }
+            TestEventKind::TestCanceled { test_instance } => {
+                self.test_points += 1;
+                writeln!(
+                    writer,
+                    "ok {} - {} # SKIP canceled",
+                    self.test_points,
+                    description(test_instance),
+                )?;
+            }
+            TestEventKind::ScriptFinished {
+                script_name,
+                kind,
+                status,
+            } => {
+                let kind_str = match kind {
+                    ScriptKind::Setup => "setup",
+                    ScriptKind::Teardown => "teardown",
+                };
+                let status_str = if status.result.is_success() {
+                    "SCRIPT"
+                } else {
+                    "SCRIPT FAIL"
+                };
+                writeln!(writer, "# {}: {} ({})", status_str, script_name, kind_str)?;
+            }
+            TestEventKind::RunInfo { running_tests } => {
+                writeln!(writer, "# INFO: {} tests running", running_tests.len())?;
+                for running_test in running_tests {
+                    writeln!(
+                        writer,
+                        "# RUNNING for {:.3}s: {}",
+                        running_test.elapsed.as_secs_f64(),
+                        description(&running_test.test_instance)
+                    )?;
+                }
+            }
+            TestEventKind::RunBeginCancel {
+                running,
+                running_tests,
+                reason,
+            } => {
+                let reason_str = match reason {
+                    CancelReason::SetupScriptFailure => "setup script failure",
+                    CancelReason::TestFailure => "test failure",
+                    CancelReason::ReportError => "error",
+                    CancelReason::Signal => "signal",
+                };
+                writeln!(
+                    writer,
+                    "# Canceling due to {}: {} tests still running",
+                    reason_str, running
+                )?;
+                for running_test in running_tests {
+                    writeln!(
+                        writer,
+                        "# RUNNING for {:.3}s: {}",
+                        running_test.elapsed.as_secs_f64(),
+                        description(&running_test.test_instance)
+                    )?;
+                }
+            }
+            TestEventKind::RunFinished { .. } => {
+                writeln!(writer, "1..{}", self.test_points)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the description for a test point.
+///
+/// `#` starts a directive in TAP, so it is escaped.
+fn description(test_instance: &TestInstance<'_>) -> String {
+    format!(
+        "{} {}",
+        test_instance.bin_info.binary_id, test_instance.name
+    )
+    .replace('#', "\\#")
+}
+
+fn write_yaml_block(status: &ExecuteStatus, mut writer: impl Write) -> io::Result<()> {
+    let result = match status.result {
+        ExecutionResult::Pass => "pass",
+        ExecutionResult::Fail => "fail",
+        ExecutionResult::ExecFail => "exec-fail",
+        ExecutionResult::Timeout => "timeout",
+        ExecutionResult::ResourceLimit => "resource-limit",
+    };
+
+    writeln!(writer, "  ---")?;
+    writeln!(writer, "  result: {}", result)?;
+    writeln!(writer, "  attempt: {}", status.attempt)?;
+    writeln!(writer, "  total_attempts: {}", status.total_attempts)?;
+    writeln!(writer, "  duration_ms: {}", status.time_taken.as_millis())?;
+    if !status.result.is_success() {
+        write_yaml_output("stdout", status.stdout(), &mut writer)?;
+        write_yaml_output("stderr", status.stderr(), &mut writer)?;
+    }
+    writeln!(writer, "  ...")
+}
+
+fn write_yaml_output(key: &str, output: &[u8], mut writer: impl Write) -> io::Result<()> {
+    if output.is_empty() {
+        return Ok(());
+    }
+
+    let output = strip_ansi_escapes::strip(output)?;
+    writeln!(writer, "  {}: |", key)?;
+    for line in String::from_utf8_lossy(&output).lines() {
+        writeln!(writer, "    {}", line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{runner::RunStats, test_list::test_helpers::make_single_binary_test_list};
+    use std::{
+        sync::Arc,
+        time::{Duration, SystemTime},
+    };
+
+    #[test]
+    fn write_yaml_block_output() {
+        let status = ExecuteStatus {
+            attempt: 2,
+            total_attempts: 3,
+            stdout_stderr: Arc::new((b"line 1\n\x1b[31mline 2\x1b[0m\n".to_vec(), vec![])),
+            result: ExecutionResult::Fail,
+            start_time: SystemTime::now(),
+            time_taken: Duration::from_millis(1234),
+            core_dump: None,
+            output_dir: None,
+        };
+
+        let mut buf = Vec::new();
+        write_yaml_block(&status, &mut buf).expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(buf).expect("output is valid UTF-8"),
+            "  ---\n  result: fail\n  attempt: 2\n  total_attempts: 3\n  duration_ms: 1234\n  \
+             stdout: |\n    line 1\n    line 2\n  ...\n"
+        );
+    }
+
+    #[test]
+    fn plan_at_end() {
+        let mut reporter = TapReporter::new();
+        let mut buf = Vec::new();
+        reporter.test_points = 4;
+        reporter
+            .write_event(
+                &TestEvent::from_kind(TestEventKind::RunBeginCancel {
+                    running: 2,
+                    running_tests: vec![],
+                    reason: CancelReason::TestFailure,
+                }),
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+        reporter
+            .write_event(
+                &TestEvent::from_kind(TestEventKind::RunFinished {
+                    start_time: SystemTime::now(),
+                    elapsed: Duration::from_secs(1),
+                    run_stats: RunStats::default(),
+                }),
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+
+        assert_eq!(
+            String::from_utf8(buf).expect("output is valid UTF-8"),
+            "# Canceling due to test failure: 2 tests still running\n1..4\n"
+        );
+    }
+
+    #[test]
+    fn test_paused() {
+        let test_list = make_single_binary_test_list("tests::paused: test\n", "");
+        let test_instance = test_list.iter_tests().next().expect("test exists");
+        let mut buf = Vec::new();
+        TapReporter::new()
+            .write_event(
+                &TestEvent::from_kind(TestEventKind::TestPaused {
+                    test_instance,
+                    pid: 1234,
+                }),
+                &mut buf,
+            )
+            .expect("writing to a Vec succeeds");
+        assert_eq!(
+            String::from_utf8(buf).expect("output is valid UTF-8"),
+            "# PAUSED as process 1234: fake-package::fake-binary tests::paused\n"
+        );
+    }
+}
@@ -0,0 +1,264 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Colors and styles used by the human-readable reporter.
+//!
+//! A theme provides a set of default styles, and individual styles can be overridden in
+//! configuration. Styles that use 24-bit colors are downgraded to the closest basic color if the
+//! terminal doesn't support them.
+
+use crate::errors::StyleSpecParseError;
+use owo_colors::{AnsiColors, DynColors, Effect, Style};
+use serde::Deserialize;
+use std::{fmt, str::FromStr};
+
+/// A set of default styles for the reporter.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ColorTheme {
+    /// Bold colors for statuses, and colors for test names.
+    ///
+    /// This is the default.
+    Default,
+
+    /// The same colors as the default theme, without any bold text. Useful for log viewers that
+    /// render bold colors as hard-to-read bright colors.
+    Basic,
+
+    /// No colors at all, only bold and underlined text.
+    Monochrome,
+}
+
+impl Default for ColorTheme {
+    fn default() -> Self {
+        ColorTheme::Default
+    }
+}
+
+/// A style for a part of the reporter's output.
+///
+/// In configuration, this is specified as a space-separated list of at most one color and any
+/// number of effects, for example `"bold red"` or `"#ff8700 underline"`. `"none"` means no style.
+///
+/// Colors are one of `black`, `red`, `green`, `yellow`, `blue`, `magenta`, `cyan` and `white`,
+/// optionally prefixed with `bright-`, or a 24-bit color in the form `#rrggbb`. Effects are one of
+/// `bold`, `dimmed`, `italic` and `underline`.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub struct StyleSpec {
+    color: Option<DynColors>,
+    bold: bool,
+    dimmed: bool,
+    italic: bool,
+    underline: bool,
+}
+
+impl StyleSpec {
+    /// Returns the style to print with.
+    ///
+    /// If `truecolor` is false, 24-bit colors are replaced with the closest basic color.
+    pub fn to_style(self, truecolor: bool) -> Style {
+        let mut style = Style::new();
+        match self.color {
+            Some(DynColors::Rgb(r, g, b)) if !truecolor => {
+                style = style.color(closest_ansi_color(r, g, b));
+            }
+            Some(color) => style = style.color(color),
+            None => {}
+        }
+        let effects = [
+            (self.bold, Effect::Bold),
+            (self.dimmed, Effect::Dimmed),
+            (self.italic, Effect::Italic),
+            (self.underline, Effect::Underline),
+        ];
+        for (enabled, effect) in effects {
+            if enabled {
+                style = style.effect(effect);
+            }
+        }
+        style
+    }
+}
+
+impl FromStr for StyleSpec {
+    type Err = StyleSpecParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut spec = StyleSpec::default();
+        if s.trim() == "none" {
+            return Ok(spec);
+        }
+
+        for word in s.split_whitespace() {
+            match word {
+                "bold" => spec.bold = true,
+                "dimmed" => spec.dimmed = true,
+                "italic" => spec.italic = true,
+                "underline" => spec.underline = true,
+                color => {
+                    if spec.color.is_some() {
+                        return Err(StyleSpecParseError::new(s, "more than one color specified"));
+                    }
+                    let color = parse_color(color).ok_or_else(|| {
+                        StyleSpecParseError::new(s, format!("unknown color or effect: {}", color))
+                    })?;
+                    spec.color = Some(color);
+                }
+            }
+        }
+        Ok(spec)
+    }
+}
+
+impl<'de> Deserialize<'de> for StyleSpec {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        let input = String::deserialize(deserializer)?;
+        input.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+impl fmt::Display for StyleSpec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut words = vec![];
+        match self.color {
+            Some(DynColors::Rgb(r, g, b)) => words.push(format!("#{:02x}{:02x}{:02x}", r, g, b)),
+            Some(DynColors::Ansi(color)) => {
+                let (name, bright) = ANSI_COLORS
+                    .iter()
+                    .find_map(|&(name, normal, bright)| {
+                        if normal == color {
+                            Some((name, false))
+                        } else if bright == color {
+                            Some((name, true))
+                        } else {
+                            None
+                        }
+                    })
+                    .expect("only known colors are parsed");
+                match bright {
+                    true => words.push(format!("bright-{}", name)),
+                    false => words.push(name.to_owned()),
+                }
+            }
+            Some(_) | None => {}
+        }
+        for (enabled, name) in [
+            (self.bold, "bold"),
+            (self.dimmed, "dimmed"),
+            (self.italic, "italic"),
+            (self.underline, "underline"),
+        ] {
+            if enabled {
+                words.push(name.to_owned());
+            }
+        }
+        if words.is_empty() {
+            write!(f, "none")
+        } else {
+            write!(f, "{}", words.join(" "))
+        }
+    }
+}
+
+/// The basic colors, along with their names and bright variants.
+const ANSI_COLORS: &[(&str, AnsiColors, AnsiColors)] = &[
+    ("black", AnsiColors::Black, AnsiColors::BrightBlack),
+    ("red", AnsiColors::Red, AnsiColors::BrightRed),
+    ("green", AnsiColors::Green, AnsiColors::BrightGreen),
+    ("yellow", AnsiColors::Yellow, AnsiColors::BrightYellow),
+    ("blue", AnsiColors::Blue, AnsiColors::BrightBlue),
+    ("magenta", AnsiColors::Magenta, AnsiColors::BrightMagenta),
+    ("cyan", AnsiColors::Cyan, AnsiColors::BrightCyan),
+    ("white", AnsiColors::White, AnsiColors::BrightWhite),
+];
+
+fn parse_color(s: &str) -> Option<DynColors> {
+    if let Some(hex) = s.strip_prefix('#') {
+        if hex.len() != 6 || !hex.is_ascii() {
+            return None;
+        }
+        let component = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(DynColors::Rgb(component(0)?, component(2)?, component(4)?));
+    }
+
+    let (name, bright) = match s.strip_prefix("bright-") {
+        Some(name) => (name, true),
+        None => (s, false),
+    };
+    ANSI_COLORS
+        .iter()
+        .find(|(color_name, _, _)| *color_name == name)
+        .map(|&(_, normal, bright_color)| {
+            DynColors::Ansi(if bright { bright_color } else { normal })
+        })
+}
+
+/// Returns the basic color closest to the given 24-bit color.
+///
+/// Each component is rounded to either off or on, and the result picks out one of the eight basic
+/// colors, using the bright variant for light colors.
+fn closest_ansi_color(r: u8, g: u8, b: u8) -> AnsiColors {
+    let index = usize::from(r >= 128) | usize::from(g >= 128) << 1 | usize::from(b >= 128) << 2;
+    // Colors are laid out in ANSI order: black, red, green, yellow, blue, magenta, cyan, white.
+    let (_, normal, bright) = ANSI_COLORS[index];
+    let max = r.max(g).max(b);
+    if max >= 192 {
+        bright
+    } else {
+        normal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_style_spec() {
+        let valid = [
+            ("none", "none"),
+            ("red", "red"),
+            ("bold  red", "red bold"),
+            (
+                "bright-cyan underline italic",
+                "bright-cyan italic underline",
+            ),
+            ("#FF8700 dimmed", "#ff8700 dimmed"),
+        ];
+        for (input, output) in valid {
+            let spec: StyleSpec = input
+                .parse()
+                .unwrap_or_else(|err| panic!("{} is valid: {}", input, err));
+            assert_eq!(spec.to_string(), output, "input: {}", input);
+        }
+
+        for input in ["red blue", "purple", "#ff87", "bold #gg0000"] {
+            assert!(
+                input.parse::<StyleSpec>().is_err(),
+                "{} should fail to parse",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn downgrade_truecolor() {
+        let cases = [
+            ((0, 0, 0), "black"),
+            ((200, 30, 30), "bright-red"),
+            ((130, 130, 0), "yellow"),
+            ((40, 90, 170), "blue"),
+            ((250, 250, 250), "bright-white"),
+        ];
+        for ((r, g, b), expected) in cases {
+            let spec = StyleSpec {
+                color: Some(DynColors::Ansi(closest_ansi_color(r, g, b))),
+                ..StyleSpec::default()
+            };
+            assert_eq!(spec.to_string(), expected, "color: ({}, {}, {})", r, g, b);
+        }
+    }
+}
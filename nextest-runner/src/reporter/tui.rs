@@ -0,0 +1,556 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! A full-screen dashboard shown while tests run.
+//!
+//! The dashboard lists running tests along with how long they've been running for, and a pane
+//! with the failures so far. It's drawn on the terminal's alternate screen by a thread of its own,
+//! which redraws it several times a second and reads key presses from standard input:
+//!
+//! * `↑`/`↓` (or `k`/`j`) select a running test, and `c` cancels it.
+//! * `q` cancels the run. Pressing it again kills running tests.
+//! * `PgUp`/`PgDn` scroll the failure pane.
+//!
+//! The human-readable output that would have been printed during the run is held back, and
+//! written out once the run finishes and the dashboard is closed.
+
+use crate::{
+    reporter::{progress::terminal_size, Styles, TestEvent, TestEventKind},
+    runner::{ExecutionResult, RunController},
+    test_list::TestInstance,
+};
+use owo_colors::{OwoColorize, Style};
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+    thread::JoinHandle,
+    time::{Duration, Instant},
+};
+
+/// How often the dashboard is redrawn.
+const REDRAW_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug)]
+pub(super) struct Tui {
+    controller: RunController,
+    state: Arc<Mutex<TuiState>>,
+    thread: Option<JoinHandle<()>>,
+    // Output held back while the dashboard is shown.
+    buffered: Vec<u8>,
+}
+
+impl Tui {
+    pub(super) fn new(controller: RunController) -> Self {
+        Self {
+            controller,
+            state: Arc::new(Mutex::new(TuiState::default())),
+            thread: None,
+            buffered: vec![],
+        }
+    }
+
+    /// Returns true if the dashboard is currently shown.
+    pub(super) fn is_shown(&self) -> bool {
+        self.thread.is_some()
+    }
+
+    /// Updates the dashboard for the event, showing it once the run starts.
+    pub(super) fn update(&mut self, event: &TestEvent<'_>, styles: &Styles) -> io::Result<()> {
+        let mut state = self.state.lock().expect("lock wasn't poisoned");
+        match &event.kind {
+            TestEventKind::RunStarted {
+                test_list, repeat, ..
+            } => {
+                state.start_time = Some(Instant::now());
+                state.total = test_list.run_count() * repeat;
+                state.skipped = test_list.skip_count();
+                state.styles = TuiStyles {
+                    pass: styles.pass,
+                    fail: styles.fail,
+                    count: styles.count,
+                };
+                drop(state);
+                self.show()?;
+            }
+            TestEventKind::TestStarted { test_instance } => {
+                state.running.push(RunningEntry {
+                    binary_id: test_instance.bin_info.binary_id.clone(),
+                    test_name: test_instance.name.to_owned(),
+                    attempt: 1,
+                    start_time: Instant::now(),
+                    canceled: false,
+                });
+            }
+            TestEventKind::TestRetry { test_instance, .. } => {
+                if let Some(entry) = state.find_running(test_instance) {
+                    entry.attempt += 1;
+                    entry.start_time = Instant::now();
+                }
+            }
+            TestEventKind::TestFinished {
+                test_instance,
+                run_statuses,
+            } => {
+                state.remove_running(test_instance);
+                let last_status = run_statuses.last_status();
+                if last_status.result.is_success() {
+                    state.passed += 1;
+                } else {
+                    state.failed += 1;
+                    let label = match last_status.result {
+                        ExecutionResult::Timeout => "TIMEOUT",
+                        ExecutionResult::ResourceLimit => "LIMIT",
+                        _ => "FAIL",
+                    };
+                    state.failures.push(FailureLine::Heading(format!(
+                        "{:>7} [{:>8.3}s] {} {}",
+                        label,
+                        last_status.time_taken.as_secs_f64(),
+                        test_instance.bin_info.binary_id,
+                        test_instance.name
+                    )));
+                    let (stdout, stderr) = &*last_status.stdout_stderr;
+                    for output in [stdout, stderr] {
+                        let output = strip_ansi_escapes::strip(output).unwrap_or_default();
+                        state.failures.extend(
+                            String::from_utf8_lossy(&output)
+                                .lines()
+                                .map(|line| FailureLine::Output(line.replace('\t', "    "))),
+                        );
+                    }
+                }
+            }
+            TestEventKind::TestCanceled { .. } => {
+                state.canceled += 1;
+            }
+            TestEventKind::RunBeginCancel { .. } => {
+                state.canceling = true;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Holds back output written while the dashboard is shown.
+    pub(super) fn buffer(&mut self, output: &[u8]) {
+        self.buffered.extend_from_slice(output);
+    }
+
+    /// Closes the dashboard, returning the output that was held back.
+    pub(super) fn finish(&mut self) -> Vec<u8> {
+        self.hide();
+        std::mem::take(&mut self.buffered)
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn show(&mut self) -> io::Result<()> {
+        let raw_mode = imp::RawMode::enable()?;
+        let mut stderr = io::stderr();
+        // Switch to the alternate screen and hide the cursor.
+        write!(stderr, "\x1b[?1049h\x1b[?25l")?;
+        stderr.flush()?;
+
+        let state = self.state.clone();
+        let controller = self.controller.clone();
+        let thread = std::thread::Builder::new()
+            .name("nextest-tui".to_owned())
+            .spawn(move || {
+                let _raw_mode = raw_mode;
+                let mut stderr = io::stderr();
+                loop {
+                    {
+                        let mut state = state.lock().expect("lock wasn't poisoned");
+                        if state.done {
+                            break;
+                        }
+                        // Errors while drawing can't be reported from here, and the next redraw
+                        // may well succeed.
+                        let _ = state.draw(&mut stderr);
+                    }
+                    for key in imp::read_keys(REDRAW_INTERVAL) {
+                        let mut state = state.lock().expect("lock wasn't poisoned");
+                        state.handle_key(key, &controller);
+                    }
+                }
+            })?;
+        self.thread = Some(thread);
+        Ok(())
+    }
+
+    fn hide(&mut self) {
+        if let Some(thread) = self.thread.take() {
+            self.state.lock().expect("lock wasn't poisoned").done = true;
+            // Terminal settings are restored once the thread exits.
+            let _ = thread.join();
+            let mut stderr = io::stderr();
+            // Show the cursor and switch back to the main screen.
+            let _ = write!(stderr, "\x1b[?25h\x1b[?1049l");
+            let _ = stderr.flush();
+        }
+    }
+}
+
+impl Drop for Tui {
+    fn drop(&mut self) {
+        // Restore the terminal even if the run didn't finish normally.
+        self.hide();
+    }
+}
+
+#[derive(Debug, Default)]
+struct TuiState {
+    start_time: Option<Instant>,
+    total: usize,
+    passed: usize,
+    failed: usize,
+    skipped: usize,
+    canceled: usize,
+    canceling: bool,
+    running: Vec<RunningEntry>,
+    // The index of the selected running test.
+    selected: usize,
+    failures: Vec<FailureLine>,
+    // The first line of the failure pane that's shown, or None to follow new failures.
+    failure_scroll: Option<usize>,
+    // The number of lines in the failure pane when it was last drawn.
+    failure_height: usize,
+    styles: TuiStyles,
+    done: bool,
+}
+
+impl TuiState {
+    fn find_running(&mut self, test_instance: &TestInstance<'_>) -> Option<&mut RunningEntry> {
+        self.running.iter_mut().find(|entry| {
+            entry.binary_id == test_instance.bin_info.binary_id
+                && entry.test_name == test_instance.name
+        })
+    }
+
+    fn remove_running(&mut self, test_instance: &TestInstance<'_>) {
+        self.running.retain(|entry| {
+            entry.binary_id != test_instance.bin_info.binary_id
+                || entry.test_name != test_instance.name
+        });
+        self.selected = self.selected.min(self.running.len().saturating_sub(1));
+    }
+
+    fn handle_key(&mut self, key: Key, controller: &RunController) {
+        match key {
+            Key::Up => self.selected = self.selected.saturating_sub(1),
+            Key::Down => {
+                self.selected = (self.selected + 1).min(self.running.len().saturating_sub(1))
+            }
+            Key::CancelTest => {
+                if let Some(entry) = self.running.get_mut(self.selected) {
+                    controller.cancel_test(&entry.binary_id, &entry.test_name);
+                    entry.canceled = true;
+                }
+            }
+            Key::CancelRun => controller.cancel_run(),
+            Key::PageUp => {
+                let top = self.failure_top();
+                self.failure_scroll = Some(top.saturating_sub(self.failure_height.max(1)));
+            }
+            Key::PageDown => {
+                let top = self.failure_top() + self.failure_height.max(1);
+                // Follow new failures again once the bottom is reached.
+                self.failure_scroll = (top < self.max_failure_top()).then(|| top);
+            }
+        }
+    }
+
+    fn max_failure_top(&self) -> usize {
+        self.failures.len().saturating_sub(self.failure_height)
+    }
+
+    fn failure_top(&self) -> usize {
+        self.failure_scroll
+            .unwrap_or_else(|| self.max_failure_top())
+            .min(self.max_failure_top())
+    }
+
+    fn draw(&mut self, mut writer: impl Write) -> io::Result<()> {
+        let (width, height) = terminal_size();
+        let elapsed = self
+            .start_time
+            .map_or(Duration::ZERO, |start_time| start_time.elapsed());
+        let styles = self.styles;
+        let mut lines: Vec<String> = vec![];
+
+        let finished = self.passed + self.failed;
+        let mut header = format!(
+            "{} {}/{} tests finished: {} passed, {} failed, {} skipped",
+            format_elapsed(elapsed),
+            finished.style(styles.count),
+            self.total.style(styles.count),
+            self.passed.style(styles.pass),
+            self.failed.style(styles.fail),
+            self.skipped,
+        );
+        if self.canceled > 0 {
+            header.push_str(&format!(", {} canceled", self.canceled));
+        }
+        if self.canceling {
+            header.push_str(&format!(" -- {}", "canceling".style(styles.fail)));
+        }
+        lines.push(header);
+
+        // The running tests take up to half the screen, and the failure pane the rest, leaving a
+        // line for each heading and the help line.
+        let running_height = ((height.saturating_sub(4)) / 2).max(1);
+        lines.push(format!(
+            "{} ({}):",
+            "Running".style(styles.count),
+            self.running.len()
+        ));
+        let first_running = (self.selected + 1).saturating_sub(running_height);
+        for (index, entry) in self
+            .running
+            .iter()
+            .enumerate()
+            .skip(first_running)
+            .take(running_height)
+        {
+            let mut line = format!(
+                "[{:>8.1}s] {} {}",
+                entry.start_time.elapsed().as_secs_f64(),
+                entry.binary_id,
+                entry.test_name
+            );
+            if entry.attempt > 1 {
+                line.push_str(&format!(" (attempt {})", entry.attempt));
+            }
+            if entry.canceled {
+                line.push_str(" (canceling)");
+            }
+            let line = truncate(&line, width.saturating_sub(2));
+            if index == self.selected {
+                lines.push(format!("> {}", line.reversed()));
+            } else {
+                lines.push(format!("  {}", line));
+            }
+        }
+        for _ in self.running.len().saturating_sub(first_running)..running_height {
+            lines.push(String::new());
+        }
+
+        lines.push(format!(
+            "{} ({}):",
+            "Failures".style(styles.fail),
+            self.failed
+        ));
+        self.failure_height = height.saturating_sub(lines.len() + 1);
+        let top = self.failure_top();
+        for line in self.failures.iter().skip(top).take(self.failure_height) {
+            match line {
+                FailureLine::Heading(heading) => {
+                    lines.push(truncate(heading, width).style(styles.fail).to_string())
+                }
+                FailureLine::Output(output) => lines.push(truncate(output, width)),
+            }
+        }
+        while lines.len() + 1 < height {
+            lines.push(String::new());
+        }
+        lines.push(truncate(
+            "up/down: select  c: cancel test  q: cancel run  PgUp/PgDn: scroll failures",
+            width,
+        ));
+
+        // Move to the top left, then write each line over the previous contents.
+        write!(writer, "\x1b[H")?;
+        for (index, line) in lines.iter().take(height).enumerate() {
+            if index > 0 {
+                write!(writer, "\r\n")?;
+            }
+            write!(writer, "{}\x1b[K", line)?;
+        }
+        write!(writer, "\x1b[J")?;
+        writer.flush()
+    }
+}
+
+#[derive(Debug)]
+struct RunningEntry {
+    binary_id: String,
+    test_name: String,
+    attempt: usize,
+    start_time: Instant,
+    canceled: bool,
+}
+
+#[derive(Debug)]
+enum FailureLine {
+    Heading(String),
+    Output(String),
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct TuiStyles {
+    pass: Style,
+    fail: Style,
+    count: Style,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Key {
+    Up,
+    Down,
+    CancelTest,
+    CancelRun,
+    PageUp,
+    PageDown,
+}
+
+/// Parses key presses out of input read from the terminal.
+fn parse_keys(input: &[u8]) -> Vec<Key> {
+    let mut keys = vec![];
+    let mut rest = input;
+    while let Some((&byte, tail)) = rest.split_first() {
+        let (key, tail) = match (byte, tail) {
+            (b'\x1b', [b'[', b'A', tail @ ..]) => (Some(Key::Up), tail),
+            (b'\x1b', [b'[', b'B', tail @ ..]) => (Some(Key::Down), tail),
+            (b'\x1b', [b'[', b'5', b'~', tail @ ..]) => (Some(Key::PageUp), tail),
+            (b'\x1b', [b'[', b'6', b'~', tail @ ..]) => (Some(Key::PageDown), tail),
+            (b'k', _) => (Some(Key::Up), tail),
+            (b'j', _) => (Some(Key::Down), tail),
+            (b'c', _) => (Some(Key::CancelTest), tail),
+            (b'q', _) => (Some(Key::CancelRun), tail),
+            _ => (None, tail),
+        };
+        keys.extend(key);
+        rest = tail;
+    }
+    keys
+}
+
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!(
+        "[{:02}:{:02}:{:02}]",
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+/// Truncates the line to the given number of characters.
+fn truncate(line: &str, width: usize) -> String {
+    line.chars().take(width).collect()
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::{parse_keys, Key};
+    use std::{io, mem::MaybeUninit, time::Duration};
+
+    /// Turns off line buffering and echoing for standard input, restoring them when dropped.
+    ///
+    /// Signals such as Ctrl-C are still generated, so they're handled as usual.
+    #[derive(Debug)]
+    pub(super) struct RawMode {
+        // None if standard input isn't a terminal.
+        original: Option<libc::termios>,
+    }
+
+    impl RawMode {
+        pub(super) fn enable() -> io::Result<Self> {
+            let mut termios = MaybeUninit::uninit();
+            // SAFETY: tcgetattr initializes termios if it succeeds.
+            if unsafe { libc::tcgetattr(libc::STDIN_FILENO, termios.as_mut_ptr()) } != 0 {
+                // Without a terminal to read from, the dashboard is still shown, but it doesn't
+                // respond to keys.
+                return Ok(Self { original: None });
+            }
+            let original = unsafe { termios.assume_init() };
+            let mut raw = original;
+            raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+            raw.c_cc[libc::VMIN] = 1;
+            raw.c_cc[libc::VTIME] = 0;
+            if unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, &raw) } != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            Ok(Self {
+                original: Some(original),
+            })
+        }
+    }
+
+    impl Drop for RawMode {
+        fn drop(&mut self) {
+            if let Some(original) = &self.original {
+                unsafe { libc::tcsetattr(libc::STDIN_FILENO, libc::TCSANOW, original) };
+            }
+        }
+    }
+
+    /// Waits up to `timeout` for input, returning the keys pressed.
+    pub(super) fn read_keys(timeout: Duration) -> Vec<Key> {
+        let mut poll_fd = libc::pollfd {
+            fd: libc::STDIN_FILENO,
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        let ret = unsafe { libc::poll(&mut poll_fd, 1, timeout.as_millis() as _) };
+        if ret <= 0 || poll_fd.revents & libc::POLLIN == 0 {
+            if ret > 0 {
+                // Standard input was closed: there's nothing more to read, so just wait.
+                std::thread::sleep(timeout);
+            }
+            return vec![];
+        }
+        let mut buf = [0u8; 64];
+        let len = unsafe { libc::read(libc::STDIN_FILENO, buf.as_mut_ptr().cast(), buf.len()) };
+        if len <= 0 {
+            std::thread::sleep(timeout);
+            return vec![];
+        }
+        parse_keys(&buf[..len as usize])
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::Key;
+    use std::{io, time::Duration};
+
+    /// Reading keys is only supported on Unix, so the dashboard doesn't respond to them.
+    #[derive(Debug)]
+    pub(super) struct RawMode;
+
+    impl RawMode {
+        pub(super) fn enable() -> io::Result<Self> {
+            Ok(Self)
+        }
+    }
+
+    pub(super) fn read_keys(timeout: Duration) -> Vec<Key> {
+        std::thread::sleep(timeout);
+        vec![]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_key_presses() {
+        assert_eq!(
+            parse_keys(b"jk\x1b[A\x1b[Bcq\x1b[5~\x1b[6~x\x1b"),
+            vec![
+                Key::Down,
+                Key::Up,
+                Key::Up,
+                Key::Down,
+                Key::CancelTest,
+                Key::CancelRun,
+                Key::PageUp,
+                Key::PageDown,
+            ]
+        );
+    }
+}
@@ -0,0 +1,423 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Comparing the results of two test runs.
+//!
+//! [`RunDiffBuilder`] compares two [`RunSummary`] instances, for example obtained from
+//! [recorded runs](crate::record), and produces a [`RunDiff`]: the tests that started failing,
+//! started passing or became flaky, and the tests that got noticeably slower.
+
+use crate::{
+    errors::WriteTestListError,
+    helpers::write_test_name,
+    test_list::{OutputFormat, Styles},
+};
+use nextest_metadata::{
+    DurationRegressionSummary, OutcomeChangeSummary, RunDiffSummary, RunSummary, TestOutcome,
+};
+use owo_colors::OwoColorize;
+use std::{
+    collections::BTreeMap,
+    io::{self, Write},
+    time::Duration,
+};
+
+/// Compares two test runs.
+#[derive(Clone, Debug, Default)]
+pub struct RunDiffBuilder {
+    min_slowdown_ratio: Option<f64>,
+    min_slowdown: Option<Duration>,
+}
+
+impl RunDiffBuilder {
+    /// The default value for [`Self::set_min_slowdown_ratio`].
+    pub const DEFAULT_MIN_SLOWDOWN_RATIO: f64 = 1.5;
+
+    /// The default value for [`Self::set_min_slowdown`].
+    pub const DEFAULT_MIN_SLOWDOWN: Duration = Duration::from_millis(100);
+
+    /// Sets how many times slower a test must be in the new run to be reported as a duration
+    /// regression.
+    pub fn set_min_slowdown_ratio(&mut self, min_slowdown_ratio: f64) -> &mut Self {
+        self.min_slowdown_ratio = Some(min_slowdown_ratio);
+        self
+    }
+
+    /// Sets how much slower a test must be in the new run to be reported as a duration regression.
+    ///
+    /// This avoids reporting very fast tests, whose run times vary a lot relative to how long they
+    /// take.
+    pub fn set_min_slowdown(&mut self, min_slowdown: Duration) -> &mut Self {
+        self.min_slowdown = Some(min_slowdown);
+        self
+    }
+
+    /// Compares the old run with the new one.
+    ///
+    /// With `--repeat`, the runs of each test are combined: a test is considered to have failed
+    /// if any run of it failed, and to be flaky if any run of it was flaky.
+    pub fn build(&self, old: &RunSummary, new: &RunSummary) -> RunDiff {
+        let min_slowdown_ratio = self
+            .min_slowdown_ratio
+            .unwrap_or(Self::DEFAULT_MIN_SLOWDOWN_RATIO);
+        let min_slowdown = self
+            .min_slowdown
+            .unwrap_or(Self::DEFAULT_MIN_SLOWDOWN)
+            .as_secs_f64();
+
+        let old_tests = CombinedTest::from_summary(old);
+        let new_tests = CombinedTest::from_summary(new);
+
+        let mut summary = RunDiffSummary::default();
+        for (&(binary_id, test_name), new_test) in &new_tests {
+            let old_test = old_tests.get(&(binary_id, test_name));
+            let old_outcome = old_test.map(|test| test.outcome);
+            let change = || OutcomeChangeSummary {
+                binary_id: binary_id.to_owned(),
+                test_name: test_name.to_owned(),
+                old_outcome,
+                new_outcome: new_test.outcome,
+            };
+
+            match new_test.outcome {
+                TestOutcome::Failed if old_outcome != Some(TestOutcome::Failed) => {
+                    summary.newly_failing.push(change());
+                }
+                TestOutcome::Passed if old_outcome == Some(TestOutcome::Failed) => {
+                    summary.newly_passing.push(change());
+                }
+                TestOutcome::Flaky if old_outcome != Some(TestOutcome::Flaky) => {
+                    summary.newly_flaky.push(change());
+                }
+                _ => {}
+            }
+
+            let exec_times = old_test
+                .and_then(CombinedTest::mean_pass_time)
+                .zip(new_test.mean_pass_time());
+            if let Some((old_exec_time, new_exec_time)) = exec_times {
+                if new_exec_time >= old_exec_time * min_slowdown_ratio
+                    && new_exec_time - old_exec_time >= min_slowdown
+                {
+                    summary
+                        .duration_regressions
+                        .push(DurationRegressionSummary {
+                            binary_id: binary_id.to_owned(),
+                            test_name: test_name.to_owned(),
+                            old_exec_time_secs: old_exec_time,
+                            new_exec_time_secs: new_exec_time,
+                        });
+                }
+            }
+        }
+
+        RunDiff {
+            summary,
+            styles: Box::new(Styles::default()),
+        }
+    }
+}
+
+/// The differences between two test runs, produced by [`RunDiffBuilder`].
+#[derive(Clone, Debug)]
+pub struct RunDiff {
+    summary: RunDiffSummary,
+    styles: Box<Styles>,
+}
+
+impl RunDiff {
+    /// Colorizes output.
+    pub fn colorize(&mut self) {
+        self.styles.colorize();
+    }
+
+    /// Returns a serializable summary of the differences.
+    pub fn summary(&self) -> &RunDiffSummary {
+        &self.summary
+    }
+
+    /// Returns true if any tests failed in the new run that didn't fail in the old run.
+    pub fn has_new_failures(&self) -> bool {
+        !self.summary.newly_failing.is_empty()
+    }
+
+    /// Outputs the differences to the given writer.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { .. } => self.write_human(writer).map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.summary, writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write) -> io::Result<()> {
+        if self.summary.is_empty() {
+            return writeln!(writer, "no differences found");
+        }
+
+        let sections = [
+            ("newly failing:", &self.summary.newly_failing),
+            ("newly passing:", &self.summary.newly_passing),
+            ("newly flaky:", &self.summary.newly_flaky),
+        ];
+        for (heading, changes) in sections.iter() {
+            if changes.is_empty() {
+                continue;
+            }
+            self.write_heading(heading, changes.len(), &mut writer)?;
+            for change in changes.iter() {
+                write!(writer, "  ")?;
+                self.write_test(&change.binary_id, &change.test_name, &mut writer)?;
+                match change.old_outcome {
+                    Some(old_outcome) => writeln!(
+                        writer,
+                        " ({} -> {})",
+                        outcome_str(old_outcome),
+                        outcome_str(change.new_outcome)
+                    )?,
+                    None => writeln!(writer, " (new test)")?,
+                }
+            }
+        }
+
+        let regressions = &self.summary.duration_regressions;
+        if !regressions.is_empty() {
+            self.write_heading("slower:", regressions.len(), &mut writer)?;
+            for regression in regressions {
+                write!(writer, "  ")?;
+                self.write_test(&regression.binary_id, &regression.test_name, &mut writer)?;
+                writeln!(
+                    writer,
+                    " ({:.3}s -> {:.3}s)",
+                    regression.old_exec_time_secs, regression.new_exec_time_secs
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn write_heading(&self, heading: &str, count: usize, mut writer: impl Write) -> io::Result<()> {
+        writeln!(
+            writer,
+            "{} {} {}",
+            heading.style(self.styles.field),
+            count,
+            if count == 1 { "test" } else { "tests" }
+        )
+    }
+
+    fn write_test(
+        &self,
+        binary_id: &str,
+        test_name: &str,
+        mut writer: impl Write,
+    ) -> io::Result<()> {
+        write!(writer, "{} ", binary_id.style(self.styles.binary_id))?;
+        write_test_name(test_name, &self.styles, writer)
+    }
+}
+
+/// All the runs of a single test, combined together.
+struct CombinedTest {
+    outcome: TestOutcome,
+    // The time taken by the passing attempt of each run that passed.
+    pass_times: Vec<f64>,
+}
+
+impl CombinedTest {
+    fn from_summary(summary: &RunSummary) -> BTreeMap<(&str, &str), Self> {
+        let mut tests: BTreeMap<_, CombinedTest> = BTreeMap::new();
+        for test in &summary.tests {
+            let combined = tests
+                .entry((test.binary_id.as_str(), test.test_name.as_str()))
+                .or_insert(CombinedTest {
+                    outcome: test.outcome,
+                    pass_times: vec![],
+                });
+            if outcome_rank(test.outcome) > outcome_rank(combined.outcome) {
+                combined.outcome = test.outcome;
+            }
+            if matches!(test.outcome, TestOutcome::Passed | TestOutcome::Flaky) {
+                if let Some(last_attempt) = test.attempts.last() {
+                    combined.pass_times.push(last_attempt.exec_time_secs);
+                }
+            }
+        }
+        tests
+    }
+
+    /// Returns the mean time taken by passing runs of this test, if it passed overall.
+    fn mean_pass_time(&self) -> Option<f64> {
+        let passed = matches!(self.outcome, TestOutcome::Passed | TestOutcome::Flaky);
+        if !passed || self.pass_times.is_empty() {
+            return None;
+        }
+        Some(self.pass_times.iter().sum::<f64>() / self.pass_times.len() as f64)
+    }
+}
+
+/// Used to combine the outcomes of several runs of a test: the outcome with the highest rank wins.
+fn outcome_rank(outcome: TestOutcome) -> u8 {
+    match outcome {
+        TestOutcome::Failed => 4,
+        TestOutcome::Flaky => 3,
+        TestOutcome::Passed => 2,
+        TestOutcome::Canceled => 1,
+        _ => 0,
+    }
+}
+
+fn outcome_str(outcome: TestOutcome) -> &'static str {
+    match outcome {
+        TestOutcome::Passed => "passed",
+        TestOutcome::Flaky => "flaky",
+        TestOutcome::Failed => "failed",
+        TestOutcome::Skipped => "skipped",
+        TestOutcome::Canceled => "canceled",
+        _ => "unknown",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use indoc::indoc;
+    use nextest_metadata::{AttemptSummary, ExecutionResultSummary, TestRunSummary};
+
+    fn test_run(test_name: &str, outcome: TestOutcome, exec_time_secs: f64) -> TestRunSummary {
+        let result = match outcome {
+            TestOutcome::Failed => ExecutionResultSummary::Fail,
+            _ => ExecutionResultSummary::Pass,
+        };
+        TestRunSummary {
+            binary_id: "my-crate".to_owned(),
+            test_name: test_name.to_owned(),
+            outcome,
+            skip_reason: None,
+            attempts: vec![AttemptSummary {
+                attempt: 1,
+                total_attempts: 1,
+                result,
+                exec_time_secs,
+                core_dump: None,
+                output_dir: None,
+            }],
+        }
+    }
+
+    fn run_summary(tests: Vec<TestRunSummary>) -> RunSummary {
+        let mut summary = RunSummary::default();
+        summary.tests = tests;
+        summary
+    }
+
+    fn names(changes: &[OutcomeChangeSummary]) -> Vec<&str> {
+        changes
+            .iter()
+            .map(|change| change.test_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn diff_runs() {
+        use TestOutcome::*;
+
+        let old = run_summary(vec![
+            test_run("fixed", Failed, 0.1),
+            test_run("broken", Passed, 0.1),
+            test_run("still_broken", Failed, 0.1),
+            test_run("now_flaky", Passed, 0.1),
+            test_run("slower", Passed, 1.0),
+            test_run("slightly_slower", Passed, 1.0),
+            test_run("fast_but_slower", Passed, 0.001),
+            // With --repeat, a single failing run means the test failed overall.
+            test_run("repeated", Passed, 0.1),
+            test_run("repeated", Failed, 0.1),
+        ]);
+        let new = run_summary(vec![
+            test_run("fixed", Passed, 0.1),
+            test_run("broken", Failed, 0.1),
+            test_run("still_broken", Failed, 0.1),
+            test_run("now_flaky", Flaky, 0.1),
+            test_run("slower", Passed, 2.0),
+            test_run("slightly_slower", Passed, 1.2),
+            test_run("fast_but_slower", Passed, 0.01),
+            test_run("repeated", Passed, 0.1),
+            test_run("repeated", Passed, 0.1),
+            test_run("added", Failed, 0.1),
+        ]);
+
+        let diff = RunDiffBuilder::default().build(&old, &new);
+        let summary = diff.summary();
+        assert_eq!(names(&summary.newly_failing), vec!["added", "broken"]);
+        assert_eq!(summary.newly_failing[0].old_outcome, None);
+        assert_eq!(summary.newly_failing[1].old_outcome, Some(Passed));
+        assert_eq!(names(&summary.newly_passing), vec!["fixed", "repeated"]);
+        assert_eq!(names(&summary.newly_flaky), vec!["now_flaky"]);
+        let regressions: Vec<_> = summary
+            .duration_regressions
+            .iter()
+            .map(|regression| regression.test_name.as_str())
+            .collect();
+        assert_eq!(
+            regressions,
+            vec!["slower"],
+            "small slowdowns and slowdowns of fast tests aren't reported"
+        );
+        assert!(diff.has_new_failures());
+
+        let diff = RunDiffBuilder::default()
+            .set_min_slowdown_ratio(1.1)
+            .set_min_slowdown(Duration::ZERO)
+            .build(&old, &new);
+        let regressions: Vec<_> = diff
+            .summary()
+            .duration_regressions
+            .iter()
+            .map(|regression| regression.test_name.as_str())
+            .collect();
+        assert_eq!(
+            regressions,
+            vec!["fast_but_slower", "slightly_slower", "slower"]
+        );
+
+        let diff = RunDiffBuilder::default().build(&new, &new);
+        assert!(
+            diff.summary().is_empty(),
+            "a run doesn't differ from itself"
+        );
+        assert_eq!(human_output(&diff), "no differences found\n");
+    }
+
+    #[test]
+    fn human_output_lists_changes() {
+        let old = run_summary(vec![test_run("tests::slow", TestOutcome::Passed, 0.5)]);
+        let new = run_summary(vec![
+            test_run("tests::slow", TestOutcome::Passed, 1.5),
+            test_run("tests::added", TestOutcome::Failed, 0.1),
+        ]);
+        let diff = RunDiffBuilder::default().build(&old, &new);
+        assert_eq!(
+            human_output(&diff),
+            indoc! {"
+                newly failing: 1 test
+                  my-crate tests::added (new test)
+                slower: 1 test
+                  my-crate tests::slow (0.500s -> 1.500s)
+            "}
+        );
+    }
+
+    fn human_output(diff: &RunDiff) -> String {
+        let mut buf = Vec::new();
+        diff.write(OutputFormat::Human { verbose: false }, &mut buf)
+            .expect("writing to a Vec succeeds");
+        String::from_utf8(buf).expect("output is valid UTF-8")
+    }
+}
@@ -0,0 +1,315 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Combining the results of partitioned test runs.
+//!
+//! When tests are split across several machines with `--partition`, each shard produces a
+//! [`RunSummary`] of its own. [`RunMerge`] combines them into a single summary, and checks that
+//! every test was run by exactly one shard.
+
+use nextest_metadata::{MismatchReason, RunSummary, TestOutcome};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// The results of several partitioned test runs, combined together.
+#[derive(Clone, Debug)]
+pub struct RunMerge {
+    summary: RunSummary,
+    missing_tests: Vec<MissingTest>,
+    duplicate_tests: Vec<DuplicateTest>,
+}
+
+impl RunMerge {
+    /// Combines the summaries of each shard in a partitioned run.
+    ///
+    /// Each shard lists the tests in other partitions as skipped. Those entries are dropped from
+    /// the combined summary; a test that every shard skipped because of partitioning is reported
+    /// as missing. A test that was run by more than one shard is reported as a duplicate.
+    ///
+    /// Tests skipped for other reasons, such as not matching a filter, are skipped by every shard
+    /// alike, so they're taken from the first shard that lists them.
+    pub fn new(shards: &[RunSummary]) -> Self {
+        let mut summary = RunSummary::default();
+        // The shards that ran each test.
+        let mut run_by: BTreeMap<(&str, &str), Vec<usize>> = BTreeMap::new();
+        // The first shard that skipped each test for a reason other than partitioning.
+        let mut skipped_by: BTreeMap<(&str, &str), usize> = BTreeMap::new();
+        let mut partitioned = BTreeSet::new();
+
+        for (index, shard) in shards.iter().enumerate() {
+            // Shards run in parallel, so the combined run took as long as the slowest shard.
+            summary.elapsed_secs = summary.elapsed_secs.max(shard.elapsed_secs);
+            add_stats(&mut summary, shard);
+
+            for test in &shard.tests {
+                let key = (test.binary_id.as_str(), test.test_name.as_str());
+                match test.skip_reason {
+                    Some(MismatchReason::Partition) => {
+                        partitioned.insert(key);
+                        continue;
+                    }
+                    Some(_) => {
+                        if *skipped_by.entry(key).or_insert(index) != index {
+                            continue;
+                        }
+                    }
+                    None => {
+                        let shards = run_by.entry(key).or_default();
+                        // With --repeat, a shard lists each run of a test separately.
+                        if shards.last() != Some(&index) {
+                            shards.push(index);
+                        }
+                    }
+                }
+                summary.tests.push(test.clone());
+            }
+        }
+
+        // The shards' own skip counts include tests in other partitions, and tests skipped by
+        // every shard. Count the skipped tests that were kept instead.
+        let stats = &mut summary.run_stats;
+        stats.skipped = 0;
+        stats.skipped_by_default_filter = 0;
+        stats.excluded = 0;
+        for test in &summary.tests {
+            if test.outcome != TestOutcome::Skipped {
+                continue;
+            }
+            stats.skipped += 1;
+            match test.skip_reason {
+                Some(MismatchReason::DefaultFilter) => stats.skipped_by_default_filter += 1,
+                Some(MismatchReason::Excluded) => stats.excluded += 1,
+                _ => {}
+            }
+        }
+
+        // Sorting is stable, so the runs of a test stay in order.
+        summary
+            .tests
+            .sort_by(|a, b| (&a.binary_id, &a.test_name).cmp(&(&b.binary_id, &b.test_name)));
+
+        let missing_tests = partitioned
+            .into_iter()
+            .filter(|key| !run_by.contains_key(key) && !skipped_by.contains_key(key))
+            .map(|(binary_id, test_name)| MissingTest {
+                binary_id: binary_id.to_owned(),
+                test_name: test_name.to_owned(),
+            })
+            .collect();
+        let duplicate_tests = run_by
+            .into_iter()
+            .filter(|(_, shards)| shards.len() > 1)
+            .map(|((binary_id, test_name), shards)| DuplicateTest {
+                binary_id: binary_id.to_owned(),
+                test_name: test_name.to_owned(),
+                shards,
+            })
+            .collect();
+
+        Self {
+            summary,
+            missing_tests,
+            duplicate_tests,
+        }
+    }
+
+    /// Returns the combined summary.
+    pub fn summary(&self) -> &RunSummary {
+        &self.summary
+    }
+
+    /// Returns the tests that no shard ran, sorted by binary ID and then by test name.
+    ///
+    /// These are usually tests in a partition whose summary wasn't included.
+    pub fn missing_tests(&self) -> &[MissingTest] {
+        &self.missing_tests
+    }
+
+    /// Returns the tests that were run by more than one shard, sorted by binary ID and then by
+    /// test name.
+    pub fn duplicate_tests(&self) -> &[DuplicateTest] {
+        &self.duplicate_tests
+    }
+
+    /// Returns true if every test was run by exactly one shard.
+    pub fn is_complete(&self) -> bool {
+        self.missing_tests.is_empty() && self.duplicate_tests.is_empty()
+    }
+
+    /// Returns true if every test was run by exactly one shard, and the combined run succeeded.
+    pub fn is_success(&self) -> bool {
+        self.is_complete() && self.summary.run_stats.is_success()
+    }
+}
+
+/// A test that wasn't run by any shard. Part of a [`RunMerge`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct MissingTest {
+    /// The unique identifier of the binary the test is in.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub test_name: String,
+}
+
+/// A test that was run by more than one shard. Part of a [`RunMerge`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DuplicateTest {
+    /// The unique identifier of the binary the test is in.
+    pub binary_id: String,
+
+    /// The name of the test.
+    pub test_name: String,
+
+    /// The indexes of the shards that ran the test, in the order they were passed in.
+    pub shards: Vec<usize>,
+}
+
+fn add_stats(summary: &mut RunSummary, shard: &RunSummary) {
+    let stats = &mut summary.run_stats;
+    let shard = &shard.run_stats;
+    stats.initial_run_count += shard.initial_run_count;
+    stats.final_run_count += shard.final_run_count;
+    stats.passed += shard.passed;
+    stats.flaky += shard.flaky;
+    stats.failed += shard.failed;
+    stats.exec_failed += shard.exec_failed;
+    stats.timed_out += shard.timed_out;
+    stats.resource_limited += shard.resource_limited;
+    stats.quarantined += shard.quarantined;
+    stats.canceled += shard.canceled;
+    stats.scripts_failed += shard.scripts_failed;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use nextest_metadata::TestRunSummary;
+
+    fn test(
+        test_name: &str,
+        outcome: TestOutcome,
+        skip_reason: Option<MismatchReason>,
+    ) -> TestRunSummary {
+        TestRunSummary {
+            binary_id: "my-crate".to_owned(),
+            test_name: test_name.to_owned(),
+            outcome,
+            skip_reason,
+            attempts: vec![],
+        }
+    }
+
+    fn passed(test_name: &str) -> TestRunSummary {
+        test(test_name, TestOutcome::Passed, None)
+    }
+
+    fn skipped(test_name: &str, reason: MismatchReason) -> TestRunSummary {
+        test(test_name, TestOutcome::Skipped, Some(reason))
+    }
+
+    fn shard(elapsed_secs: f64, tests: Vec<TestRunSummary>) -> RunSummary {
+        let mut summary = RunSummary::default();
+        summary.elapsed_secs = elapsed_secs;
+        for test in &tests {
+            match test.outcome {
+                TestOutcome::Passed => summary.run_stats.passed += 1,
+                TestOutcome::Failed => summary.run_stats.failed += 1,
+                TestOutcome::Skipped => summary.run_stats.skipped += 1,
+                _ => {}
+            }
+            if test.skip_reason.is_none() {
+                summary.run_stats.initial_run_count += 1;
+                summary.run_stats.final_run_count += 1;
+            }
+        }
+        summary.tests = tests;
+        summary
+    }
+
+    fn names(summary: &RunSummary) -> Vec<&str> {
+        summary
+            .tests
+            .iter()
+            .map(|test| test.test_name.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn merge_shards() {
+        use MismatchReason::*;
+
+        let shards = vec![
+            shard(
+                2.0,
+                vec![
+                    passed("a"),
+                    skipped("b", Partition),
+                    skipped("c", Partition),
+                    skipped("ignored", Ignored),
+                ],
+            ),
+            shard(
+                3.0,
+                vec![
+                    skipped("a", Partition),
+                    passed("b"),
+                    test("c", TestOutcome::Failed, None),
+                    skipped("ignored", Ignored),
+                ],
+            ),
+        ];
+
+        let merge = RunMerge::new(&shards);
+        let summary = merge.summary();
+        assert_eq!(names(summary), vec!["a", "b", "c", "ignored"]);
+        assert_eq!(summary.elapsed_secs, 3.0);
+        assert_eq!(summary.run_stats.initial_run_count, 3);
+        assert_eq!(summary.run_stats.passed, 2);
+        assert_eq!(summary.run_stats.failed, 1);
+        assert_eq!(
+            summary.run_stats.skipped, 1,
+            "partitioned tests aren't counted, and tests skipped by every shard are counted once"
+        );
+        assert!(merge.is_complete());
+        assert!(!merge.is_success(), "a test failed");
+
+        // Drop the second shard: b and c are missing.
+        let merge = RunMerge::new(&shards[..1]);
+        assert_eq!(names(merge.summary()), vec!["a", "ignored"]);
+        let missing: Vec<_> = merge
+            .missing_tests()
+            .iter()
+            .map(|test| test.test_name.as_str())
+            .collect();
+        assert_eq!(missing, vec!["b", "c"]);
+        assert!(merge.duplicate_tests().is_empty());
+        assert!(!merge.is_success());
+    }
+
+    #[test]
+    fn merge_duplicates() {
+        let shards = vec![
+            // With --repeat, a shard lists a test more than once; that isn't a duplicate.
+            shard(1.0, vec![passed("a"), passed("a"), passed("b")]),
+            shard(
+                1.0,
+                vec![skipped("a", MismatchReason::Partition), passed("b")],
+            ),
+            shard(1.0, vec![passed("b")]),
+        ];
+
+        let merge = RunMerge::new(&shards);
+        assert_eq!(names(merge.summary()), vec!["a", "a", "b", "b", "b"]);
+        assert!(merge.missing_tests().is_empty());
+        assert_eq!(
+            merge.duplicate_tests(),
+            &[DuplicateTest {
+                binary_id: "my-crate".to_owned(),
+                test_name: "b".to_owned(),
+                shards: vec![0, 1, 2],
+            }]
+        );
+        assert!(merge.summary().run_stats.is_success());
+        assert!(!merge.is_success());
+    }
+}
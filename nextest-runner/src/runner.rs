@@ -5,41 +5,133 @@
 //!
 //! The main structure in this module is [`TestRunner`].
 
+mod core_dump;
+mod libtest_json;
+mod netns;
+mod output;
+mod output_dir;
+mod panic;
+mod pause;
+pub(crate) mod process;
+mod recorder;
+mod slots;
+mod throttle;
+mod tmpdir;
+
 use crate::{
-    config::NextestProfile,
-    reporter::{CancelReason, StatusLevel, TestEvent},
-    signal::{SignalEvent, SignalHandler},
+    config::{
+        CpuSet, NextestProfile, ResourceLimits, RetainTmpdir, RetryPolicy, RustBacktrace,
+        ScriptConfig, SlowTimeout, TestColor, TestGroupScope, TestOutputDirConfig, TestSettings,
+        TestThreads, TestTmpdirConfig, ThrottleConfig,
+    },
+    coverage::{CoverageCollector, LLVM_PROFILE_FILE_ENV},
+    double_spawn::{self, DoubleSpawnInfo},
+    errors::{NetworkIsolationError, WriteEventError},
+    remote::SshRunner,
+    reporter::{CancelReason, Reporter, StatusLevel, TestEvent, TestEventKind},
+    script::{self, ScriptKind, ScriptStatus},
+    signal::{ShutdownEvent, SignalEvent, SignalHandler},
     stopwatch::{StopwatchEnd, StopwatchStart},
+    store::{ArtifactKind, RunStore},
     target_runner::TargetRunner,
     test_list::{TestInstance, TestList},
+    timings::TestTimings,
 };
-use crossbeam_channel::{RecvTimeoutError, Sender};
-use nextest_metadata::{FilterMatch, MismatchReason};
-use rayon::{ThreadPool, ThreadPoolBuilder};
+use camino::{Utf8Path, Utf8PathBuf};
+use core_dump::CoreDumpDir;
+use debug_ignore::DebugIgnore;
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use libtest_json::{LibtestEventKind, LibtestOutput, LibtestProgress};
+use nextest_metadata::{
+    AttemptSummary, BuildPlatform, ExecutionResultSummary, FilterMatch, MismatchReason,
+    RunStatsSummary, RustTestHarness, TestOutcome, TestRunSummary,
+};
+use once_cell::sync::OnceCell;
+use output::OutputCapture;
+use output_dir::TestOutputDir;
+pub use panic::TestPanic;
+use process::{ProcessSetup, ProcessTree};
+use rand::{seq::SliceRandom, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use recorder::Recorder;
+use serde::{Deserialize, Serialize};
+use slots::{SlotAllocator, TestSlots};
 use std::{
-    convert::Infallible,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
+    ffi::OsString,
+    io::{self, Write},
     marker::PhantomData,
+    num::NonZeroUsize,
     sync::{
         atomic::{AtomicBool, Ordering},
-        Arc,
+        Arc, Mutex,
+    },
+    time::{Duration, Instant, SystemTime},
+};
+use throttle::{SystemLoad, Throttle};
+use tmpdir::TestTmpdir;
+use tokio::{
+    runtime::Runtime,
+    sync::{
+        mpsc::{self, UnboundedReceiver, UnboundedSender},
+        watch,
     },
-    time::{Duration, SystemTime},
 };
+use uuid::Uuid;
+
+/// The tests that share a test group's limit: the group's name, along with the package name or
+/// binary ID for groups scoped to packages or binaries.
+type GroupKey<'a> = (&'a str, Option<&'a str>);
+
+/// The value of `NEXTEST_EXECUTION_MODE` for tests: each test is run in its own process.
+const EXECUTION_MODE_PROCESS_PER_TEST: &str = "process-per-test";
+
+/// The value of `NEXTEST_EXECUTION_MODE` for tests run in a batch, along with other tests from the
+/// same binary.
+const EXECUTION_MODE_BATCHED: &str = "batched";
+
+/// How often the output of a batch of tests is checked for tests starting and finishing.
+const PROGRESS_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Checks whether tests can be run in network namespaces of their own, as configured through
+/// [`TestRunnerBuilder::set_network_isolation`].
+///
+/// On Linux, this requires either `CAP_SYS_ADMIN` or support for unprivileged user namespaces.
+pub fn check_network_isolation() -> Result<(), NetworkIsolationError> {
+    netns::check_available()
+}
 
 /// Test runner options.
 #[derive(Debug, Default)]
-pub struct TestRunnerBuilder {
+pub struct TestRunnerBuilder<'a> {
     no_capture: bool,
     retries: Option<usize>,
     fail_fast: Option<bool>,
-    test_threads: Option<usize>,
+    max_fail: Option<NonZeroUsize>,
+    test_threads: Option<TestThreads>,
     target_runner: Option<TargetRunner>,
+    remote: Option<SshRunner>,
+    coverage: Option<CoverageCollector>,
+    shuffle_seed: Option<u64>,
+    timings: Option<TestTimings>,
+    repeat: Option<NonZeroUsize>,
+    network_isolation: bool,
+    record_failures: Option<bool>,
+    wait_for_debugger: bool,
+    double_spawn: DoubleSpawnInfo,
+    reporters: DebugIgnore<Vec<Box<dyn Reporter<'a> + 'a>>>,
+    control: Option<(
+        UnboundedSender<ControlEvent>,
+        UnboundedReceiver<ControlEvent>,
+    )>,
 }
 
-impl TestRunnerBuilder {
+impl<'a> TestRunnerBuilder<'a> {
     /// Sets no-capture mode.
     ///
-    /// In this mode, tests will always be run serially: `test_threads` will always be 1.
+    /// In this mode, the standard output and standard error of tests and scripts are passed through
+    /// to the terminal rather than being captured. Tests will always be run serially: `test_threads`
+    /// will always be 1.
     pub fn set_no_capture(&mut self, no_capture: bool) -> &mut Self {
         self.no_capture = no_capture;
         self
@@ -57,8 +149,17 @@ impl TestRunnerBuilder {
         self
     }
 
-    /// Sets the number of tests to run simultaneously.
-    pub fn set_test_threads(&mut self, test_threads: usize) -> &mut Self {
+    /// Cancels the test run once this many tests have failed.
+    ///
+    /// Takes precedence over the fail-fast setting: fail-fast is equivalent to a maximum of 1
+    /// failure.
+    pub fn set_max_fail(&mut self, max_fail: NonZeroUsize) -> &mut Self {
+        self.max_fail = Some(max_fail);
+        self
+    }
+
+    /// Sets the number of tests to run simultaneously, overriding the profile.
+    pub fn set_test_threads(&mut self, test_threads: TestThreads) -> &mut Self {
         self.test_threads = Some(test_threads);
         self
     }
@@ -70,8 +171,106 @@ impl TestRunnerBuilder {
         self
     }
 
+    /// Runs tests on a remote host over SSH, instead of on this machine.
+    ///
+    /// The target runner, if any, is not used for remote tests. Resource limits are not applied to
+    /// remote tests either.
+    pub fn set_remote(&mut self, remote: SshRunner) -> &mut Self {
+        self.remote = Some(remote);
+        self
+    }
+
+    /// Collects LLVM source-based coverage data for each test into the given collector.
+    pub fn set_coverage(&mut self, coverage: CoverageCollector) -> &mut Self {
+        self.coverage = Some(coverage);
+        self
+    }
+
+    /// Runs tests in a random order determined by the given seed, rather than in the order they
+    /// were listed in.
+    ///
+    /// Tests are shuffled both within and across binaries. Running the same test list with the same
+    /// seed results in the same order.
+    pub fn set_shuffle_seed(&mut self, seed: u64) -> &mut Self {
+        self.shuffle_seed = Some(seed);
+        self
+    }
+
+    /// Runs each test the given number of times in a row, for stress testing.
+    ///
+    /// Every run of a test is reported as a separate [`TestEventKind::TestFinished`] event.
+    pub fn set_repeat(&mut self, repeat: NonZeroUsize) -> &mut Self {
+        self.repeat = Some(repeat);
+        self
+    }
+
+    /// Runs each test process in a network namespace of its own, so that it can't reach the
+    /// network. Only supported on Linux.
+    ///
+    /// If [`check_network_isolation`] fails, tests will fail to start. Network isolation is not
+    /// applied to remote tests.
+    pub fn set_network_isolation(&mut self, network_isolation: bool) -> &mut Self {
+        self.network_isolation = network_isolation;
+        self
+    }
+
+    /// Runs tests that failed once more under the profile's recorder, after all tests have
+    /// finished, overriding the profile. See [`NextestProfile::record_failures`].
+    pub fn set_record_failures(&mut self, record_failures: bool) -> &mut Self {
+        self.record_failures = Some(record_failures);
+        self
+    }
+
+    /// Starts each test process stopped, right before the test is executed, and waits for the user
+    /// to press Enter before resuming it. This gives a debugger a chance to attach to the test.
+    ///
+    /// The ID of the stopped process is reported through [`TestEventKind::TestPaused`]. Requires
+    /// [double-spawning](Self::set_double_spawn) and [no-capture mode](Self::set_no_capture), and
+    /// has no effect on remote tests.
+    pub fn set_wait_for_debugger(&mut self, wait_for_debugger: bool) -> &mut Self {
+        self.wait_for_debugger = wait_for_debugger;
+        self
+    }
+
+    /// Starts test processes through a copy of nextest, if enabled. See [`crate::double_spawn`].
+    ///
+    /// Test processes are started directly by default.
+    pub fn set_double_spawn(&mut self, double_spawn: DoubleSpawnInfo) -> &mut Self {
+        self.double_spawn = double_spawn;
+        self
+    }
+
+    /// Uses timings recorded by previous runs to schedule the slowest tests first.
+    ///
+    /// Ignored if tests are shuffled.
+    pub fn set_timings(&mut self, timings: TestTimings) -> &mut Self {
+        self.timings = Some(timings);
+        self
+    }
+
+    /// Adds a reporter that is sent every test event.
+    ///
+    /// Any number of reporters can be added, for example to print human-readable output to stderr
+    /// while writing machine-readable output to stdout. Events are sent to reporters in the order
+    /// they were added, before the callback passed into [`TestRunner::try_execute`] is called.
+    pub fn add_reporter(&mut self, reporter: impl Reporter<'a> + 'a) -> &mut Self {
+        self.reporters.push(Box::new(reporter));
+        self
+    }
+
+    /// Returns a handle that can be used to cancel the run, or individual tests, while tests are
+    /// running.
+    ///
+    /// Any number of handles can be created, and they can be used from any thread.
+    pub fn controller(&mut self) -> RunController {
+        let (sender, _) = self.control.get_or_insert_with(mpsc::unbounded_channel);
+        RunController {
+            sender: sender.clone(),
+        }
+    }
+
     /// Creates a new test runner.
-    pub fn build<'a>(
+    pub fn build(
         self,
         test_list: &'a TestList,
         profile: &NextestProfile<'_>,
@@ -79,35 +278,220 @@ impl TestRunnerBuilder {
     ) -> TestRunner<'a> {
         let test_threads = match self.no_capture {
             true => 1,
-            false => self.test_threads.unwrap_or_else(num_cpus::get),
+            false => self
+                .test_threads
+                .unwrap_or_else(|| profile.test_threads())
+                .compute(num_cpus::get()),
         };
-        let retries = self.retries.unwrap_or_else(|| profile.retries());
         let fail_fast = self.fail_fast.unwrap_or_else(|| profile.fail_fast());
-        let slow_timeout = profile.slow_timeout();
+        let max_fail = self
+            .max_fail
+            .map(NonZeroUsize::get)
+            .or_else(|| fail_fast.then(|| 1));
+        let test_settings = test_list
+            .iter_tests()
+            .map(|test_instance| {
+                let settings = profile.test_settings(&test_instance);
+                ((test_instance.binary, test_instance.name), settings)
+            })
+            .collect();
+        let scripts = profile
+            .scripts()
+            .map(|(name, script)| (name.to_owned(), script.clone()))
+            .collect();
+        let test_groups = profile
+            .test_groups()
+            .map(|(name, group)| (name.to_owned(), (group.max_threads().get(), group.scope())))
+            .collect();
+        let exclude_reasons = test_list
+            .iter_tests()
+            .filter(|test_instance| {
+                test_instance.test_info.filter_match
+                    == FilterMatch::Mismatch {
+                        reason: MismatchReason::Excluded,
+                    }
+            })
+            .filter_map(|test_instance| {
+                let reason = profile.exclude_reason(&test_instance)?;
+                Some((
+                    (test_instance.binary, test_instance.name),
+                    reason.to_owned(),
+                ))
+            })
+            .collect();
+        let quarantined = test_list
+            .iter_tests()
+            .filter(|test_instance| profile.quarantine_reason(test_instance).is_some())
+            .map(|test_instance| (test_instance.binary, test_instance.name))
+            .collect();
         let target_runner = self.target_runner;
+        let network_isolation = self.network_isolation && self.remote.is_none();
+        let run_id = Uuid::new_v4();
+        let store = RunStore::new(profile.store_dir());
+        let test_tmpdir = profile.test_tmpdir();
+        // Temporary directories are created on the local machine, so they aren't any use to tests
+        // running remotely.
+        let tmpdir_run_dir = (test_tmpdir.enabled() && self.remote.is_none())
+            .then(|| store.run_dir(ArtifactKind::Tmpdir, run_id));
+        // Like temporary directories, output directories are only created for local runs.
+        let test_output_dir = profile.test_output_dir();
+        let output_dir_run_dir = (test_output_dir.enabled() && self.remote.is_none())
+            .then(|| store.run_dir(ArtifactKind::OutputDir, run_id));
+        let core_dump_dir = profile
+            .core_dumps()
+            .then(|| CoreDumpDir::new(store.run_dir(ArtifactKind::CoreDump, run_id)));
+        let recorder = self
+            .record_failures
+            .unwrap_or_else(|| profile.record_failures())
+            .then(|| {
+                Recorder::new(
+                    profile.recorder().clone(),
+                    store.run_dir(ArtifactKind::Recording, run_id),
+                )
+            });
+        let env_config = profile.env();
+        // Variables can only be removed from the environment of local processes: for remote
+        // tests, they'd be removed from the ssh process rather than from the test.
+        let inherited_env = (!env_config.inherits_all() && self.remote.is_none())
+            .then(|| env_config.inherited_vars(std::env::vars_os()));
 
         TestRunner {
             no_capture: self.no_capture,
-            // The number of tries = retries + 1.
-            tries: retries + 1,
-            fail_fast,
-            slow_timeout,
+            retries: self.retries,
+            max_fail,
+            test_settings,
+            test_threads,
+            throttle: profile.throttle(),
+            sample_load: SystemLoad::sample,
+            cpu_affinity: profile.cpu_affinity().to_vec(),
+            nice: profile.nice(),
+            scripts,
+            test_groups,
+            exclude_reasons,
+            quarantined,
+            workspace_root: profile.workspace_root().to_owned(),
             test_list,
             target_runner,
-            run_pool: ThreadPoolBuilder::new()
-                // The main run_pool closure will need its own thread.
-                .num_threads(test_threads + 1)
-                .thread_name(|idx| format!("testrunner-run-{}", idx))
-                .build()
-                .expect("run pool built"),
-            wait_pool: ThreadPoolBuilder::new()
-                .num_threads(test_threads)
-                .thread_name(|idx| format!("testrunner-wait-{}", idx))
+            remote: self.remote,
+            coverage: self.coverage,
+            shuffle_seed: self.shuffle_seed,
+            timings: self.timings,
+            repeat: self.repeat.map_or(1, NonZeroUsize::get),
+            test_tmpdir,
+            tmpdir_run_dir,
+            test_output_dir,
+            output_dir_run_dir,
+            core_dump_dir,
+            recorder,
+            wait_for_debugger: self.wait_for_debugger,
+            max_output_size: profile.max_output_size(),
+            profile_name: profile.name().to_owned(),
+            network_isolation,
+            double_spawn: self.double_spawn,
+            libtest_json: profile.libtest_json(),
+            test_color: profile.test_color(),
+            // A value set in nextest's environment is inherited by tests instead.
+            rust_backtrace: std::env::var_os("RUST_BACKTRACE")
+                .is_none()
+                .then(|| profile.rust_backtrace()),
+            env_set: env_config.set().clone(),
+            inherited_env,
+            run_id,
+            reporters: Mutex::new(self.reporters.0),
+            // Tests are run as tasks on a single thread, which waits on all of them at once.
+            runtime: tokio::runtime::Builder::new_current_thread()
+                .enable_all()
                 .build()
-                .expect("run pool built"),
-            handler,
+                .expect("runtime built"),
+            handler: Mutex::new(handler),
+            // Without a controller, the channel is closed right away.
+            control_receiver: Mutex::new(
+                self.control
+                    .map_or_else(|| mpsc::unbounded_channel().1, |(_, receiver)| receiver),
+            ),
+            canceled_tests: Mutex::new(CanceledTests::default()),
+        }
+    }
+}
+
+/// A handle to cancel a test run, or individual tests within it, while tests are running.
+///
+/// Created using [`TestRunnerBuilder::controller`].
+#[derive(Clone, Debug)]
+pub struct RunController {
+    sender: UnboundedSender<ControlEvent>,
+}
+
+impl RunController {
+    /// Cancels the run, as if Ctrl-C was pressed.
+    ///
+    /// No more tests are started, and running tests are asked to exit. Canceling the run a second
+    /// time kills running tests immediately.
+    pub fn cancel_run(&self) {
+        // Failure to send means the run is done.
+        let _ = self.sender.send(ControlEvent::CancelRun);
+    }
+
+    /// Cancels the test with the given binary ID and name.
+    ///
+    /// If the test is running, it's asked to exit, and killed if it's still running after its
+    /// grace period. It isn't retried or run again. Other tests keep running.
+    pub fn cancel_test(&self, binary_id: &str, test_name: &str) {
+        let _ = self.sender.send(ControlEvent::CancelTest {
+            binary_id: binary_id.to_owned(),
+            test_name: test_name.to_owned(),
+        });
+    }
+}
+
+#[derive(Clone, Debug)]
+enum ControlEvent {
+    CancelRun,
+    CancelTest {
+        binary_id: String,
+        test_name: String,
+    },
+}
+
+/// Tests canceled through a [`RunController`].
+#[derive(Debug, Default)]
+struct CanceledTests<'a> {
+    canceled: HashSet<(&'a Utf8Path, &'a str)>,
+    // Senders that tell running tests to exit.
+    running: HashMap<(&'a Utf8Path, &'a str), UnboundedSender<()>>,
+}
+
+impl<'a> CanceledTests<'a> {
+    /// Registers a running test, returning a receiver that's sent to if the test is canceled.
+    fn register(&mut self, test_instance: &TestInstance<'a>) -> UnboundedReceiver<()> {
+        let key = (test_instance.binary, test_instance.name);
+        let (sender, receiver) = mpsc::unbounded_channel();
+        if self.canceled.contains(&key) {
+            // The test was canceled before it started.
+            let _ = sender.send(());
+        }
+        self.running.insert(key, sender);
+        receiver
+    }
+
+    fn unregister(&mut self, test_instance: &TestInstance<'a>) {
+        self.running
+            .remove(&(test_instance.binary, test_instance.name));
+    }
+
+    fn cancel(&mut self, test_instance: &TestInstance<'a>) {
+        let key = (test_instance.binary, test_instance.name);
+        self.canceled.insert(key);
+        if let Some(sender) = self.running.get(&key) {
+            // Tests stop listening once they've been told to exit.
+            let _ = sender.send(());
         }
     }
+
+    fn is_canceled(&self, test_instance: &TestInstance<'a>) -> bool {
+        self.canceled
+            .contains(&(test_instance.binary, test_instance.name))
+    }
 }
 
 /// Context for running tests.
@@ -115,157 +499,455 @@ impl TestRunnerBuilder {
 /// Created using [`TestRunnerBuilder::build`].
 pub struct TestRunner<'a> {
     no_capture: bool,
-    tries: usize,
-    fail_fast: bool,
-    slow_timeout: Duration,
+    // Retries specified on the command line, overriding all other configuration.
+    retries: Option<usize>,
+    // The number of failures after which the run is canceled, if any.
+    max_fail: Option<usize>,
+    test_settings: HashMap<(&'a Utf8Path, &'a str), TestSettings>,
+    test_threads: usize,
+    // Limits on system load, above which fewer tests are run at once.
+    throttle: ThrottleConfig,
+    // Samples the system's load for the throttle.
+    sample_load: fn() -> SystemLoad,
+    // The sets of CPUs that tests are pinned to, by global slot.
+    cpu_affinity: Vec<CpuSet>,
+    // The nice value tests are run at, or 0 to leave it unchanged.
+    nice: u8,
+    scripts: Vec<(String, ScriptConfig)>,
+    // Map of test group name to the maximum number of tests in the group that may run at once, and
+    // what that limit applies to.
+    test_groups: HashMap<String, (usize, TestGroupScope)>,
+    // The reasons given in the configuration for excluding tests.
+    exclude_reasons: HashMap<(&'a Utf8Path, &'a str), String>,
+    // Tests whose failures don't fail the run.
+    quarantined: HashSet<(&'a Utf8Path, &'a str)>,
+    workspace_root: Utf8PathBuf,
     test_list: &'a TestList<'a>,
     target_runner: Option<TargetRunner>,
-    run_pool: ThreadPool,
-    wait_pool: ThreadPool,
-    handler: SignalHandler,
+    remote: Option<SshRunner>,
+    coverage: Option<CoverageCollector>,
+    shuffle_seed: Option<u64>,
+    timings: Option<TestTimings>,
+    repeat: usize,
+    test_tmpdir: TestTmpdirConfig,
+    // The directory that per-test temporary directories are created in, if enabled.
+    tmpdir_run_dir: Option<Utf8PathBuf>,
+    test_output_dir: TestOutputDirConfig,
+    // The directory that per-test output directories are created in, if enabled.
+    output_dir_run_dir: Option<Utf8PathBuf>,
+    // The directory that core dumps written by crashing tests are moved to, if enabled.
+    core_dump_dir: Option<CoreDumpDir>,
+    // The recorder that failing tests are run under once all tests have finished, if enabled.
+    recorder: Option<Recorder>,
+    // Whether test processes are stopped before the test starts, until the user presses Enter.
+    wait_for_debugger: bool,
+    max_output_size: Option<u64>,
+    profile_name: String,
+    network_isolation: bool,
+    double_spawn: DoubleSpawnInfo,
+    libtest_json: bool,
+    test_color: TestColor,
+    // The value of RUST_BACKTRACE set for tests, if it isn't inherited.
+    rust_backtrace: Option<RustBacktrace>,
+    // Environment variables set for every test, from the profile's [env] section.
+    env_set: BTreeMap<String, String>,
+    // The environment inherited by tests, if it isn't nextest's entire environment.
+    inherited_env: Option<Vec<(OsString, OsString)>>,
+    run_id: Uuid,
+    reporters: Mutex<Vec<Box<dyn Reporter<'a> + 'a>>>,
+    runtime: Runtime,
+    // Only used by one run at a time.
+    handler: Mutex<SignalHandler>,
+    control_receiver: Mutex<UnboundedReceiver<ControlEvent>>,
+    canceled_tests: Mutex<CanceledTests<'a>>,
 }
 
 impl<'a> TestRunner<'a> {
+    /// Returns the unique ID of this run.
+    ///
+    /// Every [`TestEvent`] produced by this runner is tagged with this ID.
+    pub fn run_id(&self) -> Uuid {
+        self.run_id
+    }
+
+    /// Returns the tests that would be run, in the order they'd be scheduled in, without running
+    /// any of them.
+    ///
+    /// Tests are started in this order as long as enough test threads are free for them and their
+    /// test group has room, so tests that need more threads may start later than listed.
+    pub fn schedule(&self) -> Vec<ScheduledTest<'a>> {
+        self.scheduling_order()
+            .into_iter()
+            .filter(|test_instance| test_instance.test_info.filter_match.is_match())
+            .map(|test_instance| {
+                let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+                let retries = match self.retries {
+                    Some(retries) => retries,
+                    None => settings.retries().count(),
+                };
+                ScheduledTest {
+                    test_instance,
+                    threads_required: self.threads_required(&test_instance),
+                    test_group: settings.test_group().map(|name| name.to_owned()),
+                    retries,
+                    priority: settings.priority(),
+                }
+            })
+            .collect()
+    }
+
+    /// Writes out the setup scripts and tests that would be run, without running any of them.
+    ///
+    /// This is used for `--dry-run`.
+    pub fn write_dry_run(&self, mut writer: impl Write) -> io::Result<()> {
+        let schedule = self.schedule();
+        write!(
+            writer,
+            "{:>12} {} tests across {} binaries with {} test threads",
+            "Dry run",
+            schedule.len(),
+            self.test_list.binary_count(),
+            self.test_threads
+        )?;
+        let skip_count = self.test_list.skip_count();
+        if skip_count > 0 {
+            write!(writer, " ({} skipped)", skip_count)?;
+        }
+        if self.repeat > 1 {
+            write!(writer, " (each run {} times)", self.repeat)?;
+        }
+        if let Some(seed) = self.shuffle_seed {
+            write!(writer, " (shuffled with seed {})", seed)?;
+        }
+        writeln!(writer)?;
+
+        for (name, _) in &self.scripts {
+            writeln!(writer, "{:>12} {}", "SETUP", name)?;
+        }
+
+        let binary_id_width = schedule
+            .iter()
+            .map(|test| test.test_instance.bin_info.binary_id.len())
+            .max()
+            .unwrap_or_default();
+        for (index, test) in schedule.iter().enumerate() {
+            write!(
+                writer,
+                "{:>12} {:<width$} {}",
+                index + 1,
+                test.test_instance.bin_info.binary_id,
+                test.test_instance.name,
+                width = binary_id_width
+            )?;
+
+            let mut details = vec![];
+            if let Some(test_group) = &test.test_group {
+                details.push(format!("group: {}", test_group));
+            }
+            if test.threads_required != 1 {
+                details.push(format!("threads: {}", test.threads_required));
+            }
+            if test.retries > 0 {
+                details.push(format!("retries: {}", test.retries));
+            }
+            if test.priority != 0 {
+                details.push(format!("priority: {}", test.priority));
+            }
+            if !details.is_empty() {
+                write!(writer, " ({})", details.join(", "))?;
+            }
+            writeln!(writer)?;
+        }
+        Ok(())
+    }
+
     /// Executes the listed tests, each one in its own process.
     ///
-    /// The callback is called with the results of each test.
-    pub fn execute<F>(&self, mut callback: F) -> RunStats
+    /// The callback is called with the results of each test, after they have been sent to
+    /// reporters. Errors if a reporter fails.
+    pub fn execute<F>(&self, mut callback: F) -> Result<RunStats, WriteEventError>
     where
         F: FnMut(TestEvent<'a>) + Send,
     {
-        self.try_execute::<Infallible, _>(|test_event| {
+        self.try_execute::<WriteEventError, _>(|test_event| {
             callback(test_event);
             Ok(())
         })
-        .expect("Err branch is infallible")
     }
 
     /// Executes the listed tests, each one in its own process.
     ///
-    /// Accepts a callback that is called with the results of each test. If the callback returns an
-    /// error, the test run terminates and the callback is no longer called.
-    pub fn try_execute<E, F>(&self, callback: F) -> Result<RunStats, E>
+    /// Each test event is sent to the reporters added with [`TestRunnerBuilder::add_reporter`],
+    /// and then to the callback. If a reporter or the callback returns an error, the test run
+    /// terminates and no more events are sent.
+    pub fn try_execute<E, F>(&self, mut callback: F) -> Result<RunStats, E>
     where
         F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
-        E: Send,
+        E: From<WriteEventError> + Send,
     {
         // TODO: add support for other test-running approaches, measure performance.
 
-        let (run_sender, run_receiver) = crossbeam_channel::unbounded();
+        let reporters = &self.reporters;
+        let callback = move |test_event: TestEvent<'a>| {
+            let mut reporters = reporters.lock().expect("reporters mutex is not poisoned");
+            for reporter in reporters.iter_mut() {
+                reporter.report_event(&test_event)?;
+            }
+            drop(reporters);
+            callback(test_event)
+        };
 
-        // This is move so that sender is moved into it. When the scope finishes the sender is
-        // dropped, and the receiver below completes iteration.
+        let (run_sender, mut run_receiver) = mpsc::unbounded_channel();
 
         let canceled = AtomicBool::new(false);
         let canceled_ref = &canceled;
+        let shutdown = Shutdown::new();
+        let shutdown_ref = &shutdown;
 
-        let mut ctx = CallbackContext::new(callback, self.test_list.run_count(), self.fail_fast);
+        let mut ctx = CallbackContext::new(
+            callback,
+            self.run_id,
+            self.test_list.run_count() * self.repeat,
+            self.max_fail,
+        );
 
         // Send the initial event.
         // (Don't need to set the canceled atomic if this fails because the run hasn't started
         // yet.)
-        ctx.run_started(self.test_list)?;
+        ctx.run_started(self.test_list, self.shuffle_seed, self.repeat)?;
 
         // Stores the first error that occurred. This error is propagated up.
         let mut first_error = None;
 
+        // ---
+        // Run setup scripts.
+        // ---
+        let mut script_env = vec![];
+        let mut setup_done = vec![];
+        let mut setup_failed = false;
+        for (name, script) in &self.scripts {
+            let (status, exported) = script::run_script(
+                script.setup(),
+                &self.workspace_root,
+                &script_env,
+                self.no_capture,
+            );
+            let success = status.result.is_success();
+            if success {
+                script_env.extend(exported);
+                setup_done.push((name, script));
+            }
+            if let Err(err) = ctx.script_finished(name, ScriptKind::Setup, status) {
+                first_error.get_or_insert(err);
+            }
+            if !success {
+                if let Err(err) = ctx.begin_cancel(CancelReason::SetupScriptFailure) {
+                    first_error.get_or_insert(err);
+                }
+            }
+
+            if !success || first_error.is_some() {
+                // Don't run any tests if a setup script failed, or if reporting its result failed.
+                setup_failed = true;
+                break;
+            }
+        }
+
         let ctx_mut = &mut ctx;
         let first_error_mut = &mut first_error;
+        let script_env = &script_env;
+        let mut handler = self.handler.lock().expect("lock wasn't poisoned");
+        let signal_receiver = &mut handler.receiver;
+        let mut control_receiver = self.control_receiver.lock().expect("lock wasn't poisoned");
 
         // ---
-        // Spawn the test threads.
+        // Run the tests.
         // ---
-        // XXX rayon requires its scope callback to be Send, there's no good reason for it but
-        // there's also no other well-maintained scoped threadpool :(
-        self.run_pool.scope(move |run_scope| {
-            self.test_list.iter_tests().for_each(|test_instance| {
-                if canceled_ref.load(Ordering::Acquire) {
-                    // Check for test cancellation.
-                    return;
-                }
+        // Each running test is a task on the runtime, which waits on all of their processes,
+        // output and timeouts at once. The tasks are driven from the scheduling loop below, so
+        // that they can borrow from the runner.
+        self.runtime.block_on(async move {
+            // Tests are scheduled as long as enough test threads are free for them. The number of
+            // test threads limits how many tests run at once, rather than being OS threads.
+            let mut running = FuturesUnordered::new();
+            let mut pending_tests: VecDeque<_> = self.scheduling_order().into();
+            let mut available_threads = self.test_threads;
+            // The number of tests currently running in each test group.
+            let mut group_running: HashMap<GroupKey<'_>, usize> = HashMap::new();
+            // Slots for running tests, across all tests and within each test group.
+            let mut global_slots = SlotAllocator::default();
+            let mut group_slots: HashMap<GroupKey<'_>, SlotAllocator> = HashMap::new();
+            // If a setup script failed, no tests are scheduled.
+            let mut run_sender = if setup_failed { None } else { Some(run_sender) };
+            // While the machine is overloaded, fewer tests are scheduled at once. The system's
+            // load is checked before any tests are scheduled, and then periodically, so that
+            // scheduling resumes once it drops.
+            let mut throttle = Throttle::new(self.throttle, self.test_threads, self.sample_load);
+            let mut throttle_tick = throttle.is_enabled().then(|| {
+                throttle.sample(0);
+                tokio::time::interval_at(
+                    (Instant::now() + Throttle::SAMPLE_INTERVAL).into(),
+                    Throttle::SAMPLE_INTERVAL,
+                )
+            });
 
-                let this_run_sender = run_sender.clone();
-                run_scope.spawn(move |_| {
+            loop {
+                while let Some(sender) = &run_sender {
                     if canceled_ref.load(Ordering::Acquire) {
-                        // Check for test cancellation.
-                        return;
+                        // Check for test cancellation. Tests that haven't been started are
+                        // reported as canceled (or skipped), rather than being left out.
+                        for test_instance in pending_tests.drain(..) {
+                            match test_instance.test_info.filter_match {
+                                FilterMatch::Matches => {
+                                    for _ in 0..self.repeat {
+                                        let _ = sender
+                                            .send(InternalTestEvent::Canceled { test_instance });
+                                    }
+                                }
+                                FilterMatch::Mismatch { reason } => {
+                                    let exclude_reason = self
+                                        .exclude_reasons
+                                        .get(&(test_instance.binary, test_instance.name))
+                                        .cloned();
+                                    let _ = sender.send(InternalTestEvent::Skipped {
+                                        test_instance,
+                                        reason,
+                                        exclude_reason,
+                                    });
+                                }
+                            }
+                        }
+                        run_sender = None;
+                        break;
                     }
 
-                    if let FilterMatch::Mismatch { reason } = test_instance.test_info.filter_match {
-                        // Failure to send means the receiver was dropped.
-                        let _ = this_run_sender.send(InternalTestEvent::Skipped {
-                            test_instance,
-                            reason,
-                        });
-                        return;
+                    if pending_tests.is_empty() {
+                        // All tests have been scheduled.
+                        run_sender = None;
+                        break;
                     }
-
-                    // Failure to send means the receiver was dropped.
-                    let _ = this_run_sender.send(InternalTestEvent::Started { test_instance });
-
-                    let mut run_statuses = vec![];
-
-                    loop {
-                        let attempt = run_statuses.len() + 1;
-
-                        let run_status = self
-                            .run_test(test_instance, attempt, &this_run_sender)
-                            .into_external(attempt, self.tries);
-
-                        if run_status.result.is_success() {
-                            // The test succeeded.
-                            run_statuses.push(run_status);
-                            break;
-                        } else if attempt < self.tries {
-                            // Retry this test: send a retry event, then retry the loop.
-                            let _ = this_run_sender.send(InternalTestEvent::Retry {
-                                test_instance,
-                                run_status: run_status.clone(),
-                            });
-                            run_statuses.push(run_status);
-                        } else {
-                            // This test failed and is out of retries.
-                            run_statuses.push(run_status);
+                    // Tests in a group that's already running as many tests as it can are passed
+                    // over, so that they don't hold up tests outside the group.
+                    let index = match pending_tests.iter().position(|test_instance| {
+                        self.group_has_room(test_instance, &group_running)
+                    }) {
+                        Some(index) => index,
+                        None => {
+                            // Wait for tests in full groups to finish.
                             break;
                         }
+                    };
+                    let test_instance = pending_tests[index];
+                    let threads_required = self.threads_required(&test_instance);
+                    if threads_required > available_threads {
+                        // Wait for running tests to finish.
+                        break;
+                    }
+                    let running_threads = self.test_threads - available_threads;
+                    if running_threads > 0 && running_threads + threads_required > throttle.limit() {
+                        // Wait for running tests to finish, or for the machine to be less busy.
+                        break;
                     }
+                    pending_tests.remove(index);
+                    // Tests that can be run in the same process as this one are taken out of the
+                    // queue along with it.
+                    let batch = self.take_batch(test_instance, &mut pending_tests);
+                    available_threads -= threads_required;
+                    if let Some((key, _)) = self.test_group(&test_instance) {
+                        *group_running.entry(key).or_default() += 1;
+                    }
+                    // Skipped tests don't run, so they aren't assigned slots.
+                    let slots =
+                        test_instance
+                            .test_info
+                            .filter_match
+                            .is_match()
+                            .then(|| TestSlots {
+                                global: global_slots.allocate(),
+                                group: self.test_group(&test_instance).map(|(key, _)| {
+                                    group_slots.entry(key).or_default().allocate()
+                                }),
+                            });
 
-                    // At this point, either:
-                    // * the test has succeeded, or
-                    // * the test has failed and we've run out of retries.
-                    // In either case, the test is finished.
-                    let _ = this_run_sender.send(InternalTestEvent::Finished {
-                        test_instance,
-                        run_statuses: ExecutionStatuses::new(run_statuses),
+                    let this_run_sender = sender.clone();
+                    running.push(async move {
+                        self.run_scheduled(
+                            &batch,
+                            slots,
+                            script_env,
+                            &this_run_sender,
+                            shutdown_ref,
+                            canceled_ref,
+                        )
+                        .await;
+                        if let Some(slots) = slots {
+                            let _ = this_run_sender.send(InternalTestEvent::Released {
+                                test_instance,
+                                slots,
+                            });
+                        }
                     });
-                })
-            });
-
-            drop(run_sender);
+                }
 
-            loop {
-                let internal_event = crossbeam_channel::select! {
-                    recv(run_receiver) -> internal_event => {
+                let internal_event = tokio::select! {
+                    internal_event = run_receiver.recv() => {
                         match internal_event {
-                            Ok(event) => InternalEvent::Test(event),
-                            Err(_) => {
+                            Some(event) => InternalEvent::Test(event),
+                            None => {
                                 // All runs have been completed.
                                 break;
                             }
                         }
                     },
-                    recv(self.handler.receiver) -> internal_event => {
-                        match internal_event {
-                            Ok(event) => InternalEvent::Signal(event),
-                            Err(_) => {
-                                // Ignore the signal thread being dropped. This is done for
-                                // noop signal handlers.
+                    // Running tests only make progress while they're polled here. Their events
+                    // are sent through run_receiver.
+                    Some(()) = running.next(), if !running.is_empty() => continue,
+                    // The signal channel is closed right away for noop signal handlers.
+                    event = recv_or_pending(Some(&mut *signal_receiver)) => InternalEvent::Signal(event),
+                    control_event = recv_or_pending(Some(&mut *control_receiver)) => {
+                        match control_event {
+                            ControlEvent::CancelRun => {
+                                InternalEvent::Signal(SignalEvent::Shutdown(ShutdownEvent::Interrupt))
+                            }
+                            ControlEvent::CancelTest { binary_id, test_name } => {
+                                self.cancel_test(&binary_id, &test_name);
                                 continue;
                             }
                         }
                     },
+                    () = tick_or_pending(throttle_tick.as_mut()) => {
+                        // Check whether more tests can be scheduled.
+                        throttle.sample(self.test_threads - available_threads);
+                        continue;
+                    },
                 };
 
+                if let InternalEvent::Signal(SignalEvent::Shutdown(event)) = &internal_event {
+                    // Running tests are told to shut down. Scheduling is stopped below, once the
+                    // run is canceled.
+                    shutdown_ref.signal_received(*event);
+                }
+
+                if let InternalEvent::Test(InternalTestEvent::Released {
+                    test_instance,
+                    slots,
+                }) = &internal_event
+                {
+                    // Make this test's threads and slots available to other tests.
+                    available_threads += self.threads_required(test_instance);
+                    global_slots.release(slots.global);
+                    if let Some((key, _)) = self.test_group(test_instance) {
+                        if let Some(running) = group_running.get_mut(&key) {
+                            *running -= 1;
+                        }
+                        if let (Some(allocator), Some(slot)) =
+                            (group_slots.get_mut(&key), slots.group)
+                        {
+                            allocator.release(slot);
+                        }
+                    }
+                }
+
                 match ctx_mut.handle_event(internal_event) {
                     Ok(()) => {}
                     Err(err) => {
@@ -273,6 +955,7 @@ impl<'a> TestRunner<'a> {
                         // a cancellation notice was received. If the callback failed, we need
                         // to send a further cancellation notice as well.
                         canceled_ref.store(true, Ordering::Release);
+                        shutdown_ref.cancel();
 
                         match err {
                             InternalError::Error(err) => {
@@ -300,9 +983,65 @@ impl<'a> TestRunner<'a> {
                 }
             }
 
+            // Any tests that are still pending weren't scheduled because the run was canceled.
+            for test_instance in pending_tests {
+                if !test_instance.test_info.filter_match.is_match() {
+                    continue;
+                }
+                for _ in 0..self.repeat {
+                    if let Err(err) = ctx_mut.test_canceled(test_instance) {
+                        first_error_mut.get_or_insert(err);
+                        return Ok(());
+                    }
+                }
+            }
+
             Ok(())
         })?;
 
+        // ---
+        // Record failing tests.
+        // ---
+        // Tests aren't recorded if the run was interrupted, or if results couldn't be reported.
+        if let Some(recorder) = &self.recorder {
+            if first_error.is_none() && !shutdown.is_signaled() {
+                let failed = std::mem::take(&mut ctx.failed);
+                let record = self.record_failed(
+                    recorder,
+                    failed,
+                    script_env,
+                    &shutdown,
+                    &mut handler.receiver,
+                    &mut ctx,
+                );
+                if let Err(err) = self.runtime.block_on(record) {
+                    first_error = Some(err);
+                }
+            }
+        }
+
+        // ---
+        // Run teardown scripts, in reverse order of setup.
+        // ---
+        for (name, script) in setup_done.into_iter().rev() {
+            if let Some(teardown) = script.teardown() {
+                let (status, _) =
+                    script::run_script(teardown, &self.workspace_root, script_env, self.no_capture);
+                if let Err(err) = ctx.script_finished(name, ScriptKind::Teardown, status) {
+                    first_error.get_or_insert(err);
+                }
+            }
+        }
+
+        // Remove the run's temporary and output directories, and the directories containing them,
+        // unless some tests' directories were retained.
+        for run_dir in self.tmpdir_run_dir.iter().chain(&self.output_dir_run_dir) {
+            let _ = std::fs::remove_dir(run_dir);
+            if let Some(parent) = run_dir.parent() {
+                let _ = std::fs::remove_dir(parent);
+            }
+        }
+
         match ctx.run_finished() {
             Ok(()) => {}
             Err(err) => {
@@ -322,90 +1061,1252 @@ impl<'a> TestRunner<'a> {
     // Helper methods
     // ---
 
-    /// Run an individual test in its own process.
-    fn run_test(
+    /// Runs tests that failed once more, one at a time, under the recorder.
+    async fn record_failed<E, F>(
         &self,
-        test: TestInstance<'a>,
-        attempt: usize,
-        run_sender: &Sender<InternalTestEvent<'a>>,
-    ) -> InternalExecuteStatus {
-        let stopwatch = StopwatchStart::now();
-
-        match self.run_test_inner(test, attempt, &stopwatch, run_sender) {
-            Ok(run_status) => run_status,
-            Err(_) => InternalExecuteStatus {
-                // TODO: can we return more information in stdout/stderr? investigate this
-                stdout: vec![],
-                stderr: vec![],
-                result: ExecutionResult::ExecFail,
-                stopwatch_end: stopwatch.end(),
-            },
+        recorder: &Recorder,
+        failed: Vec<(TestInstance<'a>, usize)>,
+        script_env: &[(String, String)],
+        shutdown: &Shutdown,
+        signal_receiver: &mut UnboundedReceiver<SignalEvent>,
+        ctx: &mut CallbackContext<'a, F, E>,
+    ) -> Result<(), E>
+    where
+        F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
+        E: Send,
+    {
+        // Recordings made on remote hosts can't be collected.
+        let failed: Vec<_> = failed
+            .into_iter()
+            .filter(|(test_instance, _)| {
+                self.remote.is_none()
+                    || test_instance.bin_info.build_platform == BuildPlatform::Host
+            })
+            .collect();
+        if failed.is_empty() {
+            return Ok(());
         }
-    }
 
-    fn run_test_inner(
-        &self,
-        test: TestInstance<'a>,
-        attempt: usize,
-        stopwatch: &StopwatchStart,
-        run_sender: &Sender<InternalTestEvent<'a>>,
-    ) -> std::io::Result<InternalExecuteStatus> {
-        let cmd = test
-            .make_expression(self.target_runner.as_ref())
-            .unchecked()
-            // Debug environment variable for testing.
-            .env("__NEXTEST_ATTEMPT", format!("{}", attempt));
-
-        let cmd = if self.no_capture {
-            cmd
-        } else {
-            // Capture stdout and stderr.
-            cmd.stdout_capture().stderr_capture()
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let stop = AtomicBool::new(false);
+        let stop_ref = &stop;
+        let record = async move {
+            for (test_instance, attempts) in failed {
+                if stop_ref.load(Ordering::Acquire) || shutdown.is_signaled() {
+                    break;
+                }
+                let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+                // Tests are recorded one at a time, so they can all use the first slot.
+                let slots = TestSlots {
+                    global: 0,
+                    group: self.test_group(&test_instance).map(|_| 0),
+                };
+                let attempt = attempts + 1;
+                let recording_dir = recorder.dir(&test_instance);
+                let run_status = match std::fs::create_dir_all(&recording_dir) {
+                    Ok(()) => {
+                        self.run_test(
+                            test_instance,
+                            attempt,
+                            slots,
+                            settings.slow_timeout(),
+                            settings.heartbeat_period(),
+                            settings.grace_period(),
+                            script_env,
+                            Some(&recording_dir),
+                            &sender,
+                            shutdown,
+                        )
+                        .await
+                    }
+                    Err(error) => InternalExecuteStatus {
+                        stdout: vec![],
+                        stderr: format!(
+                            "nextest: error creating recording directory {}: {}\n",
+                            recording_dir, error
+                        )
+                        .into_bytes(),
+                        result: ExecutionResult::ExecFail,
+                        stopwatch_end: StopwatchStart::now().end(),
+                        core_dump: None,
+                        output_dir: None,
+                    },
+                };
+                if run_status.result == ExecutionResult::ExecFail {
+                    // The recorder couldn't be run, so nothing was written out.
+                    let _ = std::fs::remove_dir(&recording_dir);
+                }
+                let _ = sender.send(InternalTestEvent::Recorded {
+                    test_instance,
+                    run_status: run_status.into_external(attempt, attempt),
+                    recording_dir,
+                });
+            }
         };
-
-        let handle = cmd.start()?;
-
-        self.wait_pool.in_place_scope(|s| {
-            let (sender, receiver) = crossbeam_channel::bounded::<()>(1);
-            let wait_handle = &handle;
-
-            // Spawn a task on the threadpool that waits for the test to finish.
-            s.spawn(move |_| {
-                // This thread is just waiting for the test to finish, we'll handle the output in the main thread
-                let _ = wait_handle.wait();
-                // We don't care if the receiver got the message or not
-                let _ = sender.send(());
-            });
-
-            // Continue waiting for the test to finish with a timeout, logging at slow-timeout
-            // intervals
-            while let Err(error) = receiver.recv_timeout(self.slow_timeout) {
-                match error {
-                    RecvTimeoutError::Timeout => {
-                        let _ = run_sender.send(InternalTestEvent::Slow {
-                            test_instance: test,
-                            elapsed: stopwatch.elapsed(),
-                        });
+        tokio::pin!(record);
+
+        let mut recorded = false;
+        let result = loop {
+            let shutdown_event = tokio::select! {
+                event = receiver.recv() => {
+                    match event {
+                        Some(event @ InternalTestEvent::Recorded { .. }) => {
+                            if let Err(InternalError::Error(err)) =
+                                ctx.handle_event(InternalEvent::Test(event))
+                            {
+                                // Stop recording.
+                                stop.store(true, Ordering::Release);
+                                break Err(err);
+                            }
+                            continue;
+                        }
+                        // Recorded runs are expected to be slow, so they aren't reported as slow
+                        // or silent.
+                        Some(_) => continue,
+                        // All recordings have been made.
+                        None => break Ok(()),
                     }
-                    RecvTimeoutError::Disconnected => {
-                        unreachable!("Waiting thread should never drop the sender")
+                },
+                () = &mut record, if !recorded => {
+                    recorded = true;
+                    continue;
+                },
+                event = recv_or_pending(Some(&mut *signal_receiver)) => {
+                    match event {
+                        SignalEvent::Shutdown(event) => event,
+                        SignalEvent::Info => continue,
                     }
-                }
+                },
+            };
+            // Stop recording, and ask the test being recorded to exit.
+            shutdown.signal_received(shutdown_event);
+            if let Err(err) = ctx.begin_cancel(CancelReason::Signal) {
+                break Err(err);
             }
+        };
+        // Wait for the current recording to finish.
+        if !recorded {
+            record.await;
+        }
+        result
+    }
+
+    /// Returns the order in which tests are scheduled.
+    ///
+    /// Tests with a higher priority always come first. Within each priority, tests are shuffled or
+    /// sorted slowest first.
+    fn scheduling_order(&self) -> Vec<TestInstance<'a>> {
+        let mut tests: Vec<_> = self.test_list.iter_tests().collect();
+        if let Some(seed) = self.shuffle_seed {
+            shuffle_with_seed(&mut tests, seed);
+        } else if let Some(timings) = &self.timings {
+            timings.sort_slowest_first(&mut tests);
+        }
+        // The sort is stable, so this keeps the order within each priority.
+        tests.sort_by_key(|test_instance| {
+            std::cmp::Reverse(
+                self.test_settings[&(test_instance.binary, test_instance.name)].priority(),
+            )
         });
+        tests
+    }
 
-        let output = handle.into_output()?;
+    /// Returns the number of test threads reserved for this test while it runs.
+    fn threads_required(&self, test_instance: &TestInstance<'a>) -> usize {
+        if !test_instance.test_info.filter_match.is_match() {
+            // Skipped tests don't run, so they don't need any threads.
+            return 0;
+        }
+        let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+        settings.threads_required().compute(self.test_threads)
+    }
 
-        let status = if output.status.success() {
-            ExecutionResult::Pass
-        } else {
-            ExecutionResult::Fail
+    /// Returns the test group this test runs in, along with the maximum number of tests in the
+    /// group that may run at once.
+    fn test_group(&self, test_instance: &TestInstance<'a>) -> Option<(GroupKey<'_>, usize)> {
+        if !test_instance.test_info.filter_match.is_match() {
+            // Skipped tests don't run, so they don't count towards the group's limit.
+            return None;
+        }
+        let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+        let name = settings.test_group()?;
+        let (max_threads, scope) = self.test_groups[name];
+        let scope = match scope {
+            TestGroupScope::Global => None,
+            TestGroupScope::Package => Some(test_instance.bin_info.package.name()),
+            TestGroupScope::Binary => Some(test_instance.bin_info.binary_id.as_str()),
         };
-        Ok(InternalExecuteStatus {
+        Some(((name, scope), max_threads))
+    }
+
+    /// Returns true if this test's group, if any, is running fewer tests than its limit.
+    fn group_has_room(
+        &self,
+        test_instance: &TestInstance<'a>,
+        group_running: &HashMap<GroupKey<'_>, usize>,
+    ) -> bool {
+        self.test_group(test_instance)
+            .map_or(true, |(key, max_threads)| {
+                group_running.get(&key).copied().unwrap_or(0) < max_threads
+            })
+    }
+
+    /// Cancels the test with the given binary ID and name, through a [`RunController`].
+    fn cancel_test(&self, binary_id: &str, test_name: &str) {
+        let test_instance = self.test_list.iter_tests().find(|test_instance| {
+            test_instance.bin_info.binary_id == binary_id && test_instance.name == test_name
+        });
+        if let Some(test_instance) = test_instance {
+            self.canceled_tests
+                .lock()
+                .expect("lock wasn't poisoned")
+                .cancel(&test_instance);
+        }
+    }
+
+    fn is_test_canceled(&self, test_instance: &TestInstance<'a>) -> bool {
+        self.canceled_tests
+            .lock()
+            .expect("lock wasn't poisoned")
+            .is_canceled(test_instance)
+    }
+
+    fn is_quarantined(&self, test_instance: &TestInstance<'a>) -> bool {
+        self.quarantined
+            .contains(&(test_instance.binary, test_instance.name))
+    }
+
+    /// Returns the retry policy for this test. Retries specified on the command line are run
+    /// without any delay.
+    fn retry_policy(&self, test_instance: &TestInstance<'a>) -> RetryPolicy {
+        self.retries
+            .map(RetryPolicy::new_without_delay)
+            .unwrap_or_else(|| {
+                self.test_settings[&(test_instance.binary, test_instance.name)].retries()
+            })
+    }
+
+    /// Returns true if this test is run on its own with libtest's JSON output turned on.
+    fn uses_libtest_json(&self, test_instance: &TestInstance<'a>) -> bool {
+        // JSON output is only useful if it's captured.
+        self.libtest_json
+            && !self.no_capture
+            && test_instance.bin_info.harness == RustTestHarness::Libtest
+    }
+
+    /// Returns true if this test can be run in a single process along with other tests from the
+    /// same binary.
+    fn can_batch(&self, test_instance: &TestInstance<'a>) -> bool {
+        // Batches are run with captured output, and libtest can only run each test once per
+        // process. Tests in groups are run on their own, so that their slots stay meaningful.
+        let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+        !self.no_capture
+            && self.repeat == 1
+            && settings.batch_size().get() > 1
+            && settings.test_group().is_none()
+            && test_instance.test_info.filter_match.is_match()
+            && test_instance.bin_info.harness == RustTestHarness::Libtest
+    }
+
+    /// Returns the tests to run in the same process as this one, starting with this test. Other
+    /// tests in the batch are removed from `pending_tests`.
+    ///
+    /// Only tests from the same binary with the same settings are batched together.
+    fn take_batch(
+        &self,
+        test_instance: TestInstance<'a>,
+        pending_tests: &mut VecDeque<TestInstance<'a>>,
+    ) -> Vec<TestInstance<'a>> {
+        let mut batch = vec![test_instance];
+        if !self.can_batch(&test_instance) {
+            return batch;
+        }
+        let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+        let batch_size = settings.batch_size().get();
+        pending_tests.retain(|&other| {
+            let matches = batch.len() < batch_size
+                && other.binary == test_instance.binary
+                && other.test_info.ignored == test_instance.test_info.ignored
+                && self.can_batch(&other)
+                && &self.test_settings[&(other.binary, other.name)] == settings;
+            if matches {
+                batch.push(other);
+            }
+            !matches
+        });
+        batch
+    }
+
+    /// Runs a test that has been scheduled, or a batch of tests from the same binary, sending
+    /// events for each test.
+    async fn run_scheduled(
+        &self,
+        batch: &[TestInstance<'a>],
+        slots: Option<TestSlots>,
+        script_env: &[(String, String)],
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+        canceled: &AtomicBool,
+    ) {
+        if canceled.load(Ordering::Acquire) {
+            // Check for test cancellation.
+            for &test_instance in batch {
+                if test_instance.test_info.filter_match.is_match() {
+                    for _ in 0..self.repeat {
+                        let _ = run_sender.send(InternalTestEvent::Canceled { test_instance });
+                    }
+                }
+            }
+            return;
+        }
+
+        let test_instance = batch[0];
+        if let FilterMatch::Mismatch { reason } = test_instance.test_info.filter_match {
+            let exclude_reason = self
+                .exclude_reasons
+                .get(&(test_instance.binary, test_instance.name))
+                .cloned();
+            // Failure to send means the receiver was dropped.
+            let _ = run_sender.send(InternalTestEvent::Skipped {
+                test_instance,
+                reason,
+                exclude_reason,
+            });
+            return;
+        }
+
+        let slots = slots.expect("tests that aren't skipped are assigned slots");
+        if batch.len() > 1 {
+            self.run_batch(batch, slots, script_env, run_sender, shutdown, canceled)
+                .await;
+            return;
+        }
+
+        for iteration in 0..self.repeat {
+            if iteration > 0
+                && (canceled.load(Ordering::Acquire) || self.is_test_canceled(&test_instance))
+            {
+                // Check for test cancellation between repeated runs.
+                for _ in iteration..self.repeat {
+                    let _ = run_sender.send(InternalTestEvent::Canceled { test_instance });
+                }
+                return;
+            }
+
+            // Failure to send means the receiver was dropped.
+            let _ = run_sender.send(InternalTestEvent::Started { test_instance });
+            let run_statuses = self
+                .run_attempts(
+                    test_instance,
+                    slots,
+                    vec![],
+                    script_env,
+                    run_sender,
+                    shutdown,
+                )
+                .await;
+            let _ = run_sender.send(InternalTestEvent::Finished {
+                test_instance,
+                run_statuses,
+                quarantined: self.is_quarantined(&test_instance),
+            });
+        }
+    }
+
+    /// Runs a batch of tests in a single process, one after the other.
+    ///
+    /// Tests that fail are retried in processes of their own, if they have retries left. Tests
+    /// that the batch didn't get to, for example because a test before them crashed the process,
+    /// are also run in processes of their own.
+    async fn run_batch(
+        &self,
+        batch: &[TestInstance<'a>],
+        slots: TestSlots,
+        script_env: &[(String, String)],
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+        canceled: &AtomicBool,
+    ) {
+        let statuses = self
+            .run_test_batch(batch, slots, script_env, run_sender, shutdown)
+            .await;
+        let tries = self.retry_policy(&batch[0]).count() + 1;
+
+        // Tests that still need to be run on their own, along with their attempts so far.
+        let mut remaining = vec![];
+        for (&test_instance, status) in batch.iter().zip(statuses) {
+            match status.map(|status| status.into_external(1, tries)) {
+                Some(run_status) if run_status.result.is_success() => {
+                    let _ = run_sender.send(InternalTestEvent::Finished {
+                        test_instance,
+                        run_statuses: ExecutionStatuses::new(vec![run_status]),
+                        quarantined: self.is_quarantined(&test_instance),
+                    });
+                }
+                Some(run_status) => remaining.push((test_instance, vec![run_status])),
+                None => remaining.push((test_instance, vec![])),
+            }
+        }
+
+        for (test_instance, run_statuses) in remaining {
+            if run_statuses.is_empty() {
+                if canceled.load(Ordering::Acquire)
+                    || shutdown.is_signaled()
+                    || self.is_test_canceled(&test_instance)
+                {
+                    let _ = run_sender.send(InternalTestEvent::Canceled { test_instance });
+                    continue;
+                }
+                let _ = run_sender.send(InternalTestEvent::Started { test_instance });
+            }
+            let run_statuses = self
+                .run_attempts(
+                    test_instance,
+                    slots,
+                    run_statuses,
+                    script_env,
+                    run_sender,
+                    shutdown,
+                )
+                .await;
+            let _ = run_sender.send(InternalTestEvent::Finished {
+                test_instance,
+                run_statuses,
+                quarantined: self.is_quarantined(&test_instance),
+            });
+        }
+    }
+
+    /// Runs a test in its own process until it passes or runs out of retries, following on from
+    /// the attempts in `run_statuses`.
+    async fn run_attempts(
+        &self,
+        test_instance: TestInstance<'a>,
+        slots: TestSlots,
+        mut run_statuses: Vec<ExecuteStatus>,
+        script_env: &[(String, String)],
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) -> ExecutionStatuses {
+        let settings = &self.test_settings[&(test_instance.binary, test_instance.name)];
+        let retry_policy = self.retry_policy(&test_instance);
+        // The number of tries = retries + 1.
+        let tries = retry_policy.count() + 1;
+
+        loop {
+            if let Some(run_status) = run_statuses.last() {
+                let attempt = run_statuses.len();
+                if run_status.result.is_success()
+                    || attempt >= tries
+                    || shutdown.is_signaled()
+                    || shutdown.is_canceled()
+                    || self.is_test_canceled(&test_instance)
+                {
+                    // Either the test succeeded, or it failed and is out of retries.
+                    break;
+                }
+                // Retry this test: send a retry event, wait out the delay (if any), then run it
+                // again.
+                let delay = retry_policy.delay_after(attempt);
+                let _ = run_sender.send(InternalTestEvent::Retry {
+                    test_instance,
+                    run_status: run_status.clone(),
+                    delay_before_next_attempt: delay,
+                });
+                if !shutdown.wait_for_retry(delay).await {
+                    // The run was canceled while waiting: report the last attempt instead.
+                    break;
+                }
+            }
+
+            let attempt = run_statuses.len() + 1;
+            let run_status = self
+                .run_test(
+                    test_instance,
+                    attempt,
+                    slots,
+                    settings.slow_timeout(),
+                    settings.heartbeat_period(),
+                    settings.grace_period(),
+                    script_env,
+                    None,
+                    run_sender,
+                    shutdown,
+                )
+                .await
+                .into_external(attempt, tries);
+            run_statuses.push(run_status);
+        }
+
+        ExecutionStatuses::new(run_statuses)
+    }
+
+    /// Run an individual test in its own process.
+    ///
+    /// If `recording_dir` is set, the test is run under the recorder, which writes the recording
+    /// to that directory.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_test(
+        &self,
+        test: TestInstance<'a>,
+        attempt: usize,
+        slots: TestSlots,
+        slow_timeout: SlowTimeout,
+        heartbeat_period: Option<Duration>,
+        grace_period: Duration,
+        script_env: &[(String, String)],
+        recording_dir: Option<&Utf8Path>,
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) -> InternalExecuteStatus {
+        let stopwatch = StopwatchStart::now();
+
+        match self
+            .run_test_inner(
+                test,
+                attempt,
+                slots,
+                slow_timeout,
+                heartbeat_period,
+                grace_period,
+                script_env,
+                recording_dir,
+                &stopwatch,
+                run_sender,
+                shutdown,
+            )
+            .await
+        {
+            Ok(run_status) => run_status,
+            Err(error) => InternalExecuteStatus {
+                stdout: vec![],
+                // Explain why the test couldn't be run, e.g. because its binary is missing or
+                // sandbox setup failed.
+                stderr: format!("nextest: error running test: {}\n", error).into_bytes(),
+                result: ExecutionResult::ExecFail,
+                stopwatch_end: stopwatch.end(),
+                core_dump: None,
+                output_dir: None,
+            },
+        }
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    async fn run_test_inner(
+        &self,
+        test: TestInstance<'a>,
+        attempt: usize,
+        slots: TestSlots,
+        slow_timeout: SlowTimeout,
+        heartbeat_period: Option<Duration>,
+        grace_period: Duration,
+        script_env: &[(String, String)],
+        recording_dir: Option<&Utf8Path>,
+        stopwatch: &StopwatchStart,
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) -> std::io::Result<InternalExecuteStatus> {
+        let process = self
+            .run_process(
+                &[test],
+                attempt,
+                slots,
+                slow_timeout,
+                heartbeat_period,
+                grace_period,
+                script_env,
+                recording_dir,
+                stopwatch,
+                run_sender,
+                shutdown,
+            )
+            .await?;
+        let mut output = process.output;
+
+        let mut stopwatch_end = stopwatch.end();
+        if self.uses_libtest_json(&test) {
+            // Remove the events from the output, and use libtest's measurement of how long the test
+            // took, which doesn't include the time taken to start up the process.
+            let libtest_output = LibtestOutput::parse(&output.stdout);
+            if let Some(exec_time) = libtest_output.exec_time(test.name) {
+                stopwatch_end.duration = exec_time;
+            }
+            output.stdout = libtest_output.stdout;
+        }
+
+        let status = process_result(&output, process.timed_out, process.resource_limits);
+        if let Some(tmpdir) = process.tmpdir {
+            // Failing to clean up the directory doesn't change the result of the test.
+            let _ = tmpdir.finish(status.is_success(), self.test_tmpdir.retain());
+        }
+        let output_dir = process.output_dir.and_then(|output_dir| {
+            output_dir.finish(status.is_success(), self.test_output_dir.retain())
+        });
+        Ok(InternalExecuteStatus {
             stdout: output.stdout,
             stderr: output.stderr,
             result: status,
-            stopwatch_end: stopwatch.end(),
+            stopwatch_end,
+            core_dump: process.core_dump,
+            output_dir,
+        })
+    }
+
+    /// Runs a batch of tests in a single process, and returns the status of each test, or `None`
+    /// for tests that didn't run.
+    async fn run_test_batch(
+        &self,
+        batch: &[TestInstance<'a>],
+        slots: TestSlots,
+        script_env: &[(String, String)],
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) -> Vec<Option<InternalExecuteStatus>> {
+        let stopwatch = StopwatchStart::now();
+        // If the batch couldn't be run, each test is run on its own instead, which reports the
+        // error if it happens again.
+        self.run_test_batch_inner(batch, slots, script_env, &stopwatch, run_sender, shutdown)
+            .await
+            .unwrap_or_else(|_| batch.iter().map(|_| None).collect())
+    }
+
+    async fn run_test_batch_inner(
+        &self,
+        batch: &[TestInstance<'a>],
+        slots: TestSlots,
+        script_env: &[(String, String)],
+        stopwatch: &StopwatchStart,
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) -> std::io::Result<Vec<Option<InternalExecuteStatus>>> {
+        // Tests in a batch have the same settings.
+        let settings = &self.test_settings[&(batch[0].binary, batch[0].name)];
+        let process = self
+            .run_process(
+                batch,
+                1,
+                slots,
+                settings.slow_timeout(),
+                settings.heartbeat_period(),
+                settings.grace_period(),
+                script_env,
+                None,
+                stopwatch,
+                run_sender,
+                shutdown,
+            )
+            .await?;
+        let process_result =
+            process_result(&process.output, process.timed_out, process.resource_limits);
+        if let Some(tmpdir) = process.tmpdir {
+            let _ = tmpdir.finish(process_result.is_success(), self.test_tmpdir.retain());
+        }
+        // The tests in a batch share an output directory. If it's retained, it's reported for each
+        // test that failed.
+        let output_dir = process.output_dir.and_then(|output_dir| {
+            output_dir.finish(process_result.is_success(), self.test_output_dir.retain())
+        });
+
+        let libtest_output = LibtestOutput::parse(&process.output.stdout);
+        // Output that isn't part of any test's events, such as output written to standard error
+        // directly rather than through the standard library, is attributed to the test that was
+        // running when the process exited, if any.
+        let mut process_output = Some((libtest_output.stdout.clone(), process.output.stderr));
+        let mut core_dump = process.core_dump;
+        let started: HashMap<_, _> = process
+            .started
+            .into_iter()
+            .map(|(test_instance, stopwatch)| (test_instance.name, stopwatch))
+            .collect();
+
+        let mut statuses = Vec::with_capacity(batch.len());
+        for test_instance in batch {
+            let test_stopwatch = started.get(test_instance.name).unwrap_or(stopwatch);
+            let status = match libtest_output.result(test_instance.name) {
+                Some(event) => {
+                    let mut stopwatch_end = test_stopwatch.end();
+                    if let Some(exec_time) = event.exec_time {
+                        stopwatch_end.duration = exec_time;
+                    }
+                    let mut stdout = event.stdout.clone().unwrap_or_default().into_bytes();
+                    if self.test_color == TestColor::Never {
+                        stdout = strip_ansi_escapes::strip(&stdout)?;
+                    }
+                    Some(InternalExecuteStatus {
+                        stdout,
+                        stderr: vec![],
+                        result: if event.kind == LibtestEventKind::Ok {
+                            ExecutionResult::Pass
+                        } else {
+                            ExecutionResult::Fail
+                        },
+                        stopwatch_end,
+                        core_dump: None,
+                        output_dir: None,
+                    })
+                }
+                None if libtest_output.started(test_instance.name) => {
+                    // The process exited while this test was running, so the test crashed, timed
+                    // out or was killed.
+                    let (stdout, stderr) = process_output.take().unwrap_or_default();
+                    Some(InternalExecuteStatus {
+                        stdout,
+                        stderr,
+                        result: if process_result.is_success() {
+                            ExecutionResult::Fail
+                        } else {
+                            process_result
+                        },
+                        stopwatch_end: test_stopwatch.end(),
+                        core_dump: core_dump.take(),
+                        output_dir: None,
+                    })
+                }
+                None => None,
+            };
+            let status = status.map(|mut status| {
+                if !status.result.is_success()
+                    || self.test_output_dir.retain() == RetainTmpdir::Always
+                {
+                    status.output_dir = output_dir.clone();
+                }
+                status
+            });
+            statuses.push(status);
+        }
+        Ok(statuses)
+    }
+
+    /// Waits for a test process that pauses itself to stop, reports it, and resumes it once the
+    /// user presses Enter or nextest receives a signal.
+    async fn wait_for_debugger(
+        &self,
+        test_instance: TestInstance<'a>,
+        pid: u32,
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) {
+        // If the process exited instead of stopping, e.g. because it couldn't be set up, the error
+        // is reported once it's waited for.
+        if !matches!(process::wait_until_stopped(pid).await, Ok(true)) {
+            return;
+        }
+        let enter_pressed = pause::enter_pressed();
+        // Ignore Enter presses from before the test was paused. If another paused test is waiting
+        // for Enter, it gets those instead.
+        if let Ok(mut enter_pressed) = enter_pressed.try_lock() {
+            while enter_pressed.try_recv().is_ok() {}
+        }
+        let _ = run_sender.send(InternalTestEvent::Paused { test_instance, pid });
+        tokio::select! {
+            _ = async { enter_pressed.lock().await.recv().await } => {}
+            // The signal is forwarded to the test once it's resumed.
+            () = shutdown.forwarded() => {}
+        }
+        process::resume(pid);
+    }
+
+    /// Returns the environment variables to set for a process running the given tests.
+    fn test_env(
+        &self,
+        tests: &[TestInstance<'a>],
+        attempt: usize,
+        slots: TestSlots,
+        script_env: &[(String, String)],
+    ) -> Vec<(String, String)> {
+        let test = tests[0];
+        let batched = tests.len() > 1;
+
+        // Information about the run, so that tests can adapt to it.
+        let mut env = vec![
+            ("NEXTEST_RUN_ID".to_owned(), self.run_id.to_string()),
+            ("NEXTEST_PROFILE".to_owned(), self.profile_name.clone()),
+            (
+                "NEXTEST_EXECUTION_MODE".to_owned(),
+                if batched {
+                    EXECUTION_MODE_BATCHED
+                } else {
+                    EXECUTION_MODE_PROCESS_PER_TEST
+                }
+                .to_owned(),
+            ),
+            ("NEXTEST_ATTEMPT".to_owned(), attempt.to_string()),
+            // The undocumented name this variable had before, which existing tests may rely on.
+            ("__NEXTEST_ATTEMPT".to_owned(), attempt.to_string()),
+            // Tests that run their own parallel work can size it to the threads reserved for them.
+            (
+                "NEXTEST_THREADS_REQUIRED".to_owned(),
+                self.threads_required(&test).to_string(),
+            ),
+        ];
+        env.extend(slots.env());
+        if let Some(rust_backtrace) = self.rust_backtrace {
+            env.push((
+                "RUST_BACKTRACE".to_owned(),
+                rust_backtrace.as_env_value().to_owned(),
+            ));
+        }
+        env.extend(
+            self.test_color
+                .env()
+                .iter()
+                .map(|&(key, value)| (key.to_owned(), value.to_owned())),
+        );
+
+        // Environment variables set through configuration. Setup scripts can override these.
+        env.extend(
+            self.env_set
+                .iter()
+                .map(|(key, value)| (key.clone(), value.clone())),
+        );
+
+        // Environment variables exported by setup scripts.
+        env.extend(script_env.iter().cloned());
+
+        if let Some(coverage) = &self.coverage {
+            env.push((
+                LLVM_PROFILE_FILE_ENV.to_owned(),
+                coverage.profile_file(&test, attempt).to_string(),
+            ));
+        }
+
+        if batched || self.uses_libtest_json(&test) {
+            let (key, value) = libtest_json::LIBTEST_JSON_ENV;
+            env.push((key.to_owned(), value.to_owned()));
+        }
+
+        env
+    }
+
+    /// Runs a process for a single test, or for a batch of tests from the same binary, and waits
+    /// for it to exit.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_process(
+        &self,
+        tests: &[TestInstance<'a>],
+        attempt: usize,
+        slots: TestSlots,
+        slow_timeout: SlowTimeout,
+        heartbeat_period: Option<Duration>,
+        grace_period: Duration,
+        script_env: &[(String, String)],
+        recording_dir: Option<&Utf8Path>,
+        stopwatch: &StopwatchStart,
+        run_sender: &UnboundedSender<InternalTestEvent<'a>>,
+        shutdown: &Shutdown,
+    ) -> std::io::Result<ProcessOutput<'a>> {
+        let test = tests[0];
+        let batched = tests.len() > 1;
+
+        let mut env = self.test_env(tests, attempt, slots, script_env);
+
+        let tmpdir = match &self.tmpdir_run_dir {
+            Some(run_dir) => {
+                let tmpdir = TestTmpdir::create(run_dir, &test, attempt)?;
+                env.extend(tmpdir.env());
+                Some(tmpdir)
+            }
+            None => None,
+        };
+        let output_dir = match &self.output_dir_run_dir {
+            Some(run_dir) => {
+                let output_dir = TestOutputDir::create(run_dir, &test, attempt)?;
+                env.push(output_dir.env());
+                Some(output_dir)
+            }
+            None => None,
+        };
+
+        // With --no-capture, tests may read from the terminal, so they must stay in nextest's
+        // process group.
+        let process_group = !self.no_capture;
+        // Resource limits and ASLR settings would apply to the local ssh process rather than the
+        // remote test. Tests built for the host are always run locally.
+        let is_remote =
+            self.remote.is_some() && test.bin_info.build_platform != BuildPlatform::Host;
+        let (resource_limits, disable_aslr) = if is_remote {
+            (ResourceLimits::default(), false)
+        } else {
+            let settings = &self.test_settings[&(test.binary, test.name)];
+            (settings.resource_limits(), settings.disable_aslr())
+        };
+        // Tests are pinned to CPUs by slot, so that tests running at the same time don't share
+        // CPUs if the sets don't overlap.
+        let cpu_set = if is_remote || self.cpu_affinity.is_empty() {
+            None
+        } else {
+            Some(self.cpu_affinity[slots.global % self.cpu_affinity.len()])
+        };
+        // Lowering the priority of the local ssh process wouldn't help the machine running tests.
+        let nice = if is_remote { 0 } else { self.nice };
+        // Core dumps are written out on the remote host, where they can't be collected.
+        let core_dump_dir = self.core_dump_dir.as_ref().filter(|_| !is_remote);
+        // Only double-spawned processes can stop themselves before the test starts. Tests being
+        // recorded would be stopped before the recorder starts, so they aren't paused either.
+        let pause = self.wait_for_debugger
+            && !is_remote
+            && recording_dir.is_none()
+            && self.double_spawn.current_exe().is_some();
+        let setup = ProcessSetup {
+            process_group,
+            resource_limits,
+            core_dumps: core_dump_dir.is_some(),
+            disable_aslr,
+            cpu_set,
+            nice,
+            pause,
+        };
+        // Local tests are set up by the double-spawn process, if enabled. Remote tests are run
+        // through ssh, which is set up like any other process.
+        let launcher = if is_remote {
+            None
+        } else {
+            self.double_spawn.command(setup)
+        };
+
+        // Tests being recorded are run under the recorder instead of their usual wrapper.
+        let wrapper = match (recording_dir, &self.recorder) {
+            (Some(dir), Some(recorder)) => {
+                env.extend(recorder::env(dir));
+                Some(recorder.command())
+            }
+            _ => self.test_settings[&(test.binary, test.name)].wrapper(),
+        };
+        let launcher_args = launcher.as_deref().unwrap_or_default();
+        let cmd = if batched {
+            let test_names: Vec<_> = tests.iter().map(|test| test.name).collect();
+            let mut args = libtest_json::LIBTEST_JSON_ARGS.to_vec();
+            args.extend(libtest_json::LIBTEST_BATCH_ARGS);
+            test.make_batch_expression(
+                &test_names,
+                self.target_runner.as_ref(),
+                wrapper,
+                self.remote.as_ref(),
+                launcher_args,
+                &args,
+                &env,
+            )
+        } else {
+            test.make_expression(
+                self.target_runner.as_ref(),
+                wrapper,
+                self.remote.as_ref(),
+                launcher_args,
+                if self.uses_libtest_json(&test) {
+                    libtest_json::LIBTEST_JSON_ARGS
+                } else {
+                    &[]
+                },
+                &env,
+            )
+        }
+        .unchecked();
+        // The variables set above are applied on top of the inherited environment.
+        let cmd = match &self.inherited_env {
+            Some(inherited_env) => {
+                cmd.full_env(inherited_env.iter().map(|(key, value)| (key, value)))
+            }
+            None => cmd,
+        };
+
+        // For batches, libtest's output is parsed as it's written, to find out which test is running.
+        let progress = batched.then(|| Arc::new(Mutex::new(LibtestProgress::default())));
+        let (cmd, capture) = if self.no_capture {
+            (cmd, None)
+        } else {
+            // Capture stdout and stderr. Standard output that's parsed as JSON is kept in full,
+            // since cutting it short would lose events.
+            let stdout_limit = if batched || self.uses_libtest_json(&test) {
+                None
+            } else {
+                self.max_output_size
+            };
+            let (cmd, capture) = OutputCapture::configure(
+                cmd,
+                stdout_limit,
+                self.max_output_size,
+                progress.clone(),
+            )?;
+            (cmd, Some(capture))
+        };
+        let cmd = match &launcher {
+            Some(_) => cmd,
+            None => ProcessTree::configure(cmd, setup),
+        };
+        let cmd = if self.network_isolation {
+            netns::isolate(cmd)
+        } else {
+            cmd
+        };
+
+        let handle = cmd.start()?;
+        // Close nextest's copies of the pipes the test writes its output to.
+        drop(cmd);
+        let process_tree = ProcessTree::new(&handle, setup);
+        let pid = handle.pids().first().copied();
+        let slow_timeout = match pid {
+            Some(pid) if pause => {
+                self.wait_for_debugger(test, pid, run_sender, shutdown)
+                    .await;
+                // The test may be held up in the debugger for any amount of time.
+                SlowTimeout {
+                    terminate_after: None,
+                    ..slow_timeout
+                }
+            }
+            _ => slow_timeout,
+        };
+        // Without captured output, there's no way to tell whether a test is silent.
+        let heartbeat_period = heartbeat_period.filter(|_| capture.is_some());
+
+        // The test that's running, along with when it started. For batches, this is only known once
+        // libtest says that a test has started.
+        let mut current = (!batched).then(|| (test, stopwatch.clone()));
+        // The tests in a batch that have started so far.
+        let mut started = vec![];
+        let mut cancel_receiver = current.as_ref().map(|(test, _)| {
+            self.canceled_tests
+                .lock()
+                .expect("lock wasn't poisoned")
+                .register(test)
+        });
+        let wait = async {
+            // Errors waiting for the test are returned when its exit status is read below.
+            let exited = process::wait(&handle);
+            tokio::pin!(exited);
+
+            // Continue waiting for the test to finish with a timeout, logging at slow-timeout
+            // intervals and terminating the test after terminate-after periods (if configured).
+            let mut periods_elapsed = 0;
+            let mut next_period = Instant::now() + slow_timeout.period;
+            // The next time to check whether the test has gone silent, if configured.
+            let mut next_heartbeat = heartbeat_period.map(|period| Instant::now() + period);
+            // Set once a signal has been forwarded to the test.
+            let mut signal_forwarded = false;
+            // Set once the test has been asked to exit, either because it timed out or because a
+            // signal was forwarded to it. The test is killed if it's still running by then.
+            let mut kill_deadline: Option<Instant> = None;
+            let mut timed_out = false;
+            loop {
+                let next_poll = progress
+                    .as_ref()
+                    .map(|_| Instant::now() + PROGRESS_POLL_INTERVAL);
+                let deadline = [kill_deadline, next_heartbeat, next_poll]
+                    .iter()
+                    .flatten()
+                    .fold(next_period, |deadline, other| deadline.min(*other));
+                tokio::select! {
+                    _ = &mut exited => {
+                        // The test finished.
+                        return timed_out;
+                    }
+                    () = shutdown.forwarded(), if !signal_forwarded => {
+                        // Nextest received a signal: pass it on to the test, and give the test a
+                        // grace period to exit.
+                        signal_forwarded = true;
+                        match shutdown.signal.get() {
+                            Some(event) => process_tree.forward_signal(&handle, *event),
+                            None => unreachable!("signal is set before forwarding"),
+                        }
+                        let signal_deadline = Instant::now() + grace_period;
+                        kill_deadline = Some(kill_deadline.map_or(signal_deadline, |kill_deadline| {
+                            kill_deadline.min(signal_deadline)
+                        }));
+                    }
+                    () = recv_or_pending(cancel_receiver.as_mut()) => {
+                        // The test was canceled through a RunController: ask it to exit, and give
+                        // it a grace period to do so.
+                        cancel_receiver = None;
+                        process_tree.terminate(&handle);
+                        let cancel_deadline = Instant::now() + grace_period;
+                        kill_deadline = Some(kill_deadline.map_or(cancel_deadline, |kill_deadline| {
+                            kill_deadline.min(cancel_deadline)
+                        }));
+                    }
+                    () = shutdown.killed() => {
+                        // A second signal was received: kill the test immediately.
+                        process_tree.kill(&handle);
+                        let _ = exited.await;
+                        return timed_out;
+                    }
+                    () = tokio::time::sleep_until(deadline.into()) => {
+                        if let Some(progress) = &progress {
+                            let events = progress
+                                .lock()
+                                .expect("lock wasn't poisoned")
+                                .take_events();
+                            for (event, event_stopwatch) in events {
+                                let test_instance = match tests
+                                    .iter()
+                                    .find(|test_instance| test_instance.name == event.name)
+                                {
+                                    Some(test_instance) => *test_instance,
+                                    None => continue,
+                                };
+                                let mut canceled_tests =
+                                    self.canceled_tests.lock().expect("lock wasn't poisoned");
+                                match event.kind {
+                                    LibtestEventKind::Started => {
+                                        let _ = run_sender
+                                            .send(InternalTestEvent::Started { test_instance });
+                                        // Slow timeouts apply to each test in the batch
+                                        // separately.
+                                        periods_elapsed = 0;
+                                        next_period = Instant::now()
+                                            + slow_timeout
+                                                .period
+                                                .saturating_sub(event_stopwatch.elapsed());
+                                        if kill_deadline.is_none() {
+                                            cancel_receiver =
+                                                Some(canceled_tests.register(&test_instance));
+                                        }
+                                        current = Some((test_instance, event_stopwatch.clone()));
+                                        started.push((test_instance, event_stopwatch));
+                                    }
+                                    LibtestEventKind::Ok
+                                    | LibtestEventKind::Failed
+                                    | LibtestEventKind::Ignored => {
+                                        canceled_tests.unregister(&test_instance);
+                                        cancel_receiver = None;
+                                        current = None;
+                                    }
+                                    LibtestEventKind::Timeout => {}
+                                }
+                            }
+                        }
+                        if kill_deadline.map_or(false, |kill_deadline| kill_deadline <= Instant::now()) {
+                            // The test didn't exit within the grace period.
+                            process_tree.kill(&handle);
+                            let _ = exited.await;
+                            return timed_out;
+                        }
+                        if let (Some(period), Some(heartbeat), Some(capture)) =
+                            (heartbeat_period, next_heartbeat, &capture)
+                        {
+                            let now = Instant::now();
+                            if heartbeat <= now {
+                                let silent_for = now.saturating_duration_since(capture.last_output());
+                                if silent_for >= period {
+                                    if let Some((test_instance, test_stopwatch)) = &current {
+                                        let _ = run_sender.send(InternalTestEvent::Silent {
+                                            test_instance: *test_instance,
+                                            silent_for,
+                                            elapsed: test_stopwatch.elapsed(),
+                                        });
+                                    }
+                                    next_heartbeat = Some(now + period);
+                                } else {
+                                    // The test produced output since the last check: wait for a
+                                    // full period of silence from then.
+                                    next_heartbeat = Some(capture.last_output() + period);
+                                }
+                            }
+                        }
+                        if next_period > Instant::now() {
+                            continue;
+                        }
+
+                        next_period += slow_timeout.period;
+                        periods_elapsed += 1;
+                        let will_terminate = match slow_timeout.terminate_after {
+                            Some(terminate_after) => periods_elapsed >= terminate_after.get(),
+                            None => false,
+                        };
+                        if let Some((test_instance, test_stopwatch)) = &current {
+                            let _ = run_sender.send(InternalTestEvent::Slow {
+                                test_instance: *test_instance,
+                                elapsed: test_stopwatch.elapsed(),
+                                will_terminate,
+                            });
+                        }
+
+                        if will_terminate {
+                            // The test is marked as timed out below, whether or not it exits
+                            // within the grace period.
+                            timed_out = true;
+                            if grace_period.is_zero() {
+                                process_tree.kill(&handle);
+                                let _ = exited.await;
+                                return true;
+                            }
+                            // Ask the test to exit. Waiting finishes once it does, and it's killed
+                            // if it's still running once the grace period is up. No more slow or
+                            // silent events are sent for it.
+                            process_tree.terminate(&handle);
+                            let deadline = Instant::now() + grace_period;
+                            kill_deadline = Some(kill_deadline.map_or(deadline, |kill_deadline| {
+                                kill_deadline.min(deadline)
+                            }));
+                            next_period = deadline;
+                            next_heartbeat = None;
+                        }
+                    }
+                }
+            }
+        };
+        // Output is read while waiting for the test, so that the test doesn't block on a full pipe.
+        let read = async {
+            match &capture {
+                Some(capture) => capture.read().await.map(Some),
+                None => Ok(None),
+            }
+        };
+        let (timed_out, captured) = tokio::join!(wait, read);
+
+        if let Some((test, _)) = &current {
+            self.canceled_tests
+                .lock()
+                .expect("lock wasn't poisoned")
+                .unregister(test);
+        }
+
+        let mut output = handle.into_output()?;
+        // For batches, the core dump belongs to the test that was running when the process crashed.
+        let core_dump = match (core_dump_dir, pid) {
+            (Some(core_dump_dir), Some(pid)) => {
+                let test = current.as_ref().map_or(test, |(test, _)| *test);
+                // Failing to collect a core dump doesn't change the result of the test.
+                core_dump_dir
+                    .collect(&test, attempt, pid, output.status)
+                    .unwrap_or_default()
+            }
+            _ => None,
+        };
+        if let Some((stdout, stderr)) = captured? {
+            output.stdout = stdout;
+            output.stderr = stderr;
+        }
+        if let Some(progress) = &progress {
+            // Tests that started since the events were last checked.
+            let events = progress.lock().expect("lock wasn't poisoned").take_events();
+            for (event, event_stopwatch) in events {
+                if event.kind != LibtestEventKind::Started {
+                    continue;
+                }
+                if let Some(test_instance) = tests
+                    .iter()
+                    .find(|test_instance| test_instance.name == event.name)
+                {
+                    let _ = run_sender.send(InternalTestEvent::Started {
+                        test_instance: *test_instance,
+                    });
+                    started.push((*test_instance, event_stopwatch));
+                }
+            }
+        }
+        if self.test_color == TestColor::Never {
+            output.stdout = strip_ansi_escapes::strip(&output.stdout)?;
+            output.stderr = strip_ansi_escapes::strip(&output.stderr)?;
+        }
+
+        Ok(ProcessOutput {
+            output,
+            timed_out,
+            resource_limits,
+            tmpdir,
+            output_dir,
+            core_dump,
+            started,
         })
     }
 }
@@ -419,7 +2320,7 @@ pub struct ExecutionStatuses {
 
 #[allow(clippy::len_without_is_empty)] // RunStatuses is never empty
 impl ExecutionStatuses {
-    fn new(statuses: Vec<ExecuteStatus>) -> Self {
+    pub(crate) fn new(statuses: Vec<ExecuteStatus>) -> Self {
         Self { statuses }
     }
 
@@ -469,6 +2370,32 @@ impl ExecutionStatuses {
             }
         }
     }
+
+    /// Constructs a serializable summary of these executions of the given test.
+    pub fn to_summary(&self, test_instance: &TestInstance<'_>) -> TestRunSummary {
+        self.summary_for(&test_instance.bin_info.binary_id, test_instance.name)
+    }
+
+    /// Constructs a serializable summary of these executions of the test with the given binary ID
+    /// and name.
+    pub(crate) fn summary_for(&self, binary_id: &str, test_name: &str) -> TestRunSummary {
+        let outcome = match self.describe() {
+            ExecutionDescription::Success { .. } => TestOutcome::Passed,
+            ExecutionDescription::Flaky { .. } => TestOutcome::Flaky,
+            ExecutionDescription::Failure { .. } => TestOutcome::Failed,
+        };
+        TestRunSummary {
+            binary_id: binary_id.to_owned(),
+            test_name: test_name.to_owned(),
+            outcome,
+            skip_reason: None,
+            attempts: self
+                .statuses
+                .iter()
+                .map(ExecuteStatus::to_summary)
+                .collect(),
+        }
+    }
 }
 
 /// A description of test executions obtained from `ExecuteStatuses`.
@@ -531,6 +2458,10 @@ pub struct ExecuteStatus {
     pub start_time: SystemTime,
     /// The time it took for the test to run.
     pub time_taken: Duration,
+    /// The core dump written out when this test crashed, if [collected](NextestProfile::core_dumps).
+    pub core_dump: Option<Utf8PathBuf>,
+    /// The [output directory](NextestProfile::test_output_dir) of this test, if it was retained.
+    pub output_dir: Option<Utf8PathBuf>,
 }
 
 impl ExecuteStatus {
@@ -543,6 +2474,67 @@ impl ExecuteStatus {
     pub fn stderr(&self) -> &[u8] {
         &self.stdout_stderr.1
     }
+
+    /// Parses the panic the test failed with out of its output, if there is one.
+    ///
+    /// Panics are written to standard error, but some test harnesses merge it into standard output.
+    pub fn panic(&self) -> Option<TestPanic> {
+        TestPanic::parse(self.stderr()).or_else(|| TestPanic::parse(self.stdout()))
+    }
+
+    /// Constructs a serializable summary for this execution.
+    pub fn to_summary(&self) -> AttemptSummary {
+        AttemptSummary {
+            attempt: self.attempt,
+            total_attempts: self.total_attempts,
+            result: self.result.to_summary(),
+            exec_time_secs: self.time_taken.as_secs_f64(),
+            core_dump: self.core_dump.clone(),
+            output_dir: self.output_dir.clone(),
+        }
+    }
+}
+
+/// A test that was running when information about the run was requested.
+#[derive(Clone, Debug)]
+pub struct RunningTest<'a> {
+    /// The test instance that is running.
+    pub test_instance: TestInstance<'a>,
+    /// The attempt that is currently running. In the range `[1, total_attempts]`.
+    pub attempt: usize,
+    /// The time that has elapsed since the test started, including any earlier attempts.
+    pub elapsed: Duration,
+}
+
+/// A test process that has exited.
+struct ProcessOutput<'a> {
+    output: std::process::Output,
+    timed_out: bool,
+    resource_limits: ResourceLimits,
+    tmpdir: Option<TestTmpdir>,
+    output_dir: Option<TestOutputDir>,
+    core_dump: Option<Utf8PathBuf>,
+    // For batches, the tests that started, along with when they did.
+    started: Vec<(TestInstance<'a>, StopwatchStart)>,
+}
+
+/// Returns the result of a test process that has exited.
+fn process_result(
+    output: &std::process::Output,
+    timed_out: bool,
+    resource_limits: ResourceLimits,
+) -> ExecutionResult {
+    if timed_out {
+        ExecutionResult::Timeout
+    } else if double_spawn::exec_failed(output) {
+        ExecutionResult::ExecFail
+    } else if output.status.success() {
+        ExecutionResult::Pass
+    } else if process::exceeded_resource_limits(output.status, &output.stderr, resource_limits) {
+        ExecutionResult::ResourceLimit
+    } else {
+        ExecutionResult::Fail
+    }
 }
 
 struct InternalExecuteStatus {
@@ -550,6 +2542,8 @@ struct InternalExecuteStatus {
     stderr: Vec<u8>,
     result: ExecutionResult,
     stopwatch_end: StopwatchEnd,
+    core_dump: Option<Utf8PathBuf>,
+    output_dir: Option<Utf8PathBuf>,
 }
 
 impl InternalExecuteStatus {
@@ -561,12 +2555,34 @@ impl InternalExecuteStatus {
             result: self.result,
             start_time: self.stopwatch_end.start_time,
             time_taken: self.stopwatch_end.duration,
+            core_dump: self.core_dump,
+            output_dir: self.output_dir,
         }
     }
 }
 
-/// Statistics for a test run.
-#[derive(Copy, Clone, Default, Debug)]
+/// A test as it would be scheduled by the runner, returned by [`TestRunner::schedule`].
+#[derive(Clone, Debug)]
+pub struct ScheduledTest<'a> {
+    /// The test instance.
+    pub test_instance: TestInstance<'a>,
+
+    /// The number of test threads the test takes up while it runs.
+    pub threads_required: usize,
+
+    /// The test group the test is run in, if any.
+    pub test_group: Option<String>,
+
+    /// The number of times the test is retried if it fails.
+    pub retries: usize,
+
+    /// The scheduling priority of the test. Tests with a higher priority are scheduled first.
+    pub priority: i32,
+}
+
+/// Statistics for a test run.
+#[derive(Copy, Clone, Default, Debug, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub struct RunStats {
     /// The total number of tests that were expected to be run at the beginning.
     ///
@@ -588,28 +2604,91 @@ pub struct RunStats {
     /// The number of tests that encountered an execution failure.
     pub exec_failed: usize,
 
+    /// The number of tests that timed out.
+    pub timed_out: usize,
+
+    /// The number of tests that were killed after exceeding a resource limit.
+    pub resource_limited: usize,
+
+    /// The number of quarantined tests that failed. These aren't included in the other failure
+    /// counts, and don't cause the run to fail.
+    pub quarantined: usize,
+
     /// The number of tests that were skipped.
     pub skipped: usize,
+
+    /// The number of skipped tests that don't match the profile's default filter. These are
+    /// included in `skipped`.
+    pub skipped_by_default_filter: usize,
+
+    /// The number of skipped tests that are excluded through the configuration. These are
+    /// included in `skipped`.
+    pub excluded: usize,
+
+    /// The number of tests that weren't run because the run was canceled.
+    ///
+    /// With `--repeat`, each run of a test that didn't happen is counted separately.
+    pub canceled: usize,
+
+    /// The number of setup or teardown scripts that failed.
+    pub scripts_failed: usize,
+
+    /// The reason the run was canceled, if it was.
+    pub cancel_reason: Option<CancelReason>,
 }
 
 impl RunStats {
+    /// Returns the number of tests that failed, encountered an execution failure, timed out or
+    /// exceeded a resource limit.
+    pub fn failed_count(&self) -> usize {
+        self.failed + self.exec_failed + self.timed_out + self.resource_limited
+    }
+
     /// Returns true if this run is considered a success.
     ///
     /// A run can be marked as failed if any of the following are true:
     /// * the run was canceled: the initial run count is greater than the final run count
     /// * any tests failed
     /// * any tests encountered an execution failure
+    /// * any tests timed out
+    /// * any tests exceeded a resource limit
+    /// * any setup or teardown scripts failed
+    ///
+    /// Failures of quarantined tests don't count.
     pub fn is_success(&self) -> bool {
         if self.initial_run_count > self.final_run_count {
             return false;
         }
-        if self.failed > 0 || self.exec_failed > 0 {
+        if self.failed_count() > 0 {
+            return false;
+        }
+        if self.scripts_failed > 0 {
             return false;
         }
         true
     }
 
-    fn on_test_finished(&mut self, run_statuses: &ExecutionStatuses) {
+    /// Constructs a serializable summary for these statistics.
+    pub fn to_summary(&self) -> RunStatsSummary {
+        let mut summary = RunStatsSummary::default();
+        summary.initial_run_count = self.initial_run_count;
+        summary.final_run_count = self.final_run_count;
+        summary.passed = self.passed;
+        summary.flaky = self.flaky;
+        summary.failed = self.failed;
+        summary.exec_failed = self.exec_failed;
+        summary.timed_out = self.timed_out;
+        summary.resource_limited = self.resource_limited;
+        summary.quarantined = self.quarantined;
+        summary.skipped = self.skipped;
+        summary.skipped_by_default_filter = self.skipped_by_default_filter;
+        summary.excluded = self.excluded;
+        summary.canceled = self.canceled;
+        summary.scripts_failed = self.scripts_failed;
+        summary
+    }
+
+    fn on_test_finished(&mut self, run_statuses: &ExecutionStatuses, quarantined: bool) {
         self.final_run_count += 1;
         // run_statuses is guaranteed to have at least one element.
         // * If the last element is success, treat it as success (and possibly flaky).
@@ -620,6 +2699,10 @@ impl RunStats {
         // This is not likely to matter much in practice since failures are likely to be of the
         // same type.
         let last_status = run_statuses.last_status();
+        if quarantined && !last_status.result.is_success() {
+            self.quarantined += 1;
+            return;
+        }
         match last_status.result {
             ExecutionResult::Pass => {
                 self.passed += 1;
@@ -629,77 +2712,153 @@ impl RunStats {
             }
             ExecutionResult::Fail => self.failed += 1,
             ExecutionResult::ExecFail => self.exec_failed += 1,
+            ExecutionResult::Timeout => self.timed_out += 1,
+            ExecutionResult::ResourceLimit => self.resource_limited += 1,
         }
     }
 }
 
-struct CallbackContext<F, E> {
+struct CallbackContext<'a, F, E> {
     callback: F,
+    run_id: Uuid,
     stopwatch: StopwatchStart,
     run_stats: RunStats,
-    fail_fast: bool,
-    running: usize,
+    max_fail: Option<usize>,
+    // Map of tests that are currently running to when they started and the current attempt.
+    running: HashMap<(&'a Utf8Path, &'a str), (TestInstance<'a>, StopwatchStart, usize)>,
+    // Tests that failed, in the order they finished, along with the number of attempts made.
+    // Quarantined tests aren't included.
+    failed: Vec<(TestInstance<'a>, usize)>,
     cancel_state: Option<CancelReason>,
     phantom: PhantomData<E>,
 }
 
-impl<'a, F, E> CallbackContext<F, E>
+impl<'a, F, E> CallbackContext<'a, F, E>
 where
     F: FnMut(TestEvent<'a>) -> Result<(), E> + Send,
 {
-    fn new(callback: F, initial_run_count: usize, fail_fast: bool) -> Self {
+    fn new(callback: F, run_id: Uuid, initial_run_count: usize, max_fail: Option<usize>) -> Self {
         Self {
             callback,
+            run_id,
             stopwatch: StopwatchStart::now(),
             run_stats: RunStats {
                 initial_run_count,
                 ..RunStats::default()
             },
-            fail_fast,
-            running: 0,
+            max_fail,
+            running: HashMap::new(),
+            failed: vec![],
             cancel_state: None,
             phantom: PhantomData,
         }
     }
 
-    fn run_started(&mut self, test_list: &'a TestList) -> Result<(), E> {
-        (self.callback)(TestEvent::RunStarted { test_list })
+    fn run_started(
+        &mut self,
+        test_list: &'a TestList,
+        shuffle_seed: Option<u64>,
+        repeat: usize,
+    ) -> Result<(), E> {
+        self.report(TestEventKind::RunStarted {
+            test_list,
+            shuffle_seed,
+            repeat,
+        })
+    }
+
+    /// Tags an event with the run ID and the current time, and passes it to the callback.
+    fn report(&mut self, kind: TestEventKind<'a>) -> Result<(), E> {
+        let now = self.stopwatch.end();
+        (self.callback)(TestEvent {
+            run_id: self.run_id,
+            timestamp: now.start_time + now.duration,
+            since_start: now.duration,
+            kind,
+        })
     }
 
     fn handle_event(&mut self, event: InternalEvent<'a>) -> Result<(), InternalError<E>> {
         match event {
             InternalEvent::Test(InternalTestEvent::Started { test_instance }) => {
-                self.running += 1;
-                (self.callback)(TestEvent::TestStarted { test_instance })
+                self.running.insert(
+                    (test_instance.binary, test_instance.name),
+                    (test_instance, StopwatchStart::now(), 1),
+                );
+                self.report(TestEventKind::TestStarted { test_instance })
                     .map_err(InternalError::Error)
             }
             InternalEvent::Test(InternalTestEvent::Slow {
                 test_instance,
                 elapsed,
-            }) => (self.callback)(TestEvent::TestSlow {
+                will_terminate,
+            }) => self
+                .report(TestEventKind::TestSlow {
+                    test_instance,
+                    elapsed,
+                    will_terminate,
+                })
+                .map_err(InternalError::Error),
+            InternalEvent::Test(InternalTestEvent::Silent {
                 test_instance,
+                silent_for,
                 elapsed,
-            })
-            .map_err(InternalError::Error),
+            }) => self
+                .report(TestEventKind::TestSilent {
+                    test_instance,
+                    silent_for,
+                    elapsed,
+                })
+                .map_err(InternalError::Error),
+            InternalEvent::Test(InternalTestEvent::Paused { test_instance, pid }) => self
+                .report(TestEventKind::TestPaused { test_instance, pid })
+                .map_err(InternalError::Error),
             InternalEvent::Test(InternalTestEvent::Retry {
                 test_instance,
                 run_status,
-            }) => (self.callback)(TestEvent::TestRetry {
-                test_instance,
-                run_status,
-            })
-            .map_err(InternalError::Error),
+                delay_before_next_attempt,
+            }) => {
+                if let Some((_, _, attempt)) = self
+                    .running
+                    .get_mut(&(test_instance.binary, test_instance.name))
+                {
+                    *attempt = run_status.attempt + 1;
+                }
+                self.report(TestEventKind::TestRetry {
+                    test_instance,
+                    run_status,
+                    delay_before_next_attempt,
+                })
+                .map_err(InternalError::Error)
+            }
             InternalEvent::Test(InternalTestEvent::Finished {
                 test_instance,
                 run_statuses,
+                quarantined,
+                ..
             }) => {
-                self.running -= 1;
-                self.run_stats.on_test_finished(&run_statuses);
+                self.running
+                    .remove(&(test_instance.binary, test_instance.name));
+                self.run_stats.on_test_finished(&run_statuses, quarantined);
+
+                let failed = !quarantined && !run_statuses.last_status().result.is_success();
+                // With --repeat, a test may fail several times, but it's only recorded once.
+                if failed
+                    && !self.failed.iter().any(|(failed, _)| {
+                        (failed.binary, failed.name) == (test_instance.binary, test_instance.name)
+                    })
+                {
+                    self.failed.push((test_instance, run_statuses.len()));
+                }
 
-                // should this run be canceled because of a failure?
-                let fail_cancel = self.fail_fast && !run_statuses.last_status().result.is_success();
+                // should this run be canceled because of a failure? Failures of quarantined tests
+                // don't count.
+                let fail_cancel = failed
+                    && self
+                        .max_fail
+                        .map_or(false, |max_fail| self.run_stats.failed_count() >= max_fail);
 
-                (self.callback)(TestEvent::TestFinished {
+                self.report(TestEventKind::TestFinished {
                     test_instance,
                     run_statuses,
                 })
@@ -714,21 +2873,49 @@ where
                     Ok(())
                 }
             }
+            InternalEvent::Test(InternalTestEvent::Released { .. }) => Ok(()),
+            InternalEvent::Test(InternalTestEvent::Canceled { test_instance }) => self
+                .test_canceled(test_instance)
+                .map_err(InternalError::Error),
+            InternalEvent::Test(InternalTestEvent::Recorded {
+                test_instance,
+                run_status,
+                recording_dir,
+            }) => self
+                .report(TestEventKind::TestRecorded {
+                    test_instance,
+                    run_status,
+                    recording_dir,
+                })
+                .map_err(InternalError::Error),
             InternalEvent::Test(InternalTestEvent::Skipped {
                 test_instance,
                 reason,
+                exclude_reason,
             }) => {
                 self.run_stats.skipped += 1;
-                (self.callback)(TestEvent::TestSkipped {
+                match reason {
+                    MismatchReason::DefaultFilter => self.run_stats.skipped_by_default_filter += 1,
+                    MismatchReason::Excluded => self.run_stats.excluded += 1,
+                    _ => {}
+                }
+                self.report(TestEventKind::TestSkipped {
                     test_instance,
                     reason,
+                    exclude_reason,
                 })
                 .map_err(InternalError::Error)
             }
-            InternalEvent::Signal(SignalEvent::Interrupted) => {
+            InternalEvent::Signal(SignalEvent::Info) => {
+                let running_tests = self.running_tests();
+                self.report(TestEventKind::RunInfo { running_tests })
+                    .map_err(InternalError::Error)
+            }
+            InternalEvent::Signal(SignalEvent::Shutdown(_)) => {
                 if self.cancel_state == Some(CancelReason::Signal) {
-                    // Ctrl-C was pressed twice -- panic in this case.
-                    panic!("Ctrl-C pressed twice, exiting immediately");
+                    // A second signal was received. Running tests are killed by the runner, so
+                    // there's nothing more to do here.
+                    return Ok(());
                 }
 
                 Err(InternalError::SignalCanceled(
@@ -738,13 +2925,52 @@ where
         }
     }
 
+    /// Returns the tests that are currently running, longest-running first.
+    fn running_tests(&self) -> Vec<RunningTest<'a>> {
+        let mut running_tests: Vec<_> = self
+            .running
+            .values()
+            .map(|(test_instance, stopwatch, attempt)| RunningTest {
+                test_instance: *test_instance,
+                attempt: *attempt,
+                elapsed: stopwatch.elapsed(),
+            })
+            .collect();
+        // Tests that have been running the longest are the most likely to be stuck, so list them
+        // first.
+        running_tests.sort_by_key(|running_test| std::cmp::Reverse(running_test.elapsed));
+        running_tests
+    }
+
+    fn test_canceled(&mut self, test_instance: TestInstance<'a>) -> Result<(), E> {
+        self.run_stats.canceled += 1;
+        self.report(TestEventKind::TestCanceled { test_instance })
+    }
+
+    fn script_finished(
+        &mut self,
+        script_name: &str,
+        kind: ScriptKind,
+        status: ScriptStatus,
+    ) -> Result<(), E> {
+        if !status.result.is_success() {
+            self.run_stats.scripts_failed += 1;
+        }
+        self.report(TestEventKind::ScriptFinished {
+            script_name: script_name.to_owned(),
+            kind,
+            status,
+        })
+    }
+
     /// Begin cancellation of a test run. Report it if the current cancel state is less than
     /// the required one.
     fn begin_cancel(&mut self, reason: CancelReason) -> Result<(), E> {
         if self.cancel_state < Some(reason) {
             self.cancel_state = Some(reason);
-            (self.callback)(TestEvent::RunBeginCancel {
-                running: self.running,
+            self.report(TestEventKind::RunBeginCancel {
+                running: self.running.len(),
+                running_tests: self.running_tests(),
                 reason,
             })?;
         }
@@ -752,8 +2978,9 @@ where
     }
 
     fn run_finished(&mut self) -> Result<(), E> {
+        self.run_stats.cancel_reason = self.cancel_state;
         let stopwatch_end = self.stopwatch.end();
-        (self.callback)(TestEvent::RunFinished {
+        self.report(TestEventKind::RunFinished {
             start_time: stopwatch_end.start_time,
             elapsed: stopwatch_end.duration,
             run_stats: self.run_stats,
@@ -775,19 +3002,165 @@ enum InternalTestEvent<'a> {
     Slow {
         test_instance: TestInstance<'a>,
         elapsed: Duration,
+        will_terminate: bool,
+    },
+    Silent {
+        test_instance: TestInstance<'a>,
+        silent_for: Duration,
+        elapsed: Duration,
+    },
+    // The test process stopped before the test started, and is waiting for a debugger.
+    Paused {
+        test_instance: TestInstance<'a>,
+        pid: u32,
     },
     Retry {
         test_instance: TestInstance<'a>,
         run_status: ExecuteStatus,
+        delay_before_next_attempt: Duration,
     },
     Finished {
         test_instance: TestInstance<'a>,
         run_statuses: ExecutionStatuses,
+        // True if the test is quarantined, in which case its failures don't fail the run.
+        quarantined: bool,
+    },
+    // The threads and slots taken up by the test, or by the batch of tests it was run in, are no
+    // longer in use. This is sent once all runs of the test or of its batch have finished.
+    Released {
+        test_instance: TestInstance<'a>,
+        slots: TestSlots,
     },
     Skipped {
         test_instance: TestInstance<'a>,
         reason: MismatchReason,
+        exclude_reason: Option<String>,
     },
+    // The test was scheduled, but the run was canceled before it started.
+    Canceled {
+        test_instance: TestInstance<'a>,
+    },
+    // The test failed earlier in the run, and was run again under the recorder.
+    Recorded {
+        test_instance: TestInstance<'a>,
+        run_status: ExecuteStatus,
+        recording_dir: Utf8PathBuf,
+    },
+}
+
+/// Tells running tests to shut down once nextest receives a signal.
+///
+/// Dropping a sender is visible to every receiver at once, so it's used to broadcast to all running
+/// tests: the first signal is forwarded to tests, and after a second signal tests are killed. Tests
+/// waiting to be retried also stop waiting once the run is canceled for any reason.
+#[derive(Debug)]
+struct Shutdown {
+    // Set before forward_sender is dropped.
+    signal: OnceCell<ShutdownEvent>,
+    forward_sender: Mutex<Option<watch::Sender<()>>>,
+    forward_receiver: watch::Receiver<()>,
+    kill_sender: Mutex<Option<watch::Sender<()>>>,
+    kill_receiver: watch::Receiver<()>,
+    cancel_sender: Mutex<Option<watch::Sender<()>>>,
+    cancel_receiver: watch::Receiver<()>,
+}
+
+impl Shutdown {
+    fn new() -> Self {
+        let (forward_sender, forward_receiver) = watch::channel(());
+        let (kill_sender, kill_receiver) = watch::channel(());
+        let (cancel_sender, cancel_receiver) = watch::channel(());
+        Self {
+            signal: OnceCell::new(),
+            forward_sender: Mutex::new(Some(forward_sender)),
+            forward_receiver,
+            kill_sender: Mutex::new(Some(kill_sender)),
+            kill_receiver,
+            cancel_sender: Mutex::new(Some(cancel_sender)),
+            cancel_receiver,
+        }
+    }
+
+    fn is_signaled(&self) -> bool {
+        self.signal.get().is_some()
+    }
+
+    fn signal_received(&self, event: ShutdownEvent) {
+        let sender = if self.signal.set(event).is_ok() {
+            &self.forward_sender
+        } else {
+            &self.kill_sender
+        };
+        sender.lock().expect("lock wasn't poisoned").take();
+    }
+
+    /// Waits for the first signal, which is forwarded to tests.
+    async fn forwarded(&self) {
+        closed(&self.forward_receiver).await
+    }
+
+    /// Waits for the second signal, after which tests are killed.
+    async fn killed(&self) {
+        closed(&self.kill_receiver).await
+    }
+
+    fn is_canceled(&self) -> bool {
+        self.cancel_sender
+            .lock()
+            .expect("lock wasn't poisoned")
+            .is_none()
+    }
+
+    /// Marks the run as canceled, e.g. because a test failed with fail-fast set.
+    fn cancel(&self) {
+        self.cancel_sender
+            .lock()
+            .expect("lock wasn't poisoned")
+            .take();
+    }
+
+    /// Waits out the delay before a test is retried.
+    ///
+    /// Returns false, as soon as it happens, if a signal is received or the run is canceled: the
+    /// test shouldn't be retried then.
+    async fn wait_for_retry(&self, delay: Duration) -> bool {
+        if !delay.is_zero() {
+            tokio::select! {
+                () = self.forwarded() => {}
+                () = self.killed() => {}
+                () = closed(&self.cancel_receiver) => {}
+                () = tokio::time::sleep(delay) => {}
+            }
+        }
+        !self.is_signaled() && !self.is_canceled()
+    }
+}
+
+/// Waits for the sender of a channel that's only used to broadcast its closing to be dropped.
+async fn closed(receiver: &watch::Receiver<()>) {
+    // Nothing is ever sent, so this only returns once the sender is dropped.
+    let _ = receiver.clone().changed().await;
+}
+
+/// Receives the next message from a channel, or waits forever if there's no channel or it's been
+/// closed.
+async fn recv_or_pending<T>(receiver: Option<&mut UnboundedReceiver<T>>) -> T {
+    if let Some(receiver) = receiver {
+        if let Some(message) = receiver.recv().await {
+            return message;
+        }
+    }
+    std::future::pending().await
+}
+
+/// Waits for the next tick of an interval, or forever if there's no interval.
+async fn tick_or_pending(interval: Option<&mut tokio::time::Interval>) {
+    match interval {
+        Some(interval) => {
+            interval.tick().await;
+        }
+        None => std::future::pending().await,
+    }
 }
 
 #[derive(Debug)]
@@ -798,7 +3171,8 @@ enum InternalError<E> {
 }
 
 /// Whether a test passed, failed or an error occurred while executing the test.
-#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
 pub enum ExecutionResult {
     /// The test passed.
     Pass,
@@ -806,6 +3180,10 @@ pub enum ExecutionResult {
     Fail,
     /// An error occurred while executing the test.
     ExecFail,
+    /// The test was terminated after exceeding its timeout.
+    Timeout,
+    /// The test was killed after exceeding one of its resource limits.
+    ResourceLimit,
 }
 
 impl ExecutionResult {
@@ -813,36 +3191,739 @@ impl ExecutionResult {
     pub fn is_success(self) -> bool {
         match self {
             ExecutionResult::Pass => true,
-            ExecutionResult::Fail | ExecutionResult::ExecFail => false,
+            ExecutionResult::Fail
+            | ExecutionResult::ExecFail
+            | ExecutionResult::Timeout
+            | ExecutionResult::ResourceLimit => false,
+        }
+    }
+
+    /// Converts this result into its serializable form.
+    pub fn to_summary(self) -> ExecutionResultSummary {
+        match self {
+            ExecutionResult::Pass => ExecutionResultSummary::Pass,
+            ExecutionResult::Fail => ExecutionResultSummary::Fail,
+            ExecutionResult::ExecFail => ExecutionResultSummary::ExecFail,
+            ExecutionResult::Timeout => ExecutionResultSummary::Timeout,
+            ExecutionResult::ResourceLimit => ExecutionResultSummary::ResourceLimit,
         }
     }
 }
 
+/// Shuffles items in place, in an order determined entirely by `seed`.
+///
+/// ChaCha8 is used rather than `StdRng`, since the latter's algorithm may change across `rand`
+/// versions -- and with it, the order a given seed produces.
+fn shuffle_with_seed<T>(items: &mut [T], seed: u64) {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    items.shuffle(&mut rng);
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::config::NextestConfig;
+    use crate::{
+        config::NextestConfig,
+        test_list::{
+            test_helpers::{self, fake_artifact, make_config, make_config_in, BASE_ID, HELPER_ID},
+            RustTestArtifact,
+        },
+    };
+
+    #[test]
+    fn shuffle_is_reproducible() {
+        let items: Vec<usize> = (0..64).collect();
+
+        let mut first = items.clone();
+        shuffle_with_seed(&mut first, 42);
+        let mut second = items.clone();
+        shuffle_with_seed(&mut second, 42);
+        assert_eq!(first, second, "same seed => same order");
+        assert_ne!(first, items, "shuffled order differs from the original");
+
+        let mut other = items.clone();
+        shuffle_with_seed(&mut other, 43);
+        assert_ne!(first, other, "different seed => different order");
+
+        first.sort_unstable();
+        assert_eq!(first, items, "shuffling is a permutation");
+    }
+
+    fn runtime() -> Runtime {
+        tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime built")
+    }
+
+    #[test]
+    fn shutdown_broadcast() {
+        use futures_util::FutureExt;
+
+        let shutdown = Shutdown::new();
+        assert!(!shutdown.is_signaled());
+        assert_eq!(shutdown.forwarded().now_or_never(), None);
+
+        // The first signal is forwarded to tests.
+        shutdown.signal_received(ShutdownEvent::Terminate);
+        assert_eq!(shutdown.signal.get(), Some(&ShutdownEvent::Terminate));
+        assert_eq!(shutdown.forwarded().now_or_never(), Some(()));
+        assert_eq!(shutdown.killed().now_or_never(), None);
+
+        // The second signal kills tests, and doesn't change the signal that was forwarded.
+        shutdown.signal_received(ShutdownEvent::Interrupt);
+        assert_eq!(shutdown.signal.get(), Some(&ShutdownEvent::Terminate));
+        assert_eq!(shutdown.killed().now_or_never(), Some(()));
+    }
+
+    #[test]
+    fn retry_wait_canceled() {
+        let runtime = runtime();
+        let shutdown = Shutdown::new();
+        assert!(runtime.block_on(shutdown.wait_for_retry(Duration::from_millis(10))));
+
+        // A cancellation or a signal during the wait ends it early, and the test isn't retried.
+        let cancel: fn(&Shutdown) = |shutdown| shutdown.cancel();
+        let interrupt: fn(&Shutdown) =
+            |shutdown| shutdown.signal_received(ShutdownEvent::Interrupt);
+        for stop in [cancel, interrupt] {
+            let shutdown = Arc::new(Shutdown::new());
+            let handle = {
+                let shutdown = shutdown.clone();
+                std::thread::spawn(move || {
+                    std::thread::sleep(Duration::from_millis(50));
+                    stop(&shutdown);
+                })
+            };
+            let start = Instant::now();
+            assert!(!runtime.block_on(shutdown.wait_for_retry(Duration::from_secs(600))));
+            assert!(
+                start.elapsed() < Duration::from_secs(60),
+                "canceled run doesn't wait out the backoff"
+            );
+            handle.join().expect("thread didn't panic");
+
+            // Once canceled, tests aren't retried even without a delay.
+            assert!(!runtime.block_on(shutdown.wait_for_retry(Duration::ZERO)));
+        }
+    }
+
+    #[test]
+    fn test_group_scopes() {
+        let test_list = make_test_list();
+
+        for (scope, expected) in [
+            (
+                "binary",
+                vec!["helper::first", "helper::second", "base::third"],
+            ),
+            ("package", vec!["helper::first", "base::third"]),
+            // Tests are scheduled in order of binary ID.
+            ("global", vec!["base::third"]),
+        ] {
+            let config = make_config(&format!(
+                r#"
+                [test-groups.serial]
+                max-threads = 1
+                scope = "{}"
+
+                [[profile.default.overrides]]
+                filter = "all()"
+                test-group = "serial"
+                "#,
+                scope
+            ));
+            let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+            let mut builder = TestRunnerBuilder::default();
+            builder.set_test_threads(TestThreads::Count(NonZeroUsize::new(8).unwrap()));
+            let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+
+            // Start every test that the group lets in, as the scheduler would while no tests
+            // finish.
+            let mut group_running = HashMap::new();
+            let mut started = vec![];
+            for test_instance in runner.scheduling_order() {
+                if runner.group_has_room(&test_instance, &group_running) {
+                    let (key, _) = runner
+                        .test_group(&test_instance)
+                        .expect("test is in a group");
+                    *group_running.entry(key).or_default() += 1;
+                    started.push(test_instance.bin_info.binary_id.as_str());
+                }
+            }
+            started.sort_unstable();
+            let mut expected = expected;
+            expected.sort_unstable();
+            assert_eq!(
+                started, expected,
+                "with scope {}, one test runs at a time for each key",
+                scope
+            );
+        }
+    }
+
+    #[test]
+    fn threads_required_env() {
+        let test_list = make_test_list();
+        let config = make_config(
+            r#"
+            [[profile.default.overrides]]
+            filter = "binary(=helper::first)"
+            threads-required = 2
+
+            [[profile.default.overrides]]
+            filter = "binary(=helper::second)"
+            threads-required = "num-test-threads"
+            "#,
+        );
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut builder = TestRunnerBuilder::default();
+        builder.set_test_threads(TestThreads::Count(NonZeroUsize::new(8).unwrap()));
+        let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+
+        let threads_required: Vec<_> = test_list
+            .iter_tests()
+            .filter(|test_instance| test_instance.name == "a")
+            .map(|test_instance| {
+                let slots = TestSlots {
+                    global: 0,
+                    group: None,
+                };
+                let env = runner.test_env(&[test_instance], 1, slots, &[]);
+                let value = env
+                    .into_iter()
+                    .find(|(key, _)| key == "NEXTEST_THREADS_REQUIRED")
+                    .map(|(_, value)| value);
+                (test_instance.bin_info.binary_id.as_str(), value)
+            })
+            .collect();
+        assert_eq!(
+            threads_required,
+            [
+                ("base::third", Some("1".to_owned())),
+                ("helper::first", Some("2".to_owned())),
+                ("helper::second", Some("8".to_owned())),
+            ],
+            "tests are told how many threads are reserved for them"
+        );
+    }
+
+    /// Makes a test list with two tests, `a` and `b`, in each of three binaries: two in one
+    /// package, and one in another.
+    fn make_test_list() -> TestList<'static> {
+        test_helpers::make_test_list(
+            vec![
+                fake_artifact(HELPER_ID, "helper::first"),
+                fake_artifact(HELPER_ID, "helper::second"),
+                fake_artifact(BASE_ID, "base::third"),
+            ]
+            .into_iter()
+            .map(|artifact| (artifact, "a: test\nb: test\n", "")),
+        )
+    }
+
+    /// Makes a test list with a single binary, `helper::script`: a shell script that runs the given
+    /// commands for each test. The script is written to `dir`, which tests are also run in.
+    #[cfg(unix)]
+    fn make_script_test_list(dir: &Utf8Path, tests: &[(&str, &str)]) -> TestList<'static> {
+        use std::os::unix::fs::PermissionsExt;
+
+        // Tests are run with `--exact <name> --nocapture`.
+        let mut script = "#!/bin/sh\ncase \"$2\" in\n".to_owned();
+        for (name, commands) in tests {
+            script.push_str(&format!("    {}) {} ;;\n", name, commands));
+        }
+        script.push_str("esac\n");
+        let binary_path = dir.join("tests.sh");
+        std::fs::write(&binary_path, script).expect("wrote test script");
+        std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755))
+            .expect("made test script executable");
+
+        let artifact = RustTestArtifact {
+            binary_path,
+            cwd: dir.to_owned(),
+            ..fake_artifact(HELPER_ID, "helper::script")
+        };
+        let list_output: String = tests
+            .iter()
+            .map(|(name, _)| format!("{}: test\n", name))
+            .collect();
+        test_helpers::make_test_list(std::iter::once((artifact, list_output.as_str(), "")))
+    }
+
+    /// Runs tests to completion, calling `on_started` with the name of each test that starts.
+    /// Returns the run's stats, along with the statuses of each test that finished.
+    #[cfg(unix)]
+    fn run_tests<'a>(
+        runner: &TestRunner<'a>,
+        mut on_started: impl FnMut(&str) + Send,
+    ) -> (RunStats, HashMap<&'a str, ExecutionStatuses>) {
+        let mut finished = HashMap::new();
+        let stats = runner
+            .execute(|event| match event.kind {
+                TestEventKind::TestStarted { test_instance } => on_started(test_instance.name),
+                TestEventKind::TestFinished {
+                    test_instance,
+                    run_statuses,
+                } => {
+                    finished.insert(test_instance.name, run_statuses);
+                }
+                _ => {}
+            })
+            .expect("no reporters to fail");
+        (stats, finished)
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn slow_timeout_terminates_test() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        let test_list = make_script_test_list(dir, &[("sleep", "exec sleep 60")]);
+        let config = make_config(
+            r#"
+            [profile.default]
+            slow-timeout = { period = "100ms", terminate-after = 2 }
+            grace-period = "60s"
+            "#,
+        );
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let runner =
+            TestRunnerBuilder::default().build(&test_list, &profile, SignalHandler::noop());
+
+        let (stats, finished) = run_tests(&runner, |_| {});
+        assert_eq!(stats.timed_out, 1);
+        let status = finished["sleep"].last_status();
+        assert_eq!(status.result, ExecutionResult::Timeout);
+        assert!(
+            status.time_taken < Duration::from_secs(30),
+            "test exits when asked to, well before the grace period is up: {:?}",
+            status.time_taken
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn grace_period_kills_test() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        // Ignored signals stay ignored across exec.
+        let test_list =
+            make_script_test_list(dir, &[("ignore_term", "trap '' TERM; exec sleep 60")]);
+        let config = make_config(
+            r#"
+            [profile.default]
+            slow-timeout = { period = "100ms", terminate-after = 1 }
+            grace-period = "500ms"
+            "#,
+        );
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let runner =
+            TestRunnerBuilder::default().build(&test_list, &profile, SignalHandler::noop());
+
+        let (stats, finished) = run_tests(&runner, |_| {});
+        assert_eq!(stats.timed_out, 1);
+        let status = finished["ignore_term"].last_status();
+        assert_eq!(status.result, ExecutionResult::Timeout);
+        assert!(
+            status.time_taken >= Duration::from_millis(600),
+            "test ignores SIGTERM, so it runs until the grace period is up: {:?}",
+            status.time_taken
+        );
+        assert!(
+            status.time_taken < Duration::from_secs(30),
+            "test is killed once the grace period is up: {:?}",
+            status.time_taken
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn signal_cancels_pending_tests() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        let test_list = make_script_test_list(
+            dir,
+            &[
+                ("a", "exec sleep 60"),
+                ("b", "exec sleep 60"),
+                ("c", "exec sleep 60"),
+            ],
+        );
+        let config = make_config("");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut builder = TestRunnerBuilder::default();
+        builder.set_test_threads(TestThreads::Count(NonZeroUsize::new(1).unwrap()));
+        let runner = builder.build(&test_list, &profile, SignalHandler { receiver });
+
+        // Ctrl-C is pressed once the first test is running.
+        let (stats, finished) = run_tests(&runner, |_| {
+            let _ = sender.send(SignalEvent::Shutdown(ShutdownEvent::Interrupt));
+        });
+        assert_eq!(stats.cancel_reason, Some(CancelReason::Signal));
+        assert_eq!(finished.len(), 1, "only the running test finishes");
+        assert_eq!(stats.final_run_count, 1);
+        assert_eq!(stats.canceled, 2, "tests that hadn't started are canceled");
+        let status = finished.values().next().unwrap().last_status();
+        assert_eq!(status.result, ExecutionResult::Fail);
+        assert!(
+            status.time_taken < Duration::from_secs(30),
+            "the signal is forwarded to the running test: {:?}",
+            status.time_taken
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn cancel_test_through_controller() {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        let test_list = make_script_test_list(dir, &[("a", "exec sleep 60"), ("b", "exit 0")]);
+        let config = make_config(
+            r#"
+            [profile.default]
+            retries = 2
+            fail-fast = false
+            "#,
+        );
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut builder = TestRunnerBuilder::default();
+        builder.set_test_threads(TestThreads::Count(NonZeroUsize::new(1).unwrap()));
+        let controller = builder.controller();
+        let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+
+        let (stats, finished) = run_tests(&runner, |name| {
+            if name == "a" {
+                controller.cancel_test("helper::script", "a");
+            }
+        });
+        assert_eq!(stats.cancel_reason, None, "the run isn't canceled");
+        assert_eq!(stats.final_run_count, 2);
+        assert_eq!(stats.passed, 1);
+        assert_eq!(
+            finished["a"].len(),
+            1,
+            "canceled tests aren't retried: {:?}",
+            finished["a"]
+        );
+        let status = finished["a"].last_status();
+        assert!(!status.result.is_success());
+        assert!(
+            status.time_taken < Duration::from_secs(30),
+            "canceled test is asked to exit: {:?}",
+            status.time_taken
+        );
+        assert_eq!(finished["b"].last_status().result, ExecutionResult::Pass);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn throttle_tick_reschedules() {
+        // The machine is low on memory at first, and has recovered by the next sample.
+        fn sample_load() -> SystemLoad {
+            static SAMPLES: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+            let available_memory = if SAMPLES.fetch_add(1, Ordering::SeqCst) == 0 {
+                0
+            } else {
+                u64::MAX
+            };
+            SystemLoad {
+                load: None,
+                cpus: 1,
+                available_memory: Some(available_memory),
+            }
+        }
+
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        // a only finishes once b has started, so b has to be scheduled while a is running.
+        let test_list = make_script_test_list(
+            dir,
+            &[
+                (
+                    "a",
+                    "for i in $(seq 600); do [ -e b-started ] && exit 0; sleep 0.05; done; exit 1",
+                ),
+                ("b", "touch b-started"),
+            ],
+        );
+        let config = make_config(
+            r#"
+            [profile.default]
+            throttle = { min-available-memory = "1GiB" }
+            "#,
+        );
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut builder = TestRunnerBuilder::default();
+        builder.set_test_threads(TestThreads::Count(NonZeroUsize::new(2).unwrap()));
+        let mut runner = builder.build(&test_list, &profile, SignalHandler::noop());
+        runner.sample_load = sample_load;
+
+        let mut started = vec![];
+        let (stats, _) = run_tests(&runner, |name| started.push(name.to_owned()));
+        assert_eq!(started, ["a", "b"], "one test at a time at first");
+        assert_eq!(stats.passed, 2, "b started while a was running");
+    }
 
     #[test]
     fn no_capture_settings() {
         // Ensure that output settings are ignored with no-capture.
         let mut builder = TestRunnerBuilder::default();
-        builder.set_no_capture(true).set_test_threads(20);
+        builder
+            .set_no_capture(true)
+            .set_test_threads(TestThreads::Count(NonZeroUsize::new(20).unwrap()));
         let test_list = TestList::empty();
         let config = NextestConfig::default_config("/fake/dir");
         let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
         let handler = SignalHandler::noop();
         let runner = builder.build(&test_list, &profile, handler);
         assert!(runner.no_capture, "no_capture is true");
+        assert_eq!(runner.test_threads, 1, "tests run serially");
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn no_capture_setup_script_inherits_output() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        let fds_path = dir.join("fds");
+        // Records what the script's standard output and standard error point to.
+        let script_path = dir.join("setup.sh");
+        std::fs::write(
+            &script_path,
+            format!(
+                "#!/bin/sh\nfds=$(readlink /proc/$$/fd/1 /proc/$$/fd/2)\necho \"$fds\" > {}\n",
+                fds_path
+            ),
+        )
+        .expect("wrote setup script");
+        std::fs::set_permissions(&script_path, std::fs::Permissions::from_mode(0o755))
+            .expect("made setup script executable");
+
+        let test_list = make_script_test_list(dir, &[("a", "exit 0")]);
+        let config = make_config_in(
+            dir,
+            &format!(
+                r#"
+                experimental = ["setup-scripts"]
+
+                [script.fds]
+                setup = "{}"
+                "#,
+                script_path
+            ),
+        );
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let own_fds = format!(
+            "{}\n{}\n",
+            std::fs::read_link("/proc/self/fd/1").unwrap().display(),
+            std::fs::read_link("/proc/self/fd/2").unwrap().display(),
+        );
+
+        let script_fds = |no_capture: bool| {
+            let mut builder = TestRunnerBuilder::default();
+            builder.set_no_capture(no_capture);
+            let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+            let (stats, _) = run_tests(&runner, |_| {});
+            assert_eq!(stats.passed, 1, "setup script succeeded, and the test ran");
+            std::fs::read_to_string(&fds_path).expect("setup script wrote its fds")
+        };
+
         assert_eq!(
-            runner.run_pool.current_num_threads(),
-            2,
-            "tests run serially => run pool has 1 + 1 = 2 threads"
+            script_fds(true),
+            own_fds,
+            "with no-capture, the script's output is passed through"
         );
+        assert_ne!(script_fds(false), own_fds, "otherwise, it's captured");
+    }
+
+    #[test]
+    fn max_fail_settings() {
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+
+        let max_fail = |configure: fn(&mut TestRunnerBuilder<'_>)| {
+            let mut builder = TestRunnerBuilder::default();
+            configure(&mut builder);
+            builder
+                .build(&test_list, &profile, SignalHandler::noop())
+                .max_fail
+        };
+
         assert_eq!(
-            runner.wait_pool.current_num_threads(),
-            1,
-            "tests run serially => wait pool has 1 thread"
+            max_fail(|builder| {
+                builder.set_fail_fast(true);
+            }),
+            Some(1),
+            "fail-fast => max 1 failure"
+        );
+        assert_eq!(
+            max_fail(|builder| {
+                builder.set_fail_fast(false);
+            }),
+            None,
+            "no fail-fast => no maximum"
+        );
+        assert_eq!(
+            max_fail(|builder| {
+                builder
+                    .set_fail_fast(true)
+                    .set_max_fail(NonZeroUsize::new(3).unwrap());
+            }),
+            Some(3),
+            "max-fail overrides fail-fast"
+        );
+    }
+
+    #[test]
+    fn dry_run() {
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let mut builder = TestRunnerBuilder::default();
+        builder
+            .set_test_threads(TestThreads::Count(NonZeroUsize::new(4).unwrap()))
+            .set_shuffle_seed(42);
+        let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+
+        assert!(
+            runner.schedule().is_empty(),
+            "no tests => nothing scheduled"
+        );
+        let mut output = vec![];
+        runner
+            .write_dry_run(&mut output)
+            .expect("writing to a vec succeeds");
+        assert_eq!(
+            String::from_utf8(output).unwrap(),
+            "     Dry run 0 tests across 0 binaries with 4 test threads (shuffled with seed 42)\n"
+        );
+    }
+
+    /// Records the kinds of events it sees, tagged with its name.
+    struct RecordingReporter {
+        name: &'static str,
+        events: Arc<Mutex<Vec<String>>>,
+        fail: bool,
+    }
+
+    impl<'a> Reporter<'a> for RecordingReporter {
+        fn report_event(&mut self, event: &TestEvent<'a>) -> Result<(), WriteEventError> {
+            let kind = match &event.kind {
+                TestEventKind::RunStarted { .. } => "started",
+                TestEventKind::RunFinished { .. } => "finished",
+                _ => "other",
+            };
+            self.events
+                .lock()
+                .unwrap()
+                .push(format!("{} {}", self.name, kind));
+            if self.fail {
+                Err(WriteEventError::Io(std::io::Error::new(
+                    std::io::ErrorKind::Other,
+                    "reporter failed",
+                )))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn reporters_fan_out() {
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let events = Arc::new(Mutex::new(vec![]));
+
+        let mut builder = TestRunnerBuilder::default();
+        for name in ["first", "second"] {
+            builder.add_reporter(RecordingReporter {
+                name,
+                events: events.clone(),
+                fail: false,
+            });
+        }
+        let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+        let mut callback_count = 0;
+        let run_stats = runner
+            .execute(|_| callback_count += 1)
+            .expect("reporters succeeded");
+        assert!(run_stats.is_success(), "empty run => success");
+        assert_eq!(
+            *events.lock().unwrap(),
+            [
+                "first started",
+                "second started",
+                "first finished",
+                "second finished"
+            ],
+            "every reporter sees every event, in the order reporters were added"
+        );
+        assert_eq!(callback_count, 2, "callback sees every event");
+
+        events.lock().unwrap().clear();
+        let mut builder = TestRunnerBuilder::default();
+        builder.add_reporter(RecordingReporter {
+            name: "failing",
+            events: events.clone(),
+            fail: true,
+        });
+        let runner = builder.build(&test_list, &profile, SignalHandler::noop());
+        let mut callback_count = 0;
+        runner
+            .execute(|_| callback_count += 1)
+            .expect_err("reporter error is returned");
+        assert_eq!(
+            *events.lock().unwrap(),
+            ["failing started"],
+            "no more events are sent after an error"
+        );
+        assert_eq!(callback_count, 0, "callback isn't called after an error");
+    }
+
+    #[test]
+    fn events_are_tagged() {
+        let test_list = TestList::empty();
+        let config = NextestConfig::default_config("/fake/dir");
+        let profile = config.profile(NextestConfig::DEFAULT_PROFILE).unwrap();
+        let runner =
+            TestRunnerBuilder::default().build(&test_list, &profile, SignalHandler::noop());
+
+        let mut events = vec![];
+        runner
+            .execute(|event| events.push(event))
+            .expect("no reporters were added");
+        assert_eq!(events.len(), 2, "run started and finished");
+        for event in &events {
+            assert_eq!(event.run_id, runner.run_id(), "event is tagged with run ID");
+        }
+        assert!(
+            events[0].since_start <= events[1].since_start,
+            "offsets don't go backwards"
+        );
+        let started_at = events[1].timestamp - events[1].since_start;
+        assert_eq!(
+            events[0].timestamp - events[0].since_start,
+            started_at,
+            "timestamps are consistent with offsets"
+        );
+        if let TestEventKind::RunFinished { start_time, .. } = events[1].kind {
+            assert_eq!(start_time, started_at, "run started at the same time");
+        } else {
+            panic!("last event is RunFinished");
+        }
+
+        let other_runner =
+            TestRunnerBuilder::default().build(&test_list, &profile, SignalHandler::noop());
+        assert_ne!(
+            runner.run_id(),
+            other_runner.run_id(),
+            "each runner has a unique ID"
         );
     }
 
@@ -887,6 +3968,26 @@ mod tests {
             .is_success(),
             "exec failed => failure"
         );
+        assert!(
+            !RunStats {
+                initial_run_count: 42,
+                final_run_count: 42,
+                timed_out: 1,
+                ..RunStats::default()
+            }
+            .is_success(),
+            "timed out => failure"
+        );
+        assert!(
+            !RunStats {
+                initial_run_count: 42,
+                final_run_count: 42,
+                scripts_failed: 1,
+                ..RunStats::default()
+            }
+            .is_success(),
+            "script failed => failure"
+        );
         assert!(
             RunStats {
                 initial_run_count: 42,
@@ -897,5 +3998,35 @@ mod tests {
             .is_success(),
             "skipped => not considered a failure"
         );
+        assert!(
+            RunStats {
+                initial_run_count: 42,
+                final_run_count: 42,
+                quarantined: 1,
+                ..RunStats::default()
+            }
+            .is_success(),
+            "quarantined failure => not considered a failure"
+        );
+    }
+
+    #[test]
+    fn quarantined_failures() {
+        let failure = ExecutionStatuses::new(vec![ExecuteStatus {
+            attempt: 1,
+            total_attempts: 1,
+            stdout_stderr: Arc::new((vec![], vec![])),
+            result: ExecutionResult::Fail,
+            start_time: SystemTime::now(),
+            time_taken: Duration::ZERO,
+            core_dump: None,
+            output_dir: None,
+        }]);
+        let mut run_stats = RunStats::default();
+        run_stats.on_test_finished(&failure, true);
+        assert_eq!(run_stats.quarantined, 1);
+        assert_eq!(run_stats.failed_count(), 0);
+        run_stats.on_test_finished(&failure, false);
+        assert_eq!(run_stats.failed_count(), 1);
     }
 }
@@ -0,0 +1,196 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Collection of core dumps written by crashing tests.
+//!
+//! Where the kernel writes core dumps to depends on how the system is configured. On Linux,
+//! `/proc/sys/kernel/core_pattern` either names a file, relative to the working directory of the
+//! crashing process, or a program that core dumps are piped to (for example `systemd-coredump`).
+//! Only core dumps written to a file can be collected. On macOS, core dumps are written to
+//! `/cores/core.<pid>`, and on other Unix platforms to `core` in the working directory.
+//!
+//! Collected core dumps are moved into a run-scoped directory, so that they aren't overwritten by
+//! later crashes, and so that they're easy to find after the run.
+
+use crate::{coverage::sanitize, test_list::TestInstance};
+use camino::Utf8PathBuf;
+use std::{fs, io, process::ExitStatus};
+
+/// The directory core dumps written by tests are moved to.
+#[derive(Clone, Debug)]
+pub(super) struct CoreDumpDir {
+    dir: Utf8PathBuf,
+}
+
+impl CoreDumpDir {
+    pub(super) fn new(dir: Utf8PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Moves the core dump written by a test process that just exited, if any, into the directory.
+    ///
+    /// Returns the new path to the core dump. `pid` is the ID of the test process.
+    pub(super) fn collect(
+        &self,
+        test_instance: &TestInstance<'_>,
+        attempt: usize,
+        pid: u32,
+        status: ExitStatus,
+    ) -> io::Result<Option<Utf8PathBuf>> {
+        // Whether a core dump was actually written out is checked below.
+        if !imp::dumps_core(status) {
+            return Ok(None);
+        }
+        // The kernel names core dumps after the name of the executable, truncated to the length
+        // of a thread name.
+        let exe_name = test_instance.binary.file_name().unwrap_or_default();
+        let exe_name = &exe_name[..floor_char_boundary(exe_name, 15)];
+        let (pattern, uses_pid) = imp::core_pattern();
+        let source = match expand_pattern(&pattern, uses_pid, pid, exe_name) {
+            Some(path) => test_instance.bin_info.cwd.join(path),
+            None => return Ok(None),
+        };
+        if !source.is_file() {
+            return Ok(None);
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let dest = self.dir.join(format!(
+            "{}-{}-{}-{}.core",
+            sanitize(&test_instance.bin_info.binary_id),
+            sanitize(test_instance.name),
+            attempt,
+            pid
+        ));
+        if fs::rename(&source, &dest).is_err() {
+            // The core dump may be on a different file system.
+            fs::copy(&source, &dest)?;
+            fs::remove_file(&source)?;
+        }
+        Ok(Some(dest))
+    }
+}
+
+/// Expands a core pattern into the path of a core dump, relative to the working directory of the
+/// process.
+///
+/// Returns `None` if core dumps are piped to a program, or if the pattern uses specifiers whose
+/// values aren't known.
+fn expand_pattern(pattern: &str, uses_pid: bool, pid: u32, exe_name: &str) -> Option<String> {
+    if pattern.is_empty() || pattern.starts_with('|') {
+        return None;
+    }
+    let mut path = String::new();
+    let mut has_pid = false;
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            path.push(c);
+            continue;
+        }
+        match chars.next()? {
+            '%' => path.push('%'),
+            'p' | 'P' => {
+                has_pid = true;
+                path.push_str(&pid.to_string());
+            }
+            'e' => path.push_str(exe_name),
+            _ => return None,
+        }
+    }
+    // With core_uses_pid set, the PID is appended if the pattern doesn't include it.
+    if uses_pid && !has_pid {
+        path.push_str(&format!(".{}", pid));
+    }
+    Some(path)
+}
+
+fn floor_char_boundary(s: &str, index: usize) -> usize {
+    (0..=index.min(s.len()))
+        .rev()
+        .find(|&index| s.is_char_boundary(index))
+        .unwrap_or(0)
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{os::unix::process::ExitStatusExt, process::ExitStatus};
+
+    /// Returns true if the process was killed by a signal that dumps core by default.
+    pub(super) fn dumps_core(status: ExitStatus) -> bool {
+        matches!(
+            status.signal(),
+            Some(
+                libc::SIGABRT
+                    | libc::SIGBUS
+                    | libc::SIGFPE
+                    | libc::SIGILL
+                    | libc::SIGQUIT
+                    | libc::SIGSEGV
+                    | libc::SIGSYS
+                    | libc::SIGTRAP
+                    | libc::SIGXCPU
+                    | libc::SIGXFSZ
+            )
+        )
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    pub(super) fn core_pattern() -> (String, bool) {
+        let read = |name| std::fs::read_to_string(format!("/proc/sys/kernel/{}", name));
+        let pattern = read("core_pattern").unwrap_or_default();
+        let uses_pid = read("core_uses_pid").map_or(false, |value| value.trim() == "1");
+        (pattern.trim_end().to_owned(), uses_pid)
+    }
+
+    #[cfg(target_os = "macos")]
+    pub(super) fn core_pattern() -> (String, bool) {
+        ("/cores/core.%p".to_owned(), false)
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android", target_os = "macos")))]
+    pub(super) fn core_pattern() -> (String, bool) {
+        ("core".to_owned(), false)
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use std::process::ExitStatus;
+
+    pub(super) fn dumps_core(_status: ExitStatus) -> bool {
+        false
+    }
+
+    pub(super) fn core_pattern() -> (String, bool) {
+        (String::new(), false)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn expand_core_pattern() {
+        assert_eq!(
+            expand_pattern("core", false, 42, "t-abc"),
+            Some("core".to_owned())
+        );
+        assert_eq!(
+            expand_pattern("core", true, 42, "t-abc"),
+            Some("core.42".to_owned())
+        );
+        assert_eq!(
+            expand_pattern("/var/cores/%e.%p.100%%", true, 42, "t-abc"),
+            Some("/var/cores/t-abc.42.100%".to_owned())
+        );
+        // Core dumps piped to a program, or named after values that aren't known, can't be found.
+        assert_eq!(
+            expand_pattern("|/usr/lib/systemd/systemd-coredump %P", false, 42, "t"),
+            None
+        );
+        assert_eq!(expand_pattern("core.%t", false, 42, "t"), None);
+        assert_eq!(expand_pattern("core%", false, 42, "t"), None);
+    }
+}
@@ -0,0 +1,269 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Parsing of libtest's JSON output.
+//!
+//! libtest test binaries can write out events as JSON, with `-Z unstable-options --format json`.
+//! This is an unstable option, so it's only accepted if `RUSTC_BOOTSTRAP=1` is set in the
+//! environment of the test binary. Events are written to standard output, one per line:
+//!
+//! ```text
+//! { "type": "suite", "event": "started", "test_count": 1 }
+//! { "type": "test", "event": "started", "name": "tests::foo" }
+//! { "type": "test", "name": "tests::foo", "event": "ok", "exec_time": 0.001 }
+//! { "type": "suite", "event": "ok", "passed": 1, "failed": 0, ... }
+//! ```
+//!
+//! Tests run on their own are run with `--nocapture`, so anything they print is written out along
+//! with the events. Batches of tests are run without it: libtest captures what each test prints,
+//! and includes it in the event for the test once it finishes.
+
+use crate::stopwatch::StopwatchStart;
+use serde::Deserialize;
+use std::time::Duration;
+
+/// The arguments passed to libtest test binaries to get JSON output.
+pub(super) const LIBTEST_JSON_ARGS: &[&str] = &[
+    "-Z",
+    "unstable-options",
+    "--format",
+    "json",
+    "--report-time",
+];
+
+/// The arguments passed to libtest test binaries running a batch of tests, along with
+/// [`LIBTEST_JSON_ARGS`]. Tests are run one at a time, and what passing tests print is included in
+/// their events.
+pub(super) const LIBTEST_BATCH_ARGS: &[&str] = &["--test-threads", "1", "--show-output"];
+
+/// The environment variable that lets libtest accept unstable options on stable Rust.
+pub(super) const LIBTEST_JSON_ENV: (&str, &str) = ("RUSTC_BOOTSTRAP", "1");
+
+/// An event for a single test, parsed out of libtest's JSON output.
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct LibtestEvent {
+    pub(super) name: String,
+    pub(super) kind: LibtestEventKind,
+    /// The time the test took to run, as measured by libtest. Only set once the test has finished.
+    pub(super) exec_time: Option<Duration>,
+    /// The output captured by libtest while the test ran, if any.
+    pub(super) stdout: Option<String>,
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) enum LibtestEventKind {
+    Started,
+    Ok,
+    Failed,
+    Ignored,
+    Timeout,
+}
+
+/// Standard output of a libtest binary, split into test events and everything else.
+#[derive(Clone, Debug, Default)]
+pub(super) struct LibtestOutput {
+    pub(super) events: Vec<LibtestEvent>,
+    /// Standard output with the JSON lines removed.
+    pub(super) stdout: Vec<u8>,
+}
+
+impl LibtestOutput {
+    /// Splits out the events in standard output.
+    pub(super) fn parse(stdout: &[u8]) -> Self {
+        let mut output = Self::default();
+        for line in stdout.split_inclusive(|&b| b == b'\n') {
+            match parse_line(line) {
+                Some((prefix, message)) => {
+                    // A test that printed something without a trailing newline leaves it at the
+                    // start of the line.
+                    output.stdout.extend_from_slice(prefix);
+                    if let Some(event) = message.into_event() {
+                        output.events.push(event);
+                    }
+                }
+                None => output.stdout.extend_from_slice(line),
+            }
+        }
+        output
+    }
+
+    /// Returns the time libtest measured for the test with the given name, if it finished.
+    pub(super) fn exec_time(&self, name: &str) -> Option<Duration> {
+        self.result(name).and_then(|event| event.exec_time)
+    }
+
+    /// Returns the event for the test with the given name passing or failing, if it finished.
+    pub(super) fn result(&self, name: &str) -> Option<&LibtestEvent> {
+        self.events.iter().rev().find(|event| {
+            event.name == name
+                && matches!(event.kind, LibtestEventKind::Ok | LibtestEventKind::Failed)
+        })
+    }
+
+    /// Returns true if the test with the given name started.
+    pub(super) fn started(&self, name: &str) -> bool {
+        self.events
+            .iter()
+            .any(|event| event.name == name && event.kind == LibtestEventKind::Started)
+    }
+}
+
+/// Events parsed out of the output of a libtest binary while it's still running.
+///
+/// Output is pushed in as it's read, and complete lines are parsed as they come in. Each event is
+/// returned along with the time it was read at, which is when a test started for `started` events.
+#[derive(Debug, Default)]
+pub(super) struct LibtestProgress {
+    partial_line: Vec<u8>,
+    events: Vec<(LibtestEvent, StopwatchStart)>,
+}
+
+impl LibtestProgress {
+    /// Parses the events in the newly read output.
+    pub(super) fn push(&mut self, mut bytes: &[u8]) {
+        while let Some(end) = bytes.iter().position(|&b| b == b'\n') {
+            self.partial_line.extend_from_slice(&bytes[..=end]);
+            bytes = &bytes[end + 1..];
+            if let Some(event) =
+                parse_line(&self.partial_line).and_then(|(_, message)| message.into_event())
+            {
+                self.events.push((event, StopwatchStart::now()));
+            }
+            self.partial_line.clear();
+        }
+        self.partial_line.extend_from_slice(bytes);
+    }
+
+    /// Returns the events parsed since the last call.
+    pub(super) fn take_events(&mut self) -> Vec<(LibtestEvent, StopwatchStart)> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawMessage {
+    #[serde(rename = "type")]
+    kind: String,
+    event: String,
+    #[serde(default)]
+    name: Option<String>,
+    #[serde(default)]
+    exec_time: Option<f64>,
+    #[serde(default)]
+    stdout: Option<String>,
+}
+
+impl RawMessage {
+    fn into_event(self) -> Option<LibtestEvent> {
+        if self.kind != "test" {
+            return None;
+        }
+        let kind = match self.event.as_str() {
+            "started" => LibtestEventKind::Started,
+            "ok" => LibtestEventKind::Ok,
+            "failed" => LibtestEventKind::Failed,
+            "ignored" => LibtestEventKind::Ignored,
+            "timeout" => LibtestEventKind::Timeout,
+            _ => return None,
+        };
+        Some(LibtestEvent {
+            name: self.name?,
+            kind,
+            exec_time: self
+                .exec_time
+                .filter(|secs| secs.is_finite() && *secs >= 0.0)
+                .map(Duration::from_secs_f64),
+            stdout: self.stdout,
+        })
+    }
+}
+
+/// The start of each message written out by libtest. Within strings in messages, quotes are
+/// escaped, so this only appears at the start of a message.
+const MESSAGE_START: &[u8] = b"{ \"type\": \"";
+
+/// Parses a JSON message at the end of the line, returning whatever came before it.
+fn parse_line(line: &[u8]) -> Option<(&[u8], RawMessage)> {
+    let trimmed = line.strip_suffix(b"\n").unwrap_or(line);
+    let trimmed = trimmed.strip_suffix(b"\r").unwrap_or(trimmed);
+    if !trimmed.ends_with(b"}") {
+        return None;
+    }
+    // The output of a test may itself contain something that looks like the start of a message,
+    // so try each one in turn.
+    (0..trimmed.len())
+        .filter(|&start| trimmed[start..].starts_with(MESSAGE_START))
+        .find_map(|start| {
+            let message: RawMessage = serde_json::from_slice(&trimmed[start..]).ok()?;
+            matches!(message.kind.as_str(), "suite" | "test").then(|| (&trimmed[..start], message))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_libtest_output() {
+        let stdout = b"{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 1 }\n\
+            { \"type\": \"test\", \"event\": \"started\", \"name\": \"tests::foo\" }\n\
+            printed by the test\n\
+            no newline{ \"type\": \"test\", \"name\": \"tests::foo\", \"event\": \"failed\", \"exec_time\": 0.25 }\n\
+            { \"type\": \"suite\", \"event\": \"failed\", \"passed\": 0, \"failed\": 1, \"exec_time\": 0.3 }\n\
+            {\"not\": \"a message\"}\n";
+        let output = LibtestOutput::parse(stdout);
+        assert_eq!(
+            output.events,
+            vec![
+                LibtestEvent {
+                    name: "tests::foo".to_owned(),
+                    kind: LibtestEventKind::Started,
+                    exec_time: None,
+                    stdout: None,
+                },
+                LibtestEvent {
+                    name: "tests::foo".to_owned(),
+                    kind: LibtestEventKind::Failed,
+                    exec_time: Some(Duration::from_millis(250)),
+                    stdout: None,
+                },
+            ]
+        );
+        assert_eq!(
+            output.exec_time("tests::foo"),
+            Some(Duration::from_millis(250))
+        );
+        assert_eq!(output.exec_time("tests::bar"), None);
+        assert_eq!(
+            String::from_utf8(output.stdout).expect("output is valid UTF-8"),
+            "printed by the test\nno newline{\"not\": \"a message\"}\n"
+        );
+    }
+
+    #[test]
+    fn progress() {
+        let mut progress = LibtestProgress::default();
+        progress.push(
+            b"{ \"type\": \"suite\", \"event\": \"started\", \"test_count\": 2 }\n{ \"type\": ",
+        );
+        progress.push(b"\"test\", \"event\": \"started\", \"name\": \"a\" }\n");
+        let events: Vec<_> = progress
+            .take_events()
+            .into_iter()
+            .map(|(event, _)| event)
+            .collect();
+        assert_eq!(events.len(), 1);
+        assert_eq!(
+            (events[0].name.as_str(), events[0].kind),
+            ("a", LibtestEventKind::Started)
+        );
+
+        progress.push(b"{ \"type\": \"test\", \"name\": \"a\", \"event\": \"ok\", \"exec_time\": 0.5, \"stdout\": \"{ \\\"x\\\": 1 }\\n\" }\n");
+        let events = progress.take_events();
+        assert_eq!(
+            (events[0].0.kind, events[0].0.stdout.as_deref()),
+            (LibtestEventKind::Ok, Some("{ \"x\": 1 }\n"))
+        );
+        assert!(progress.take_events().is_empty());
+    }
+}
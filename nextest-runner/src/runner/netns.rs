@@ -0,0 +1,237 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Network isolation for tests.
+//!
+//! On Linux, each test process can be run in a network namespace of its own. The only network
+//! interface in a new namespace is the loopback interface, so tests can still talk to servers they
+//! start themselves, but any attempt to reach another host fails straight away.
+//!
+//! Creating a network namespace requires `CAP_SYS_ADMIN`. Without it, a user namespace is created
+//! first, which is possible for unprivileged users on most Linux distributions. In the user
+//! namespace, the test's user and group IDs are mapped to themselves, so the test sees the same
+//! IDs it would otherwise.
+
+use crate::errors::NetworkIsolationError;
+
+/// Checks whether test processes can be isolated from the network.
+pub(super) fn check_available() -> Result<(), NetworkIsolationError> {
+    imp::check_available()
+}
+
+/// Configures the command for a test to run in a new network namespace.
+pub(super) fn isolate(cmd: duct::Expression) -> duct::Expression {
+    imp::isolate(cmd)
+}
+
+#[cfg(target_os = "linux")]
+mod imp {
+    use crate::errors::NetworkIsolationError;
+    use std::{
+        ffi::CStr,
+        io,
+        os::{
+            raw::{c_char, c_int, c_short},
+            unix::process::CommandExt,
+        },
+        sync::Arc,
+    };
+
+    pub(super) fn check_available() -> Result<(), NetworkIsolationError> {
+        let ids = IdMaps::current();
+        // Creating a user namespace requires the calling process to be single-threaded, so the
+        // check is done in a child process.
+        // SAFETY: only async-signal-safe functions are called in the child process, which exits
+        // without returning.
+        unsafe {
+            match libc::fork() {
+                -1 => Err(NetworkIsolationError::Setup(io::Error::last_os_error())),
+                0 => {
+                    let code = match enter_namespace(&ids) {
+                        Ok(()) => 0,
+                        Err(error) => error.raw_os_error().unwrap_or(libc::EINVAL),
+                    };
+                    libc::_exit(code)
+                }
+                pid => {
+                    let mut status = 0;
+                    while libc::waitpid(pid, &mut status, 0) == -1 {
+                        let error = io::Error::last_os_error();
+                        if error.kind() != io::ErrorKind::Interrupted {
+                            return Err(NetworkIsolationError::Setup(error));
+                        }
+                    }
+                    match (libc::WIFEXITED(status), libc::WEXITSTATUS(status)) {
+                        (true, 0) => Ok(()),
+                        (true, code) => Err(NetworkIsolationError::Setup(
+                            io::Error::from_raw_os_error(code),
+                        )),
+                        (false, _) => Err(NetworkIsolationError::Setup(io::Error::new(
+                            io::ErrorKind::Other,
+                            "child process was killed",
+                        ))),
+                    }
+                }
+            }
+        }
+    }
+
+    pub(super) fn isolate(cmd: duct::Expression) -> duct::Expression {
+        // The ID maps are formatted ahead of time, since allocating between fork and exec isn't
+        // safe.
+        let ids = Arc::new(IdMaps::current());
+        cmd.before_spawn(move |cmd| {
+            let ids = ids.clone();
+            // SAFETY: enter_namespace only calls async-signal-safe functions.
+            unsafe {
+                cmd.pre_exec(move || enter_namespace(&ids));
+            }
+            Ok(())
+        })
+    }
+
+    /// The contents of the user and group ID maps for a new user namespace, mapping the current
+    /// IDs to themselves.
+    #[derive(Debug)]
+    struct IdMaps {
+        is_root: bool,
+        uid_map: String,
+        gid_map: String,
+    }
+
+    impl IdMaps {
+        fn current() -> Self {
+            // SAFETY: these functions have no memory safety requirements, and always succeed.
+            let (euid, egid) = unsafe { (libc::geteuid(), libc::getegid()) };
+            Self {
+                is_root: euid == 0,
+                uid_map: format!("{0} {0} 1", euid),
+                gid_map: format!("{0} {0} 1", egid),
+            }
+        }
+    }
+
+    /// Moves the current process into a new network namespace, and brings up the loopback
+    /// interface within it.
+    fn enter_namespace(ids: &IdMaps) -> io::Result<()> {
+        // SAFETY: unshare has no memory safety requirements.
+        unsafe {
+            if ids.is_root {
+                check(libc::unshare(libc::CLONE_NEWNET))?;
+            } else {
+                check(libc::unshare(libc::CLONE_NEWUSER | libc::CLONE_NEWNET))?;
+                // setgroups must be disabled before an unprivileged process can write a gid map.
+                write_file(cstr(b"/proc/self/setgroups\0"), b"deny")?;
+                write_file(cstr(b"/proc/self/uid_map\0"), ids.uid_map.as_bytes())?;
+                write_file(cstr(b"/proc/self/gid_map\0"), ids.gid_map.as_bytes())?;
+            }
+        }
+        bring_up_loopback()
+    }
+
+    /// The subset of `struct ifreq` used to get and set interface flags. (`libc::ifreq` isn't
+    /// available in older versions of libc.)
+    #[repr(C)]
+    struct IfreqFlags {
+        name: [c_char; libc::IFNAMSIZ],
+        flags: c_short,
+        // The rest of the union in struct ifreq, which is at most 24 bytes long. The kernel only
+        // reads and writes sizeof(struct ifreq) bytes, so extra padding is harmless.
+        _padding: [u8; 22],
+    }
+
+    fn bring_up_loopback() -> io::Result<()> {
+        // SAFETY: the ifreq struct passed to ioctl is valid for the requests made, and the socket
+        // is closed before returning.
+        unsafe {
+            let fd = libc::socket(libc::AF_INET, libc::SOCK_DGRAM | libc::SOCK_CLOEXEC, 0);
+            if fd == -1 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut req = IfreqFlags {
+                name: [0; libc::IFNAMSIZ],
+                flags: 0,
+                _padding: [0; 22],
+            };
+            for (dest, src) in req.name.iter_mut().zip(b"lo") {
+                *dest = *src as c_char;
+            }
+            let res = check(libc::ioctl(fd, libc::SIOCGIFFLAGS as _, &mut req)).and_then(|()| {
+                req.flags |= libc::IFF_UP as c_short;
+                check(libc::ioctl(fd, libc::SIOCSIFFLAGS as _, &req))
+            });
+            libc::close(fd);
+            res
+        }
+    }
+
+    unsafe fn write_file(path: &CStr, contents: &[u8]) -> io::Result<()> {
+        let fd = libc::open(path.as_ptr(), libc::O_WRONLY | libc::O_CLOEXEC);
+        if fd == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        let written = libc::write(fd, contents.as_ptr().cast(), contents.len());
+        let res = if written < 0 {
+            Err(io::Error::last_os_error())
+        } else if written as usize != contents.len() {
+            // The ID map files must be written in a single call.
+            Err(io::Error::from_raw_os_error(libc::EIO))
+        } else {
+            Ok(())
+        };
+        libc::close(fd);
+        res
+    }
+
+    fn cstr(bytes: &'static [u8]) -> &'static CStr {
+        CStr::from_bytes_with_nul(bytes).expect("string is nul-terminated")
+    }
+
+    fn check(ret: c_int) -> io::Result<()> {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+mod imp {
+    use crate::errors::NetworkIsolationError;
+
+    pub(super) fn check_available() -> Result<(), NetworkIsolationError> {
+        Err(NetworkIsolationError::Unsupported)
+    }
+
+    pub(super) fn isolate(cmd: duct::Expression) -> duct::Expression {
+        cmd
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_loopback_available() {
+        if let Err(err) = check_available() {
+            // Namespaces may not be available in this environment.
+            eprintln!("skipping test: {}", err);
+            return;
+        }
+
+        let output = isolate(duct::cmd!("cat", "/proc/self/net/dev"))
+            .read()
+            .expect("isolated command ran");
+        let interfaces: Vec<_> = output
+            .lines()
+            .filter_map(|line| Some(line.split_once(':')?.0.trim()))
+            .collect();
+        assert_eq!(
+            interfaces,
+            ["lo"],
+            "only the loopback interface is available"
+        );
+    }
+}
@@ -0,0 +1,333 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Capturing the output of tests.
+//!
+//! Standard output and standard error are each read from a pipe, while the runner waits for the
+//! test to exit. On Unix, the pipes are non-blocking and read from the runner's async runtime, so
+//! running tests don't need threads of their own. On other platforms, each pipe is read from a
+//! blocking thread of the runtime. Along with the output itself, the time at which the test last
+//! wrote anything is kept track of, so that tests that have gone silent can be reported while
+//! they're still running.
+//!
+//! For batches of tests, standard output is also parsed as it's read, to find out which test in the
+//! batch is running.
+//!
+//! Output is drained as it's written, so tests never block on a full pipe. If a limit is set and a
+//! stream goes over it, only its beginning and end are kept, with a marker saying how much was left
+//! out in between. This keeps memory use bounded even for tests that write gigabytes of logs. The
+//! runner doesn't set a limit on output that's parsed as JSON, since cutting it short would lose
+//! events.
+
+use super::libtest_json::LibtestProgress;
+use std::{
+    collections::VecDeque,
+    convert::TryFrom,
+    io,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+/// The output of a single attempt of a test, as it's being captured.
+#[derive(Debug)]
+pub(super) struct OutputCapture {
+    stdout: imp::Stream,
+    stderr: imp::Stream,
+    last_output: Arc<Mutex<Instant>>,
+}
+
+impl OutputCapture {
+    /// Redirects standard output and standard error for the command to pipes, to be read from with
+    /// [`Self::read`].
+    ///
+    /// If `stdout_limit` or `stderr_limit` is set, at most that many bytes are kept from the
+    /// stream. If `progress` is set, standard output is pushed into it as it's read.
+    ///
+    /// The returned command must be dropped once the test has been started: until then, it holds
+    /// the write ends of the pipes open, and reading never finishes. Must be called from within
+    /// the runner's runtime.
+    pub(super) fn configure(
+        cmd: duct::Expression,
+        stdout_limit: Option<u64>,
+        stderr_limit: Option<u64>,
+        progress: Option<Arc<Mutex<LibtestProgress>>>,
+    ) -> io::Result<(duct::Expression, Self)> {
+        let (stdout_reader, stdout_writer) = os_pipe::pipe()?;
+        let (stderr_reader, stderr_writer) = os_pipe::pipe()?;
+        let cmd = cmd.stdout_file(stdout_writer).stderr_file(stderr_writer);
+
+        let last_output = Arc::new(Mutex::new(Instant::now()));
+        let capture = Self {
+            stdout: imp::Stream::new(
+                stdout_reader,
+                StreamState {
+                    output: BoundedOutput::new(stdout_limit),
+                    last_output: last_output.clone(),
+                    progress,
+                },
+            )?,
+            stderr: imp::Stream::new(
+                stderr_reader,
+                StreamState {
+                    output: BoundedOutput::new(stderr_limit),
+                    last_output: last_output.clone(),
+                    progress: None,
+                },
+            )?,
+            last_output,
+        };
+        Ok((cmd, capture))
+    }
+
+    /// Returns the time at which the test last produced output, or the time at which capturing
+    /// started if it hasn't produced any.
+    pub(super) fn last_output(&self) -> Instant {
+        *self.last_output.lock().expect("lock wasn't poisoned")
+    }
+
+    /// Reads standard output and standard error until the test closes them, and returns everything
+    /// written to them.
+    ///
+    /// Must only be called once.
+    pub(super) async fn read(&self) -> io::Result<(Vec<u8>, Vec<u8>)> {
+        let (stdout, stderr) = tokio::join!(self.stdout.read(), self.stderr.read());
+        Ok((stdout?, stderr?))
+    }
+}
+
+/// A stream being read, along with what's been read from it so far.
+#[derive(Debug)]
+struct StreamState {
+    output: BoundedOutput,
+    last_output: Arc<Mutex<Instant>>,
+    progress: Option<Arc<Mutex<LibtestProgress>>>,
+}
+
+impl StreamState {
+    fn push(&mut self, bytes: &[u8]) {
+        self.output.push(bytes);
+        if let Some(progress) = &self.progress {
+            progress.lock().expect("lock wasn't poisoned").push(bytes);
+        }
+        *self.last_output.lock().expect("lock wasn't poisoned") = Instant::now();
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::StreamState;
+    use std::{
+        io::{self, Read},
+        os::unix::io::AsRawFd,
+        sync::Mutex,
+    };
+    use tokio::io::unix::AsyncFd;
+
+    /// A pipe that's read from without blocking, whenever the runtime sees that it's readable.
+    #[derive(Debug)]
+    pub(super) struct Stream {
+        reader: AsyncFd<os_pipe::PipeReader>,
+        state: Mutex<Option<StreamState>>,
+    }
+
+    impl Stream {
+        pub(super) fn new(reader: os_pipe::PipeReader, state: StreamState) -> io::Result<Self> {
+            set_nonblocking(&reader)?;
+            Ok(Self {
+                reader: AsyncFd::new(reader)?,
+                state: Mutex::new(Some(state)),
+            })
+        }
+
+        pub(super) async fn read(&self) -> io::Result<Vec<u8>> {
+            let mut state = self
+                .state
+                .lock()
+                .expect("lock wasn't poisoned")
+                .take()
+                .expect("streams are only read once");
+            let mut buf = [0; 8192];
+            loop {
+                let mut guard = self.reader.readable().await?;
+                match guard.try_io(|reader| reader.get_ref().read(&mut buf)) {
+                    Ok(Ok(0)) => return Ok(state.output.finish()),
+                    Ok(Ok(n)) => state.push(&buf[..n]),
+                    Ok(Err(error)) if error.kind() == io::ErrorKind::Interrupted => {}
+                    Ok(Err(error)) => return Err(error),
+                    // The pipe was drained: wait for the test to write to it again.
+                    Err(_would_block) => {}
+                }
+            }
+        }
+    }
+
+    fn set_nonblocking(reader: &os_pipe::PipeReader) -> io::Result<()> {
+        let fd = reader.as_raw_fd();
+        // SAFETY: fcntl with F_GETFL and F_SETFL has no memory safety requirements. The flag only
+        // applies to nextest's end of the pipe, not to the end the test writes to.
+        unsafe {
+            let flags = libc::fcntl(fd, libc::F_GETFL);
+            if flags == -1 || libc::fcntl(fd, libc::F_SETFL, flags | libc::O_NONBLOCK) == -1 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::StreamState;
+    use std::{
+        io::{self, Read},
+        sync::Mutex,
+    };
+    use tokio::task::JoinHandle;
+
+    /// A pipe that's read from on a blocking thread, since anonymous pipes can't be waited on by
+    /// the runtime on this platform.
+    #[derive(Debug)]
+    pub(super) struct Stream {
+        handle: Mutex<Option<JoinHandle<io::Result<Vec<u8>>>>>,
+    }
+
+    impl Stream {
+        pub(super) fn new(
+            mut reader: os_pipe::PipeReader,
+            mut state: StreamState,
+        ) -> io::Result<Self> {
+            let handle = tokio::task::spawn_blocking(move || {
+                let mut buf = [0; 8192];
+                loop {
+                    match reader.read(&mut buf) {
+                        Ok(0) => return Ok(state.output.finish()),
+                        Ok(n) => state.push(&buf[..n]),
+                        Err(error) if error.kind() == io::ErrorKind::Interrupted => {}
+                        Err(error) => return Err(error),
+                    }
+                }
+            });
+            Ok(Self {
+                handle: Mutex::new(Some(handle)),
+            })
+        }
+
+        pub(super) async fn read(&self) -> io::Result<Vec<u8>> {
+            let handle = self
+                .handle
+                .lock()
+                .expect("lock wasn't poisoned")
+                .take()
+                .expect("streams are only read once");
+            handle.await.expect("output readers don't panic")
+        }
+    }
+}
+
+/// The output of a stream, limited in size.
+///
+/// Once the limit is reached, the first half of the limit is kept as is, and the rest of the
+/// output goes through a buffer holding the last half.
+#[derive(Debug)]
+struct BoundedOutput {
+    head: Vec<u8>,
+    head_limit: usize,
+    tail: VecDeque<u8>,
+    tail_limit: usize,
+    omitted: u64,
+}
+
+impl BoundedOutput {
+    fn new(limit: Option<u64>) -> Self {
+        let limit = limit.map_or(usize::MAX, |limit| {
+            usize::try_from(limit).unwrap_or(usize::MAX)
+        });
+        let head_limit = limit / 2 + limit % 2;
+        Self {
+            head: vec![],
+            head_limit,
+            tail: VecDeque::new(),
+            tail_limit: limit - head_limit,
+            omitted: 0,
+        }
+    }
+
+    fn push(&mut self, mut bytes: &[u8]) {
+        if self.head.len() < self.head_limit {
+            let n = bytes.len().min(self.head_limit - self.head.len());
+            self.head.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+        }
+
+        self.tail.extend(bytes);
+        if self.tail.len() > self.tail_limit {
+            let excess = self.tail.len() - self.tail_limit;
+            self.tail.drain(..excess);
+            self.omitted += excess as u64;
+        }
+    }
+
+    fn finish(self) -> Vec<u8> {
+        let mut output = self.head;
+        if self.omitted > 0 {
+            output.extend_from_slice(
+                format!("\n[nextest: {} bytes of output omitted]\n", self.omitted).as_bytes(),
+            );
+        }
+        output.extend(self.tail);
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn captures_output() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .expect("runtime built");
+        let (stdout, stderr) = runtime.block_on(async {
+            // Enough output to fill the pipes, so that reading has to keep up with the test.
+            let cmd = duct::cmd!(
+                "sh",
+                "-c",
+                "echo out; echo err >&2; head -c 1000000 /dev/zero"
+            );
+            let (cmd, capture) =
+                OutputCapture::configure(cmd, None, None, None).expect("pipes created");
+            let handle = cmd.start().expect("command started");
+            drop(cmd);
+            let output = capture.read().await.expect("output read");
+            handle.wait().expect("command finished");
+            output
+        });
+        assert_eq!(&stdout[..4], b"out\n");
+        assert_eq!(stdout.len(), 4 + 1_000_000);
+        assert_eq!(stderr, b"err\n");
+    }
+
+    #[test]
+    fn bounded_output() {
+        let mut output = BoundedOutput::new(Some(8));
+        output.push(b"abc");
+        output.push(b"defg");
+        assert_eq!(
+            output.finish(),
+            b"abcdefg",
+            "output under the limit is kept"
+        );
+
+        let mut output = BoundedOutput::new(Some(8));
+        for chunk in [&b"abcdef"[..], b"ghijklmn", b"op"] {
+            output.push(chunk);
+        }
+        assert_eq!(
+            String::from_utf8(output.finish()).unwrap(),
+            "abcd\n[nextest: 8 bytes of output omitted]\nmnop"
+        );
+    }
+}
@@ -0,0 +1,102 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Output directories for tests.
+//!
+//! Tests that produce artifacts, such as screenshots or logs, can write them to the directory that
+//! `NEXTEST_TEST_OUTPUT_DIR` points to. Each attempt of each test gets a directory of its own within
+//! a run-scoped directory. Unlike [temporary directories](super::tmpdir), output directories that
+//! are kept are reported along with the result of the test.
+
+use super::tmpdir::create_test_dir;
+use crate::{config::RetainTmpdir, test_list::TestInstance};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::io;
+
+/// The environment variable that points to the output directory for a test.
+pub(super) const TEST_OUTPUT_DIR_ENV: &str = "NEXTEST_TEST_OUTPUT_DIR";
+
+/// The output directory for a single attempt of a test.
+#[derive(Debug)]
+pub(super) struct TestOutputDir {
+    path: Utf8PathBuf,
+}
+
+impl TestOutputDir {
+    /// Creates a new output directory for the test within `run_dir`, creating `run_dir` if
+    /// necessary.
+    pub(super) fn create(
+        run_dir: &Utf8Path,
+        test_instance: &TestInstance<'_>,
+        attempt: usize,
+    ) -> io::Result<Self> {
+        let path = create_test_dir(run_dir, test_instance, attempt)?;
+        Ok(Self { path })
+    }
+
+    /// Returns the environment variable to set for the test.
+    pub(super) fn env(&self) -> (String, String) {
+        (TEST_OUTPUT_DIR_ENV.to_owned(), self.path.to_string())
+    }
+
+    /// Removes the directory once the test has finished, unless it should be retained. Empty
+    /// directories are always removed, since there's nothing in them to report.
+    ///
+    /// Returns the path to the directory if it was retained.
+    pub(super) fn finish(self, success: bool, retain: RetainTmpdir) -> Option<Utf8PathBuf> {
+        let keep = match retain {
+            RetainTmpdir::Never => false,
+            RetainTmpdir::OnFailure => !success,
+            RetainTmpdir::Always => true,
+        };
+        let is_empty = std::fs::read_dir(&self.path).map_or(true, |mut dir| dir.next().is_none());
+        if keep && !is_empty {
+            Some(self.path)
+        } else {
+            // Failing to clean up the directory doesn't change the result of the test.
+            let _ = std::fs::remove_dir_all(&self.path);
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn retain_non_empty() {
+        let run_dir = tempfile::tempdir().expect("created temp dir");
+        let run_dir = Utf8Path::from_path(run_dir.path()).expect("temp dir is valid UTF-8");
+        let mut count = 0;
+        let mut make_dir = |contents: Option<&str>| {
+            count += 1;
+            let path = run_dir.join(format!("test-{}", count));
+            std::fs::create_dir(&path).expect("created test output dir");
+            if let Some(contents) = contents {
+                std::fs::write(path.join("log.txt"), contents).expect("wrote artifact");
+            }
+            TestOutputDir { path }
+        };
+
+        let failed = make_dir(Some("failure details"));
+        let failed_path = failed.path.clone();
+        assert_eq!(
+            failed.finish(false, RetainTmpdir::OnFailure),
+            Some(failed_path.clone())
+        );
+        assert!(failed_path.exists(), "directory for failing test retained");
+
+        let passed = make_dir(Some("all good"));
+        let passed_path = passed.path.clone();
+        assert_eq!(passed.finish(true, RetainTmpdir::OnFailure), None);
+        assert!(!passed_path.exists(), "directory for passing test removed");
+
+        let empty = make_dir(None);
+        let empty_path = empty.path.clone();
+        assert_eq!(empty.finish(false, RetainTmpdir::Always), None);
+        assert!(!empty_path.exists(), "empty directory removed");
+
+        assert_eq!(make_dir(None).env().0, "NEXTEST_TEST_OUTPUT_DIR");
+    }
+}
@@ -0,0 +1,208 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Extraction of panic messages and backtraces from the output of tests.
+//!
+//! The standard library's panic hook writes a panic out to standard error as:
+//!
+//! ```text
+//! thread 'tests::foo' panicked at src/lib.rs:10:5:
+//! assertion `left == right` failed
+//!   left: 1
+//!  right: 2
+//! stack backtrace:
+//!    0: rust_begin_unwind
+//!              at /rustc/.../library/std/src/panicking.rs:645:5
+//!    ...
+//! note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.
+//! ```
+//!
+//! Versions of Rust before 1.73 write the message on the first line instead, as
+//! `thread 'tests::foo' panicked at 'message', src/lib.rs:10:5`. Both forms are understood.
+
+/// A panic in a test, parsed out of its output.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TestPanic {
+    /// The name of the thread that panicked.
+    pub thread: String,
+    /// The panic message. May span several lines.
+    pub message: String,
+    /// The location of the panic in source code, as `file:line:column`.
+    pub location: Option<String>,
+    /// The backtrace written out for the panic, if backtraces were enabled.
+    pub backtrace: Option<String>,
+}
+
+impl TestPanic {
+    /// Parses the first panic out of the output of a test, if there is one.
+    pub fn parse(output: &[u8]) -> Option<Self> {
+        let output = String::from_utf8_lossy(output);
+        let mut lines = output.lines().peekable();
+        while let Some(line) = lines.next() {
+            let (thread, rest) = match split_thread(line) {
+                Some(split) => split,
+                None => continue,
+            };
+
+            let mut message = vec![];
+            let location = if let Some(rest) = rest.strip_prefix('\'') {
+                // The message is followed by the location, on the same line as its end.
+                let mut line = rest;
+                loop {
+                    if let Some((last, location)) = split_location(line) {
+                        message.push(last);
+                        break Some(location.to_owned());
+                    }
+                    message.push(line);
+                    match lines.next() {
+                        Some(next) => line = next,
+                        // The output ended partway through the message.
+                        None => break None,
+                    }
+                }
+            } else {
+                // The message is on the lines after the location.
+                while let Some(line) = lines.peek() {
+                    if is_message_end(line) {
+                        break;
+                    }
+                    message.push(line);
+                    lines.next();
+                }
+                let location = rest.strip_suffix(':').unwrap_or(rest);
+                is_location(location).then(|| location.to_owned())
+            };
+            while message.last().map_or(false, |line| line.is_empty()) {
+                message.pop();
+            }
+
+            let mut backtrace = None;
+            if lines.peek().map(|line| line.trim_end()) == Some("stack backtrace:") {
+                lines.next();
+                let mut frames = vec![];
+                while let Some(line) = lines.peek() {
+                    if !line.starts_with(char::is_whitespace) {
+                        break;
+                    }
+                    frames.push(*line);
+                    lines.next();
+                }
+                backtrace = Some(frames.join("\n"));
+            }
+
+            return Some(Self {
+                thread: thread.to_owned(),
+                message: message.join("\n"),
+                location,
+                backtrace,
+            });
+        }
+        None
+    }
+}
+
+/// Splits the first line of a panic into the name of the thread and the text after
+/// `panicked at `. Recent versions of Rust also write out the ID of the thread after its name.
+fn split_thread(line: &str) -> Option<(&str, &str)> {
+    let start = line.find("thread '")? + "thread '".len();
+    let (thread, rest) = line[start..].split_once("' ")?;
+    let rest = match rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(") "))
+    {
+        Some((id, rest)) if id.bytes().all(|b| b.is_ascii_digit()) => rest,
+        _ => rest,
+    };
+    Some((thread, rest.strip_prefix("panicked at ")?))
+}
+
+/// Returns true if the line comes after the message of a panic.
+fn is_message_end(line: &str) -> bool {
+    line.trim_end() == "stack backtrace:" || line.starts_with("note: run with `RUST_BACKTRACE")
+}
+
+/// Splits a line ending with `', file:line:column` into the text before it and the location.
+fn split_location(line: &str) -> Option<(&str, &str)> {
+    let (before, location) = line.trim_end().rsplit_once("', ")?;
+    is_location(location).then(|| (before, location))
+}
+
+/// Returns true if the text has the form `file:line:column`.
+fn is_location(text: &str) -> bool {
+    let mut parts = text.rsplitn(3, ':');
+    let is_number = |part: Option<&str>| {
+        part.map_or(false, |part| {
+            !part.is_empty() && part.bytes().all(|b| b.is_ascii_digit())
+        })
+    };
+    is_number(parts.next())
+        && is_number(parts.next())
+        && parts.next().map_or(false, |file| !file.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_panic() {
+        let output = b"running 1 test\n\
+            thread 'tests::foo' panicked at src/lib.rs:10:5:\n\
+            assertion `left == right` failed\n  left: 1\n right: 2\n\
+            note: run with `RUST_BACKTRACE=1` environment variable to display a backtrace\n";
+        assert_eq!(
+            TestPanic::parse(output),
+            Some(TestPanic {
+                thread: "tests::foo".to_owned(),
+                message: "assertion `left == right` failed\n  left: 1\n right: 2".to_owned(),
+                location: Some("src/lib.rs:10:5".to_owned()),
+                backtrace: None,
+            })
+        );
+
+        let output = b"thread '<unnamed>' (1234) panicked at tests/basic.rs:3:5:\nboom\n\
+            stack backtrace:\n   0: rust_begin_unwind\n             at /rustc/library/std/src/panicking.rs:645:5\n\
+            \x20  1: basic::test_panic\n\
+            note: Some details are omitted, run with `RUST_BACKTRACE=full` for a verbose backtrace.\n";
+        assert_eq!(
+            TestPanic::parse(output),
+            Some(TestPanic {
+                thread: "<unnamed>".to_owned(),
+                message: "boom".to_owned(),
+                location: Some("tests/basic.rs:3:5".to_owned()),
+                backtrace: Some(
+                    "   0: rust_begin_unwind\n             at /rustc/library/std/src/panicking.rs:645:5\n   1: basic::test_panic"
+                        .to_owned()
+                ),
+            })
+        );
+    }
+
+    #[test]
+    fn parse_panic_old_format() {
+        let output = b"thread 'tests::foo' panicked at 'assertion failed: `(left == right)`\n  left: `1`,\n right: `2`', src/lib.rs:10:5\n\
+            stack backtrace:\n   0: rust_begin_unwind\n";
+        assert_eq!(
+            TestPanic::parse(output),
+            Some(TestPanic {
+                thread: "tests::foo".to_owned(),
+                message: "assertion failed: `(left == right)`\n  left: `1`,\n right: `2`"
+                    .to_owned(),
+                location: Some("src/lib.rs:10:5".to_owned()),
+                backtrace: Some("   0: rust_begin_unwind".to_owned()),
+            })
+        );
+
+        let output = b"thread 'main' panicked at 'it's broken', src/main.rs:2:5\n";
+        assert_eq!(
+            TestPanic::parse(output).map(|panic| (panic.message, panic.location)),
+            Some(("it's broken".to_owned(), Some("src/main.rs:2:5".to_owned())))
+        );
+    }
+
+    #[test]
+    fn parse_no_panic() {
+        assert_eq!(TestPanic::parse(b""), None);
+        assert_eq!(TestPanic::parse(b"test result: ok. 1 passed\n"), None);
+    }
+}
@@ -0,0 +1,36 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Pausing tests so that a debugger can be attached to them.
+//!
+//! With `--wait-for-debugger`, the double-spawn process stops itself right before executing the
+//! test. Nextest reports the ID of the stopped process, then resumes it once the user presses
+//! Enter. Since the test hasn't started running yet, attaching a debugger isn't a race.
+
+use once_cell::sync::OnceCell;
+use std::io::BufRead;
+use tokio::sync::{
+    mpsc::{self, UnboundedReceiver},
+    Mutex,
+};
+
+/// Returns a receiver that gets a message each time the user presses Enter.
+///
+/// Standard input is read by a single thread for the lifetime of nextest: a read that's in progress
+/// can't be interrupted, so a thread started for each paused test could consume input meant for
+/// the next one. If several tests are paused, they take turns receiving messages.
+pub(super) fn enter_pressed() -> &'static Mutex<UnboundedReceiver<()>> {
+    static RECEIVER: OnceCell<Mutex<UnboundedReceiver<()>>> = OnceCell::new();
+    RECEIVER.get_or_init(|| {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        std::thread::spawn(move || {
+            let stdin = std::io::stdin();
+            for line in stdin.lock().lines() {
+                if line.is_err() || sender.send(()).is_err() {
+                    break;
+                }
+            }
+        });
+        Mutex::new(receiver)
+    })
+}
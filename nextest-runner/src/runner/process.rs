@@ -0,0 +1,766 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Management of the processes spawned for a test.
+//!
+//! Tests may spawn processes of their own. When a test times out or a run is canceled, those
+//! processes are terminated along with the test, so that they aren't left running after nextest
+//! exits.
+//!
+//! * On Unix, each test is run in its own process group, and signals are sent to the whole group.
+//! * On Windows, each test is run in a new process group, so that Ctrl-C can be forwarded to it as
+//!   `CTRL_BREAK_EVENT`. The test is also assigned to a job object, which is used to terminate it
+//!   along with every process it spawns.
+//!
+//! On Unix, test processes are also [set up](ProcessSetup) here before the test starts, unless
+//! they're [double-spawned](crate::double_spawn).
+//!
+//! Test processes are waited on from the runner's async runtime, rather than from a thread per
+//! test. On Unix, nextest listens for `SIGCHLD` and checks on its tests each time a child process
+//! changes state. On other platforms, running tests are checked on periodically.
+
+use crate::{
+    config::{CpuSet, ResourceLimits},
+    signal::ShutdownEvent,
+};
+#[cfg(not(unix))]
+use std::time::Duration;
+use std::{io, process::ExitStatus};
+
+/// How a test process is set up before the test starts.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub(crate) struct ProcessSetup {
+    /// Whether the test is run in a process group of its own.
+    pub(crate) process_group: bool,
+    /// The resource limits applied to the test. Only enforced on Unix.
+    pub(crate) resource_limits: ResourceLimits,
+    /// Whether the test may write out a core dump if it crashes. Only supported on Unix.
+    pub(crate) core_dumps: bool,
+    /// Whether address space layout randomization is disabled for the test. Only supported on
+    /// Linux.
+    pub(crate) disable_aslr: bool,
+    /// The CPUs the test is pinned to, if any. Only supported on Linux and Windows.
+    pub(crate) cpu_set: Option<CpuSet>,
+    /// The nice value the test is run at, or 0 to leave it unchanged. Mapped to a priority class on
+    /// Windows.
+    pub(crate) nice: u8,
+    /// Whether the process stops itself right before the test is executed, so that a debugger can
+    /// be attached to it. Only supported for double-spawned processes.
+    pub(crate) pause: bool,
+}
+
+/// The processes spawned for a single attempt of a test.
+#[derive(Debug)]
+pub(super) struct ProcessTree {
+    // With --no-capture, tests may read from the terminal, so they stay in nextest's process
+    // group.
+    process_group: bool,
+    #[cfg(windows)]
+    job: Option<windows::Job>,
+}
+
+impl ProcessTree {
+    /// Configures the command for a test before it is started.
+    pub(super) fn configure(cmd: duct::Expression, setup: ProcessSetup) -> duct::Expression {
+        if setup == ProcessSetup::default() {
+            cmd
+        } else {
+            imp::configure(cmd, setup)
+        }
+    }
+
+    /// Starts tracking the processes for a test that was just started.
+    ///
+    /// On Windows, this is also where the test is pinned to its CPUs, since that's done through
+    /// the job it's assigned to.
+    pub(super) fn new(handle: &duct::Handle, setup: ProcessSetup) -> Self {
+        #[cfg(unix)]
+        if setup.process_group {
+            imp::move_to_process_group(handle);
+        }
+        #[cfg(not(any(unix, windows)))]
+        let _ = handle;
+        Self {
+            process_group: setup.process_group,
+            #[cfg(windows)]
+            job: windows::Job::assign(handle, setup),
+        }
+    }
+
+    /// Forwards a signal received by nextest to the test.
+    pub(super) fn forward_signal(&self, handle: &duct::Handle, event: ShutdownEvent) {
+        imp::forward_signal(self, handle, event)
+    }
+
+    /// Asks the test to exit, e.g. because it timed out.
+    ///
+    /// On Unix, the test is sent `SIGTERM`. On Windows, it is sent Ctrl-Break if it's in its own
+    /// process group.
+    pub(super) fn terminate(&self, handle: &duct::Handle) {
+        imp::terminate(self, handle)
+    }
+
+    /// Kills the test, along with any processes it has spawned.
+    pub(super) fn kill(&self, handle: &duct::Handle) {
+        imp::kill(self, handle);
+        let _ = handle.kill();
+    }
+}
+
+/// Waits for all the processes started for a test to exit.
+///
+/// Their exit statuses are collected by the handle, and can be read from it afterwards without
+/// blocking.
+pub(super) async fn wait(handle: &duct::Handle) -> io::Result<()> {
+    // Listening starts before the processes are first checked, so that an exit in between isn't
+    // missed.
+    let mut child_events = ChildEvents::new()?;
+    while handle.try_wait()?.is_none() {
+        child_events.next().await;
+    }
+    Ok(())
+}
+
+/// Waits for a test process that [pauses itself](ProcessSetup::pause) to stop.
+///
+/// Returns false if the process exited instead, for example because it couldn't be set up.
+pub(super) async fn wait_until_stopped(pid: u32) -> io::Result<bool> {
+    let mut child_events = ChildEvents::new()?;
+    loop {
+        if let Some(stopped) = imp::stopped(pid)? {
+            return Ok(stopped);
+        }
+        child_events.next().await;
+    }
+}
+
+/// Resumes a test process that was stopped.
+pub(super) fn resume(pid: u32) {
+    imp::resume(pid)
+}
+
+/// Returns true if a test process was killed because it exceeded one of its resource limits.
+///
+/// `stderr` is the captured standard error of the test, if any.
+pub(super) fn exceeded_resource_limits(
+    status: ExitStatus,
+    stderr: &[u8],
+    resource_limits: ResourceLimits,
+) -> bool {
+    imp::exceeded_resource_limits(status, stderr, resource_limits)
+}
+
+#[cfg(unix)]
+pub(crate) use imp::set_up_current_process;
+
+/// Notifications that child processes may have changed state, i.e. exited or stopped.
+///
+/// Notifications may be spurious, since they're sent for every child process of nextest.
+#[derive(Debug)]
+struct ChildEvents {
+    #[cfg(unix)]
+    sigchld: tokio::signal::unix::Signal,
+    #[cfg(not(unix))]
+    interval: tokio::time::Interval,
+}
+
+impl ChildEvents {
+    /// How often child processes are checked on, on platforms without `SIGCHLD`.
+    #[cfg(not(unix))]
+    const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+    fn new() -> io::Result<Self> {
+        #[cfg(unix)]
+        {
+            use tokio::signal::unix::{signal, SignalKind};
+            Ok(Self {
+                sigchld: signal(SignalKind::child())?,
+            })
+        }
+        #[cfg(not(unix))]
+        {
+            Ok(Self {
+                interval: tokio::time::interval(Self::POLL_INTERVAL),
+            })
+        }
+    }
+
+    /// Waits for the next notification.
+    async fn next(&mut self) {
+        #[cfg(unix)]
+        self.sigchld.recv().await;
+        #[cfg(not(unix))]
+        self.interval.tick().await;
+    }
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::{ProcessSetup, ProcessTree};
+    use crate::{
+        config::{CpuSet, ResourceLimits},
+        signal::ShutdownEvent,
+    };
+    use std::{
+        io,
+        os::{
+            raw::c_int,
+            unix::process::{CommandExt, ExitStatusExt},
+        },
+        process::ExitStatus,
+    };
+
+    pub(super) fn configure(cmd: duct::Expression, setup: ProcessSetup) -> duct::Expression {
+        cmd.before_spawn(move |cmd| {
+            // SAFETY: set_up_current_process only calls setpgid, getrlimit, setrlimit,
+            // personality, sched_setaffinity, getpriority and setpriority, which are
+            // async-signal-safe, so it can be called between fork and exec.
+            unsafe {
+                cmd.pre_exec(move || set_up_current_process(setup));
+            }
+            Ok(())
+        })
+    }
+
+    /// Sets up the current process to run a test in.
+    pub(crate) fn set_up_current_process(setup: ProcessSetup) -> io::Result<()> {
+        if setup.process_group {
+            // SAFETY: setpgid has no memory safety requirements.
+            check(unsafe { libc::setpgid(0, 0) })?;
+        }
+        apply_resource_limits(setup.resource_limits)?;
+        if setup.core_dumps {
+            // Core dumps are often disabled through a soft limit of 0. Raise it as far as allowed.
+            let mut limit = get_limit(libc::RLIMIT_CORE)?;
+            limit.rlim_cur = limit.rlim_max;
+            set_limit(libc::RLIMIT_CORE, &limit)?;
+        }
+        if setup.disable_aslr {
+            disable_aslr()?;
+        }
+        if let Some(cpu_set) = setup.cpu_set {
+            set_affinity(cpu_set)?;
+        }
+        if setup.nice > 0 {
+            lower_priority(setup.nice)?;
+        }
+        Ok(())
+    }
+
+    /// Raises this process's nice value to `nice`, unless it's already at least that high.
+    ///
+    /// Lowering the nice value would require privileges, so it's never done.
+    fn lower_priority(nice: u8) -> io::Result<()> {
+        let nice = c_int::from(nice);
+        // getpriority can return -1 on success, but it can't fail for the current process.
+        // SAFETY: getpriority has no memory safety requirements.
+        let current = unsafe { libc::getpriority(libc::PRIO_PROCESS as _, 0) };
+        if current >= nice {
+            return Ok(());
+        }
+        // SAFETY: setpriority has no memory safety requirements.
+        check(unsafe { libc::setpriority(libc::PRIO_PROCESS as _, 0, nice) })
+    }
+
+    /// Pins this process, and the programs it executes, to a set of CPUs.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn set_affinity(cpu_set: CpuSet) -> io::Result<()> {
+        // SAFETY: cpu_set_t is a plain bitmask, for which all zeroes is a valid (empty) value.
+        let mut set: libc::cpu_set_t = unsafe { std::mem::zeroed() };
+        for cpu in cpu_set.iter() {
+            // SAFETY: CpuSet only contains CPUs below CPU_SETSIZE, so the CPU is within the set.
+            unsafe { libc::CPU_SET(cpu, &mut set) };
+        }
+        // SAFETY: the set is valid for the size passed in.
+        check(unsafe { libc::sched_setaffinity(0, std::mem::size_of_val(&set), &set) })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn set_affinity(_cpu_set: CpuSet) -> io::Result<()> {
+        // There's no way to pin a process to a set of CPUs on this platform.
+        Ok(())
+    }
+
+    /// Disables address space layout randomization for the programs this process executes.
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn disable_aslr() -> io::Result<()> {
+        // Passing 0xffffffff returns the current persona without changing it.
+        // SAFETY: personality has no memory safety requirements.
+        let persona = unsafe { libc::personality(0xffff_ffff_u32 as _) };
+        if persona == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        // SAFETY: personality has no memory safety requirements.
+        if unsafe { libc::personality((persona | libc::ADDR_NO_RANDOMIZE) as _) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn disable_aslr() -> io::Result<()> {
+        // There's no way to disable ASLR for a process on this platform.
+        Ok(())
+    }
+
+    fn apply_resource_limits(resource_limits: ResourceLimits) -> io::Result<()> {
+        if let Some(address_space) = resource_limits.address_space() {
+            let mut limit = get_limit(libc::RLIMIT_AS)?;
+            lower_limit(&mut limit, address_space as libc::rlim_t);
+            set_limit(libc::RLIMIT_AS, &limit)?;
+        }
+        if let Some(cpu_time) = resource_limits.cpu_time() {
+            // CPU time limits are in whole seconds: round up.
+            let mut secs = cpu_time.as_secs();
+            if cpu_time.subsec_nanos() > 0 {
+                secs += 1;
+            }
+            let mut limit = get_limit(libc::RLIMIT_CPU)?;
+            // The process is sent SIGXCPU once it reaches the soft limit. Set the hard limit a
+            // little higher, since reaching it results in a SIGKILL that can't be told apart from
+            // other causes.
+            lower_limit(&mut limit, secs.saturating_add(1) as libc::rlim_t);
+            limit.rlim_cur = limit.rlim_cur.min(secs as libc::rlim_t);
+            set_limit(libc::RLIMIT_CPU, &limit)?;
+        }
+        Ok(())
+    }
+
+    /// Returns whether the process has stopped (true) or exited (false), or `None` if it's still
+    /// running.
+    pub(super) fn stopped(pid: u32) -> io::Result<Option<bool>> {
+        // SAFETY: siginfo_t is a plain C struct, for which all zeroes is a valid value.
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        loop {
+            // WNOWAIT leaves the process in a waitable state, so that its exit status can still be
+            // collected once it exits.
+            // SAFETY: info is a valid siginfo_t for waitid to write to.
+            let ret = unsafe {
+                libc::waitid(
+                    libc::P_PID,
+                    pid as libc::id_t,
+                    &mut info,
+                    libc::WSTOPPED | libc::WEXITED | libc::WNOWAIT | libc::WNOHANG,
+                )
+            };
+            match check(ret) {
+                // With WNOHANG, si_signo is left as 0 if the process hasn't changed state.
+                Ok(()) if info.si_signo == 0 => return Ok(None),
+                Ok(()) => return Ok(Some(info.si_code == libc::CLD_STOPPED)),
+                Err(error) if error.kind() == io::ErrorKind::Interrupted => continue,
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
+    pub(super) fn resume(pid: u32) {
+        // SAFETY: kill has no memory safety requirements. Errors (e.g. if the process has been
+        // killed in the meantime) are ignored.
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGCONT);
+        }
+    }
+
+    /// Moves a test that was just started into its process group from nextest's side as well.
+    ///
+    /// A test that's double-spawned only moves itself into the group once it's running, so
+    /// signals sent to the group before then would otherwise be lost. Errors are ignored: the
+    /// test may have already executed, in which case it's already in the group.
+    pub(super) fn move_to_process_group(handle: &duct::Handle) {
+        for pid in handle.pids() {
+            let pid = pid as libc::pid_t;
+            // SAFETY: setpgid has no memory safety requirements.
+            unsafe {
+                libc::setpgid(pid, pid);
+            }
+        }
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    type Resource = libc::__rlimit_resource_t;
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    type Resource = c_int;
+
+    fn get_limit(resource: Resource) -> io::Result<libc::rlimit> {
+        let mut limit = libc::rlimit {
+            rlim_cur: 0,
+            rlim_max: 0,
+        };
+        // SAFETY: limit is a valid rlimit for getrlimit to write to.
+        check(unsafe { libc::getrlimit(resource, &mut limit) })?;
+        Ok(limit)
+    }
+
+    fn set_limit(resource: Resource, limit: &libc::rlimit) -> io::Result<()> {
+        // SAFETY: limit is a valid rlimit for setrlimit to read from.
+        check(unsafe { libc::setrlimit(resource, limit) })
+    }
+
+    /// Sets both the soft and hard limits to `value`. Limits can't be raised above the current hard
+    /// limit without privileges, so they are capped to it.
+    fn lower_limit(limit: &mut libc::rlimit, value: libc::rlim_t) {
+        limit.rlim_max = limit.rlim_max.min(value);
+        limit.rlim_cur = limit.rlim_max;
+    }
+
+    fn check(ret: c_int) -> io::Result<()> {
+        if ret == 0 {
+            Ok(())
+        } else {
+            Err(io::Error::last_os_error())
+        }
+    }
+
+    pub(super) fn exceeded_resource_limits(
+        status: ExitStatus,
+        stderr: &[u8],
+        resource_limits: ResourceLimits,
+    ) -> bool {
+        match status.signal() {
+            Some(libc::SIGXCPU) => resource_limits.cpu_time().is_some(),
+            // When an allocation fails, the Rust standard library prints out a message and aborts.
+            Some(libc::SIGABRT) => {
+                const ALLOC_FAILED: &[u8] = b"memory allocation of ";
+                resource_limits.address_space().is_some()
+                    && stderr
+                        .windows(ALLOC_FAILED.len())
+                        .any(|window| window == ALLOC_FAILED)
+            }
+            _ => false,
+        }
+    }
+
+    pub(super) fn forward_signal(tree: &ProcessTree, handle: &duct::Handle, event: ShutdownEvent) {
+        send_signal(handle, tree.process_group, event.to_signal());
+    }
+
+    pub(super) fn terminate(tree: &ProcessTree, handle: &duct::Handle) {
+        send_signal(handle, tree.process_group, libc::SIGTERM);
+    }
+
+    pub(super) fn kill(tree: &ProcessTree, handle: &duct::Handle) {
+        // The test itself is killed by duct, but processes spawned by the test are only reachable
+        // through the process group.
+        if tree.process_group {
+            send_signal(handle, true, libc::SIGKILL);
+        }
+    }
+
+    fn send_signal(handle: &duct::Handle, process_group: bool, signal: c_int) {
+        for pid in handle.pids() {
+            let pid = pid as libc::pid_t;
+            // A negative PID sends the signal to every process in the group.
+            let pid = if process_group { -pid } else { pid };
+            // SAFETY: kill has no memory safety requirements. Errors (e.g. if the process has
+            // already exited) are ignored.
+            unsafe {
+                libc::kill(pid, signal);
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use super::{ProcessSetup, ProcessTree};
+    use crate::{config::ResourceLimits, signal::ShutdownEvent};
+    use std::{io, os::windows::process::CommandExt, process::ExitStatus};
+    use winapi::um::{
+        winbase::CREATE_NEW_PROCESS_GROUP,
+        wincon::{GenerateConsoleCtrlEvent, CTRL_BREAK_EVENT},
+    };
+
+    pub(super) fn configure(cmd: duct::Expression, setup: ProcessSetup) -> duct::Expression {
+        // Resource limits, core dumps and disabling ASLR aren't supported on this platform yet.
+        // CPU affinity and priority are set through the job the test is assigned to.
+        if setup.process_group {
+            cmd.before_spawn(|cmd| {
+                cmd.creation_flags(CREATE_NEW_PROCESS_GROUP);
+                Ok(())
+            })
+        } else {
+            cmd
+        }
+    }
+
+    pub(super) fn forward_signal(tree: &ProcessTree, handle: &duct::Handle, event: ShutdownEvent) {
+        match event {
+            ShutdownEvent::Interrupt if tree.process_group => {
+                // Ctrl-C can't be sent to another process group, so Ctrl-Break is sent instead.
+                // The ID of a process group is the ID of the process that created it.
+                for pid in handle.pids() {
+                    // SAFETY: GenerateConsoleCtrlEvent has no memory safety requirements.
+                    unsafe {
+                        GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid);
+                    }
+                }
+            }
+            ShutdownEvent::Interrupt => {
+                // The test is in nextest's process group, so it has already received the Ctrl-C.
+            }
+            ShutdownEvent::Terminate | ShutdownEvent::Hangup => {
+                // These events aren't produced on Windows, and have no equivalent there.
+            }
+        }
+    }
+
+    pub(super) fn terminate(tree: &ProcessTree, handle: &duct::Handle) {
+        // Ctrl-Break is the closest equivalent to SIGTERM. It can only be sent to a process group,
+        // so without one, the test is left running until the grace period is up.
+        if tree.process_group {
+            for pid in handle.pids() {
+                // SAFETY: GenerateConsoleCtrlEvent has no memory safety requirements.
+                unsafe {
+                    GenerateConsoleCtrlEvent(CTRL_BREAK_EVENT, pid);
+                }
+            }
+        }
+    }
+
+    pub(super) fn exceeded_resource_limits(
+        _status: ExitStatus,
+        _stderr: &[u8],
+        _resource_limits: ResourceLimits,
+    ) -> bool {
+        false
+    }
+
+    pub(super) fn kill(tree: &ProcessTree, _handle: &duct::Handle) {
+        if let Some(job) = &tree.job {
+            job.terminate();
+        }
+    }
+
+    pub(super) fn stopped(_pid: u32) -> io::Result<Option<bool>> {
+        // Test processes are never paused on this platform.
+        Ok(Some(false))
+    }
+
+    pub(super) fn resume(_pid: u32) {}
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use super::{ProcessSetup, ProcessTree};
+    use crate::{config::ResourceLimits, signal::ShutdownEvent};
+    use std::{io, process::ExitStatus};
+
+    pub(super) fn configure(cmd: duct::Expression, _setup: ProcessSetup) -> duct::Expression {
+        cmd
+    }
+
+    pub(super) fn forward_signal(
+        _tree: &ProcessTree,
+        handle: &duct::Handle,
+        _event: ShutdownEvent,
+    ) {
+        // There's no equivalent to forwarding a signal on this platform.
+        let _ = handle.kill();
+    }
+
+    pub(super) fn terminate(_tree: &ProcessTree, handle: &duct::Handle) {
+        let _ = handle.kill();
+    }
+
+    pub(super) fn exceeded_resource_limits(
+        _status: ExitStatus,
+        _stderr: &[u8],
+        _resource_limits: ResourceLimits,
+    ) -> bool {
+        false
+    }
+
+    pub(super) fn kill(_tree: &ProcessTree, _handle: &duct::Handle) {}
+
+    pub(super) fn stopped(_pid: u32) -> io::Result<Option<bool>> {
+        // Test processes are never paused on this platform.
+        Ok(Some(false))
+    }
+
+    pub(super) fn resume(_pid: u32) {}
+}
+
+#[cfg(windows)]
+mod windows {
+    use super::ProcessSetup;
+    use std::{mem, ptr};
+    use winapi::um::{
+        handleapi::CloseHandle,
+        jobapi2::{
+            AssignProcessToJobObject, CreateJobObjectW, SetInformationJobObject, TerminateJobObject,
+        },
+        processthreadsapi::OpenProcess,
+        winbase::{BELOW_NORMAL_PRIORITY_CLASS, IDLE_PRIORITY_CLASS},
+        winnt::{
+            JobObjectBasicLimitInformation, HANDLE, JOBOBJECT_BASIC_LIMIT_INFORMATION,
+            JOB_OBJECT_LIMIT_AFFINITY, JOB_OBJECT_LIMIT_PRIORITY_CLASS, PROCESS_SET_QUOTA,
+            PROCESS_TERMINATE,
+        },
+    };
+
+    /// A job object that a test's processes are assigned to.
+    ///
+    /// Processes spawned by a process in a job are in the same job, so terminating the job
+    /// terminates the test along with every process it spawned.
+    #[derive(Debug)]
+    pub(super) struct Job {
+        handle: HANDLE,
+    }
+
+    impl Job {
+        /// Creates a new job and assigns the processes for a test to it.
+        ///
+        /// The job's processes are limited to the test's CPU set, if any. Only CPUs in the first
+        /// processor group, numbered below 64, can be used. A nice value of 15 or more runs the
+        /// test at idle priority, and any other non-zero nice value at below normal priority.
+        ///
+        /// Returns `None` if the job couldn't be set up. The test can still be killed, but
+        /// processes it spawned may be left running.
+        pub(super) fn assign(handle: &duct::Handle, setup: ProcessSetup) -> Option<Self> {
+            // SAFETY: all handles passed in are checked to be valid, and are closed exactly once.
+            unsafe {
+                let job = CreateJobObjectW(ptr::null_mut(), ptr::null());
+                if job.is_null() {
+                    return None;
+                }
+                let job = Self { handle: job };
+                let affinity = setup.cpu_set.map_or(0, |cpu_set| {
+                    cpu_set
+                        .iter()
+                        .take_while(|&cpu| cpu < usize::BITS as usize)
+                        .fold(0_usize, |mask, cpu| mask | (1 << cpu))
+                });
+                // Limits are set before processes are assigned, so that they apply to them.
+                let mut limits: JOBOBJECT_BASIC_LIMIT_INFORMATION = mem::zeroed();
+                if affinity != 0 {
+                    limits.LimitFlags |= JOB_OBJECT_LIMIT_AFFINITY;
+                    limits.Affinity = affinity;
+                }
+                if setup.nice > 0 {
+                    limits.LimitFlags |= JOB_OBJECT_LIMIT_PRIORITY_CLASS;
+                    limits.PriorityClass = if setup.nice >= 15 {
+                        IDLE_PRIORITY_CLASS
+                    } else {
+                        BELOW_NORMAL_PRIORITY_CLASS
+                    };
+                }
+                if limits.LimitFlags != 0 {
+                    SetInformationJobObject(
+                        job.handle,
+                        JobObjectBasicLimitInformation,
+                        &mut limits as *mut _ as *mut _,
+                        mem::size_of_val(&limits) as u32,
+                    );
+                }
+                for pid in handle.pids() {
+                    let process = OpenProcess(PROCESS_SET_QUOTA | PROCESS_TERMINATE, 0, pid);
+                    if process.is_null() {
+                        // The process may have already exited.
+                        continue;
+                    }
+                    AssignProcessToJobObject(job.handle, process);
+                    CloseHandle(process);
+                }
+                Some(job)
+            }
+        }
+
+        /// Terminates all processes in the job.
+        pub(super) fn terminate(&self) {
+            // SAFETY: the handle is valid until self is dropped.
+            unsafe {
+                TerminateJobObject(self.handle, 1);
+            }
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            // SAFETY: the handle is valid, and is only closed here.
+            unsafe {
+                CloseHandle(self.handle);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::runner::process::ProcessTree;
+        use std::time::{Duration, Instant};
+        use winapi::um::{
+            minwinbase::STILL_ACTIVE, processthreadsapi::GetExitCodeProcess,
+            winnt::PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+
+        /// A test that times out is killed through its job, which must take down any processes the
+        /// test started too.
+        #[test]
+        fn kill_terminates_process_tree() {
+            let dir = tempfile::tempdir().expect("created temp dir");
+            let go_path = dir.path().join("go");
+            let pid_path = dir.path().join("pid");
+            // The test only starts its child once it's been assigned to the job, so that the child
+            // is assigned to it as well.
+            let script = format!(
+                "while (-not (Test-Path '{}')) {{ Start-Sleep -Milliseconds 50 }}; \
+                 $child = Start-Process -PassThru -NoNewWindow -FilePath ping \
+                 -ArgumentList '-n 60 127.0.0.1'; \
+                 Set-Content -Path '{}' -Value $child.Id; \
+                 Start-Sleep 60",
+                go_path.display(),
+                pid_path.display(),
+            );
+            let handle = duct::cmd!(
+                "powershell",
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                script
+            )
+            .stdout_null()
+            .unchecked()
+            .start()
+            .expect("started test process");
+            let tree = ProcessTree::new(&handle, ProcessSetup::default());
+            std::fs::write(&go_path, "").expect("wrote go file");
+
+            let deadline = Instant::now() + Duration::from_secs(30);
+            let child_pid = loop {
+                let pid = std::fs::read_to_string(&pid_path)
+                    .ok()
+                    .and_then(|contents| contents.trim().parse::<u32>().ok());
+                if let Some(pid) = pid {
+                    break pid;
+                }
+                assert!(Instant::now() < deadline, "test started its child");
+                std::thread::sleep(Duration::from_millis(50));
+            };
+            // SAFETY: the handle is checked to be valid, and is closed exactly once below.
+            let child = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, child_pid) };
+            assert!(!child.is_null(), "child of the test is running");
+
+            tree.kill(&handle);
+            let _ = handle.wait();
+            let child_exited = loop {
+                let mut exit_code = 0;
+                // SAFETY: the handle is valid, and exit_code is a valid DWORD to write to.
+                unsafe {
+                    GetExitCodeProcess(child, &mut exit_code);
+                }
+                if exit_code != STILL_ACTIVE {
+                    break true;
+                }
+                if Instant::now() >= deadline {
+                    break false;
+                }
+                std::thread::sleep(Duration::from_millis(50));
+            };
+            // SAFETY: the handle is valid, and is only closed here.
+            unsafe {
+                CloseHandle(child);
+            }
+            assert!(child_exited, "child was killed along with the test");
+        }
+    }
+}
@@ -0,0 +1,48 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Recording failing tests under a record-and-replay debugger such as `rr`.
+//!
+//! Once all tests have finished, each test that failed is run once more under the recorder. The
+//! recorder is told where to write the recording through environment variables: `rr` reads
+//! `_RR_TRACE_DIR`, and other recorders can read `NEXTEST_RECORDING_DIR`.
+
+use crate::{config::WrapperCommand, coverage::sanitize, test_list::TestInstance};
+use camino::{Utf8Path, Utf8PathBuf};
+
+/// The environment variables set to the directory a recording should be written to.
+const RECORDING_DIR_ENV: &[&str] = &["NEXTEST_RECORDING_DIR", "_RR_TRACE_DIR"];
+
+/// The command failing tests are recorded under, and the directory recordings are written to.
+#[derive(Clone, Debug)]
+pub(super) struct Recorder {
+    command: WrapperCommand,
+    dir: Utf8PathBuf,
+}
+
+impl Recorder {
+    pub(super) fn new(command: WrapperCommand, dir: Utf8PathBuf) -> Self {
+        Self { command, dir }
+    }
+
+    /// Returns the command tests are recorded under.
+    pub(super) fn command(&self) -> &WrapperCommand {
+        &self.command
+    }
+
+    /// Returns the directory the recording of a test is written to.
+    pub(super) fn dir(&self, test_instance: &TestInstance<'_>) -> Utf8PathBuf {
+        self.dir.join(format!(
+            "{}-{}",
+            sanitize(&test_instance.bin_info.binary_id),
+            sanitize(test_instance.name)
+        ))
+    }
+}
+
+/// Returns the environment variables to set for a test being recorded into `dir`.
+pub(super) fn env(dir: &Utf8Path) -> impl Iterator<Item = (String, String)> + '_ {
+    RECORDING_DIR_ENV
+        .iter()
+        .map(move |&key| (key.to_owned(), dir.to_string()))
+}
@@ -0,0 +1,91 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Slot numbers for running tests.
+//!
+//! Each running test is assigned the lowest-numbered slot that's free at the time it's scheduled,
+//! both across all tests and within its test group. Slots are passed to tests through environment
+//! variables, so that tests running at the same time can use them to pick resources, like ports or
+//! database names, that don't clash.
+
+/// The environment variable containing the test's slot across all running tests.
+pub(super) const GLOBAL_SLOT_ENV: &str = "NEXTEST_TEST_GLOBAL_SLOT";
+
+/// The environment variable containing the test's slot within its test group, or `none` if it
+/// isn't in a group.
+pub(super) const GROUP_SLOT_ENV: &str = "NEXTEST_TEST_GROUP_SLOT";
+
+/// The slots assigned to a running test.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(super) struct TestSlots {
+    pub(super) global: usize,
+    pub(super) group: Option<usize>,
+}
+
+impl TestSlots {
+    /// Returns the environment variables to set for the test.
+    pub(super) fn env(&self) -> [(String, String); 2] {
+        [
+            (GLOBAL_SLOT_ENV.to_owned(), self.global.to_string()),
+            (
+                GROUP_SLOT_ENV.to_owned(),
+                self.group
+                    .map_or_else(|| "none".to_owned(), |slot| slot.to_string()),
+            ),
+        ]
+    }
+}
+
+/// Hands out slots, starting from 0.
+#[derive(Clone, Debug, Default)]
+pub(super) struct SlotAllocator {
+    in_use: Vec<bool>,
+}
+
+impl SlotAllocator {
+    /// Returns the lowest-numbered free slot, and marks it as in use.
+    pub(super) fn allocate(&mut self) -> usize {
+        match self.in_use.iter().position(|in_use| !in_use) {
+            Some(slot) => {
+                self.in_use[slot] = true;
+                slot
+            }
+            None => {
+                self.in_use.push(true);
+                self.in_use.len() - 1
+            }
+        }
+    }
+
+    /// Marks a slot as free again.
+    pub(super) fn release(&mut self, slot: usize) {
+        debug_assert!(self.in_use[slot], "slot {} released twice", slot);
+        self.in_use[slot] = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lowest_free_slot() {
+        let mut slots = SlotAllocator::default();
+        assert_eq!(slots.allocate(), 0);
+        assert_eq!(slots.allocate(), 1);
+        assert_eq!(slots.allocate(), 2);
+        slots.release(1);
+        slots.release(0);
+        assert_eq!(slots.allocate(), 0, "lowest free slot is reused first");
+        assert_eq!(slots.allocate(), 1);
+        assert_eq!(slots.allocate(), 3);
+
+        let env = TestSlots {
+            global: 3,
+            group: None,
+        }
+        .env();
+        assert_eq!(env[0], (GLOBAL_SLOT_ENV.to_owned(), "3".to_owned()));
+        assert_eq!(env[1], (GROUP_SLOT_ENV.to_owned(), "none".to_owned()));
+    }
+}
@@ -0,0 +1,286 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Running fewer tests at once while the machine is overloaded.
+//!
+//! On shared hosts, a fixed number of test threads can oversubscribe the machine when other work
+//! is running. With a [`ThrottleConfig`], the runner samples the system's load every few seconds.
+//! Each time the machine is over a limit, one fewer test thread is used, down to a single thread;
+//! each time there's room for another test, one more is used again, up to the configured number of
+//! test threads.
+//!
+//! The load average includes the tests nextest is running, and lags behind changes in how many are
+//! running. To tell the load caused by other work apart from its own, the throttle keeps an average
+//! of the number of running tests that decays the same way as the one-minute load average, and
+//! subtracts it from the load. Otherwise, after running fewer tests, the load left over from the
+//! tests that finished would hold the number of test threads down further.
+
+use crate::config::ThrottleConfig;
+use std::time::{Duration, Instant};
+
+/// Tracks the number of test threads that may be used, based on the system's load.
+#[derive(Debug)]
+pub(super) struct Throttle {
+    config: ThrottleConfig,
+    max_threads: usize,
+    limit: usize,
+    last_sample: Option<Instant>,
+    /// The number of threads used by running tests, averaged like the one-minute load average.
+    own_load: f64,
+    sample: fn() -> SystemLoad,
+}
+
+impl Throttle {
+    /// How often the system's load is sampled. Unix kernels update the load average every five
+    /// seconds, so sampling more often wouldn't see any changes.
+    pub(super) const SAMPLE_INTERVAL: Duration = Duration::from_secs(5);
+
+    /// The period over which the load average is taken.
+    const LOAD_AVERAGE_PERIOD: Duration = Duration::from_secs(60);
+
+    /// Creates a throttle that samples the system's load with `sample`, which is
+    /// [`SystemLoad::sample`] outside of tests.
+    pub(super) fn new(
+        config: ThrottleConfig,
+        max_threads: usize,
+        sample: fn() -> SystemLoad,
+    ) -> Self {
+        Self {
+            config,
+            max_threads,
+            limit: max_threads,
+            last_sample: None,
+            own_load: 0.0,
+            sample,
+        }
+    }
+
+    /// Returns true if the number of test threads is adjusted at all.
+    pub(super) fn is_enabled(&self) -> bool {
+        !self.config.is_empty()
+    }
+
+    /// Returns the number of test threads that may currently be in use.
+    pub(super) fn limit(&self) -> usize {
+        if self.is_enabled() {
+            self.limit
+        } else {
+            self.max_threads
+        }
+    }
+
+    /// Samples the system's load and adjusts the limit, given the number of threads that running
+    /// tests use. Called every [`SAMPLE_INTERVAL`](Self::SAMPLE_INTERVAL).
+    pub(super) fn sample(&mut self, running_threads: usize) {
+        let now = Instant::now();
+        let elapsed = self
+            .last_sample
+            .map_or(Self::SAMPLE_INTERVAL, |last| now.duration_since(last));
+        self.last_sample = Some(now);
+        self.adjust((self.sample)(), running_threads, elapsed);
+    }
+
+    fn adjust(&mut self, load: SystemLoad, running_threads: usize, elapsed: Duration) {
+        let decay = (-elapsed.as_secs_f64() / Self::LOAD_AVERAGE_PERIOD.as_secs_f64()).exp();
+        self.own_load = self.own_load * decay + running_threads as f64 * (1.0 - decay);
+
+        // Limits that can't be measured on this platform are ignored.
+        let (over_load, room_for_more) = match (self.config.max_load(), load.load) {
+            (Some(max_load), Some(total)) => {
+                let capacity = max_load * load.cpus as f64;
+                let other = (total - self.own_load).max(0.0);
+                // Only use another thread if it would still fit, so that the number of threads
+                // settles rather than going back and forth around the limit.
+                (
+                    other + self.limit as f64 > capacity,
+                    other + (self.limit + 1) as f64 <= capacity,
+                )
+            }
+            _ => (false, true),
+        };
+        let under_memory = match (self.config.min_available_memory(), load.available_memory) {
+            (Some(min_available), Some(available)) => available < min_available,
+            _ => false,
+        };
+
+        if over_load || under_memory {
+            self.limit = self.limit.saturating_sub(1).max(1);
+        } else if room_for_more && self.limit < self.max_threads {
+            self.limit += 1;
+        }
+    }
+}
+
+/// A sample of how busy the system is.
+#[derive(Copy, Clone, Debug, Default)]
+pub(super) struct SystemLoad {
+    /// The one-minute load average.
+    pub(super) load: Option<f64>,
+    /// The number of logical CPUs.
+    pub(super) cpus: usize,
+    /// The amount of memory available to new processes, in bytes.
+    pub(super) available_memory: Option<u64>,
+}
+
+impl SystemLoad {
+    pub(super) fn sample() -> Self {
+        Self {
+            load: load_average(),
+            cpus: num_cpus::get(),
+            available_memory: available_memory(),
+        }
+    }
+}
+
+#[cfg(unix)]
+fn load_average() -> Option<f64> {
+    let mut loads = [0.0; 1];
+    // SAFETY: getloadavg writes at most as many samples as the length passed in.
+    let count = unsafe { libc::getloadavg(loads.as_mut_ptr(), 1) };
+    (count == 1).then(|| loads[0])
+}
+
+#[cfg(not(unix))]
+fn load_average() -> Option<f64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+fn available_memory() -> Option<u64> {
+    let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+    parse_available_memory(&meminfo)
+}
+
+#[cfg(not(target_os = "linux"))]
+fn available_memory() -> Option<u64> {
+    None
+}
+
+/// Parses the `MemAvailable` line of `/proc/meminfo`, which is in kibibytes.
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse_available_memory(meminfo: &str) -> Option<u64> {
+    let line = meminfo
+        .lines()
+        .find_map(|line| line.strip_prefix("MemAvailable:"))?;
+    let kib: u64 = line.trim().strip_suffix("kB")?.trim().parse().ok()?;
+    kib.checked_mul(1024)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn throttle(config: &str, max_threads: usize) -> Throttle {
+        let config: ThrottleConfig = toml::from_str(config).expect("config is valid");
+        Throttle::new(config, max_threads, SystemLoad::sample)
+    }
+
+    fn load(load: f64) -> SystemLoad {
+        SystemLoad {
+            load: Some(load),
+            cpus: 4,
+            available_memory: None,
+        }
+    }
+
+    #[test]
+    fn adjust_limit() {
+        // With 4 CPUs, the machine can take a load of 4.
+        let mut throttle = throttle("max-load = 1.0", 3);
+        let interval = Throttle::LOAD_AVERAGE_PERIOD * 100;
+
+        throttle.adjust(load(8.0), 0, interval);
+        assert_eq!(throttle.limit, 2);
+        throttle.adjust(load(8.0), 0, interval);
+        throttle.adjust(load(8.0), 0, interval);
+        assert_eq!(throttle.limit, 1, "at least one test thread is used");
+        throttle.adjust(load(2.0), 0, interval);
+        assert_eq!(throttle.limit, 2);
+        throttle.adjust(load(1.5), 0, interval);
+        assert_eq!(throttle.limit, 2, "no room for another test");
+        throttle.adjust(load(1.0), 0, interval);
+        throttle.adjust(load(1.0), 0, interval);
+        assert_eq!(
+            throttle.limit, 3,
+            "no more than the configured test threads"
+        );
+
+        // The load from nextest's own tests is left out.
+        throttle.adjust(load(4.0), 3, interval);
+        assert_eq!(throttle.limit, 3);
+
+        // Limits that can't be measured are ignored.
+        throttle.adjust(SystemLoad::default(), 0, interval);
+        assert_eq!(throttle.limit, 3);
+    }
+
+    #[test]
+    fn lagging_load() {
+        // The load average catches up with the number of running tests over about a minute, while
+        // the limit is adjusted every few seconds. Simulate the load average for a machine with 4
+        // CPUs and no other work, with nextest running as many tests as it may.
+        let mut throttle = throttle("max-load = 1.0", 8);
+        let decay = (-Throttle::SAMPLE_INTERVAL.as_secs_f64()
+            / Throttle::LOAD_AVERAGE_PERIOD.as_secs_f64())
+        .exp();
+        let mut load_average = 0.0;
+        let mut running = 0;
+        let mut limits = vec![];
+        for _ in 0..120 {
+            load_average = load_average * decay + running as f64 * (1.0 - decay);
+            throttle.adjust(load(load_average), running, Throttle::SAMPLE_INTERVAL);
+            running = throttle.limit;
+            limits.push(throttle.limit);
+        }
+        assert!(
+            limits.iter().all(|&limit| limit >= 4),
+            "the limit doesn't collapse: {:?}",
+            limits
+        );
+        assert_eq!(
+            &limits[limits.len() - 10..],
+            &[4; 10],
+            "the limit settles at the machine's capacity: {:?}",
+            limits
+        );
+    }
+
+    #[test]
+    fn adjust_limit_memory() {
+        let mut throttle = throttle(r#"min-available-memory = "512MiB""#, 3);
+        throttle.adjust(
+            SystemLoad {
+                load: Some(100.0),
+                cpus: 4,
+                available_memory: Some(1 << 30),
+            },
+            0,
+            Throttle::SAMPLE_INTERVAL,
+        );
+        assert_eq!(throttle.limit, 3, "only memory is limited");
+        throttle.adjust(
+            SystemLoad {
+                load: None,
+                cpus: 4,
+                available_memory: Some(1 << 20),
+            },
+            0,
+            Throttle::SAMPLE_INTERVAL,
+        );
+        assert_eq!(throttle.limit, 2);
+    }
+
+    #[test]
+    fn disabled_throttle() {
+        let throttle = throttle("", 8);
+        assert!(!throttle.is_enabled());
+        assert_eq!(throttle.limit(), 8);
+    }
+
+    #[test]
+    fn parse_meminfo() {
+        let meminfo = "MemTotal:       16316412 kB\nMemFree:         1040548 kB\nMemAvailable:    8246060 kB\n";
+        assert_eq!(parse_available_memory(meminfo), Some(8246060 * 1024));
+        assert_eq!(parse_available_memory("MemTotal: 1 kB\n"), None);
+    }
+}
@@ -0,0 +1,152 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Temporary directories for tests.
+//!
+//! Each attempt of each test gets a fresh directory within a run-scoped directory, and `TMPDIR`
+//! and friends point to it. That way, tests that write to the system temporary directory can't
+//! step on each other, and anything they leave behind is cleaned up once they finish.
+
+use crate::{config::RetainTmpdir, coverage::sanitize, test_list::TestInstance};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::io;
+
+/// The environment variable that points to the temporary directory for a test.
+pub(super) const TEST_TMPDIR_ENV: &str = "NEXTEST_TEST_TMPDIR";
+
+/// The environment variables, other than [`TEST_TMPDIR_ENV`], that are set to the temporary
+/// directory. `TMPDIR` is used on Unix, and `TEMP` and `TMP` on Windows.
+const SYSTEM_TMPDIR_ENVS: &[&str] = &["TMPDIR", "TEMP", "TMP"];
+
+// Keep directory names short, since some tests create sockets in their temporary directory and
+// socket paths are limited to around 100 bytes.
+const MAX_NAME_LEN: usize = 48;
+
+/// The temporary directory for a single attempt of a test.
+#[derive(Debug)]
+pub(super) struct TestTmpdir {
+    path: Utf8PathBuf,
+}
+
+impl TestTmpdir {
+    /// Creates a new temporary directory for the test within `run_dir`, creating `run_dir` if
+    /// necessary.
+    pub(super) fn create(
+        run_dir: &Utf8Path,
+        test_instance: &TestInstance<'_>,
+        attempt: usize,
+    ) -> io::Result<Self> {
+        let path = create_test_dir(run_dir, test_instance, attempt)?;
+        Ok(Self { path })
+    }
+
+    /// Returns the path to the directory.
+    pub(super) fn path(&self) -> &Utf8Path {
+        &self.path
+    }
+
+    /// Returns the environment variables to set for the test.
+    pub(super) fn env(&self) -> impl Iterator<Item = (String, String)> + '_ {
+        std::iter::once(TEST_TMPDIR_ENV)
+            .chain(SYSTEM_TMPDIR_ENVS.iter().copied())
+            .map(move |name| (name.to_owned(), self.path().to_string()))
+    }
+
+    /// Removes the directory once the test has finished, unless it should be retained.
+    pub(super) fn finish(self, success: bool, retain: RetainTmpdir) -> io::Result<()> {
+        let keep = match retain {
+            RetainTmpdir::Never => false,
+            RetainTmpdir::OnFailure => !success,
+            RetainTmpdir::Always => true,
+        };
+        if keep {
+            Ok(())
+        } else {
+            std::fs::remove_dir_all(&self.path)
+        }
+    }
+}
+
+/// Creates a new directory for an attempt of a test within `run_dir`, creating `run_dir` if
+/// necessary. Also used for [output directories](super::output_dir).
+pub(super) fn create_test_dir(
+    run_dir: &Utf8Path,
+    test_instance: &TestInstance<'_>,
+    attempt: usize,
+) -> io::Result<Utf8PathBuf> {
+    std::fs::create_dir_all(run_dir)?;
+    let name = dir_name(
+        &test_instance.bin_info.binary_id,
+        test_instance.name,
+        attempt,
+    );
+    // The run directory is new for each run, so the name can only be taken if the test is
+    // repeated. In that case, a suffix is added to tell the directories apart.
+    let mut suffix = 1;
+    loop {
+        let path = match suffix {
+            1 => run_dir.join(&name),
+            _ => run_dir.join(format!("{}-{}", name, suffix)),
+        };
+        match std::fs::create_dir(&path) {
+            Ok(()) => return Ok(path),
+            Err(error) if error.kind() == io::ErrorKind::AlreadyExists => suffix += 1,
+            Err(error) => return Err(error),
+        }
+    }
+}
+
+/// Returns the name of the directory for an attempt of a test.
+fn dir_name(binary_id: &str, test_name: &str, attempt: usize) -> String {
+    let mut name = format!("{}-{}", sanitize(binary_id), sanitize(test_name));
+    name.truncate(MAX_NAME_LEN);
+    format!("{}-{}", name, attempt)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dir_name_is_sanitized() {
+        assert_eq!(
+            dir_name("my-crate::integration", "tests::foo", 2),
+            "my-crate__integration-tests__foo-2"
+        );
+        let long_name = "a".repeat(100);
+        assert_eq!(
+            dir_name("my-crate", &long_name, 1).len(),
+            MAX_NAME_LEN + "-1".len()
+        );
+    }
+
+    #[test]
+    fn retain_on_failure() {
+        let run_dir = tempfile::tempdir().expect("created temp dir");
+        let run_dir = Utf8Path::from_path(run_dir.path()).expect("temp dir is valid UTF-8");
+        let mut count = 0;
+        let mut make_dir = || {
+            count += 1;
+            let path = run_dir.join(format!("test-{}", count));
+            std::fs::create_dir(&path).expect("created test temp dir");
+            TestTmpdir { path }
+        };
+
+        let passed = make_dir();
+        let passed_path = passed.path().to_owned();
+        passed
+            .finish(true, RetainTmpdir::OnFailure)
+            .expect("removed directory");
+        assert!(!passed_path.exists(), "directory for passing test removed");
+
+        let failed = make_dir();
+        let failed_path = failed.path().to_owned();
+        failed
+            .finish(false, RetainTmpdir::OnFailure)
+            .expect("retained directory");
+        assert!(failed_path.exists(), "directory for failing test retained");
+
+        let env: Vec<_> = make_dir().env().map(|(name, _)| name).collect();
+        assert_eq!(env, ["NEXTEST_TEST_TMPDIR", "TMPDIR", "TEMP", "TMP"]);
+    }
+}
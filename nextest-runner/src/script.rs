@@ -0,0 +1,172 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Setup and teardown scripts.
+//!
+//! Scripts are configured through `[script.<name>]` sections in the nextest config. Setup commands
+//! are run before any tests, and teardown commands are run after all tests have finished.
+//!
+//! A setup command can export environment variables to tests by writing `KEY=VALUE` lines to the
+//! file pointed to by the `NEXTEST_ENV` environment variable.
+
+use crate::{config::ScriptCommand, runner::ExecutionResult, stopwatch::StopwatchStart};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+/// The environment variable pointing to the file that setup scripts can write environment
+/// variables to.
+pub const NEXTEST_ENV: &str = "NEXTEST_ENV";
+
+/// Whether a script is being run before or after tests.
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ScriptKind {
+    /// The setup command, run before tests.
+    Setup,
+
+    /// The teardown command, run after tests.
+    Teardown,
+}
+
+/// Information about a single execution of a script.
+#[derive(Clone, Debug)]
+pub struct ScriptStatus {
+    /// Standard output and standard error for this script.
+    pub stdout_stderr: Arc<(Vec<u8>, Vec<u8>)>,
+    /// The result of running this script: pass, fail or execution error.
+    pub result: ExecutionResult,
+    /// The time at which the script started.
+    pub start_time: SystemTime,
+    /// The time it took for the script to run.
+    pub time_taken: Duration,
+}
+
+impl ScriptStatus {
+    /// Returns the standard output.
+    pub fn stdout(&self) -> &[u8] {
+        &self.stdout_stderr.0
+    }
+
+    /// Returns the standard error.
+    pub fn stderr(&self) -> &[u8] {
+        &self.stdout_stderr.1
+    }
+}
+
+/// Runs a script command in the workspace root, with the given environment variables set.
+///
+/// If `no_capture` is true, the script's standard output and standard error are passed through to
+/// the terminal rather than being captured.
+///
+/// Returns the status of the script, along with any environment variables it exported.
+pub(crate) fn run_script(
+    command: &ScriptCommand,
+    workspace_root: &Utf8Path,
+    env: &[(String, String)],
+    no_capture: bool,
+) -> (ScriptStatus, Vec<(String, String)>) {
+    let stopwatch = StopwatchStart::now();
+
+    let (stdout, stderr, result, exported) =
+        match run_script_inner(command, workspace_root, env, no_capture) {
+            Ok(output) => output,
+            Err(error) => (
+                vec![],
+                format!("error running script: {}\n", error).into_bytes(),
+                ExecutionResult::ExecFail,
+                vec![],
+            ),
+        };
+
+    let stopwatch_end = stopwatch.end();
+    let status = ScriptStatus {
+        stdout_stderr: Arc::new((stdout, stderr)),
+        result,
+        start_time: stopwatch_end.start_time,
+        time_taken: stopwatch_end.duration,
+    };
+    (status, exported)
+}
+
+type ScriptOutput = (Vec<u8>, Vec<u8>, ExecutionResult, Vec<(String, String)>);
+
+fn run_script_inner(
+    command: &ScriptCommand,
+    workspace_root: &Utf8Path,
+    env: &[(String, String)],
+    no_capture: bool,
+) -> std::io::Result<ScriptOutput> {
+    let env_file = tempfile::NamedTempFile::new()?;
+
+    let mut cmd = duct::cmd(command.program(), command.args())
+        .dir(workspace_root)
+        .env(NEXTEST_ENV, env_file.path())
+        .unchecked();
+    if !no_capture {
+        cmd = cmd.stdout_capture().stderr_capture();
+    }
+    for (key, value) in env {
+        cmd = cmd.env(key, value);
+    }
+
+    let output = cmd.run()?;
+    let mut stderr = output.stderr;
+    if !output.status.success() {
+        return Ok((output.stdout, stderr, ExecutionResult::Fail, vec![]));
+    }
+
+    let contents = std::fs::read_to_string(env_file.path())?;
+    match parse_env_file(&contents) {
+        Ok(exported) => Ok((output.stdout, stderr, ExecutionResult::Pass, exported)),
+        Err(line) => {
+            stderr.extend_from_slice(
+                format!(
+                    "invalid line in {} file (expected KEY=VALUE): {}\n",
+                    NEXTEST_ENV, line
+                )
+                .as_bytes(),
+            );
+            Ok((output.stdout, stderr, ExecutionResult::ExecFail, vec![]))
+        }
+    }
+}
+
+/// Parses `KEY=VALUE` lines, skipping blank lines. Returns the first invalid line on failure.
+fn parse_env_file(contents: &str) -> Result<Vec<(String, String)>, &str> {
+    contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(|line| match line.split_once('=') {
+            Some((key, value)) if !key.is_empty() => Ok((key.to_owned(), value.to_owned())),
+            _ => Err(line),
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_env_file_lines() {
+        let contents = "DATABASE_URL=postgres://localhost:5432/db\n\nEMPTY=\nEQUALS=a=b\n";
+        assert_eq!(
+            parse_env_file(contents),
+            Ok(vec![
+                (
+                    "DATABASE_URL".to_owned(),
+                    "postgres://localhost:5432/db".to_owned()
+                ),
+                ("EMPTY".to_owned(), "".to_owned()),
+                ("EQUALS".to_owned(), "a=b".to_owned()),
+            ])
+        );
+
+        assert_eq!(parse_env_file("FOO=bar\nnot-valid\n"), Err("not-valid"));
+        assert_eq!(parse_env_file("=value"), Err("=value"));
+    }
+}
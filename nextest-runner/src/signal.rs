@@ -2,41 +2,175 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 //! Support for handling signals in nextest.
+//!
+//! When nextest receives a signal that asks it to shut down (Ctrl-C, `SIGTERM` or `SIGHUP`), it
+//! stops scheduling new tests and forwards the signal to tests that are currently running. Tests
+//! that are still running after a grace period are killed. If a second signal is received, running
+//! tests are killed immediately.
+//!
+//! On Unix, nextest also prints out the tests that are currently running if it receives `SIGUSR1`,
+//! or `SIGINFO` on platforms that have it (for example, by pressing Ctrl-T on macOS).
 
-use crossbeam_channel::Receiver;
+use crate::errors::SignalHandlerSetupError;
+use tokio::sync::mpsc::{self, UnboundedReceiver};
 
-/// A receiver that generates signals if ctrl-c is pressed.
+/// A receiver that generates signals if ctrl-c is pressed, or if nextest is asked to terminate.
 ///
 /// A `SignalHandler` can be passed into
 /// [`TestRunnerBuilder::build`](crate::runner::TestRunnerBuilder::build).
 #[derive(Debug)]
 pub struct SignalHandler {
-    pub(crate) receiver: Receiver<SignalEvent>,
+    pub(crate) receiver: UnboundedReceiver<SignalEvent>,
 }
 
 impl SignalHandler {
-    /// Creates a new `SignalReceiver` that handles Ctrl-C errors.
+    /// Creates a new `SignalReceiver` that handles Ctrl-C and termination signals.
     ///
-    /// Errors if a signal handler has already been registered in this process. Only one signal
-    /// handler can be registered for a process at any given time.
-    pub fn new() -> Result<Self, ctrlc::Error> {
-        let (sender, receiver) = crossbeam_channel::unbounded();
-        ctrlc::set_handler(move || {
-            let _ = sender.send(SignalEvent::Interrupted);
-        })?;
+    /// Errors if the signal handler couldn't be registered. On Windows, only one Ctrl-C handler
+    /// can be registered for a process at any given time.
+    pub fn new() -> Result<Self, SignalHandlerSetupError> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        imp::register(sender).map_err(SignalHandlerSetupError::new)?;
 
         Ok(Self { receiver })
     }
 
     /// Creates a new `SignalReceiver` that does nothing.
     pub fn noop() -> Self {
-        let (_sender, receiver) = crossbeam_channel::bounded(1);
+        let (_sender, receiver) = mpsc::unbounded_channel();
         Self { receiver }
     }
 }
 
-// Just a single-valued enum for now, might have more information in the future.
-#[derive(Debug)]
+/// A signal received by nextest.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub(crate) enum SignalEvent {
-    Interrupted,
+    /// Nextest was asked to shut down.
+    Shutdown(ShutdownEvent),
+    /// Information about the tests that are currently running was requested.
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Info,
+}
+
+/// A signal that asks nextest to shut down.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub(crate) enum ShutdownEvent {
+    /// Ctrl-C was pressed (`SIGINT` on Unix).
+    Interrupt,
+    /// Nextest was asked to terminate (`SIGTERM` on Unix).
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Terminate,
+    /// The controlling terminal was closed (`SIGHUP` on Unix).
+    #[cfg_attr(not(unix), allow(dead_code))]
+    Hangup,
+}
+
+#[cfg(unix)]
+mod imp {
+    use super::{ShutdownEvent, SignalEvent};
+    use signal_hook::{
+        consts::{SIGHUP, SIGINT, SIGTERM, SIGUSR1},
+        iterator::Signals,
+    };
+    use std::{io, os::raw::c_int};
+    use tokio::sync::mpsc::UnboundedSender;
+
+    #[cfg(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    ))]
+    const INFO_SIGNALS: &[c_int] = &[SIGUSR1, signal_hook::consts::SIGINFO];
+    #[cfg(not(any(
+        target_os = "macos",
+        target_os = "ios",
+        target_os = "freebsd",
+        target_os = "dragonfly",
+        target_os = "openbsd",
+        target_os = "netbsd"
+    )))]
+    const INFO_SIGNALS: &[c_int] = &[SIGUSR1];
+
+    pub(super) fn register(sender: UnboundedSender<SignalEvent>) -> io::Result<()> {
+        let mut signals = Signals::new([SIGINT, SIGTERM, SIGHUP].iter().chain(INFO_SIGNALS))?;
+        std::thread::Builder::new()
+            .name("nextest-signal".to_owned())
+            .spawn(move || {
+                for signal in signals.forever() {
+                    let event = match to_event(signal) {
+                        Some(event) => event,
+                        None => continue,
+                    };
+                    // Failure to send means the runner is done.
+                    if sender.send(event).is_err() {
+                        break;
+                    }
+                }
+            })?;
+        Ok(())
+    }
+
+    fn to_event(signal: c_int) -> Option<SignalEvent> {
+        match signal {
+            SIGINT => Some(SignalEvent::Shutdown(ShutdownEvent::Interrupt)),
+            SIGTERM => Some(SignalEvent::Shutdown(ShutdownEvent::Terminate)),
+            SIGHUP => Some(SignalEvent::Shutdown(ShutdownEvent::Hangup)),
+            signal if INFO_SIGNALS.contains(&signal) => Some(SignalEvent::Info),
+            _ => None,
+        }
+    }
+
+    impl ShutdownEvent {
+        /// Returns the signal to forward to running tests.
+        pub(crate) fn to_signal(self) -> c_int {
+            match self {
+                ShutdownEvent::Interrupt => SIGINT,
+                ShutdownEvent::Terminate => SIGTERM,
+                ShutdownEvent::Hangup => SIGHUP,
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use signal_hook::consts::SIGUSR2;
+
+        #[test]
+        fn signal_events() {
+            for event in [
+                ShutdownEvent::Interrupt,
+                ShutdownEvent::Terminate,
+                ShutdownEvent::Hangup,
+            ] {
+                assert_eq!(
+                    to_event(event.to_signal()),
+                    Some(SignalEvent::Shutdown(event)),
+                    "shutdown signals are forwarded as they were received"
+                );
+            }
+            for &signal in INFO_SIGNALS {
+                assert_eq!(to_event(signal), Some(SignalEvent::Info));
+            }
+            assert_eq!(to_event(SIGUSR1), Some(SignalEvent::Info));
+            assert_eq!(to_event(SIGUSR2), None, "other signals are ignored");
+        }
+    }
+}
+
+#[cfg(not(unix))]
+mod imp {
+    use super::{ShutdownEvent, SignalEvent};
+    use std::io;
+    use tokio::sync::mpsc::UnboundedSender;
+
+    pub(super) fn register(sender: UnboundedSender<SignalEvent>) -> io::Result<()> {
+        ctrlc::set_handler(move || {
+            let _ = sender.send(SignalEvent::Shutdown(ShutdownEvent::Interrupt));
+        })
+        .map_err(|error| io::Error::new(io::ErrorKind::Other, error))
+    }
 }
@@ -0,0 +1,460 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! The store directory for a profile, `target/nextest/<profile>` by default.
+//!
+//! Nextest keeps two kinds of files in the store:
+//!
+//! * State that's carried over from one run to the next, such as the tests that failed in the
+//!   [last run](crate::last_run) and [test timings](crate::timings). Each run overwrites these
+//!   files.
+//! * Artifacts of individual runs, such as core dumps and recordings of failing tests. Each kind of
+//!   artifact is written to a directory per run, `<kind>/<run-id>`, so they pile up over time.
+//!   They're removed according to the store's [retention policy](StoreRetention) by
+//!   [`RunStore::gc`], which `cargo nextest run` calls before running tests.
+//!
+//! Nextest processes using the same store, such as parallel CI jobs that share a target directory,
+//! would overwrite each other's state. To avoid that, `cargo nextest run` holds an advisory
+//! [lock](RunStore::lock) on the store while it's running.
+
+use crate::{config::StoreRetention, errors::StoreError};
+use camino::{Utf8Path, Utf8PathBuf};
+use std::{
+    cmp::Reverse,
+    collections::BTreeMap,
+    fmt,
+    fs::{self, File},
+    io,
+    time::SystemTime,
+};
+use uuid::Uuid;
+
+/// A kind of artifact that's written to a directory per run within the store.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+#[non_exhaustive]
+pub enum ArtifactKind {
+    /// Per-test temporary directories that were retained.
+    Tmpdir,
+
+    /// Per-test output directories that were retained.
+    OutputDir,
+
+    /// Core dumps written out by crashing tests.
+    CoreDump,
+
+    /// Recordings of failing tests.
+    Recording,
+}
+
+impl ArtifactKind {
+    /// All kinds of artifacts.
+    pub const ALL: &'static [Self] = &[
+        ArtifactKind::Tmpdir,
+        ArtifactKind::OutputDir,
+        ArtifactKind::CoreDump,
+        ArtifactKind::Recording,
+    ];
+
+    /// Returns the name of the directory, within the store, that this kind of artifact is written
+    /// to.
+    pub fn dir_name(self) -> &'static str {
+        match self {
+            ArtifactKind::Tmpdir => "tmp",
+            ArtifactKind::OutputDir => "output",
+            ArtifactKind::CoreDump => "core-dumps",
+            ArtifactKind::Recording => "recordings",
+        }
+    }
+}
+
+impl fmt::Display for ArtifactKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.dir_name())
+    }
+}
+
+/// The store directory for a profile.
+#[derive(Clone, Debug)]
+pub struct RunStore {
+    dir: Utf8PathBuf,
+}
+
+impl RunStore {
+    /// Creates a new store for the given directory, typically
+    /// [`NextestProfile::store_dir`](crate::config::NextestProfile::store_dir).
+    pub fn new(dir: impl Into<Utf8PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    /// Returns the store directory.
+    pub fn dir(&self) -> &Utf8Path {
+        &self.dir
+    }
+
+    /// Returns the path to the file that's locked while the store is in use.
+    pub fn lock_path(&self) -> Utf8PathBuf {
+        self.dir.join(".lock")
+    }
+
+    /// Locks the store, blocking until any other process holding the lock releases it.
+    ///
+    /// The lock is released when the returned [`StoreLock`] is dropped, or when the process
+    /// exits. The store directory must already exist.
+    pub fn lock(&self) -> Result<StoreLock, StoreError> {
+        self.lock_impl(true)
+            .map(|lock| lock.expect("blocking lock is always acquired"))
+    }
+
+    /// Locks the store if no other process holds the lock, returning `None` otherwise.
+    pub fn try_lock(&self) -> Result<Option<StoreLock>, StoreError> {
+        self.lock_impl(false)
+    }
+
+    fn lock_impl(&self, block: bool) -> Result<Option<StoreLock>, StoreError> {
+        let path = self.lock_path();
+        let lock_error = |error| StoreError::Lock {
+            path: path.clone(),
+            error,
+        };
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(&path)
+            .map_err(lock_error)?;
+        if imp::lock(&file, block).map_err(lock_error)? {
+            Ok(Some(StoreLock { _file: file }))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Returns the directory that artifacts of this kind are written to for a run.
+    pub fn run_dir(&self, kind: ArtifactKind, run_id: Uuid) -> Utf8PathBuf {
+        self.dir.join(kind.dir_name()).join(run_id.to_string())
+    }
+
+    /// Returns the earlier runs that have artifacts in the store, most recently modified first.
+    pub fn runs(&self) -> Result<Vec<StoredRun>, StoreError> {
+        let mut runs: BTreeMap<Uuid, Vec<StoredArtifact>> = BTreeMap::new();
+        for &kind in ArtifactKind::ALL {
+            let kind_dir = self.dir.join(kind.dir_name());
+            let read_error = |error| StoreError::Read {
+                path: kind_dir.clone(),
+                error,
+            };
+            let entries = match fs::read_dir(&kind_dir) {
+                Ok(entries) => entries,
+                Err(error) if error.kind() == io::ErrorKind::NotFound => continue,
+                Err(error) => return Err(read_error(error)),
+            };
+            for entry in entries {
+                let entry = entry.map_err(read_error)?;
+                // Anything that isn't named after a run wasn't written by nextest.
+                let run_id = match entry.file_name().to_str().map(Uuid::parse_str) {
+                    Some(Ok(run_id)) => run_id,
+                    _ => continue,
+                };
+                let path = kind_dir.join(run_id.to_string());
+                let (size, modified) = disk_usage(&path).map_err(|error| StoreError::Read {
+                    path: path.clone(),
+                    error,
+                })?;
+                runs.entry(run_id).or_default().push(StoredArtifact {
+                    kind,
+                    path,
+                    size,
+                    modified,
+                });
+            }
+        }
+
+        let mut runs: Vec<_> = runs
+            .into_iter()
+            .map(|(run_id, artifacts)| StoredRun { run_id, artifacts })
+            .collect();
+        runs.sort_by_key(|run| Reverse(run.modified()));
+        Ok(runs)
+    }
+
+    /// Removes the artifacts of earlier runs according to the retention policy.
+    ///
+    /// Runs that were last modified longer than [`StoreRetention::max_age`] before `now` are
+    /// removed. Then, if the remaining runs take up more than [`StoreRetention::max_size`], runs
+    /// are removed oldest first until they don't.
+    pub fn gc(&self, retention: StoreRetention, now: SystemTime) -> Result<GcSummary, StoreError> {
+        let mut summary = GcSummary::default();
+        if retention == StoreRetention::default() {
+            return Ok(summary);
+        }
+
+        let mut kept_size = 0;
+        // Once a run is removed because of its size, older runs are removed as well.
+        let mut over_size = false;
+        for run in self.runs()? {
+            let expired = retention.max_age().map_or(false, |max_age| {
+                now.duration_since(run.modified())
+                    .map_or(false, |age| age > max_age)
+            });
+            over_size = over_size
+                || retention
+                    .max_size()
+                    .map_or(false, |max_size| kept_size + run.size() > max_size);
+            if !expired && !over_size {
+                kept_size += run.size();
+                continue;
+            }
+
+            for artifact in &run.artifacts {
+                fs::remove_dir_all(&artifact.path).map_err(|error| StoreError::Remove {
+                    path: artifact.path.clone(),
+                    error,
+                })?;
+            }
+            summary.removed_runs += 1;
+            summary.bytes_freed += run.size();
+        }
+        Ok(summary)
+    }
+}
+
+/// A lock on the store, returned by [`RunStore::lock`] and [`RunStore::try_lock`].
+///
+/// The lock is released when this is dropped.
+#[derive(Debug)]
+pub struct StoreLock {
+    // Closing the file releases the lock.
+    _file: File,
+}
+
+/// An earlier run that has artifacts in the store, returned by [`RunStore::runs`].
+#[derive(Clone, Debug)]
+pub struct StoredRun {
+    /// The ID of the run.
+    pub run_id: Uuid,
+
+    /// The artifacts of the run, one for each kind.
+    pub artifacts: Vec<StoredArtifact>,
+}
+
+impl StoredRun {
+    /// Returns the total size of the run's artifacts, in bytes.
+    pub fn size(&self) -> u64 {
+        self.artifacts.iter().map(|artifact| artifact.size).sum()
+    }
+
+    /// Returns the last time any of the run's artifacts was modified.
+    pub fn modified(&self) -> SystemTime {
+        self.artifacts
+            .iter()
+            .map(|artifact| artifact.modified)
+            .max()
+            .unwrap_or(SystemTime::UNIX_EPOCH)
+    }
+}
+
+/// The directory that a kind of artifact was written to for a run. Part of a [`StoredRun`].
+#[derive(Clone, Debug)]
+pub struct StoredArtifact {
+    /// The kind of artifact.
+    pub kind: ArtifactKind,
+
+    /// The directory the artifacts were written to.
+    pub path: Utf8PathBuf,
+
+    /// The total size of the files in the directory, in bytes.
+    pub size: u64,
+
+    /// The last time the directory, or anything in it, was modified.
+    pub modified: SystemTime,
+}
+
+/// The runs removed by [`RunStore::gc`].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+#[non_exhaustive]
+pub struct GcSummary {
+    /// The number of runs whose artifacts were removed.
+    pub removed_runs: usize,
+
+    /// The total size of the artifacts that were removed, in bytes.
+    pub bytes_freed: u64,
+}
+
+/// Returns the total size of the files within a path, and the last time anything within it was
+/// modified. Symlinks aren't followed.
+fn disk_usage(path: &Utf8Path) -> io::Result<(u64, SystemTime)> {
+    let metadata = fs::symlink_metadata(path)?;
+    let mut size = if metadata.is_dir() { 0 } else { metadata.len() };
+    let mut modified = metadata.modified()?;
+    if metadata.is_dir() {
+        for entry in path.read_dir_utf8()? {
+            let (entry_size, entry_modified) = disk_usage(entry?.path())?;
+            size += entry_size;
+            modified = modified.max(entry_modified);
+        }
+    }
+    Ok((size, modified))
+}
+
+#[cfg(unix)]
+mod imp {
+    use std::{fs::File, io, os::unix::io::AsRawFd};
+
+    /// Takes an exclusive lock on the file, returning false if it's held elsewhere and `block` is
+    /// false.
+    pub(super) fn lock(file: &File, block: bool) -> io::Result<bool> {
+        let operation = if block {
+            libc::LOCK_EX
+        } else {
+            libc::LOCK_EX | libc::LOCK_NB
+        };
+        loop {
+            // SAFETY: flock has no memory safety requirements.
+            if unsafe { libc::flock(file.as_raw_fd(), operation) } == 0 {
+                return Ok(true);
+            }
+            let error = io::Error::last_os_error();
+            match error.kind() {
+                io::ErrorKind::Interrupted => {}
+                io::ErrorKind::WouldBlock => return Ok(false),
+                _ => return Err(error),
+            }
+        }
+    }
+}
+
+#[cfg(windows)]
+mod imp {
+    use std::{fs::File, io, os::windows::io::AsRawHandle};
+    use winapi::{
+        shared::winerror::ERROR_LOCK_VIOLATION,
+        um::{
+            fileapi::LockFileEx,
+            minwinbase::{LOCKFILE_EXCLUSIVE_LOCK, LOCKFILE_FAIL_IMMEDIATELY, OVERLAPPED},
+        },
+    };
+
+    /// Takes an exclusive lock on the file, returning false if it's held elsewhere and `block` is
+    /// false.
+    pub(super) fn lock(file: &File, block: bool) -> io::Result<bool> {
+        let flags = if block {
+            LOCKFILE_EXCLUSIVE_LOCK
+        } else {
+            LOCKFILE_EXCLUSIVE_LOCK | LOCKFILE_FAIL_IMMEDIATELY
+        };
+        // SAFETY: the handle is valid for as long as the file is open, and the OVERLAPPED
+        // structure is only used for the duration of this call.
+        let ret = unsafe {
+            let mut overlapped: OVERLAPPED = std::mem::zeroed();
+            LockFileEx(file.as_raw_handle() as _, flags, 0, !0, !0, &mut overlapped)
+        };
+        if ret != 0 {
+            return Ok(true);
+        }
+        let error = io::Error::last_os_error();
+        if error.raw_os_error() == Some(ERROR_LOCK_VIOLATION as i32) {
+            Ok(false)
+        } else {
+            Err(error)
+        }
+    }
+}
+
+#[cfg(not(any(unix, windows)))]
+mod imp {
+    use std::{fs::File, io};
+
+    pub(super) fn lock(_file: &File, _block: bool) -> io::Result<bool> {
+        // Locking isn't supported on this platform yet.
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use filetime::FileTime;
+    use std::time::Duration;
+
+    #[test]
+    fn gc_by_age_and_size() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let store =
+            RunStore::new(Utf8Path::from_path(tempdir.path()).expect("temp dir is valid UTF-8"));
+        let now = SystemTime::now();
+        let day = Duration::from_secs(24 * 60 * 60);
+
+        // Runs that are 1, 2, 3 and 10 days old, each with 100 bytes of artifacts.
+        let mut run_ids = vec![];
+        for (index, &age) in [1, 2, 3, 10].iter().enumerate() {
+            let run_id = Uuid::new_v4();
+            let kind = ArtifactKind::ALL[index % ArtifactKind::ALL.len()];
+            let dir = store.run_dir(kind, run_id);
+            fs::create_dir_all(&dir).expect("created run dir");
+            let file = dir.join("artifact");
+            fs::write(&file, vec![0; 100]).expect("wrote artifact");
+            let mtime = FileTime::from_system_time(now - day * age);
+            filetime::set_file_mtime(&file, mtime).expect("set mtime");
+            filetime::set_file_mtime(&dir, mtime).expect("set mtime");
+            run_ids.push(run_id);
+        }
+        // Files that weren't written by nextest are left alone.
+        fs::write(store.dir().join("last-run.json"), "{}").expect("wrote file");
+        fs::create_dir_all(store.dir().join("tmp").join("not-a-run")).expect("created dir");
+
+        let runs = store.runs().expect("listed runs");
+        assert_eq!(
+            runs.iter().map(|run| run.run_id).collect::<Vec<_>>(),
+            run_ids,
+            "runs are listed newest first"
+        );
+        assert_eq!(runs[0].size(), 100);
+
+        let summary = store
+            .gc(StoreRetention::new(Some(day * 7), None), now)
+            .expect("collected garbage");
+        assert_eq!(
+            summary,
+            GcSummary {
+                removed_runs: 1,
+                bytes_freed: 100,
+            }
+        );
+
+        let summary = store
+            .gc(StoreRetention::new(None, Some(250)), now)
+            .expect("collected garbage");
+        assert_eq!(summary.removed_runs, 1);
+        let remaining: Vec<_> = store
+            .runs()
+            .expect("listed runs")
+            .iter()
+            .map(|run| run.run_id)
+            .collect();
+        assert_eq!(remaining, &run_ids[..2], "oldest runs removed first");
+        assert!(store.dir().join("last-run.json").exists());
+        assert!(store.dir().join("tmp").join("not-a-run").exists());
+    }
+
+    #[test]
+    fn lock_excludes_other_holders() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let store =
+            RunStore::new(Utf8Path::from_path(tempdir.path()).expect("temp dir is valid UTF-8"));
+
+        let lock = store.lock().expect("locked store");
+        assert!(
+            store.try_lock().expect("tried to lock store").is_none(),
+            "store is already locked"
+        );
+        drop(lock);
+        assert!(
+            store.try_lock().expect("tried to lock store").is_some(),
+            "lock was released"
+        );
+        assert!(
+            store.runs().expect("listed runs").is_empty(),
+            "lock file isn't mistaken for a run"
+        );
+    }
+}
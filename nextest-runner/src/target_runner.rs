@@ -3,7 +3,7 @@
 
 //! Adds support for [target runners](https://doc.rust-lang.org/cargo/reference/config.html#targettriplerunner)
 
-use crate::errors::TargetRunnerError;
+use crate::{config::WasmConfig, errors::TargetRunnerError};
 use camino::Utf8PathBuf;
 use target_spec::Platform;
 
@@ -25,6 +25,27 @@ impl TargetRunner {
         Self::get_runner_by_precedence(target_triple, true, None)
     }
 
+    /// Returns the runner used for a WebAssembly target if no target runner is configured for it,
+    /// or `None` if the target isn't a WebAssembly target that nextest knows how to run.
+    ///
+    /// Tests built for WASI targets (`wasm32-wasi`, `wasm32-wasip1` and so on) are run through the
+    /// configured WebAssembly runtime, and tests built for `wasm32-unknown-unknown` through
+    /// `wasm-bindgen-test-runner`.
+    pub fn for_wasm_target(
+        target_triple: &str,
+        config: &WasmConfig,
+    ) -> Result<Option<Self>, TargetRunnerError> {
+        let (key, runner) = if target_triple == "wasm32-unknown-unknown" {
+            ("wasm.bindgen-runner", config.bindgen_runner())
+        } else if target_triple.starts_with("wasm32-wasi") {
+            ("wasm.runtime", config.runtime())
+        } else {
+            return Ok(None);
+        };
+
+        Self::parse_runner(key, runner.to_owned()).map(Some)
+    }
+
     /// Configures the root directory that starts the search for cargo configs.
     ///
     /// The default is normally the current working directory, but this method
@@ -7,11 +7,15 @@
 
 use crate::{
     errors::RunIgnoredParseError,
+    filter_expr::FilterExpr,
+    last_run::LastRun,
     partition::{Partitioner, PartitionerBuilder},
+    test_list::RustTestArtifact,
 };
 use aho_corasick::AhoCorasick;
-use nextest_metadata::{FilterMatch, MismatchReason};
-use std::{fmt, str::FromStr};
+use guppy::PackageId;
+use nextest_metadata::{FilterMatch, MismatchReason, RustTestBinaryKind};
+use std::{collections::BTreeSet, fmt, str::FromStr};
 
 /// Whether to run ignored tests.
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
@@ -70,13 +74,48 @@ impl FromStr for RunIgnored {
 pub struct TestFilterBuilder {
     run_ignored: RunIgnored,
     partitioner_builder: Option<PartitionerBuilder>,
+    patterns: Vec<Vec<u8>>,
+    skip_patterns: Vec<Vec<u8>>,
+    exact: bool,
     name_match: NameMatch,
+    skip_match: NameMatch,
+    exprs: Vec<FilterExpr>,
+    default_filter: Option<FilterExpr>,
+    excludes: Vec<FilterExpr>,
+    include_benches: bool,
+    rerun_failed: Option<LastRun>,
+    binary_filter: BinaryFilter,
 }
 
 #[derive(Clone, Debug)]
 enum NameMatch {
-    MatchAll,
-    MatchSet(Box<AhoCorasick>),
+    All,
+    Nothing,
+    Substring(Box<AhoCorasick>),
+    Exact(BTreeSet<Vec<u8>>),
+}
+
+impl NameMatch {
+    /// Matches names that contain any of the patterns, or that are equal to any of them if `exact`
+    /// is true. `if_empty` is used if there are no patterns.
+    fn new(patterns: &[Vec<u8>], exact: bool, if_empty: NameMatch) -> Self {
+        if patterns.is_empty() {
+            if_empty
+        } else if exact {
+            NameMatch::Exact(patterns.iter().cloned().collect())
+        } else {
+            NameMatch::Substring(Box::new(AhoCorasick::new_auto_configured(patterns)))
+        }
+    }
+
+    fn is_match(&self, name: &str) -> bool {
+        match self {
+            NameMatch::All => true,
+            NameMatch::Nothing => false,
+            NameMatch::Substring(set) => set.is_match(name),
+            NameMatch::Exact(names) => names.contains(name.as_bytes()),
+        }
+    }
 }
 
 impl TestFilterBuilder {
@@ -88,15 +127,25 @@ impl TestFilterBuilder {
         partitioner_builder: Option<PartitionerBuilder>,
         patterns: &[impl AsRef<[u8]>],
     ) -> Self {
-        let name_match = if patterns.is_empty() {
-            NameMatch::MatchAll
-        } else {
-            NameMatch::MatchSet(Box::new(AhoCorasick::new_auto_configured(patterns)))
-        };
+        let patterns: Vec<_> = patterns
+            .iter()
+            .map(|pattern| pattern.as_ref().to_vec())
+            .collect();
+        let name_match = NameMatch::new(&patterns, false, NameMatch::All);
         Self {
             run_ignored,
             partitioner_builder,
+            patterns,
+            skip_patterns: vec![],
+            exact: false,
             name_match,
+            skip_match: NameMatch::Nothing,
+            exprs: vec![],
+            default_filter: None,
+            excludes: vec![],
+            include_benches: false,
+            rerun_failed: None,
+            binary_filter: BinaryFilter::default(),
         }
     }
 
@@ -105,35 +154,247 @@ impl TestFilterBuilder {
         Self {
             run_ignored,
             partitioner_builder: None,
-            name_match: NameMatch::MatchAll,
+            patterns: vec![],
+            skip_patterns: vec![],
+            exact: false,
+            name_match: NameMatch::All,
+            skip_match: NameMatch::Nothing,
+            exprs: vec![],
+            default_filter: None,
+            excludes: vec![],
+            include_benches: false,
+            rerun_failed: None,
+            binary_filter: BinaryFilter::default(),
         }
     }
 
-    /// Creates a new test filter scoped to a single binary.
+    /// Skips tests whose names contain any of the given patterns, like libtest's `--skip`.
+    ///
+    /// Skip patterns take precedence over the patterns passed into [`Self::new`].
+    pub fn set_skip_patterns(&mut self, patterns: &[impl AsRef<[u8]>]) -> &mut Self {
+        self.skip_patterns = patterns
+            .iter()
+            .map(|pattern| pattern.as_ref().to_vec())
+            .collect();
+        self.skip_match = NameMatch::new(&self.skip_patterns, self.exact, NameMatch::Nothing);
+        self
+    }
+
+    /// Sets whether test names must be equal to patterns to match them, rather than just contain
+    /// them, like libtest's `--exact`.
+    ///
+    /// This applies to both the patterns passed into [`Self::new`] and skip patterns.
+    pub fn set_exact(&mut self, exact: bool) -> &mut Self {
+        self.exact = exact;
+        self.name_match = NameMatch::new(&self.patterns, exact, NameMatch::All);
+        self.skip_match = NameMatch::new(&self.skip_patterns, exact, NameMatch::Nothing);
+        self
+    }
+
+    /// Only matches tests that match at least one of the given filter expressions.
+    ///
+    /// Tests must match the string filters as well. If no expressions are passed in, all tests
+    /// match.
+    pub fn set_exprs(&mut self, exprs: impl IntoIterator<Item = FilterExpr>) -> &mut Self {
+        self.exprs = exprs.into_iter().collect();
+        self
+    }
+
+    /// Only matches tests that match the given default filter, typically from the profile.
+    ///
+    /// Tests that don't match are skipped with [`MismatchReason::DefaultFilter`], so that they
+    /// can be told apart from tests skipped for other reasons. Callers are expected to only set
+    /// a default filter if no other filters were passed in explicitly.
+    pub fn set_default_filter(&mut self, default_filter: FilterExpr) -> &mut Self {
+        self.default_filter = Some(default_filter);
+        self
+    }
+
+    /// Never matches tests that match any of the given filter expressions, typically from the
+    /// `[[exclude]]` sections in the configuration.
+    ///
+    /// Unlike the default filter, exclusions apply even if other filters are passed in. Excluded
+    /// tests are skipped with [`MismatchReason::Excluded`].
+    pub fn set_excludes(&mut self, excludes: impl IntoIterator<Item = FilterExpr>) -> &mut Self {
+        self.excludes = excludes.into_iter().collect();
+        self
+    }
+
+    /// Sets whether benchmarks (`#[bench]` functions) are included in the test list.
+    ///
+    /// Included benchmarks are run once each in test mode, like ordinary tests. By default,
+    /// benchmarks are left out of the test list.
+    pub fn set_include_benches(&mut self, include_benches: bool) -> &mut Self {
+        self.include_benches = include_benches;
+        self
+    }
+
+    /// Returns true if benchmarks are included in the test list.
+    pub fn include_benches(&self) -> bool {
+        self.include_benches
+    }
+
+    /// Only matches tests that failed in the given run.
+    ///
+    /// Tests are partitioned after this filter is applied, so that failed tests are spread evenly
+    /// across partitions.
+    pub fn set_rerun_failed(&mut self, last_run: LastRun) -> &mut Self {
+        self.rerun_failed = Some(last_run);
+        self
+    }
+
+    /// Only matches tests in the packages with the given names.
+    ///
+    /// Like the other binary filters, this is applied before binaries are queried for their tests:
+    /// binaries in other packages aren't listed or run at all. If no packages are passed in, tests
+    /// in all packages match.
+    pub fn set_packages(
+        &mut self,
+        packages: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.binary_filter.packages = packages.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only matches tests in binaries of the given kinds, e.g. only unit tests of libraries.
+    ///
+    /// If no kinds are passed in, tests in binaries of all kinds match.
+    pub fn set_kinds(&mut self, kinds: impl IntoIterator<Item = RustTestBinaryKind>) -> &mut Self {
+        self.binary_filter.kinds = kinds.into_iter().collect();
+        self
+    }
+
+    /// Only matches tests in the binaries with the given IDs.
+    ///
+    /// If no binary IDs are passed in, tests in all binaries match.
+    pub fn set_binary_ids(
+        &mut self,
+        binary_ids: impl IntoIterator<Item = impl Into<String>>,
+    ) -> &mut Self {
+        self.binary_filter.binary_ids = binary_ids.into_iter().map(Into::into).collect();
+        self
+    }
+
+    /// Only matches tests in the given packages, for example the ones returned by
+    /// [`AffectedPackages`](crate::affected::AffectedPackages).
+    ///
+    /// Unlike [`Self::set_packages`], this is combined with the other binary filters: a test must be
+    /// in one of these packages as well as matching them.
+    pub fn set_affected_packages(
+        &mut self,
+        package_ids: impl IntoIterator<Item = PackageId>,
+    ) -> &mut Self {
+        self.binary_filter.affected = Some(package_ids.into_iter().collect());
+        self
+    }
+
+    /// Returns true if tests in the given binary can match this filter.
+    ///
+    /// Tests in binaries for which this returns false are skipped with [`MismatchReason::Binary`].
+    pub fn matches_binary(&self, test_binary: &RustTestArtifact<'_>) -> bool {
+        let BinaryFilter {
+            packages,
+            kinds,
+            binary_ids,
+            affected,
+        } = &self.binary_filter;
+        (packages.is_empty()
+            || packages
+                .iter()
+                .any(|name| name == test_binary.package.name()))
+            && (kinds.is_empty() || kinds.contains(&test_binary.kind))
+            && (binary_ids.is_empty() || binary_ids.contains(&test_binary.binary_id))
+            && affected
+                .as_ref()
+                .map_or(true, |affected| affected.contains(test_binary.package.id()))
+    }
+
+    /// Creates a new test filter scoped to the given binary.
     ///
     /// This test filter may be stateful.
-    pub fn build(&self) -> TestFilter<'_> {
+    pub fn build<'filter>(
+        &'filter self,
+        test_binary: &'filter RustTestArtifact<'_>,
+    ) -> TestFilter<'filter> {
+        self.build_impl(
+            test_binary.package.name(),
+            &test_binary.binary_id,
+            self.matches_binary(test_binary),
+        )
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    fn build_impl<'filter>(
+        &'filter self,
+        package_name: &'filter str,
+        binary_id: &'filter str,
+        binary_match: bool,
+    ) -> TestFilter<'filter> {
         let partitioner = self
             .partitioner_builder
             .as_ref()
-            .map(|partitioner_builder| partitioner_builder.build());
+            .map(|partitioner_builder| partitioner_builder.build(binary_id));
         TestFilter {
             builder: self,
+            package_name,
+            binary_id,
+            binary_match,
             partitioner,
         }
     }
 }
 
+/// Filters on test binaries, as opposed to individual tests. Empty lists match everything.
+#[derive(Clone, Debug, Default)]
+struct BinaryFilter {
+    packages: Vec<String>,
+    kinds: Vec<RustTestBinaryKind>,
+    binary_ids: Vec<String>,
+    // Unlike the lists above, an empty set matches nothing: no packages were affected.
+    affected: Option<BTreeSet<PackageId>>,
+}
+
 /// Test filter, scoped to a single binary.
 #[derive(Debug)]
-pub struct TestFilter<'builder> {
-    builder: &'builder TestFilterBuilder,
+pub struct TestFilter<'filter> {
+    builder: &'filter TestFilterBuilder,
+    package_name: &'filter str,
+    binary_id: &'filter str,
+    binary_match: bool,
     partitioner: Option<Box<dyn Partitioner>>,
 }
 
 impl<'filter> TestFilter<'filter> {
     /// Returns an enum describing the match status of this filter.
     pub fn filter_match(&mut self, test_name: &str, ignored: bool) -> FilterMatch {
+        if !self.binary_match {
+            return FilterMatch::Mismatch {
+                reason: MismatchReason::Binary,
+            };
+        }
+
+        if self
+            .builder
+            .excludes
+            .iter()
+            .any(|expr| expr.matches_parts(self.package_name, self.binary_id, test_name))
+        {
+            return FilterMatch::Mismatch {
+                reason: MismatchReason::Excluded,
+            };
+        }
+
+        if let Some(default_filter) = &self.builder.default_filter {
+            if !default_filter.matches_parts(self.package_name, self.binary_id, test_name) {
+                return FilterMatch::Mismatch {
+                    reason: MismatchReason::DefaultFilter,
+                };
+            }
+        }
+
         match self.builder.run_ignored {
             RunIgnored::IgnoredOnly => {
                 if !ignored {
@@ -152,16 +413,33 @@ impl<'filter> TestFilter<'filter> {
             _ => {}
         };
 
-        let string_match = match &self.builder.name_match {
-            NameMatch::MatchAll => true,
-            NameMatch::MatchSet(set) => set.is_match(test_name),
-        };
+        let string_match = self.builder.name_match.is_match(test_name)
+            && !self.builder.skip_match.is_match(test_name);
         if !string_match {
             return FilterMatch::Mismatch {
                 reason: MismatchReason::String,
             };
         }
 
+        let exprs = &self.builder.exprs;
+        if !exprs.is_empty()
+            && !exprs
+                .iter()
+                .any(|expr| expr.matches_parts(self.package_name, self.binary_id, test_name))
+        {
+            return FilterMatch::Mismatch {
+                reason: MismatchReason::Expression,
+            };
+        }
+
+        if let Some(last_run) = &self.builder.rerun_failed {
+            if !last_run.is_failed(self.binary_id, test_name) {
+                return FilterMatch::Mismatch {
+                    reason: MismatchReason::NotFailed,
+                };
+            }
+        }
+
         let partition_match = match &mut self.partitioner {
             Some(partitioner) => partitioner.test_matches(test_name),
             None => true,
@@ -186,7 +464,7 @@ mod tests {
         fn proptest_empty(test_names in vec(any::<String>(), 0..16)) {
             let patterns: &[String] = &[];
             let test_filter = TestFilterBuilder::new(RunIgnored::Default, None, patterns);
-            let mut single_filter = test_filter.build();
+            let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
             for test_name in test_names {
                 prop_assert!(single_filter.filter_match(&test_name, false).is_match());
             }
@@ -196,7 +474,7 @@ mod tests {
         #[test]
         fn proptest_exact(test_names in vec(any::<String>(), 0..16)) {
             let test_filter = TestFilterBuilder::new(RunIgnored::Default, None, &test_names);
-            let mut single_filter = test_filter.build();
+            let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
             for test_name in test_names {
                 prop_assert!(single_filter.filter_match(&test_name, false).is_match());
             }
@@ -215,7 +493,7 @@ mod tests {
             }
 
             let test_filter = TestFilterBuilder::new(RunIgnored::Default, None, &patterns);
-            let mut single_filter = test_filter.build();
+            let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
             for test_name in test_names {
                 prop_assert!(single_filter.filter_match(&test_name, false).is_match());
             }
@@ -231,11 +509,170 @@ mod tests {
             prop_assume!(!substring.is_empty() && !(prefix.is_empty() && suffix.is_empty()));
             let pattern = prefix + &substring + &suffix;
             let test_filter = TestFilterBuilder::new(RunIgnored::Default, None, &[&pattern]);
-            let mut single_filter = test_filter.build();
+            let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
             prop_assert!(!single_filter.filter_match(&substring, false).is_match());
         }
     }
 
+    #[test]
+    fn rerun_failed() {
+        let last_run: LastRun =
+            serde_json::from_str(r#"{ "failed": { "my-binary": ["tests::a", "tests::b"] } }"#)
+                .expect("valid JSON");
+        let mut test_filter = TestFilterBuilder::new(RunIgnored::Default, None, &["tests::a"]);
+        test_filter.set_rerun_failed(last_run);
+
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(single_filter.filter_match("tests::a", false).is_match());
+        assert_eq!(
+            single_filter.filter_match("tests::b", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::String
+            },
+            "string filters still apply"
+        );
+        assert_eq!(
+            single_filter.filter_match("tests::a2", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::NotFailed
+            }
+        );
+
+        let mut other_filter = test_filter.build_impl("my-package", "other-binary", true);
+        assert_eq!(
+            other_filter.filter_match("tests::a", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::NotFailed
+            },
+            "failed tests are scoped to their binary"
+        );
+    }
+
+    #[test]
+    fn skip_and_exact() {
+        let mut test_filter = TestFilterBuilder::new(RunIgnored::Default, None, &["tests::a"]);
+        test_filter.set_skip_patterns(&["slow"]);
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(single_filter.filter_match("tests::a", false).is_match());
+        assert!(single_filter.filter_match("tests::ab", false).is_match());
+        assert_eq!(
+            single_filter.filter_match("tests::a_slow", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::String
+            },
+            "skip patterns take precedence"
+        );
+
+        test_filter.set_exact(true);
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(single_filter.filter_match("tests::a", false).is_match());
+        assert!(!single_filter.filter_match("tests::ab", false).is_match());
+
+        // Skip patterns are exact too, and apply even without positive patterns.
+        let mut test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        test_filter.set_exact(true).set_skip_patterns(&["tests::a"]);
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(!single_filter.filter_match("tests::a", false).is_match());
+        assert!(single_filter.filter_match("tests::ab", false).is_match());
+    }
+
+    #[test]
+    fn filter_exprs() {
+        let mut test_filter = TestFilterBuilder::new(RunIgnored::Default, None, &["tests::"]);
+        test_filter.set_exprs(vec![
+            FilterExpr::parse("test(a)").expect("valid expression"),
+            FilterExpr::parse("package(other-package)").expect("valid expression"),
+        ]);
+
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(single_filter.filter_match("tests::a", false).is_match());
+        assert_eq!(
+            single_filter.filter_match("tests::b", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::Expression
+            }
+        );
+        assert_eq!(
+            single_filter.filter_match("other::a", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::String
+            },
+            "string filters still apply"
+        );
+
+        let mut other_filter = test_filter.build_impl("other-package", "other-binary", true);
+        assert!(
+            other_filter.filter_match("tests::b", false).is_match(),
+            "any expression can match"
+        );
+
+        let mut excluded_filter = test_filter.build_impl("other-package", "other-binary", false);
+        assert_eq!(
+            excluded_filter.filter_match("tests::a", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::Binary
+            },
+        );
+    }
+
+    #[test]
+    fn default_filter() {
+        let mut test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        test_filter.set_default_filter(
+            FilterExpr::parse("not package(slow-e2e)").expect("valid expression"),
+        );
+
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(single_filter.filter_match("tests::a", false).is_match());
+
+        let mut slow_filter = test_filter.build_impl("slow-e2e", "slow-e2e", true);
+        for ignored in [false, true] {
+            assert_eq!(
+                slow_filter.filter_match("tests::a", ignored),
+                FilterMatch::Mismatch {
+                    reason: MismatchReason::DefaultFilter
+                },
+                "default filter takes precedence over run-ignored"
+            );
+        }
+    }
+
+    #[test]
+    fn excludes() {
+        let mut test_filter = TestFilterBuilder::new(RunIgnored::All, None, &["tests::"]);
+        test_filter
+            .set_excludes(vec![
+                FilterExpr::parse("test(flaky)").expect("valid expression")
+            ])
+            .set_default_filter(FilterExpr::parse("none()").expect("valid expression"));
+
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert_eq!(
+            single_filter.filter_match("tests::flaky", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::Excluded
+            },
+            "exclusions take precedence over the default filter"
+        );
+        assert_eq!(
+            single_filter.filter_match("tests::a", false),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::DefaultFilter
+            },
+        );
+
+        test_filter.default_filter = None;
+        let mut single_filter = test_filter.build_impl("my-package", "my-binary", true);
+        assert!(single_filter.filter_match("tests::a", true).is_match());
+        assert_eq!(
+            single_filter.filter_match("tests::flaky", true),
+            FilterMatch::Mismatch {
+                reason: MismatchReason::Excluded
+            },
+            "exclusions apply alongside explicit filters"
+        );
+    }
+
     // /// Creates a fake test binary instance.
     // fn make_test_binary() -> TestBinary {
     //     TestBinary {
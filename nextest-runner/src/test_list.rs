@@ -9,22 +9,41 @@ mod output_format;
 pub use output_format::*;
 
 use crate::{
-    errors::{FromMessagesError, ParseTestListError, WriteTestListError},
+    config::WrapperCommand,
+    errors::{FromBinaryListError, FromMessagesError, ParseTestListError, WriteTestListError},
     helpers::write_test_name,
+    list_cache::ListCache,
+    partition::balance_by_duration,
+    remote::SshRunner,
     target_runner::TargetRunner,
     test_filter::TestFilterBuilder,
+    timings::TestTimings,
 };
 use camino::{Utf8Path, Utf8PathBuf};
 use cargo_metadata::Message;
 use duct::{cmd, Expression};
 use guppy::{
-    graph::{PackageGraph, PackageMetadata},
+    graph::{BuildTargetId, BuildTargetKind, PackageGraph, PackageMetadata},
     PackageId,
 };
-use nextest_metadata::{RustTestCaseSummary, RustTestSuiteSummary, TestListSummary};
+use nextest_metadata::{
+    BinaryListSummary, BuildPlatform, BuildProfile, FilterMatch, MismatchReason,
+    RustTestBinaryKind, RustTestBinarySummary, RustTestCaseSummary, RustTestHarness,
+    RustTestSuiteSummary, TestListSummary,
+};
 use once_cell::sync::OnceCell;
 use owo_colors::{OwoColorize, Style};
-use std::{collections::BTreeMap, io, io::Write};
+use rayon::{prelude::*, ThreadPoolBuilder};
+use serde::Deserialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    ffi::OsString,
+    io,
+    io::Write,
+};
+
+/// The number of test binaries listed at once on a remote host.
+const REMOTE_LIST_THREADS: usize = 4;
 
 /// A Rust test binary built by Cargo. This artifact hasn't been run yet so there's no information
 /// about the tests within it.
@@ -48,6 +67,27 @@ pub struct RustTestArtifact<'g> {
     /// The working directory that this test should be executed in. If None, the current directory
     /// will not be changed.
     pub cwd: Utf8PathBuf,
+
+    /// The platform this binary was built for.
+    pub build_platform: BuildPlatform,
+
+    /// The test harness this binary was built with.
+    pub harness: RustTestHarness,
+
+    /// Environment variables to set when running this binary, on top of the ones nextest always
+    /// sets. Empty for binaries built by Cargo.
+    pub env: BTreeMap<String, String>,
+
+    /// The kind of build target this binary was built from.
+    pub kind: RustTestBinaryKind,
+
+    /// The features that must be enabled for this binary to be built, as specified with
+    /// `required-features` in `Cargo.toml`.
+    pub required_features: Vec<String>,
+
+    /// The settings of the Cargo profile this binary was built with, or `None` if they aren't
+    /// known.
+    pub profile: Option<BuildProfile>,
 }
 
 impl<'g> RustTestArtifact<'g> {
@@ -57,6 +97,7 @@ impl<'g> RustTestArtifact<'g> {
         reader: impl io::BufRead,
     ) -> Result<Vec<Self>, FromMessagesError> {
         let mut binaries = vec![];
+        let mut manifests = HashMap::new();
 
         for message in Message::parse_stream(reader) {
             let message = message.map_err(FromMessagesError::ReadMessages)?;
@@ -81,6 +122,12 @@ impl<'g> RustTestArtifact<'g> {
                             })
                             .to_path_buf();
 
+                        let harness = detect_harness(
+                            &mut manifests,
+                            package.manifest_path(),
+                            &artifact.target,
+                        )?;
+
                         // Construct the binary ID from the package and build target.
                         let mut binary_id = package.name().to_owned();
                         if artifact.target.name != package.name() {
@@ -88,12 +135,34 @@ impl<'g> RustTestArtifact<'g> {
                             binary_id.push_str(&artifact.target.name);
                         }
 
+                        // Procedural macros are run by the compiler, so their tests are built for
+                        // the host platform.
+                        let kind = binary_kind(&artifact.target.kind);
+                        let build_platform = if kind == RustTestBinaryKind::ProcMacro {
+                            BuildPlatform::Host
+                        } else {
+                            BuildPlatform::Target
+                        };
+
+                        let profile = BuildProfile {
+                            opt_level: artifact.profile.opt_level,
+                            debuginfo: artifact.profile.debuginfo,
+                            debug_assertions: artifact.profile.debug_assertions,
+                            overflow_checks: artifact.profile.overflow_checks,
+                        };
+
                         binaries.push(RustTestArtifact {
                             binary_id,
                             package,
                             binary_path: binary,
                             binary_name: artifact.target.name,
                             cwd,
+                            build_platform,
+                            harness,
+                            env: BTreeMap::new(),
+                            kind,
+                            required_features: artifact.target.required_features,
+                            profile: Some(profile),
                         })
                     }
                 }
@@ -105,6 +174,206 @@ impl<'g> RustTestArtifact<'g> {
 
         Ok(binaries)
     }
+
+    /// Constructs a list of test binaries from a previously generated [`BinaryListSummary`].
+    ///
+    /// This does not require Cargo to be present, as long as the package graph can be constructed.
+    pub fn from_binary_list(
+        graph: &'g PackageGraph,
+        binary_list: &BinaryListSummary,
+    ) -> Result<Vec<Self>, FromBinaryListError> {
+        binary_list
+            .rust_binaries
+            .iter()
+            .map(|(binary_id, summary)| {
+                let package_id = PackageId::new(summary.package_id.clone());
+                let package = graph
+                    .metadata(&package_id)
+                    .map_err(FromBinaryListError::PackageGraph)?;
+                let (kind, required_features) = match summary.kind {
+                    Some(kind) => (kind, summary.required_features.clone()),
+                    None => target_info(package, &summary.binary_name),
+                };
+                Ok(RustTestArtifact {
+                    binary_id: binary_id.clone(),
+                    package,
+                    binary_path: summary.binary_path.clone(),
+                    binary_name: summary.binary_name.clone(),
+                    cwd: summary.cwd.clone(),
+                    build_platform: summary.build_platform,
+                    harness: summary.harness,
+                    env: BTreeMap::new(),
+                    kind,
+                    required_features,
+                    profile: summary.profile.clone(),
+                })
+            })
+            .collect()
+    }
+
+    /// Constructs a serializable summary for this test binary.
+    pub fn to_summary(&self) -> RustTestBinarySummary {
+        RustTestBinarySummary {
+            binary_name: self.binary_name.clone(),
+            package_id: self.package.id().repr().to_owned(),
+            binary_path: self.binary_path.clone(),
+            cwd: self.cwd.clone(),
+            build_platform: self.build_platform,
+            harness: self.harness,
+            kind: Some(self.kind),
+            required_features: self.required_features.clone(),
+            profile: self.profile.clone(),
+        }
+    }
+}
+
+/// Returns the kind of a test binary, given the kinds Cargo reports for its build target.
+fn binary_kind(cargo_kinds: &[String]) -> RustTestBinaryKind {
+    // Libraries are reported with their crate types, e.g. "rlib" or "cdylib", so anything that
+    // isn't one of the other kinds is a library.
+    let has_kind = |kind: &str| cargo_kinds.iter().any(|k| k == kind);
+    if has_kind("test") {
+        RustTestBinaryKind::Test
+    } else if has_kind("bench") {
+        RustTestBinaryKind::Bench
+    } else if has_kind("bin") {
+        RustTestBinaryKind::Bin
+    } else if has_kind("example") {
+        RustTestBinaryKind::Example
+    } else if has_kind("proc-macro") {
+        RustTestBinaryKind::ProcMacro
+    } else {
+        RustTestBinaryKind::Lib
+    }
+}
+
+/// Looks up the kind and required features of a test binary's build target in the package graph.
+///
+/// Binary lists produced by older versions of nextest don't record these.
+fn target_info(
+    package: PackageMetadata<'_>,
+    binary_name: &str,
+) -> (RustTestBinaryKind, Vec<String>) {
+    let target = package
+        .build_targets()
+        .find(|target| target.name() == binary_name && target.id() != BuildTargetId::BuildScript);
+    let target = match target {
+        Some(target) => target,
+        // The binary list doesn't match the graph. Running the binary will fail anyway, so the
+        // kind doesn't matter much.
+        None => return (RustTestBinaryKind::Test, vec![]),
+    };
+    let kind = match target.id() {
+        BuildTargetId::Library if target.kind() == BuildTargetKind::ProcMacro => {
+            RustTestBinaryKind::ProcMacro
+        }
+        BuildTargetId::Binary(_) => RustTestBinaryKind::Bin,
+        BuildTargetId::Example(_) => RustTestBinaryKind::Example,
+        BuildTargetId::Test(_) => RustTestBinaryKind::Test,
+        BuildTargetId::Benchmark(_) => RustTestBinaryKind::Bench,
+        _ => RustTestBinaryKind::Lib,
+    };
+    (kind, target.required_features().to_vec())
+}
+
+/// List of test binaries built by Cargo, without information about the tests within them.
+///
+/// This is output by `cargo nextest list --list-type binaries-only`.
+#[derive(Clone, Debug)]
+pub struct BinaryList<'g> {
+    rust_binaries: Vec<RustTestArtifact<'g>>,
+    styles: Box<Styles>,
+}
+
+impl<'g> BinaryList<'g> {
+    /// Creates a new binary list from the given test artifacts.
+    pub fn new(rust_binaries: Vec<RustTestArtifact<'g>>) -> Self {
+        Self {
+            rust_binaries,
+            styles: Box::new(Styles::default()),
+        }
+    }
+
+    /// Colorizes output.
+    pub fn colorize(&mut self) {
+        self.styles.colorize();
+    }
+
+    /// Iterates over the test binaries in this list.
+    pub fn iter(&self) -> impl Iterator<Item = &RustTestArtifact<'g>> + '_ {
+        self.rust_binaries.iter()
+    }
+
+    /// Constructs a serializable summary for this binary list.
+    pub fn to_summary(&self) -> BinaryListSummary {
+        let mut summary = BinaryListSummary::new();
+        summary.rust_binaries = self
+            .rust_binaries
+            .iter()
+            .map(|artifact| (artifact.binary_id.clone(), artifact.to_summary()))
+            .collect();
+        summary
+    }
+
+    /// Outputs this list to the given writer.
+    pub fn write(
+        &self,
+        output_format: OutputFormat,
+        writer: impl Write,
+    ) -> Result<(), WriteTestListError> {
+        match output_format {
+            OutputFormat::Human { verbose } => self
+                .write_human(writer, verbose)
+                .map_err(WriteTestListError::Io),
+            OutputFormat::Serializable(format) => format
+                .to_writer(&self.to_summary(), writer)
+                .map_err(WriteTestListError::Json),
+        }
+    }
+
+    fn write_human(&self, mut writer: impl Write, verbose: bool) -> io::Result<()> {
+        let mut rust_binaries: Vec<_> = self.rust_binaries.iter().collect();
+        rust_binaries.sort_by(|a, b| a.binary_id.cmp(&b.binary_id));
+        for artifact in rust_binaries {
+            writeln!(
+                writer,
+                "{}",
+                artifact.binary_id.style(self.styles.binary_id)
+            )?;
+            if verbose {
+                writeln!(
+                    writer,
+                    "  {} {}",
+                    "bin:".style(self.styles.field),
+                    artifact.binary_path
+                )?;
+                writeln!(
+                    writer,
+                    "  {} {}",
+                    "cwd:".style(self.styles.field),
+                    artifact.cwd
+                )?;
+                writeln!(
+                    writer,
+                    "  {} {}",
+                    "kind:".style(self.styles.field),
+                    artifact.kind
+                )?;
+                if !artifact.required_features.is_empty() {
+                    writeln!(
+                        writer,
+                        "  {} {}",
+                        "required-features:".style(self.styles.field),
+                        artifact.required_features.join(", ")
+                    )?;
+                }
+                if artifact.build_platform == BuildPlatform::Host {
+                    writeln!(writer, "  {} host", "platform:".style(self.styles.field))?;
+                }
+            }
+        }
+        Ok(())
+    }
 }
 
 /// List of test instances, obtained by querying the [`RustTestArtifact`] instances generated by Cargo.
@@ -135,29 +404,95 @@ pub struct RustTestSuite<'g> {
     /// will not be changed.
     pub cwd: Utf8PathBuf,
 
+    /// The platform this binary was built for.
+    ///
+    /// Binaries built for the host platform are run directly, rather than through the target
+    /// runner or on a remote host.
+    pub build_platform: BuildPlatform,
+
+    /// The test harness this binary is run with.
+    ///
+    /// If a binary with a custom harness couldn't be queried for its tests, this is
+    /// [`RustTestHarness::Opaque`].
+    pub harness: RustTestHarness,
+
+    /// Environment variables to set when running this binary, on top of the ones nextest always
+    /// sets.
+    pub env: BTreeMap<String, String>,
+
     /// Test case names and other information about them.
     pub testcases: BTreeMap<String, RustTestCaseSummary>,
 }
 
 impl<'g> TestList<'g> {
     /// Creates a new test list by running the given command and applying the specified filter.
+    ///
+    /// If a cache is passed in, binaries that haven't changed since they were cached aren't run,
+    /// and the list output of binaries that are run is added to it.
     pub fn new(
         test_artifacts: impl IntoIterator<Item = RustTestArtifact<'g>>,
         filter: &TestFilterBuilder,
         runner: Option<&TargetRunner>,
+        cache: Option<&ListCache>,
+    ) -> Result<Self, ParseTestListError> {
+        Self::new_impl(test_artifacts, filter, runner, None, cache)
+    }
+
+    /// Creates a new test list by running the given command on a remote host, and applying the
+    /// specified filter.
+    ///
+    /// The binaries must have been copied to the remote host by [`SshRunner`]. The cache is used
+    /// in the same way as for [`Self::new`].
+    pub fn new_remote(
+        test_artifacts: impl IntoIterator<Item = RustTestArtifact<'g>>,
+        filter: &TestFilterBuilder,
+        remote: &SshRunner,
+        cache: Option<&ListCache>,
+    ) -> Result<Self, ParseTestListError> {
+        Self::new_impl(test_artifacts, filter, None, Some(remote), cache)
+    }
+
+    fn new_impl(
+        test_artifacts: impl IntoIterator<Item = RustTestArtifact<'g>>,
+        filter: &TestFilterBuilder,
+        runner: Option<&TargetRunner>,
+        remote: Option<&SshRunner>,
+        cache: Option<&ListCache>,
     ) -> Result<Self, ParseTestListError> {
         let mut test_count = 0;
 
+        // Listing the tests in a binary means running it twice, which adds up in workspaces with
+        // many test binaries: list several binaries at once. Hosts may refuse SSH connections if
+        // too many are being set up at the same time, so fewer binaries are listed at once there.
+        let list_threads = match remote {
+            Some(_) => REMOTE_LIST_THREADS,
+            None => num_cpus::get(),
+        };
+        let list_pool = ThreadPoolBuilder::new()
+            .num_threads(list_threads)
+            .thread_name(|idx| format!("nextest-list-{}", idx))
+            .build()
+            .expect("list pool built");
+        let test_artifacts: Vec<_> = test_artifacts.into_iter().collect();
+        let outputs: Vec<_> = list_pool.install(|| {
+            test_artifacts
+                .par_iter()
+                .map(|test_binary| {
+                    if test_binary.harness == RustTestHarness::Opaque {
+                        // Opaque binaries aren't queried for the tests they contain.
+                        Ok(Default::default())
+                    } else {
+                        test_binary.exec(runner, remote, cache)
+                    }
+                })
+                .collect()
+        });
+
         let test_artifacts = test_artifacts
             .into_iter()
-            .map(|test_binary| {
-                let (non_ignored, ignored) = test_binary.exec(runner)?;
-                let (bin, info) = Self::process_output(
-                    test_binary,
-                    filter,
-                    non_ignored.as_str(),
-                    ignored.as_str(),
-                )?;
+            .zip(outputs)
+            .map(|(test_binary, outputs)| {
+                let (bin, info) = Self::process_listed(test_binary, filter, outputs)?;
                 test_count += info.testcases.len();
                 Ok((bin, info))
             })
@@ -183,12 +518,8 @@ impl<'g> TestList<'g> {
         let test_artifacts = test_bin_outputs
             .into_iter()
             .map(|(test_binary, non_ignored, ignored)| {
-                let (bin, info) = Self::process_output(
-                    test_binary,
-                    filter,
-                    non_ignored.as_ref(),
-                    ignored.as_ref(),
-                )?;
+                let (bin, info) =
+                    Self::process_listed(test_binary, filter, Ok((non_ignored, ignored)))?;
                 test_count += info.testcases.len();
                 Ok((bin, info))
             })
@@ -202,6 +533,44 @@ impl<'g> TestList<'g> {
         })
     }
 
+    /// Reconstructs a test list from its serializable summary, as produced by
+    /// [`Self::to_summary`] or `cargo nextest list --message-format json`.
+    ///
+    /// The test binaries aren't run, so they don't need to be present: this allows a test list to
+    /// be generated on one machine and inspected on another. The packages in the summary are
+    /// looked up in the given package graph, which must be for the same workspace.
+    pub fn from_summary(
+        graph: &'g PackageGraph,
+        summary: &TestListSummary,
+    ) -> Result<Self, guppy::Error> {
+        let rust_suites = summary
+            .rust_suites
+            .iter()
+            .map(|(binary_id, suite)| {
+                let package_id = PackageId::new(suite.package_id.clone());
+                let package = graph.metadata(&package_id)?;
+                let info = RustTestSuite {
+                    binary_id: binary_id.clone(),
+                    package,
+                    binary_name: suite.binary_name.clone(),
+                    cwd: suite.cwd.clone(),
+                    build_platform: suite.build_platform,
+                    harness: suite.harness,
+                    env: BTreeMap::new(),
+                    testcases: suite.testcases.clone(),
+                };
+                Ok((suite.binary_path.clone(), info))
+            })
+            .collect::<Result<BTreeMap<_, _>, _>>()?;
+
+        Ok(Self {
+            test_count: summary.test_count,
+            rust_suites,
+            styles: Box::new(Styles::default()),
+            skip_count: OnceCell::new(),
+        })
+    }
+
     /// Colorizes output.
     pub fn colorize(&mut self) {
         self.styles.colorize();
@@ -221,6 +590,34 @@ impl<'g> TestList<'g> {
         })
     }
 
+    /// Returns the number of tests skipped because they don't match the profile's default filter.
+    ///
+    /// These are included in [`Self::skip_count`].
+    pub fn default_filter_skip_count(&self) -> usize {
+        self.iter_tests()
+            .filter(|instance| {
+                instance.test_info.filter_match
+                    == FilterMatch::Mismatch {
+                        reason: MismatchReason::DefaultFilter,
+                    }
+            })
+            .count()
+    }
+
+    /// Returns the number of tests skipped because they're excluded through the configuration.
+    ///
+    /// These are included in [`Self::skip_count`].
+    pub fn excluded_count(&self) -> usize {
+        self.iter_tests()
+            .filter(|instance| {
+                instance.test_info.filter_match
+                    == FilterMatch::Mismatch {
+                        reason: MismatchReason::Excluded,
+                    }
+            })
+            .count()
+    }
+
     /// Returns the total number of tests that aren't skipped.
     ///
     /// It is always the case that `run_count + skip_count == test_count`.
@@ -233,6 +630,44 @@ impl<'g> TestList<'g> {
         self.rust_suites.len()
     }
 
+    /// Partitions the tests to run into shards of about the same expected duration, and skips the
+    /// tests that aren't in the given shard.
+    ///
+    /// This implements [`PartitionerBuilder::Duration`](crate::partition::PartitionerBuilder::Duration).
+    /// Durations are taken from the timings recorded by previous runs. Every shard must be passed
+    /// the same timings, or tests may be run by more than one shard or by none at all.
+    pub fn partition_by_duration(&mut self, shard: u64, total_shards: u64, timings: &TestTimings) {
+        // Binary paths differ between machines that extract the same archive, so order tests by
+        // binary ID instead.
+        let mut tests: Vec<_> = self
+            .rust_suites
+            .values_mut()
+            .flat_map(|suite| {
+                let binary_id = suite.binary_id.as_str();
+                suite
+                    .testcases
+                    .iter_mut()
+                    .filter(|(_, test_info)| test_info.filter_match.is_match())
+                    .map(move |(name, test_info)| (binary_id, name.as_str(), test_info))
+            })
+            .collect();
+        tests.sort_by(|(a_id, a_name, _), (b_id, b_name, _)| (a_id, a_name).cmp(&(b_id, b_name)));
+
+        let durations: Vec<_> = tests
+            .iter()
+            .map(|(binary_id, name, _)| timings.duration(binary_id, name))
+            .collect();
+        let shards = balance_by_duration(&durations, total_shards);
+        for ((_, _, test_info), test_shard) in tests.into_iter().zip(shards) {
+            if test_shard != shard {
+                test_info.filter_match = FilterMatch::Mismatch {
+                    reason: MismatchReason::Partition,
+                };
+            }
+        }
+        self.skip_count = OnceCell::new();
+    }
+
     /// Returns the tests for a given binary, or `None` if the binary wasn't in the list.
     pub fn get(&self, test_bin: impl AsRef<Utf8Path>) -> Option<&RustTestSuite> {
         self.rust_suites.get(test_bin.as_ref())
@@ -250,6 +685,8 @@ impl<'g> TestList<'g> {
                     package_id: info.package.id().repr().to_owned(),
                     binary_path: binary_path.clone(),
                     cwd: info.cwd.clone(),
+                    build_platform: info.build_platform,
+                    harness: info.harness,
                     testcases: info.testcases.clone(),
                 };
                 (info.binary_id.clone(), testsuite)
@@ -326,8 +763,9 @@ impl<'g> TestList<'g> {
 
         // Treat ignored and non-ignored as separate sets of single filters, so that partitioning
         // based on one doesn't affect the other.
-        let mut non_ignored_filter = filter.build();
-        for test_name in Self::parse(non_ignored.as_ref())? {
+        let mut non_ignored_filter = filter.build(&test_binary);
+        let include_benches = filter.include_benches();
+        for test_name in Self::parse(non_ignored.as_ref(), include_benches)? {
             tests.insert(
                 test_name.into(),
                 RustTestCaseSummary {
@@ -337,8 +775,8 @@ impl<'g> TestList<'g> {
             );
         }
 
-        let mut ignored_filter = filter.build();
-        for test_name in Self::parse(ignored.as_ref())? {
+        let mut ignored_filter = filter.build(&test_binary);
+        for test_name in Self::parse(ignored.as_ref(), include_benches)? {
             // TODO: catch dups
             tests.insert(
                 test_name.into(),
@@ -349,35 +787,104 @@ impl<'g> TestList<'g> {
             );
         }
 
+        let harness = test_binary.harness;
+        Ok(Self::make_suite(test_binary, harness, tests))
+    }
+
+    /// Processes the list output of a test binary, taking its harness into account.
+    fn process_listed(
+        test_binary: RustTestArtifact<'g>,
+        filter: &TestFilterBuilder,
+        outputs: Result<(impl AsRef<str>, impl AsRef<str>), ParseTestListError>,
+    ) -> Result<(Utf8PathBuf, RustTestSuite<'g>), ParseTestListError> {
+        match test_binary.harness {
+            RustTestHarness::Opaque => Ok(Self::process_opaque(test_binary, filter)),
+            RustTestHarness::Custom => {
+                // Custom harnesses might not support listing their tests. If listing fails or
+                // doesn't produce any tests, run the binary as a single opaque test instead.
+                let listed = outputs.and_then(|(non_ignored, ignored)| {
+                    Self::process_output(
+                        test_binary.clone(),
+                        filter,
+                        non_ignored.as_ref(),
+                        ignored.as_ref(),
+                    )
+                });
+                match listed {
+                    Ok((bin, info)) if !info.testcases.is_empty() => Ok((bin, info)),
+                    _ => Ok(Self::process_opaque(test_binary, filter)),
+                }
+            }
+            _ => {
+                let (non_ignored, ignored) = outputs?;
+                Self::process_output(test_binary, filter, non_ignored.as_ref(), ignored.as_ref())
+            }
+        }
+    }
+
+    /// Creates a suite containing a single test, named after the binary, that runs the whole
+    /// binary.
+    fn process_opaque(
+        test_binary: RustTestArtifact<'g>,
+        filter: &TestFilterBuilder,
+    ) -> (Utf8PathBuf, RustTestSuite<'g>) {
+        let mut filter = filter.build(&test_binary);
+        let mut tests = BTreeMap::new();
+        tests.insert(
+            test_binary.binary_name.clone(),
+            RustTestCaseSummary {
+                ignored: false,
+                filter_match: filter.filter_match(&test_binary.binary_name, false),
+            },
+        );
+        Self::make_suite(test_binary, RustTestHarness::Opaque, tests)
+    }
+
+    fn make_suite(
+        test_binary: RustTestArtifact<'g>,
+        harness: RustTestHarness,
+        testcases: BTreeMap<String, RustTestCaseSummary>,
+    ) -> (Utf8PathBuf, RustTestSuite<'g>) {
         let RustTestArtifact {
             binary_id,
             package,
             binary_path,
             binary_name,
             cwd,
+            build_platform,
+            harness: _,
+            env,
+            kind: _,
+            required_features: _,
+            profile: _,
         } = test_binary;
 
-        Ok((
+        (
             binary_path,
             RustTestSuite {
                 binary_id,
                 package,
                 binary_name,
-                testcases: tests,
+                testcases,
                 cwd,
+                build_platform,
+                harness,
+                env,
             },
-        ))
+        )
     }
 
     /// Parses the output of --list --format terse and returns a sorted list.
-    fn parse(list_output: &str) -> Result<Vec<&'_ str>, ParseTestListError> {
-        let mut list = Self::parse_impl(list_output).collect::<Result<Vec<_>, _>>()?;
+    fn parse(list_output: &str, include_benches: bool) -> Result<Vec<&'_ str>, ParseTestListError> {
+        let mut list =
+            Self::parse_impl(list_output, include_benches).collect::<Result<Vec<_>, _>>()?;
         list.sort_unstable();
         Ok(list)
     }
 
     fn parse_impl(
         list_output: &str,
+        include_benches: bool,
     ) -> impl Iterator<Item = Result<&'_ str, ParseTestListError>> + '_ {
         // The output is in the form:
         // <test name>: test
@@ -385,10 +892,14 @@ impl<'g> TestList<'g> {
         // ...
 
         list_output.lines().filter_map(move |line| {
-            if line.ends_with(": benchmark") {
+            if let Some(bench_name) = line.strip_suffix(": benchmark") {
                 // These lines are produced by the default Rust benchmark harness (#[bench]).
-                // Ignore them.
-                return None;
+                // Benchmarks are run in test mode if requested, and ignored otherwise.
+                return if include_benches {
+                    Some(Ok(bench_name))
+                } else {
+                    None
+                };
             }
 
             let res = line.strip_suffix(": test").ok_or_else(|| {
@@ -410,6 +921,9 @@ impl<'g> TestList<'g> {
             if verbose {
                 writeln!(writer, "  {} {}", "bin:".style(self.styles.field), test_bin)?;
                 writeln!(writer, "  {} {}", "cwd:".style(self.styles.field), info.cwd)?;
+                if info.build_platform == BuildPlatform::Host {
+                    writeln!(writer, "  {} host", "platform:".style(self.styles.field))?;
+                }
             }
 
             let mut indented = indent_write::io::IndentWriter::new("    ", &mut writer);
@@ -419,8 +933,12 @@ impl<'g> TestList<'g> {
             } else {
                 for (name, info) in &info.testcases {
                     write_test_name(name, &self.styles, &mut indented)?;
-                    if !info.filter_match.is_match() {
-                        write!(indented, " (skipped)")?;
+                    match info.filter_match {
+                        FilterMatch::Matches => {}
+                        FilterMatch::Mismatch { reason } if verbose => {
+                            write!(indented, " (skipped: {})", reason)?;
+                        }
+                        FilterMatch::Mismatch { .. } => write!(indented, " (skipped)")?,
                     }
                     writeln!(indented)?;
                 }
@@ -432,9 +950,31 @@ impl<'g> TestList<'g> {
 
 impl<'g> RustTestArtifact<'g> {
     /// Run this binary with and without --ignored and get the corresponding outputs.
-    fn exec(&self, runner: Option<&TargetRunner>) -> Result<(String, String), ParseTestListError> {
-        let non_ignored = self.exec_single(false, runner)?;
-        let ignored = self.exec_single(true, runner)?;
+    ///
+    /// If the outputs are in the cache, the binary isn't run.
+    fn exec(
+        &self,
+        runner: Option<&TargetRunner>,
+        remote: Option<&SshRunner>,
+        cache: Option<&ListCache>,
+    ) -> Result<(String, String), ParseTestListError> {
+        // Custom harnesses may generate their tests at runtime, so only libtest output is cached.
+        let cache = cache.filter(|_| self.harness == RustTestHarness::Libtest);
+        if let Some(outputs) = cache.and_then(|cache| cache.get(&self.binary_path)) {
+            return Ok(outputs);
+        }
+
+        // Binaries built for the host are run on this machine, without the target runner.
+        let (runner, remote) = if self.build_platform == BuildPlatform::Host {
+            (None, None)
+        } else {
+            (runner, remote)
+        };
+        let non_ignored = self.exec_single(false, runner, remote)?;
+        let ignored = self.exec_single(true, runner, remote)?;
+        if let Some(cache) = cache {
+            cache.insert(&self.binary_path, &non_ignored, &ignored);
+        }
         Ok((non_ignored, ignored))
     }
 
@@ -442,25 +982,51 @@ impl<'g> RustTestArtifact<'g> {
         &self,
         ignored: bool,
         runner: Option<&TargetRunner>,
+        remote: Option<&SshRunner>,
     ) -> Result<String, ParseTestListError> {
-        let mut argv = Vec::new();
+        let mut list_args = vec!["--list", "--format", "terse"];
+        if ignored {
+            list_args.push("--ignored");
+        }
 
-        let program: std::ffi::OsString = if let Some(runner) = runner {
-            argv.extend(runner.args());
-            argv.push(self.binary_path.as_str());
-            runner.binary().into()
-        } else {
-            use duct::IntoExecutablePath;
-            self.binary_path.as_std_path().to_executable()
+        let env: Vec<_> = self
+            .env
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.clone()))
+            .collect();
+        let cmd = match remote {
+            Some(remote) => {
+                let remote_cwd = remote.remote_cwd(&self.cwd);
+                remote.command(&self.binary_path, None, list_args, &remote_cwd, &env)
+            }
+            None => {
+                let mut argv = Vec::new();
+
+                let program: OsString = if let Some(runner) = runner {
+                    argv.extend(runner.args());
+                    argv.push(self.binary_path.as_str());
+                    runner.binary().into()
+                } else {
+                    use duct::IntoExecutablePath;
+                    self.binary_path.as_std_path().to_executable()
+                };
+
+                argv.extend(list_args);
+                let mut cmd = cmd(program, argv).dir(&self.cwd);
+                for (key, value) in env {
+                    cmd = cmd.env(key, value);
+                }
+                cmd
+            }
         };
 
-        argv.extend(["--list", "--format", "terse"]);
-        if ignored {
-            argv.push("--ignored");
+        let mut cmd = cmd.stdout_capture();
+        if self.harness == RustTestHarness::Custom {
+            // Custom harnesses might not support listing tests, in which case they're run as a
+            // single test. Any errors they print out aren't relevant.
+            cmd = cmd.stderr_null();
         }
 
-        let cmd = cmd(program, argv).dir(&self.cwd).stdout_capture();
-
         cmd.read().map_err(|error| {
             ParseTestListError::command(
                 format!(
@@ -507,84 +1073,283 @@ impl<'a> TestInstance<'a> {
     }
 
     /// Creates the command expression for this test instance.
-    pub(crate) fn make_expression(&self, target_runner: Option<&TargetRunner>) -> Expression {
+    ///
+    /// `extra_args` are passed to the test binary after the arguments that select the test, unless
+    /// the binary is opaque. `extra_env` is set in addition to the environment variables Cargo sets
+    /// for tests and those set for the binary, and takes precedence over both. If `remote` is
+    /// specified, the test is run on the remote host and `target_runner` is ignored. Neither is
+    /// used for binaries built for the host platform, which are always run directly on this
+    /// machine.
+    ///
+    /// Local tests are started through `launcher` if it's non-empty, e.g. to
+    /// [double-spawn](crate::double_spawn) them. It runs the wrapper, if any.
+    pub(crate) fn make_expression(
+        &self,
+        target_runner: Option<&TargetRunner>,
+        wrapper: Option<&WrapperCommand>,
+        remote: Option<&SshRunner>,
+        launcher: &[OsString],
+        extra_args: &[&'a str],
+        extra_env: &[(String, String)],
+    ) -> Expression {
         // TODO: non-rust tests
 
-        let mut args = Vec::new();
-
-        let program: std::ffi::OsString = match target_runner {
-            Some(tr) => {
-                args.extend(tr.args());
-                args.push(self.binary.as_str());
-                tr.binary().into()
-            }
-            None => {
-                use duct::IntoExecutablePath;
-                self.binary.as_std_path().to_executable()
+        let mut test_args = Vec::new();
+        // Opaque binaries are run as a whole, without any arguments.
+        if self.bin_info.harness != RustTestHarness::Opaque {
+            test_args.extend(["--exact", self.name, "--nocapture"]);
+            if self.test_info.ignored {
+                test_args.push("--ignored");
             }
-        };
+            test_args.extend(extra_args);
+        }
+        self.make_expression_with_args(
+            target_runner,
+            wrapper,
+            remote,
+            launcher,
+            test_args,
+            extra_env,
+        )
+    }
 
-        args.extend(["--exact", self.name, "--nocapture"]);
+    /// Creates the command expression for running the tests with the given names from this test
+    /// instance's binary, including this test, in a single process.
+    ///
+    /// Output isn't passed through with `--nocapture`, so that libtest captures the output of each
+    /// test separately. The binary must use the libtest harness, and the tests must all be run with
+    /// or without `--ignored` like this test. Otherwise, this is the same as
+    /// [`make_expression`](Self::make_expression).
+    #[allow(clippy::too_many_arguments)]
+    pub(crate) fn make_batch_expression(
+        &self,
+        test_names: &[&'a str],
+        target_runner: Option<&TargetRunner>,
+        wrapper: Option<&WrapperCommand>,
+        remote: Option<&SshRunner>,
+        launcher: &[OsString],
+        extra_args: &[&'a str],
+        extra_env: &[(String, String)],
+    ) -> Expression {
+        debug_assert_eq!(self.bin_info.harness, RustTestHarness::Libtest);
+        let mut test_args = vec!["--exact"];
+        test_args.extend(test_names);
         if self.test_info.ignored {
-            args.push("--ignored");
+            test_args.push("--ignored");
         }
+        test_args.extend(extra_args);
+        self.make_expression_with_args(
+            target_runner,
+            wrapper,
+            remote,
+            launcher,
+            test_args,
+            extra_env,
+        )
+    }
+
+    fn make_expression_with_args(
+        &self,
+        target_runner: Option<&TargetRunner>,
+        wrapper: Option<&WrapperCommand>,
+        remote: Option<&SshRunner>,
+        launcher: &[OsString],
+        test_args: Vec<&'a str>,
+        extra_env: &[(String, String)],
+    ) -> Expression {
+        let (target_runner, remote) = if self.bin_info.build_platform == BuildPlatform::Host {
+            (None, None)
+        } else {
+            (target_runner, remote)
+        };
 
         let package = self.bin_info.package;
+        // The cwd is the directory containing Cargo.toml. Use it rather than the manifest path
+        // from the package graph, since it may have been remapped when reusing a build.
+        let cwd = match remote {
+            Some(remote) => remote.remote_cwd(&self.bin_info.cwd),
+            None => self.bin_info.cwd.to_string(),
+        };
 
-        let cmd = cmd(program, args)
-            .dir(&self.bin_info.cwd)
+        let mut env = vec![
             // This environment variable is set to indicate that tests are being run under nextest.
-            .env("NEXTEST", "1")
+            ("NEXTEST", "1".to_owned()),
             // These environment variables are set at runtime by cargo test:
             // https://doc.rust-lang.org/cargo/reference/environment-variables.html#environment-variables-cargo-sets-for-crates
-            .env(
-                "CARGO_MANIFEST_DIR",
-                package.manifest_path().parent().unwrap(),
-            )
-            .env("CARGO_PKG_VERSION", format!("{}", package.version()))
-            .env(
+            ("CARGO_MANIFEST_DIR", cwd.clone()),
+            ("CARGO_PKG_VERSION", format!("{}", package.version())),
+            (
                 "CARGO_PKG_VERSION_MAJOR",
                 format!("{}", package.version().major),
-            )
-            .env(
+            ),
+            (
                 "CARGO_PKG_VERSION_MINOR",
                 format!("{}", package.version().minor),
-            )
-            .env(
+            ),
+            (
                 "CARGO_PKG_VERSION_PATCH",
                 format!("{}", package.version().patch),
-            )
-            .env(
+            ),
+            (
                 "CARGO_PKG_VERSION_PRE",
                 format!("{}", package.version().pre),
-            )
-            .env("CARGO_PKG_AUTHORS", package.authors().join(":"))
-            .env("CARGO_PKG_NAME", package.name())
-            .env(
+            ),
+            ("CARGO_PKG_AUTHORS", package.authors().join(":")),
+            ("CARGO_PKG_NAME", package.name().to_owned()),
+            (
                 "CARGO_PKG_DESCRIPTION",
-                package.description().unwrap_or_default(),
-            )
-            .env("CARGO_PKG_HOMEPAGE", package.homepage().unwrap_or_default())
-            .env("CARGO_PKG_LICENSE", package.license().unwrap_or_default())
-            .env(
+                package.description().unwrap_or_default().to_owned(),
+            ),
+            (
+                "CARGO_PKG_HOMEPAGE",
+                package.homepage().unwrap_or_default().to_owned(),
+            ),
+            (
+                "CARGO_PKG_LICENSE",
+                package.license().unwrap_or_default().to_owned(),
+            ),
+            (
                 "CARGO_PKG_LICENSE_FILE",
-                package.license_file().unwrap_or_else(|| "".as_ref()),
-            )
-            .env(
+                package
+                    .license_file()
+                    .map_or_else(String::new, |path| path.to_string()),
+            ),
+            (
                 "CARGO_PKG_REPOSITORY",
-                package.repository().unwrap_or_default(),
-            );
+                package.repository().unwrap_or_default().to_owned(),
+            ),
+        ];
+        env.extend(
+            self.bin_info
+                .env
+                .iter()
+                .chain(extra_env.iter().map(|(key, value)| (key, value)))
+                .map(|(key, value)| (key.as_str(), value.clone())),
+        );
+
+        if let Some(remote) = remote {
+            return remote.command(self.binary, wrapper, test_args, &cwd, &env);
+        }
+
+        // The launcher, if any, runs the wrapper, if any, which runs the target runner, if any, which
+        // runs the test binary.
+        let mut command: Vec<OsString> = launcher.to_vec();
+        if let Some(wrapper) = wrapper {
+            command.push(wrapper.program().into());
+            command.extend(wrapper.args().map(Into::into));
+        }
+        match target_runner {
+            Some(tr) => {
+                command.push(tr.binary().into());
+                command.extend(tr.args().map(Into::into));
+                command.push(self.binary.as_str().into());
+            }
+            None => {
+                use duct::IntoExecutablePath;
+                command.push(self.binary.as_std_path().to_executable());
+            }
+        }
+        command.extend(test_args.into_iter().map(Into::into));
+        let (program, args) = command.split_first().expect("command is non-empty");
 
+        let mut cmd = cmd(program, args).dir(&self.bin_info.cwd);
+        for (key, value) in env {
+            cmd = cmd.env(key, value);
+        }
         cmd
     }
 }
 
+/// Determines the harness for a build target by looking it up in its package's `Cargo.toml`.
+///
+/// Manifests are cached in `manifests`, keyed by path.
+fn detect_harness(
+    manifests: &mut HashMap<Utf8PathBuf, CargoManifest>,
+    manifest_path: &Utf8Path,
+    target: &cargo_metadata::Target,
+) -> Result<RustTestHarness, FromMessagesError> {
+    let manifest = match manifests.get(manifest_path) {
+        Some(manifest) => manifest,
+        None => {
+            let contents = std::fs::read_to_string(manifest_path).map_err(|error| {
+                FromMessagesError::ReadManifest {
+                    path: manifest_path.to_path_buf(),
+                    error,
+                }
+            })?;
+            let manifest =
+                toml::from_str(&contents).map_err(|error| FromMessagesError::ParseManifest {
+                    path: manifest_path.to_path_buf(),
+                    error,
+                })?;
+            manifests
+                .entry(manifest_path.to_path_buf())
+                .or_insert(manifest)
+        }
+    };
+
+    if manifest.harness(&target.kind, &target.name) {
+        Ok(RustTestHarness::Libtest)
+    } else {
+        Ok(RustTestHarness::Custom)
+    }
+}
+
+/// The subset of `Cargo.toml` required to determine build target harnesses.
+#[derive(Debug, Default, Deserialize)]
+struct CargoManifest {
+    lib: Option<ManifestTarget>,
+    #[serde(default)]
+    bin: Vec<ManifestTarget>,
+    #[serde(default)]
+    test: Vec<ManifestTarget>,
+    #[serde(default)]
+    bench: Vec<ManifestTarget>,
+    #[serde(default)]
+    example: Vec<ManifestTarget>,
+}
+
+impl CargoManifest {
+    /// Returns the value of `harness` for this target. Targets not defined in the manifest (e.g.
+    /// automatically discovered ones) use the default harness.
+    fn harness(&self, kind: &[String], name: &str) -> bool {
+        let has_kind = |expected: &str| kind.iter().any(|k| k == expected);
+        let targets = if has_kind("test") {
+            &self.test
+        } else if has_kind("bench") {
+            &self.bench
+        } else if has_kind("bin") {
+            &self.bin
+        } else if has_kind("example") {
+            &self.example
+        } else {
+            // Everything else (lib, proc-macro etc) is a library target.
+            return self.lib.as_ref().map_or(true, |lib| lib.harness);
+        };
+        targets
+            .iter()
+            .find(|t| t.name.as_deref() == Some(name))
+            .map_or(true, |t| t.harness)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct ManifestTarget {
+    name: Option<String>,
+    #[serde(default = "default_harness")]
+    harness: bool,
+}
+
+fn default_harness() -> bool {
+    true
+}
+
 #[derive(Clone, Debug, Default)]
 pub(super) struct Styles {
     pub(super) binary_id: Style,
     pub(super) test_name: Style,
     pub(super) module_path: Style,
-    field: Style,
+    pub(super) field: Style,
 }
 
 impl Styles {
@@ -600,11 +1365,8 @@ impl Styles {
 mod tests {
     use super::*;
     use crate::test_filter::RunIgnored;
-    use guppy::CargoMetadata;
     use indoc::indoc;
     use maplit::btreemap;
-    use nextest_metadata::{FilterMatch, MismatchReason};
-    use once_cell::sync::Lazy;
     use pretty_assertions::assert_eq;
     use std::iter;
 
@@ -632,6 +1394,12 @@ mod tests {
             package: package_metadata(),
             binary_name: fake_binary_name.clone(),
             binary_id: fake_binary_id.clone(),
+            build_platform: BuildPlatform::Target,
+            harness: RustTestHarness::Libtest,
+            env: BTreeMap::new(),
+            kind: RustTestBinaryKind::Lib,
+            required_features: vec![],
+            profile: None,
         };
         let test_list = TestList::new_with_outputs(
             iter::once((test_binary, &non_ignored_output, &ignored_output)),
@@ -664,6 +1432,9 @@ mod tests {
                     package: package_metadata(),
                     binary_name: fake_binary_name,
                     binary_id: fake_binary_id,
+                    build_platform: BuildPlatform::Target,
+                    harness: RustTestHarness::Libtest,
+                    env: BTreeMap::new(),
                 }
             }
         );
@@ -680,13 +1451,14 @@ mod tests {
             fake-package::fake-binary:
               bin: /fake/binary
               cwd: /fake/cwd
-                tests::baz::test_ignored (skipped)
+                tests::baz::test_ignored (skipped: does not match the run-ignored option)
                 tests::baz::test_quux
                 tests::foo::test_bar
-                tests::ignored::test_bar (skipped)
+                tests::ignored::test_bar (skipped: does not match the run-ignored option)
         "};
         static EXPECTED_JSON_PRETTY: &str = indoc! {r#"
             {
+              "format-version": 1,
               "test-count": 4,
               "rust-suites": {
                 "fake-package::fake-binary": {
@@ -695,6 +1467,8 @@ mod tests {
                   "package-id": "metadata-helper 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-helper)",
                   "binary-path": "/fake/binary",
                   "cwd": "/fake/cwd",
+                  "build-platform": "target",
+                  "harness": "libtest",
                   "testcases": {
                     "tests::baz::test_ignored": {
                       "ignored": true,
@@ -753,18 +1527,529 @@ mod tests {
         );
     }
 
-    static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
-        static FIXTURE_JSON: &str = include_str!("../../fixtures/cargo-metadata.json");
+    #[test]
+    fn test_parse_custom_harness() {
+        let test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        let make_binary = |binary_name: &str, harness| RustTestArtifact {
+            binary_path: format!("/fake/{}", binary_name).into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: binary_name.to_owned(),
+            binary_id: format!("fake-package::{}", binary_name),
+            build_platform: BuildPlatform::Target,
+            harness,
+            env: BTreeMap::new(),
+            kind: RustTestBinaryKind::Lib,
+            required_features: vec![],
+            profile: None,
+        };
+
+        let test_list = TestList::new_with_outputs(
+            vec![
+                // A custom harness that supports listing.
+                (
+                    make_binary("listed", RustTestHarness::Custom),
+                    "my_test: test\n",
+                    "",
+                ),
+                // A custom harness with output that can't be parsed.
+                (
+                    make_binary("unparseable", RustTestHarness::Custom),
+                    "running 1 test\n",
+                    "",
+                ),
+                // Opaque binaries aren't parsed at all.
+                (
+                    make_binary("opaque", RustTestHarness::Opaque),
+                    "my_test: test\n",
+                    "",
+                ),
+            ],
+            &test_filter,
+        )
+        .expect("valid output");
+
+        let suites: Vec<_> = test_list
+            .iter()
+            .map(|(_, suite)| {
+                (
+                    suite.binary_name.as_str(),
+                    suite.harness,
+                    suite
+                        .testcases
+                        .keys()
+                        .map(|s| s.as_str())
+                        .collect::<Vec<_>>(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            suites,
+            vec![
+                ("listed", RustTestHarness::Custom, vec!["my_test"]),
+                ("opaque", RustTestHarness::Opaque, vec!["opaque"]),
+                ("unparseable", RustTestHarness::Opaque, vec!["unparseable"]),
+            ]
+        );
+
+        // A libtest binary with unparseable output is still an error.
+        let res = TestList::new_with_outputs(
+            iter::once((
+                make_binary("libtest", RustTestHarness::Libtest),
+                "running 1 test\n",
+                "",
+            )),
+            &test_filter,
+        );
+        assert!(res.is_err(), "unparseable libtest output is an error");
+    }
+
+    #[test]
+    fn test_parse_include_benches() {
+        let non_ignored_output = indoc! {"
+            tests::test_foo: test
+            benches::bench_foo: benchmark
+        "};
+        let ignored_output = indoc! {"
+            benches::bench_ignored: benchmark
+        "};
+
+        let mut test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        test_filter.set_include_benches(true);
+        let test_binary = RustTestArtifact {
+            binary_path: "/fake/binary".into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: "fake-binary".to_owned(),
+            binary_id: "fake-package::fake-binary".to_owned(),
+            build_platform: BuildPlatform::Target,
+            harness: RustTestHarness::Libtest,
+            env: BTreeMap::new(),
+            kind: RustTestBinaryKind::Lib,
+            required_features: vec![],
+            profile: None,
+        };
+        let test_list = TestList::new_with_outputs(
+            iter::once((test_binary, &non_ignored_output, &ignored_output)),
+            &test_filter,
+        )
+        .expect("valid output");
+
+        let suite = test_list.get("/fake/binary").expect("binary is present");
+        assert_eq!(
+            suite.testcases,
+            btreemap! {
+                "benches::bench_foo".to_owned() => RustTestCaseSummary {
+                    ignored: false,
+                    filter_match: FilterMatch::Matches,
+                },
+                "benches::bench_ignored".to_owned() => RustTestCaseSummary {
+                    ignored: true,
+                    filter_match: FilterMatch::Mismatch { reason: MismatchReason::Ignored },
+                },
+                "tests::test_foo".to_owned() => RustTestCaseSummary {
+                    ignored: false,
+                    filter_match: FilterMatch::Matches,
+                },
+            }
+        );
+    }
+
+    #[test]
+    fn test_binary_filters() {
+        let make_binary = |binary_name: &str, kind| RustTestArtifact {
+            binary_path: format!("/fake/{}", binary_name).into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: binary_name.to_owned(),
+            binary_id: format!("metadata-helper::{}", binary_name),
+            build_platform: BuildPlatform::Target,
+            harness: RustTestHarness::Libtest,
+            env: BTreeMap::new(),
+            kind,
+            required_features: vec![],
+            profile: None,
+        };
+        let binaries = || {
+            vec![
+                make_binary("lib", RustTestBinaryKind::Lib),
+                make_binary("integration", RustTestBinaryKind::Test),
+                make_binary("example", RustTestBinaryKind::Example),
+            ]
+            .into_iter()
+            .map(|test_binary| (test_binary, "tests::foo: test\n", ""))
+        };
+        // Binaries that are filtered out are still listed, but all their tests are skipped.
+        let binary_ids = |filter: &TestFilterBuilder| {
+            let test_list = TestList::new_with_outputs(binaries(), filter).expect("valid output");
+            assert_eq!(test_list.binary_count(), 3, "all binaries are listed");
+            test_list
+                .iter_tests()
+                .filter_map(|instance| match instance.test_info.filter_match {
+                    FilterMatch::Matches => Some(instance.bin_info.binary_id.clone()),
+                    FilterMatch::Mismatch { reason } => {
+                        assert_eq!(reason, MismatchReason::Binary);
+                        None
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+
+        let mut test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        assert_eq!(binary_ids(&test_filter).len(), 3, "no binary filters");
+
+        test_filter.set_kinds(vec![RustTestBinaryKind::Lib, RustTestBinaryKind::Test]);
+        assert_eq!(
+            binary_ids(&test_filter),
+            vec!["metadata-helper::integration", "metadata-helper::lib"]
+        );
+
+        test_filter.set_binary_ids(vec!["metadata-helper::lib", "metadata-helper::example"]);
+        assert_eq!(
+            binary_ids(&test_filter),
+            vec!["metadata-helper::lib"],
+            "binary filters are combined"
+        );
+
+        test_filter.set_packages(vec!["metadata-helper"]);
+        assert_eq!(binary_ids(&test_filter), vec!["metadata-helper::lib"]);
+        test_filter.set_packages(vec!["other-package"]);
+        assert!(binary_ids(&test_filter).is_empty());
+
+        let test_list = TestList::new_with_outputs(binaries(), &test_filter).expect("valid output");
+        let mut verbose = vec![];
+        test_list
+            .write(OutputFormat::Human { verbose: true }, &mut verbose)
+            .expect("human output written");
+        assert!(
+            String::from_utf8(verbose)
+                .expect("output is valid UTF-8")
+                .contains("tests::foo (skipped: is in a binary that was filtered out)"),
+            "verbose output shows the reason tests are skipped"
+        );
+    }
+
+    #[test]
+    fn test_binary_list() {
+        let make_binary = |binary_name: &str, build_platform, kind| RustTestArtifact {
+            binary_path: format!("/fake/{}", binary_name).into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(),
+            binary_name: binary_name.to_owned(),
+            binary_id: format!("fake-package::{}", binary_name),
+            build_platform,
+            harness: RustTestHarness::Libtest,
+            env: BTreeMap::new(),
+            kind,
+            required_features: vec![],
+            profile: None,
+        };
+        let mut tests = make_binary("tests", BuildPlatform::Target, RustTestBinaryKind::Test);
+        tests.required_features = vec!["a".to_owned(), "b".to_owned()];
+        let binary_list = BinaryList::new(vec![
+            tests,
+            make_binary("macros", BuildPlatform::Host, RustTestBinaryKind::ProcMacro),
+        ]);
+
+        let mut human = vec![];
+        binary_list
+            .write(OutputFormat::Human { verbose: true }, &mut human)
+            .expect("human output written");
+        assert_eq!(
+            String::from_utf8(human).expect("output is valid UTF-8"),
+            indoc! {"
+                fake-package::macros
+                  bin: /fake/macros
+                  cwd: /fake/cwd
+                  kind: proc-macro
+                  platform: host
+                fake-package::tests
+                  bin: /fake/tests
+                  cwd: /fake/cwd
+                  kind: test
+                  required-features: a, b
+            "}
+        );
+
+        let mut json = vec![];
+        binary_list
+            .write(
+                OutputFormat::Serializable(SerializableFormat::Json),
+                &mut json,
+            )
+            .expect("JSON output written");
+        let summary = BinaryListSummary::parse_json(String::from_utf8(json).unwrap())
+            .expect("JSON output is a valid binary list");
+        assert_eq!(summary, binary_list.to_summary());
+        assert_eq!(summary.format_version, BinaryListSummary::FORMAT_VERSION);
+        assert_eq!(
+            summary.rust_binaries["fake-package::macros"].build_platform,
+            BuildPlatform::Host
+        );
+
+        // Binary lists from older versions of nextest don't record the kind, so it's looked up in
+        // the package graph instead.
+        let mut summary = summary;
+        let mut old_summary = summary.rust_binaries["fake-package::tests"].clone();
+        old_summary.binary_name = "metadata-helper".to_owned();
+        old_summary.kind = None;
+        old_summary.required_features = vec![];
+        summary.rust_binaries = btreemap! { "metadata-helper".to_owned() => old_summary };
+        let artifacts =
+            RustTestArtifact::from_binary_list(&test_helpers::PACKAGE_GRAPH_FIXTURE, &summary)
+                .expect("binary list is valid");
+        assert_eq!(artifacts[0].kind, RustTestBinaryKind::Lib);
+        assert!(artifacts[0].required_features.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_list_several_binaries() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let dir = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        // Binaries are listed at once, so the output of each one must end up with that binary.
+        let names: Vec<_> = (0..8).map(|idx| format!("binary{}", idx)).collect();
+        let test_binaries = names.iter().map(|name| {
+            let binary_path = dir.join(name);
+            std::fs::write(
+                &binary_path,
+                format!(
+                    "#!/bin/sh\n\
+                     if [ \"$4\" = --ignored ]; then echo '{0}::ignored: test'; \
+                     else echo '{0}::test: test'; fi\n",
+                    name
+                ),
+            )
+            .expect("wrote fake binary");
+            std::fs::set_permissions(&binary_path, std::fs::Permissions::from_mode(0o755))
+                .expect("made fake binary executable");
+            RustTestArtifact {
+                binary_path,
+                cwd: dir.to_path_buf(),
+                package: package_metadata(),
+                binary_name: name.clone(),
+                binary_id: format!("fake-package::{}", name),
+                build_platform: BuildPlatform::Target,
+                harness: RustTestHarness::Libtest,
+                env: BTreeMap::new(),
+                kind: RustTestBinaryKind::Test,
+                required_features: vec![],
+                profile: None,
+            }
+        });
+
+        let test_filter = TestFilterBuilder::any(RunIgnored::Default);
+        let test_list =
+            TestList::new(test_binaries, &test_filter, None, None).expect("binaries are listed");
+        assert_eq!(test_list.binary_count(), names.len());
+        for name in &names {
+            let suite = &test_list.rust_suites[&dir.join(name)];
+            let testcases: Vec<_> = suite.testcases.keys().map(|name| name.as_str()).collect();
+            assert_eq!(
+                testcases,
+                [format!("{}::ignored", name), format!("{}::test", name)],
+                "tests are listed for the right binary"
+            );
+        }
+    }
+
+    #[test]
+    fn test_binary_kind() {
+        let kinds = |kinds: &[&str]| {
+            kinds
+                .iter()
+                .map(|&kind| kind.to_owned())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(binary_kind(&kinds(&["lib"])), RustTestBinaryKind::Lib);
+        assert_eq!(
+            binary_kind(&kinds(&["rlib", "cdylib"])),
+            RustTestBinaryKind::Lib
+        );
+        assert_eq!(
+            binary_kind(&kinds(&["proc-macro"])),
+            RustTestBinaryKind::ProcMacro
+        );
+        assert_eq!(binary_kind(&kinds(&["bin"])), RustTestBinaryKind::Bin);
+        assert_eq!(binary_kind(&kinds(&["test"])), RustTestBinaryKind::Test);
+        assert_eq!(binary_kind(&kinds(&["bench"])), RustTestBinaryKind::Bench);
+        assert_eq!(
+            binary_kind(&kinds(&["example"])),
+            RustTestBinaryKind::Example
+        );
+    }
+
+    #[test]
+    fn test_host_binaries_skip_target_runner() {
+        let config = crate::config::NextestConfig::default_config("/fake/dir");
+        let target_runner = TargetRunner::for_wasm_target("wasm32-unknown-unknown", config.wasm())
+            .expect("default runner is valid")
+            .expect("wasm32-unknown-unknown has a runner");
+        let test_info = RustTestCaseSummary {
+            ignored: false,
+            filter_match: FilterMatch::Matches,
+        };
+
+        for (build_platform, uses_runner) in
+            [(BuildPlatform::Target, true), (BuildPlatform::Host, false)]
+        {
+            let suite = RustTestSuite {
+                binary_id: "fake-package".to_owned(),
+                package: package_metadata(),
+                binary_name: "fake-package".to_owned(),
+                cwd: "/fake/cwd".into(),
+                build_platform,
+                harness: RustTestHarness::Libtest,
+                env: BTreeMap::new(),
+                testcases: BTreeMap::new(),
+            };
+            let test = TestInstance::new("tests::foo", "/fake/binary", &suite, &test_info);
+            let expression = format!(
+                "{:?}",
+                test.make_expression(Some(&target_runner), None, None, &[], &[], &[])
+            );
+            assert_eq!(
+                expression.contains("wasm-bindgen-test-runner"),
+                uses_runner,
+                "for {:?}: {}",
+                build_platform,
+                expression
+            );
+        }
+    }
+
+    #[test]
+    fn test_manifest_harness() {
+        let manifest: CargoManifest = toml::from_str(indoc! {r#"
+            [package]
+            name = "my-package"
+
+            [lib]
+            harness = false
+
+            [[test]]
+            name = "custom"
+            harness = false
+
+            [[test]]
+            name = "standard"
+
+            [[bench]]
+            name = "custom"
+            harness = false
+        "#})
+        .expect("manifest is valid");
+
+        let kind = |kind: &str| vec![kind.to_owned()];
+        assert!(!manifest.harness(&kind("lib"), "my_package"));
+        assert!(!manifest.harness(&kind("test"), "custom"));
+        assert!(manifest.harness(&kind("test"), "standard"));
+        assert!(manifest.harness(&kind("test"), "autodiscovered"));
+        assert!(!manifest.harness(&kind("bench"), "custom"));
+        assert!(manifest.harness(&kind("bin"), "custom"));
+
+        let empty: CargoManifest = toml::from_str("").expect("manifest is valid");
+        assert!(empty.harness(&kind("lib"), "my_package"));
+    }
+
+    fn package_metadata() -> PackageMetadata<'static> {
+        test_helpers::package_metadata(test_helpers::HELPER_ID)
+    }
+}
+
+/// Fixtures shared by the tests in this crate.
+#[cfg(test)]
+pub(crate) mod test_helpers {
+    use super::*;
+    use crate::{config::NextestConfig, test_filter::RunIgnored};
+    use guppy::CargoMetadata;
+    use once_cell::sync::Lazy;
+
+    /// The `cargo metadata` output for a workspace with two packages: `metadata-base`, and
+    /// `metadata-helper`, a path dependency of it outside the workspace.
+    pub(crate) static FIXTURE_JSON: &str = include_str!("../../fixtures/cargo-metadata.json");
+
+    pub(crate) static PACKAGE_GRAPH_FIXTURE: Lazy<PackageGraph> = Lazy::new(|| {
         let metadata = CargoMetadata::parse_json(FIXTURE_JSON).expect("fixture is valid JSON");
         metadata
             .build_graph()
             .expect("fixture is valid PackageGraph")
     });
 
-    static PACKAGE_METADATA_ID: &str = "metadata-helper 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-helper)";
-    fn package_metadata() -> PackageMetadata<'static> {
+    pub(crate) static HELPER_ID: &str = "metadata-helper 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-helper)";
+    pub(crate) static BASE_ID: &str =
+        "metadata-base 0.1.0 (path+file:///Users/fakeuser/local/testcrates/metadata/metadata-base)";
+
+    pub(crate) fn package_metadata(package_id: &str) -> PackageMetadata<'static> {
         PACKAGE_GRAPH_FIXTURE
-            .metadata(&PackageId::new(PACKAGE_METADATA_ID))
+            .metadata(&PackageId::new(package_id))
             .expect("package ID is valid")
     }
+
+    /// Returns a libtest binary with the given ID in the given package. The binary doesn't exist.
+    pub(crate) fn fake_artifact(package_id: &str, binary_id: &str) -> RustTestArtifact<'static> {
+        let binary_name = binary_id
+            .rsplit("::")
+            .next()
+            .expect("rsplit returns an item");
+        RustTestArtifact {
+            binary_path: format!("/fake/{}", binary_id).into(),
+            cwd: "/fake/cwd".into(),
+            package: package_metadata(package_id),
+            binary_name: binary_name.to_owned(),
+            binary_id: binary_id.to_owned(),
+            build_platform: BuildPlatform::Target,
+            harness: RustTestHarness::Libtest,
+            env: BTreeMap::new(),
+            kind: RustTestBinaryKind::Test,
+            required_features: vec![],
+            profile: None,
+        }
+    }
+
+    /// Makes a test list from the given binaries, along with their output when listing tests and
+    /// when listing ignored tests.
+    pub(crate) fn make_test_list<'a>(
+        binaries: impl IntoIterator<Item = (RustTestArtifact<'static>, &'a str, &'a str)>,
+    ) -> TestList<'static> {
+        TestList::new_with_outputs(binaries, &TestFilterBuilder::any(RunIgnored::Default))
+            .expect("valid output")
+    }
+
+    /// Makes a test list with a single binary, `fake-package::fake-binary`, from its output when
+    /// listing tests and when listing ignored tests.
+    pub(crate) fn make_single_binary_test_list(
+        non_ignored: &str,
+        ignored: &str,
+    ) -> TestList<'static> {
+        make_test_list(std::iter::once((
+            fake_artifact(HELPER_ID, "fake-package::fake-binary"),
+            non_ignored,
+            ignored,
+        )))
+    }
+
+    /// Makes a config for a workspace in a temporary directory, read from the given contents.
+    pub(crate) fn make_config(contents: &str) -> NextestConfig {
+        let dir = tempfile::tempdir().expect("created temp dir");
+        let workspace_root = Utf8Path::from_path(dir.path()).expect("temp dir is UTF-8");
+        make_config_in(workspace_root, contents)
+    }
+
+    /// Makes a config from the given contents, for a workspace that's rooted at an existing
+    /// directory.
+    pub(crate) fn make_config_in(workspace_root: &Utf8Path, contents: &str) -> NextestConfig {
+        let config_path = workspace_root.join("nextest.toml");
+        std::fs::write(&config_path, contents).expect("wrote config");
+        NextestConfig::from_sources_impl(
+            workspace_root.to_owned(),
+            Some(&config_path),
+            &[],
+            None,
+            std::iter::empty(),
+        )
+        .expect("config is valid")
+    }
 }
@@ -0,0 +1,166 @@
+// Copyright (c) The nextest Contributors
+// SPDX-License-Identifier: MIT OR Apache-2.0
+
+//! Test timings recorded by previous runs.
+//!
+//! At the end of each run, nextest records how long each test took within the profile's store
+//! directory. Subsequent runs use these timings to schedule the slowest tests first, so that a
+//! long-running test doesn't start near the end of a run and hold up its completion.
+
+use crate::{
+    errors::{TestTimingsError, WriteEventError},
+    test_list::{TestInstance, TestList},
+};
+use camino::Utf8Path;
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fs, io, time::Duration};
+
+/// Durations of tests, as recorded by previous runs.
+#[derive(Clone, Debug, Default, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TestTimings {
+    /// Map of binary ID to test name to the duration of the test in seconds.
+    binaries: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl TestTimings {
+    /// The name of the file timings are stored in, within the profile's store directory.
+    pub const FILE_NAME: &'static str = "test-timings.json";
+
+    /// Reads timings from the given store directory.
+    ///
+    /// Returns an empty set of timings if no timings have been recorded yet.
+    pub fn read(store_dir: &Utf8Path) -> Result<Self, TestTimingsError> {
+        let path = store_dir.join(Self::FILE_NAME);
+        let contents = match fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Self::default()),
+            Err(error) => return Err(TestTimingsError::Read { path, error }),
+        };
+        serde_json::from_str(&contents).map_err(|error| TestTimingsError::Parse { path, error })
+    }
+
+    /// Returns true if no timings have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.binaries.is_empty()
+    }
+
+    /// Returns the duration recorded for a test, if any.
+    pub fn get(&self, test_instance: &TestInstance<'_>) -> Option<Duration> {
+        self.duration(&test_instance.bin_info.binary_id, test_instance.name)
+    }
+
+    /// Sorts tests so that the slowest ones come first.
+    ///
+    /// Tests without a recorded duration may be new or changed, so nothing is known about how long
+    /// they take: they are put before all other tests. The sort is stable, so if no timings have
+    /// been recorded the order is unchanged.
+    pub fn sort_slowest_first(&self, tests: &mut [TestInstance<'_>]) {
+        tests.sort_by_key(|test_instance| {
+            std::cmp::Reverse(self.get(test_instance).unwrap_or(Duration::MAX))
+        });
+    }
+
+    // ---
+    // Helper methods
+    // ---
+
+    pub(crate) fn duration(&self, binary_id: &str, test_name: &str) -> Option<Duration> {
+        let secs = *self.binaries.get(binary_id)?.get(test_name)?;
+        // Guard against hand-edited files: Duration::from_secs_f64 panics on negative values.
+        if secs.is_finite() && secs >= 0.0 {
+            Some(Duration::from_secs_f64(secs))
+        } else {
+            None
+        }
+    }
+
+    /// Discards timings for tests that are no longer in the test list.
+    pub(crate) fn retain_listed(&mut self, test_list: &TestList<'_>) {
+        let mut binaries: BTreeMap<String, BTreeMap<String, f64>> = BTreeMap::new();
+        for test_instance in test_list.iter_tests() {
+            let binary_id = &test_instance.bin_info.binary_id;
+            if let Some(secs) = self
+                .binaries
+                .get(binary_id)
+                .and_then(|tests| tests.get(test_instance.name))
+            {
+                binaries
+                    .entry(binary_id.clone())
+                    .or_default()
+                    .insert(test_instance.name.to_owned(), *secs);
+            }
+        }
+        self.binaries = binaries;
+    }
+
+    pub(crate) fn record(&mut self, binary_id: &str, test_name: &str, duration: Duration) {
+        self.binaries
+            .entry(binary_id.to_owned())
+            .or_default()
+            .insert(test_name.to_owned(), duration.as_secs_f64());
+    }
+
+    pub(crate) fn write(&self, store_dir: &Utf8Path) -> Result<(), WriteEventError> {
+        fs::create_dir_all(store_dir).map_err(|error| WriteEventError::Fs {
+            file: store_dir.to_path_buf(),
+            error,
+        })?;
+        let path = store_dir.join(Self::FILE_NAME);
+        let f = fs::File::create(&path).map_err(|error| WriteEventError::Fs {
+            file: path.clone(),
+            error,
+        })?;
+        serde_json::to_writer_pretty(f, self).map_err(WriteEventError::Json)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn read_write_timings() {
+        let tempdir = tempfile::tempdir().expect("created temp dir");
+        let store_dir = Utf8Path::from_path(tempdir.path()).expect("temp dir is valid UTF-8");
+
+        // No history => no timings.
+        let timings = TestTimings::read(store_dir).expect("missing file is not an error");
+        assert!(timings.is_empty(), "no timings recorded yet");
+
+        let mut timings = TestTimings::default();
+        timings.record("my-crate", "tests::slow", Duration::from_millis(2500));
+        timings.record("my-crate", "tests::fast", Duration::from_millis(10));
+        timings.write(store_dir).expect("wrote timings");
+
+        let timings = TestTimings::read(store_dir).expect("read timings");
+        assert_eq!(
+            timings.duration("my-crate", "tests::slow"),
+            Some(Duration::from_millis(2500))
+        );
+        assert_eq!(
+            timings.duration("my-crate", "tests::fast"),
+            Some(Duration::from_millis(10))
+        );
+        assert_eq!(timings.duration("my-crate", "tests::new"), None);
+        assert_eq!(timings.duration("other-crate", "tests::slow"), None);
+
+        std::fs::write(store_dir.join(TestTimings::FILE_NAME), "not json").expect("wrote file");
+        assert!(matches!(
+            TestTimings::read(store_dir),
+            Err(TestTimingsError::Parse { .. })
+        ));
+    }
+
+    #[test]
+    fn invalid_durations_ignored() {
+        let timings: TestTimings =
+            serde_json::from_str(r#"{ "binaries": { "my-crate": { "neg": -1.0, "ok": 1.5 } } }"#)
+                .expect("valid JSON");
+        assert_eq!(timings.duration("my-crate", "neg"), None);
+        assert_eq!(
+            timings.duration("my-crate", "ok"),
+            Some(Duration::from_millis(1500))
+        );
+    }
+}
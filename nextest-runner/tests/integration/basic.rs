@@ -16,7 +16,7 @@ use pretty_assertions::assert_eq;
 fn test_list_tests() -> Result<()> {
     let test_filter = TestFilterBuilder::any(RunIgnored::Default);
     let test_bins: Vec<_> = FIXTURE_TARGETS.values().cloned().collect();
-    let test_list = TestList::new(test_bins, &test_filter, None)?;
+    let test_list = TestList::new(test_bins, &test_filter, None, None)?;
 
     for (name, expected) in &*EXPECTED_TESTS {
         let test_binary = FIXTURE_TARGETS
@@ -40,9 +40,9 @@ fn test_list_tests() -> Result<()> {
 fn test_run() -> Result<()> {
     let test_filter = TestFilterBuilder::any(RunIgnored::Default);
     let test_bins: Vec<_> = FIXTURE_TARGETS.values().cloned().collect();
-    let test_list = TestList::new(test_bins, &test_filter, None)?;
+    let test_list = TestList::new(test_bins, &test_filter, None, None)?;
     let config =
-        NextestConfig::from_sources(&workspace_root(), None).expect("loaded fixture config");
+        NextestConfig::from_sources(&workspace_root(), None, &[]).expect("loaded fixture config");
     let profile = config
         .profile(NextestConfig::DEFAULT_PROFILE)
         .expect("default config is valid");
@@ -89,9 +89,9 @@ fn test_run() -> Result<()> {
 fn test_run_ignored() -> Result<()> {
     let test_filter = TestFilterBuilder::any(RunIgnored::IgnoredOnly);
     let test_bins: Vec<_> = FIXTURE_TARGETS.values().cloned().collect();
-    let test_list = TestList::new(test_bins, &test_filter, None)?;
+    let test_list = TestList::new(test_bins, &test_filter, None, None)?;
     let config =
-        NextestConfig::from_sources(&workspace_root(), None).expect("loaded fixture config");
+        NextestConfig::from_sources(&workspace_root(), None, &[]).expect("loaded fixture config");
     let profile = config
         .profile(NextestConfig::DEFAULT_PROFILE)
         .expect("default config is valid");
@@ -138,14 +138,14 @@ fn test_run_ignored() -> Result<()> {
 fn test_retries() -> Result<()> {
     let test_filter = TestFilterBuilder::any(RunIgnored::Default);
     let test_bins: Vec<_> = FIXTURE_TARGETS.values().cloned().collect();
-    let test_list = TestList::new(test_bins, &test_filter, None)?;
+    let test_list = TestList::new(test_bins, &test_filter, None, None)?;
     let config =
-        NextestConfig::from_sources(&workspace_root(), None).expect("loaded fixture config");
+        NextestConfig::from_sources(&workspace_root(), None, &[]).expect("loaded fixture config");
     let profile = config
         .profile("with-retries")
         .expect("with-retries config is valid");
 
-    let retries = profile.retries();
+    let retries = profile.retries().count();
     assert_eq!(retries, 2, "retries set in with-retries profile");
 
     let runner = TestRunnerBuilder::default().build(&test_list, &profile, SignalHandler::noop());
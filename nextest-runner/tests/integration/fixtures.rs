@@ -8,7 +8,7 @@ use guppy::{graph::PackageGraph, MetadataCommand};
 use maplit::btreemap;
 use nextest_metadata::{FilterMatch, MismatchReason};
 use nextest_runner::{
-    reporter::TestEvent,
+    reporter::TestEventKind,
     runner::{ExecutionResult, ExecutionStatuses, RunStats, TestRunner},
     test_list::RustTestArtifact,
 };
@@ -182,28 +182,31 @@ pub(crate) fn execute_collect<'a>(
     RunStats,
 ) {
     let mut instance_statuses = HashMap::new();
-    let run_stats = runner.execute(|event| {
-        let (test_instance, status) = match event {
-            TestEvent::TestSkipped {
-                test_instance,
-                reason,
-            } => (test_instance, InstanceStatus::Skipped(reason)),
-            TestEvent::TestFinished {
-                test_instance,
-                run_statuses,
-            } => (test_instance, InstanceStatus::Finished(run_statuses)),
-            _ => return,
-        };
-
-        instance_statuses.insert(
-            (test_instance.binary, test_instance.name),
-            InstanceValue {
-                binary_id: test_instance.bin_info.binary_id.as_str(),
-                cwd: test_instance.bin_info.cwd.as_path(),
-                status,
-            },
-        );
-    });
+    let run_stats = runner
+        .execute(|event| {
+            let (test_instance, status) = match event.kind {
+                TestEventKind::TestSkipped {
+                    test_instance,
+                    reason,
+                    ..
+                } => (test_instance, InstanceStatus::Skipped(reason)),
+                TestEventKind::TestFinished {
+                    test_instance,
+                    run_statuses,
+                } => (test_instance, InstanceStatus::Finished(run_statuses)),
+                _ => return,
+            };
+
+            instance_statuses.insert(
+                (test_instance.binary, test_instance.name),
+                InstanceValue {
+                    binary_id: test_instance.bin_info.binary_id.as_str(),
+                    cwd: test_instance.bin_info.cwd.as_path(),
+                    status,
+                },
+            );
+        })
+        .expect("no reporters were added");
 
     (instance_statuses, run_stats)
 }
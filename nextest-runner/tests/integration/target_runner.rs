@@ -2,7 +2,9 @@
 // SPDX-License-Identifier: MIT OR Apache-2.0
 
 use camino::Utf8PathBuf;
-use nextest_runner::{errors::TargetRunnerError, target_runner::TargetRunner};
+use nextest_runner::{
+    config::NextestConfig, errors::TargetRunnerError, target_runner::TargetRunner,
+};
 use once_cell::sync::OnceCell;
 use std::{env, sync::Mutex};
 
@@ -190,7 +192,7 @@ mod run {
         let test_filter = TestFilterBuilder::any(RunIgnored::Default);
         let test_bins: Vec<_> = FIXTURE_TARGETS.values().cloned().collect();
 
-        let test_list = TestList::new(test_bins.clone(), &test_filter, None)?;
+        let test_list = TestList::new(test_bins.clone(), &test_filter, None, None)?;
         let bin_count = test_list.binary_count();
         let test_count = test_list.test_count();
 
@@ -204,7 +206,8 @@ mod run {
             )?
             .unwrap();
 
-            let test_list = TestList::new(test_bins.clone(), &test_filter, Some(&target_runner))?;
+            let test_list =
+                TestList::new(test_bins.clone(), &test_filter, Some(&target_runner), None)?;
 
             assert_eq!(bin_count, test_list.binary_count());
             assert_eq!(test_count, test_list.test_count());
@@ -236,10 +239,10 @@ mod run {
 
         assert_eq!(passthrough_path(), target_runner.binary());
 
-        let test_list = TestList::new(test_bins, &test_filter, Some(&target_runner))?;
+        let test_list = TestList::new(test_bins, &test_filter, Some(&target_runner), None)?;
 
-        let config =
-            NextestConfig::from_sources(&workspace_root(), None).expect("loaded fixture config");
+        let config = NextestConfig::from_sources(&workspace_root(), None, &[])
+            .expect("loaded fixture config");
         let profile = config
             .profile(NextestConfig::DEFAULT_PROFILE)
             .expect("default config is valid");
@@ -285,3 +288,32 @@ mod run {
         Ok(())
     }
 }
+
+#[test]
+fn wasm_runners() {
+    let config = NextestConfig::default_config("/fake/dir");
+
+    let runner = TargetRunner::for_wasm_target("wasm32-wasi", config.wasm())
+        .unwrap()
+        .unwrap();
+    assert_eq!("wasmtime", runner.binary());
+    assert_eq!(vec!["run", "--dir=."], runner.args().collect::<Vec<_>>());
+
+    let runner = TargetRunner::for_wasm_target("wasm32-wasip1", config.wasm())
+        .unwrap()
+        .unwrap();
+    assert_eq!("wasmtime", runner.binary());
+
+    let runner = TargetRunner::for_wasm_target("wasm32-unknown-unknown", config.wasm())
+        .unwrap()
+        .unwrap();
+    assert_eq!("wasm-bindgen-test-runner", runner.binary());
+    assert_eq!(0, runner.args().count());
+
+    assert!(
+        TargetRunner::for_wasm_target("x86_64-unknown-linux-gnu", config.wasm())
+            .unwrap()
+            .is_none(),
+        "native targets don't get a runner"
+    );
+}